@@ -1,18 +1,267 @@
+//! Fails the transaction unless its accounts look as expected, so wallets
+//! can guard, e.g., the destination of a settlement in the same transaction.
+//!
+//! The instruction data is either the 32 byte owner the first account must
+//! have, or a count byte followed by that many guards, each checking one
+//! predicate against one of the instruction's accounts. A guard is packed
+//! as the account index, the predicate tag, then 32 bytes of arguments
+//! padded with zeroes, so a list of guards is never 32 bytes long.
+
 use solana_program::{
     account_info::AccountInfo, entrypoint, entrypoint::ProgramResult, info,
     program_error::ProgramError, pubkey::Pubkey,
 };
 
+/// Length of an SPL token account, whose mint is its first 32 bytes.
+pub const TOKEN_ACCOUNT_LEN: usize = 165;
+/// Longest discriminator a guard can check.
+pub const MAX_DISCRIMINATOR_LEN: usize = 27;
+
+const GUARD_LEN: usize = 34;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Predicate {
+    /// Tag 0. The account is owned by the given program.
+    Owner(Pubkey),
+    /// Tag 1. The account is a token account of the mint. Combine with
+    /// `Owner` of the token program to rule out lookalike accounts.
+    TokenMint(Pubkey),
+    /// Tag 2. The account's data is exactly this long.
+    DataLen(u64),
+    /// Tag 3. The account's data holds `bytes` at `offset`, packed as a u32
+    /// offset, a length byte and the bytes.
+    Discriminator { offset: u32, bytes: Vec<u8> },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Guard {
+    pub account_index: u8,
+    pub predicate: Predicate,
+}
+
+impl Guard {
+    fn pack_into(&self, buf: &mut Vec<u8>) {
+        let mut args = [0u8; 32];
+        let tag = match &self.predicate {
+            Predicate::Owner(owner) => {
+                args.copy_from_slice(owner.as_ref());
+                0
+            }
+            Predicate::TokenMint(mint) => {
+                args.copy_from_slice(mint.as_ref());
+                1
+            }
+            Predicate::DataLen(len) => {
+                args[..8].copy_from_slice(&len.to_le_bytes());
+                2
+            }
+            Predicate::Discriminator { offset, bytes } => {
+                assert!(bytes.len() <= MAX_DISCRIMINATOR_LEN);
+                args[..4].copy_from_slice(&offset.to_le_bytes());
+                args[4] = bytes.len() as u8;
+                args[5..5 + bytes.len()].copy_from_slice(bytes);
+                3
+            }
+        };
+        buf.push(self.account_index);
+        buf.push(tag);
+        buf.extend_from_slice(&args);
+    }
+
+    fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() != GUARD_LEN {
+            return None;
+        }
+        let [account_index, tag]: [u8; 2] = array_of(&data[..2])?;
+        let args = &data[2..];
+        let predicate = match tag {
+            0 => Predicate::Owner(Pubkey::new(args)),
+            1 => Predicate::TokenMint(Pubkey::new(args)),
+            2 => Predicate::DataLen(u64::from_le_bytes(array_of(&args[..8])?)),
+            3 => {
+                let len = args[4] as usize;
+                if len > MAX_DISCRIMINATOR_LEN {
+                    return None;
+                }
+                Predicate::Discriminator {
+                    offset: u32::from_le_bytes(array_of(&args[..4])?),
+                    bytes: args[5..5 + len].to_vec(),
+                }
+            }
+            _ => return None,
+        };
+        Some(Guard {
+            account_index,
+            predicate,
+        })
+    }
+
+    fn check(&self, accounts: &[AccountInfo]) -> ProgramResult {
+        let account = accounts
+            .get(self.account_index as usize)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+        let data = account.try_borrow_data()?;
+        let ok = match &self.predicate {
+            Predicate::Owner(owner) => account.owner == owner,
+            Predicate::TokenMint(mint) => {
+                data.len() == TOKEN_ACCOUNT_LEN && &data[..32] == mint.as_ref()
+            }
+            Predicate::DataLen(len) => data.len() as u64 == *len,
+            Predicate::Discriminator { offset, bytes } => {
+                let start = *offset as usize;
+                data.get(start..start + bytes.len()) == Some(&bytes[..])
+            }
+        };
+        if !ok {
+            info!("Account guard failed");
+            info!(self.account_index, 0, 0, 0, 0);
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    fn error(&self) -> ProgramError {
+        // 0x100 is what the owner check has always failed with.
+        ProgramError::Custom(match self.predicate {
+            Predicate::Owner(_) => 0x100,
+            Predicate::TokenMint(_) => 0x101,
+            Predicate::DataLen(_) => 0x102,
+            Predicate::Discriminator { .. } => 0x103,
+        })
+    }
+}
+
+fn array_of<A: Default + AsMut<[u8]>>(bytes: &[u8]) -> Option<A> {
+    let mut array = A::default();
+    if array.as_mut().len() != bytes.len() {
+        return None;
+    }
+    array.as_mut().copy_from_slice(bytes);
+    Some(array)
+}
+
+/// The instruction data checking every guard.
+pub fn pack_guards(guards: &[Guard]) -> Vec<u8> {
+    assert!(guards.len() <= std::u8::MAX as usize);
+    let mut buf = Vec::with_capacity(1 + guards.len() * GUARD_LEN);
+    buf.push(guards.len() as u8);
+    for guard in guards {
+        guard.pack_into(&mut buf);
+    }
+    buf
+}
+
+pub fn unpack_guards(instruction_data: &[u8]) -> Option<Vec<Guard>> {
+    if instruction_data.len() == 32 {
+        return Some(vec![Guard {
+            account_index: 0,
+            predicate: Predicate::Owner(Pubkey::new(instruction_data)),
+        }]);
+    }
+    let (&count, rest) = instruction_data.split_first()?;
+    if rest.len() != count as usize * GUARD_LEN {
+        return None;
+    }
+    rest.chunks(GUARD_LEN).map(Guard::unpack).collect()
+}
+
 entrypoint!(entry);
 fn entry(_program_id: &Pubkey, accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
-    let account = accounts.get(0).ok_or(ProgramError::NotEnoughAccountKeys)?;
-    if instruction_data.len() != 32 {
-        return Err(ProgramError::InvalidInstructionData);
-    }
-    let expected_owner = Pubkey::new(instruction_data);
-    if expected_owner != *account.owner {
-        info!("Account owner mismatch");
-        return Err(ProgramError::Custom(0x100));
+    let guards = unpack_guards(instruction_data).ok_or(ProgramError::InvalidInstructionData)?;
+    for guard in &guards {
+        guard.check(accounts)?;
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_program::clock::Epoch;
+
+    #[test]
+    fn guards_round_trip() {
+        let guards = vec![
+            Guard {
+                account_index: 1,
+                predicate: Predicate::TokenMint(Pubkey::new(&[3; 32])),
+            },
+            Guard {
+                account_index: 0,
+                predicate: Predicate::Discriminator {
+                    offset: 5,
+                    bytes: vec![1, 2, 3],
+                },
+            },
+            Guard {
+                account_index: 2,
+                predicate: Predicate::DataLen(3228),
+            },
+        ];
+        let data = pack_guards(&guards);
+        assert_ne!(data.len(), 32);
+        assert_eq!(unpack_guards(&data), Some(guards));
+        assert_eq!(unpack_guards(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn checks_every_guard() {
+        let token_program = Pubkey::new(&[1; 32]);
+        let mint = Pubkey::new(&[2; 32]);
+        let key = Pubkey::new(&[4; 32]);
+        let mut lamports = 0;
+        let mut data = vec![0; TOKEN_ACCOUNT_LEN];
+        data[..32].copy_from_slice(mint.as_ref());
+        data[40] = 9;
+        let account = AccountInfo::new(
+            &key,
+            false,
+            true,
+            &mut lamports,
+            &mut data,
+            &token_program,
+            false,
+            Epoch::default(),
+        );
+        let accounts = [account];
+
+        let check = |predicate| {
+            let data = pack_guards(&[Guard {
+                account_index: 0,
+                predicate,
+            }]);
+            entry(&key, &accounts, &data)
+        };
+        assert_eq!(check(Predicate::Owner(token_program)), Ok(()));
+        assert_eq!(check(Predicate::TokenMint(mint)), Ok(()));
+        assert_eq!(
+            check(Predicate::TokenMint(key)),
+            Err(ProgramError::Custom(0x101))
+        );
+        assert_eq!(
+            check(Predicate::DataLen(TOKEN_ACCOUNT_LEN as u64 + 1)),
+            Err(ProgramError::Custom(0x102))
+        );
+        assert_eq!(
+            check(Predicate::Discriminator {
+                offset: 40,
+                bytes: vec![9, 0],
+            }),
+            Ok(())
+        );
+        assert_eq!(
+            check(Predicate::Discriminator {
+                offset: TOKEN_ACCOUNT_LEN as u32,
+                bytes: vec![0],
+            }),
+            Err(ProgramError::Custom(0x103))
+        );
+
+        // The original format, a bare owner.
+        assert_eq!(
+            entry(&key, &accounts, key.as_ref()),
+            Err(ProgramError::Custom(0x100))
+        );
+        assert_eq!(entry(&key, &accounts, token_program.as_ref()), Ok(()));
+    }
+}
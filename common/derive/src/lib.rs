@@ -0,0 +1,105 @@
+//! Derive macro for `serum_common::pack::Pack`, re-exported by serum-common.
+//! For documentation, see `serum_common::pack`.
+
+use proc_quote::quote;
+use syn::parse_macro_input;
+
+#[proc_macro_derive(Pack, attributes(pack))]
+pub fn derive_pack(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as syn::DeriveInput);
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    // Same as the `packable!` macro.
+    let pack_impl = quote! {
+        impl #impl_generics serum_common::pack::Pack for #name #ty_generics #where_clause {
+            fn pack(src: Self, dst: &mut [u8]) -> Result<(), serum_common::pack::ProgramError> {
+                if serum_common::pack::Pack::size(&src)? != dst.len() as u64 {
+                    return Err(serum_common::pack::ProgramError::InvalidAccountData);
+                }
+                serum_common::pack::into_bytes(&src, dst)
+            }
+
+            fn unpack_unchecked(src: &mut &[u8]) -> Result<Self, serum_common::pack::ProgramError> {
+                serum_common::pack::from_reader(src)
+            }
+
+            fn size(&self) -> Result<u64, serum_common::pack::ProgramError> {
+                serum_common::pack::bytes_size(self)
+            }
+        }
+    };
+
+    let packed_size_impl = match packed_size(&input) {
+        None => quote! {},
+        Some(size) => quote! {
+            impl #impl_generics serum_common::pack::PackedSize for #name #ty_generics #where_clause {
+                const SIZE: u64 = #size;
+            }
+        },
+    };
+
+    proc_macro::TokenStream::from(quote! {
+        #pack_impl
+        #packed_size_impl
+    })
+}
+
+// The expression for the serialized size of the type, if it's the same for
+// every value. That's the case for structs, unless marked with
+// `#[pack(variable_size)]`, and for enums without fields.
+fn packed_size(input: &syn::DeriveInput) -> Option<proc_macro2::TokenStream> {
+    if is_variable_size(&input.attrs) {
+        return None;
+    }
+    match &input.data {
+        syn::Data::Struct(data) => {
+            let sizes = data.fields.iter().map(|field| field_size(&field.ty));
+            Some(quote! { 0 #(+ #sizes)* })
+        }
+        syn::Data::Enum(data) => {
+            match data
+                .variants
+                .iter()
+                .all(|variant| variant.fields == syn::Fields::Unit)
+            {
+                // The variant index is serialized as a u32.
+                true => Some(quote! { 4 }),
+                false => None,
+            }
+        }
+        syn::Data::Union(_) => panic!("Pack can't be derived for unions"),
+    }
+}
+
+fn is_variable_size(attrs: &[syn::Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("pack"))
+        .any(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::List(list)) => list.nested.iter().any(|nested| match nested {
+                syn::NestedMeta::Meta(syn::Meta::Word(ident)) if ident == "variable_size" => true,
+                _ => panic!("expected #[pack(variable_size)]"),
+            }),
+            _ => panic!("expected #[pack(variable_size)]"),
+        })
+}
+
+// Arrays and tuples are sized here, since `PackedSize` can't be implemented
+// for arrays of every length.
+fn field_size(ty: &syn::Type) -> proc_macro2::TokenStream {
+    match ty {
+        syn::Type::Array(array) => {
+            let len = &array.len;
+            let elem = field_size(&array.elem);
+            quote! { (#len) as u64 * (#elem) }
+        }
+        syn::Type::Tuple(tuple) => {
+            let sizes = tuple.elems.iter().map(field_size);
+            quote! { 0 #(+ #sizes)* }
+        }
+        syn::Type::Paren(paren) => field_size(&paren.elem),
+        syn::Type::Group(group) => field_size(&group.elem),
+        _ => quote! { <#ty as serum_common::pack::PackedSize>::SIZE },
+    }
+}
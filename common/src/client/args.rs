@@ -0,0 +1,162 @@
+//! Command line argument types shared by the binaries, so that they all
+//! accept the same formats and report the same errors.
+
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use std::str::FromStr;
+
+/// Path to a keypair file. The file is read when the keypair is needed
+/// rather than when the arguments are parsed.
+#[derive(Clone, Debug)]
+pub struct KeypairPath(String);
+
+impl KeypairPath {
+    pub fn read(&self) -> Result<Keypair> {
+        solana_sdk::signature::read_keypair_file(&self.0)
+            .map_err(|_| anyhow!("failed to read keypair from {}", self.0))
+    }
+}
+
+impl FromStr for KeypairPath {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.is_empty() {
+            return Err(anyhow!("keypair path must not be empty"));
+        }
+        Ok(KeypairPath(s.to_string()))
+    }
+}
+
+impl std::fmt::Display for KeypairPath {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// A base58 pubkey, or the path to a keypair file whose pubkey is used.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PubkeyOrKeypair(pub Pubkey);
+
+impl PubkeyOrKeypair {
+    pub fn pubkey(&self) -> Pubkey {
+        self.0
+    }
+}
+
+impl FromStr for PubkeyOrKeypair {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if let Ok(pubkey) = Pubkey::from_str(s) {
+            return Ok(PubkeyOrKeypair(pubkey));
+        }
+        let keypair = KeypairPath::from_str(s)?
+            .read()
+            .map_err(|_| anyhow!("{} is neither a pubkey nor a keypair file", s))?;
+        Ok(PubkeyOrKeypair(keypair.pubkey()))
+    }
+}
+
+/// A token amount in whole units, e.g. `1.5`, converted to native units
+/// once the mint's decimals are known.
+#[derive(Clone, Debug, PartialEq)]
+pub struct UiAmount {
+    whole: u64,
+    // Fractional digits as written, without trailing zeros.
+    fraction: String,
+}
+
+impl UiAmount {
+    pub fn to_native(&self, decimals: u8) -> Result<u64> {
+        if self.fraction.len() > decimals as usize {
+            return Err(anyhow!(
+                "{} has more than {} decimal places",
+                self,
+                decimals
+            ));
+        }
+        let padded = format!("{:0<width$}", self.fraction, width = decimals as usize);
+        let fraction = if padded.is_empty() {
+            0
+        } else {
+            padded.parse::<u64>()?
+        };
+        10u64
+            .checked_pow(decimals as u32)
+            .and_then(|scale| self.whole.checked_mul(scale))
+            .and_then(|whole| whole.checked_add(fraction))
+            .ok_or_else(|| anyhow!("{} is too large for {} decimals", self, decimals))
+    }
+
+    /// Converts using the decimals of the given mint.
+    pub fn to_native_for_mint(&self, client: &RpcClient, mint: &Pubkey) -> Result<u64> {
+        let data = client.get_account_data(mint)?;
+        let mint_state = spl_token::state::Mint::unpack(&data)
+            .map_err(|_| anyhow!("{} is not a token mint", mint))?;
+        self.to_native(mint_state.decimals)
+    }
+}
+
+impl FromStr for UiAmount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || anyhow!("invalid amount {}, expected e.g. 10 or 1.5", s);
+        let (whole, fraction) = match s.find('.') {
+            None => (s, ""),
+            Some(i) => (&s[..i], &s[i + 1..]),
+        };
+        if whole.is_empty() && fraction.is_empty() {
+            return Err(invalid());
+        }
+        if !whole.bytes().all(|b| b.is_ascii_digit())
+            || !fraction.bytes().all(|b| b.is_ascii_digit())
+        {
+            return Err(invalid());
+        }
+        let whole = if whole.is_empty() {
+            0
+        } else {
+            whole.parse().map_err(|_| invalid())?
+        };
+        Ok(UiAmount {
+            whole,
+            fraction: fraction.trim_end_matches('0').to_string(),
+        })
+    }
+}
+
+impl std::fmt::Display for UiAmount {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        if self.fraction.is_empty() {
+            write!(f, "{}", self.whole)
+        } else {
+            write!(f, "{}.{}", self.whole, self.fraction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ui_amount_to_native() {
+        let amount = |s: &str| UiAmount::from_str(s).unwrap();
+        assert_eq!(amount("10").to_native(6).unwrap(), 10_000_000);
+        assert_eq!(amount("1.5").to_native(6).unwrap(), 1_500_000);
+        assert_eq!(amount(".25").to_native(2).unwrap(), 25);
+        assert_eq!(amount("3.10").to_native(1).unwrap(), 31);
+        assert_eq!(amount("7").to_native(0).unwrap(), 7);
+        assert!(amount("1.001").to_native(2).is_err());
+        assert!(amount("18446744073709551615").to_native(1).is_err());
+        assert!(UiAmount::from_str("").is_err());
+        assert!(UiAmount::from_str(".").is_err());
+        assert!(UiAmount::from_str("-1").is_err());
+        assert!(UiAmount::from_str("1.2.3").is_err());
+    }
+}
@@ -0,0 +1,310 @@
+//! Sends many independent instructions, e.g., hundreds of cancels or
+//! settles, packed into as few transactions as fit, a bounded number in
+//! flight at a time, and reports what became of each instruction.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+use solana_client::client_error::{ClientErrorKind, Result};
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::message::Message;
+use solana_sdk::packet::PACKET_DATA_SIZE;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::signers::Signers;
+use solana_sdk::transaction::Transaction;
+
+use crate::client::rpc::is_dry_run;
+use crate::client::session::RpcSession;
+
+/// Most accounts the runtime lets a transaction lock.
+pub const MAX_ACCOUNT_LOCKS: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct BatchConfig {
+    /// Most transactions sent but not yet confirmed at a time.
+    pub max_in_flight: usize,
+    /// Most accounts a transaction may reference, program ids included.
+    pub max_account_locks: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        BatchConfig {
+            max_in_flight: 8,
+            max_account_locks: MAX_ACCOUNT_LOCKS,
+        }
+    }
+}
+
+/// What became of one instruction of a batch.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Outcome {
+    /// Confirmed in the transaction with this signature.
+    Confirmed(Signature),
+    /// Simulated without error, in dry run mode.
+    Simulated,
+    /// The transaction failed or never confirmed. The other instructions
+    /// packed into it share the error.
+    Failed(String),
+    /// Sent in the transaction with this signature, which neither confirmed
+    /// nor could be shown to have expired, e.g., the endpoints stopped
+    /// answering. It may still land, so it isn't resent.
+    Unknown(Signature),
+}
+
+impl Outcome {
+    pub fn is_ok(&self) -> bool {
+        matches!(self, Outcome::Confirmed(_) | Outcome::Simulated)
+    }
+}
+
+/// Size in bytes of a transaction containing `instructions`, once signed.
+pub fn transaction_size(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    let message = Message::new(instructions, Some(payer));
+    let num_signatures = message.header.num_required_signatures as usize;
+    short_vec_len(num_signatures) + num_signatures * 64 + message.serialize().len()
+}
+
+/// Number of accounts a transaction containing `instructions` locks.
+pub fn account_locks(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    Message::new(instructions, Some(payer)).account_keys.len()
+}
+
+/// Splits `groups` into as few transactions as possible, in order, returning
+/// the range of groups in each. Every transaction starts with `prefix`,
+/// e.g., ComputeBudget instructions.
+///
+/// Each group is a run of instructions that must land in the same
+/// transaction, so groups are never broken up and never reordered. Fails if
+/// a group doesn't fit in a transaction on its own.
+pub fn pack(
+    groups: &[Vec<Instruction>],
+    prefix: &[Instruction],
+    payer: &Pubkey,
+    max_account_locks: usize,
+) -> Result<Vec<Range<usize>>> {
+    let fits = |instructions: &[Instruction]| {
+        transaction_size(instructions, payer) <= PACKET_DATA_SIZE
+            && account_locks(instructions, payer) <= max_account_locks
+    };
+    let mut batches = vec![];
+    let mut start = 0;
+    let mut current = prefix.to_vec();
+    for (i, group) in groups.iter().enumerate() {
+        let mut candidate = current.clone();
+        candidate.extend(group.iter().cloned());
+        if fits(&candidate) {
+            current = candidate;
+            continue;
+        }
+        let mut alone = prefix.to_vec();
+        alone.extend(group.iter().cloned());
+        if !fits(&alone) {
+            return Err(ClientErrorKind::Custom(format!(
+                "instruction group {} doesn't fit in a transaction of {} bytes and {} accounts",
+                i, PACKET_DATA_SIZE, max_account_locks
+            ))
+            .into());
+        }
+        batches.push(start..i);
+        start = i;
+        current = alone;
+    }
+    if start < groups.len() {
+        batches.push(start..groups.len());
+    }
+    Ok(batches)
+}
+
+/// Packs `instructions` into transactions, each starting with `prefix`, and
+/// sends them signed by `signers`, which must all sign every transaction.
+/// At most `config.max_in_flight` transactions are awaiting confirmation at
+/// a time. Ones that don't confirm in time are resent like in
+/// `RpcSession::send_and_confirm_with_config`, once their blockhash has
+/// expired, and reported as `Outcome::Unknown` if that can't be told.
+///
+/// Returns one outcome per instruction, in order. Only fails if an
+/// instruction doesn't fit in a transaction on its own, before anything is
+/// sent.
+pub fn send_batched<T: Signers>(
+    session: &RpcSession,
+    instructions: &[Instruction],
+    prefix: &[Instruction],
+    payer: &Pubkey,
+    signers: &T,
+    config: &BatchConfig,
+) -> Result<Vec<Outcome>> {
+    let groups: Vec<Vec<Instruction>> = instructions.iter().map(|ix| vec![ix.clone()]).collect();
+    let batches = pack(&groups, prefix, payer, config.max_account_locks)?;
+
+    let mut outcomes = vec![Outcome::Failed("not sent".to_string()); instructions.len()];
+    let mut set = |range: Range<usize>, outcome: Outcome| {
+        for o in &mut outcomes[range] {
+            *o = outcome.clone();
+        }
+    };
+    let transaction_instructions = |range: &Range<usize>| {
+        let mut txn_instructions = prefix.to_vec();
+        txn_instructions.extend_from_slice(&instructions[range.clone()]);
+        txn_instructions
+    };
+    let send_config = RpcSendTransactionConfig {
+        skip_preflight: true,
+        ..RpcSendTransactionConfig::default()
+    };
+
+    // Each batch with the number of times it was sent before.
+    let mut pending: VecDeque<(Range<usize>, usize)> =
+        batches.into_iter().map(|range| (range, 0)).collect();
+    while !pending.is_empty() {
+        let window_len = config.max_in_flight.max(1).min(pending.len());
+        let window: Vec<_> = pending.drain(..window_len).collect();
+        let blockhash = match session.recent_blockhash() {
+            Ok((blockhash, _fees)) => blockhash,
+            Err(err) => {
+                for (range, _) in window {
+                    set(range, Outcome::Failed(err.to_string()));
+                }
+                continue;
+            }
+        };
+
+        let mut sent = Vec::with_capacity(window.len());
+        for (range, attempts) in window {
+            let txn_instructions = transaction_instructions(&range);
+            if is_dry_run() {
+                let outcome = match session.simulate(&txn_instructions, payer) {
+                    Ok(simulation) => match simulation.err {
+                        None => Outcome::Simulated,
+                        Some(err) => Outcome::Failed(format!("{:?}", err)),
+                    },
+                    Err(err) => Outcome::Failed(err.to_string()),
+                };
+                set(range, outcome);
+                continue;
+            }
+            let mut txn = Transaction::new_with_payer(&txn_instructions, Some(payer));
+            if let Err(err) = txn.try_sign(signers, blockhash) {
+                set(range, Outcome::Failed(err.to_string()));
+                continue;
+            }
+            match session.retry(|client| client.send_transaction_with_config(&txn, send_config)) {
+                Ok(signature) => sent.push((range, attempts, signature)),
+                Err(err) => set(range, Outcome::Failed(err.to_string())),
+            }
+        }
+
+        // Everything in the window was sent before any is awaited, so they
+        // confirm concurrently.
+        let commitment = session.config().commitment;
+        let mut expired = false;
+        for (range, attempts, signature) in sent {
+            let confirmed = match session.confirm(&signature, commitment) {
+                Ok(None) => {
+                    // The transaction can land until its blockhash expires,
+                    // so resending any earlier could execute it twice.
+                    session.confirm_expired(&signature, &blockhash, commitment)
+                }
+                confirmed => confirmed,
+            };
+            match confirmed {
+                Ok(Some(signature)) => set(range, Outcome::Confirmed(signature)),
+                Ok(None) if attempts < session.config().max_retries => {
+                    expired = true;
+                    pending.push_back((range, attempts + 1));
+                }
+                Ok(None) => set(
+                    range,
+                    Outcome::Failed(format!(
+                        "transaction not confirmed after {} attempts",
+                        attempts + 1
+                    )),
+                ),
+                Err(err) => match err.kind() {
+                    ClientErrorKind::TransactionError(_) => {
+                        set(range, Outcome::Failed(err.to_string()))
+                    }
+                    _ => set(range, Outcome::Unknown(signature)),
+                },
+            }
+        }
+        if expired {
+            session.invalidate_blockhash();
+        }
+    }
+    Ok(outcomes)
+}
+
+// Length of the compact-u16 prefix Solana uses for array lengths.
+fn short_vec_len(len: usize) -> usize {
+    match len {
+        0..=0x7f => 1,
+        0x80..=0x3fff => 2,
+        _ => 3,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::AccountMeta;
+
+    fn instruction(account: u8, data_len: usize) -> Instruction {
+        Instruction {
+            program_id: Pubkey::new(&[255; 32]),
+            accounts: vec![AccountMeta::new(Pubkey::new(&[account; 32]), false)],
+            data: vec![0; data_len],
+        }
+    }
+
+    fn groups(instructions: Vec<Instruction>) -> Vec<Vec<Instruction>> {
+        instructions.into_iter().map(|ix| vec![ix]).collect()
+    }
+
+    #[test]
+    fn pack_within_account_locks() {
+        let payer = Pubkey::new(&[254; 32]);
+        let instructions = (0..20).map(|i| instruction(i, 0)).collect();
+        // The payer and the program leave room for 8 more accounts.
+        let batches = pack(&groups(instructions), &[], &payer, 10).unwrap();
+        assert_eq!(batches, vec![0..8, 8..16, 16..20]);
+    }
+
+    #[test]
+    fn pack_within_packet_size() {
+        let payer = Pubkey::new(&[254; 32]);
+        let instructions: Vec<_> = (0..10).map(|i| instruction(i, 400)).collect();
+        let batches = pack(
+            &groups(instructions.clone()),
+            &[],
+            &payer,
+            MAX_ACCOUNT_LOCKS,
+        )
+        .unwrap();
+        assert_eq!(batches, vec![0..2, 2..4, 4..6, 6..8, 8..10]);
+        for range in batches {
+            assert!(transaction_size(&instructions[range], &payer) <= PACKET_DATA_SIZE);
+        }
+    }
+
+    #[test]
+    fn pack_counts_prefix() {
+        let payer = Pubkey::new(&[254; 32]);
+        let instructions = (0..8).map(|i| instruction(i, 0)).collect();
+        let prefix = [instruction(100, 0)];
+        let batches = pack(&groups(instructions), &prefix, &payer, 10).unwrap();
+        assert_eq!(batches, vec![0..7, 7..8]);
+    }
+
+    #[test]
+    fn pack_rejects_oversized_group() {
+        let payer = Pubkey::new(&[254; 32]);
+        let instructions = vec![instruction(0, 0), instruction(1, PACKET_DATA_SIZE)];
+        assert!(pack(&groups(instructions), &[], &payer, MAX_ACCOUNT_LOCKS).is_err());
+        assert!(pack(&[], &[], &payer, MAX_ACCOUNT_LOCKS)
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,304 @@
+//! An RPC client over several endpoints of the same cluster. Requests go to
+//! the current endpoint and fail over to the next healthy one on transient
+//! errors. Each endpoint is rate limited on its own, and a health check
+//! takes endpoints that error or lag behind the others out of rotation.
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use solana_client::client_error::{ClientError, ClientErrorKind, Result};
+use solana_client::rpc_client::RpcClient;
+
+#[derive(Clone, Debug)]
+pub struct FailoverConfig {
+    /// Requests sent to any one endpoint per second. None for no limit.
+    pub requests_per_second: Option<u32>,
+    /// How long an endpoint that failed is tried only after the others.
+    pub unhealthy_cooldown: Duration,
+    /// Slots an endpoint can trail the most recent one by in a health check
+    /// and still be healthy.
+    pub max_slot_lag: u64,
+}
+
+impl Default for FailoverConfig {
+    fn default() -> Self {
+        FailoverConfig {
+            requests_per_second: None,
+            unhealthy_cooldown: Duration::from_secs(30),
+            max_slot_lag: 50,
+        }
+    }
+}
+
+/// An endpoint's state as of a health check.
+#[derive(Clone, Debug)]
+pub struct EndpointHealth {
+    pub url: String,
+    /// None if the endpoint failed to return its slot.
+    pub slot: Option<u64>,
+    pub healthy: bool,
+}
+
+pub struct FailoverClient {
+    endpoints: Vec<Endpoint>,
+    // Index of the endpoint requests go to first.
+    current: AtomicUsize,
+    config: FailoverConfig,
+}
+
+struct Endpoint {
+    url: String,
+    client: RpcClient,
+    // Earliest time the next request may be sent.
+    next_request: Mutex<Instant>,
+    unhealthy_until: Mutex<Option<Instant>>,
+}
+
+impl FailoverClient {
+    /// Panics if `urls` is empty.
+    pub fn new<S: AsRef<str>>(urls: &[S], config: FailoverConfig) -> Self {
+        assert!(!urls.is_empty(), "at least one RPC endpoint is required");
+        let endpoints = urls
+            .iter()
+            .map(|url| Endpoint {
+                url: url.as_ref().to_string(),
+                client: RpcClient::new(url.as_ref().to_string()),
+                next_request: Mutex::new(Instant::now()),
+                unhealthy_until: Mutex::new(None),
+            })
+            .collect();
+        FailoverClient {
+            endpoints,
+            current: AtomicUsize::new(0),
+            config,
+        }
+    }
+
+    /// The client of the current endpoint, for requests that don't go
+    /// through `call`.
+    pub fn client(&self) -> &RpcClient {
+        &self.endpoints[self.current()].client
+    }
+
+    pub fn url(&self) -> &str {
+        &self.endpoints[self.current()].url
+    }
+
+    pub fn urls(&self) -> Vec<&str> {
+        self.endpoints.iter().map(|e| e.url.as_str()).collect()
+    }
+
+    pub fn config(&self) -> &FailoverConfig {
+        &self.config
+    }
+
+    /// Runs `f` against the current endpoint, then against every other
+    /// endpoint in turn while the error is transient, healthy endpoints
+    /// first. The endpoint that succeeds becomes the current one. Returns
+    /// the last error if all of them fail.
+    pub fn call<T, F: FnMut(&RpcClient) -> Result<T>>(&self, mut f: F) -> Result<T> {
+        let mut last_err = None;
+        for i in self.failover_order() {
+            let endpoint = &self.endpoints[i];
+            self.throttle(endpoint);
+            match f(&endpoint.client) {
+                Ok(t) => {
+                    *endpoint.unhealthy_until.lock().unwrap() = None;
+                    self.current.store(i, Ordering::Relaxed);
+                    return Ok(t);
+                }
+                Err(err) if is_transient(&err) => {
+                    self.mark_unhealthy(endpoint);
+                    last_err = Some(err);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last_err.unwrap())
+    }
+
+    /// Fetches every endpoint's slot, marking those that fail or trail the
+    /// most recent slot by more than the configured lag unhealthy and the
+    /// rest healthy. Moves off the current endpoint if it's unhealthy.
+    pub fn check_health(&self) -> Vec<EndpointHealth> {
+        let slots: Vec<Option<u64>> = self
+            .endpoints
+            .iter()
+            .map(|endpoint| {
+                self.throttle(endpoint);
+                endpoint.client.get_slot().ok()
+            })
+            .collect();
+        let max_slot = slots.iter().flatten().max().copied().unwrap_or(0);
+        let health: Vec<EndpointHealth> = self
+            .endpoints
+            .iter()
+            .zip(slots)
+            .map(|(endpoint, slot)| {
+                let healthy = match slot {
+                    Some(slot) => max_slot - slot <= self.config.max_slot_lag,
+                    None => false,
+                };
+                if healthy {
+                    *endpoint.unhealthy_until.lock().unwrap() = None;
+                } else {
+                    self.mark_unhealthy(endpoint);
+                }
+                EndpointHealth {
+                    url: endpoint.url.clone(),
+                    slot,
+                    healthy,
+                }
+            })
+            .collect();
+        if !health[self.current()].healthy {
+            if let Some(i) = health.iter().position(|h| h.healthy) {
+                self.current.store(i, Ordering::Relaxed);
+            }
+        }
+        health
+    }
+
+    fn current(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    // Every endpoint, starting from the current one, with those marked
+    // unhealthy moved to the back.
+    fn failover_order(&self) -> Vec<usize> {
+        let now = Instant::now();
+        let current = self.current();
+        let n = self.endpoints.len();
+        let mut order: Vec<usize> = (0..n).map(|i| (current + i) % n).collect();
+        // Stable, so the rotation is kept within each group.
+        order.sort_by_key(|i| self.endpoints[*i].is_unhealthy(now));
+        order
+    }
+
+    fn mark_unhealthy(&self, endpoint: &Endpoint) {
+        *endpoint.unhealthy_until.lock().unwrap() =
+            Some(Instant::now() + self.config.unhealthy_cooldown);
+    }
+
+    // Waits for the endpoint's next request slot and reserves it.
+    fn throttle(&self, endpoint: &Endpoint) {
+        let rps = match self.config.requests_per_second {
+            None | Some(0) => return,
+            Some(rps) => rps,
+        };
+        let wait = {
+            let mut next = endpoint.next_request.lock().unwrap();
+            let now = Instant::now();
+            let start = (*next).max(now);
+            *next = start + Duration::from_secs(1) / rps;
+            start - now
+        };
+        if wait > Duration::from_millis(0) {
+            thread::sleep(wait);
+        }
+    }
+}
+
+impl Endpoint {
+    fn is_unhealthy(&self, now: Instant) -> bool {
+        match *self.unhealthy_until.lock().unwrap() {
+            Some(until) => until > now,
+            None => false,
+        }
+    }
+}
+
+/// False for errors from the transaction itself or from signing, which
+/// another attempt or endpoint won't fix.
+pub fn is_transient(err: &ClientError) -> bool {
+    match err.kind() {
+        ClientErrorKind::TransactionError(_) | ClientErrorKind::SigningError(_) => false,
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::transaction::TransactionError;
+
+    // Nothing listens on these, but none of the tests reach the network.
+    fn failover(n: usize, config: FailoverConfig) -> FailoverClient {
+        let urls: Vec<String> = (0..n)
+            .map(|i| format!("http://127.0.0.1:{}", 1 + i))
+            .collect();
+        FailoverClient::new(&urls, config)
+    }
+
+    fn transient() -> ClientError {
+        ClientErrorKind::Custom("connection reset".to_string()).into()
+    }
+
+    #[test]
+    fn fails_over_on_transient_errors() {
+        let client = failover(3, FailoverConfig::default());
+        let mut calls = 0;
+        let result = client.call(|_| {
+            calls += 1;
+            if calls < 3 {
+                Err(transient())
+            } else {
+                Ok(calls)
+            }
+        });
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(client.url(), "http://127.0.0.1:3");
+
+        // The failed endpoints are tried last until their cooldown ends.
+        assert_eq!(client.failover_order(), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn returns_last_error_when_all_fail() {
+        let client = failover(2, FailoverConfig::default());
+        let mut calls = 0;
+        let result: Result<()> = client.call(|_| {
+            calls += 1;
+            Err(transient())
+        });
+        assert!(result.is_err());
+        assert_eq!(calls, 2);
+        assert_eq!(client.url(), "http://127.0.0.1:1");
+    }
+
+    #[test]
+    fn doesnt_fail_over_transaction_errors() {
+        let client = failover(2, FailoverConfig::default());
+        let mut calls = 0;
+        let result: Result<()> = client.call(|_| {
+            calls += 1;
+            Err(TransactionError::AccountNotFound.into())
+        });
+        assert!(!is_transient(&result.unwrap_err()));
+        assert_eq!(calls, 1);
+        assert_eq!(client.failover_order(), vec![0, 1]);
+    }
+
+    #[test]
+    fn throttles_each_endpoint() {
+        let client = failover(
+            2,
+            FailoverConfig {
+                requests_per_second: Some(20),
+                ..FailoverConfig::default()
+            },
+        );
+        let start = Instant::now();
+        for _ in 0..3 {
+            client.call(|_| Ok(())).unwrap();
+        }
+        assert!(start.elapsed() >= Duration::from_millis(100));
+
+        // The other endpoint has its own budget.
+        let start = Instant::now();
+        client.throttle(&client.endpoints[1]);
+        assert!(start.elapsed() < Duration::from_millis(50));
+    }
+}
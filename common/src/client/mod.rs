@@ -1,7 +1,13 @@
 use anyhow::Result;
 use std::str::FromStr;
 
+pub mod args;
+pub mod batch;
+pub mod failover;
 pub mod rpc;
+pub mod session;
+pub mod simulation;
+pub mod versioned;
 
 #[derive(Clone, Debug)]
 pub enum Cluster {
@@ -23,8 +29,9 @@ impl FromStr for Cluster {
             "d" | "devnet" => Ok(Cluster::Devnet),
             "l" | "localnet" => Ok(Cluster::Localnet),
             "g" | "debug" => Ok(Cluster::Debug),
-            _ => Err(anyhow::Error::msg(
-                "Cluster must be one of [testnet, mainnet, devnet]\n",
+            _ => Err(anyhow::anyhow!(
+                "invalid cluster {}, expected one of [testnet, mainnet, vipmainnet, devnet, localnet, debug]",
+                s
             )),
         }
     }
@@ -1,17 +1,71 @@
+use crate::client::simulation::Simulation;
+use crate::client::versioned::{
+    fetch_lookup_table, send_versioned, simulate_versioned, MessageV0, VersionedTransaction,
+};
 use crate::pack::Pack;
 use anyhow::{anyhow, Result};
+use lazy_static::lazy_static;
 use rand::rngs::OsRng;
+use solana_client::client_error::{ClientError, ClientErrorKind};
 use solana_client::rpc_client::RpcClient;
 use solana_client::rpc_config::RpcSendTransactionConfig;
 use solana_client::rpc_request::RpcRequest;
 use solana_client::rpc_response::{RpcResult, RpcSimulateTransactionResult};
 use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::{Instruction, InstructionError};
+use solana_sdk::nonce::state::{State as NonceState, Versions as NonceVersions};
 use solana_sdk::program_pack::Pack as TokenPack;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::{Keypair, Signature, Signer};
-use solana_sdk::transaction::Transaction;
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::{Transaction, TransactionError};
 use spl_token::instruction as token_instruction;
 use std::convert::Into;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::RwLock;
+use std::thread;
+use std::time::{Duration, Instant};
+
+static DRY_RUN: AtomicBool = AtomicBool::new(false);
+
+lazy_static! {
+    static ref SEND_POLICY: RwLock<SendPolicy> = RwLock::new(SendPolicy::default());
+}
+
+/// How `send_instructions` gets its blockhash and retries failures.
+#[derive(Clone, Debug)]
+pub struct SendPolicy {
+    /// Attempts after the first, each signed with a fresh blockhash.
+    pub max_retries: usize,
+    /// Wait before the first retry, doubled after each one.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Durable nonce account whose blockhash is used instead of a recent
+    /// one, so transactions don't expire. The fee payer must be its
+    /// authority.
+    pub nonce_account: Option<Pubkey>,
+    /// Address lookup tables to load accounts from. When set, transactions
+    /// are sent as version 0 rather than legacy.
+    pub lookup_tables: Vec<Pubkey>,
+}
+
+impl Default for SendPolicy {
+    fn default() -> Self {
+        SendPolicy {
+            max_retries: 0,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(8),
+            nonce_account: None,
+            lookup_tables: vec![],
+        }
+    }
+}
+
+/// Sets the policy of `send_instructions` for the rest of the process.
+pub fn set_send_policy(policy: SendPolicy) {
+    *SEND_POLICY.write().unwrap() = policy;
+}
 
 pub fn create_account_rent_exempt(
     client: &RpcClient,
@@ -94,6 +148,26 @@ pub fn create_and_init_mint(
     mint_keypair: &Keypair,
     owner_pubkey: &Pubkey,
     decimals: u8,
+) -> Result<Signature> {
+    create_and_init_mint_with_freeze_authority(
+        client,
+        payer_keypair,
+        mint_keypair,
+        owner_pubkey,
+        None,
+        decimals,
+    )
+}
+
+/// Same as `create_and_init_mint`, with an authority able to freeze the
+/// mint's token accounts.
+pub fn create_and_init_mint_with_freeze_authority(
+    client: &RpcClient,
+    payer_keypair: &Keypair,
+    mint_keypair: &Keypair,
+    owner_pubkey: &Pubkey,
+    freeze_authority: Option<&Pubkey>,
+    decimals: u8,
 ) -> Result<Signature> {
     let signers = vec![payer_keypair, mint_keypair];
 
@@ -110,7 +184,7 @@ pub fn create_and_init_mint(
         &spl_token::ID,
         &mint_keypair.pubkey(),
         owner_pubkey,
-        None,
+        freeze_authority,
         decimals,
     )?;
     let instructions = vec![create_mint_account_instruction, initialize_mint_instruction];
@@ -177,7 +251,21 @@ pub fn mint_to_new_account(
     Ok(recip_keypair)
 }
 
+/// Makes `send_txn` simulate transactions and print the results, rather
+/// than send them, for the rest of the process.
+pub fn set_dry_run(dry_run: bool) {
+    DRY_RUN.store(dry_run, Ordering::Relaxed);
+}
+
+pub fn is_dry_run() -> bool {
+    DRY_RUN.load(Ordering::Relaxed)
+}
+
 pub fn send_txn(client: &RpcClient, txn: &Transaction, _simulate: bool) -> Result<Signature> {
+    if is_dry_run() {
+        print_simulation(client, txn)?;
+        return Ok(txn.signatures[0]);
+    }
     Ok(client.send_and_confirm_transaction_with_spinner_and_config(
         txn,
         CommitmentConfig::single(),
@@ -188,6 +276,160 @@ pub fn send_txn(client: &RpcClient, txn: &Transaction, _simulate: bool) -> Resul
     )?)
 }
 
+/// Signs `instructions` with `signers`, which must include `payer`, and
+/// sends them following the process' `SendPolicy`. A failed attempt is only
+/// retried once its transaction can no longer land: it failed, its blockhash
+/// expired, or, with a durable nonce, the nonce is unchanged so the two
+/// attempts can't both land. Waiting out a blockhash takes up to two minutes.
+pub fn send_instructions(
+    client: &RpcClient,
+    instructions: &[Instruction],
+    payer: &Pubkey,
+    signers: &[&Keypair],
+) -> Result<Signature> {
+    let policy = SEND_POLICY.read().unwrap().clone();
+    let tables = policy
+        .lookup_tables
+        .iter()
+        .map(|key| fetch_lookup_table(client, key))
+        .collect::<Result<Vec<_>>>()?;
+    let mut backoff = policy.initial_backoff;
+    let mut retries = 0;
+    loop {
+        let (instructions, blockhash) = match policy.nonce_account {
+            None => (instructions.to_vec(), client.get_recent_blockhash()?.0),
+            Some(nonce_account) => {
+                let mut with_advance = vec![system_instruction::advance_nonce_account(
+                    &nonce_account,
+                    payer,
+                )];
+                with_advance.extend_from_slice(instructions);
+                (with_advance, nonce_blockhash(client, &nonce_account)?)
+            }
+        };
+        let (signature, result) = if tables.is_empty() {
+            let txn =
+                Transaction::new_signed_with_payer(&instructions, Some(payer), signers, blockhash);
+            (txn.signatures[0], send_txn(client, &txn, false))
+        } else {
+            let message = MessageV0::compile(payer, &instructions, &tables, blockhash)?;
+            let txn = VersionedTransaction::new(message, signers)?;
+            (txn.signatures[0], send_versioned_txn(client, &txn))
+        };
+        let err = match result {
+            Ok(signature) => return Ok(signature),
+            Err(err) => err,
+        };
+        // A confirmation timeout doesn't mean the transaction failed, and it
+        // can land until its blockhash expires, so resending any earlier
+        // could execute it twice.
+        let status = match client.get_signature_status(&signature)? {
+            None => match policy.nonce_account {
+                None => confirm_expired(client, &signature, &blockhash)?,
+                // The resend uses the same nonce, so only one of the two can
+                // land, unless this one already advanced it.
+                Some(nonce_account) if nonce_blockhash(client, &nonce_account)? == blockhash => {
+                    None
+                }
+                Some(_) => Some(client.get_signature_status(&signature)?.ok_or_else(|| {
+                    anyhow!("nonce advanced, but transaction {} wasn't found", signature)
+                })?),
+            },
+            status => status,
+        };
+        if let Some(Ok(())) = status {
+            return Ok(signature);
+        }
+        if retries == policy.max_retries {
+            return Err(err);
+        }
+        eprintln!(
+            "Transaction failed, retrying in {:?} ({}/{}): {}",
+            backoff,
+            retries + 1,
+            policy.max_retries,
+            err
+        );
+        thread::sleep(backoff);
+        backoff = (backoff * 2).min(policy.max_backoff);
+        retries += 1;
+    }
+}
+
+// How long `confirm_expired` waits for a blockhash to expire. One stays
+// valid for about two minutes.
+const BLOCKHASH_EXPIRY_TIMEOUT: Duration = Duration::from_secs(180);
+
+// Waits for `blockhash` to expire, like `RpcSession::confirm_expired`, then
+// returns the status of `signature`, None if its transaction never landed.
+fn confirm_expired(
+    client: &RpcClient,
+    signature: &Signature,
+    blockhash: &Hash,
+) -> Result<Option<std::result::Result<(), TransactionError>>> {
+    let start = Instant::now();
+    while client
+        .get_fee_calculator_for_blockhash(blockhash)?
+        .is_some()
+    {
+        if start.elapsed() >= BLOCKHASH_EXPIRY_TIMEOUT {
+            return Err(anyhow!(
+                "blockhash {} still valid after {:?}",
+                blockhash,
+                BLOCKHASH_EXPIRY_TIMEOUT
+            ));
+        }
+        thread::sleep(Duration::from_millis(500));
+    }
+    Ok(client.get_signature_status(signature)?)
+}
+
+// Like `send_txn`, for version 0 transactions.
+fn send_versioned_txn(client: &RpcClient, txn: &VersionedTransaction) -> Result<Signature> {
+    if !is_dry_run() {
+        return send_versioned(client, txn);
+    }
+    let result = simulate_versioned(client, txn, CommitmentConfig::single())?;
+    print_simulation_result(&txn.signatures[0], result);
+    Ok(txn.signatures[0])
+}
+
+fn nonce_blockhash(client: &RpcClient, nonce_account: &Pubkey) -> Result<Hash> {
+    let account = client.get_account(nonce_account)?;
+    let versions: NonceVersions = bincode::deserialize(&account.data)?;
+    match versions.convert_to_current() {
+        NonceState::Initialized(data) => Ok(data.blockhash),
+        NonceState::Uninitialized => Err(anyhow!(
+            "nonce account {} is not initialized",
+            nonce_account
+        )),
+    }
+}
+
+/// Simulates `txn`, printing its logs and the error it would fail with.
+pub fn print_simulation(client: &RpcClient, txn: &Transaction) -> Result<()> {
+    let result = simulate_transaction(client, txn, true, CommitmentConfig::single())?.value;
+    print_simulation_result(&txn.signatures[0], result);
+    Ok(())
+}
+
+fn print_simulation_result(signature: &Signature, result: RpcSimulateTransactionResult) {
+    println!("Simulated transaction {}", signature);
+    for log in result.logs.iter().flatten() {
+        println!("  {}", log);
+    }
+    match &result.err {
+        Some(err) => println!("  Error: {:?}", err),
+        None => println!("  Success"),
+    }
+    for phase in Simulation::from(result).compute_phases() {
+        println!(
+            "  Compute: instruction {} {}: {} units",
+            phase.instruction, phase.label, phase.units
+        );
+    }
+}
+
 pub fn simulate_transaction(
     client: &RpcClient,
     transaction: &Transaction,
@@ -228,3 +470,15 @@ pub fn account_token_unpacked<T: TokenPack>(client: &RpcClient, addr: &Pubkey) -
 pub fn account_unpacked<T: Pack>(client: &RpcClient, addr: &Pubkey) -> T {
     get_account(client, addr).unwrap()
 }
+
+/// The custom error code a program failed the transaction with, if any.
+/// Programs map these to their own error enums.
+pub fn custom_error_code(err: &ClientError) -> Option<u32> {
+    match err.kind() {
+        ClientErrorKind::TransactionError(TransactionError::InstructionError(
+            _,
+            InstructionError::Custom(code),
+        )) => Some(*code),
+        _ => None,
+    }
+}
@@ -0,0 +1,304 @@
+//! An RPC client shared by many transactions. It caches the recent
+//! blockhash, retries transient RPC errors with jittered backoff, and polls
+//! for confirmation instead of relying on a single RPC call. Given several
+//! endpoints, each attempt fails over between them.
+
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use rand::Rng;
+use solana_client::client_error::{ClientErrorKind, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcSendTransactionConfig;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::fee_calculator::FeeCalculator;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_sdk::signers::Signers;
+use solana_sdk::transaction::Transaction;
+
+use crate::client::failover::{is_transient, EndpointHealth, FailoverClient, FailoverConfig};
+use crate::client::rpc::simulate_transaction;
+use crate::client::simulation::Simulation;
+
+#[derive(Clone, Debug)]
+pub struct SessionConfig {
+    /// Attempts after the first for a failed RPC, or for a transaction that
+    /// expired before confirming.
+    pub max_retries: usize,
+    /// Wait before the first retry, doubled after each one. Each wait is
+    /// jittered down by up to half.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// How long a fetched blockhash is reused. It stays valid for about a
+    /// minute, so this leaves most of that for the transaction to land.
+    pub blockhash_ttl: Duration,
+    /// How long to wait for a transaction before treating it as expired.
+    pub confirm_timeout: Duration,
+    pub poll_interval: Duration,
+    pub commitment: CommitmentConfig,
+    /// Rate limits and health of the endpoints.
+    pub failover: FailoverConfig,
+}
+
+impl Default for SessionConfig {
+    fn default() -> Self {
+        SessionConfig {
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(250),
+            max_backoff: Duration::from_secs(4),
+            blockhash_ttl: Duration::from_secs(10),
+            confirm_timeout: Duration::from_secs(60),
+            poll_interval: Duration::from_millis(500),
+            commitment: CommitmentConfig::single(),
+            failover: FailoverConfig::default(),
+        }
+    }
+}
+
+pub struct RpcSession {
+    client: FailoverClient,
+    config: SessionConfig,
+    blockhash: Mutex<Option<(Hash, FeeCalculator, Instant)>>,
+}
+
+impl RpcSession {
+    pub fn new(url: &str) -> Self {
+        Self::with_config(url, SessionConfig::default())
+    }
+
+    pub fn with_config(url: &str, config: SessionConfig) -> Self {
+        Self::with_endpoints(&[url], config)
+    }
+
+    /// A session over several endpoints of the same cluster, preferring
+    /// them in the given order. Panics if `urls` is empty.
+    pub fn with_endpoints<S: AsRef<str>>(urls: &[S], config: SessionConfig) -> Self {
+        RpcSession {
+            client: FailoverClient::new(urls, config.failover.clone()),
+            config,
+            blockhash: Mutex::new(None),
+        }
+    }
+
+    /// The client of the endpoint currently in use. Requests sent with it
+    /// directly don't fail over; see `retry`.
+    pub fn client(&self) -> &RpcClient {
+        self.client.client()
+    }
+
+    pub fn endpoints(&self) -> &FailoverClient {
+        &self.client
+    }
+
+    pub fn config(&self) -> &SessionConfig {
+        &self.config
+    }
+
+    /// The cached blockhash and its fees, fetched again once older than the
+    /// configured TTL.
+    pub fn recent_blockhash(&self) -> Result<(Hash, FeeCalculator)> {
+        if let Some((hash, fees, fetched)) = &*self.blockhash.lock().unwrap() {
+            if fetched.elapsed() < self.config.blockhash_ttl {
+                return Ok((*hash, fees.clone()));
+            }
+        }
+        let (hash, fees) = self.retry(|client| client.get_recent_blockhash())?;
+        *self.blockhash.lock().unwrap() = Some((hash, fees.clone(), Instant::now()));
+        Ok((hash, fees))
+    }
+
+    /// Drops the cached blockhash, e.g., after a transaction using it
+    /// expired.
+    pub fn invalidate_blockhash(&self) {
+        *self.blockhash.lock().unwrap() = None;
+    }
+
+    /// Runs `f`, retrying transient errors. Each attempt fails over across
+    /// the endpoints before backing off. Errors from the transaction itself
+    /// or from signing are returned right away.
+    pub fn retry<T, F: FnMut(&RpcClient) -> Result<T>>(&self, mut f: F) -> Result<T> {
+        let mut backoff = self.config.initial_backoff;
+        let mut retries = 0;
+        loop {
+            let err = match self.client.call(&mut f) {
+                Ok(t) => return Ok(t),
+                Err(err) => err,
+            };
+            if !is_transient(&err) || retries == self.config.max_retries {
+                return Err(err);
+            }
+            thread::sleep(jitter(backoff));
+            backoff = (backoff * 2).min(self.config.max_backoff);
+            retries += 1;
+        }
+    }
+
+    /// Polls the status of `signature` until it is confirmed or fails, or
+    /// returns None if it doesn't land within the confirmation timeout.
+    pub fn confirm(
+        &self,
+        signature: &Signature,
+        commitment: CommitmentConfig,
+    ) -> Result<Option<Signature>> {
+        let start = Instant::now();
+        loop {
+            // Errors polling are as likely to be transient as anything else,
+            // so keep polling until the timeout.
+            if let Ok(Some(status)) = self
+                .client
+                .call(|client| client.get_signature_status_with_commitment(signature, commitment))
+            {
+                return status.map(|()| Some(*signature)).map_err(Into::into);
+            }
+            if start.elapsed() >= self.config.confirm_timeout {
+                return Ok(None);
+            }
+            thread::sleep(self.config.poll_interval);
+        }
+    }
+
+    /// Waits for `blockhash` to expire, then checks `signature` once more.
+    /// Returns the signature if its transaction landed after all, or None if
+    /// it never will, so it's safe to sign again and resend. Fails if the
+    /// blockhash is still valid a confirmation timeout later, or the
+    /// endpoints stop answering.
+    pub fn confirm_expired(
+        &self,
+        signature: &Signature,
+        blockhash: &Hash,
+        commitment: CommitmentConfig,
+    ) -> Result<Option<Signature>> {
+        let start = Instant::now();
+        while self
+            .retry(|client| {
+                client.get_fee_calculator_for_blockhash_with_commitment(blockhash, commitment)
+            })?
+            .value
+            .is_some()
+        {
+            if start.elapsed() >= self.config.confirm_timeout {
+                return Err(ClientErrorKind::Custom(format!(
+                    "blockhash {} still valid after {:?}",
+                    blockhash, self.config.confirm_timeout
+                ))
+                .into());
+            }
+            thread::sleep(self.config.poll_interval);
+        }
+        match self
+            .retry(|client| client.get_signature_status_with_commitment(signature, commitment))?
+        {
+            Some(status) => status.map(|()| Some(*signature)).map_err(Into::into),
+            None => Ok(None),
+        }
+    }
+
+    /// Signs `instructions` with the cached blockhash, sends them without
+    /// preflight and waits for confirmation at the session's commitment.
+    pub fn send_and_confirm<T: Signers>(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &T,
+    ) -> Result<Signature> {
+        self.send_and_confirm_with_config(
+            instructions,
+            payer,
+            signers,
+            self.config.commitment,
+            RpcSendTransactionConfig {
+                skip_preflight: true,
+                ..RpcSendTransactionConfig::default()
+            },
+        )
+    }
+
+    /// Like `send_and_confirm`. A transaction that doesn't confirm in time
+    /// is signed again with a new blockhash and resent, up to the configured
+    /// number of retries, but only once its blockhash has expired, so the
+    /// two can't both land.
+    pub fn send_and_confirm_with_config<T: Signers>(
+        &self,
+        instructions: &[Instruction],
+        payer: &Pubkey,
+        signers: &T,
+        commitment: CommitmentConfig,
+        config: RpcSendTransactionConfig,
+    ) -> Result<Signature> {
+        for _ in 0..=self.config.max_retries {
+            let (blockhash, _fees) = self.recent_blockhash()?;
+            let mut txn = Transaction::new_with_payer(instructions, Some(payer));
+            txn.try_sign(signers, blockhash)?;
+            let signature =
+                self.retry(|client| client.send_transaction_with_config(&txn, config))?;
+            if let Some(signature) = self.confirm(&signature, commitment)? {
+                return Ok(signature);
+            }
+            self.invalidate_blockhash();
+            if let Some(signature) = self.confirm_expired(&signature, &blockhash, commitment)? {
+                return Ok(signature);
+            }
+        }
+        Err(ClientErrorKind::Custom(format!(
+            "transaction not confirmed after {} attempts",
+            self.config.max_retries + 1
+        ))
+        .into())
+    }
+
+    /// Simulates `instructions` with `payer` paying the fees. The
+    /// transaction isn't signed, so it can be checked before asking anyone
+    /// to sign it.
+    pub fn simulate(&self, instructions: &[Instruction], payer: &Pubkey) -> Result<Simulation> {
+        let (blockhash, _fees) = self.recent_blockhash()?;
+        let mut txn = Transaction::new_with_payer(instructions, Some(payer));
+        txn.message.recent_blockhash = blockhash;
+        let result =
+            self.retry(|client| simulate_transaction(client, &txn, false, self.config.commitment))?;
+        Ok(result.value.into())
+    }
+}
+
+/// Checks the health of `session`'s endpoints every `interval` on a
+/// background thread, until the session is dropped. Only worth it with
+/// more than one endpoint.
+pub fn spawn_health_checks(
+    session: &Arc<RpcSession>,
+    interval: Duration,
+    mut on_check: impl FnMut(&[EndpointHealth]) + Send + 'static,
+) -> thread::JoinHandle<()> {
+    let session = Arc::downgrade(session);
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        match session.upgrade() {
+            Some(session) => on_check(&session.endpoints().check_health()),
+            None => return,
+        }
+    })
+}
+
+// A random duration between half of `backoff` and all of it, so clients
+// that failed together don't retry together.
+fn jitter(backoff: Duration) -> Duration {
+    let ms = backoff.as_millis() as u64;
+    Duration::from_millis(rand::thread_rng().gen_range(ms / 2, ms + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jitter_within_half_of_backoff() {
+        let backoff = Duration::from_millis(400);
+        for _ in 0..100 {
+            let wait = jitter(backoff);
+            assert!(wait >= backoff / 2 && wait <= backoff);
+        }
+        assert_eq!(jitter(Duration::from_millis(0)), Duration::from_millis(0));
+    }
+}
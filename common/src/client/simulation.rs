@@ -0,0 +1,350 @@
+//! Results of simulating a transaction, with the program logs parsed into
+//! events.
+
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use crate::compute;
+use solana_client::rpc_response::RpcSimulateTransactionResult;
+use solana_sdk::instruction::InstructionError;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::transaction::TransactionError;
+
+/// A line of the program logs.
+#[derive(Clone, Debug, PartialEq)]
+pub enum LogEvent {
+    /// `program_id` was invoked, at `depth` 1 by the transaction itself and
+    /// deeper by cross program invocation.
+    Invoke {
+        program_id: Pubkey,
+        depth: usize,
+    },
+    /// A message logged by the running program.
+    Log(String),
+    /// Compute units used by an invocation, including the programs it
+    /// invoked, out of its budget.
+    Consumed {
+        program_id: Pubkey,
+        units: u64,
+        budget: u64,
+    },
+    /// Compute units left to the running instruction, logged by a
+    /// `compute_checkpoint!`.
+    Remaining {
+        units: u64,
+    },
+    Success {
+        program_id: Pubkey,
+    },
+    Failed {
+        program_id: Pubkey,
+        reason: String,
+    },
+    /// Anything else, e.g., logs truncated by the runtime.
+    Other(String),
+}
+
+impl LogEvent {
+    pub fn parse(line: &str) -> LogEvent {
+        let other = || LogEvent::Other(line.to_string());
+        if let Some(msg) = line.strip_prefix("Program log: ") {
+            return LogEvent::Log(msg.to_string());
+        }
+        let rest = match line.strip_prefix("Program ") {
+            Some(rest) => rest,
+            None => return other(),
+        };
+        if let Some(remaining) = rest.strip_prefix("consumption: ") {
+            return match remaining.strip_suffix(" units remaining").map(str::parse) {
+                Some(Ok(units)) => LogEvent::Remaining { units },
+                _ => other(),
+            };
+        }
+        let (program_id, rest) = match rest.find(' ') {
+            Some(i) => (&rest[..i], &rest[i + 1..]),
+            None => return other(),
+        };
+        let program_id = match Pubkey::from_str(program_id) {
+            Ok(program_id) => program_id,
+            Err(_) => return other(),
+        };
+        if rest == "success" {
+            return LogEvent::Success { program_id };
+        }
+        if let Some(reason) = rest.strip_prefix("failed: ") {
+            return LogEvent::Failed {
+                program_id,
+                reason: reason.to_string(),
+            };
+        }
+        if let Some(depth) = rest.strip_prefix("invoke [") {
+            return match depth.trim_end_matches(']').parse() {
+                Ok(depth) => LogEvent::Invoke { program_id, depth },
+                Err(_) => other(),
+            };
+        }
+        if let Some(consumed) = rest.strip_prefix("consumed ") {
+            let words: Vec<&str> = consumed.split(' ').collect();
+            return match words.as_slice() {
+                [units, "of", budget, "compute", "units"] => {
+                    match (units.parse(), budget.parse()) {
+                        (Ok(units), Ok(budget)) => LogEvent::Consumed {
+                            program_id,
+                            units,
+                            budget,
+                        },
+                        _ => other(),
+                    }
+                }
+                _ => other(),
+            };
+        }
+        other()
+    }
+}
+
+/// Compute units an instruction used between two of its checkpoints.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ComputePhase {
+    /// Index of the instruction in the transaction.
+    pub instruction: usize,
+    /// Label of the checkpoint ending the phase.
+    pub label: String,
+    /// Units used since the previous checkpoint, or since the instruction
+    /// started for its first checkpoint.
+    pub units: u64,
+}
+
+/// The outcome of a simulated transaction.
+#[derive(Clone, Debug)]
+pub struct Simulation {
+    /// The error the transaction would fail with, if any.
+    pub err: Option<TransactionError>,
+    pub logs: Vec<LogEvent>,
+}
+
+impl Simulation {
+    /// The custom error code a program would fail the transaction with.
+    pub fn error_code(&self) -> Option<u32> {
+        match &self.err {
+            Some(TransactionError::InstructionError(_, InstructionError::Custom(code))) => {
+                Some(*code)
+            }
+            _ => None,
+        }
+    }
+
+    /// The program error the transaction would fail with, decoded into the
+    /// program's error code enum.
+    pub fn program_error<E: TryFrom<u32>>(&self) -> Option<E> {
+        self.error_code().and_then(|code| E::try_from(code).ok())
+    }
+
+    /// Compute units used by the transaction's instructions, or None if the
+    /// runtime didn't log them.
+    pub fn units_consumed(&self) -> Option<u64> {
+        // Nested invocations are counted by the instruction invoking them,
+        // so only count the outermost ones.
+        let mut depth = 0;
+        let mut total = None;
+        for event in &self.logs {
+            match event {
+                LogEvent::Invoke { depth: d, .. } => depth = *d,
+                LogEvent::Consumed { units, .. } if depth == 1 => {
+                    total = Some(total.unwrap_or(0) + units)
+                }
+                LogEvent::Success { .. } | LogEvent::Failed { .. } => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+        }
+        total
+    }
+
+    /// The compute used between the checkpoints logged by programs built
+    /// with their `compute-units` feature. An instruction's phases are only
+    /// known once the runtime logs its budget, so instructions with
+    /// truncated logs are left out.
+    pub fn compute_phases(&self) -> Vec<ComputePhase> {
+        let mut phases = vec![];
+        let mut instruction = None;
+        let mut depth = 0;
+        let mut label = None;
+        // Units remaining at each checkpoint of the running instruction.
+        let mut checkpoints: Vec<(String, u64)> = vec![];
+        for event in &self.logs {
+            // A label only applies to the line right after it.
+            let pending = label.take();
+            match event {
+                LogEvent::Invoke { depth: d, .. } => {
+                    depth = *d;
+                    if depth == 1 {
+                        instruction = Some(instruction.map_or(0, |i| i + 1));
+                        checkpoints.clear();
+                    }
+                }
+                LogEvent::Log(msg) => {
+                    label = msg.strip_prefix(compute::LOG_PREFIX).map(str::to_string)
+                }
+                LogEvent::Remaining { units } => {
+                    if let Some(label) = pending {
+                        checkpoints.push((label, *units));
+                    }
+                }
+                LogEvent::Consumed { budget, .. } if depth == 1 => {
+                    let mut previous = *budget;
+                    for (label, remaining) in checkpoints.drain(..) {
+                        phases.push(ComputePhase {
+                            instruction: instruction.unwrap_or(0),
+                            label,
+                            units: previous.saturating_sub(remaining),
+                        });
+                        previous = remaining;
+                    }
+                }
+                LogEvent::Success { .. } | LogEvent::Failed { .. } => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
+            }
+        }
+        phases
+    }
+
+    /// The messages logged by programs, in order.
+    pub fn messages(&self) -> impl Iterator<Item = &str> {
+        self.logs.iter().filter_map(|event| match event {
+            LogEvent::Log(msg) => Some(msg.as_str()),
+            _ => None,
+        })
+    }
+}
+
+impl From<RpcSimulateTransactionResult> for Simulation {
+    fn from(result: RpcSimulateTransactionResult) -> Self {
+        Simulation {
+            err: result.err,
+            logs: result
+                .logs
+                .unwrap_or_default()
+                .iter()
+                .map(|line| LogEvent::parse(line))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_logs() {
+        let program_id = Pubkey::new_rand();
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            "Program log: Instruction: Deposit".to_string(),
+            format!("Program {} invoke [2]", program_id),
+            format!(
+                "Program {} consumed 1200 of 198000 compute units",
+                program_id
+            ),
+            format!("Program {} success", program_id),
+            format!(
+                "Program {} consumed 5000 of 200000 compute units",
+                program_id
+            ),
+            format!("Program {} failed: custom program error: 0x3", program_id),
+            "Log truncated".to_string(),
+        ];
+        let simulation = Simulation::from(RpcSimulateTransactionResult {
+            err: Some(TransactionError::InstructionError(
+                0,
+                InstructionError::Custom(3),
+            )),
+            logs: Some(logs),
+        });
+
+        assert_eq!(
+            simulation.logs[0],
+            LogEvent::Invoke {
+                program_id,
+                depth: 1
+            }
+        );
+        assert_eq!(
+            simulation.logs[6],
+            LogEvent::Failed {
+                program_id,
+                reason: "custom program error: 0x3".to_string()
+            }
+        );
+        assert_eq!(
+            simulation.logs[7],
+            LogEvent::Other("Log truncated".to_string())
+        );
+        assert_eq!(simulation.units_consumed(), Some(5000));
+        assert_eq!(
+            simulation.messages().collect::<Vec<_>>(),
+            vec!["Instruction: Deposit"]
+        );
+        assert_eq!(simulation.error_code(), Some(3));
+    }
+
+    #[test]
+    fn compute_phases() {
+        let program_id = Pubkey::new_rand();
+        let token_program_id = Pubkey::new_rand();
+        let logs = vec![
+            format!("Program {} invoke [1]", program_id),
+            "Program log: compute: access-control".to_string(),
+            "Program consumption: 197000 units remaining".to_string(),
+            format!("Program {} invoke [2]", token_program_id),
+            format!(
+                "Program {} consumed 2000 of 196500 compute units",
+                token_program_id
+            ),
+            format!("Program {} success", token_program_id),
+            "Program log: compute: state-transition".to_string(),
+            "Program consumption: 194000 units remaining".to_string(),
+            format!(
+                "Program {} consumed 6500 of 200000 compute units",
+                program_id
+            ),
+            format!("Program {} success", program_id),
+            format!("Program {} invoke [1]", program_id),
+            // Not right after a label.
+            "Program log: compute: unpack".to_string(),
+            "Program log: Instruction: Stake".to_string(),
+            "Program consumption: 199000 units remaining".to_string(),
+            "Program log: compute: access-control".to_string(),
+            "Program consumption: 198500 units remaining".to_string(),
+            format!(
+                "Program {} consumed 1500 of 200000 compute units",
+                program_id
+            ),
+            format!("Program {} success", program_id),
+        ];
+        let simulation = Simulation::from(RpcSimulateTransactionResult {
+            err: None,
+            logs: Some(logs),
+        });
+
+        assert_eq!(simulation.logs[2], LogEvent::Remaining { units: 197000 });
+        let phase = |instruction, label: &str, units| ComputePhase {
+            instruction,
+            label: label.to_string(),
+            units,
+        };
+        assert_eq!(
+            simulation.compute_phases(),
+            vec![
+                phase(0, "access-control", 3000),
+                phase(0, "state-transition", 3000),
+                phase(1, "access-control", 1500),
+            ]
+        );
+        assert_eq!(simulation.units_consumed(), Some(8000));
+    }
+}
@@ -0,0 +1,388 @@
+//! Version 0 transactions, which can load accounts from address lookup
+//! tables to fit more accounts than a legacy transaction. The SDK this crate
+//! builds against only knows legacy transactions, so the format is encoded
+//! here.
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::str::FromStr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{anyhow, Result};
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_request::RpcRequest;
+use solana_client::rpc_response::{Response, RpcSimulateTransactionResult};
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::hash::Hash;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signature, Signer};
+
+// Marks a serialized message as versioned, with the version in the low bits.
+const MESSAGE_VERSION_PREFIX: u8 = 0x80;
+// Lookup table accounts start with this much metadata before the addresses.
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+// How long a blockhash stays valid, which bounds the wait for confirmation.
+const CONFIRMATION_TIMEOUT: Duration = Duration::from_secs(90);
+
+pub fn address_lookup_table_program_id() -> Pubkey {
+    Pubkey::from_str("AddressLookupTab1e1111111111111111111111111").unwrap()
+}
+
+#[derive(Clone, Debug)]
+pub struct AddressLookupTable {
+    pub key: Pubkey,
+    pub addresses: Vec<Pubkey>,
+}
+
+pub fn fetch_lookup_table(client: &RpcClient, key: &Pubkey) -> Result<AddressLookupTable> {
+    let account = client.get_account(key)?;
+    if account.owner != address_lookup_table_program_id() {
+        return Err(anyhow!("{} is not an address lookup table", key));
+    }
+    if account.data.len() < LOOKUP_TABLE_META_SIZE {
+        return Err(anyhow!("lookup table {} is too short", key));
+    }
+    let addresses = account.data[LOOKUP_TABLE_META_SIZE..]
+        .chunks_exact(32)
+        .map(Pubkey::new)
+        .collect();
+    Ok(AddressLookupTable {
+        key: *key,
+        addresses,
+    })
+}
+
+#[derive(Clone, Debug)]
+struct CompiledInstruction {
+    program_id_index: u8,
+    accounts: Vec<u8>,
+    data: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+struct LookupTableIndexes {
+    key: Pubkey,
+    writable: Vec<u8>,
+    readonly: Vec<u8>,
+}
+
+#[derive(Clone, Debug)]
+pub struct MessageV0 {
+    num_required_signatures: u8,
+    num_readonly_signed: u8,
+    num_readonly_unsigned: u8,
+    static_keys: Vec<Pubkey>,
+    recent_blockhash: Hash,
+    instructions: Vec<CompiledInstruction>,
+    lookups: Vec<LookupTableIndexes>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct KeyMeta {
+    signer: bool,
+    writable: bool,
+    program: bool,
+}
+
+impl MessageV0 {
+    /// Compiles `instructions`, loading every account that isn't a signer
+    /// or a program from the first of `tables` that holds it.
+    pub fn compile(
+        payer: &Pubkey,
+        instructions: &[Instruction],
+        tables: &[AddressLookupTable],
+        recent_blockhash: Hash,
+    ) -> Result<Self> {
+        let mut metas: BTreeMap<Pubkey, KeyMeta> = BTreeMap::new();
+        metas.insert(
+            *payer,
+            KeyMeta {
+                signer: true,
+                writable: true,
+                program: false,
+            },
+        );
+        for ix in instructions {
+            metas.entry(ix.program_id).or_default().program = true;
+            for account in &ix.accounts {
+                let meta = metas.entry(account.pubkey).or_default();
+                meta.signer |= account.is_signer;
+                meta.writable |= account.is_writable;
+            }
+        }
+
+        // Static keys are ordered writable signers (the payer first),
+        // readonly signers, writable non-signers, then readonly non-signers.
+        let mut groups: [Vec<Pubkey>; 4] = Default::default();
+        let mut lookups: Vec<LookupTableIndexes> = tables
+            .iter()
+            .map(|table| LookupTableIndexes {
+                key: table.key,
+                writable: vec![],
+                readonly: vec![],
+            })
+            .collect();
+        let mut loaded_writable = vec![];
+        let mut loaded_readonly = vec![];
+        groups[0].push(*payer);
+        for (key, meta) in &metas {
+            if key == payer {
+                continue;
+            }
+            if !meta.signer && !meta.program {
+                let found = tables.iter().enumerate().find_map(|(t, table)| {
+                    table
+                        .addresses
+                        .iter()
+                        .position(|address| address == key)
+                        .map(|i| (t, i))
+                });
+                if let Some((t, i)) = found {
+                    let i = account_index(i)?;
+                    if meta.writable {
+                        lookups[t].writable.push(i);
+                        loaded_writable.push((t, *key));
+                    } else {
+                        lookups[t].readonly.push(i);
+                        loaded_readonly.push((t, *key));
+                    }
+                    continue;
+                }
+            }
+            let group = match (meta.signer, meta.writable) {
+                (true, true) => 0,
+                (true, false) => 1,
+                (false, true) => 2,
+                (false, false) => 3,
+            };
+            groups[group].push(*key);
+        }
+        lookups.retain(|l| !l.writable.is_empty() || !l.readonly.is_empty());
+
+        // Instructions index into the static keys followed by the loaded
+        // writable and then readonly keys, each in table order.
+        loaded_writable.sort_by_key(|(t, _)| *t);
+        loaded_readonly.sort_by_key(|(t, _)| *t);
+        let static_keys: Vec<Pubkey> = groups.iter().flatten().cloned().collect();
+        let all_keys: Vec<Pubkey> = static_keys
+            .iter()
+            .cloned()
+            .chain(loaded_writable.into_iter().map(|(_, key)| key))
+            .chain(loaded_readonly.into_iter().map(|(_, key)| key))
+            .collect();
+        let index_of = |key: &Pubkey| -> Result<u8> {
+            let i = all_keys.iter().position(|k| k == key).unwrap();
+            account_index(i)
+        };
+        let compiled = instructions
+            .iter()
+            .map(|ix| {
+                Ok(CompiledInstruction {
+                    program_id_index: index_of(&ix.program_id)?,
+                    accounts: ix
+                        .accounts
+                        .iter()
+                        .map(|a| index_of(&a.pubkey))
+                        .collect::<Result<_>>()?,
+                    data: ix.data.clone(),
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(MessageV0 {
+            num_required_signatures: (groups[0].len() + groups[1].len()) as u8,
+            num_readonly_signed: groups[1].len() as u8,
+            num_readonly_unsigned: groups[3].len() as u8,
+            static_keys,
+            recent_blockhash,
+            instructions: compiled,
+            lookups,
+        })
+    }
+
+    /// The keys that must sign, in signature order.
+    pub fn signer_keys(&self) -> &[Pubkey] {
+        &self.static_keys[..self.num_required_signatures as usize]
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = vec![
+            MESSAGE_VERSION_PREFIX,
+            self.num_required_signatures,
+            self.num_readonly_signed,
+            self.num_readonly_unsigned,
+        ];
+        write_len(&mut buf, self.static_keys.len());
+        for key in &self.static_keys {
+            buf.extend_from_slice(key.as_ref());
+        }
+        buf.extend_from_slice(self.recent_blockhash.as_ref());
+        write_len(&mut buf, self.instructions.len());
+        for ix in &self.instructions {
+            buf.push(ix.program_id_index);
+            write_len(&mut buf, ix.accounts.len());
+            buf.extend_from_slice(&ix.accounts);
+            write_len(&mut buf, ix.data.len());
+            buf.extend_from_slice(&ix.data);
+        }
+        write_len(&mut buf, self.lookups.len());
+        for lookup in &self.lookups {
+            buf.extend_from_slice(lookup.key.as_ref());
+            write_len(&mut buf, lookup.writable.len());
+            buf.extend_from_slice(&lookup.writable);
+            write_len(&mut buf, lookup.readonly.len());
+            buf.extend_from_slice(&lookup.readonly);
+        }
+        buf
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct VersionedTransaction {
+    pub signatures: Vec<Signature>,
+    pub message: MessageV0,
+}
+
+impl VersionedTransaction {
+    pub fn new(message: MessageV0, signers: &[&Keypair]) -> Result<Self> {
+        let message_bytes = message.serialize();
+        let signatures = message
+            .signer_keys()
+            .iter()
+            .map(|key| {
+                signers
+                    .iter()
+                    .find(|signer| signer.pubkey() == *key)
+                    .map(|signer| signer.sign_message(&message_bytes))
+                    .ok_or_else(|| anyhow!("missing signer {}", key))
+            })
+            .collect::<Result<_>>()?;
+        Ok(VersionedTransaction {
+            signatures,
+            message,
+        })
+    }
+
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut buf = vec![];
+        write_len(&mut buf, self.signatures.len());
+        for signature in &self.signatures {
+            buf.extend_from_slice(signature.as_ref());
+        }
+        buf.extend_from_slice(&self.message.serialize());
+        buf
+    }
+}
+
+/// Sends `txn` without preflight and waits for it to be confirmed.
+pub fn send_versioned(client: &RpcClient, txn: &VersionedTransaction) -> Result<Signature> {
+    let encoded = base64::encode(txn.serialize());
+    let signature: String = client.send(
+        RpcRequest::SendTransaction,
+        serde_json::json!([encoded, {"encoding": "base64", "skipPreflight": true}]),
+    )?;
+    let signature = Signature::from_str(&signature)?;
+    let start = Instant::now();
+    while start.elapsed() < CONFIRMATION_TIMEOUT {
+        match client.get_signature_status(&signature)? {
+            Some(Ok(())) => return Ok(signature),
+            Some(Err(err)) => return Err(anyhow!("transaction {} failed: {}", signature, err)),
+            None => thread::sleep(Duration::from_millis(500)),
+        }
+    }
+    Err(anyhow!("transaction {} was not confirmed", signature))
+}
+
+pub fn simulate_versioned(
+    client: &RpcClient,
+    txn: &VersionedTransaction,
+    cfg: CommitmentConfig,
+) -> Result<RpcSimulateTransactionResult> {
+    let encoded = base64::encode(txn.serialize());
+    let response: Response<RpcSimulateTransactionResult> = client.send(
+        RpcRequest::SimulateTransaction,
+        serde_json::json!([encoded, {
+            "encoding": "base64", "sigVerify": true, "commitment": cfg.commitment
+        }]),
+    )?;
+    Ok(response.value)
+}
+
+// Writes a length as a compact-u16.
+fn write_len(buf: &mut Vec<u8>, len: usize) {
+    let mut rem = len as u16;
+    loop {
+        let mut byte = (rem & 0x7f) as u8;
+        rem >>= 7;
+        if rem == 0 {
+            buf.push(byte);
+            return;
+        }
+        byte |= 0x80;
+        buf.push(byte);
+    }
+}
+
+fn account_index(i: usize) -> Result<u8> {
+    u8::try_from(i).map_err(|_| anyhow!("too many accounts for one transaction"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::instruction::AccountMeta;
+
+    #[test]
+    fn compile_with_lookup_table() {
+        let key = |n: u8| Pubkey::new(&[n; 32]);
+        let (payer, program, signer) = (key(1), key(2), key(3));
+        let (writable, readonly, unlisted) = (key(4), key(5), key(6));
+        let table = AddressLookupTable {
+            key: key(7),
+            addresses: vec![readonly, program, writable, signer],
+        };
+        let ix = Instruction {
+            program_id: program,
+            accounts: vec![
+                AccountMeta::new_readonly(signer, true),
+                AccountMeta::new(writable, false),
+                AccountMeta::new_readonly(readonly, false),
+                AccountMeta::new(unlisted, false),
+            ],
+            data: vec![1, 2, 3],
+        };
+        let message = MessageV0::compile(&payer, &[ix], &[table], Hash::default()).unwrap();
+
+        // Signers and programs stay static even when a table holds them.
+        assert_eq!(message.static_keys, vec![payer, signer, unlisted, program]);
+        assert_eq!(message.signer_keys(), &[payer, signer]);
+        assert_eq!(message.num_readonly_signed, 1);
+        assert_eq!(message.num_readonly_unsigned, 1);
+        assert_eq!(message.lookups.len(), 1);
+        assert_eq!(message.lookups[0].writable, vec![2]);
+        assert_eq!(message.lookups[0].readonly, vec![0]);
+        // The loaded writable key comes after the static keys, then the
+        // loaded readonly key.
+        assert_eq!(message.instructions[0].program_id_index, 3);
+        assert_eq!(message.instructions[0].accounts, vec![1, 4, 5, 2]);
+
+        let bytes = message.serialize();
+        assert_eq!(&bytes[..5], &[MESSAGE_VERSION_PREFIX, 2, 1, 1, 4]);
+    }
+
+    #[test]
+    fn compact_lengths() {
+        let encode = |len| {
+            let mut buf = vec![];
+            write_len(&mut buf, len);
+            buf
+        };
+        assert_eq!(encode(0), vec![0]);
+        assert_eq!(encode(0x7f), vec![0x7f]);
+        assert_eq!(encode(0x80), vec![0x80, 0x01]);
+        assert_eq!(encode(0x3fff), vec![0xff, 0x7f]);
+        assert_eq!(encode(0x4000), vec![0x80, 0x80, 0x01]);
+    }
+}
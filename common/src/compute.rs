@@ -0,0 +1,31 @@
+//! compute.rs defines compute unit checkpoints, logged by programs built
+//! with their `compute-units` feature so that clients can tell where an
+//! instruction's compute goes.
+//!
+//! A checkpoint is logged as two lines, `compute: ` followed by its label,
+//! then the runtime's `Program consumption: <units> units remaining`. See
+//! `Simulation::compute_phases` for reading them back.
+
+/// The prefix of a checkpoint's label line.
+pub const LOG_PREFIX: &str = "compute: ";
+
+/// Logs a checkpoint labeled `$label`, naming the work done since the
+/// previous one. Compiles to nothing unless the invoking crate is built with
+/// its `compute-units` feature.
+///
+/// ```ignore
+/// compute_checkpoint!("access-control");
+/// ```
+#[macro_export]
+macro_rules! compute_checkpoint {
+    ($label:expr) => {
+        #[cfg(feature = "compute-units")]
+        $crate::compute::checkpoint($label);
+    };
+}
+
+#[cfg(feature = "program")]
+pub fn checkpoint(label: &str) {
+    solana_sdk::log::sol_log(&format!("{}{}", LOG_PREFIX, label));
+    solana_sdk::log::sol_log_compute_units();
+}
@@ -0,0 +1,140 @@
+//! event.rs defines structured events, logged by programs so that indexers
+//! can follow what happened without diffing account snapshots.
+//!
+//! An event is logged as a single line, `event: ` followed by the base64
+//! encoding of its 8 byte discriminator and its bincode serialization, the
+//! same serialization as `Pack`.
+
+use crate::pack::{from_bytes, to_bytes};
+use solana_sdk::hash::hashv;
+use solana_sdk::program_error::ProgramError;
+
+/// The prefix of every event's log line.
+pub const LOG_PREFIX: &str = "event: ";
+
+/// A type that can be logged with `emit_event!`.
+///
+/// ```ignore
+/// #[derive(serde::Serialize, serde::Deserialize)]
+/// pub struct Staked {
+///     pub member: Pubkey,
+///     pub amount: u64,
+/// }
+///
+/// impl Event for Staked {
+///     const NAME: &'static str = "Staked";
+/// }
+/// ```
+pub trait Event: serde::Serialize {
+    /// Unique among the events of a program.
+    const NAME: &'static str;
+
+    /// The first 8 bytes of the sha256 of `event:<NAME>`.
+    fn discriminator() -> [u8; 8] {
+        let hash = hashv(&[b"event:", Self::NAME.as_bytes()]);
+        let mut discriminator = [0; 8];
+        discriminator.copy_from_slice(&hash.as_ref()[..8]);
+        discriminator
+    }
+}
+
+/// Logs `$event`, which must implement `Event`. Evaluates to a
+/// `Result<(), ProgramError>`.
+///
+/// ```ignore
+/// emit_event!(Staked { member, amount })?;
+/// ```
+#[macro_export]
+macro_rules! emit_event {
+    ($event:expr) => {
+        $crate::event::emit(&$event)
+    };
+}
+
+/// The event's log line, without the prefix.
+pub fn encode<E: Event>(event: &E) -> Result<String, ProgramError> {
+    let mut data = E::discriminator().to_vec();
+    data.extend(to_bytes(event)?);
+    Ok(base64::encode(data))
+}
+
+pub fn emit<E: Event>(event: &E) -> Result<(), ProgramError> {
+    let line = format!("{}{}", LOG_PREFIX, encode(event)?);
+    solana_sdk::log::sol_log(&line);
+    Ok(())
+}
+
+/// Decodes a log line, with or without the runtime's `Program log: `
+/// prefix. None if the line isn't an `E`.
+pub fn decode<E>(log: &str) -> Option<E>
+where
+    E: Event + serde::de::DeserializeOwned,
+{
+    let log = log.trim_start_matches("Program log: ");
+    let data = base64::decode(log.strip_prefix(LOG_PREFIX)?).ok()?;
+    if data.len() < 8 || data[..8] != E::discriminator() {
+        return None;
+    }
+    from_bytes(&data[8..]).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Deposited {
+        account: Pubkey,
+        amount: u64,
+    }
+
+    impl Event for Deposited {
+        const NAME: &'static str = "Deposited";
+    }
+
+    #[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+    struct Withdrew {
+        account: Pubkey,
+        amount: u64,
+    }
+
+    impl Event for Withdrew {
+        const NAME: &'static str = "Withdrew";
+    }
+
+    #[test]
+    fn encode_decode() {
+        let event = Deposited {
+            account: Pubkey::new(&[3; 32]),
+            amount: 17,
+        };
+        let line = format!("Program log: {}{}", LOG_PREFIX, encode(&event).unwrap());
+        assert_eq!(decode::<Deposited>(&line), Some(event.clone()));
+        assert_eq!(
+            decode::<Deposited>(line.trim_start_matches("Program log: ")),
+            Some(event)
+        );
+
+        // Same layout, different discriminator.
+        assert_ne!(Deposited::discriminator(), Withdrew::discriminator());
+        assert_eq!(decode::<Withdrew>(&line), None);
+    }
+
+    #[test]
+    fn decode_other_logs() {
+        assert_eq!(decode::<Deposited>("Program log: handler: stake"), None);
+        assert_eq!(decode::<Deposited>("Program log: event: !!"), None);
+        let short = format!("{}{}", LOG_PREFIX, base64::encode([1, 2, 3]));
+        assert_eq!(decode::<Deposited>(&short), None);
+    }
+
+    #[test]
+    fn emit_macro() {
+        emit_event!(Withdrew {
+            account: Pubkey::new(&[1; 32]),
+            amount: 2,
+        })
+        .unwrap();
+    }
+}
@@ -3,4 +3,8 @@
 #[cfg(feature = "client")]
 pub mod client;
 #[macro_use]
+pub mod compute;
+#[macro_use]
+pub mod event;
+#[macro_use]
 pub mod pack;
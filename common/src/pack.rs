@@ -3,6 +3,31 @@
 // Re-export for users of the `packable` macro.
 pub use solana_sdk::program_error::ProgramError;
 
+/// Derives `Pack` for any type implementing serde's Serialize, Deserialize
+/// traits, like the `packable` macro.
+///
+/// Structs also get `PackedSize`, so each of their fields must implement it,
+/// or be an array or tuple of types that do. Enums without fields get it
+/// too. Mark structs that don't have a fixed size with
+/// `#[pack(variable_size)]`.
+///
+/// ```ignore
+/// #[derive(Default, Serialize, Deserialize, Pack)]
+/// pub struct Member {
+///     pub initialized: bool,
+///     pub entity: Pubkey,
+///     pub memo: [u8; 32],
+/// }
+///
+/// let lamports = rent.minimum_balance(Member::SIZE as usize);
+/// ```
+pub use serum_common_derive::Pack;
+
+use bytemuck::{try_from_bytes, try_from_bytes_mut, Pod};
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::pubkey::Pubkey;
+use std::cell::{Ref, RefMut};
+
 /// The Pack trait defines Account serialization for Solana programs.
 ///
 /// If possible, don't use `*_unchecked` methods.
@@ -61,8 +86,79 @@ pub trait Pack: std::marker::Sized {
     }
 }
 
+/// Zero-copy access to account data, for `Pod` types. Unlike `Pack`, which
+/// deserializes and serializes the whole account on every access, this
+/// reads and writes the account data in place.
+///
+/// The layout is the type's in-memory representation, so types should be
+/// `#[repr(C)]` without implicit padding. Account data is aligned to 8
+/// bytes on chain.
+pub trait PackZeroCopy: Pod {
+    /// Views `src` as Self. The size of `src` must equal the size of Self.
+    fn view(src: &[u8]) -> Result<&Self, ProgramError> {
+        try_from_bytes(src).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Mutable version of view.
+    fn view_mut(src: &mut [u8]) -> Result<&mut Self, ProgramError> {
+        try_from_bytes_mut(src).map_err(|_| ProgramError::InvalidAccountData)
+    }
+
+    /// Borrows the data of `account` as Self.
+    fn load<'a>(account: &'a AccountInfo) -> Result<Ref<'a, Self>, ProgramError> {
+        let data = account.try_borrow_data()?;
+        Self::view(&data)?;
+        Ok(Ref::map(data, |data| {
+            Self::view(data).unwrap_or_else(|_| unreachable!())
+        }))
+    }
+
+    /// Mutably borrows the data of `account` as Self.
+    fn load_mut<'a>(account: &'a AccountInfo) -> Result<RefMut<'a, Self>, ProgramError> {
+        let mut data = account.try_borrow_mut_data()?;
+        Self::view_mut(&mut data)?;
+        Ok(RefMut::map(data, |data| {
+            Self::view_mut(data).unwrap_or_else(|_| unreachable!())
+        }))
+    }
+}
+
+impl<T: Pod> PackZeroCopy for T {}
+
+/// The size of every serialization of a type, for types that have a fixed
+/// size, e.g., to allocate accounts with.
+pub trait PackedSize {
+    const SIZE: u64;
+}
+
+macro_rules! impl_packed_size {
+    ($($ty:ty => $size:expr),* $(,)?) => {
+        $(
+            impl PackedSize for $ty {
+                const SIZE: u64 = $size;
+            }
+        )*
+    };
+}
+
+impl_packed_size!(
+    bool => 1,
+    u8 => 1,
+    i8 => 1,
+    u16 => 2,
+    i16 => 2,
+    u32 => 4,
+    i32 => 4,
+    u64 => 8,
+    i64 => 8,
+    u128 => 16,
+    i128 => 16,
+    Pubkey => 32,
+);
+
 /// A convenience macro to easily implement `Pack` for any type that implements
-/// serde's Serialize, Deserialize traits.
+/// serde's Serialize, Deserialize traits. Prefer `#[derive(Pack)]`, which
+/// also implements `PackedSize` for types of a fixed size.
 ///
 /// When using this, one should consider the performance impact of using
 /// Serde and the associated serializer when targeting BPF. The state of this
@@ -197,4 +293,107 @@ mod tests {
         let r = VarLenStruct::unpack(&data);
         assert_eq!(r.unwrap_err(), ProgramError::InvalidAccountData);
     }
+
+    #[derive(Clone, Copy, Debug, PartialEq, serde::Serialize, serde::Deserialize, Pack)]
+    enum TestKind {
+        A,
+        B,
+    }
+
+    impl Default for TestKind {
+        fn default() -> Self {
+            TestKind::B
+        }
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize, Pack)]
+    struct DerivedStruct {
+        initialized: bool,
+        key: Pubkey,
+        amounts: [u64; 3],
+        pair: (u16, i8),
+        kind: TestKind,
+    }
+
+    #[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize, Pack)]
+    #[pack(variable_size)]
+    struct DerivedVarLenStruct {
+        a: u64,
+        v: Vec<u64>,
+    }
+
+    #[test]
+    fn derived_size() {
+        assert_eq!(DerivedStruct::SIZE, 1 + 32 + 24 + 3 + 4);
+        assert_eq!(
+            DerivedStruct::SIZE,
+            DerivedStruct::default().size().unwrap()
+        );
+        assert_eq!(TestKind::SIZE, TestKind::A.size().unwrap());
+    }
+
+    #[test]
+    fn derived_pack_unpack() {
+        let strct = DerivedStruct {
+            initialized: true,
+            key: Pubkey::new(&[7; 32]),
+            amounts: [1, 2, 3],
+            pair: (4, -5),
+            kind: TestKind::A,
+        };
+        let mut dst = vec![0; DerivedStruct::SIZE as usize];
+        DerivedStruct::pack(strct.clone(), &mut dst).unwrap();
+        assert_eq!(DerivedStruct::unpack(&dst).unwrap(), strct);
+
+        let r = DerivedStruct::pack(strct, &mut vec![0; 8]);
+        assert_eq!(r.unwrap_err(), ProgramError::InvalidAccountData);
+    }
+
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    #[repr(C)]
+    struct ZeroCopyStruct {
+        amount: u64,
+        owner: [u8; 32],
+    }
+
+    unsafe impl bytemuck::Zeroable for ZeroCopyStruct {}
+    unsafe impl Pod for ZeroCopyStruct {}
+
+    #[test]
+    fn zero_copy_view() {
+        // Backed by u64s, for alignment.
+        let mut words = [0u64; 5];
+        let data: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+        ZeroCopyStruct::view_mut(data).unwrap().amount = 7;
+        assert_eq!(ZeroCopyStruct::view(data).unwrap().amount, 7);
+        assert_eq!(&data[..8], &7u64.to_le_bytes());
+
+        let r = ZeroCopyStruct::view(&data[..32]);
+        assert_eq!(r.unwrap_err(), ProgramError::InvalidAccountData);
+    }
+
+    #[test]
+    fn zero_copy_load() {
+        let key = Pubkey::new(&[1; 32]);
+        let owner = Pubkey::new(&[2; 32]);
+        let mut lamports = 0;
+        let mut words = [0u64; 5];
+        let data: &mut [u8] = bytemuck::cast_slice_mut(&mut words);
+        let account = AccountInfo::new(&key, false, true, &mut lamports, data, &owner, false, 0);
+        ZeroCopyStruct::load_mut(&account).unwrap().owner = [3; 32];
+        assert_eq!(ZeroCopyStruct::load(&account).unwrap().owner, [3; 32]);
+
+        // The account can't be borrowed mutably while viewed.
+        let view = ZeroCopyStruct::load(&account).unwrap();
+        assert!(ZeroCopyStruct::load_mut(&account).is_err());
+        drop(view);
+    }
+
+    #[test]
+    fn derived_var_len_struct_unpack_unchecked() {
+        let mut data = [0; 100].as_ref();
+        let r = DerivedVarLenStruct::unpack_unchecked(&mut data);
+        assert_eq!(r.unwrap(), DerivedVarLenStruct::default());
+        assert_eq!(data.len(), 84);
+    }
 }
@@ -0,0 +1,48 @@
+//! golden.rs checks account serialization against checked-in fixtures, so
+//! that layout changes show up as test failures rather than after a deploy.
+//!
+//! Fixtures are hex encoded, 32 bytes per line. To regenerate them after an
+//! intentional layout change, run the tests with `SERUM_UPDATE_GOLDEN=1`
+//! and review the diff.
+
+use serum_common::pack::Pack;
+use std::path::Path;
+
+// Env variable that, when set, rewrites fixtures instead of comparing.
+pub static SERUM_UPDATE_GOLDEN: &str = "SERUM_UPDATE_GOLDEN";
+
+// Packs `src` and asserts the bytes match the fixture at `path`.
+pub fn assert_golden<T: Pack>(src: T, path: impl AsRef<Path>) {
+    let mut bytes = vec![0; src.size().unwrap() as usize];
+    T::pack(src, &mut bytes).unwrap();
+    assert_golden_bytes(&bytes, path);
+}
+
+// Asserts `bytes` match the fixture at `path`. Useful for account types
+// that aren't `Pack`, e.g., zero copy structs.
+pub fn assert_golden_bytes(bytes: &[u8], path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    if std::env::var(SERUM_UPDATE_GOLDEN).is_ok() {
+        std::fs::write(path, to_hex(bytes)).unwrap();
+        return;
+    }
+    let fixture = std::fs::read_to_string(path)
+        .unwrap_or_else(|e| panic!("failed to read fixture {}: {}", path.display(), e));
+    assert_eq!(
+        to_hex(bytes),
+        fixture,
+        "serialization of {} changed",
+        path.display()
+    );
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    let mut hex = String::with_capacity(bytes.len() * 2 + bytes.len() / 32 + 1);
+    for line in bytes.chunks(32) {
+        for b in line {
+            hex.push_str(&format!("{:02x}", b));
+        }
+        hex.push('\n');
+    }
+    hex
+}
@@ -6,6 +6,8 @@ use solana_client_gen::prelude::*;
 use solana_client_gen::solana_client::rpc_config::RpcSendTransactionConfig;
 use solana_client_gen::solana_sdk::commitment_config::CommitmentConfig;
 
+pub mod golden;
+
 // Env variables that must be exported to use this crate.
 pub static TEST_PROGRAM_ID: &str = "TEST_PROGRAM_ID";
 pub static TEST_PAYER_FILEPATH: &str = "TEST_PAYER_FILEPATH";
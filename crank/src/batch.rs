@@ -0,0 +1,29 @@
+//! Packing of dex instructions into transactions that fit the packet limit.
+//! See `serum_common::client::batch` for sending them.
+
+use anyhow::Result;
+use serum_common::client::batch::{self, MAX_ACCOUNT_LOCKS};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+
+/// Size in bytes of a transaction containing `instructions`, once signed.
+pub fn estimate_transaction_size(instructions: &[Instruction], payer: &Pubkey) -> usize {
+    batch::transaction_size(instructions, payer)
+}
+
+/// Splits `groups` into as few transactions as possible, in order.
+///
+/// Each group is a run of instructions that must land in the same
+/// transaction (e.g. a new order followed by the match that fills it), so
+/// groups are never broken up and never reordered. Fails if a single group
+/// doesn't fit in a transaction on its own.
+pub fn split_into_transactions(
+    groups: Vec<Vec<Instruction>>,
+    payer: &Pubkey,
+) -> Result<Vec<Vec<Instruction>>> {
+    let batches = batch::pack(&groups, &[], payer, MAX_ACCOUNT_LOCKS)?;
+    Ok(batches
+        .into_iter()
+        .map(|range| groups[range].iter().flatten().cloned().collect())
+        .collect())
+}
@@ -0,0 +1,8 @@
+use anyhow::Result;
+use clap::Clap;
+use crank::cli::CliOpts;
+
+fn main() -> Result<()> {
+    let opts = CliOpts::parse();
+    crank::cli::run(opts)
+}
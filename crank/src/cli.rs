@@ -0,0 +1,592 @@
+//! `serum-dex-cli`, for the everyday operations of market admins and
+//! traders: listing and inspecting markets, placing, cancelling and settling
+//! orders, sweeping fees and cranking by hand. It builds the same
+//! instructions as the crank.
+
+use std::borrow::Cow;
+use std::convert::identity;
+use std::mem::size_of;
+use std::num::NonZeroU64;
+
+use anyhow::{format_err, Result};
+use bytemuck::{bytes_of, cast_slice};
+use clap::Clap;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::instruction::{AccountMeta, Instruction};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signer;
+
+use serum_common::client::args::KeypairPath;
+use serum_common::client::rpc::send_instructions;
+use serum_common::client::Cluster;
+use serum_dex::client::{read_market, MarketFilter};
+use serum_dex::instruction::{
+    CancelOrderInstructionV2, MarketInstruction, NewOrderInstructionV1, SelfTradeBehavior,
+    SetFeeDestinationsInstruction,
+};
+use serum_dex::matching::{OrderType, Side};
+use serum_dex::state::{find_market_listing_address, FeeDestinations, MarketStats, OpenOrders};
+
+use crate::compute_budget::ComputeBudget;
+use crate::markets::{list_markets, MarketListingSummary, MarketStatsSummary, MarketSummary};
+use crate::{
+    create_dex_account, get_keys_for_market, new_order_v3_instruction, read_pod,
+    remove_dex_account_padding, snapshot, ListMarketArgs, MarketPubkeys,
+};
+
+#[derive(Clap, Debug)]
+pub struct CliOpts {
+    #[clap(default_value = "mainnet")]
+    pub cluster: Cluster,
+    #[clap(subcommand)]
+    pub command: CliCommand,
+}
+
+#[derive(Clap, Debug)]
+pub enum CliCommand {
+    ListMarket(ListMarketArgs),
+    /// Prints the market's accounts, lot sizes, fees and deposits as JSON.
+    ShowMarket {
+        dex_program_id: Pubkey,
+        market: Pubkey,
+    },
+    /// Prints a page of the program's markets as JSON, ordered by address.
+    FindMarkets {
+        dex_program_id: Pubkey,
+        #[clap(long)]
+        coin_mint: Option<Pubkey>,
+        #[clap(long)]
+        pc_mint: Option<Pubkey>,
+        /// The `next` of the previous page.
+        #[clap(long)]
+        after: Option<Pubkey>,
+        #[clap(long, default_value = "100")]
+        limit: usize,
+    },
+    /// Prints every resting order, best price first.
+    Orderbook {
+        dex_program_id: Pubkey,
+        market: Pubkey,
+        /// `json` or `csv`.
+        #[clap(long, default_value = "json")]
+        format: snapshot::Format,
+    },
+    /// Places an order, matching it against the book right away. Prices and
+    /// quantities are in lots.
+    PlaceOrder {
+        payer: KeypairPath,
+        dex_program_id: Pubkey,
+        market: Pubkey,
+        /// The token account paying for the order: pc for bids, coin for
+        /// asks.
+        #[clap(long)]
+        wallet: Pubkey,
+        /// `bid` or `ask`.
+        #[clap(long, parse(try_from_str = parse_side))]
+        side: Side,
+        #[clap(long)]
+        price: u64,
+        #[clap(long)]
+        quantity: u64,
+        /// `limit`, `ioc` or `post-only`.
+        #[clap(long, default_value = "limit", parse(try_from_str = parse_order_type))]
+        order_type: OrderType,
+        #[clap(long, default_value = "0")]
+        client_id: u64,
+        /// Open orders account of the payer on this market. A new one is
+        /// created when omitted.
+        #[clap(long)]
+        orders: Option<Pubkey>,
+        /// MarketStats account recording the order's trades.
+        #[clap(long)]
+        stats: Option<Pubkey>,
+    },
+    /// Creates a MarketStats account, bound to the market of the first
+    /// order placed with it.
+    CreateMarketStats {
+        payer: KeypairPath,
+        dex_program_id: Pubkey,
+    },
+    /// Prints the volume, trade count, last trade and self-cross counts of
+    /// a MarketStats account as JSON.
+    ShowMarketStats {
+        stats: Pubkey,
+    },
+    /// Records the market as the canonical market of its mint pair. Fails
+    /// if the pair already has one.
+    RegisterMarket {
+        payer: KeypairPath,
+        dex_program_id: Pubkey,
+        market: Pubkey,
+    },
+    /// Prints the canonical market of a mint pair as JSON.
+    ShowListedMarket {
+        dex_program_id: Pubkey,
+        coin_mint: Pubkey,
+        pc_mint: Pubkey,
+    },
+    /// Cancels an order by its order id or by its client id.
+    CancelOrder {
+        payer: KeypairPath,
+        dex_program_id: Pubkey,
+        market: Pubkey,
+        #[clap(long)]
+        orders: Pubkey,
+        /// `bid` or `ask`. Needed with --order-id.
+        #[clap(long, parse(try_from_str = parse_side))]
+        side: Option<Side>,
+        #[clap(long)]
+        order_id: Option<u128>,
+        #[clap(long)]
+        client_id: Option<u64>,
+    },
+    /// Moves the free funds of an open orders account to the owner's wallets.
+    Settle {
+        payer: KeypairPath,
+        dex_program_id: Pubkey,
+        market: Pubkey,
+        #[clap(long)]
+        orders: Pubkey,
+        #[clap(long)]
+        coin_wallet: Pubkey,
+        #[clap(long)]
+        pc_wallet: Pubkey,
+        /// Owner of the open orders account, if not the payer.
+        #[clap(long)]
+        signer: Option<KeypairPath>,
+    },
+    /// Pays the market's accrued pc fees into `receiver`, or splits them
+    /// between the wallets of `fee_destinations` with the remainder going to
+    /// `receiver`. The payer must be the fee sweeping authority.
+    SweepFees {
+        payer: KeypairPath,
+        dex_program_id: Pubkey,
+        market: Pubkey,
+        #[clap(long)]
+        receiver: Pubkey,
+        #[clap(long)]
+        fee_destinations: Option<Pubkey>,
+    },
+    /// Registers the pc wallets `SweepFees` splits the market's fees
+    /// between, each given as `WALLET:WEIGHT`. The payer must be the fee
+    /// sweeping authority.
+    SetFeeDestinations {
+        payer: KeypairPath,
+        dex_program_id: Pubkey,
+        market: Pubkey,
+        #[clap(long = "destination", parse(try_from_str = parse_fee_destination))]
+        destinations: Vec<(Pubkey, u32)>,
+        /// The account to update. A new one is created when omitted.
+        #[clap(long)]
+        fee_destinations: Option<Pubkey>,
+    },
+    /// Consumes one batch of the market's events.
+    ConsumeEvents {
+        payer: KeypairPath,
+        dex_program_id: Pubkey,
+        market: Pubkey,
+        #[clap(long)]
+        coin_wallet: Pubkey,
+        #[clap(long)]
+        pc_wallet: Pubkey,
+    },
+}
+
+fn parse_side(s: &str) -> Result<Side> {
+    match s {
+        "bid" | "buy" => Ok(Side::Bid),
+        "ask" | "sell" => Ok(Side::Ask),
+        _ => Err(format_err!("expected bid or ask, got {}", s)),
+    }
+}
+
+fn parse_order_type(s: &str) -> Result<OrderType> {
+    match s {
+        "limit" => Ok(OrderType::Limit),
+        "ioc" => Ok(OrderType::ImmediateOrCancel),
+        "post-only" => Ok(OrderType::PostOnly),
+        _ => Err(format_err!("expected limit, ioc or post-only, got {}", s)),
+    }
+}
+
+fn parse_fee_destination(s: &str) -> Result<(Pubkey, u32)> {
+    let mut parts = s.splitn(2, ':');
+    match (parts.next(), parts.next()) {
+        (Some(wallet), Some(weight)) => Ok((
+            wallet
+                .parse()
+                .map_err(|_| format_err!("invalid wallet {}", wallet))?,
+            weight.parse()?,
+        )),
+        _ => Err(format_err!("expected WALLET:WEIGHT, got {}", s)),
+    }
+}
+
+pub fn run(opts: CliOpts) -> Result<()> {
+    let client = RpcClient::new(opts.cluster.url().to_string());
+    match opts.command {
+        CliCommand::ListMarket(ref args) => {
+            let market_keys = args.run(&client)?;
+            println!("Listed market: {:#?}", market_keys);
+        }
+        CliCommand::ShowMarket {
+            ref dex_program_id,
+            ref market,
+        } => {
+            let summary = MarketSummary::load(&client, dex_program_id, market)?;
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+        CliCommand::FindMarkets {
+            ref dex_program_id,
+            coin_mint,
+            pc_mint,
+            after,
+            limit,
+        } => {
+            let filter = MarketFilter { coin_mint, pc_mint };
+            let page = list_markets(&client, dex_program_id, &filter, after.as_ref(), limit)?;
+            println!("{}", serde_json::to_string_pretty(&page)?);
+        }
+        CliCommand::Orderbook {
+            ref dex_program_id,
+            ref market,
+            format,
+        } => {
+            let book = snapshot::snapshot(&client, dex_program_id, market)?;
+            snapshot::write(&book, format, &mut std::io::stdout())?;
+        }
+        CliCommand::PlaceOrder {
+            ref payer,
+            ref dex_program_id,
+            ref market,
+            ref wallet,
+            side,
+            price,
+            quantity,
+            order_type,
+            client_id,
+            ref orders,
+            ref stats,
+        } => {
+            let payer = payer.read()?;
+            let market_keys = get_keys_for_market(&client, dex_program_id, market)?;
+            let new_order = NewOrderInstructionV1 {
+                side,
+                limit_price: NonZeroU64::new(price)
+                    .ok_or_else(|| format_err!("price must be positive"))?,
+                max_qty: NonZeroU64::new(quantity)
+                    .ok_or_else(|| format_err!("quantity must be positive"))?,
+                order_type,
+                client_id,
+            }
+            .add_self_trade_behavior(SelfTradeBehavior::DecrementTake);
+
+            let mut instructions = Vec::new();
+            let orders_keypair;
+            let mut signers = vec![&payer];
+            let orders = match orders {
+                Some(orders) => *orders,
+                None => {
+                    let (orders_key, instruction) = create_dex_account(
+                        &client,
+                        dex_program_id,
+                        &payer.pubkey(),
+                        size_of::<OpenOrders>(),
+                    )?;
+                    orders_keypair = orders_key;
+                    signers.push(&orders_keypair);
+                    instructions.push(instruction);
+                    println!("Open orders account: {}", orders_keypair.pubkey());
+                    orders_keypair.pubkey()
+                }
+            };
+            let mut instruction = new_order_v3_instruction(
+                dex_program_id,
+                &market_keys,
+                &orders,
+                wallet,
+                &payer.pubkey(),
+                new_order,
+            );
+            if let Some(stats) = stats {
+                instruction.accounts.extend(vec![
+                    AccountMeta::new(*stats, false),
+                    AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+                ]);
+            }
+            instructions.push(instruction);
+            send_instructions(&client, &instructions, &payer.pubkey(), &signers)?;
+        }
+        CliCommand::CreateMarketStats {
+            ref payer,
+            ref dex_program_id,
+        } => {
+            let payer = payer.read()?;
+            let (stats, instruction) = create_dex_account(
+                &client,
+                dex_program_id,
+                &payer.pubkey(),
+                size_of::<MarketStats>(),
+            )?;
+            send_instructions(&client, &[instruction], &payer.pubkey(), &[&payer, &stats])?;
+            println!("Market stats account: {}", stats.pubkey());
+        }
+        CliCommand::ShowMarketStats { ref stats } => {
+            let summary = MarketStatsSummary::load(&client, stats)?;
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+        CliCommand::RegisterMarket {
+            ref payer,
+            ref dex_program_id,
+            ref market,
+        } => {
+            let payer = payer.read()?;
+            let data = client.get_account_data(market)?;
+            let state = read_market(&data).map_err(|e| format_err!("{}: {:?}", market, e))?;
+            let coin_mint = Pubkey::new(bytes_of(&identity(state.coin_mint)));
+            let pc_mint = Pubkey::new(bytes_of(&identity(state.pc_mint)));
+            let (listing, nonce) =
+                find_market_listing_address(&coin_mint, &pc_mint, dex_program_id);
+            let instruction = Instruction {
+                program_id: *dex_program_id,
+                data: MarketInstruction::ListMarket(nonce).pack(),
+                accounts: vec![
+                    AccountMeta::new_readonly(*market, false),
+                    AccountMeta::new(payer.pubkey(), true),
+                    AccountMeta::new(listing, false),
+                    AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+                    AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+                ],
+            };
+            send_instructions(&client, &[instruction], &payer.pubkey(), &[&payer])?;
+            println!("Market listing account: {}", listing);
+        }
+        CliCommand::ShowListedMarket {
+            ref dex_program_id,
+            ref coin_mint,
+            ref pc_mint,
+        } => {
+            let summary = MarketListingSummary::load(&client, dex_program_id, coin_mint, pc_mint)?;
+            println!("{}", serde_json::to_string_pretty(&summary)?);
+        }
+        CliCommand::CancelOrder {
+            ref payer,
+            ref dex_program_id,
+            ref market,
+            ref orders,
+            side,
+            order_id,
+            client_id,
+        } => {
+            let payer = payer.read()?;
+            let market_keys = get_keys_for_market(&client, dex_program_id, market)?;
+            let cancel = match (side, order_id, client_id) {
+                (Some(side), Some(order_id), None) => {
+                    MarketInstruction::CancelOrderV2(CancelOrderInstructionV2 { side, order_id })
+                }
+                (None, None, Some(client_id)) => {
+                    MarketInstruction::CancelOrderByClientIdV2(client_id)
+                }
+                _ => {
+                    return Err(format_err!(
+                        "pass either --side and --order-id, or --client-id"
+                    ))
+                }
+            };
+            let instruction = cancel_order_instruction(
+                dex_program_id,
+                &market_keys,
+                orders,
+                &payer.pubkey(),
+                cancel,
+            );
+            send_instructions(&client, &[instruction], &payer.pubkey(), &[&payer])?;
+        }
+        CliCommand::Settle {
+            ref payer,
+            ref dex_program_id,
+            ref market,
+            ref orders,
+            ref coin_wallet,
+            ref pc_wallet,
+            ref signer,
+        } => {
+            let payer = payer.read()?;
+            let signer = signer.as_ref().map(KeypairPath::read).transpose()?;
+            let market_keys = get_keys_for_market(&client, dex_program_id, market)?;
+            crate::settle_funds(
+                &client,
+                dex_program_id,
+                &payer,
+                &market_keys,
+                signer.as_ref(),
+                orders,
+                coin_wallet,
+                pc_wallet,
+                None,
+                &ComputeBudget::default(),
+            )?;
+        }
+        CliCommand::SweepFees {
+            ref payer,
+            ref dex_program_id,
+            ref market,
+            ref receiver,
+            ref fee_destinations,
+        } => {
+            let payer = payer.read()?;
+            let market_keys = get_keys_for_market(&client, dex_program_id, market)?;
+            let fee_destinations = match fee_destinations {
+                None => None,
+                Some(key) => {
+                    let data = client.get_account_data(key)?;
+                    let words: Cow<[u64]> = remove_dex_account_padding(&data)?;
+                    let destinations: FeeDestinations = read_pod(cast_slice(&words))?;
+                    let wallets: Vec<Pubkey> = destinations
+                        .wallets()
+                        .iter()
+                        .map(|wallet| Pubkey::new(bytes_of(wallet)))
+                        .collect();
+                    Some((*key, wallets))
+                }
+            };
+            let instruction = sweep_fees_instruction(
+                dex_program_id,
+                &market_keys,
+                &payer.pubkey(),
+                receiver,
+                fee_destinations
+                    .as_ref()
+                    .map(|(key, wallets)| (key, wallets.as_slice())),
+            );
+            send_instructions(&client, &[instruction], &payer.pubkey(), &[&payer])?;
+        }
+        CliCommand::SetFeeDestinations {
+            ref payer,
+            ref dex_program_id,
+            ref market,
+            ref destinations,
+            ref fee_destinations,
+        } => {
+            let payer = payer.read()?;
+            let mut instructions = Vec::new();
+            let destinations_keypair;
+            let mut signers = vec![&payer];
+            let fee_destinations = match fee_destinations {
+                Some(fee_destinations) => *fee_destinations,
+                None => {
+                    let (destinations_key, instruction) = create_dex_account(
+                        &client,
+                        dex_program_id,
+                        &payer.pubkey(),
+                        size_of::<FeeDestinations>(),
+                    )?;
+                    destinations_keypair = destinations_key;
+                    signers.push(&destinations_keypair);
+                    instructions.push(instruction);
+                    println!(
+                        "Fee destinations account: {}",
+                        destinations_keypair.pubkey()
+                    );
+                    destinations_keypair.pubkey()
+                }
+            };
+            let mut accounts = vec![
+                AccountMeta::new_readonly(*market, false),
+                AccountMeta::new_readonly(payer.pubkey(), true),
+                AccountMeta::new(fee_destinations, false),
+                AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+            ];
+            accounts.extend(
+                destinations
+                    .iter()
+                    .map(|(wallet, _)| AccountMeta::new_readonly(*wallet, false)),
+            );
+            let weights = destinations.iter().map(|&(_, weight)| weight).collect();
+            instructions.push(Instruction {
+                program_id: *dex_program_id,
+                data: MarketInstruction::SetFeeDestinations(SetFeeDestinationsInstruction {
+                    weights,
+                })
+                .pack(),
+                accounts,
+            });
+            send_instructions(&client, &instructions, &payer.pubkey(), &signers)?;
+        }
+        CliCommand::ConsumeEvents {
+            ref payer,
+            ref dex_program_id,
+            ref market,
+            ref coin_wallet,
+            ref pc_wallet,
+        } => {
+            let payer = payer.read()?;
+            let market_keys = get_keys_for_market(&client, dex_program_id, market)?;
+            crate::consume_events(
+                &client,
+                dex_program_id,
+                &payer,
+                &market_keys,
+                coin_wallet,
+                pc_wallet,
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// Builds a `CancelOrderV2` or `CancelOrderByClientIdV2` instruction.
+pub fn cancel_order_instruction(
+    program_id: &Pubkey,
+    state: &MarketPubkeys,
+    orders: &Pubkey,
+    owner: &Pubkey,
+    cancel: MarketInstruction,
+) -> Instruction {
+    Instruction {
+        program_id: *program_id,
+        data: cancel.pack(),
+        accounts: vec![
+            AccountMeta::new(*state.market, false),
+            AccountMeta::new(*orders, false),
+            AccountMeta::new(*state.req_q, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*state.event_q, false),
+            AccountMeta::new(*state.bids, false),
+            AccountMeta::new(*state.asks, false),
+        ],
+    }
+}
+
+/// Builds a `SweepFees` instruction. `fee_destinations` is the
+/// FeeDestinations account to split the fees by, with its wallets in the
+/// order they were registered.
+pub fn sweep_fees_instruction(
+    program_id: &Pubkey,
+    state: &MarketPubkeys,
+    sweep_authority: &Pubkey,
+    receiver: &Pubkey,
+    fee_destinations: Option<(&Pubkey, &[Pubkey])>,
+) -> Instruction {
+    let mut accounts = vec![
+        AccountMeta::new(*state.market, false),
+        AccountMeta::new(*state.pc_vault, false),
+        AccountMeta::new_readonly(*sweep_authority, true),
+        AccountMeta::new(*receiver, false),
+        AccountMeta::new_readonly(*state.vault_signer_key, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+    ];
+    if let Some((fee_destinations, wallets)) = fee_destinations {
+        accounts.push(AccountMeta::new_readonly(*fee_destinations, false));
+        accounts.extend(
+            wallets
+                .iter()
+                .map(|wallet| AccountMeta::new(*wallet, false)),
+        );
+    }
+    Instruction {
+        program_id: *program_id,
+        data: MarketInstruction::SweepFees.pack(),
+        accounts,
+    }
+}
@@ -0,0 +1,128 @@
+//! ComputeBudget instructions prepended to crank transactions, so they carry
+//! a priority fee during congestion. The SDK this crate builds against
+//! predates the ComputeBudget program, so the instructions are encoded here.
+
+use std::str::FromStr;
+
+use anyhow::{format_err, Result};
+use serde_json::json;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+
+// Units a transaction may use per instruction when it doesn't set a limit.
+const DEFAULT_UNITS_PER_INSTRUCTION: u64 = 200_000;
+const MAX_UNITS: u64 = 1_400_000;
+const MICRO_LAMPORTS_PER_LAMPORT: u64 = 1_000_000;
+
+pub fn id() -> Pubkey {
+    Pubkey::from_str("ComputeBudget111111111111111111111111111111").unwrap()
+}
+
+pub fn set_compute_unit_limit(units: u32) -> Instruction {
+    let mut data = vec![2];
+    data.extend_from_slice(&units.to_le_bytes());
+    Instruction {
+        program_id: id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+pub fn set_compute_unit_price(micro_lamports: u64) -> Instruction {
+    let mut data = vec![3];
+    data.extend_from_slice(&micro_lamports.to_le_bytes());
+    Instruction {
+        program_id: id(),
+        accounts: vec![],
+        data,
+    }
+}
+
+/// Price per compute unit, in micro-lamports.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PriorityFee {
+    Fixed(u64),
+    /// The median fee recently paid to write the transaction's accounts.
+    Auto,
+}
+
+impl FromStr for PriorityFee {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s == "auto" {
+            return Ok(PriorityFee::Auto);
+        }
+        s.parse()
+            .map(PriorityFee::Fixed)
+            .map_err(|_| format_err!("expected micro-lamports or `auto`, got {}", s))
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ComputeBudget {
+    pub unit_limit: Option<u32>,
+    pub unit_price: Option<PriorityFee>,
+    // Queried for recent fees in auto mode.
+    pub rpc_url: String,
+}
+
+impl ComputeBudget {
+    /// Prepends the ComputeBudget instructions to `instructions`, returning
+    /// the priority fee they add in lamports.
+    pub fn prepend(&self, instructions: &mut Vec<Instruction>) -> Result<u64> {
+        let units = match self.unit_limit {
+            Some(units) => units as u64,
+            None => (DEFAULT_UNITS_PER_INSTRUCTION * instructions.len() as u64).min(MAX_UNITS),
+        };
+        let price = match self.unit_price {
+            None => 0,
+            Some(PriorityFee::Fixed(price)) => price,
+            Some(PriorityFee::Auto) => self.recent_fee(instructions)?,
+        };
+
+        let mut budget = vec![];
+        if let Some(units) = self.unit_limit {
+            budget.push(set_compute_unit_limit(units));
+        }
+        if price > 0 {
+            budget.push(set_compute_unit_price(price));
+        }
+        instructions.splice(0..0, budget);
+
+        let fee = (price as u128 * units as u128 + MICRO_LAMPORTS_PER_LAMPORT as u128 - 1)
+            / MICRO_LAMPORTS_PER_LAMPORT as u128;
+        Ok(fee as u64)
+    }
+
+    fn recent_fee(&self, instructions: &[Instruction]) -> Result<u64> {
+        let mut writable: Vec<String> = instructions
+            .iter()
+            .flat_map(|ix| ix.accounts.iter())
+            .filter(|meta| meta.is_writable)
+            .map(|meta| meta.pubkey.to_string())
+            .collect();
+        writable.sort();
+        writable.dedup();
+        // The RPC node accepts at most 128 accounts.
+        writable.truncate(128);
+
+        let result = crate::json_rpc(
+            &self.rpc_url,
+            "getRecentPrioritizationFees",
+            json!([writable]),
+        )?;
+        let results = result.as_array().ok_or_else(|| {
+            format_err!("unexpected getRecentPrioritizationFees result: {}", result)
+        })?;
+        let mut fees: Vec<u64> = results
+            .iter()
+            .filter_map(|r| r["prioritizationFee"].as_u64())
+            .collect();
+        if fees.is_empty() {
+            return Ok(0);
+        }
+        fees.sort_unstable();
+        Ok(fees[fees.len() / 2])
+    }
+}
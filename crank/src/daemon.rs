@@ -0,0 +1,334 @@
+//! Cranks many markets from one process. Each market is a table in a TOML
+//! config file, with its own keypair, intervals, and limits:
+//!
+//! ```toml
+//! log_directory = "logs/crank.log"
+//!
+//! [[markets]]
+//! name = "SRM/USDC"
+//! dex_program_id = "..."
+//! market = "..."
+//! payer = "keys/srm-usdc.json"
+//! worker_payers = ["keys/srm-usdc-1.json", "keys/srm-usdc-2.json"]
+//! coin_wallet = "..."
+//! pc_wallet = "..."
+//! num_workers = 4
+//! events_per_worker = 5
+//!
+//! # Optional, settles referrer rebates like `settle-referrer-rebates`.
+//! [markets.fee_sweep]
+//! referrer_pc_wallet = "..."
+//! orders = ["<open orders>,<coin wallet>,<pc wallet>"]
+//! interval_secs = 600
+//! ```
+//!
+//! By default each market is cranked by its own workers. With a
+//! `[scheduler]` table, markets instead share `max_in_flight` transactions,
+//! which go to the longest queues first (see `scheduler`):
+//!
+//! ```toml
+//! [scheduler]
+//! max_in_flight = 16
+//! max_starvation_ms = 10000
+//! ```
+
+use std::fmt::Display;
+use std::panic::{self, AssertUnwindSafe};
+use std::str::FromStr;
+use std::{thread, time};
+
+use anyhow::{format_err, Result};
+use log::{error, info};
+use serde::{Deserialize, Deserializer};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use serum_common::client::args::KeypairPath;
+use serum_common::client::Cluster;
+
+use crate::compute_budget::ComputeBudget;
+use crate::endpoints::RpcEndpoints;
+use crate::metrics::Metrics;
+use crate::scheduler::{self, SchedulerConfig};
+use crate::throttle::InFlight;
+use crate::RebateAccounts;
+
+// Pause before restarting a task that failed, so an RPC outage doesn't turn
+// into a busy loop.
+const RESTART_DELAY: time::Duration = time::Duration::from_secs(5);
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// Log file shared by all markets.
+    pub log_directory: String,
+    pub markets: Vec<MarketConfig>,
+    /// Shares transactions between markets by backlog.
+    pub scheduler: Option<SchedulerConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MarketConfig {
+    /// Label for the market's threads and logs. Defaults to the market
+    /// address.
+    pub name: Option<String>,
+    #[serde(deserialize_with = "from_str")]
+    pub dex_program_id: Pubkey,
+    #[serde(deserialize_with = "from_str")]
+    pub market: Pubkey,
+    #[serde(deserialize_with = "from_str")]
+    pub payer: KeypairPath,
+    /// More keypairs for the workers to pay with, in turn with `payer`.
+    #[serde(default, deserialize_with = "from_str_seq")]
+    pub worker_payers: Vec<KeypairPath>,
+    #[serde(deserialize_with = "from_str")]
+    pub coin_wallet: Pubkey,
+    #[serde(deserialize_with = "from_str")]
+    pub pc_wallet: Pubkey,
+    pub num_workers: usize,
+    pub events_per_worker: usize,
+    #[serde(default = "default_num_accounts")]
+    pub num_accounts: usize,
+    /// Time between reads of the event queue.
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+    /// Defaults to the number of workers.
+    pub max_in_flight: Option<usize>,
+    #[serde(default = "default_target_confirmation_ms")]
+    pub target_confirmation_ms: u64,
+    /// Waits on a websocket subscription to the event queue while idle,
+    /// instead of polling. Not used by the scheduler.
+    #[serde(default)]
+    pub websocket: bool,
+    pub fee_sweep: Option<FeeSweepConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FeeSweepConfig {
+    #[serde(deserialize_with = "from_str")]
+    pub referrer_pc_wallet: Pubkey,
+    #[serde(deserialize_with = "from_str_seq")]
+    pub orders: Vec<RebateAccounts>,
+    #[serde(default = "default_sweep_interval_secs")]
+    pub interval_secs: u64,
+}
+
+fn default_num_accounts() -> usize {
+    32
+}
+
+fn default_poll_interval_ms() -> u64 {
+    300
+}
+
+fn default_target_confirmation_ms() -> u64 {
+    5_000
+}
+
+fn default_sweep_interval_secs() -> u64 {
+    600
+}
+
+impl Config {
+    /// Reads and checks the config, including that every keypair can be
+    /// read, so mistakes show up before any market is cranked.
+    pub fn load(path: &str) -> Result<Config> {
+        let contents = std::fs::read_to_string(path)
+            .map_err(|e| format_err!("failed to read {}: {}", path, e))?;
+        let config: Config =
+            toml::from_str(&contents).map_err(|e| format_err!("invalid {}: {}", path, e))?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> Result<()> {
+        if self.markets.is_empty() {
+            return Err(format_err!("no markets configured"));
+        }
+        for market in &self.markets {
+            let name = market.name();
+            if market.num_workers == 0 || market.events_per_worker == 0 {
+                return Err(format_err!(
+                    "{}: num_workers and events_per_worker must be positive",
+                    name
+                ));
+            }
+            for payer in market.payers() {
+                payer.read().map_err(|e| format_err!("{}: {}", name, e))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl MarketConfig {
+    pub fn name(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.market.to_string())
+    }
+
+    /// Keypairs the market's workers pay with, in turn.
+    pub fn payers(&self) -> Vec<KeypairPath> {
+        let mut payers = vec![self.payer.clone()];
+        payers.extend(self.worker_payers.iter().cloned());
+        payers
+    }
+}
+
+/// Starts the configured tasks for every market and runs until killed.
+pub fn run(
+    rpc: &RpcEndpoints,
+    config: Config,
+    compute_budget: &ComputeBudget,
+    metrics: &Metrics,
+) -> Result<()> {
+    super::init_file_logging(&config.log_directory);
+
+    let mut handles = vec![];
+    if let Some(scheduler) = config.scheduler.clone() {
+        info!("Starting crank for {} markets", config.markets.len());
+        let rpc = rpc.clone();
+        let markets = config.markets.clone();
+        let compute_budget = compute_budget.clone();
+        let metrics = metrics.clone();
+        handles.push(
+            thread::Builder::new()
+                .name("consume-events".to_string())
+                .spawn(move || {
+                    supervise("consume-events", || {
+                        scheduler::run(&rpc, &markets, &scheduler, &compute_budget, &metrics)
+                    })
+                })?,
+        );
+    }
+
+    for market in config.markets {
+        if config.scheduler.is_some() {
+            spawn_fee_sweep(&mut handles, &rpc.cluster, &market, compute_budget)?;
+            continue;
+        }
+        let name = market.name();
+        info!("Starting crank for {}", name);
+
+        let consume_rpc = rpc.clone();
+        let consume = market.clone();
+        let consume_budget = compute_budget.clone();
+        let consume_metrics = metrics.clone();
+        handles.push(
+            thread::Builder::new()
+                .name(format!("consume-events {}", name))
+                .spawn(move || {
+                    supervise(&format!("consume-events {}", name), || {
+                        consume_events(&consume_rpc, &consume, &consume_budget, &consume_metrics)
+                    })
+                })?,
+        );
+
+        spawn_fee_sweep(&mut handles, &rpc.cluster, &market, compute_budget)?;
+    }
+
+    // Tasks restart themselves, so this only returns if one can't.
+    for handle in handles {
+        handle
+            .join()
+            .map_err(|_| format_err!("crank task exited unexpectedly"))?;
+    }
+    Ok(())
+}
+
+fn spawn_fee_sweep(
+    handles: &mut Vec<thread::JoinHandle<()>>,
+    cluster: &Cluster,
+    market: &MarketConfig,
+    compute_budget: &ComputeBudget,
+) -> Result<()> {
+    if let Some(sweep) = market.fee_sweep.clone() {
+        let name = market.name();
+        let client = RpcClient::new(cluster.url().to_string());
+        let dex_program_id = market.dex_program_id;
+        let payer = market.payer.clone();
+        let compute_budget = compute_budget.clone();
+        handles.push(
+            thread::Builder::new()
+                .name(format!("fee-sweep {}", name))
+                .spawn(move || {
+                    supervise(&format!("fee-sweep {}", name), || {
+                        super::settle_referrer_rebates_loop(
+                            &client,
+                            &dex_program_id,
+                            &payer.read()?,
+                            &sweep.referrer_pc_wallet,
+                            &sweep.orders,
+                            time::Duration::from_secs(sweep.interval_secs),
+                            &compute_budget,
+                        )
+                    })
+                })?,
+        );
+    }
+    Ok(())
+}
+
+fn consume_events(
+    rpc: &RpcEndpoints,
+    market: &MarketConfig,
+    compute_budget: &ComputeBudget,
+    metrics: &Metrics,
+) -> Result<()> {
+    let in_flight = InFlight::new(
+        market.max_in_flight.unwrap_or(market.num_workers),
+        time::Duration::from_millis(market.target_confirmation_ms),
+    );
+    super::consume_events_loop(
+        rpc,
+        &market.dex_program_id,
+        &market.payers(),
+        &market.market,
+        &market.coin_wallet,
+        &market.pc_wallet,
+        market.num_workers,
+        market.events_per_worker,
+        market.num_accounts,
+        time::Duration::from_millis(market.poll_interval_ms),
+        market.websocket,
+        in_flight,
+        compute_budget,
+        metrics,
+    )
+}
+
+// Reruns a task whenever it fails or panics, so trouble with one market
+// doesn't stop the others.
+fn supervise<F: FnMut() -> Result<()>>(task: &str, mut f: F) {
+    loop {
+        match panic::catch_unwind(AssertUnwindSafe(&mut f)) {
+            Ok(Ok(())) => error!("{} stopped, restarting", task),
+            Ok(Err(err)) => error!("{} failed, restarting: {:?}", task, err),
+            Err(_) => error!("{} panicked, restarting", task),
+        }
+        thread::sleep(RESTART_DELAY);
+    }
+}
+
+fn from_str<'de, D, T>(deserializer: D) -> std::result::Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    let s = String::deserialize(deserializer)?;
+    T::from_str(&s).map_err(serde::de::Error::custom)
+}
+
+fn from_str_seq<'de, D, T>(deserializer: D) -> std::result::Result<Vec<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: FromStr,
+    T::Err: Display,
+{
+    Vec::<String>::deserialize(deserializer)?
+        .iter()
+        .map(|s| T::from_str(s).map_err(serde::de::Error::custom))
+        .collect()
+}
@@ -0,0 +1,60 @@
+//! The RPC endpoints the crank sends through: the cluster's own, then any
+//! fallbacks, so one flaky provider doesn't stop the crank.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::warn;
+
+use serum_common::client::failover::FailoverConfig;
+use serum_common::client::session::{spawn_health_checks, RpcSession, SessionConfig};
+use serum_common::client::Cluster;
+
+/// How often the endpoints of a session are health checked, when there's
+/// more than one.
+const HEALTH_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug)]
+pub struct RpcEndpoints {
+    pub cluster: Cluster,
+    /// Other endpoints of the same cluster, in order of preference.
+    pub fallback_urls: Vec<String>,
+    pub failover: FailoverConfig,
+}
+
+impl RpcEndpoints {
+    pub fn new(cluster: Cluster) -> Self {
+        RpcEndpoints {
+            cluster,
+            fallback_urls: vec![],
+            failover: FailoverConfig::default(),
+        }
+    }
+
+    pub fn urls(&self) -> Vec<String> {
+        let mut urls = vec![self.cluster.url().to_string()];
+        urls.extend(self.fallback_urls.iter().cloned());
+        urls
+    }
+
+    /// A session failing over between the endpoints, health checked in the
+    /// background until it's dropped.
+    pub fn session(&self) -> Arc<RpcSession> {
+        let config = SessionConfig {
+            failover: self.failover.clone(),
+            ..SessionConfig::default()
+        };
+        let session = Arc::new(RpcSession::with_endpoints(&self.urls(), config));
+        if !self.fallback_urls.is_empty() {
+            spawn_health_checks(&session, HEALTH_CHECK_INTERVAL, |health| {
+                for endpoint in health.iter().filter(|endpoint| !endpoint.healthy) {
+                    warn!(
+                        "RPC endpoint {} is unhealthy, slot {:?}",
+                        endpoint.url, endpoint.slot
+                    );
+                }
+            });
+        }
+        session
+    }
+}
@@ -0,0 +1,81 @@
+//! Fill history of a market, rebuilt from the fills `ConsumeEvents` logs in
+//! confirmed transactions touching its event queue.
+
+use std::collections::BTreeMap;
+
+use anyhow::{format_err, Result};
+use serde_json::json;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use serum_dex::client::{parse_fill_logs, FillEvent};
+
+use crate::{get_keys_for_market, json_rpc};
+
+/// A fill, along with the transaction that consumed it.
+#[derive(Debug, Clone)]
+pub struct HistoricalFill {
+    pub slot: u64,
+    pub signature: String,
+    pub fill: FillEvent,
+}
+
+/// Fills consumed between `start_slot` and `end_slot`, inclusive, keyed by
+/// open orders account and oldest first.
+///
+/// Fills are logged when the crank consumes them, which may be a few slots
+/// after they matched. Transactions consuming the events of several
+/// markets at once, if any, contribute their other markets' fills too.
+pub fn fill_history(
+    client: &RpcClient,
+    rpc_url: &str,
+    program_id: &Pubkey,
+    market: &Pubkey,
+    start_slot: u64,
+    end_slot: u64,
+) -> Result<BTreeMap<Pubkey, Vec<HistoricalFill>>> {
+    let keys = get_keys_for_market(client, program_id, market)?;
+    let signatures = json_rpc(
+        rpc_url,
+        "getConfirmedSignaturesForAddress",
+        json!([keys.event_q.to_string(), start_slot, end_slot]),
+    )?;
+    let signatures = signatures
+        .as_array()
+        .ok_or_else(|| format_err!("unexpected signatures: {}", signatures))?;
+
+    let mut history: BTreeMap<Pubkey, Vec<HistoricalFill>> = BTreeMap::new();
+    for signature in signatures {
+        let signature = signature
+            .as_str()
+            .ok_or_else(|| format_err!("unexpected signature: {}", signature))?;
+        let transaction = json_rpc(
+            rpc_url,
+            "getConfirmedTransaction",
+            json!([signature, "json"]),
+        )?;
+        let meta = &transaction["meta"];
+        // Failed transactions' fills were rolled back.
+        if transaction.is_null() || !meta["err"].is_null() {
+            continue;
+        }
+        let slot = transaction["slot"]
+            .as_u64()
+            .ok_or_else(|| format_err!("{} has no slot", signature))?;
+        let logs = meta["logMessages"]
+            .as_array()
+            .ok_or_else(|| format_err!("{} has no log messages", signature))?;
+        for fill in parse_fill_logs(logs.iter().filter_map(|line| line.as_str())) {
+            history.entry(fill.owner).or_default().push(HistoricalFill {
+                slot,
+                signature: signature.to_string(),
+                fill,
+            });
+        }
+    }
+    // The node doesn't promise any order for the signatures.
+    for fills in history.values_mut() {
+        fills.sort_by_key(|fill| fill.slot);
+    }
+    Ok(history)
+}
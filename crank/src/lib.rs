@@ -3,22 +3,21 @@
 
 use std::borrow::Cow;
 use std::cmp::min;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::convert::identity;
 use std::mem::size_of;
+use std::net::SocketAddr;
 use std::num::NonZeroU64;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use std::{thread, time};
 
 use anyhow::{format_err, Result};
+use bytemuck::{bytes_of, cast_slice, cast_slice_mut, try_cast_slice, try_from_bytes, Pod};
 use clap::Clap;
 use debug_print::debug_println;
 use log::{error, info};
 use rand::rngs::OsRng;
-use safe_transmute::{
-    guard::SingleManyGuard,
-    to_bytes::{transmute_one_to_bytes, transmute_to_bytes},
-    transmute_many, transmute_many_pedantic, transmute_one_pedantic,
-};
 use sloggers::file::FileLoggerBuilder;
 use sloggers::types::Severity;
 use sloggers::Build;
@@ -34,33 +33,109 @@ use solana_sdk::transaction::Transaction;
 use spl_token::instruction as token_instruction;
 use warp::Filter;
 
+use serum_common::client::args::{KeypairPath, PubkeyOrKeypair, UiAmount};
+use serum_common::client::batch::{send_batched, BatchConfig, Outcome};
 use serum_common::client::rpc::{
-    create_and_init_mint, create_token_account, mint_to_new_account, send_txn, simulate_transaction,
+    create_and_init_mint, create_token_account, get_token_account, is_dry_run, mint_to_new_account,
+    print_simulation, send_instructions, set_dry_run, set_send_policy, simulate_transaction,
+    SendPolicy,
 };
+use serum_common::client::session::RpcSession;
 use serum_common::client::Cluster;
-use serum_dex::instruction::{MarketInstruction, NewOrderInstructionV1};
+use serum_dex::client::{LotSizes, OrderBookReader};
+use serum_dex::critbit::{AnyNode, Slab, SLAB_HEADER_LEN};
+use serum_dex::fees::{FeeSchedule, FeeTier};
+use serum_dex::instruction::{
+    MarketInstruction, NewOrderInstructionV1, NewOrderInstructionV2, SelfTradeBehavior,
+};
 use serum_dex::matching::{OrderType, Side};
 use serum_dex::state::gen_vault_signer_key;
+use serum_dex::state::validate_lot_sizes;
 use serum_dex::state::Event;
 use serum_dex::state::EventQueueHeader;
 use serum_dex::state::MarketState;
+use serum_dex::state::OpenOrders;
 use serum_dex::state::QueueHeader;
 use serum_dex::state::Request;
 use serum_dex::state::RequestQueueHeader;
+use serum_dex::state::ToAlignedBytes;
+
+pub mod batch;
+pub mod cli;
+pub mod compute_budget;
+pub mod daemon;
+pub mod endpoints;
+pub mod fills;
+pub mod markets;
+pub mod metrics;
+pub mod monitor;
+pub mod native_sol;
+pub mod portfolio;
+pub mod rebates;
+pub mod replica;
+pub mod scheduler;
+pub mod snapshot;
+pub mod subscription;
+pub mod throttle;
+
+use compute_budget::{ComputeBudget, PriorityFee};
+use endpoints::RpcEndpoints;
+use metrics::Metrics;
+use native_sol::TempWsolAccount;
+use subscription::{wait_for_events, QueueWatcher};
+use throttle::InFlight;
+
+// Longest an idle crank waits on its websocket before reading the queue.
+const WEBSOCKET_RESYNC: time::Duration = time::Duration::from_secs(30);
 
 pub fn with_logging<F: FnOnce()>(_to: &str, fnc: F) {
     fnc();
 }
 
-fn read_keypair_file(s: &str) -> Result<Keypair> {
-    solana_sdk::signature::read_keypair_file(s)
-        .map_err(|_| format_err!("failed to read keypair from {}", s))
-}
-
 #[derive(Clap, Debug)]
 pub struct Opts {
     #[clap(default_value = "mainnet")]
     pub cluster: Cluster,
+    /// Serves Prometheus metrics for the cranked markets at
+    /// `http://<addr>/metrics`.
+    #[clap(long)]
+    pub metrics_addr: Option<SocketAddr>,
+    /// Compute unit limit requested by crank transactions.
+    #[clap(long)]
+    pub compute_unit_limit: Option<u32>,
+    /// Priority fee of crank transactions, in micro-lamports per compute
+    /// unit, or `auto` for the median fee recently paid to write the same
+    /// accounts.
+    #[clap(long)]
+    pub priority_fee_micro_lamports: Option<PriorityFee>,
+    /// Simulates transactions and prints their logs instead of sending them.
+    #[clap(long)]
+    pub dry_run: bool,
+    /// Durable nonce account to sign transactions with instead of a recent
+    /// blockhash. Its authority must be the fee payer. Not used for
+    /// ConsumeEvents, which sends many transactions at once.
+    #[clap(long)]
+    pub nonce_account: Option<Pubkey>,
+    /// Times a failed transaction is signed again and resent.
+    #[clap(long)]
+    pub max_retries: Option<usize>,
+    /// Wait before the first retry, doubled after each one.
+    #[clap(long)]
+    pub retry_backoff_ms: Option<u64>,
+    /// Address lookup table to load accounts from, sending version 0
+    /// transactions so more accounts fit in each. May be repeated. Not
+    /// used for ConsumeEvents.
+    #[clap(long)]
+    pub lookup_table: Vec<Pubkey>,
+    /// Another RPC endpoint of the cluster, failed over to when the
+    /// cluster's own errors or falls behind. May be repeated. Used by the
+    /// ConsumeEvents loops and the daemon.
+    #[clap(long)]
+    pub fallback_rpc_url: Vec<String>,
+    /// Requests per second sent to any one RPC endpoint by the ConsumeEvents
+    /// loops and the daemon.
+    #[clap(long)]
+    pub rpc_requests_per_second: Option<u32>,
     #[clap(subcommand)]
     pub command: Command,
 }
@@ -69,23 +144,23 @@ pub struct Opts {
 pub enum Command {
     Genesis {
         #[clap(long, short)]
-        payer: String,
+        payer: KeypairPath,
 
         #[clap(long, short)]
-        mint: String,
+        mint: KeypairPath,
 
         #[clap(long, short)]
-        owner_pubkey: Pubkey,
+        owner_pubkey: PubkeyOrKeypair,
 
         #[clap(long, short)]
         decimals: u8,
     },
     Mint {
         #[clap(long, short)]
-        payer: String,
+        payer: KeypairPath,
 
         #[clap(long, short)]
-        signer: String,
+        signer: KeypairPath,
 
         #[clap(long, short)]
         mint_pubkey: Pubkey,
@@ -93,20 +168,27 @@ pub enum Command {
         #[clap(long, short)]
         recipient: Option<Pubkey>,
 
+        /// Amount in whole tokens, e.g. `1.5`.
         #[clap(long, short)]
-        quantity: u64,
+        quantity: UiAmount,
     },
     CreateAccount {
         mint_pubkey: Pubkey,
         owner_pubkey: Pubkey,
-        payer: String,
+        payer: KeypairPath,
     },
     ConsumeEvents {
         #[clap(long, short)]
         dex_program_id: Pubkey,
 
         #[clap(long)]
-        payer: String,
+        payer: KeypairPath,
+
+        /// More keypairs for the workers to pay with, in turn with `payer`,
+        /// so concurrent transactions don't all draw on one account. May be
+        /// repeated.
+        #[clap(long)]
+        worker_payer: Vec<KeypairPath>,
 
         #[clap(long, short)]
         market: Pubkey,
@@ -128,13 +210,27 @@ pub enum Command {
 
         #[clap(long)]
         log_directory: String,
+
+        /// Most unconfirmed ConsumeEvents transactions for the market.
+        /// Defaults to the number of workers.
+        #[clap(long)]
+        max_in_flight: Option<usize>,
+
+        /// Confirmation latency above which fewer transactions are sent.
+        #[clap(long)]
+        target_confirmation_ms: Option<u64>,
+
+        /// Waits for changes to the event queue over a websocket while idle,
+        /// instead of polling. Polls while the websocket is down.
+        #[clap(long)]
+        websocket: bool,
     },
     MatchOrders {
         #[clap(long, short)]
         dex_program_id: Pubkey,
 
         #[clap(long)]
-        payer: String,
+        payer: KeypairPath,
 
         #[clap(long, short)]
         market: Pubkey,
@@ -149,56 +245,265 @@ pub enum Command {
         #[clap(long, short)]
         dex_program_id: Pubkey,
 
+        /// May be repeated to watch several markets.
         #[clap(long, short)]
-        market: Pubkey,
+        market: Vec<Pubkey>,
+
+        /// Serves the event queue length of a single market at
+        /// `http://127.0.0.1:<port>/length`.
+        #[clap(long)]
+        port: Option<u16>,
 
+        /// Queue length above which a market is reported.
         #[clap(long)]
-        port: u16,
+        threshold: Option<usize>,
+
+        /// URL posted a JSON alert when a market goes over the threshold
+        /// or recovers.
+        #[clap(long)]
+        webhook: Option<String>,
+
+        /// Keeps watching at this interval. Otherwise the queues are checked
+        /// once and the command fails if any is over the threshold.
+        #[clap(long)]
+        interval_secs: Option<u64>,
     },
     PrintEventQueue {
         dex_program_id: Pubkey,
         market: Pubkey,
     },
+    /// Writes every resting order of a market, best price first.
+    SnapshotOrderbook {
+        #[clap(long, short)]
+        dex_program_id: Pubkey,
+
+        market: Pubkey,
+
+        /// Defaults to stdout.
+        #[clap(long, short)]
+        output: Option<String>,
+
+        /// `json` or `csv`. Defaults to csv for a `.csv` output, and json
+        /// otherwise.
+        #[clap(long, short)]
+        format: Option<snapshot::Format>,
+    },
     WholeShebang {
-        payer: String,
+        payer: KeypairPath,
         dex_program_id: Pubkey,
     },
     SettleFunds {
-        payer: String,
+        payer: KeypairPath,
         dex_program_id: Pubkey,
         market: Pubkey,
         orders: Pubkey,
         coin_wallet: Pubkey,
         pc_wallet: Pubkey,
         #[clap(long, short)]
-        signer: Option<String>,
+        signer: Option<KeypairPath>,
+    },
+    ListMarket(ListMarketArgs),
+    InitializeTokenAccount {
+        mint: Pubkey,
+        owner_account: KeypairPath,
+    },
+    SettleReferrerRebates {
+        #[clap(long, short)]
+        dex_program_id: Pubkey,
+
+        #[clap(long)]
+        payer: KeypairPath,
+
+        /// Quote token account the rebates are paid into.
+        #[clap(long, short)]
+        referrer_pc_wallet: Pubkey,
+
+        /// Token accounts of the payer that other users settle with as
+        /// their referrer. Their whole balance is swept into
+        /// `referrer_pc_wallet`, so they must hold nothing but rebates.
+        #[clap(long)]
+        referrer_wallets: Vec<Pubkey>,
+
+        /// `<open orders>,<coin wallet>,<pc wallet>`, one per market the
+        /// payer trades on, to collect the rebates on its own fills.
+        #[clap(long, short)]
+        orders: Vec<RebateAccounts>,
+
+        #[clap(long)]
+        interval_secs: Option<u64>,
     },
-    ListMarket {
-        payer: String,
+    /// Settles the free balances of a wallet's open orders accounts.
+    Settle {
+        #[clap(long, short)]
         dex_program_id: Pubkey,
+
+        #[clap(long)]
+        payer: KeypairPath,
+
+        /// Owner of the open orders accounts. Defaults to the payer.
+        #[clap(long)]
+        owner: Option<KeypairPath>,
+
+        /// `<market>,<coin wallet>,<pc wallet>`, one per market.
         #[clap(long, short)]
-        coin_mint: Pubkey,
+        markets: Vec<SettleAccounts>,
+
+        /// Free coin, in native units, above which an account is settled.
+        #[clap(long)]
+        min_coin_free: Option<u64>,
+
+        /// Free pc, in native units, above which an account is settled.
+        #[clap(long)]
+        min_pc_free: Option<u64>,
+
+        /// Keeps settling at this interval instead of exiting.
+        #[clap(long)]
+        interval_secs: Option<u64>,
+    },
+    /// Settles the free balances of all of a wallet's open orders
+    /// accounts, on every market.
+    SettleAll {
         #[clap(long, short)]
-        pc_mint: Pubkey,
+        dex_program_id: Pubkey,
+
         #[clap(long)]
-        coin_lot_size: Option<u64>,
+        payer: KeypairPath,
+
+        /// Owner of the open orders accounts, which must sign. Defaults to
+        /// the payer.
         #[clap(long)]
-        pc_lot_size: Option<u64>,
+        owner: Option<KeypairPath>,
     },
-    InitializeTokenAccount {
-        mint: Pubkey,
-        owner_account: String,
+    /// Prints the fills consumed on a market between two slots as CSV.
+    FillHistory {
+        #[clap(long, short)]
+        dex_program_id: Pubkey,
+
+        market: Pubkey,
+
+        start_slot: u64,
+
+        end_slot: u64,
+
+        /// Only prints the fills of this open orders account.
+        #[clap(long, short)]
+        orders: Option<Pubkey>,
+    },
+    /// Prints every open orders account of a wallet, on any market, with
+    /// its resting orders, unsettled balances and rent.
+    Portfolio {
+        #[clap(long, short)]
+        dex_program_id: Pubkey,
+
+        owner: Pubkey,
+    },
+    /// Cranks every market listed in a config file until killed.
+    Daemon {
+        /// TOML file with one `[[markets]]` table per market.
+        #[clap(long, short)]
+        config: String,
     },
 }
 
+/// Open orders account owned by the crank operator, along with the wallets
+/// its free balances are settled into.
+#[derive(Debug, Clone)]
+pub struct RebateAccounts {
+    pub orders: Pubkey,
+    pub coin_wallet: Pubkey,
+    pub pc_wallet: Pubkey,
+}
+
+impl FromStr for RebateAccounts {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match parse_pubkeys(s)?.as_slice() {
+            &[orders, coin_wallet, pc_wallet] => Ok(RebateAccounts {
+                orders,
+                coin_wallet,
+                pc_wallet,
+            }),
+            _ => Err(format_err!(
+                "expected <open orders>,<coin wallet>,<pc wallet>, got {}",
+                s
+            )),
+        }
+    }
+}
+
+/// Market whose open orders accounts are settled, along with the wallets
+/// their free balances are settled into.
+#[derive(Debug, Clone)]
+pub struct SettleAccounts {
+    pub market: Pubkey,
+    pub coin_wallet: Pubkey,
+    pub pc_wallet: Pubkey,
+}
+
+impl FromStr for SettleAccounts {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match parse_pubkeys(s)?.as_slice() {
+            &[market, coin_wallet, pc_wallet] => Ok(SettleAccounts {
+                market,
+                coin_wallet,
+                pc_wallet,
+            }),
+            _ => Err(format_err!(
+                "expected <market>,<coin wallet>,<pc wallet>, got {}",
+                s
+            )),
+        }
+    }
+}
+
+fn parse_pubkeys(s: &str) -> Result<Vec<Pubkey>> {
+    s.split(',')
+        .map(|key| Pubkey::from_str(key.trim()).map_err(|e| format_err!("{}: {:?}", key, e)))
+        .collect()
+}
+
 impl Opts {
     fn client(&self) -> RpcClient {
         RpcClient::new(self.cluster.url().to_string())
     }
+
+    fn rpc_endpoints(&self) -> RpcEndpoints {
+        let mut endpoints = RpcEndpoints::new(self.cluster.clone());
+        endpoints.fallback_urls = self.fallback_rpc_url.clone();
+        endpoints.failover.requests_per_second = self.rpc_requests_per_second;
+        endpoints
+    }
+
+    fn compute_budget(&self) -> ComputeBudget {
+        ComputeBudget {
+            unit_limit: self.compute_unit_limit,
+            unit_price: self.priority_fee_micro_lamports,
+            rpc_url: self.cluster.url().to_string(),
+        }
+    }
 }
 
 pub fn start(opts: Opts) -> Result<()> {
+    set_dry_run(opts.dry_run);
+    let mut send_policy = SendPolicy {
+        nonce_account: opts.nonce_account,
+        lookup_tables: opts.lookup_table.clone(),
+        max_retries: opts.max_retries.unwrap_or(0),
+        ..SendPolicy::default()
+    };
+    if let Some(ms) = opts.retry_backoff_ms {
+        send_policy.initial_backoff = time::Duration::from_millis(ms);
+    }
+    set_send_policy(send_policy);
     let client = opts.client();
+    let compute_budget = opts.compute_budget();
+    let metrics = Metrics::default();
+    if let Some(addr) = opts.metrics_addr {
+        metrics.serve(addr)?;
+    }
 
     match opts.command {
         Command::Genesis {
@@ -207,9 +512,9 @@ pub fn start(opts: Opts) -> Result<()> {
             owner_pubkey,
             decimals,
         } => {
-            let payer = read_keypair_file(&payer)?;
-            let mint = read_keypair_file(&mint)?;
-            create_and_init_mint(&client, &payer, &mint, &owner_pubkey, decimals)?;
+            let payer = payer.read()?;
+            let mint = mint.read()?;
+            create_and_init_mint(&client, &payer, &mint, &owner_pubkey.pubkey(), decimals)?;
         }
         Command::Mint {
             payer,
@@ -218,8 +523,9 @@ pub fn start(opts: Opts) -> Result<()> {
             recipient,
             quantity,
         } => {
-            let payer = read_keypair_file(&payer)?;
-            let minter = read_keypair_file(&signer)?;
+            let payer = payer.read()?;
+            let minter = signer.read()?;
+            let quantity = quantity.to_native_for_mint(&client, &mint_pubkey)?;
             match recipient.as_ref() {
                 Some(recipient) => {
                     mint_to_existing_account(
@@ -244,7 +550,7 @@ pub fn start(opts: Opts) -> Result<()> {
             ref coin_wallet,
             ref pc_wallet,
         } => {
-            let payer = read_keypair_file(&payer)?;
+            let payer = payer.read()?;
 
             debug_println!("Getting market keys ...");
             let market_keys = get_keys_for_market(&client, dex_program_id, &market)?;
@@ -261,6 +567,7 @@ pub fn start(opts: Opts) -> Result<()> {
         Command::ConsumeEvents {
             ref dex_program_id,
             ref payer,
+            ref worker_payer,
             ref market,
             ref coin_wallet,
             ref pc_wallet,
@@ -268,31 +575,84 @@ pub fn start(opts: Opts) -> Result<()> {
             events_per_worker,
             ref num_accounts,
             ref log_directory,
+            max_in_flight,
+            target_confirmation_ms,
+            websocket,
         } => {
+            let in_flight = InFlight::new(
+                max_in_flight.unwrap_or(num_workers),
+                time::Duration::from_millis(target_confirmation_ms.unwrap_or(5_000)),
+            );
+            let mut payers = vec![payer.clone()];
+            payers.extend(worker_payer.iter().cloned());
+            init_file_logging(log_directory);
             consume_events_loop(
-                &opts,
+                &opts.rpc_endpoints(),
                 &dex_program_id,
-                &payer,
+                &payers,
                 &market,
                 &coin_wallet,
                 &pc_wallet,
                 num_workers,
                 events_per_worker,
                 num_accounts.unwrap_or(32),
-                log_directory,
+                time::Duration::from_millis(300),
+                websocket,
+                in_flight,
+                &compute_budget,
+                &metrics,
             )
             .unwrap();
         }
         Command::MonitorQueue {
             dex_program_id,
-            market,
+            ref market,
             port,
+            threshold,
+            ref webhook,
+            interval_secs,
+        } => match (port, threshold) {
+            (Some(port), None) => {
+                let market = match market.as_slice() {
+                    &[market] => market,
+                    _ => return Err(format_err!("--port takes exactly one market")),
+                };
+                let client = opts.client();
+                let mut runtime = tokio::runtime::Runtime::new().unwrap();
+                runtime
+                    .block_on(read_queue_length_loop(client, dex_program_id, market, port))
+                    .unwrap();
+            }
+            (None, Some(threshold)) => {
+                monitor::watch(
+                    &client,
+                    &dex_program_id,
+                    market,
+                    threshold,
+                    webhook.as_deref(),
+                    interval_secs.map(time::Duration::from_secs),
+                )?;
+            }
+            _ => return Err(format_err!("expected one of --port or --threshold")),
+        },
+        Command::SnapshotOrderbook {
+            ref dex_program_id,
+            ref market,
+            ref output,
+            format,
         } => {
-            let client = opts.client();
-            let mut runtime = tokio::runtime::Runtime::new().unwrap();
-            runtime
-                .block_on(read_queue_length_loop(client, dex_program_id, market, port))
-                .unwrap();
+            let format = format.unwrap_or_else(|| match output {
+                Some(path) if path.ends_with(".csv") => snapshot::Format::Csv,
+                _ => snapshot::Format::Json,
+            });
+            let book = snapshot::snapshot(&client, dex_program_id, market)?;
+            match output {
+                Some(path) => {
+                    let mut file = std::fs::File::create(path)?;
+                    snapshot::write(&book, format, &mut file)?;
+                }
+                None => snapshot::write(&book, format, &mut std::io::stdout())?,
+            }
         }
         Command::PrintEventQueue {
             ref dex_program_id,
@@ -310,7 +670,7 @@ pub fn start(opts: Opts) -> Result<()> {
             ref dex_program_id,
             ref payer,
         } => {
-            let payer = read_keypair_file(payer)?;
+            let payer = payer.read()?;
             whole_shebang(&client, dex_program_id, &payer)?;
         }
         Command::SettleFunds {
@@ -322,8 +682,8 @@ pub fn start(opts: Opts) -> Result<()> {
             ref pc_wallet,
             ref signer,
         } => {
-            let payer = read_keypair_file(payer)?;
-            let signer = signer.as_ref().map(|s| read_keypair_file(&s)).transpose()?;
+            let payer = payer.read()?;
+            let signer = signer.as_ref().map(KeypairPath::read).transpose()?;
             let market_keys = get_keys_for_market(&client, dex_program_id, &market)?;
             settle_funds(
                 &client,
@@ -334,54 +694,169 @@ pub fn start(opts: Opts) -> Result<()> {
                 orders,
                 coin_wallet,
                 pc_wallet,
+                None,
+                &compute_budget,
             )?;
         }
-        Command::ListMarket {
-            ref payer,
-            ref dex_program_id,
-            ref coin_mint,
-            ref pc_mint,
-            coin_lot_size,
-            pc_lot_size,
-        } => {
-            let payer = read_keypair_file(payer)?;
-            let market_keys = list_market(
-                &client,
-                dex_program_id,
-                &payer,
-                coin_mint,
-                pc_mint,
-                coin_lot_size.unwrap_or(1_000_000),
-                pc_lot_size.unwrap_or(10_000),
-            )?;
+        Command::ListMarket(ref args) => {
+            let market_keys = args.run(&client)?;
             println!("Listed market: {:#?}", market_keys);
         }
         Command::InitializeTokenAccount {
             ref mint,
             ref owner_account,
         } => {
-            let owner = read_keypair_file(owner_account)?;
+            let owner = owner_account.read()?;
             let initialized_account = initialize_token_account(&client, mint, &owner)?;
             debug_println!("Initialized account: {}", initialized_account.pubkey());
         }
+        Command::SettleReferrerRebates {
+            ref dex_program_id,
+            ref payer,
+            ref referrer_pc_wallet,
+            ref referrer_wallets,
+            ref orders,
+            interval_secs,
+        } => {
+            let payer = payer.read()?;
+            settle_referrer_rebates_loop(
+                &client,
+                dex_program_id,
+                &payer,
+                referrer_pc_wallet,
+                referrer_wallets,
+                orders,
+                time::Duration::from_secs(interval_secs.unwrap_or(600)),
+                &compute_budget,
+            )?;
+        }
+        Command::Settle {
+            ref dex_program_id,
+            ref payer,
+            ref owner,
+            ref markets,
+            min_coin_free,
+            min_pc_free,
+            interval_secs,
+        } => {
+            let payer = payer.read()?;
+            let owner = owner.as_ref().map(KeypairPath::read).transpose()?;
+            let thresholds = (min_coin_free.unwrap_or(1), min_pc_free.unwrap_or(1));
+            let session = opts.rpc_endpoints().session();
+            loop {
+                for accounts in markets {
+                    if let Err(err) = settle_open_orders(
+                        &session,
+                        opts.cluster.url(),
+                        dex_program_id,
+                        &payer,
+                        owner.as_ref(),
+                        accounts,
+                        thresholds,
+                        &compute_budget,
+                    ) {
+                        eprintln!("Failed to settle market {}: {:?}", accounts.market, err);
+                    }
+                }
+                match interval_secs {
+                    Some(secs) => thread::sleep(time::Duration::from_secs(secs)),
+                    None => break,
+                }
+            }
+        }
+        Command::SettleAll {
+            ref dex_program_id,
+            ref payer,
+            ref owner,
+        } => {
+            let payer = payer.read()?;
+            let owner = owner.as_ref().map(KeypairPath::read).transpose()?;
+            settle_all(
+                &opts.rpc_endpoints().session(),
+                opts.cluster.url(),
+                dex_program_id,
+                &payer,
+                owner.as_ref(),
+                &compute_budget,
+            )?;
+        }
+        Command::FillHistory {
+            ref dex_program_id,
+            ref market,
+            start_slot,
+            end_slot,
+            ref orders,
+        } => {
+            let history = fills::fill_history(
+                &client,
+                opts.cluster.url(),
+                dex_program_id,
+                market,
+                start_slot,
+                end_slot,
+            )?;
+            println!(
+                "orders,slot,signature,side,maker,price,coin,pc,fee_or_rebate,client_order_id"
+            );
+            for (owner, owner_fills) in &history {
+                if orders.map_or(false, |orders| orders != *owner) {
+                    continue;
+                }
+                for h in owner_fills {
+                    println!(
+                        "{},{},{},{:?},{},{},{},{},{},{}",
+                        owner,
+                        h.slot,
+                        h.signature,
+                        h.fill.side,
+                        h.fill.maker,
+                        h.fill.price_lots(),
+                        h.fill.native_coin(),
+                        h.fill.native_pc(),
+                        h.fill.native_fee_or_rebate,
+                        h.fill.client_order_id.map_or(0, NonZeroU64::get)
+                    );
+                }
+            }
+        }
+        Command::Portfolio {
+            ref dex_program_id,
+            ref owner,
+        } => {
+            let portfolio =
+                portfolio::portfolio(&client, opts.cluster.url(), dex_program_id, owner)?;
+            serde_json::to_writer_pretty(std::io::stdout(), &portfolio)?;
+            println!();
+            let unsettled = portfolio.unsettled().count();
+            if unsettled > 0 {
+                eprintln!(
+                    "{} account(s) have no resting orders but funds left to settle",
+                    unsettled
+                );
+            }
+        }
+        Command::Daemon { ref config } => {
+            let config = daemon::Config::load(config)?;
+            daemon::run(&opts.rpc_endpoints(), config, &compute_budget, &metrics)?;
+        }
     }
     Ok(())
 }
 
 #[derive(Debug)]
-struct MarketPubkeys {
-    market: Box<Pubkey>,
-    req_q: Box<Pubkey>,
-    event_q: Box<Pubkey>,
-    bids: Box<Pubkey>,
-    asks: Box<Pubkey>,
-    coin_vault: Box<Pubkey>,
-    pc_vault: Box<Pubkey>,
-    vault_signer_key: Box<Pubkey>,
+pub struct MarketPubkeys {
+    pub market: Box<Pubkey>,
+    pub req_q: Box<Pubkey>,
+    pub event_q: Box<Pubkey>,
+    pub bids: Box<Pubkey>,
+    pub asks: Box<Pubkey>,
+    pub coin_vault: Box<Pubkey>,
+    pub pc_vault: Box<Pubkey>,
+    pub vault_signer_key: Box<Pubkey>,
 }
 
 #[cfg(target_endian = "little")]
-fn remove_dex_account_padding<'a>(data: &'a [u8]) -> Result<Cow<'a, [u64]>> {
+pub fn remove_dex_account_padding<'a>(data: &'a [u8]) -> Result<Cow<'a, [u64]>> {
     use serum_dex::state::{ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING};
     let head = &data[..ACCOUNT_HEAD_PADDING.len()];
     if data.len() < ACCOUNT_HEAD_PADDING.len() + ACCOUNT_TAIL_PADDING.len() {
@@ -399,64 +874,65 @@ fn remove_dex_account_padding<'a>(data: &'a [u8]) -> Result<Cow<'a, [u64]>> {
     }
     let inner_data_range = ACCOUNT_HEAD_PADDING.len()..(data.len() - ACCOUNT_TAIL_PADDING.len());
     let inner: &'a [u8] = &data[inner_data_range];
-    let words: Cow<'a, [u64]> = match transmute_many_pedantic::<u64>(inner) {
+    if inner.len() % size_of::<u64>() != 0 {
+        return Err(format_err!(
+            "dex account data is not a whole number of words"
+        ));
+    }
+    let words: Cow<'a, [u64]> = match try_cast_slice::<u8, u64>(inner) {
         Ok(word_slice) => Cow::Borrowed(word_slice),
-        Err(transmute_error) => {
-            let word_vec = transmute_error.copy().map_err(|e| e.without_src())?;
+        Err(_) => {
+            let mut word_vec = vec![0u64; inner.len() / size_of::<u64>()];
+            cast_slice_mut(&mut word_vec).copy_from_slice(inner);
             Cow::Owned(word_vec)
         }
     };
     Ok(words)
 }
 
+pub fn read_pod<T: Pod>(bytes: &[u8]) -> Result<T> {
+    try_from_bytes::<T>(bytes)
+        .map(|value| *value)
+        .map_err(|e| format_err!("failed to read account data: {:?}", e))
+}
+
+pub fn read_pod_slice<T: Pod>(bytes: &[u8]) -> Result<&[T]> {
+    let len = bytes.len() - bytes.len() % size_of::<T>();
+    try_cast_slice(&bytes[..len]).map_err(|e| format_err!("failed to read account data: {:?}", e))
+}
+
 #[cfg(target_endian = "little")]
-fn get_keys_for_market<'a>(
+pub fn get_keys_for_market<'a>(
     client: &'a RpcClient,
     program_id: &'a Pubkey,
     market: &'a Pubkey,
 ) -> Result<MarketPubkeys> {
     let account_data: Vec<u8> = client.get_account_data(&market)?;
     let words: Cow<[u64]> = remove_dex_account_padding(&account_data)?;
-    let market_state: MarketState =
-        transmute_one_pedantic::<MarketState>(transmute_to_bytes(&words))
-            .map_err(|e| e.without_src())?;
+    let market_state: MarketState = read_pod(cast_slice(&words))?;
     market_state.check_flags()?;
     let vault_signer_key =
         gen_vault_signer_key(market_state.vault_signer_nonce, market, program_id)?;
     assert_eq!(
-        transmute_to_bytes(&identity(market_state.own_address)),
+        bytes_of(&identity(market_state.own_address)),
         market.as_ref()
     );
     Ok(MarketPubkeys {
         market: Box::new(*market),
-        req_q: Box::new(Pubkey::new(transmute_one_to_bytes(&identity(
-            market_state.req_q,
-        )))),
-        event_q: Box::new(Pubkey::new(transmute_one_to_bytes(&identity(
-            market_state.event_q,
-        )))),
-        bids: Box::new(Pubkey::new(transmute_one_to_bytes(&identity(
-            market_state.bids,
-        )))),
-        asks: Box::new(Pubkey::new(transmute_one_to_bytes(&identity(
-            market_state.asks,
-        )))),
-        coin_vault: Box::new(Pubkey::new(transmute_one_to_bytes(&identity(
-            market_state.coin_vault,
-        )))),
-        pc_vault: Box::new(Pubkey::new(transmute_one_to_bytes(&identity(
-            market_state.pc_vault,
-        )))),
+        req_q: Box::new(Pubkey::new(bytes_of(&identity(market_state.req_q)))),
+        event_q: Box::new(Pubkey::new(bytes_of(&identity(market_state.event_q)))),
+        bids: Box::new(Pubkey::new(bytes_of(&identity(market_state.bids)))),
+        asks: Box::new(Pubkey::new(bytes_of(&identity(market_state.asks)))),
+        coin_vault: Box::new(Pubkey::new(bytes_of(&identity(market_state.coin_vault)))),
+        pc_vault: Box::new(Pubkey::new(bytes_of(&identity(market_state.pc_vault)))),
         vault_signer_key: Box::new(vault_signer_key),
     })
 }
 
-fn parse_event_queue(data_words: &[u64]) -> Result<(EventQueueHeader, &[Event], &[Event])> {
+pub fn parse_event_queue(data_words: &[u64]) -> Result<(EventQueueHeader, &[Event], &[Event])> {
     let (header_words, event_words) = data_words.split_at(size_of::<EventQueueHeader>() >> 3);
-    let header: EventQueueHeader =
-        transmute_one_pedantic(transmute_to_bytes(header_words)).map_err(|e| e.without_src())?;
-    let events: &[Event] = transmute_many::<_, SingleManyGuard>(transmute_to_bytes(event_words))
-        .map_err(|e| e.without_src())?;
+    let header: EventQueueHeader = read_pod(cast_slice(header_words))?;
+    let events: &[Event] = read_pod_slice(cast_slice(event_words))?;
     let (tail_seg, head_seg) = events.split_at(header.head() as usize);
     let head_len = head_seg.len().min(header.count() as usize);
     let tail_len = header.count() as usize - head_len;
@@ -465,11 +941,8 @@ fn parse_event_queue(data_words: &[u64]) -> Result<(EventQueueHeader, &[Event],
 
 fn parse_req_queue(data_words: &[u64]) -> Result<(RequestQueueHeader, &[Request], &[Request])> {
     let (header_words, request_words) = data_words.split_at(size_of::<RequestQueueHeader>() >> 3);
-    let header: RequestQueueHeader =
-        transmute_one_pedantic(transmute_to_bytes(header_words)).map_err(|e| e.without_src())?;
-    let request: &[Request] =
-        transmute_many::<_, SingleManyGuard>(transmute_to_bytes(request_words))
-            .map_err(|e| e.without_src())?;
+    let header: RequestQueueHeader = read_pod(cast_slice(header_words))?;
+    let request: &[Request] = read_pod_slice(cast_slice(request_words))?;
     let (tail_seg, head_seg) = request.split_at(header.head() as usize);
     let head_len = head_seg.len().min(header.count() as usize);
     let tail_len = header.count() as usize - head_len;
@@ -480,18 +953,8 @@ fn hash_accounts(val: &[u64; 4]) -> u64 {
     val.iter().fold(0, |a, b| b.wrapping_add(a))
 }
 
-fn consume_events_loop(
-    opts: &Opts,
-    program_id: &Pubkey,
-    payer_path: &String,
-    market: &Pubkey,
-    coin_wallet: &Pubkey,
-    pc_wallet: &Pubkey,
-    num_workers: usize,
-    events_per_worker: usize,
-    num_accounts: usize,
-    log_directory: &str,
-) -> Result<()> {
+/// Sends the `log` records of every crank task to a rotating file.
+fn init_file_logging(log_directory: &str) {
     let path = std::path::Path::new(log_directory);
     let parent = path.parent().unwrap();
     std::fs::create_dir_all(parent).unwrap();
@@ -501,14 +964,70 @@ fn consume_events_loop(
     let _guard = slog_scope::set_global_logger(log);
     _guard.cancel_reset();
     slog_stdlog::init().unwrap();
+}
 
+fn consume_events_loop(
+    rpc: &RpcEndpoints,
+    program_id: &Pubkey,
+    payers: &[KeypairPath],
+    market: &Pubkey,
+    coin_wallet: &Pubkey,
+    pc_wallet: &Pubkey,
+    num_workers: usize,
+    events_per_worker: usize,
+    num_accounts: usize,
+    poll_interval: time::Duration,
+    websocket: bool,
+    in_flight: InFlight,
+    compute_budget: &ComputeBudget,
+    metrics: &Metrics,
+) -> Result<()> {
     info!("Getting market keys ...");
-    let client = opts.client();
-    let market_keys = get_keys_for_market(&client, &program_id, &market)?;
+    // Shared by the workers, so they reuse one recent blockhash.
+    let session = rpc.session();
+    let client = session.client();
+    let market_keys = get_keys_for_market(client, &program_id, &market)?;
     info!("{:#?}", market_keys);
+    let watcher = if websocket {
+        Some(QueueWatcher::spawn(
+            rpc.cluster.url(),
+            *market_keys.event_q,
+        )?)
+    } else {
+        None
+    };
     let pool = threadpool::ThreadPool::new(num_workers);
+    let in_flight = Arc::new(Mutex::new(in_flight));
+    // Rotates through the payers across loops, not just within one.
+    let mut next_payer = 0;
+    // Whether the last read found the event queue empty.
+    let mut queue_empty = false;
     loop {
-        thread::sleep(time::Duration::from_millis(300));
+        let idle = queue_empty && in_flight.lock().unwrap().pending() == 0;
+        wait_for_events(watcher.as_ref(), idle, poll_interval, WEBSOCKET_RESYNC);
+
+        // Unconfirmed transactions may already cover the queued events, so
+        // resending while they are pending would mostly pay for duplicates.
+        let available = {
+            let mut in_flight = in_flight.lock().unwrap();
+            match in_flight.poll(client) {
+                Ok(finished) => metrics.record_finished(market, &finished),
+                Err(e) => {
+                    error!("Failed to fetch signature statuses: {:?}", e);
+                    continue;
+                }
+            }
+            if in_flight.available() == 0 {
+                info!(
+                    "{} transactions in flight, limit {}, market {}",
+                    in_flight.pending(),
+                    in_flight.limit(),
+                    market
+                );
+                continue;
+            }
+            in_flight.available()
+        };
 
         let loop_start = std::time::Instant::now();
         let start_time = std::time::Instant::now();
@@ -523,7 +1042,8 @@ fn consume_events_loop(
             .expect("Failed to retrieve account")
             .data;
         let inner: Cow<[u64]> = remove_dex_account_padding(&event_q_data)?;
-        let (_header, seg0, seg1) = parse_event_queue(&inner)?;
+        let (header, seg0, seg1) = parse_event_queue(&inner)?;
+        metrics.record_queue(market, header.seq_num(), header.count());
         let req_inner: Cow<[u64]> = remove_dex_account_padding(&req_q_data)?;
         let (_req_header, req_seg0, req_seg1) = parse_event_queue(&req_inner)?;
         let event_q_len = seg0.len() + seg1.len();
@@ -533,6 +1053,7 @@ fn consume_events_loop(
             req_q_len, market, coin_wallet, pc_wallet
         );
 
+        queue_empty = event_q_len == 0;
         if event_q_len == 0 {
             continue;
         } else {
@@ -540,7 +1061,12 @@ fn consume_events_loop(
                 "Total event queue length: {}, market {}, coin {}, pc {}",
                 event_q_len, market, coin_wallet, pc_wallet
             );
-            let accounts = seg0.iter().chain(seg1.iter()).map(|event| event.owner);
+            // Events consumed out of order only wait for the ones ahead.
+            let accounts = seg0
+                .iter()
+                .chain(seg1.iter())
+                .filter(|event| !event.is_consumed())
+                .map(|event| event.owner);
             let mut used_accounts = BTreeSet::new();
             for account in accounts {
                 used_accounts.insert(account);
@@ -567,7 +1093,7 @@ fn consume_events_loop(
 
             let mut account_metas = Vec::with_capacity(orders_accounts.len() + 4);
             for pubkey_words in orders_accounts {
-                let pubkey = Pubkey::new(transmute_to_bytes(&pubkey_words));
+                let pubkey = Pubkey::new(bytes_of(&pubkey_words));
                 account_metas.push(AccountMeta::new(pubkey, false));
             }
             for pubkey in [
@@ -587,20 +1113,30 @@ fn consume_events_loop(
                 event_q_len,
                 end_time.duration_since(start_time).as_millis()
             );
-            for thread_num in 0..min(num_workers, 2 * event_q_len / events_per_worker + 1) {
-                let payer = read_keypair_file(&payer_path)?;
+            let num_txs = min(num_workers, 2 * event_q_len / events_per_worker + 1);
+            for thread_num in 0..min(num_txs, available) {
+                let payer = payers[next_payer % payers.len()].read()?;
+                next_payer += 1;
                 let program_id = program_id.clone();
-                let client = opts.client();
+                let session = session.clone();
                 let account_metas = account_metas.clone();
+                let in_flight = in_flight.clone();
+                let market = *market;
+                let compute_budget = compute_budget.clone();
+                let metrics = metrics.clone();
 
                 pool.execute(move || {
                     consume_events_wrapper(
-                        &client,
+                        &session,
                         &program_id,
                         &payer,
                         account_metas,
                         thread_num,
                         events_per_worker,
+                        &in_flight,
+                        &compute_budget,
+                        &market,
+                        &metrics,
                     )
                 });
             }
@@ -615,43 +1151,57 @@ fn consume_events_loop(
 }
 
 fn consume_events_wrapper(
-    client: &RpcClient,
+    session: &RpcSession,
     program_id: &Pubkey,
     payer: &Keypair,
     account_metas: Vec<AccountMeta>,
     thread_num: usize,
     to_consume: usize,
+    in_flight: &Mutex<InFlight>,
+    compute_budget: &ComputeBudget,
+    market: &Pubkey,
+    metrics: &Metrics,
 ) {
     let start = std::time::Instant::now();
     let result = consume_events_once(
-        &client,
+        session,
         program_id,
         &payer,
         account_metas,
         to_consume,
         thread_num,
+        compute_budget,
     );
     match result {
-        Ok(signature) => info!(
-            "[thread {}] Successfully consumed events after {:?}: {}.",
-            thread_num,
-            start.elapsed(),
-            signature
-        ),
+        Ok((signature, fee)) => {
+            // Simulated transactions never land, so waiting on them would
+            // only stall the loop.
+            if !is_dry_run() {
+                in_flight.lock().unwrap().track(signature, fee);
+            }
+            info!(
+                "[thread {}] Successfully consumed events after {:?}: {}.",
+                thread_num,
+                start.elapsed(),
+                signature
+            )
+        }
         Err(err) => {
+            metrics.record_send_error(market);
             error!("[thread {}] Received error: {:?}", thread_num, err);
         }
     };
 }
 
 fn consume_events_once(
-    client: &RpcClient,
+    session: &RpcSession,
     program_id: &Pubkey,
     payer: &Keypair,
     account_metas: Vec<AccountMeta>,
     to_consume: usize,
     _thread_number: usize,
-) -> Result<Signature> {
+    compute_budget: &ComputeBudget,
+) -> Result<(Signature, u64)> {
     let _start = std::time::Instant::now();
     let instruction_data: Vec<u8> = MarketInstruction::ConsumeEvents(to_consume as u16).pack();
 
@@ -665,23 +1215,34 @@ fn consume_events_once(
         &payer.pubkey(),
         rand::random::<u64>() % 10000 + 1,
     );
-    let (recent_hash, _fee_calc) = client.get_recent_blockhash()?;
+    let mut instructions = vec![instruction, random_instruction];
+    let priority_fee = compute_budget.prepend(&mut instructions)?;
+    // The random transfer keeps transactions sharing the cached blockhash
+    // distinct.
+    let (recent_hash, fee_calc) = session.recent_blockhash()?;
     let txn = Transaction::new_signed_with_payer(
-        &[instruction, random_instruction],
+        &instructions,
         Some(&payer.pubkey()),
         &[payer],
         recent_hash,
     );
 
+    if is_dry_run() {
+        print_simulation(session.client(), &txn)?;
+        return Ok((txn.signatures[0], 0));
+    }
     info!("Consuming events ...");
-    let signature = client.send_transaction_with_config(
-        &txn,
-        RpcSendTransactionConfig {
-            skip_preflight: true,
-            ..RpcSendTransactionConfig::default()
-        },
-    )?;
-    Ok(signature)
+    let signature = session.retry(|client| {
+        client.send_transaction_with_config(
+            &txn,
+            RpcSendTransactionConfig {
+                skip_preflight: true,
+                ..RpcSendTransactionConfig::default()
+            },
+        )
+    })?;
+    // The payer is the only signer.
+    Ok((signature, fee_calc.lamports_per_signature + priority_fee))
 }
 
 #[cfg(target_endian = "little")]
@@ -703,7 +1264,11 @@ fn consume_events(
     } else {
         info!("Total event queue length: {}", seg0.len() + seg1.len());
     }
-    let accounts = seg0.iter().chain(seg1.iter()).map(|event| event.owner);
+    let accounts = seg0
+        .iter()
+        .chain(seg1.iter())
+        .filter(|event| !event.is_consumed())
+        .map(|event| event.owner);
     let mut orders_accounts: Vec<_> = accounts.collect();
     orders_accounts.sort_unstable();
     orders_accounts.dedup();
@@ -713,7 +1278,7 @@ fn consume_events(
 
     let mut account_metas = Vec::with_capacity(orders_accounts.len() + 4);
     for pubkey_words in orders_accounts {
-        let pubkey = Pubkey::new(transmute_to_bytes(&pubkey_words));
+        let pubkey = Pubkey::new(bytes_of(&pubkey_words));
         account_metas.push(AccountMeta::new(pubkey, false));
     }
     for pubkey in [&state.market, &state.event_q, coin_wallet, pc_wallet].iter() {
@@ -729,16 +1294,13 @@ fn consume_events(
         data: instruction_data,
     };
 
-    let (recent_hash, _fee_calc) = client.get_recent_blockhash()?;
     info!("Consuming events ...");
-    let txn = Transaction::new_signed_with_payer(
+    send_instructions(
+        client,
         std::slice::from_ref(&instruction),
-        Some(&payer.pubkey()),
+        &payer.pubkey(),
         &[payer],
-        recent_hash,
-    );
-    info!("Consuming events ...");
-    send_txn(client, &txn, false)?;
+    )?;
     Ok(())
 }
 
@@ -757,8 +1319,7 @@ fn whole_shebang(client: &RpcClient, program_id: &Pubkey, payer: &Keypair) -> Re
         payer,
         &coin_mint.pubkey(),
         &pc_mint.pubkey(),
-        1_000_000,
-        10_000,
+        &ListingParams::default(),
     )?;
     debug_println!("Market keys: {:#?}", market_keys);
 
@@ -822,16 +1383,6 @@ fn whole_shebang(client: &RpcClient, program_id: &Pubkey, payer: &Keypair) -> Re
 
     debug_println!("Ask account: {}", orders.unwrap());
 
-    debug_println!("Matching orders in 15s ...");
-    std::thread::sleep(std::time::Duration::new(15, 0));
-    match_orders(
-        client,
-        program_id,
-        payer,
-        &market_keys,
-        &coin_wallet.pubkey(),
-        &pc_wallet.pubkey(),
-    )?;
     debug_println!("Consuming events in 15s ...");
     std::thread::sleep(std::time::Duration::new(15, 0));
     consume_events(
@@ -851,6 +1402,8 @@ fn whole_shebang(client: &RpcClient, program_id: &Pubkey, payer: &Keypair) -> Re
         &orders.unwrap(),
         &coin_wallet.pubkey(),
         &pc_wallet.pubkey(),
+        None,
+        &ComputeBudget::default(),
     )?;
     Ok(())
 }
@@ -884,70 +1437,423 @@ fn place_order(
         }
     };
     *orders = Some(orders_pubkey);
-    let _side = new_order.side;
-    let data = MarketInstruction::NewOrder(new_order).pack();
-    let instruction = Instruction {
-        program_id: *program_id,
-        data,
-        accounts: vec![
-            AccountMeta::new(*state.market, false),
-            AccountMeta::new(orders_pubkey, false),
-            AccountMeta::new(*state.req_q, false),
-            AccountMeta::new(*wallet, false),
-            AccountMeta::new(payer.pubkey(), true),
-            AccountMeta::new(*state.coin_vault, false),
-            AccountMeta::new(*state.pc_vault, false),
-            AccountMeta::new(spl_token::ID, false),
-            AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
-        ],
-    };
-    instructions.push(instruction);
+    instructions.push(new_order_v3_instruction(
+        program_id,
+        state,
+        &orders_pubkey,
+        wallet,
+        &payer.pubkey(),
+        new_order.add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+    ));
     signers.push(payer);
 
-    let (recent_hash, _fee_calc) = client.get_recent_blockhash()?;
-    let txn = Transaction::new_signed_with_payer(
-        &instructions,
-        Some(&payer.pubkey()),
-        &signers,
-        recent_hash,
-    );
-    send_txn(client, &txn, false)?;
+    send_instructions(client, &instructions, &payer.pubkey(), &signers)?;
     Ok(())
 }
 
-fn settle_funds(
+/// Places an order paid for with `lamports` of the payer's native SOL,
+/// which is wrapped into a temporary account for the order and unwrapped
+/// again in the same transaction.
+fn place_order_with_native_sol(
     client: &RpcClient,
     program_id: &Pubkey,
     payer: &Keypair,
+    lamports: u64,
     state: &MarketPubkeys,
-    signer: Option<&Keypair>,
-    orders: &Pubkey,
-    coin_wallet: &Pubkey,
-    pc_wallet: &Pubkey,
+    orders: &mut Option<Pubkey>,
+
+    new_order: NewOrderInstructionV1,
 ) -> Result<()> {
-    let data = MarketInstruction::SettleFunds.pack();
-    let instruction = Instruction {
+    let wsol = TempWsolAccount::new(client, lamports)?;
+    let mut instructions = wsol.open_instructions(&payer.pubkey(), &payer.pubkey())?;
+    let orders_keypair;
+    let mut signers = vec![payer, wsol.keypair()];
+    let orders_pubkey = match *orders {
+        Some(pk) => pk,
+        None => {
+            let (orders_key, instruction) = create_dex_account(
+                client,
+                program_id,
+                &payer.pubkey(),
+                size_of::<serum_dex::state::OpenOrders>(),
+            )?;
+            orders_keypair = orders_key;
+            signers.push(&orders_keypair);
+            instructions.push(instruction);
+            orders_keypair.pubkey()
+        }
+    };
+    *orders = Some(orders_pubkey);
+    instructions.push(new_order_v3_instruction(
+        program_id,
+        state,
+        &orders_pubkey,
+        &wsol.pubkey(),
+        &payer.pubkey(),
+        new_order.add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+    ));
+    instructions.push(wsol.close_instruction(&payer.pubkey())?);
+
+    send_instructions(client, &instructions, &payer.pubkey(), &signers)?;
+    Ok(())
+}
+
+/// Builds a `NewOrderV3` instruction, which matches against the book
+/// directly instead of waiting for the crank.
+pub fn new_order_v3_instruction(
+    program_id: &Pubkey,
+    state: &MarketPubkeys,
+    orders: &Pubkey,
+    wallet: &Pubkey,
+    owner: &Pubkey,
+    new_order: NewOrderInstructionV2,
+) -> Instruction {
+    let data = MarketInstruction::NewOrderV3(new_order).pack();
+    Instruction {
         program_id: *program_id,
         data,
         accounts: vec![
             AccountMeta::new(*state.market, false),
             AccountMeta::new(*orders, false),
-            AccountMeta::new_readonly(signer.unwrap_or(payer).pubkey(), true),
+            AccountMeta::new(*state.req_q, false),
+            AccountMeta::new(*wallet, false),
+            AccountMeta::new_readonly(*owner, true),
             AccountMeta::new(*state.coin_vault, false),
             AccountMeta::new(*state.pc_vault, false),
-            AccountMeta::new(*coin_wallet, false),
-            AccountMeta::new(*pc_wallet, false),
-            AccountMeta::new_readonly(*state.vault_signer_key, false),
             AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+            AccountMeta::new(*state.event_q, false),
+            AccountMeta::new(*state.bids, false),
+            AccountMeta::new(*state.asks, false),
         ],
+    }
+}
+
+/// Builds a `NewOrderV3` followed by a `ConsumeEvents` in the same
+/// transaction, for markets without a reliable crank.
+///
+/// Events can only be consumed from the front of the queue, so the
+/// `ConsumeEvents` lists the owners of the events already queued as well as
+/// the makers the order is expected to fill, read from the current book.
+/// Up to `max_events` events are consumed, and it stops early at the first
+/// event whose owner didn't fit in the transaction. Makers credited this way
+/// can settle their fills right away.
+pub fn new_order_v3_with_consume_instructions(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    state: &MarketPubkeys,
+    orders: &Pubkey,
+    wallet: &Pubkey,
+    owner: &Pubkey,
+    new_order: NewOrderInstructionV2,
+    max_events: u16,
+    coin_fee_receivable: &Pubkey,
+    pc_fee_receivable: &Pubkey,
+) -> Result<Vec<Instruction>> {
+    let event_q_data = client.get_account_data(&state.event_q)?;
+    let inner: Cow<[u64]> = remove_dex_account_padding(&event_q_data)?;
+    let (_header, seg0, seg1) = parse_event_queue(&inner)?;
+    let mut owners: Vec<[u64; 4]> = seg0
+        .iter()
+        .chain(seg1)
+        .filter(|event| !event.is_consumed())
+        .map(|event| event.owner)
+        .collect();
+
+    let book = match new_order.side {
+        Side::Bid => &state.asks,
+        Side::Ask => &state.bids,
+    };
+    let book_data = client.get_account_data(book)?;
+    let book_words: Cow<[u64]> = remove_dex_account_padding(&book_data)?;
+    // The first word holds the account flags.
+    let mut slab_bytes: Vec<u8> = cast_slice(&book_words[1..]).to_vec();
+    let slab = Slab::new(&mut slab_bytes);
+    let mut remaining_qty = new_order.max_qty.get();
+    while remaining_qty > 0 {
+        let leaf = match new_order.side {
+            Side::Bid => slab.remove_min(),
+            Side::Ask => slab.remove_max(),
+        };
+        let leaf = match leaf {
+            Some(leaf) => leaf,
+            None => break,
+        };
+        let crosses = match new_order.side {
+            Side::Bid => leaf.price() <= new_order.limit_price,
+            Side::Ask => leaf.price() >= new_order.limit_price,
+        };
+        if !crosses {
+            break;
+        }
+        owners.push(leaf.owner());
+        remaining_qty = remaining_qty.saturating_sub(leaf.quantity());
+    }
+    owners.push(orders.to_aligned_bytes());
+
+    // Keep the owners in queue order while deduplicating, then sort since the
+    // program looks them up by binary search.
+    let mut seen = BTreeSet::new();
+    owners.retain(|owner| seen.insert(*owner));
+    owners.truncate(max_events as usize);
+    owners.sort_unstable();
+
+    let mut account_metas: Vec<AccountMeta> = owners
+        .iter()
+        .map(|words| AccountMeta::new(Pubkey::new(bytes_of(words)), false))
+        .collect();
+    for pubkey in [
+        &*state.market,
+        &*state.event_q,
+        coin_fee_receivable,
+        pc_fee_receivable,
+    ]
+    .iter()
+    {
+        account_metas.push(AccountMeta::new(**pubkey, false));
+    }
+    let consume_events = Instruction {
+        program_id: *program_id,
+        accounts: account_metas,
+        data: MarketInstruction::ConsumeEvents(max_events).pack(),
+    };
+
+    Ok(vec![
+        new_order_v3_instruction(program_id, state, orders, wallet, owner, new_order),
+        consume_events,
+    ])
+}
+
+/// The outcome of a `swap`, in native units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapFill {
+    /// Taken from the input wallet, including fees.
+    pub paid: u64,
+    /// Added to the output wallet.
+    pub received: u64,
+    /// Open orders account the order was placed from, to reuse for later
+    /// swaps on the market.
+    pub open_orders: Pubkey,
+}
+
+/// Swaps `amount_in` native units of one token of `market` for the other
+/// with an immediate-or-cancel order, then settles the proceeds.
+///
+/// Bids pay pc from `pc_wallet` for coin into `coin_wallet`, and asks the
+/// reverse. The limit price is the worst price the order would reach on the
+/// current book, assuming the market's taker fee for the base fee tier. If
+/// that estimate returns less than `min_out`, nothing is sent. If the book
+/// moves before the order lands, the order only fills partially and the rest
+/// of `amount_in` is settled back, so it never trades at a worse rate than
+/// `amount_in` for `min_out`.
+///
+/// Taker proceeds are credited to the open orders account as the order
+/// matches, so they're settled in the same transaction without waiting for
+/// the crank. An open orders account is created when `orders` is None.
+/// The fill is measured from the wallet balances, so it also counts any
+/// balance already free in the open orders account.
+pub fn swap(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    market: &Pubkey,
+    side: Side,
+    amount_in: u64,
+    min_out: u64,
+    coin_wallet: &Pubkey,
+    pc_wallet: &Pubkey,
+    orders: Option<&Pubkey>,
+) -> Result<SwapFill> {
+    let state = get_keys_for_market(client, program_id, market)?;
+    let market_data = client.get_account_data(market)?;
+    let market_words: Cow<[u64]> = remove_dex_account_padding(&market_data)?;
+    let market_state: MarketState = read_pod(cast_slice(&market_words))?;
+    let lot_sizes = LotSizes::new(&market_state)?;
+    let book_data = client.get_account_data(match side {
+        Side::Bid => &state.asks,
+        Side::Ask => &state.bids,
+    })?;
+    let book = OrderBookReader::new(&book_data)?;
+
+    let (limit_price, max_qty, estimate) = swap_order(
+        &book,
+        side,
+        &lot_sizes,
+        market_state.fee_schedule(),
+        amount_in,
+    )?;
+    if estimate < min_out {
+        return Err(format_err!(
+            "the book only returns {} for {}, less than {}",
+            estimate,
+            amount_in,
+            min_out
+        ));
+    }
+    debug_println!(
+        "Swapping at limit price {} for up to {} lots, expecting {}",
+        limit_price,
+        max_qty,
+        estimate
+    );
+
+    let (in_wallet, out_wallet) = match side {
+        Side::Bid => (pc_wallet, coin_wallet),
+        Side::Ask => (coin_wallet, pc_wallet),
+    };
+    let in_before = get_token_account::<spl_token::state::Account>(client, in_wallet)?.amount;
+    let out_before = get_token_account::<spl_token::state::Account>(client, out_wallet)?.amount;
+
+    let mut instructions = Vec::new();
+    let orders_keypair;
+    let mut signers = vec![payer];
+    let orders_pubkey = match orders {
+        Some(pk) => *pk,
+        None => {
+            let (orders_key, instruction) =
+                create_dex_account(client, program_id, &payer.pubkey(), size_of::<OpenOrders>())?;
+            orders_keypair = orders_key;
+            signers.push(&orders_keypair);
+            instructions.push(instruction);
+            orders_keypair.pubkey()
+        }
+    };
+    instructions.push(new_order_v3_instruction(
+        program_id,
+        &state,
+        &orders_pubkey,
+        in_wallet,
+        &payer.pubkey(),
+        NewOrderInstructionV2 {
+            side,
+            limit_price,
+            max_qty,
+            order_type: OrderType::ImmediateOrCancel,
+            client_id: 0,
+            self_trade_behavior: SelfTradeBehavior::DecrementTake,
+        },
+    ));
+    instructions.push(settle_funds_instruction(
+        program_id,
+        &state,
+        &orders_pubkey,
+        &payer.pubkey(),
+        coin_wallet,
+        pc_wallet,
+        None,
+    ));
+    send_instructions(client, &instructions, &payer.pubkey(), &signers)?;
+
+    let in_after = get_token_account::<spl_token::state::Account>(client, in_wallet)?.amount;
+    let out_after = get_token_account::<spl_token::state::Account>(client, out_wallet)?.amount;
+    Ok(SwapFill {
+        paid: in_before.saturating_sub(in_after),
+        received: out_after.saturating_sub(out_before),
+        open_orders: orders_pubkey,
+    })
+}
+
+// Walks the opposite side of the book to find the limit price and coin lots
+// of an order spending `amount_in`, along with the native amount it's
+// expected to return.
+fn swap_order(
+    book: &OrderBookReader,
+    side: Side,
+    lot_sizes: &LotSizes,
+    fee_schedule: FeeSchedule,
+    amount_in: u64,
+) -> Result<(NonZeroU64, NonZeroU64, u64)> {
+    let fee_tier = FeeTier::Base;
+    let mut worst_price = None;
+    let (max_qty, estimate) = match side {
+        Side::Bid => {
+            // The pc locked for a bid pays for the fee as well.
+            let budget = lot_sizes.pc_lots(fee_schedule.remove_taker_fee(fee_tier, amount_in));
+            let mut remaining = budget;
+            let mut bought = 0;
+            for level in book.levels(usize::MAX) {
+                let lots = min(level.quantity, remaining / level.price);
+                if lots == 0 {
+                    break;
+                }
+                worst_price = Some(level.price);
+                remaining -= lots * level.price;
+                bought += lots;
+            }
+            // The order can only lock enough pc to buy at the limit price.
+            let max_qty = worst_price.map_or(0, |price| min(bought, budget / price));
+            let estimate = lot_sizes
+                .native_coin(max_qty)
+                .ok_or_else(|| format_err!("swap amount overflows"))?;
+            (max_qty, estimate)
+        }
+        Side::Ask => {
+            let max_qty = lot_sizes.coin_lots(amount_in);
+            let mut remaining = max_qty;
+            let mut proceeds: u64 = 0;
+            for level in book.levels(usize::MAX) {
+                if remaining == 0 {
+                    break;
+                }
+                let lots = min(level.quantity, remaining);
+                worst_price = Some(level.price);
+                remaining -= lots;
+                proceeds = lots
+                    .checked_mul(level.price)
+                    .and_then(|pc_lots| proceeds.checked_add(pc_lots))
+                    .ok_or_else(|| format_err!("swap amount overflows"))?;
+            }
+            let proceeds = lot_sizes
+                .native_pc(proceeds)
+                .ok_or_else(|| format_err!("swap amount overflows"))?;
+            (
+                max_qty,
+                proceeds - fee_schedule.taker_fee(fee_tier, proceeds),
+            )
+        }
     };
+    match (
+        worst_price.and_then(NonZeroU64::new),
+        NonZeroU64::new(max_qty),
+    ) {
+        (Some(limit_price), Some(max_qty)) => Ok((limit_price, max_qty, estimate)),
+        _ => Err(format_err!(
+            "{} is too small to fill a lot against the book",
+            amount_in
+        )),
+    }
+}
+
+fn settle_funds(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    state: &MarketPubkeys,
+    signer: Option<&Keypair>,
+    orders: &Pubkey,
+    coin_wallet: &Pubkey,
+    pc_wallet: &Pubkey,
+    referrer_pc_wallet: Option<&Pubkey>,
+    compute_budget: &ComputeBudget,
+) -> Result<()> {
+    let instruction = settle_funds_instruction(
+        program_id,
+        state,
+        orders,
+        &signer.unwrap_or(payer).pubkey(),
+        coin_wallet,
+        pc_wallet,
+        referrer_pc_wallet,
+    );
+    let mut instructions = vec![instruction];
+    compute_budget.prepend(&mut instructions)?;
     let (recent_hash, _fee_calc) = client.get_recent_blockhash()?;
     let mut signers = vec![payer];
     if let Some(s) = signer {
         signers.push(s);
     }
     let txn = Transaction::new_signed_with_payer(
-        &[instruction],
+        &instructions,
         Some(&payer.pubkey()),
         &signers,
         recent_hash,
@@ -967,21 +1873,617 @@ fn settle_funds(
         }
     }
     debug_println!("Settling ...");
-    send_txn(client, &txn, false)?;
+    send_instructions(client, &instructions, &payer.pubkey(), &signers)?;
     Ok(())
 }
 
-fn list_market(
+/// Settles funds like `settle_funds`, paying the side without a wallet out
+/// to the payer as native SOL through a temporary wrapped SOL account.
+fn settle_funds_to_native_sol(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    state: &MarketPubkeys,
+    orders: &Pubkey,
+    coin_wallet: Option<&Pubkey>,
+    pc_wallet: Option<&Pubkey>,
+) -> Result<()> {
+    let wsol = TempWsolAccount::new(client, 0)?;
+    let wsol_pubkey = wsol.pubkey();
+    let (coin_wallet, pc_wallet) = match (coin_wallet, pc_wallet) {
+        (None, Some(pc_wallet)) => (&wsol_pubkey, pc_wallet),
+        (Some(coin_wallet), None) => (coin_wallet, &wsol_pubkey),
+        _ => {
+            return Err(format_err!(
+                "exactly one of the coin and pc wallets must be native SOL"
+            ))
+        }
+    };
+    let mut instructions = wsol.open_instructions(&payer.pubkey(), &payer.pubkey())?;
+    instructions.push(settle_funds_instruction(
+        program_id,
+        state,
+        orders,
+        &payer.pubkey(),
+        coin_wallet,
+        pc_wallet,
+        None,
+    ));
+    instructions.push(wsol.close_instruction(&payer.pubkey())?);
+
+    debug_println!("Settling ...");
+    send_instructions(
+        client,
+        &instructions,
+        &payer.pubkey(),
+        &[payer, wsol.keypair()],
+    )?;
+    Ok(())
+}
+
+pub fn settle_funds_instruction(
+    program_id: &Pubkey,
+    state: &MarketPubkeys,
+    orders: &Pubkey,
+    owner: &Pubkey,
+    coin_wallet: &Pubkey,
+    pc_wallet: &Pubkey,
+    referrer_pc_wallet: Option<&Pubkey>,
+) -> Instruction {
+    let data = MarketInstruction::SettleFunds.pack();
+    let mut instruction = Instruction {
+        program_id: *program_id,
+        data,
+        accounts: vec![
+            AccountMeta::new(*state.market, false),
+            AccountMeta::new(*orders, false),
+            AccountMeta::new_readonly(*owner, true),
+            AccountMeta::new(*state.coin_vault, false),
+            AccountMeta::new(*state.pc_vault, false),
+            AccountMeta::new(*coin_wallet, false),
+            AccountMeta::new(*pc_wallet, false),
+            AccountMeta::new_readonly(*state.vault_signer_key, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+        ],
+    };
+    if let Some(referrer_pc_wallet) = referrer_pc_wallet {
+        instruction
+            .accounts
+            .push(AccountMeta::new(*referrer_pc_wallet, false));
+    }
+    instruction
+}
+
+/// Periodically collects referrer rebates into `referrer_pc_wallet`.
+///
+/// Rebates earned as the referrer of other users' settles are paid into
+/// `referrer_wallets` by those settles, and swept from there, see
+/// `rebates::sweep_referrer_wallets`. Rebates on the operator's own fills
+/// accrue in its open orders accounts, which are settled with the payer as
+/// referrer. Failures on one account are logged and don't stop the others.
+fn settle_referrer_rebates_loop(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    referrer_pc_wallet: &Pubkey,
+    referrer_wallets: &[Pubkey],
+    orders: &[RebateAccounts],
+    interval: time::Duration,
+    compute_budget: &ComputeBudget,
+) -> Result<()> {
+    loop {
+        if !referrer_wallets.is_empty() {
+            match rebates::sweep_referrer_wallets(
+                client,
+                payer,
+                referrer_pc_wallet,
+                referrer_wallets,
+                compute_budget,
+            ) {
+                Ok(0) => {}
+                Ok(swept) => info!("Swept {} in referrer rebates", swept),
+                Err(err) => error!("Failed to sweep referrer rebates: {:?}", err),
+            }
+        }
+        for accounts in orders {
+            match settle_referrer_rebates_once(
+                client,
+                program_id,
+                payer,
+                referrer_pc_wallet,
+                accounts,
+                compute_budget,
+            ) {
+                Ok(0) => {}
+                Ok(rebates) => info!(
+                    "Settled {} in referrer rebates from {}",
+                    rebates, accounts.orders
+                ),
+                Err(err) => error!(
+                    "Failed to settle referrer rebates from {}: {:?}",
+                    accounts.orders, err
+                ),
+            }
+        }
+        thread::sleep(interval);
+    }
+}
+
+#[cfg(target_endian = "little")]
+fn settle_referrer_rebates_once(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    referrer_pc_wallet: &Pubkey,
+    accounts: &RebateAccounts,
+    compute_budget: &ComputeBudget,
+) -> Result<u64> {
+    let orders_data = client.get_account_data(&accounts.orders)?;
+    let words: Cow<[u64]> = remove_dex_account_padding(&orders_data)?;
+    let open_orders: OpenOrders = read_pod(cast_slice(&words))?;
+    let rebates = open_orders.referrer_rebates_accrued;
+    if rebates == 0 {
+        return Ok(0);
+    }
+    let market = Pubkey::new(bytes_of(&identity(open_orders.market)));
+    let market_keys = get_keys_for_market(client, program_id, &market)?;
+    settle_funds(
+        client,
+        program_id,
+        payer,
+        &market_keys,
+        None,
+        &accounts.orders,
+        &accounts.coin_wallet,
+        &accounts.pc_wallet,
+        Some(referrer_pc_wallet),
+        compute_budget,
+    )?;
+    Ok(rebates)
+}
+
+/// Settles every open orders account of `owner` on the market whose free
+/// coin or pc reaches its threshold.
+#[cfg(target_endian = "little")]
+fn settle_open_orders(
+    session: &RpcSession,
+    rpc_url: &str,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    owner: Option<&Keypair>,
+    accounts: &SettleAccounts,
+    (min_coin_free, min_pc_free): (u64, u64),
+    compute_budget: &ComputeBudget,
+) -> Result<()> {
+    let client = session.client();
+    let owner_pubkey = owner.unwrap_or(payer).pubkey();
+    let market_keys = get_keys_for_market(client, program_id, &accounts.market)?;
+    let mut instructions = vec![];
+    let mut settled = vec![];
+    for orders in find_open_orders(rpc_url, program_id, Some(&accounts.market), &owner_pubkey)? {
+        let orders_data = client.get_account_data(&orders)?;
+        let words: Cow<[u64]> = remove_dex_account_padding(&orders_data)?;
+        let open_orders: OpenOrders = read_pod(cast_slice(&words))?;
+        let coin_free = open_orders.native_coin_free;
+        let pc_free = open_orders.native_pc_free;
+        if coin_free < min_coin_free && pc_free < min_pc_free {
+            continue;
+        }
+        instructions.push(settle_funds_instruction(
+            program_id,
+            &market_keys,
+            &orders,
+            &owner_pubkey,
+            &accounts.coin_wallet,
+            &accounts.pc_wallet,
+            None,
+        ));
+        settled.push((orders, coin_free, pc_free));
+    }
+
+    let outcomes = send_settle_instructions(session, &instructions, payer, owner, compute_budget)?;
+    let mut failed = 0;
+    for ((orders, coin_free, pc_free), outcome) in settled.iter().zip(outcomes) {
+        match outcome {
+            Outcome::Failed(err) => {
+                eprintln!("Failed to settle {}: {}", orders, err);
+                failed += 1;
+            }
+            Outcome::Unknown(signature) => {
+                eprintln!("Settle of {} unconfirmed, check {}", orders, signature);
+                failed += 1;
+            }
+            _ => println!(
+                "Settled {} coin and {} pc from {}",
+                coin_free, pc_free, orders
+            ),
+        }
+    }
+    if failed > 0 {
+        return Err(format_err!(
+            "failed to settle {} open orders account(s)",
+            failed
+        ));
+    }
+    Ok(())
+}
+
+/// Sends SettleFunds `instructions` of `owner`'s open orders accounts,
+/// packed into as few transactions as fit.
+#[cfg(target_endian = "little")]
+fn send_settle_instructions(
+    session: &RpcSession,
+    instructions: &[Instruction],
+    payer: &Keypair,
+    owner: Option<&Keypair>,
+    compute_budget: &ComputeBudget,
+) -> Result<Vec<Outcome>> {
+    let mut prefix = vec![];
+    compute_budget.prepend(&mut prefix)?;
+    let mut signers = vec![payer];
+    if let Some(owner) = owner {
+        signers.push(owner);
+    }
+    Ok(send_batched(
+        session,
+        instructions,
+        &prefix,
+        &payer.pubkey(),
+        &signers,
+        &BatchConfig::default(),
+    )?)
+}
+
+/// Addresses of the open orders accounts `owner` has on `market`, or on
+/// every market if none is given.
+fn find_open_orders(
+    rpc_url: &str,
+    program_id: &Pubkey,
+    market: Option<&Pubkey>,
+    owner: &Pubkey,
+) -> Result<Vec<Pubkey>> {
+    // Offsets into the padded account: 5 bytes of padding, then the flags.
+    let market_offset = 5 + 8;
+    let owner_offset = market_offset + 32;
+    let mut filters = vec![
+        serde_json::json!({ "dataSize": size_of::<OpenOrders>() + 12 }),
+        serde_json::json!({ "memcmp": { "offset": owner_offset, "bytes": owner.to_string() } }),
+    ];
+    if let Some(market) = market {
+        filters.push(
+            serde_json::json!({ "memcmp": { "offset": market_offset, "bytes": market.to_string() } }),
+        );
+    }
+    let params = serde_json::json!([
+        program_id.to_string(),
+        {
+            "encoding": "base64",
+            "dataSlice": { "offset": 0, "length": 0 },
+            "filters": filters,
+        },
+    ]);
+    let result = json_rpc(rpc_url, "getProgramAccounts", params)?;
+    result
+        .as_array()
+        .ok_or_else(|| format_err!("unexpected getProgramAccounts result: {}", result))?
+        .iter()
+        .map(|account| {
+            let pubkey = account["pubkey"].as_str().unwrap_or_default();
+            Pubkey::from_str(pubkey).map_err(|e| format_err!("{}: {:?}", pubkey, e))
+        })
+        .collect()
+}
+
+/// Settles every open orders account of `owner`, on any market, that has a
+/// free balance, into the owner's token accounts for the market's mints.
+#[cfg(target_endian = "little")]
+fn settle_all(
+    session: &RpcSession,
+    rpc_url: &str,
+    program_id: &Pubkey,
+    payer: &Keypair,
+    owner: Option<&Keypair>,
+    compute_budget: &ComputeBudget,
+) -> Result<()> {
+    let client = session.client();
+    let owner_pubkey = owner.unwrap_or(payer).pubkey();
+    let mut by_market: BTreeMap<Pubkey, Vec<Pubkey>> = BTreeMap::new();
+    let all_orders = find_open_orders(rpc_url, program_id, None, &owner_pubkey)?;
+    for orders in &all_orders {
+        let orders_data = client.get_account_data(orders)?;
+        let words: Cow<[u64]> = remove_dex_account_padding(&orders_data)?;
+        let open_orders: OpenOrders = read_pod(cast_slice(&words))?;
+        if open_orders.native_coin_free == 0 && open_orders.native_pc_free == 0 {
+            continue;
+        }
+        let market = Pubkey::new(bytes_of(&identity(open_orders.market)));
+        by_market.entry(market).or_default().push(*orders);
+    }
+    println!(
+        "Found {} open orders accounts, {} with free balances on {} markets",
+        all_orders.len(),
+        by_market.values().map(Vec::len).sum::<usize>(),
+        by_market.len()
+    );
+
+    let mut failed_markets = 0;
+    let mut instructions = vec![];
+    let mut settled = vec![];
+    for (market, orders) in by_market {
+        let result = (|| -> Result<()> {
+            let market_data = client.get_account_data(&market)?;
+            let words: Cow<[u64]> = remove_dex_account_padding(&market_data)?;
+            let market_state: MarketState = read_pod(cast_slice(&words))?;
+            let coin_mint = Pubkey::new(bytes_of(&identity(market_state.coin_mint)));
+            let pc_mint = Pubkey::new(bytes_of(&identity(market_state.pc_mint)));
+            let coin_wallet = find_token_account(rpc_url, &owner_pubkey, &coin_mint)?;
+            let pc_wallet = find_token_account(rpc_url, &owner_pubkey, &pc_mint)?;
+            let market_keys = get_keys_for_market(client, program_id, &market)?;
+            for orders in &orders {
+                instructions.push(settle_funds_instruction(
+                    program_id,
+                    &market_keys,
+                    orders,
+                    &owner_pubkey,
+                    &coin_wallet,
+                    &pc_wallet,
+                    None,
+                ));
+                settled.push((*orders, market));
+            }
+            Ok(())
+        })();
+        if let Err(err) = result {
+            eprintln!("Failed to settle market {}: {:?}", market, err);
+            failed_markets += 1;
+        }
+    }
+
+    let outcomes = send_settle_instructions(session, &instructions, payer, owner, compute_budget)?;
+    let mut failed_orders = 0;
+    for ((orders, market), outcome) in settled.iter().zip(outcomes) {
+        match outcome {
+            Outcome::Failed(err) => {
+                eprintln!("Failed to settle {} on market {}: {}", orders, market, err);
+                failed_orders += 1;
+            }
+            Outcome::Unknown(signature) => {
+                eprintln!(
+                    "Settle of {} on market {} unconfirmed, check {}",
+                    orders, market, signature
+                );
+                failed_orders += 1;
+            }
+            _ => println!("Settled {} on market {}", orders, market),
+        }
+    }
+    if failed_markets > 0 || failed_orders > 0 {
+        return Err(format_err!(
+            "failed to settle {} market(s) and {} open orders account(s)",
+            failed_markets,
+            failed_orders
+        ));
+    }
+    Ok(())
+}
+
+/// The first token account `owner` has for `mint`.
+fn find_token_account(rpc_url: &str, owner: &Pubkey, mint: &Pubkey) -> Result<Pubkey> {
+    let params = serde_json::json!([
+        owner.to_string(),
+        { "mint": mint.to_string() },
+        { "encoding": "base64", "dataSlice": { "offset": 0, "length": 0 } },
+    ]);
+    let result = json_rpc(rpc_url, "getTokenAccountsByOwner", params)?;
+    let pubkey = result["value"][0]["pubkey"]
+        .as_str()
+        .ok_or_else(|| format_err!("{} has no token account for mint {}", owner, mint))?;
+    Pubkey::from_str(pubkey).map_err(|e| format_err!("{}: {:?}", pubkey, e))
+}
+
+/// Calls an RPC method the `RpcClient` of this SDK version doesn't expose,
+/// returning its result.
+pub(crate) fn json_rpc(
+    rpc_url: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<serde_json::Value> {
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": method,
+        "params": params,
+    });
+    let mut response: serde_json::Value = reqwest::blocking::Client::new()
+        .post(rpc_url)
+        .json(&request)
+        .send()?
+        .json()?;
+    match response.get_mut("result") {
+        Some(result) => Ok(result.take()),
+        None => Err(format_err!("{} failed: {}", method, response["error"])),
+    }
+}
+
+/// Creates the accounts of a new market and initializes it. Lot sizes are
+/// either given, derived from the quoting precision, or the defaults.
+#[derive(Clap, Debug)]
+pub struct ListMarketArgs {
+    pub payer: KeypairPath,
+    pub dex_program_id: Pubkey,
+    #[clap(long, short)]
+    pub coin_mint: Pubkey,
+    #[clap(long, short)]
+    pub pc_mint: Pubkey,
+    #[clap(long)]
+    pub coin_lot_size: Option<u64>,
+    #[clap(long)]
+    pub pc_lot_size: Option<u64>,
+    /// Decimal places of the coin order sizes are quoted to. With
+    /// --price-decimals, derives the lot sizes from the mints' decimals.
+    #[clap(long)]
+    pub size_decimals: Option<u8>,
+    /// Decimal places of pc per coin prices are quoted to.
+    #[clap(long)]
+    pub price_decimals: Option<u8>,
+    #[clap(long)]
+    pub pc_dust_threshold: Option<u64>,
+    #[clap(long)]
+    pub request_queue_capacity: Option<usize>,
+    #[clap(long)]
+    pub event_queue_capacity: Option<usize>,
+    /// Orders each side of the book can hold.
+    #[clap(long)]
+    pub slab_capacity: Option<usize>,
+}
+
+impl ListMarketArgs {
+    pub fn run(&self, client: &RpcClient) -> Result<MarketPubkeys> {
+        let payer = self.payer.read()?;
+        let defaults = ListingParams::default();
+        let (coin_lot_size, pc_lot_size) = match (
+            self.coin_lot_size,
+            self.pc_lot_size,
+            self.size_decimals,
+            self.price_decimals,
+        ) {
+            (coin_lot_size, pc_lot_size, None, None) => (
+                coin_lot_size.unwrap_or(defaults.coin_lot_size),
+                pc_lot_size.unwrap_or(defaults.pc_lot_size),
+            ),
+            (None, None, Some(size_decimals), Some(price_decimals)) => {
+                let lot_sizes = LotSizes::derive(
+                    mint_decimals(client, &self.coin_mint)?,
+                    mint_decimals(client, &self.pc_mint)?,
+                    size_decimals,
+                    price_decimals,
+                )
+                .map_err(|e| format_err!("can't derive lot sizes: {}", e))?;
+                (lot_sizes.coin_lot_size, lot_sizes.pc_lot_size)
+            }
+            _ => {
+                return Err(format_err!(
+                    "pass both --size-decimals and --price-decimals, without lot sizes"
+                ))
+            }
+        };
+        let params = ListingParams {
+            coin_lot_size,
+            pc_lot_size,
+            pc_dust_threshold: self.pc_dust_threshold.unwrap_or(defaults.pc_dust_threshold),
+            request_queue_capacity: self
+                .request_queue_capacity
+                .unwrap_or(defaults.request_queue_capacity),
+            event_queue_capacity: self
+                .event_queue_capacity
+                .unwrap_or(defaults.event_queue_capacity),
+            slab_capacity: self.slab_capacity.unwrap_or(defaults.slab_capacity),
+        };
+        list_market(
+            client,
+            &self.dex_program_id,
+            &payer,
+            &self.coin_mint,
+            &self.pc_mint,
+            &params,
+        )
+    }
+}
+
+/// Parameters of a new market. Capacities are in entries, and the queue and
+/// slab accounts are sized to hold them.
+#[derive(Debug, Clone, Copy)]
+pub struct ListingParams {
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub pc_dust_threshold: u64,
+    pub request_queue_capacity: usize,
+    pub event_queue_capacity: usize,
+    /// Orders each side of the book can hold.
+    pub slab_capacity: usize,
+}
+
+impl Default for ListingParams {
+    fn default() -> Self {
+        ListingParams {
+            coin_lot_size: 1_000_000,
+            pc_lot_size: 10_000,
+            pc_dust_threshold: 100,
+            request_queue_capacity: 7,
+            event_queue_capacity: 11_915,
+            slab_capacity: 454,
+        }
+    }
+}
+
+impl ListingParams {
+    // The minimums InitializeMarket checks for.
+    const MIN_EVENT_QUEUE_CAPACITY: usize = 128;
+    const MIN_SLAB_CAPACITY: usize = 101;
+
+    /// Checks the parameters against what InitializeMarket accepts.
+    pub fn check(&self) -> Result<()> {
+        if self.coin_lot_size == 0 || self.pc_lot_size == 0 {
+            return Err(format_err!("lot sizes must not be zero"));
+        }
+        if self.request_queue_capacity == 0 {
+            return Err(format_err!("the request queue must hold a request"));
+        }
+        if self.event_queue_capacity < Self::MIN_EVENT_QUEUE_CAPACITY {
+            return Err(format_err!(
+                "the event queue must hold at least {} events",
+                Self::MIN_EVENT_QUEUE_CAPACITY
+            ));
+        }
+        if self.slab_capacity < Self::MIN_SLAB_CAPACITY {
+            return Err(format_err!(
+                "each side of the book must hold at least {} orders",
+                Self::MIN_SLAB_CAPACITY
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn request_queue_len(&self) -> usize {
+        size_of::<RequestQueueHeader>() + self.request_queue_capacity * size_of::<Request>()
+    }
+
+    pub fn event_queue_len(&self) -> usize {
+        size_of::<EventQueueHeader>() + self.event_queue_capacity * size_of::<Event>()
+    }
+
+    // The account flags, then a slab with room for the inner nodes joining
+    // the orders.
+    pub fn slab_len(&self) -> usize {
+        size_of::<u64>() + SLAB_HEADER_LEN + (2 * self.slab_capacity) * size_of::<AnyNode>()
+    }
+}
+
+/// Lists a market for `coin_mint` and `pc_mint`: creates its vaults, owned
+/// by the vault signer, and its queue and slab accounts, then initializes
+/// it. Returns the addresses of everything created.
+fn mint_decimals(client: &RpcClient, mint: &Pubkey) -> Result<u8> {
+    Ok(get_token_account::<spl_token::state::Mint>(client, mint)?.decimals)
+}
+
+pub fn list_market(
     client: &RpcClient,
     program_id: &Pubkey,
     payer: &Keypair,
     coin_mint: &Pubkey,
     pc_mint: &Pubkey,
-    coin_lot_size: u64,
-    pc_lot_size: u64,
+    params: &ListingParams,
 ) -> Result<MarketPubkeys> {
+    params.check()?;
+    validate_lot_sizes(
+        params.coin_lot_size,
+        params.pc_lot_size,
+        mint_decimals(client, coin_mint)?,
+        mint_decimals(client, pc_mint)?,
+    )
+    .map_err(|e| format_err!("InitializeMarket would reject the lot sizes: {}", e))?;
     let (listing_keys, mut instructions) =
-        gen_listing_params(client, program_id, &payer.pubkey(), coin_mint, pc_mint)?;
+        gen_listing_params(client, program_id, &payer.pubkey(), params)?;
     let ListingKeys {
         market_key,
         req_q_key,
@@ -1011,10 +2513,10 @@ fn list_market(
         &asks_key.pubkey(),
         &req_q_key.pubkey(),
         &event_q_key.pubkey(),
-        coin_lot_size,
-        pc_lot_size,
+        params.coin_lot_size,
+        params.pc_lot_size,
         vault_signer_nonce,
-        100,
+        params.pc_dust_threshold,
     )?;
     debug_println!(
         "initialize_market_instruction: {:#?}",
@@ -1031,8 +2533,6 @@ fn list_market(
         &event_q_key,
         &bids_key,
         &asks_key,
-        &req_q_key,
-        &event_q_key,
     ];
     let txn = Transaction::new_signed_with_payer(
         &instructions,
@@ -1048,7 +2548,7 @@ fn list_market(
     }
     debug_println!("{:#?}", result.value);
     debug_println!("Listing {} ...", market_key.pubkey());
-    send_txn(client, &txn, false)?;
+    send_instructions(client, &instructions, &payer.pubkey(), &signers)?;
 
     Ok(MarketPubkeys {
         market: Box::new(market_key.pubkey()),
@@ -1076,14 +2576,16 @@ fn gen_listing_params(
     client: &RpcClient,
     program_id: &Pubkey,
     payer: &Pubkey,
-    _coin_mint: &Pubkey,
-    _pc_mint: &Pubkey,
+    params: &ListingParams,
 ) -> Result<(ListingKeys, Vec<Instruction>)> {
-    let (market_key, create_market) = create_dex_account(client, program_id, payer, 376)?;
-    let (req_q_key, create_req_q) = create_dex_account(client, program_id, payer, 640)?;
-    let (event_q_key, create_event_q) = create_dex_account(client, program_id, payer, 1 << 20)?;
-    let (bids_key, create_bids) = create_dex_account(client, program_id, payer, 1 << 16)?;
-    let (asks_key, create_asks) = create_dex_account(client, program_id, payer, 1 << 16)?;
+    let (market_key, create_market) =
+        create_dex_account(client, program_id, payer, size_of::<MarketState>())?;
+    let (req_q_key, create_req_q) =
+        create_dex_account(client, program_id, payer, params.request_queue_len())?;
+    let (event_q_key, create_event_q) =
+        create_dex_account(client, program_id, payer, params.event_queue_len())?;
+    let (bids_key, create_bids) = create_dex_account(client, program_id, payer, params.slab_len())?;
+    let (asks_key, create_asks) = create_dex_account(client, program_id, payer, params.slab_len())?;
     let (vault_signer_nonce, vault_signer_pk) = {
         let mut i = 0;
         loop {
@@ -1113,7 +2615,7 @@ fn gen_listing_params(
     Ok((info, instructions))
 }
 
-fn create_dex_account(
+pub fn create_dex_account(
     client: &RpcClient,
     program_id: &Pubkey,
     payer: &Pubkey,
@@ -1171,7 +2673,12 @@ fn match_orders(
     debug_println!("{:#?}", result.value);
     if result.value.err.is_none() {
         debug_println!("Matching orders ...");
-        send_txn(client, &txn, false)?;
+        send_instructions(
+            client,
+            std::slice::from_ref(&instruction),
+            &payer.pubkey(),
+            &[payer],
+        )?;
     }
     Ok(())
 }
@@ -1204,17 +2711,8 @@ fn create_account(
 
     let instructions = vec![create_account_instr, init_account_instr];
 
-    let (recent_hash, _fee_calc) = client.get_recent_blockhash()?;
-
-    let txn = Transaction::new_signed_with_payer(
-        &instructions,
-        Some(&payer.pubkey()),
-        &signers,
-        recent_hash,
-    );
-
     debug_println!("Creating account: {} ...", spl_account.pubkey());
-    send_txn(client, &txn, false)?;
+    send_instructions(client, &instructions, &payer.pubkey(), &signers)?;
     Ok(spl_account)
 }
 
@@ -1238,14 +2736,7 @@ fn mint_to_existing_account(
     )?;
 
     let instructions = vec![mint_tokens_instr];
-    let (recent_hash, _fee_calc) = client.get_recent_blockhash()?;
-    let txn = Transaction::new_signed_with_payer(
-        &instructions,
-        Some(&payer.pubkey()),
-        &signers,
-        recent_hash,
-    );
-    send_txn(client, &txn, false)?;
+    send_instructions(client, &instructions, &payer.pubkey(), &signers)?;
     Ok(())
 }
 
@@ -1267,14 +2758,7 @@ fn initialize_token_account(client: &RpcClient, mint: &Pubkey, owner: &Keypair)
     )?;
     let signers = vec![owner, &recip_keypair];
     let instructions = vec![create_recip_instr, init_recip_instr];
-    let (recent_hash, _fee_calc) = client.get_recent_blockhash()?;
-    let txn = Transaction::new_signed_with_payer(
-        &instructions,
-        Some(&owner.pubkey()),
-        &signers,
-        recent_hash,
-    );
-    send_txn(client, &txn, false)?;
+    send_instructions(client, &instructions, &owner.pubkey(), &signers)?;
     Ok(recip_keypair)
 }
 
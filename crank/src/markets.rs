@@ -0,0 +1,229 @@
+//! Market discovery: every market of a dex program, optionally of given
+//! mints, fetched with one `getProgramAccounts` call and paged locally.
+
+use std::borrow::Cow;
+use std::convert::identity;
+
+use anyhow::{format_err, Result};
+use bytemuck::{bytes_of, cast_slice};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_client::rpc_request::RpcRequest;
+use solana_sdk::pubkey::Pubkey;
+
+use serum_dex::client::{read_market, MarketFilter};
+use serum_dex::state::{
+    find_market_listing_address, gen_vault_signer_key, MarketListing, MarketState, MarketStats,
+    ToAlignedBytes,
+};
+
+use crate::{read_pod, remove_dex_account_padding};
+
+#[derive(Debug, Serialize)]
+pub struct MarketSummary {
+    pub market: String,
+    pub version: u8,
+    pub coin_mint: String,
+    pub pc_mint: String,
+    pub coin_vault: String,
+    pub pc_vault: String,
+    pub vault_signer: String,
+    pub request_queue: String,
+    pub event_queue: String,
+    pub bids: String,
+    pub asks: String,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    pub pc_dust_threshold: u64,
+    pub taker_fee_bps: u16,
+    pub maker_rebate_bps: u16,
+    pub coin_deposits_total: u64,
+    pub pc_deposits_total: u64,
+    pub coin_fees_accrued: u64,
+    pub pc_fees_accrued: u64,
+    pub referrer_rebates_accrued: u64,
+}
+
+impl MarketSummary {
+    pub fn load(client: &RpcClient, program_id: &Pubkey, market: &Pubkey) -> Result<Self> {
+        let data = client.get_account_data(market)?;
+        let state = read_market(&data).map_err(|e| format_err!("{}: {:?}", market, e))?;
+        Self::new(program_id, market, &state)
+    }
+
+    pub fn new(program_id: &Pubkey, market: &Pubkey, state: &MarketState) -> Result<Self> {
+        let key = |words: [u64; 4]| Pubkey::new(bytes_of(&words)).to_string();
+        let vault_signer = gen_vault_signer_key(state.vault_signer_nonce, market, program_id)?;
+        let fee_schedule = state.fee_schedule();
+        Ok(MarketSummary {
+            market: market.to_string(),
+            version: state.version,
+            coin_mint: key(identity(state.coin_mint)),
+            pc_mint: key(identity(state.pc_mint)),
+            coin_vault: key(identity(state.coin_vault)),
+            pc_vault: key(identity(state.pc_vault)),
+            vault_signer: vault_signer.to_string(),
+            request_queue: key(identity(state.req_q)),
+            event_queue: key(identity(state.event_q)),
+            bids: key(identity(state.bids)),
+            asks: key(identity(state.asks)),
+            coin_lot_size: state.coin_lot_size,
+            pc_lot_size: state.pc_lot_size,
+            pc_dust_threshold: state.pc_dust_threshold,
+            taker_fee_bps: fee_schedule.taker_fee_bps,
+            maker_rebate_bps: fee_schedule.maker_rebate_bps,
+            coin_deposits_total: state.coin_deposits_total,
+            pc_deposits_total: state.pc_deposits_total,
+            coin_fees_accrued: state.coin_fees_accrued,
+            pc_fees_accrued: state.pc_fees_accrued,
+            referrer_rebates_accrued: state.referrer_rebates_accrued,
+        })
+    }
+}
+
+/// One page of markets, ordered by address.
+#[derive(Debug, Serialize)]
+pub struct MarketPage {
+    pub markets: Vec<MarketSummary>,
+    /// Pass as `after` for the next page. None on the last page.
+    pub next: Option<String>,
+}
+
+/// The enabled markets of `program_id` selected by `filter`, by address.
+pub fn find_markets(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    filter: &MarketFilter,
+) -> Result<Vec<(Pubkey, MarketState)>> {
+    let mut rpc_filters = vec![serde_json::json!({ "dataSize": MarketFilter::DATA_SIZE })];
+    for (offset, key) in filter.memcmp_filters() {
+        rpc_filters.push(serde_json::json!({
+            "memcmp": { "offset": offset, "bytes": key.to_string() }
+        }));
+    }
+    let response: Vec<serde_json::Value> = client.send(
+        RpcRequest::GetProgramAccounts,
+        serde_json::json!([
+            program_id.to_string(),
+            { "encoding": "base64", "filters": rpc_filters },
+        ]),
+    )?;
+
+    let malformed = || format_err!("malformed getProgramAccounts response");
+    let mut markets = Vec::new();
+    for keyed in &response {
+        let address: Pubkey = keyed["pubkey"]
+            .as_str()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(malformed)?;
+        let data = keyed["account"]["data"][0]
+            .as_str()
+            .and_then(|s| base64::decode(s).ok())
+            .ok_or_else(malformed)?;
+        if !filter.matches(&data) {
+            continue;
+        }
+        // Disabled markets, and any other account of the same size, fail to
+        // decode.
+        if let Ok(market) = read_market(&data) {
+            if identity(market.own_address) == address.to_aligned_bytes() {
+                markets.push((address, market));
+            }
+        }
+    }
+    markets.sort_by_key(|(address, _)| *address);
+    Ok(markets)
+}
+
+/// Up to `limit` of the markets `find_markets` returns, starting after the
+/// market `after`. The RPC has no paging, so every page fetches them all.
+pub fn list_markets(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    filter: &MarketFilter,
+    after: Option<&Pubkey>,
+    limit: usize,
+) -> Result<MarketPage> {
+    let markets = find_markets(client, program_id, filter)?;
+    let start = match after {
+        None => 0,
+        Some(after) => match markets.binary_search_by_key(after, |(address, _)| *address) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        },
+    };
+    let end = markets.len().min(start.saturating_add(limit));
+    let next = if end > start && end < markets.len() {
+        Some(markets[end - 1].0.to_string())
+    } else {
+        None
+    };
+    let markets = markets[start..end]
+        .iter()
+        .map(|(address, market)| MarketSummary::new(program_id, address, market))
+        .collect::<Result<_>>()?;
+    Ok(MarketPage { markets, next })
+}
+
+/// The running totals of a MarketStats account. Volumes are native.
+#[derive(Debug, Serialize)]
+pub struct MarketStatsSummary {
+    pub market: String,
+    pub coin_volume: u64,
+    pub pc_volume: u64,
+    pub trade_count: u64,
+    pub last_price: u64,
+    pub last_trade_slot: u64,
+    pub self_cross_count: u64,
+    pub same_owner_cross_count: u64,
+}
+
+impl MarketStatsSummary {
+    pub fn load(client: &RpcClient, stats: &Pubkey) -> Result<Self> {
+        let data = client.get_account_data(stats)?;
+        let words: Cow<[u64]> = remove_dex_account_padding(&data)?;
+        let state: MarketStats = read_pod(cast_slice(&words))?;
+        if identity(state.account_flags) == 0 {
+            return Err(format_err!("{} has no trades recorded yet", stats));
+        }
+        Ok(MarketStatsSummary {
+            market: Pubkey::new(bytes_of(&identity(state.market))).to_string(),
+            coin_volume: state.coin_volume,
+            pc_volume: state.pc_volume,
+            trade_count: state.trade_count,
+            last_price: state.last_price,
+            last_trade_slot: state.last_trade_slot,
+            self_cross_count: state.self_cross_count,
+            same_owner_cross_count: state.same_owner_cross_count,
+        })
+    }
+}
+
+/// The canonical market of a mint pair, from its MarketListing account.
+#[derive(Debug, Serialize)]
+pub struct MarketListingSummary {
+    pub listing: String,
+    pub market: String,
+    pub lister: String,
+}
+
+impl MarketListingSummary {
+    pub fn load(
+        client: &RpcClient,
+        program_id: &Pubkey,
+        coin_mint: &Pubkey,
+        pc_mint: &Pubkey,
+    ) -> Result<Self> {
+        let (listing, _) = find_market_listing_address(coin_mint, pc_mint, program_id);
+        let data = client
+            .get_account_data(&listing)
+            .map_err(|_| format_err!("{}/{} has no listed market", coin_mint, pc_mint))?;
+        let words: Cow<[u64]> = remove_dex_account_padding(&data)?;
+        let state: MarketListing = read_pod(cast_slice(&words))?;
+        Ok(MarketListingSummary {
+            listing: listing.to_string(),
+            market: Pubkey::new(bytes_of(&identity(state.market))).to_string(),
+            lister: Pubkey::new(bytes_of(&identity(state.lister))).to_string(),
+        })
+    }
+}
@@ -0,0 +1,189 @@
+//! Per-market crank metrics, served in the Prometheus text format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+
+use anyhow::Result;
+use solana_sdk::pubkey::Pubkey;
+use warp::Filter;
+
+use crate::throttle::{Finished, Outcome};
+
+/// Shared handle to the metrics of every market cranked by this process.
+/// Cloning it is cheap, and recording into it costs nothing beyond a lock
+/// when the metrics aren't served.
+#[derive(Clone, Default)]
+pub struct Metrics {
+    markets: Arc<Mutex<BTreeMap<Pubkey, MarketMetrics>>>,
+}
+
+#[derive(Default)]
+struct MarketMetrics {
+    queue_length: u64,
+    // Total events ever removed from the queue, as of the last poll.
+    consumed: Option<u64>,
+    events_consumed: u64,
+    succeeded: u64,
+    failed: u64,
+    expired: u64,
+    send_errors: u64,
+    latency_sum: f64,
+    latency_count: u64,
+    lamports_spent: u64,
+}
+
+impl Metrics {
+    /// Records a read of the market's event queue. `seq_num` and `count`
+    /// come from the queue header; their difference is the number of events
+    /// ever consumed, whoever consumed them.
+    pub fn record_queue(&self, market: &Pubkey, seq_num: u64, count: u64) {
+        let mut markets = self.markets.lock().unwrap();
+        let m = markets.entry(*market).or_default();
+        let consumed = seq_num.saturating_sub(count);
+        if let Some(last) = m.consumed {
+            m.events_consumed += consumed.saturating_sub(last);
+        }
+        m.consumed = Some(consumed);
+        m.queue_length = count;
+    }
+
+    /// Records a transaction the RPC node refused to send.
+    pub fn record_send_error(&self, market: &Pubkey) {
+        let mut markets = self.markets.lock().unwrap();
+        markets.entry(*market).or_default().send_errors += 1;
+    }
+
+    /// Records transactions that are no longer in flight.
+    pub fn record_finished(&self, market: &Pubkey, finished: &[Finished]) {
+        let mut markets = self.markets.lock().unwrap();
+        let m = markets.entry(*market).or_default();
+        for f in finished {
+            match f.outcome {
+                Outcome::Succeeded => m.succeeded += 1,
+                Outcome::Failed => m.failed += 1,
+                Outcome::Expired => m.expired += 1,
+            }
+            if f.outcome != Outcome::Expired {
+                m.latency_sum += f.latency.as_secs_f64();
+                m.latency_count += 1;
+            }
+            m.lamports_spent += f.fee;
+        }
+    }
+
+    pub fn render(&self) -> String {
+        let markets = self.markets.lock().unwrap();
+        let mut out = String::new();
+
+        header(
+            &mut out,
+            "crank_event_queue_length",
+            "gauge",
+            "Events in the market's event queue at the last poll.",
+        );
+        for (market, m) in markets.iter() {
+            writeln!(
+                out,
+                "crank_event_queue_length{{market=\"{}\"}} {}",
+                market, m.queue_length
+            )
+            .unwrap();
+        }
+
+        header(
+            &mut out,
+            "crank_events_consumed_total",
+            "counter",
+            "Events removed from the market's event queue since the crank started.",
+        );
+        for (market, m) in markets.iter() {
+            writeln!(
+                out,
+                "crank_events_consumed_total{{market=\"{}\"}} {}",
+                market, m.events_consumed
+            )
+            .unwrap();
+        }
+
+        header(
+            &mut out,
+            "crank_transactions_total",
+            "counter",
+            "ConsumeEvents transactions by outcome.",
+        );
+        for (market, m) in markets.iter() {
+            for (status, n) in &[
+                ("success", m.succeeded),
+                ("failure", m.failed),
+                ("expired", m.expired),
+                ("send_error", m.send_errors),
+            ] {
+                writeln!(
+                    out,
+                    "crank_transactions_total{{market=\"{}\",status=\"{}\"}} {}",
+                    market, status, n
+                )
+                .unwrap();
+            }
+        }
+
+        header(
+            &mut out,
+            "crank_confirmation_latency_seconds",
+            "summary",
+            "Time from sending a transaction to seeing it processed.",
+        );
+        for (market, m) in markets.iter() {
+            writeln!(
+                out,
+                "crank_confirmation_latency_seconds_sum{{market=\"{}\"}} {}",
+                market, m.latency_sum
+            )
+            .unwrap();
+            writeln!(
+                out,
+                "crank_confirmation_latency_seconds_count{{market=\"{}\"}} {}",
+                market, m.latency_count
+            )
+            .unwrap();
+        }
+
+        header(
+            &mut out,
+            "crank_lamports_spent_total",
+            "counter",
+            "Transaction fees paid by the crank.",
+        );
+        for (market, m) in markets.iter() {
+            writeln!(
+                out,
+                "crank_lamports_spent_total{{market=\"{}\"}} {}",
+                market, m.lamports_spent
+            )
+            .unwrap();
+        }
+
+        out
+    }
+
+    /// Serves `/metrics` on `addr` from a background thread.
+    pub fn serve(&self, addr: SocketAddr) -> Result<()> {
+        let metrics = self.clone();
+        thread::Builder::new()
+            .name("metrics".to_string())
+            .spawn(move || {
+                let route = warp::path("metrics").map(move || metrics.render());
+                let mut runtime = tokio::runtime::Runtime::new().unwrap();
+                runtime.block_on(warp::serve(route).run(addr));
+            })?;
+        Ok(())
+    }
+}
+
+fn header(out: &mut String, name: &str, kind: &str, help: &str) {
+    writeln!(out, "# HELP {} {}", name, help).unwrap();
+    writeln!(out, "# TYPE {} {}", name, kind).unwrap();
+}
@@ -0,0 +1,132 @@
+//! Alerts on markets whose event or request queues are backing up.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::{thread, time};
+
+use anyhow::{format_err, Result};
+use serde_json::json;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{get_keys_for_market, parse_event_queue, parse_req_queue, remove_dex_account_padding};
+
+#[derive(Debug, Clone, Copy)]
+pub struct QueueLengths {
+    pub event_q: usize,
+    pub req_q: usize,
+}
+
+pub fn queue_lengths(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    market: &Pubkey,
+) -> Result<QueueLengths> {
+    let market_keys = get_keys_for_market(client, program_id, market)?;
+    let read = |key: &Pubkey| -> Result<Vec<u8>> {
+        Ok(client
+            .get_account_with_commitment(key, CommitmentConfig::recent())?
+            .value
+            .ok_or_else(|| format_err!("account {} not found", key))?
+            .data)
+    };
+
+    let event_q_data = read(&market_keys.event_q)?;
+    let inner: Cow<[u64]> = remove_dex_account_padding(&event_q_data)?;
+    let (_header, seg0, seg1) = parse_event_queue(&inner)?;
+    let event_q = seg0.len() + seg1.len();
+
+    let req_q_data = read(&market_keys.req_q)?;
+    let inner: Cow<[u64]> = remove_dex_account_padding(&req_q_data)?;
+    let (_header, seg0, seg1) = parse_req_queue(&inner)?;
+    let req_q = seg0.len() + seg1.len();
+
+    Ok(QueueLengths { event_q, req_q })
+}
+
+/// Checks the queues of `markets` against `threshold`, posting to `webhook`
+/// when a market's backlog goes over it and again when it recovers. Without
+/// an `interval` the markets are checked once, and an error is returned if
+/// any is over the threshold or can't be read.
+pub fn watch(
+    client: &RpcClient,
+    program_id: &Pubkey,
+    markets: &[Pubkey],
+    threshold: usize,
+    webhook: Option<&str>,
+    interval: Option<time::Duration>,
+) -> Result<()> {
+    let mut alerting = BTreeSet::new();
+    loop {
+        let mut unhealthy = vec![];
+        for market in markets {
+            let lengths = match queue_lengths(client, program_id, market) {
+                Ok(lengths) => lengths,
+                Err(err) => {
+                    eprintln!("Failed to read queues of {}: {:?}", market, err);
+                    unhealthy.push(*market);
+                    continue;
+                }
+            };
+            println!(
+                "market {}: event queue {}, request queue {}",
+                market, lengths.event_q, lengths.req_q
+            );
+            let backed_up = lengths.event_q > threshold || lengths.req_q > threshold;
+            if backed_up {
+                unhealthy.push(*market);
+            }
+            let text = match (backed_up, alerting.contains(market)) {
+                (true, false) => {
+                    alerting.insert(*market);
+                    format!(
+                        "Serum market {} is backed up: event queue {}, request queue {}, threshold {}",
+                        market, lengths.event_q, lengths.req_q, threshold
+                    )
+                }
+                (false, true) => {
+                    alerting.remove(market);
+                    format!(
+                        "Serum market {} recovered: event queue {}, request queue {}",
+                        market, lengths.event_q, lengths.req_q
+                    )
+                }
+                _ => continue,
+            };
+            if let Some(url) = webhook {
+                let body = json!({
+                    "text": text,
+                    "market": market.to_string(),
+                    "event_queue_length": lengths.event_q,
+                    "request_queue_length": lengths.req_q,
+                    "threshold": threshold,
+                });
+                if let Err(err) = post(url, &body) {
+                    eprintln!("Failed to post to webhook: {:?}", err);
+                }
+            }
+        }
+
+        match interval {
+            Some(interval) => thread::sleep(interval),
+            None if unhealthy.is_empty() => return Ok(()),
+            None => {
+                return Err(format_err!(
+                    "{} market(s) backed up or unreadable: {:?}",
+                    unhealthy.len(),
+                    unhealthy
+                ))
+            }
+        }
+    }
+}
+
+fn post(url: &str, body: &serde_json::Value) -> Result<()> {
+    reqwest::blocking::Client::new()
+        .post(url)
+        .json(body)
+        .send()?
+        .error_for_status()?;
+    Ok(())
+}
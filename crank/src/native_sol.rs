@@ -0,0 +1,71 @@
+//! Temporary wrapped SOL accounts, so SOL pairs can be traded from and
+//! settled into a plain system account.
+
+use anyhow::Result;
+use rand::rngs::OsRng;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use spl_token::instruction as token_instruction;
+
+/// A wrapped SOL token account that is opened and closed within a single
+/// transaction.
+pub struct TempWsolAccount {
+    keypair: Keypair,
+    lamports: u64,
+}
+
+impl TempWsolAccount {
+    /// Wraps `amount` lamports on top of the account's rent exempt reserve.
+    pub fn new(client: &RpcClient, amount: u64) -> Result<Self> {
+        let rent = client.get_minimum_balance_for_rent_exemption(spl_token::state::Account::LEN)?;
+        Ok(TempWsolAccount {
+            keypair: Keypair::generate(&mut OsRng),
+            lamports: rent + amount,
+        })
+    }
+
+    pub fn pubkey(&self) -> Pubkey {
+        self.keypair.pubkey()
+    }
+
+    /// Must sign the transaction containing `open_instructions`.
+    pub fn keypair(&self) -> &Keypair {
+        &self.keypair
+    }
+
+    /// Funds the account from `payer` and initializes it as a native mint
+    /// token account owned by `owner`.
+    pub fn open_instructions(&self, payer: &Pubkey, owner: &Pubkey) -> Result<Vec<Instruction>> {
+        Ok(vec![
+            system_instruction::create_account(
+                payer,
+                &self.pubkey(),
+                self.lamports,
+                spl_token::state::Account::LEN as u64,
+                &spl_token::ID,
+            ),
+            token_instruction::initialize_account(
+                &spl_token::ID,
+                &self.pubkey(),
+                &spl_token::native_mint::ID,
+                owner,
+            )?,
+        ])
+    }
+
+    /// Closes the account, returning everything left in it, including the
+    /// rent reserve, to `owner` as SOL.
+    pub fn close_instruction(&self, owner: &Pubkey) -> Result<Instruction> {
+        Ok(token_instruction::close_account(
+            &spl_token::ID,
+            &self.pubkey(),
+            owner,
+            owner,
+            &[],
+        )?)
+    }
+}
@@ -0,0 +1,126 @@
+//! Every open orders account a wallet owns, on any market, with the orders
+//! resting in it and the funds it holds.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::convert::identity;
+
+use anyhow::{format_err, Result};
+use bytemuck::{bytes_of, cast_slice};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use serum_dex::matching::Side;
+use serum_dex::state::{MarketState, OpenOrders};
+
+use crate::{find_open_orders, read_pod, remove_dex_account_padding};
+
+#[derive(Debug, Serialize)]
+pub struct Portfolio {
+    pub owner: String,
+    pub accounts: Vec<OpenOrdersSummary>,
+    /// Lamports held for rent by all of the accounts.
+    pub rent_locked: u64,
+}
+
+/// An open orders account. Balances are in native units, and the part of a
+/// total that isn't free is locked in resting orders.
+#[derive(Debug, Serialize)]
+pub struct OpenOrdersSummary {
+    pub address: String,
+    pub market: String,
+    pub coin_mint: String,
+    pub pc_mint: String,
+    pub coin_free: u64,
+    pub coin_total: u64,
+    pub pc_free: u64,
+    pub pc_total: u64,
+    pub referrer_rebates_accrued: u64,
+    /// Held for rent, returned when the account is closed.
+    pub lamports: u64,
+    pub orders: Vec<RestingOrder>,
+}
+
+/// An order resting on the book. Prices are in lots.
+#[derive(Debug, Serialize)]
+pub struct RestingOrder {
+    pub side: Side,
+    pub price: u64,
+    // A string, as JSON numbers can't hold a u128.
+    pub order_id: String,
+    pub client_order_id: u64,
+}
+
+impl Portfolio {
+    /// Accounts with nothing locked in orders but funds left to settle.
+    pub fn unsettled(&self) -> impl Iterator<Item = &OpenOrdersSummary> {
+        self.accounts
+            .iter()
+            .filter(|account| account.orders.is_empty())
+            .filter(|account| account.coin_total > 0 || account.pc_total > 0)
+    }
+}
+
+pub fn portfolio(
+    client: &RpcClient,
+    rpc_url: &str,
+    program_id: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Portfolio> {
+    // Mints of each market, fetched once however many accounts use it.
+    let mut mints: BTreeMap<Pubkey, (Pubkey, Pubkey)> = BTreeMap::new();
+    let mut accounts = vec![];
+    for address in find_open_orders(rpc_url, program_id, None, owner)? {
+        let account = client
+            .get_account(&address)
+            .map_err(|e| format_err!("{}: {}", address, e))?;
+        let words: Cow<[u64]> = remove_dex_account_padding(&account.data)?;
+        let open_orders: OpenOrders = read_pod(cast_slice(&words))?;
+        let market = Pubkey::new(bytes_of(&identity(open_orders.market)));
+        let (coin_mint, pc_mint) = match mints.get(&market) {
+            Some(mints) => *mints,
+            None => {
+                let market_data = client.get_account_data(&market)?;
+                let words: Cow<[u64]> = remove_dex_account_padding(&market_data)?;
+                let market_state: MarketState = read_pod(cast_slice(&words))?;
+                let market_mints = (
+                    Pubkey::new(bytes_of(&identity(market_state.coin_mint))),
+                    Pubkey::new(bytes_of(&identity(market_state.pc_mint))),
+                );
+                *mints.entry(market).or_insert(market_mints)
+            }
+        };
+        accounts.push(OpenOrdersSummary {
+            address: address.to_string(),
+            market: market.to_string(),
+            coin_mint: coin_mint.to_string(),
+            pc_mint: pc_mint.to_string(),
+            coin_free: open_orders.native_coin_free,
+            coin_total: open_orders.native_coin_total,
+            pc_free: open_orders.native_pc_free,
+            pc_total: open_orders.native_pc_total,
+            referrer_rebates_accrued: open_orders.referrer_rebates_accrued,
+            lamports: account.lamports,
+            orders: resting_orders(&open_orders),
+        });
+    }
+    Ok(Portfolio {
+        owner: owner.to_string(),
+        rent_locked: accounts.iter().map(|account| account.lamports).sum(),
+        accounts,
+    })
+}
+
+fn resting_orders(open_orders: &OpenOrders) -> Vec<RestingOrder> {
+    open_orders
+        .iter_orders()
+        .map(|(_, order_id, client_order_id, side)| RestingOrder {
+            side,
+            // The price is the top half of the order id.
+            price: (order_id >> 64) as u64,
+            order_id: order_id.to_string(),
+            client_order_id,
+        })
+        .collect()
+}
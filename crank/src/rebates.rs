@@ -0,0 +1,169 @@
+//! Referrer rebates earned by the crank operator on other users' settles.
+//!
+//! A user settling with the operator's wallet as referrer has the rebates
+//! their fills accrued paid straight into that wallet. The operator gives a
+//! referrer wallet per quote mint, and the crank sweeps whatever lands in
+//! them into a single quote token account.
+
+use anyhow::{format_err, Result};
+use log::error;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use spl_token::instruction as token_instruction;
+use spl_token::state::Account as TokenAccount;
+
+use serum_common::client::rpc::{get_token_account, send_instructions};
+
+use crate::compute_budget::ComputeBudget;
+
+/// Transfers every token in `wallet`, a referrer wallet owned by `owner`,
+/// into `destination`. None if there's nothing to sweep.
+pub fn sweep_instruction(
+    wallet_address: &Pubkey,
+    wallet: &TokenAccount,
+    destination: &Pubkey,
+    destination_mint: &Pubkey,
+    owner: &Pubkey,
+) -> Result<Option<Instruction>> {
+    if wallet.owner != *owner {
+        return Err(format_err!(
+            "referrer wallet {} isn't owned by {}",
+            wallet_address,
+            owner
+        ));
+    }
+    if wallet.mint != *destination_mint {
+        return Err(format_err!(
+            "referrer wallet {} holds mint {}, not {}",
+            wallet_address,
+            wallet.mint,
+            destination_mint
+        ));
+    }
+    if wallet.amount == 0 || wallet_address == destination {
+        return Ok(None);
+    }
+    let instruction = token_instruction::transfer(
+        &spl_token::ID,
+        wallet_address,
+        destination,
+        owner,
+        &[],
+        wallet.amount,
+    )?;
+    Ok(Some(instruction))
+}
+
+/// Sweeps the rebates paid into `referrer_wallets` into `destination` in
+/// one transaction, returning the amount swept. A wallet that can't be
+/// swept is logged and skipped.
+pub fn sweep_referrer_wallets(
+    client: &RpcClient,
+    payer: &Keypair,
+    destination: &Pubkey,
+    referrer_wallets: &[Pubkey],
+    compute_budget: &ComputeBudget,
+) -> Result<u64> {
+    let destination_mint = get_token_account::<TokenAccount>(client, destination)?.mint;
+    let mut swept = 0;
+    let mut instructions = vec![];
+    for wallet_address in referrer_wallets {
+        let instruction =
+            get_token_account::<TokenAccount>(client, wallet_address).and_then(|wallet| {
+                let instruction = sweep_instruction(
+                    wallet_address,
+                    &wallet,
+                    destination,
+                    &destination_mint,
+                    &payer.pubkey(),
+                )?;
+                Ok(instruction.map(|instruction| (instruction, wallet.amount)))
+            });
+        match instruction {
+            Ok(None) => {}
+            Ok(Some((instruction, amount))) => {
+                instructions.push(instruction);
+                swept += amount;
+            }
+            Err(err) => error!(
+                "Failed to sweep referrer wallet {}: {:?}",
+                wallet_address, err
+            ),
+        }
+    }
+    if instructions.is_empty() {
+        return Ok(0);
+    }
+    compute_budget.prepend(&mut instructions)?;
+    send_instructions(client, &instructions, &payer.pubkey(), &[payer])?;
+    Ok(swept)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use spl_token::state::AccountState;
+
+    fn wallet(mint: Pubkey, owner: Pubkey, amount: u64) -> TokenAccount {
+        TokenAccount {
+            mint,
+            owner,
+            amount,
+            state: AccountState::Initialized,
+            ..TokenAccount::default()
+        }
+    }
+
+    #[test]
+    fn sweeps_the_whole_balance() {
+        let (address, destination, mint, owner) = (
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+        );
+        let instruction = sweep_instruction(
+            &address,
+            &wallet(mint, owner, 1_234),
+            &destination,
+            &mint,
+            &owner,
+        )
+        .unwrap()
+        .unwrap();
+        let expected =
+            token_instruction::transfer(&spl_token::ID, &address, &destination, &owner, &[], 1_234)
+                .unwrap();
+        assert_eq!(instruction, expected);
+
+        // Nothing to sweep from an empty wallet or the destination itself.
+        let empty = wallet(mint, owner, 0);
+        assert!(
+            sweep_instruction(&address, &empty, &destination, &mint, &owner)
+                .unwrap()
+                .is_none()
+        );
+        let full = wallet(mint, owner, 1);
+        assert!(
+            sweep_instruction(&destination, &full, &destination, &mint, &owner)
+                .unwrap()
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn rejects_foreign_wallets() {
+        let (address, destination, mint, owner) = (
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+        );
+        let other_owner = wallet(mint, Pubkey::new_rand(), 1);
+        assert!(sweep_instruction(&address, &other_owner, &destination, &mint, &owner).is_err());
+        let other_mint = wallet(Pubkey::new_rand(), owner, 1);
+        assert!(sweep_instruction(&address, &other_mint, &destination, &mint, &owner).is_err());
+    }
+}
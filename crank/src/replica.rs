@@ -0,0 +1,290 @@
+//! An in-memory replica of a market's order book, kept up to date from
+//! `accountSubscribe` websocket notifications for its bids, asks and event
+//! queue.
+//!
+//! The replica boots from RPC snapshots of the three accounts. Each slab
+//! notification replaces that side of the book, and each event queue
+//! notification yields the events pushed since the last one. Notifications
+//! older than the state already applied are dropped. If events were
+//! consumed before the replica saw them, or the websocket drops, the
+//! replica fetches every account again and reports the resync.
+
+use std::collections::HashMap;
+
+use anyhow::{format_err, Result};
+use log::{info, warn};
+use reqwest::Url;
+use serde_json::json;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use tungstenite::client::AutoStream;
+use tungstenite::{Message, WebSocket};
+
+use serum_dex::client::{EventQueueReader, OrderBookReader, QueueEvent};
+use serum_dex::matching::Side;
+
+use crate::get_keys_for_market;
+use crate::subscription::ws_url;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Account {
+    Bids,
+    Asks,
+    EventQueue,
+}
+
+const ACCOUNTS: [Account; 3] = [Account::Bids, Account::Asks, Account::EventQueue];
+
+#[derive(Debug)]
+pub enum ReplicaUpdate {
+    /// That side of the book changed.
+    Book(Side),
+    /// Events pushed to the queue since the last update, with their
+    /// sequence numbers.
+    Events(Vec<(u64, QueueEvent)>),
+    /// Updates may have been missed, so the book was fetched again. Events
+    /// pushed in the meantime are lost.
+    Resynced,
+}
+
+pub struct BookReplica {
+    client: RpcClient,
+    ws_url: Url,
+    addresses: HashMap<Account, Pubkey>,
+    socket: Option<WebSocket<AutoStream>>,
+    // Pending subscription request ids, then subscription ids, to the
+    // account each is for.
+    requests: HashMap<u64, Account>,
+    subscriptions: HashMap<u64, Account>,
+    // Account data, checked when it was applied.
+    data: HashMap<Account, Vec<u8>>,
+    // Slot of the last update applied to each account.
+    slots: HashMap<Account, u64>,
+    // Sequence number of the next event to report.
+    event_seq_num: u64,
+}
+
+impl BookReplica {
+    pub fn connect(rpc_url: &str, program_id: &Pubkey, market: &Pubkey) -> Result<Self> {
+        let client = RpcClient::new(rpc_url.to_string());
+        let keys = get_keys_for_market(&client, program_id, market)?;
+        let addresses = vec![
+            (Account::Bids, *keys.bids),
+            (Account::Asks, *keys.asks),
+            (Account::EventQueue, *keys.event_q),
+        ]
+        .into_iter()
+        .collect();
+        let mut replica = BookReplica {
+            client,
+            ws_url: ws_url(rpc_url)?,
+            addresses,
+            socket: None,
+            requests: HashMap::new(),
+            subscriptions: HashMap::new(),
+            data: HashMap::new(),
+            slots: HashMap::new(),
+            event_seq_num: 0,
+        };
+        replica.reconnect()?;
+        Ok(replica)
+    }
+
+    /// Orders on the bid side, best price first.
+    pub fn bids(&self) -> OrderBookReader<'_> {
+        self.book(Account::Bids)
+    }
+
+    /// Orders on the ask side, best price first.
+    pub fn asks(&self) -> OrderBookReader<'_> {
+        self.book(Account::Asks)
+    }
+
+    /// Blocks until the next change to the book or event queue. Websocket
+    /// errors are recovered from by reconnecting, which is reported as a
+    /// resync.
+    pub fn next_update(&mut self) -> Result<ReplicaUpdate> {
+        loop {
+            let result = match self.read_notification() {
+                Ok((account, slot, data)) => self.apply(account, slot, data),
+                Err(err) => Err(err),
+            };
+            match result {
+                Ok(Some(update)) => return Ok(update),
+                Ok(None) => continue,
+                Err(err) => {
+                    warn!("Book replica lost sync, resubscribing: {:?}", err);
+                    self.reconnect()?;
+                    return Ok(ReplicaUpdate::Resynced);
+                }
+            }
+        }
+    }
+
+    fn book(&self, account: Account) -> OrderBookReader<'_> {
+        OrderBookReader::new(&self.data[&account]).expect("checked when applied")
+    }
+
+    // Subscribes to every account, then fetches them, so no change between
+    // the two is missed.
+    fn reconnect(&mut self) -> Result<()> {
+        self.socket = None;
+        self.requests.clear();
+        self.subscriptions.clear();
+        let (mut socket, _) = tungstenite::connect(self.ws_url.clone())?;
+        for (id, account) in ACCOUNTS.iter().enumerate() {
+            let request = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "accountSubscribe",
+                "params": [
+                    self.addresses[account].to_string(),
+                    {"encoding": "base64", "commitment": "recent"},
+                ],
+            });
+            socket.write_message(Message::Text(request.to_string()))?;
+            self.requests.insert(id as u64, *account);
+        }
+        self.socket = Some(socket);
+        self.resync()
+    }
+
+    fn resync(&mut self) -> Result<()> {
+        self.slots.clear();
+        for account in ACCOUNTS.iter() {
+            let response = self.client.get_account_with_commitment(
+                &self.addresses[account],
+                CommitmentConfig::recent(),
+            )?;
+            let data = response
+                .value
+                .ok_or_else(|| format_err!("account {} not found", self.addresses[account]))?
+                .data;
+            self.check(*account, &data)?;
+            if *account == Account::EventQueue {
+                self.event_seq_num = EventQueueReader::new(&data)?.seq_num();
+            }
+            self.data.insert(*account, data);
+            self.slots.insert(*account, response.context.slot);
+        }
+        info!(
+            "Book replica synced at slot {}",
+            self.slots[&Account::EventQueue]
+        );
+        Ok(())
+    }
+
+    fn check(&self, account: Account, data: &[u8]) -> Result<()> {
+        match account {
+            Account::Bids | Account::Asks => {
+                let side = OrderBookReader::new(data)?.side();
+                let expected = match account {
+                    Account::Bids => Side::Bid,
+                    _ => Side::Ask,
+                };
+                if side != expected {
+                    return Err(format_err!("{:?} account holds {:?}s", account, side));
+                }
+            }
+            Account::EventQueue => {
+                EventQueueReader::new(data)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Applies a notification, returning the update to report, if any.
+    fn apply(
+        &mut self,
+        account: Account,
+        slot: u64,
+        data: Vec<u8>,
+    ) -> Result<Option<ReplicaUpdate>> {
+        if slot < self.slots.get(&account).copied().unwrap_or(0) {
+            return Ok(None);
+        }
+        self.check(account, &data)?;
+        let update = match account {
+            Account::Bids => Some(ReplicaUpdate::Book(Side::Bid)),
+            Account::Asks => Some(ReplicaUpdate::Book(Side::Ask)),
+            Account::EventQueue => {
+                let queue = EventQueueReader::new(&data)?;
+                let oldest = queue.seq_num().wrapping_sub(queue.len() as u64);
+                // Events are consumed oldest first, so the ones already
+                // reported are at the front.
+                let reported = self.event_seq_num.wrapping_sub(oldest);
+                if reported > queue.len() as u64 {
+                    return Err(format_err!(
+                        "missed events {} to {}",
+                        self.event_seq_num,
+                        oldest
+                    ));
+                }
+                let events = queue
+                    .iter()
+                    .skip(reported as usize)
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.event_seq_num = queue.seq_num();
+                match events.is_empty() {
+                    true => None,
+                    false => Some(ReplicaUpdate::Events(events)),
+                }
+            }
+        };
+        self.data.insert(account, data);
+        self.slots.insert(account, slot);
+        Ok(update)
+    }
+
+    // Reads up to the next account notification, recording subscription
+    // ids as they're confirmed.
+    fn read_notification(&mut self) -> Result<(Account, u64, Vec<u8>)> {
+        let socket = self
+            .socket
+            .as_mut()
+            .ok_or_else(|| format_err!("not connected"))?;
+        loop {
+            let text = match socket.read_message()? {
+                Message::Text(text) => text,
+                Message::Ping(data) => {
+                    socket.write_message(Message::Pong(data))?;
+                    continue;
+                }
+                Message::Close(_) => return Err(format_err!("websocket closed")),
+                _ => continue,
+            };
+            let message: serde_json::Value = serde_json::from_str(&text)?;
+            if let Some(err) = message.get("error") {
+                return Err(format_err!("accountSubscribe failed: {}", err));
+            }
+            if let (Some(id), Some(subscription)) =
+                (message["id"].as_u64(), message["result"].as_u64())
+            {
+                if let Some(account) = self.requests.remove(&id) {
+                    self.subscriptions.insert(subscription, account);
+                }
+                continue;
+            }
+            if message["method"] != "accountNotification" {
+                continue;
+            }
+            let params = &message["params"];
+            let account = match params["subscription"]
+                .as_u64()
+                .and_then(|id| self.subscriptions.get(&id))
+            {
+                Some(account) => *account,
+                None => continue,
+            };
+            let malformed = || format_err!("unexpected notification: {}", text);
+            let slot = params["result"]["context"]["slot"]
+                .as_u64()
+                .ok_or_else(malformed)?;
+            let data = params["result"]["value"]["data"][0]
+                .as_str()
+                .ok_or_else(malformed)?;
+            return Ok((account, slot, base64::decode(data)?));
+        }
+    }
+}
@@ -0,0 +1,241 @@
+//! Shares one budget of in-flight ConsumeEvents transactions between many
+//! markets, spending it on the markets with the most queued events first.
+//! A market that has waited longer than the starvation limit is served
+//! before any other, however short its queue.
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use std::{cmp, thread};
+
+use anyhow::{format_err, Result};
+use bytemuck::bytes_of;
+use log::info;
+use serde::Deserialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+
+use serum_dex::state::QueueHeader;
+
+use crate::compute_budget::ComputeBudget;
+use crate::daemon::MarketConfig;
+use crate::endpoints::RpcEndpoints;
+use crate::metrics::Metrics;
+use crate::throttle::InFlight;
+use crate::{get_keys_for_market, parse_event_queue, remove_dex_account_padding, MarketPubkeys};
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SchedulerConfig {
+    /// Most unconfirmed transactions across all markets.
+    pub max_in_flight: usize,
+    /// Longest a market with queued events may go unserved.
+    #[serde(default = "default_max_starvation_ms")]
+    pub max_starvation_ms: u64,
+    /// Time between reads of the event queues.
+    #[serde(default = "default_poll_interval_ms")]
+    pub poll_interval_ms: u64,
+}
+
+fn default_max_starvation_ms() -> u64 {
+    10_000
+}
+
+fn default_poll_interval_ms() -> u64 {
+    300
+}
+
+struct Market {
+    config: MarketConfig,
+    keys: MarketPubkeys,
+    in_flight: Arc<Mutex<InFlight>>,
+    // Last time the market was served or had nothing to serve.
+    last_served: Instant,
+    // Index of the payer the next transaction is sent with.
+    next_payer: usize,
+}
+
+/// Cranks `markets` until an RPC request fails.
+pub fn run(
+    rpc: &RpcEndpoints,
+    markets: &[MarketConfig],
+    config: &SchedulerConfig,
+    compute_budget: &ComputeBudget,
+    metrics: &Metrics,
+) -> Result<()> {
+    let session = rpc.session();
+    let client = session.client();
+    let max_starvation = Duration::from_millis(config.max_starvation_ms);
+    let mut markets = markets
+        .iter()
+        .map(|config| {
+            Ok(Market {
+                keys: get_keys_for_market(client, &config.dex_program_id, &config.market)?,
+                in_flight: Arc::new(Mutex::new(InFlight::new(
+                    config.max_in_flight.unwrap_or(config.num_workers),
+                    Duration::from_millis(config.target_confirmation_ms),
+                ))),
+                last_served: Instant::now(),
+                next_payer: 0,
+                config: config.clone(),
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+    let pool = threadpool::ThreadPool::new(config.max_in_flight.max(1));
+
+    loop {
+        thread::sleep(Duration::from_millis(config.poll_interval_ms));
+
+        let mut pending = 0;
+        for market in &markets {
+            let mut in_flight = market.in_flight.lock().unwrap();
+            let finished = in_flight.poll(client)?;
+            metrics.record_finished(&market.config.market, &finished);
+            pending += in_flight.pending();
+        }
+        let mut budget = config.max_in_flight.saturating_sub(pending);
+        if budget == 0 {
+            info!("{} transactions in flight, waiting", pending);
+            continue;
+        }
+
+        let now = Instant::now();
+        let mut queues = Vec::with_capacity(markets.len());
+        for market in &mut markets {
+            let (backlog, account_metas) = read_queue(client, market, metrics)?;
+            if backlog == 0 {
+                market.last_served = now;
+            }
+            queues.push((
+                backlog,
+                now.duration_since(market.last_served),
+                account_metas,
+            ));
+        }
+
+        for i in priority_order(&queues, max_starvation) {
+            if budget == 0 {
+                break;
+            }
+            let market = &mut markets[i];
+            let (backlog, waited, account_metas) = &queues[i];
+            let c = &market.config;
+            let wanted = (backlog + c.events_per_worker - 1) / c.events_per_worker;
+            let available = market.in_flight.lock().unwrap().available();
+            let num_txs = cmp::min(cmp::min(wanted, c.num_workers), cmp::min(available, budget));
+            if num_txs == 0 {
+                continue;
+            }
+            info!(
+                "Sending {} transactions for {}, {} events queued for {:?}",
+                num_txs,
+                c.name(),
+                backlog,
+                waited
+            );
+            let payers = c.payers();
+            let program_id = c.dex_program_id;
+            let market_key = c.market;
+            let events_per_worker = c.events_per_worker;
+            for thread_num in 0..num_txs {
+                let session = session.clone();
+                let payer = payers[market.next_payer % payers.len()].read()?;
+                market.next_payer += 1;
+                let account_metas = account_metas.clone();
+                let in_flight = market.in_flight.clone();
+                let compute_budget = compute_budget.clone();
+                let metrics = metrics.clone();
+                pool.execute(move || {
+                    crate::consume_events_wrapper(
+                        &session,
+                        &program_id,
+                        &payer,
+                        account_metas,
+                        thread_num,
+                        events_per_worker,
+                        &in_flight,
+                        &compute_budget,
+                        &market_key,
+                        &metrics,
+                    )
+                });
+            }
+            market.last_served = now;
+            budget -= num_txs;
+        }
+        pool.join();
+    }
+}
+
+/// Indices of the markets with queued events, in the order they should be
+/// served: markets that have waited `max_starvation` or longer, longest wait
+/// first, then the rest, longest queue first.
+fn priority_order<T>(queues: &[(usize, Duration, T)], max_starvation: Duration) -> Vec<usize> {
+    let mut starved = vec![];
+    let mut rest = vec![];
+    for (i, (backlog, waited, _)) in queues.iter().enumerate() {
+        if *backlog == 0 {
+            continue;
+        }
+        if *waited >= max_starvation {
+            starved.push(i);
+        } else {
+            rest.push(i);
+        }
+    }
+    starved.sort_by_key(|&i| cmp::Reverse(queues[i].1));
+    rest.sort_by_key(|&i| cmp::Reverse(queues[i].0));
+    starved.extend(rest);
+    starved
+}
+
+// Reads the market's event queue, returning its length and the accounts of
+// a ConsumeEvents instruction for it.
+fn read_queue(
+    client: &RpcClient,
+    market: &Market,
+    metrics: &Metrics,
+) -> Result<(usize, Vec<AccountMeta>)> {
+    let c = &market.config;
+    let event_q_data = client
+        .get_account_with_commitment(&market.keys.event_q, CommitmentConfig::recent())?
+        .value
+        .ok_or_else(|| format_err!("event queue {} not found", market.keys.event_q))?
+        .data;
+    let inner: Cow<[u64]> = remove_dex_account_padding(&event_q_data)?;
+    let (header, seg0, seg1) = parse_event_queue(&inner)?;
+    metrics.record_queue(&c.market, header.seq_num(), header.count());
+
+    let mut owners = BTreeSet::new();
+    for event in seg0.iter().chain(seg1.iter()) {
+        owners.insert(event.owner);
+        if owners.len() >= c.num_accounts {
+            break;
+        }
+    }
+    let mut account_metas = Vec::with_capacity(owners.len() + 4);
+    for owner in owners {
+        account_metas.push(AccountMeta::new(Pubkey::new(bytes_of(&owner)), false));
+    }
+    for pubkey in [
+        &*market.keys.market,
+        &*market.keys.event_q,
+        &c.coin_wallet,
+        &c.pc_wallet,
+    ]
+    .iter()
+    {
+        account_metas.push(AccountMeta::new(**pubkey, false));
+    }
+    if seg0.len() + seg1.len() > 0 {
+        info!(
+            "Event queue length {}, market {}",
+            seg0.len() + seg1.len(),
+            c.name()
+        );
+    }
+    Ok((seg0.len() + seg1.len(), account_metas))
+}
@@ -0,0 +1,134 @@
+//! Level 3 order book snapshots: every resting order on both sides of a
+//! market, best price first.
+
+use std::borrow::Cow;
+use std::io::Write;
+use std::str::FromStr;
+
+use anyhow::{format_err, Result};
+use bytemuck::{bytes_of, cast_slice};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use serum_dex::critbit::Slab;
+use serum_dex::matching::Side;
+use serum_dex::state::MarketState;
+
+use crate::{get_keys_for_market, read_pod, remove_dex_account_padding};
+
+#[derive(Debug, Serialize)]
+pub struct Snapshot {
+    pub market: String,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+    /// Highest price first.
+    pub bids: Vec<Order>,
+    /// Lowest price first.
+    pub asks: Vec<Order>,
+}
+
+/// A resting order. Prices and quantities are in lots.
+#[derive(Debug, Serialize)]
+pub struct Order {
+    pub price: u64,
+    pub quantity: u64,
+    // A string, as JSON numbers can't hold a u128.
+    pub order_id: String,
+    /// The open orders account that placed the order.
+    pub owner: String,
+    pub owner_slot: u8,
+    pub client_order_id: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(Format::Json),
+            "csv" => Ok(Format::Csv),
+            _ => Err(format_err!("expected json or csv, got {}", s)),
+        }
+    }
+}
+
+pub fn snapshot(client: &RpcClient, program_id: &Pubkey, market: &Pubkey) -> Result<Snapshot> {
+    let market_data = client.get_account_data(market)?;
+    let words: Cow<[u64]> = remove_dex_account_padding(&market_data)?;
+    let market_state: MarketState = read_pod(cast_slice(&words))?;
+    let market_keys = get_keys_for_market(client, program_id, market)?;
+    Ok(Snapshot {
+        market: market.to_string(),
+        coin_lot_size: market_state.coin_lot_size,
+        pc_lot_size: market_state.pc_lot_size,
+        bids: orders(client, &market_keys.bids, Side::Bid)?,
+        asks: orders(client, &market_keys.asks, Side::Ask)?,
+    })
+}
+
+// Drains a copy of the slab from the best price outwards.
+fn orders(client: &RpcClient, slab_address: &Pubkey, side: Side) -> Result<Vec<Order>> {
+    let data = client.get_account_data(slab_address)?;
+    let words: Cow<[u64]> = remove_dex_account_padding(&data)?;
+    // The first word holds the account flags.
+    let mut slab_bytes: Vec<u8> = cast_slice(&words[1..]).to_vec();
+    let slab = Slab::new(&mut slab_bytes);
+    let mut orders = vec![];
+    loop {
+        let leaf = match side {
+            Side::Bid => slab.remove_max(),
+            Side::Ask => slab.remove_min(),
+        };
+        let leaf = match leaf {
+            Some(leaf) => leaf,
+            None => break,
+        };
+        orders.push(Order {
+            price: leaf.price().get(),
+            quantity: leaf.quantity(),
+            order_id: leaf.order_id().to_string(),
+            owner: Pubkey::new(bytes_of(&leaf.owner())).to_string(),
+            owner_slot: leaf.owner_slot(),
+            client_order_id: leaf.client_order_id(),
+        });
+    }
+    Ok(orders)
+}
+
+pub fn write(snapshot: &Snapshot, format: Format, out: &mut dyn Write) -> Result<()> {
+    match format {
+        Format::Json => {
+            serde_json::to_writer_pretty(&mut *out, snapshot)?;
+            writeln!(out)?;
+        }
+        Format::Csv => {
+            writeln!(
+                out,
+                "side,price,quantity,order_id,owner,owner_slot,client_order_id"
+            )?;
+            for (side, orders) in &[("bid", &snapshot.bids), ("ask", &snapshot.asks)] {
+                for o in orders.iter() {
+                    writeln!(
+                        out,
+                        "{},{},{},{},{},{},{}",
+                        side,
+                        o.price,
+                        o.quantity,
+                        o.order_id,
+                        o.owner,
+                        o.owner_slot,
+                        o.client_order_id
+                    )?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
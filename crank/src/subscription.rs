@@ -0,0 +1,167 @@
+//! Watches an event queue over an `accountSubscribe` websocket, so an idle
+//! crank can wait for events instead of polling for them.
+
+use std::borrow::Cow;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use anyhow::{format_err, Result};
+use log::{error, info};
+use reqwest::Url;
+use serde_json::json;
+use solana_sdk::pubkey::Pubkey;
+use tungstenite::Message;
+
+use serum_dex::state::QueueHeader;
+
+use crate::{parse_event_queue, remove_dex_account_padding};
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+#[derive(Default)]
+struct State {
+    connected: bool,
+    // Head and count of the queue in the last notification.
+    head: Option<(u64, u64)>,
+    changed: bool,
+}
+
+/// Handle to a thread that keeps a subscription to one event queue open,
+/// reconnecting whenever it drops. The thread exits after the last handle
+/// is dropped.
+#[derive(Clone)]
+pub struct QueueWatcher {
+    state: Arc<(Mutex<State>, Condvar)>,
+}
+
+impl QueueWatcher {
+    pub fn spawn(rpc_url: &str, event_q: Pubkey) -> Result<Self> {
+        let url = ws_url(rpc_url)?;
+        let watcher = QueueWatcher {
+            state: Arc::new((Mutex::new(State::default()), Condvar::new())),
+        };
+        let w = watcher.clone();
+        thread::Builder::new()
+            .name(format!("subscribe-{}", event_q))
+            .spawn(move || {
+                while !w.abandoned() {
+                    if let Err(err) = w.subscribe(&url, &event_q) {
+                        error!("Event queue subscription failed: {:?}", err);
+                    }
+                    w.set_connected(false);
+                    thread::sleep(RECONNECT_DELAY);
+                }
+            })?;
+        Ok(watcher)
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.state.0.lock().unwrap().connected
+    }
+
+    /// Blocks until the queue's head or count changes, the subscription
+    /// drops or `timeout` passes. Returns right away if the queue changed
+    /// since the last call.
+    pub fn wait(&self, timeout: Duration) {
+        let (lock, cvar) = &*self.state;
+        let state = lock.lock().unwrap();
+        let (mut state, _) = cvar
+            .wait_timeout_while(state, timeout, |s| s.connected && !s.changed)
+            .unwrap();
+        state.changed = false;
+    }
+
+    // Whether the subscription thread holds the only handle.
+    fn abandoned(&self) -> bool {
+        Arc::strong_count(&self.state) == 1
+    }
+
+    fn set_connected(&self, connected: bool) {
+        let (lock, cvar) = &*self.state;
+        lock.lock().unwrap().connected = connected;
+        cvar.notify_all();
+    }
+
+    fn subscribe(&self, url: &Url, event_q: &Pubkey) -> Result<()> {
+        let (mut socket, _) = tungstenite::connect(url.clone())?;
+        let request = json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "method": "accountSubscribe",
+            "params": [
+                event_q.to_string(),
+                {"encoding": "base64", "commitment": "recent"},
+            ],
+        });
+        socket.write_message(Message::Text(request.to_string()))?;
+        info!("Subscribed to event queue {} at {}", event_q, url);
+        self.set_connected(true);
+
+        while !self.abandoned() {
+            let text = match socket.read_message()? {
+                Message::Text(text) => text,
+                Message::Ping(data) => {
+                    socket.write_message(Message::Pong(data))?;
+                    continue;
+                }
+                Message::Close(_) => return Err(format_err!("websocket closed")),
+                _ => continue,
+            };
+            let message: serde_json::Value = serde_json::from_str(&text)?;
+            if let Some(err) = message.get("error") {
+                return Err(format_err!("accountSubscribe failed: {}", err));
+            }
+            if message["method"] != "accountNotification" {
+                continue;
+            }
+            let data = message["params"]["result"]["value"]["data"][0]
+                .as_str()
+                .ok_or_else(|| format_err!("unexpected notification: {}", text))?;
+            self.update(&base64::decode(data)?)?;
+        }
+        Ok(())
+    }
+
+    fn update(&self, event_q_data: &[u8]) -> Result<()> {
+        let inner: Cow<[u64]> = remove_dex_account_padding(event_q_data)?;
+        let (header, _, _) = parse_event_queue(&inner)?;
+        let head = Some((header.head(), header.count()));
+        let (lock, cvar) = &*self.state;
+        let mut state = lock.lock().unwrap();
+        if state.head != head {
+            state.head = head;
+            state.changed = true;
+            cvar.notify_all();
+        }
+        Ok(())
+    }
+}
+
+// The RPC node serves websockets on the port after its HTTP port.
+pub(crate) fn ws_url(rpc_url: &str) -> Result<Url> {
+    let mut url = Url::parse(rpc_url)?;
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    url.set_scheme(scheme)
+        .map_err(|_| format_err!("can't make a websocket URL of {}", rpc_url))?;
+    if let Some(port) = url.port() {
+        url.set_port(Some(port + 1))
+            .map_err(|_| format_err!("can't make a websocket URL of {}", rpc_url))?;
+    }
+    Ok(url)
+}
+
+/// Sleeps for `poll_interval`, or while `watcher` is connected and the crank
+/// is idle, until the queue changes. A long wait still ends after `resync`,
+/// in case a notification was missed.
+pub fn wait_for_events(
+    watcher: Option<&QueueWatcher>,
+    idle: bool,
+    poll_interval: Duration,
+    resync: Duration,
+) {
+    match watcher {
+        Some(watcher) if idle && watcher.is_connected() => watcher.wait(resync),
+        _ => thread::sleep(poll_interval),
+    }
+}
@@ -0,0 +1,141 @@
+//! Caps the number of unconfirmed transactions the crank has outstanding,
+//! backing off while confirmations are slow.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::signature::Signature;
+
+// A transaction whose blockhash has expired can no longer land.
+const EXPIRY: Duration = Duration::from_secs(90);
+
+// Most signatures `getSignatureStatuses` accepts per request.
+const MAX_STATUSES_PER_REQUEST: usize = 256;
+
+pub struct InFlight {
+    max: usize,
+    limit: usize,
+    target_latency: Duration,
+    latency: Option<Duration>,
+    pending: Vec<(Signature, Instant, u64)>,
+}
+
+/// What became of a transaction that is no longer in flight.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Outcome {
+    Succeeded,
+    Failed,
+    Expired,
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct Finished {
+    pub outcome: Outcome,
+    pub latency: Duration,
+    /// Fee paid, which is zero for expired transactions.
+    pub fee: u64,
+}
+
+impl InFlight {
+    /// Allows up to `max` unconfirmed transactions while the average
+    /// confirmation latency stays under `target_latency`.
+    pub fn new(max: usize, target_latency: Duration) -> Self {
+        let max = max.max(1);
+        InFlight {
+            max,
+            limit: max,
+            target_latency,
+            latency: None,
+            pending: vec![],
+        }
+    }
+
+    /// Starts tracking a transaction that pays `fee` lamports if it lands.
+    pub fn track(&mut self, signature: Signature, fee: u64) {
+        self.pending.push((signature, Instant::now(), fee));
+    }
+
+    /// Number of transactions that may be sent right now.
+    pub fn available(&self) -> usize {
+        self.limit.saturating_sub(self.pending.len())
+    }
+
+    pub fn pending(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn limit(&self) -> usize {
+        self.limit
+    }
+
+    /// Forgets transactions that have been processed or have expired,
+    /// returning what became of them, and adjusts the limit to the latency
+    /// they saw. Expired transactions count as taking the full expiry, so an
+    /// RPC slowdown halves the limit until confirmations speed up again.
+    pub fn poll(&mut self, client: &RpcClient) -> Result<Vec<Finished>> {
+        let signatures: Vec<Signature> = self.pending.iter().map(|(sig, _, _)| *sig).collect();
+        let mut processed = Vec::with_capacity(signatures.len());
+        for chunk in signatures.chunks(MAX_STATUSES_PER_REQUEST) {
+            let statuses = client.get_signature_statuses(chunk)?.value;
+            processed.extend(
+                statuses
+                    .into_iter()
+                    .map(|status| status.map(|status| status.err.is_none())),
+            );
+        }
+
+        let mut finished = vec![];
+        let now = Instant::now();
+        let mut i = 0;
+        self.pending.retain(|&(_, sent, fee)| {
+            let latency = now.duration_since(sent);
+            let outcome = match processed[i] {
+                Some(true) => Some(Outcome::Succeeded),
+                Some(false) => Some(Outcome::Failed),
+                None if latency >= EXPIRY => Some(Outcome::Expired),
+                None => None,
+            };
+            i += 1;
+            match outcome {
+                None => true,
+                Some(outcome) => {
+                    let fee = if outcome == Outcome::Expired { 0 } else { fee };
+                    finished.push(Finished {
+                        outcome,
+                        latency,
+                        fee,
+                    });
+                    false
+                }
+            }
+        });
+        if finished.is_empty() {
+            return Ok(finished);
+        }
+        for f in &finished {
+            self.record(f.latency);
+        }
+        self.adjust();
+        Ok(finished)
+    }
+
+    fn record(&mut self, latency: Duration) {
+        self.latency = Some(match self.latency {
+            None => latency,
+            Some(average) => (average * 7 + latency) / 8,
+        });
+    }
+
+    // Additive increase, multiplicative decrease, at most once per poll so a
+    // burst of slow confirmations only halves the limit once.
+    fn adjust(&mut self) {
+        match self.latency {
+            Some(average) if average > self.target_latency => {
+                self.limit = (self.limit / 2).max(1);
+            }
+            _ if self.limit < self.max => self.limit += 1,
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,8 @@
+use anyhow::Result;
+use clap::Clap;
+use serum_data::Opts;
+
+fn main() -> Result<()> {
+    let opts = Opts::parse();
+    serum_data::start(opts)
+}
@@ -0,0 +1,199 @@
+//! Serves decoded market data over HTTP and WebSocket.
+//!
+//! * `GET /markets` lists the served markets.
+//! * `GET /markets/<market>/book?depth=<n>` returns aggregated price levels.
+//! * `GET /markets/<market>/trades` returns the trades still in the event queue.
+//! * `GET /markets/<market>/trades/ws` streams new trades as they happen.
+
+use std::convert::Infallible;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{format_err, Result};
+use clap::Clap;
+use futures::{SinkExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+use warp::http::StatusCode;
+use warp::ws::{Message, WebSocket};
+use warp::Filter;
+
+use serum_common::client::Cluster;
+
+pub mod market;
+
+use market::{Market, MarketInfo};
+
+#[derive(Clap, Debug)]
+pub struct Opts {
+    #[clap(default_value = "mainnet")]
+    pub cluster: Cluster,
+
+    #[clap(long, short)]
+    pub dex_program_id: Pubkey,
+
+    /// Market to serve. Repeat for each market.
+    #[clap(long, short)]
+    pub market: Vec<Pubkey>,
+
+    #[clap(long, default_value = "127.0.0.1")]
+    pub host: IpAddr,
+
+    #[clap(long, default_value = "8080")]
+    pub port: u16,
+
+    /// How often trade streams poll the event queue.
+    #[clap(long, default_value = "1000")]
+    pub poll_interval_ms: u64,
+}
+
+pub fn start(opts: Opts) -> Result<()> {
+    let client = RpcClient::new(opts.cluster.url().to_string());
+    let markets = opts
+        .market
+        .iter()
+        .map(|address| Market::load(&client, &opts.dex_program_id, address))
+        .collect::<Result<Vec<_>>>()?;
+    let server = Arc::new(Server {
+        client,
+        markets,
+        poll_interval: Duration::from_millis(opts.poll_interval_ms),
+    });
+
+    let mut runtime = tokio::runtime::Runtime::new()?;
+    runtime.block_on(serve(server, opts.host, opts.port));
+    Ok(())
+}
+
+struct Server {
+    client: RpcClient,
+    markets: Vec<Market>,
+    poll_interval: Duration,
+}
+
+impl Server {
+    fn market(&self, address: &str) -> Result<&Market> {
+        let address = Pubkey::from_str(address).map_err(|e| format_err!("{}: {:?}", address, e))?;
+        let address = address.to_string();
+        self.markets
+            .iter()
+            .find(|market| market.info.address == address)
+            .ok_or_else(|| format_err!("market {} is not served", address))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct BookQuery {
+    depth: Option<usize>,
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+const DEFAULT_BOOK_DEPTH: usize = 50;
+
+async fn serve(server: Arc<Server>, host: IpAddr, port: u16) {
+    let with_server = warp::any().map(move || server.clone());
+
+    let markets = warp::path!("markets")
+        .and(warp::get())
+        .and(with_server.clone())
+        .map(|server: Arc<Server>| {
+            let infos: Vec<&MarketInfo> = server.markets.iter().map(|m| &m.info).collect();
+            warp::reply::json(&infos)
+        });
+    let book = warp::path!("markets" / String / "book")
+        .and(warp::get())
+        .and(warp::query::<BookQuery>())
+        .and(with_server.clone())
+        .and_then(|address: String, query: BookQuery, server: Arc<Server>| {
+            let depth = query.depth.unwrap_or(DEFAULT_BOOK_DEPTH);
+            blocking_reply(server, move |server| {
+                server.market(&address)?.book(&server.client, depth)
+            })
+        });
+    let trades = warp::path!("markets" / String / "trades")
+        .and(warp::get())
+        .and(with_server.clone())
+        .and_then(|address: String, server: Arc<Server>| {
+            blocking_reply(server, move |server| {
+                let (_, trades) = server.market(&address)?.trades(&server.client, Some(0))?;
+                Ok(trades)
+            })
+        });
+    let trade_stream = warp::path!("markets" / String / "trades" / "ws")
+        .and(warp::ws())
+        .and(with_server)
+        .map(|address: String, ws: warp::ws::Ws, server: Arc<Server>| {
+            ws.on_upgrade(move |socket| stream_trades(server, address, socket))
+        });
+
+    warp::serve(markets.or(book).or(trades).or(trade_stream))
+        .run((host, port))
+        .await
+}
+
+// RPC calls block, so they run on the blocking pool to keep the server
+// responsive while requests are in flight.
+async fn blocking_reply<T, F>(
+    server: Arc<Server>,
+    f: F,
+) -> Result<warp::reply::WithStatus<warp::reply::Json>, Infallible>
+where
+    T: Serialize + Send + 'static,
+    F: FnOnce(&Server) -> Result<T> + Send + 'static,
+{
+    let result = tokio::task::spawn_blocking(move || f(&server))
+        .await
+        .unwrap_or_else(|e| Err(format_err!("{}", e)));
+    Ok(match result {
+        Ok(body) => warp::reply::with_status(warp::reply::json(&body), StatusCode::OK),
+        Err(e) => warp::reply::with_status(
+            warp::reply::json(&ErrorBody {
+                error: e.to_string(),
+            }),
+            StatusCode::BAD_REQUEST,
+        ),
+    })
+}
+
+// Sends each new trade as a JSON text message, starting from the moment the
+// client connects.
+async fn stream_trades(server: Arc<Server>, address: String, socket: WebSocket) {
+    let (mut tx, _) = socket.split();
+    let mut since = None;
+    let mut interval = tokio::time::interval(server.poll_interval);
+    loop {
+        interval.tick().await;
+        let server = server.clone();
+        let address = address.clone();
+        let polled = tokio::task::spawn_blocking(move || {
+            server.market(&address)?.trades(&server.client, since)
+        })
+        .await
+        .unwrap_or_else(|e| Err(format_err!("{}", e)));
+        let (seq_num, trades) = match polled {
+            Ok(polled) => polled,
+            Err(e) => {
+                let body = serde_json::to_string(&ErrorBody {
+                    error: e.to_string(),
+                })
+                .unwrap();
+                let _ = tx.send(Message::text(body)).await;
+                return;
+            }
+        };
+        since = Some(seq_num);
+        for trade in trades {
+            let body = serde_json::to_string(&trade).unwrap();
+            if tx.send(Message::text(body)).await.is_err() {
+                return;
+            }
+        }
+    }
+}
@@ -0,0 +1,176 @@
+//! Decodes market accounts into the shapes served over the API.
+
+use std::borrow::Cow;
+use std::convert::identity;
+use std::mem::size_of;
+
+use anyhow::{format_err, Result};
+use bytemuck::{bytes_of, cast_slice};
+use serde::Serialize;
+use solana_client::rpc_client::RpcClient;
+use solana_sdk::pubkey::Pubkey;
+
+use crank::{
+    get_keys_for_market, read_pod, read_pod_slice, remove_dex_account_padding, MarketPubkeys,
+};
+use serum_dex::critbit::Slab;
+use serum_dex::matching::Side;
+use serum_dex::state::{Event, EventQueueHeader, EventView, MarketState, QueueHeader};
+
+#[derive(Debug, Clone, Serialize)]
+pub struct MarketInfo {
+    pub address: String,
+    pub coin_mint: String,
+    pub pc_mint: String,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+}
+
+/// Prices are in pc lots per coin lot and quantities in coin lots, as they
+/// are stored on chain.
+#[derive(Debug, Serialize)]
+pub struct Level {
+    pub price: u64,
+    pub quantity: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct BookSnapshot {
+    pub bids: Vec<Level>,
+    pub asks: Vec<Level>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Trade {
+    pub seq_num: u64,
+    /// Side of the order that took liquidity.
+    pub side: Side,
+    pub price: u64,
+    pub quantity: u64,
+}
+
+pub struct Market {
+    pub info: MarketInfo,
+    keys: MarketPubkeys,
+    coin_lot_size: u64,
+}
+
+impl Market {
+    pub fn load(client: &RpcClient, program_id: &Pubkey, address: &Pubkey) -> Result<Self> {
+        let keys = get_keys_for_market(client, program_id, address)?;
+        let data = client.get_account_data(address)?;
+        let words: Cow<[u64]> = remove_dex_account_padding(&data)?;
+        let state: MarketState = read_pod(cast_slice(&words))?;
+        let coin_lot_size = state.coin_lot_size;
+        Ok(Market {
+            info: MarketInfo {
+                address: address.to_string(),
+                coin_mint: Pubkey::new(bytes_of(&identity(state.coin_mint))).to_string(),
+                pc_mint: Pubkey::new(bytes_of(&identity(state.pc_mint))).to_string(),
+                coin_lot_size,
+                pc_lot_size: state.pc_lot_size,
+            },
+            keys,
+            coin_lot_size,
+        })
+    }
+
+    /// Aggregates the best `depth` price levels on each side of the book.
+    pub fn book(&self, client: &RpcClient, depth: usize) -> Result<BookSnapshot> {
+        Ok(BookSnapshot {
+            bids: levels(&mut load_slab(client, &self.keys.bids)?, Side::Bid, depth),
+            asks: levels(&mut load_slab(client, &self.keys.asks)?, Side::Ask, depth),
+        })
+    }
+
+    /// Returns the current event sequence number and the trades recorded
+    /// after `since`, oldest first. Consumed events are still read until the
+    /// queue wraps around and overwrites them.
+    pub fn trades(&self, client: &RpcClient, since: Option<u64>) -> Result<(u64, Vec<Trade>)> {
+        let data = client.get_account_data(&self.keys.event_q)?;
+        let words: Cow<[u64]> = remove_dex_account_padding(&data)?;
+        let (header_words, event_words) = words.split_at(size_of::<EventQueueHeader>() >> 3);
+        let header: EventQueueHeader = read_pod(cast_slice(header_words))?;
+        let events: &[Event] = read_pod_slice(cast_slice(event_words))?;
+        if events.is_empty() {
+            return Err(format_err!("event queue has no capacity"));
+        }
+
+        let len = events.len() as u64;
+        let seq_num = header.seq_num();
+        // The newest event sits just before the next write position.
+        let next = (header.head() + header.count()) % len;
+        let oldest = seq_num.saturating_sub(len);
+        let start = since.unwrap_or(seq_num).max(oldest).min(seq_num);
+
+        let mut trades = vec![];
+        for event_seq_num in start..seq_num {
+            let back = seq_num - event_seq_num;
+            let event = &events[((next + len - back) % len) as usize];
+            if let Some(trade) = self.trade(event_seq_num, event) {
+                trades.push(trade);
+            }
+        }
+        Ok((seq_num, trades))
+    }
+
+    // Every match emits a fill for both sides; the maker's carries the price
+    // the trade happened at.
+    fn trade(&self, seq_num: u64, event: &Event) -> Option<Trade> {
+        match event.as_view().ok()? {
+            EventView::Fill {
+                side,
+                maker: true,
+                native_qty_paid,
+                native_qty_received,
+                order_id,
+                ..
+            } => {
+                let (taker_side, native_coin_qty) = match side {
+                    Side::Bid => (Side::Ask, native_qty_received),
+                    Side::Ask => (Side::Bid, native_qty_paid),
+                };
+                Some(Trade {
+                    seq_num,
+                    side: taker_side,
+                    price: (order_id >> 64) as u64,
+                    quantity: native_coin_qty / self.coin_lot_size,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+fn load_slab(client: &RpcClient, address: &Pubkey) -> Result<Vec<u8>> {
+    let data = client.get_account_data(address)?;
+    let words: Cow<[u64]> = remove_dex_account_padding(&data)?;
+    // The first word holds the account flags.
+    Ok(cast_slice(&words[1..]).to_vec())
+}
+
+// Drains a copy of the slab from the best price outwards.
+fn levels(slab_bytes: &mut [u8], side: Side, depth: usize) -> Vec<Level> {
+    let slab = Slab::new(slab_bytes);
+    let mut levels: Vec<Level> = vec![];
+    loop {
+        let leaf = match side {
+            Side::Bid => slab.remove_max(),
+            Side::Ask => slab.remove_min(),
+        };
+        let leaf = match leaf {
+            Some(leaf) => leaf,
+            None => break,
+        };
+        let price = leaf.price().get();
+        match levels.last_mut() {
+            Some(level) if level.price == price => level.quantity += leaf.quantity(),
+            _ if levels.len() == depth => break,
+            _ => levels.push(Level {
+                price,
+                quantity: leaf.quantity(),
+            }),
+        }
+    }
+    levels
+}
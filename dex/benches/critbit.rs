@@ -0,0 +1,82 @@
+//! Order book operations on a deep book, as matching does them.
+//!
+//! Compute units can't be measured off chain, but they track the number of
+//! nodes touched closely enough that these timings show the same trend. To
+//! compare against another revision, run `cargo bench -- --save-baseline
+//! before` there and `cargo bench -- --baseline before` here.
+
+use bytemuck::cast_slice_mut;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion};
+use rand::prelude::*;
+use serum_dex::critbit::{LeafNode, Slab, SlabView};
+use serum_dex::fees::FeeTier;
+
+const DEPTH: usize = 4096;
+// Room for the orders `place_orders` adds. Every leaf but the first brings
+// an inner node with it.
+const CAPACITY: usize = DEPTH + 64;
+
+fn deep_book() -> Vec<u64> {
+    // A 32 byte header, then 72 byte nodes.
+    let mut aligned_buf = vec![0u64; 4 + CAPACITY * 2 * 9];
+    let slab = Slab::new(cast_slice_mut(&mut aligned_buf));
+    let mut rng = StdRng::seed_from_u64(0);
+    for seq_num in 0..DEPTH as u64 {
+        // Prices clustered the way a real book's are, in the top half of the
+        // key, with the sequence number in the bottom half.
+        let price: u64 = rng.gen_range(90_000, 110_000);
+        let key = ((price as u128) << 64) | seq_num as u128;
+        let leaf = LeafNode::new(0, key, rng.gen(), rng.gen(), FeeTier::Base, 0, 0);
+        slab.insert_leaf(&leaf).unwrap();
+    }
+    aligned_buf
+}
+
+fn best_order(c: &mut Criterion) {
+    let mut aligned_buf = deep_book();
+    let slab = Slab::new(cast_slice_mut(&mut aligned_buf));
+    c.bench_function("find best bid", |b| {
+        b.iter(|| slab.get(black_box(slab.find_max().unwrap())).unwrap())
+    });
+}
+
+fn fill_orders(c: &mut Criterion) {
+    let book = deep_book();
+    // Taking out the 64 best bids, as a large market order would.
+    c.bench_function("fill 64 best bids", |b| {
+        b.iter_batched_ref(
+            || book.clone(),
+            |aligned_buf| {
+                let slab = Slab::new(cast_slice_mut(aligned_buf));
+                for _ in 0..64 {
+                    let best = slab.find_max().unwrap();
+                    let key = slab.get(best).unwrap().as_leaf().unwrap().order_id();
+                    black_box(slab.remove_by_key(key).unwrap());
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+fn place_orders(c: &mut Criterion) {
+    let book = deep_book();
+    c.bench_function("place 64 orders", |b| {
+        b.iter_batched_ref(
+            || (book.clone(), StdRng::seed_from_u64(1)),
+            |(aligned_buf, rng)| {
+                let slab = Slab::new(cast_slice_mut(aligned_buf));
+                for seq_num in DEPTH as u64..DEPTH as u64 + 64 {
+                    let price: u64 = rng.gen_range(90_000, 110_000);
+                    let key = ((price as u128) << 64) | seq_num as u128;
+                    let leaf = LeafNode::new(0, key, rng.gen(), rng.gen(), FeeTier::Base, 0, 0);
+                    black_box(slab.insert_leaf(&leaf).unwrap());
+                }
+            },
+            BatchSize::LargeInput,
+        )
+    });
+}
+
+criterion_group!(benches, best_order, fill_orders, place_orders);
+criterion_main!(benches);
@@ -0,0 +1,7 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use serum_dex_fuzz::conservation::{run, Action};
+
+fuzz_target!(|actions: Vec<Action>| { run(actions) });
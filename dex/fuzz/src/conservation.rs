@@ -0,0 +1,346 @@
+//! Drives random sequences of direct orders, cancels, event consumption and
+//! settlement against an in-memory market, checking after every step that
+//! no funds are created or lost:
+//!
+//! - the coin vault holds the coin deposits plus accrued coin fees,
+//! - the pc vault holds the pc deposits plus accrued fees and referrer
+//!   rebates,
+//! - and once every event is consumed, the deposits are exactly the free and
+//!   locked balances of the OpenOrders accounts.
+//!
+//! Prices and quantities are kept small so most orders are accepted.
+
+use std::cell::RefMut;
+use std::collections::BTreeMap;
+use std::convert::identity;
+use std::mem::size_of;
+use std::num::NonZeroU64;
+
+use arbitrary::{Arbitrary, Unstructured};
+use bumpalo::Bump;
+use solana_program::account_info::AccountInfo;
+
+use serum_dex::error::DexError;
+use serum_dex::instruction::{
+    CancelOrderInstructionV2, MarketInstruction, NewOrderInstructionV2, SelfTradeBehavior,
+};
+use serum_dex::matching::{OrderType, Side};
+use serum_dex::state::{strip_header, EventQueue, MarketState, OpenOrders, Queue, ToAlignedBytes};
+
+use crate::{
+    get_token_account_balance, new_dex_owned_account_with_lamports, new_sol_account,
+    new_token_account, process_instruction, setup_market, MarketAccounts,
+};
+
+const INITIAL_COIN_BALANCE: u64 = 1_000_000_000_000;
+const INITIAL_PC_BALANCE: u64 = 1_000_000_000_000;
+const MAX_PRICE_LOTS: u64 = 200;
+const MAX_QTY_LOTS: u64 = 100;
+
+#[derive(Debug, Arbitrary, Clone)]
+pub enum Action {
+    NewOrder {
+        owner_id: OwnerId,
+        side: Side,
+        price: u16,
+        qty: u16,
+        order_type: OrderType,
+        self_trade_behavior: SelfTradeBehavior,
+        client_id: u8,
+    },
+    CancelOrder {
+        owner_id: OwnerId,
+        slot: u8,
+    },
+    ConsumeEvents(u16),
+    SettleFunds(OwnerId),
+    SweepFees,
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy, PartialOrd, Ord)]
+pub struct OwnerId(u8);
+
+impl Arbitrary for OwnerId {
+    fn arbitrary(u: &mut Unstructured<'_>) -> arbitrary::Result<Self> {
+        let i: u8 = u.arbitrary()?;
+        Ok(OwnerId(i % 4))
+    }
+
+    fn size_hint(_: usize) -> (usize, Option<usize>) {
+        (1, Some(1))
+    }
+}
+
+struct Owner<'bump> {
+    signer_account: AccountInfo<'bump>,
+    orders_account: AccountInfo<'bump>,
+    coin_account: AccountInfo<'bump>,
+    pc_account: AccountInfo<'bump>,
+}
+
+impl<'bump> Owner<'bump> {
+    fn new(market_accounts: &MarketAccounts<'bump>, bump: &'bump Bump) -> Self {
+        let signer_account = new_sol_account(10, &bump);
+        let orders_account = new_dex_owned_account_with_lamports(
+            size_of::<OpenOrders>(),
+            10000000000,
+            market_accounts.market.owner,
+            &bump,
+        );
+        let coin_account = new_token_account(
+            market_accounts.coin_mint.key,
+            signer_account.key,
+            INITIAL_COIN_BALANCE,
+            &bump,
+        );
+        let pc_account = new_token_account(
+            market_accounts.pc_mint.key,
+            signer_account.key,
+            INITIAL_PC_BALANCE,
+            &bump,
+        );
+        Self {
+            signer_account,
+            orders_account,
+            coin_account,
+            pc_account,
+        }
+    }
+
+    fn open_orders(&self) -> Option<RefMut<OpenOrders>> {
+        let (orders, _) = strip_header::<OpenOrders, u8>(&self.orders_account, false).ok()?;
+        Some(orders)
+    }
+}
+
+/// Runs `actions` against a new market, panicking as soon as an invariant
+/// breaks or an instruction fails with anything but a dex error code.
+pub fn run(actions: Vec<Action>) {
+    let bump = Bump::new();
+    let market_accounts = setup_market(&bump);
+    let mut owners: BTreeMap<OwnerId, Owner> = BTreeMap::new();
+
+    for action in actions {
+        run_action(action, &market_accounts, &mut owners, &bump);
+        check_invariants(&market_accounts, &owners);
+    }
+
+    // Consuming every event must leave the deposits owned by the accounts.
+    run_action(
+        Action::ConsumeEvents(std::u16::MAX),
+        &market_accounts,
+        &mut owners,
+        &bump,
+    );
+    assert_eq!(event_queue_len(&market_accounts), 0);
+    check_invariants(&market_accounts, &owners);
+}
+
+fn run_action<'bump>(
+    action: Action,
+    market_accounts: &MarketAccounts<'bump>,
+    owners: &mut BTreeMap<OwnerId, Owner<'bump>>,
+    bump: &'bump Bump,
+) {
+    let program_id = market_accounts.market.owner;
+    let result = match action {
+        Action::NewOrder {
+            owner_id,
+            side,
+            price,
+            qty,
+            order_type,
+            self_trade_behavior,
+            client_id,
+        } => {
+            let owner = owners
+                .entry(owner_id)
+                .or_insert_with(|| Owner::new(market_accounts, bump));
+            let instruction = NewOrderInstructionV2 {
+                side,
+                limit_price: lots(price, MAX_PRICE_LOTS),
+                max_qty: lots(qty, MAX_QTY_LOTS),
+                order_type,
+                client_id: client_id as u64,
+                self_trade_behavior,
+            };
+            process_instruction(
+                program_id,
+                &[
+                    market_accounts.market.clone(),
+                    owner.orders_account.clone(),
+                    market_accounts.req_q.clone(),
+                    match side {
+                        Side::Bid => owner.pc_account.clone(),
+                        Side::Ask => owner.coin_account.clone(),
+                    },
+                    owner.signer_account.clone(),
+                    market_accounts.coin_vault.clone(),
+                    market_accounts.pc_vault.clone(),
+                    market_accounts.spl_token_program.clone(),
+                    market_accounts.rent_sysvar.clone(),
+                    market_accounts.event_q.clone(),
+                    market_accounts.bids.clone(),
+                    market_accounts.asks.clone(),
+                ],
+                &MarketInstruction::NewOrderV3(instruction).pack(),
+            )
+        }
+        Action::CancelOrder { owner_id, slot } => {
+            let owner = match owners.get(&owner_id) {
+                Some(owner) if slot < 128 => owner,
+                _ => return,
+            };
+            let (side, order_id) = match owner.open_orders() {
+                Some(orders) => match orders.slot_side(slot) {
+                    Some(side) => (side, orders.orders[slot as usize]),
+                    None => return,
+                },
+                None => return,
+            };
+            process_instruction(
+                program_id,
+                &[
+                    market_accounts.market.clone(),
+                    owner.orders_account.clone(),
+                    market_accounts.req_q.clone(),
+                    owner.signer_account.clone(),
+                    market_accounts.event_q.clone(),
+                    market_accounts.bids.clone(),
+                    market_accounts.asks.clone(),
+                ],
+                &MarketInstruction::CancelOrderV2(CancelOrderInstructionV2 { side, order_id })
+                    .pack(),
+            )
+        }
+        Action::ConsumeEvents(limit) => {
+            let mut accounts: Vec<AccountInfo> = owners
+                .values()
+                .filter(|owner| owner.open_orders().is_some())
+                .map(|owner| owner.orders_account.clone())
+                .collect();
+            if accounts.is_empty() {
+                return;
+            }
+            accounts.sort_by_key(|account| account.key.to_aligned_bytes());
+            accounts.extend_from_slice(&[
+                market_accounts.market.clone(),
+                market_accounts.event_q.clone(),
+                market_accounts.coin_vault.clone(),
+                market_accounts.pc_vault.clone(),
+            ]);
+            process_instruction(
+                program_id,
+                &accounts,
+                &MarketInstruction::ConsumeEvents(limit).pack(),
+            )
+        }
+        Action::SettleFunds(owner_id) => {
+            let owner = match owners.get(&owner_id) {
+                Some(owner) if owner.open_orders().is_some() => owner,
+                _ => return,
+            };
+            process_instruction(
+                program_id,
+                &[
+                    market_accounts.market.clone(),
+                    owner.orders_account.clone(),
+                    owner.signer_account.clone(),
+                    market_accounts.coin_vault.clone(),
+                    market_accounts.pc_vault.clone(),
+                    owner.coin_account.clone(),
+                    owner.pc_account.clone(),
+                    market_accounts.vault_signer.clone(),
+                    market_accounts.spl_token_program.clone(),
+                ],
+                &MarketInstruction::SettleFunds.pack(),
+            )
+        }
+        Action::SweepFees => process_instruction(
+            program_id,
+            &[
+                market_accounts.market.clone(),
+                market_accounts.pc_vault.clone(),
+                market_accounts.sweep_authority.clone(),
+                market_accounts.fee_receiver.clone(),
+                market_accounts.vault_signer.clone(),
+                market_accounts.spl_token_program.clone(),
+            ],
+            &MarketInstruction::SweepFees.pack(),
+        ),
+    };
+    // Rejected instructions are rolled back, but must be rejected with a
+    // dex error code rather than a failed assertion.
+    match result {
+        Ok(()) | Err(DexError::ErrorCode(_)) => {}
+        Err(e) => panic!("{:?}", e),
+    }
+}
+
+fn lots(raw: u16, max: u64) -> NonZeroU64 {
+    NonZeroU64::new(raw as u64 % max + 1).unwrap()
+}
+
+fn event_queue_len(market_accounts: &MarketAccounts) -> u64 {
+    let (header, buf) = strip_header(&market_accounts.event_q, false).unwrap();
+    let events: EventQueue = Queue::new(header, buf);
+    events.len()
+}
+
+fn check_invariants(market_accounts: &MarketAccounts, owners: &BTreeMap<OwnerId, Owner>) {
+    let market = MarketState::load(&market_accounts.market, market_accounts.market.owner).unwrap();
+    let coin_vault = get_token_account_balance(&market_accounts.coin_vault);
+    let pc_vault = get_token_account_balance(&market_accounts.pc_vault);
+    assert_eq!(
+        coin_vault,
+        market.coin_deposits_total + market.coin_fees_accrued,
+        "coin vault doesn't match the market's ledger"
+    );
+    assert_eq!(
+        pc_vault,
+        market.pc_deposits_total + market.pc_fees_accrued + market.referrer_rebates_accrued,
+        "pc vault doesn't match the market's ledger"
+    );
+
+    // Fills and cancels are credited to the accounts by ConsumeEvents, so
+    // the accounts only add up to the deposits once the queue is empty.
+    if event_queue_len(market_accounts) != 0 {
+        return;
+    }
+    let (coin_total, pc_total) = owners
+        .values()
+        .filter_map(|owner| owner.open_orders())
+        .fold((0, 0), |(coin, pc), orders| {
+            (
+                coin + identity(orders.native_coin_total),
+                pc + identity(orders.native_pc_total),
+            )
+        });
+    assert_eq!(
+        coin_total,
+        identity(market.coin_deposits_total),
+        "open orders don't add up to the coin deposits"
+    );
+    assert_eq!(
+        pc_total,
+        identity(market.pc_deposits_total),
+        "open orders don't add up to the pc deposits"
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn conserves_funds(data in proptest::collection::vec(any::<u8>(), 0..4096)) {
+            let actions = Vec::<Action>::arbitrary_take_rest(Unstructured::new(&data))
+                .unwrap_or_default();
+            run(actions);
+        }
+    }
+}
@@ -21,6 +21,8 @@ use serum_dex::state::{
     gen_vault_signer_key, strip_header, EventQueue, MarketState, Queue, RequestQueue, State,
 };
 
+pub mod conservation;
+
 fn random_pubkey(bump: &Bump) -> &Pubkey {
     bump.alloc(Pubkey::new(transmute_to_bytes(&rand::random::<[u64; 4]>())))
 }
@@ -0,0 +1,642 @@
+//! Decodes markets, the event queue and the order book from raw account
+//! data, for cranks, indexers and UIs that read them over RPC, and the
+//! dex's custom error codes.
+
+use std::convert::TryFrom;
+use std::mem::size_of;
+use std::num::NonZeroU64;
+
+use arrayref::array_ref;
+use bytemuck::{bytes_of, cast, cast_slice, try_from_bytes};
+use solana_program::pubkey::Pubkey;
+
+use crate::critbit::{LeafNode, Slab};
+use crate::error::{DexErrorCode, DexResult, SourceFileId};
+use crate::fees::FeeTier;
+use crate::matching::{extract_price_from_order_id, Side};
+use crate::state::{
+    validate_lot_sizes, AccountFlag, Event, EventQueueHeader, EventView, MarketState, QueueHeader,
+    ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING, FILL_LOG_TAG, ORDER_LOG_TAG,
+};
+
+declare_check_assert_macros!(SourceFileId::Client);
+
+/// The lot sizes of a market, for converting between native quantities and
+/// lots.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct LotSizes {
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+}
+
+impl LotSizes {
+    pub fn new(market: &MarketState) -> DexResult<Self> {
+        let (coin_lot_size, pc_lot_size) = (market.coin_lot_size, market.pc_lot_size);
+        check_assert!(coin_lot_size > 0 && pc_lot_size > 0)?;
+        Ok(LotSizes {
+            coin_lot_size,
+            pc_lot_size,
+        })
+    }
+
+    /// Lot sizes for quoting quantities to `size_decimals` decimal places
+    /// of a coin, and prices to `price_decimals` decimal places of pc per
+    /// coin. E.g., with 6 decimals on both mints, sizes to 4 places and
+    /// prices to 1 give a coin lot of 100 and a pc lot of 10.
+    ///
+    /// The pc mint needs at least `size_decimals + price_decimals`
+    /// decimals, as a pc lot can't be smaller than one native unit.
+    pub fn derive(
+        coin_decimals: u8,
+        pc_decimals: u8,
+        size_decimals: u8,
+        price_decimals: u8,
+    ) -> DexResult<Self> {
+        let coin_lot_decimals = coin_decimals.checked_sub(size_decimals);
+        let pc_lot_decimals = pc_decimals
+            .checked_sub(size_decimals)
+            .and_then(|decimals| decimals.checked_sub(price_decimals));
+        let lot_sizes = match (coin_lot_decimals, pc_lot_decimals) {
+            (Some(coin_lot_decimals), Some(pc_lot_decimals)) => LotSizes {
+                coin_lot_size: 10u64
+                    .checked_pow(coin_lot_decimals.into())
+                    .ok_or(DexErrorCode::InvalidLotSizes)?,
+                pc_lot_size: 10u64
+                    .checked_pow(pc_lot_decimals.into())
+                    .ok_or(DexErrorCode::InvalidLotSizes)?,
+            },
+            _ => Err(DexErrorCode::InvalidLotSizes)?,
+        };
+        validate_lot_sizes(
+            lot_sizes.coin_lot_size,
+            lot_sizes.pc_lot_size,
+            coin_decimals,
+            pc_decimals,
+        )?;
+        Ok(lot_sizes)
+    }
+
+    /// Whole coin lots in `native` coin, rounding down.
+    pub fn coin_lots(&self, native: u64) -> u64 {
+        native / self.coin_lot_size
+    }
+
+    pub fn native_coin(&self, lots: u64) -> Option<u64> {
+        lots.checked_mul(self.coin_lot_size)
+    }
+
+    /// Whole pc lots in `native` pc, rounding down.
+    pub fn pc_lots(&self, native: u64) -> u64 {
+        native / self.pc_lot_size
+    }
+
+    pub fn native_pc(&self, lots: u64) -> Option<u64> {
+        lots.checked_mul(self.pc_lot_size)
+    }
+
+    /// Native pc per native coin at a price of `price_lots` pc lots per
+    /// coin lot.
+    pub fn native_price(&self, price_lots: u64) -> f64 {
+        price_lots as f64 * self.pc_lot_size as f64 / self.coin_lot_size as f64
+    }
+}
+
+/// Selects a dex program's markets by mint, as `getProgramAccounts`
+/// filters: a `dataSize` of `DATA_SIZE`, and a `memcmp` per mint given.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct MarketFilter {
+    pub coin_mint: Option<Pubkey>,
+    pub pc_mint: Option<Pubkey>,
+}
+
+impl MarketFilter {
+    /// The length of a market account's data, padding included.
+    pub const DATA_SIZE: usize =
+        ACCOUNT_HEAD_PADDING.len() + size_of::<MarketState>() + ACCOUNT_TAIL_PADDING.len();
+    /// Offsets into the account data, padding included, of the mints, which
+    /// are the 6th and 10th words of `MarketState`.
+    pub const COIN_MINT_OFFSET: usize = ACCOUNT_HEAD_PADDING.len() + 6 * size_of::<u64>();
+    pub const PC_MINT_OFFSET: usize = ACCOUNT_HEAD_PADDING.len() + 10 * size_of::<u64>();
+
+    /// The `(offset, bytes)` of each `memcmp` filter.
+    pub fn memcmp_filters(&self) -> Vec<(usize, Pubkey)> {
+        let mut filters = Vec::new();
+        if let Some(coin_mint) = self.coin_mint {
+            filters.push((Self::COIN_MINT_OFFSET, coin_mint));
+        }
+        if let Some(pc_mint) = self.pc_mint {
+            filters.push((Self::PC_MINT_OFFSET, pc_mint));
+        }
+        filters
+    }
+
+    /// Whether the filters select the account with this data.
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() == Self::DATA_SIZE
+            && self
+                .memcmp_filters()
+                .iter()
+                .all(|(offset, key)| &data[*offset..*offset + 32] == key.as_ref())
+    }
+}
+
+/// Decodes the data of an enabled market account.
+pub fn read_market(data: &[u8]) -> DexResult<MarketState> {
+    check_assert_eq!(data.len(), MarketFilter::DATA_SIZE)?;
+    let (head, rest) = data.split_at(ACCOUNT_HEAD_PADDING.len());
+    let (inner, tail) = rest.split_at(size_of::<MarketState>());
+    check_assert_eq!(head, &ACCOUNT_HEAD_PADDING[..])?;
+    check_assert_eq!(tail, &ACCOUNT_TAIL_PADDING[..])?;
+    let market: MarketState =
+        *try_from_bytes(inner).map_err(|_| DexErrorCode::InvalidMarketFlags)?;
+    market.check_flags()?;
+    Ok(market)
+}
+
+/// The flags packed into an event's first byte.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct EventFlags {
+    pub fill: bool,
+    pub out: bool,
+    pub bid: bool,
+    pub maker: bool,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FillEvent {
+    pub side: Side,
+    pub maker: bool,
+    pub native_qty_paid: u64,
+    pub native_qty_received: u64,
+    /// A fee for takers, a rebate for makers.
+    pub native_fee_or_rebate: u64,
+    pub order_id: u128,
+    /// The open orders account of the order.
+    pub owner: Pubkey,
+    pub owner_slot: u8,
+    pub fee_tier: FeeTier,
+    pub client_order_id: Option<NonZeroU64>,
+}
+
+impl FillEvent {
+    /// The limit price of the order, in pc lots per coin lot. For maker
+    /// fills, this is the price the trade happened at.
+    pub fn price_lots(&self) -> u64 {
+        extract_price_from_order_id(self.order_id)
+    }
+
+    /// Coin bought or sold.
+    pub fn native_coin(&self) -> u64 {
+        match self.side {
+            Side::Bid => self.native_qty_received,
+            Side::Ask => self.native_qty_paid,
+        }
+    }
+
+    /// Pc paid or received, after fees and rebates.
+    pub fn native_pc(&self) -> u64 {
+        match self.side {
+            Side::Bid => self.native_qty_paid,
+            Side::Ask => self.native_qty_received,
+        }
+    }
+
+    pub fn coin_lots(&self, lot_sizes: &LotSizes) -> u64 {
+        lot_sizes.coin_lots(self.native_coin())
+    }
+}
+
+/// An order leaving the book, whether cancelled or filled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct OutEvent {
+    pub side: Side,
+    pub native_qty_unlocked: u64,
+    pub native_qty_still_locked: u64,
+    pub order_id: u128,
+    pub owner: Pubkey,
+    pub owner_slot: u8,
+    pub client_order_id: Option<NonZeroU64>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum QueueEvent {
+    Fill(FillEvent),
+    Out(OutEvent),
+}
+
+impl QueueEvent {
+    pub fn decode(event: &Event) -> DexResult<Self> {
+        Ok(match event.as_view()? {
+            EventView::Fill {
+                side,
+                maker,
+                native_qty_paid,
+                native_qty_received,
+                native_fee_or_rebate,
+                order_id,
+                owner,
+                owner_slot,
+                fee_tier,
+                client_order_id,
+            } => QueueEvent::Fill(FillEvent {
+                side,
+                maker,
+                native_qty_paid,
+                native_qty_received,
+                native_fee_or_rebate,
+                order_id,
+                owner: Pubkey::new(bytes_of(&owner)),
+                owner_slot,
+                fee_tier,
+                client_order_id,
+            }),
+            EventView::Out {
+                side,
+                native_qty_unlocked,
+                native_qty_still_locked,
+                order_id,
+                owner,
+                owner_slot,
+                client_order_id,
+            } => QueueEvent::Out(OutEvent {
+                side,
+                native_qty_unlocked,
+                native_qty_still_locked,
+                order_id,
+                owner: Pubkey::new(bytes_of(&owner)),
+                owner_slot,
+                client_order_id,
+            }),
+        })
+    }
+
+    pub fn flags(&self) -> EventFlags {
+        match self {
+            QueueEvent::Fill(fill) => EventFlags {
+                fill: true,
+                out: false,
+                bid: fill.side == Side::Bid,
+                maker: fill.maker,
+            },
+            QueueEvent::Out(out) => EventFlags {
+                fill: false,
+                out: true,
+                bid: out.side == Side::Bid,
+                maker: false,
+            },
+        }
+    }
+
+    pub fn owner(&self) -> &Pubkey {
+        match self {
+            QueueEvent::Fill(fill) => &fill.owner,
+            QueueEvent::Out(out) => &out.owner,
+        }
+    }
+}
+
+/// A view of an event queue account's data.
+pub struct EventQueueReader<'a> {
+    header: EventQueueHeader,
+    events: &'a [Event],
+}
+
+impl<'a> EventQueueReader<'a> {
+    /// Checks the padding and flags of the account data of an event queue.
+    pub fn new(data: &'a [u8]) -> DexResult<Self> {
+        let head_len = ACCOUNT_HEAD_PADDING.len();
+        let tail_len = ACCOUNT_TAIL_PADDING.len();
+        check_assert!(data.len() >= head_len + size_of::<EventQueueHeader>() + tail_len)?;
+        let (head, rest) = data.split_at(head_len);
+        let (inner, tail) = rest.split_at(rest.len() - tail_len);
+        check_assert_eq!(head, &ACCOUNT_HEAD_PADDING[..])?;
+        check_assert_eq!(tail, &ACCOUNT_TAIL_PADDING[..])?;
+
+        let (header_bytes, event_bytes) = inner.split_at(size_of::<EventQueueHeader>());
+        let header: EventQueueHeader =
+            *try_from_bytes(header_bytes).map_err(|_| assertion_error!())?;
+        check_assert_eq!(
+            header.account_flags(),
+            (AccountFlag::Initialized | AccountFlag::EventQueue).bits()
+        )?;
+        let slop = event_bytes.len() % size_of::<Event>();
+        let events: &[Event] = cast_slice(&event_bytes[..event_bytes.len() - slop]);
+        check_assert!(header.count() <= events.len() as u64)?;
+        check_assert!(events.is_empty() || header.head() < events.len() as u64)?;
+        Ok(EventQueueReader { header, events })
+    }
+
+    /// Sequence number the next event pushed will get.
+    pub fn seq_num(&self) -> u64 {
+        self.header.seq_num()
+    }
+
+    /// Number of queue slots in use, including those of events consumed
+    /// ahead of their turn that haven't been freed yet, which `iter` skips.
+    pub fn len(&self) -> usize {
+        self.header.count() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The events waiting to be consumed, oldest first, each with its
+    /// sequence number.
+    pub fn iter(&self) -> impl Iterator<Item = DexResult<(u64, QueueEvent)>> + '_ {
+        let head = self.header.head() as usize;
+        let count = self.len();
+        let first_seq_num = self.seq_num().wrapping_sub(count as u64);
+        (0..count).filter_map(move |i| {
+            let event = &self.events[(head + i) % self.events.len()];
+            if event.is_consumed() {
+                return None;
+            }
+            Some(
+                QueueEvent::decode(event)
+                    .map(|decoded| (first_seq_num.wrapping_add(i as u64), decoded)),
+            )
+        })
+    }
+}
+
+/// Decodes the fills `ConsumeEvents` logged, from the log messages of a
+/// transaction, in the order they were consumed.
+pub fn parse_fill_logs<'a>(logs: impl IntoIterator<Item = &'a str>) -> Vec<FillEvent> {
+    let mut fills = vec![];
+    // The words of the fill being read, once its tagged first line was seen.
+    let mut words: Vec<u64> = vec![];
+    for line in logs {
+        let line_words = match parse_log_words(line) {
+            Some(line_words) => line_words,
+            None => {
+                words.clear();
+                continue;
+            }
+        };
+        if !words.is_empty() {
+            words.extend_from_slice(&line_words);
+        } else if line_words[0] == FILL_LOG_TAG {
+            words.extend_from_slice(&line_words[1..]);
+        }
+        if words.len() >= size_of::<Event>() / size_of::<u64>() {
+            let event: Event = cast(*array_ref![words, 0, 11]);
+            if let Ok(QueueEvent::Fill(fill)) = QueueEvent::decode(&event) {
+                fills.push(fill);
+            }
+            words.clear();
+        }
+    }
+    fills
+}
+
+/// What `NewOrderBatch` logged for one of its orders.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PlacedOrder {
+    /// The order's index in the batch.
+    pub index: u64,
+    pub order_id: u128,
+    /// Lots left on the book after matching. Zero if the order was filled
+    /// or cancelled in full.
+    pub resting_qty: u64,
+}
+
+/// Decodes the orders `NewOrderBatch` logged, from the log messages of a
+/// transaction, in the order they were placed.
+pub fn parse_order_logs<'a>(logs: impl IntoIterator<Item = &'a str>) -> Vec<PlacedOrder> {
+    logs.into_iter()
+        .filter_map(parse_log_words)
+        .filter(|words| words[0] == ORDER_LOG_TAG)
+        .map(|words| PlacedOrder {
+            index: words[1],
+            order_id: ((words[3] as u128) << 64) | words[2] as u128,
+            resting_qty: words[4],
+        })
+        .collect()
+}
+
+// Parses the five hex words of a `sol_log_64` line.
+fn parse_log_words(line: &str) -> Option<[u64; 5]> {
+    let mut parts = line.strip_prefix("Program log: ")?.split(", ");
+    let mut words = [0; 5];
+    for word in words.iter_mut() {
+        *word = u64::from_str_radix(parts.next()?.strip_prefix("0x")?, 16).ok()?;
+    }
+    match parts.next() {
+        Some(_) => None,
+        None => Some(words),
+    }
+}
+
+/// A view of a bids or asks account's data.
+pub struct OrderBookReader<'a> {
+    side: Side,
+    slab: &'a Slab,
+}
+
+impl<'a> OrderBookReader<'a> {
+    /// Checks the padding and flags of the account data of either side of
+    /// the order book.
+    pub fn new(data: &'a [u8]) -> DexResult<Self> {
+        let head_len = ACCOUNT_HEAD_PADDING.len();
+        let tail_len = ACCOUNT_TAIL_PADDING.len();
+        check_assert!(data.len() >= head_len + size_of::<u64>() + tail_len)?;
+        let (head, rest) = data.split_at(head_len);
+        let (inner, tail) = rest.split_at(rest.len() - tail_len);
+        check_assert_eq!(head, &ACCOUNT_HEAD_PADDING[..])?;
+        check_assert_eq!(tail, &ACCOUNT_TAIL_PADDING[..])?;
+
+        let (flags_bytes, slab_bytes) = inner.split_at(size_of::<u64>());
+        let flags = u64::from_le_bytes(*array_ref![flags_bytes, 0, 8]);
+        let side = if flags == (AccountFlag::Initialized | AccountFlag::Bids).bits() {
+            Side::Bid
+        } else if flags == (AccountFlag::Initialized | AccountFlag::Asks).bits() {
+            Side::Ask
+        } else {
+            check_unreachable!()?
+        };
+        Ok(OrderBookReader {
+            side,
+            slab: Slab::from_bytes(slab_bytes)?,
+        })
+    }
+
+    pub fn side(&self) -> Side {
+        self.side
+    }
+
+    /// The orders on the book, best price first.
+    pub fn orders(&self) -> impl Iterator<Item = &'a LeafNode> {
+        self.slab.iter(self.side == Side::Bid)
+    }
+
+    /// The orders aggregated by price, best price first, up to `depth`
+    /// price levels.
+    pub fn levels(&self, depth: usize) -> Vec<PriceLevel> {
+        let mut levels: Vec<PriceLevel> = Vec::new();
+        for order in self.orders() {
+            let price = order.price().get();
+            match levels.last_mut() {
+                Some(level) if level.price == price => {
+                    level.quantity += order.quantity();
+                    level.order_count += 1;
+                }
+                _ if levels.len() == depth => break,
+                _ => levels.push(PriceLevel {
+                    price,
+                    quantity: order.quantity(),
+                    order_count: 1,
+                }),
+            }
+        }
+        levels
+    }
+}
+
+/// The orders on one side of the book at a single price.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct PriceLevel {
+    /// Pc lots per coin lot.
+    pub price: u64,
+    /// Total coin lots.
+    pub quantity: u64,
+    pub order_count: usize,
+}
+
+impl PriceLevel {
+    pub fn to_native(&self, lot_sizes: &LotSizes) -> Option<NativePriceLevel> {
+        Some(NativePriceLevel {
+            price: lot_sizes.native_price(self.price),
+            quantity: lot_sizes.native_coin(self.quantity)?,
+            order_count: self.order_count,
+        })
+    }
+}
+
+/// A `PriceLevel` in native units.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct NativePriceLevel {
+    /// Native pc per native coin.
+    pub price: f64,
+    /// Total native coin.
+    pub quantity: u64,
+    pub order_count: usize,
+}
+
+impl DexErrorCode {
+    /// Describes the error for users of a client.
+    pub fn message(&self) -> &'static str {
+        match self {
+            DexErrorCode::InvalidMarketFlags => "market account has the wrong flags",
+            DexErrorCode::InvalidAskFlags => "asks account has the wrong flags",
+            DexErrorCode::InvalidBidFlags => "bids account has the wrong flags",
+            DexErrorCode::InvalidQueueLength => "queue account has the wrong length",
+            DexErrorCode::OwnerAccountNotProvided => "open orders owner account not provided",
+            DexErrorCode::ConsumeEventsQueueFailure => "failed to consume events",
+            DexErrorCode::WrongCoinVault => "wrong coin vault",
+            DexErrorCode::WrongPcVault => "wrong pc vault",
+            DexErrorCode::WrongCoinMint => "wrong coin mint",
+            DexErrorCode::WrongPcMint => "wrong pc mint",
+            DexErrorCode::CoinVaultProgramId => "coin vault isn't owned by the token program",
+            DexErrorCode::PcVaultProgramId => "pc vault isn't owned by the token program",
+            DexErrorCode::CoinMintProgramId => "coin mint isn't owned by the token program",
+            DexErrorCode::PcMintProgramId => "pc mint isn't owned by the token program",
+            DexErrorCode::WrongCoinMintSize => "coin mint has the wrong size",
+            DexErrorCode::WrongPcMintSize => "pc mint has the wrong size",
+            DexErrorCode::WrongCoinVaultSize => "coin vault has the wrong size",
+            DexErrorCode::WrongPcVaultSize => "pc vault has the wrong size",
+            DexErrorCode::UninitializedVault => "vault is not initialized",
+            DexErrorCode::UninitializedMint => "mint is not initialized",
+            DexErrorCode::CoinMintUninitialized => "coin mint is not initialized",
+            DexErrorCode::PcMintUninitialized => "pc mint is not initialized",
+            DexErrorCode::WrongMint => "wrong mint",
+            DexErrorCode::WrongVaultOwner => "vault isn't owned by the market's vault signer",
+            DexErrorCode::VaultHasDelegate => "vault has a delegate",
+            DexErrorCode::AlreadyInitialized => "account is already initialized",
+            DexErrorCode::WrongAccountDataAlignment => "account data is misaligned",
+            DexErrorCode::WrongAccountDataPaddingLength => {
+                "account data has the wrong padding length"
+            }
+            DexErrorCode::WrongAccountHeadPadding => "account data has the wrong head padding",
+            DexErrorCode::WrongAccountTailPadding => "account data has the wrong tail padding",
+            DexErrorCode::RequestQueueEmpty => "request queue is empty",
+            DexErrorCode::EventQueueTooSmall => "event queue is too small",
+            DexErrorCode::SlabTooSmall => "order book account is too small",
+            DexErrorCode::BadVaultSignerNonce => "wrong vault signer nonce",
+            DexErrorCode::InsufficientFunds => "insufficient funds",
+            DexErrorCode::SplAccountProgramId => "token account isn't owned by the token program",
+            DexErrorCode::SplAccountLen => "token account has the wrong size",
+            DexErrorCode::WrongFeeDiscountAccountOwner => {
+                "fee discount account has the wrong owner"
+            }
+            DexErrorCode::WrongFeeDiscountMint => "fee discount account has the wrong mint",
+            DexErrorCode::CoinPayerProgramId => "coin payer isn't owned by the token program",
+            DexErrorCode::PcPayerProgramId => "pc payer isn't owned by the token program",
+            DexErrorCode::ClientIdNotFound => "no order with the client order id",
+            DexErrorCode::TooManyOpenOrders => "too many open orders",
+            DexErrorCode::FakeErrorSoWeDontChangeNumbers => "unused",
+            DexErrorCode::BorrowError => "account is already borrowed",
+            DexErrorCode::WrongOrdersAccount => "wrong open orders account",
+            DexErrorCode::WrongBidsAccount => "wrong bids account",
+            DexErrorCode::WrongAsksAccount => "wrong asks account",
+            DexErrorCode::WrongRequestQueueAccount => "wrong request queue account",
+            DexErrorCode::WrongEventQueueAccount => "wrong event queue account",
+            DexErrorCode::RequestQueueFull => "request queue is full",
+            DexErrorCode::EventQueueFull => "event queue is full",
+            DexErrorCode::MarketIsDisabled => "market is disabled",
+            DexErrorCode::WrongSigner => "missing or wrong signer",
+            DexErrorCode::TransferFailed => "token transfer failed",
+            DexErrorCode::ClientOrderIdIsZero => "client order id must not be zero",
+            DexErrorCode::WrongRentSysvarAccount => "wrong rent sysvar account",
+            DexErrorCode::RentNotProvided => "rent sysvar not provided",
+            DexErrorCode::OrdersNotRentExempt => "open orders account is not rent exempt",
+            DexErrorCode::RequestQueueNotEmpty => "request queue is not empty",
+            DexErrorCode::OrderNotFound => "order not found",
+            DexErrorCode::OrderBookNotEmpty => "order book is not empty",
+            DexErrorCode::WrongTriggerOrderAccount => "wrong trigger order account",
+            DexErrorCode::TriggerPriceNotReached => "trigger price not reached",
+            DexErrorCode::WrongDelegateAccount => "wrong delegate account",
+            DexErrorCode::WrongSettleDestinationAccount => "wrong settle destination account",
+            DexErrorCode::InvalidFeeSchedule => "fee schedule out of bounds",
+            DexErrorCode::WrongRegistryMemberAccount => "wrong registry member account",
+            DexErrorCode::MarketNotUpgraded => "market needs UpgradeMarket first",
+            DexErrorCode::InvalidLotSizes => "lot sizes unusable with the mints' decimals",
+            DexErrorCode::WrongFeeDestinationsAccount => "wrong fee destinations account",
+            DexErrorCode::InvalidFeeDestinations => "fee destinations need 1 to 8 nonzero weights",
+            DexErrorCode::WrongMarketStatsAccount => "wrong market stats account",
+            DexErrorCode::WrongLockupVestingAccount => "wrong lockup vesting account",
+            DexErrorCode::WrongTraderStatsAccount => "wrong trader stats account",
+            DexErrorCode::InvalidMarketOptions => "unknown market option",
+            DexErrorCode::DuplicateClientOrderId => "client order id already in use",
+            DexErrorCode::WrongMarketListingAccount => "wrong market listing account",
+            DexErrorCode::MarketAlreadyListed => "mint pair already has a listed market",
+            DexErrorCode::WrongBlockTradeAccount => "wrong block trade account",
+            DexErrorCode::InvalidBlockTrade => "invalid block trade terms or deposit",
+            DexErrorCode::BlockTradeNotFunded => "block trade needs both deposits",
+            DexErrorCode::BlockTradePriceOutsideSpread => "block trade price outside the spread",
+            DexErrorCode::WrongMarketParamsAccount => "wrong market params account",
+            DexErrorCode::MarketParamsNotProvided => "market params account not provided",
+            DexErrorCode::PriceOutsideBand => "limit price outside the reference price band",
+            DexErrorCode::OrderBelowMinNotional => "order below the market's minimum notional",
+            DexErrorCode::RequestQueueRetired => "market only takes directly matched orders",
+            DexErrorCode::TriggerOrdersPending => "market has trigger orders pending",
+            DexErrorCode::MarketStatsNotProvided => "market stats account not provided",
+            DexErrorCode::Unknown => "unknown error",
+            DexErrorCode::AssertionError => "assertion failed",
+        }
+    }
+}
+
+/// Describes the custom error code a dex instruction failed with. Codes of
+/// failed assertions are decoded into the source file and line.
+pub fn describe_error(code: u32) -> String {
+    let error = DexErrorCode::from(code);
+    if error != DexErrorCode::AssertionError {
+        return format!("{} ({:?})", error.message(), error);
+    }
+    let line = code & 0xffff;
+    match SourceFileId::try_from((code >> 24) as u8) {
+        Ok(file_id) => format!("assertion failed at {}:{}", file_id, line),
+        Err(_) => format!("assertion failed at line {} of file {}", line, code >> 24),
+    }
+}
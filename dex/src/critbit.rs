@@ -26,7 +26,7 @@ enum NodeTag {
 }
 
 #[derive(Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 struct InnerNode {
     tag: u32,
     prefix_len: u32,
@@ -46,12 +46,12 @@ impl InnerNode {
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct LeafNode {
     tag: u32,
     owner_slot: u8,
     fee_tier: u8,
-    padding: [u8; 2],
+    cancel_if_through_reference_bps: u16,
     key: u128,
     owner: [u64; 4],
     quantity: u64,
@@ -69,12 +69,13 @@ impl LeafNode {
         quantity: u64,
         fee_tier: FeeTier,
         client_order_id: u64,
+        cancel_if_through_reference_bps: u16,
     ) -> Self {
         LeafNode {
             tag: NodeTag::LeafNode.into(),
             owner_slot,
             fee_tier: fee_tier.into(),
-            padding: [0; 2],
+            cancel_if_through_reference_bps,
             key,
             owner,
             quantity,
@@ -121,10 +122,15 @@ impl LeafNode {
     pub fn client_order_id(&self) -> u64 {
         self.client_order_id
     }
+
+    #[inline]
+    pub fn cancel_if_through_reference_bps(&self) -> u16 {
+        self.cancel_if_through_reference_bps
+    }
 }
 
 #[derive(Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 struct FreeNode {
     tag: u32,
     next: u32,
@@ -157,7 +163,7 @@ const_assert_eq!(_NODE_ALIGN, _LEAF_NODE_ALIGN);
 const_assert_eq!(_NODE_ALIGN, _FREE_NODE_ALIGN);
 
 #[derive(Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct AnyNode {
     tag: u32,
     padding: [u32; 17],
@@ -248,34 +254,30 @@ const_assert_eq!(_NODE_SIZE, size_of::<AnyNode>());
 const_assert_eq!(_NODE_ALIGN, align_of::<AnyNode>());
 
 #[derive(Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 struct SlabHeader {
-    bump_index: u64,
-    free_list_len: u64,
+    bump_index: u32,
+    // The leaves with the smallest and largest keys, i.e., the best ask and
+    // bid, so matching doesn't walk down the tree to find them. They live in
+    // what were the top halves of u64 counters that can't exceed a u32, and
+    // are only valid while `bounds_cached` is set, which slabs written
+    // before the cache existed don't have.
+    min_leaf: u32,
+    free_list_len: u32,
+    max_leaf: u32,
     free_list_head: u32,
 
     root_node: u32,
-    leaf_count: u64,
+    leaf_count: u32,
+    bounds_cached: u32,
 }
 unsafe impl Zeroable for SlabHeader {}
 unsafe impl Pod for SlabHeader {}
 
-const SLAB_HEADER_LEN: usize = size_of::<SlabHeader>();
+pub const SLAB_HEADER_LEN: usize = size_of::<SlabHeader>();
 
-#[cfg(debug_assertions)]
-unsafe fn invariant(check: bool) {
-    if check {
-        unreachable!();
-    }
-}
-
-#[cfg(not(debug_assertions))]
-#[inline(always)]
-unsafe fn invariant(check: bool) {
-    if check {
-        std::hint::unreachable_unchecked();
-    }
-}
+const_assert_eq!(SLAB_HEADER_LEN, 32);
+const_assert_eq!(align_of::<SlabHeader>(), 1);
 
 #[repr(transparent)]
 pub struct Slab([u8]);
@@ -295,11 +297,39 @@ impl Slab {
         let slop = len_without_header % size_of::<AnyNode>();
         let truncated_len = bytes.len() - slop;
         let bytes = &mut bytes[..truncated_len];
+        // Safety: `Slab` is a `repr(transparent)` wrapper around `[u8]`, so
+        // the pointer cast preserves both the layout and the slice length.
         let slab: &mut Self = unsafe { &mut *(bytes as *mut [u8] as *mut Slab) };
         slab.check_size_align(); // check alignment
         slab
     }
 
+    /// Views `bytes` as a slab without modifying it, e.g., to read an order
+    /// book account fetched over RPC.
+    pub fn from_bytes(bytes: &[u8]) -> DexResult<&Self> {
+        let len_without_header = match bytes.len().checked_sub(SLAB_HEADER_LEN) {
+            Some(len) => len,
+            None => Err(DexErrorCode::SlabTooSmall)?,
+        };
+        let slop = len_without_header % size_of::<AnyNode>();
+        let bytes = &bytes[..bytes.len() - slop];
+        // Safety: as in `new`.
+        let slab: &Self = unsafe { &*(bytes as *const [u8] as *const Slab) };
+        slab.check_size_align();
+        Ok(slab)
+    }
+
+    /// Copies this slab into `dest`, which must be at least as large. Node
+    /// handles are indices into the node array, so the copy is a valid slab
+    /// with the extra space available to the bump allocator.
+    pub fn copy_into(&self, dest: &mut Slab) -> DexResult {
+        if dest.nodes().len() < self.nodes().len() {
+            Err(DexErrorCode::SlabTooSmall)?
+        }
+        dest.0[..self.0.len()].copy_from_slice(&self.0);
+        Ok(())
+    }
+
     #[inline]
     pub fn assert_minimum_capacity(&self, capacity: u32) -> DexResult {
         if self.nodes().len() <= (capacity as usize) * 2 {
@@ -315,14 +345,6 @@ impl Slab {
     }
 
     fn parts(&self) -> (&SlabHeader, &[AnyNode]) {
-        unsafe {
-            invariant(self.0.len() < size_of::<SlabHeader>());
-            invariant((self.0.as_ptr() as usize) % align_of::<SlabHeader>() != 0);
-            invariant(
-                ((self.0.as_ptr() as usize) + size_of::<SlabHeader>()) % align_of::<AnyNode>() != 0,
-            );
-        }
-
         let (header_bytes, nodes_bytes) = array_refs![&self.0, SLAB_HEADER_LEN; .. ;];
         let header = cast_ref(header_bytes);
         let nodes = cast_slice(nodes_bytes);
@@ -330,14 +352,6 @@ impl Slab {
     }
 
     fn parts_mut(&mut self) -> (&mut SlabHeader, &mut [AnyNode]) {
-        unsafe {
-            invariant(self.0.len() < size_of::<SlabHeader>());
-            invariant((self.0.as_ptr() as usize) % align_of::<SlabHeader>() != 0);
-            invariant(
-                ((self.0.as_ptr() as usize) + size_of::<SlabHeader>()) % align_of::<AnyNode>() != 0,
-            );
-        }
-
         let (header_bytes, nodes_bytes) = mut_array_refs![&mut self.0, SLAB_HEADER_LEN; .. ;];
         let header = cast_mut(header_bytes);
         let nodes = cast_slice_mut(nodes_bytes);
@@ -381,11 +395,14 @@ impl SlabView<AnyNode> for Slab {
         let (header, _nodes) = self.parts_mut();
         *header = SlabHeader {
             bump_index: 0,
+            min_leaf: 0,
             free_list_len: 0,
+            max_leaf: 0,
             free_list_head: 0,
 
             root_node: 0,
             leaf_count: 0,
+            bounds_cached: 0,
         }
     }
 
@@ -429,10 +446,10 @@ impl SlabView<AnyNode> for Slab {
                 return Err(());
             }
 
-            if header.bump_index == std::u32::MAX as u64 {
+            if header.bump_index == std::u32::MAX {
                 return Err(());
             }
-            let key = header.bump_index as u32;
+            let key = header.bump_index;
             header.bump_index += 1;
 
             nodes[key as usize] = *val;
@@ -483,6 +500,34 @@ impl SlabView<AnyNode> for Slab {
     }
 }
 
+pub struct SlabIter<'a> {
+    slab: &'a Slab,
+    // Subtrees left to visit, the next one last.
+    stack: Vec<NodeHandle>,
+    descending: bool,
+}
+
+impl<'a> Iterator for SlabIter<'a> {
+    type Item = &'a LeafNode;
+
+    fn next(&mut self) -> Option<&'a LeafNode> {
+        while let Some(handle) = self.stack.pop() {
+            match self.slab.get(handle)?.case()? {
+                NodeRef::Leaf(leaf) => return Some(leaf),
+                NodeRef::Inner(&InnerNode { children, .. }) => {
+                    let (first, second) = match self.descending {
+                        false => (children[0], children[1]),
+                        true => (children[1], children[0]),
+                    };
+                    self.stack.push(second);
+                    self.stack.push(first);
+                }
+            }
+        }
+        None
+    }
+}
+
 #[derive(Debug)]
 pub enum SlabTreeError {
     OutOfSpace,
@@ -497,8 +542,7 @@ impl Slab {
         Some(self.header().root_node)
     }
 
-    fn find_min_max(&self, find_max: bool) -> Option<NodeHandle> {
-        let mut root: NodeHandle = self.root()?;
+    fn find_min_max_from(&self, mut root: NodeHandle, find_max: bool) -> NodeHandle {
         loop {
             let root_contents = self.get(root).unwrap();
             match root_contents.case().unwrap() {
@@ -506,11 +550,43 @@ impl Slab {
                     root = children[if find_max { 1 } else { 0 }];
                     continue;
                 }
-                _ => return Some(root),
+                _ => return root,
             }
         }
     }
 
+    fn find_min_max(&self, find_max: bool) -> Option<NodeHandle> {
+        let root: NodeHandle = self.root()?;
+        let header = self.header();
+        if header.bounds_cached != 0 {
+            return Some(if find_max {
+                header.max_leaf
+            } else {
+                header.min_leaf
+            });
+        }
+        Some(self.find_min_max_from(root, find_max))
+    }
+
+    /// Fills in the cached bounds of a slab written before they existed, so
+    /// that mutations only have to keep them up to date.
+    fn cache_bounds(&mut self) {
+        if self.header().bounds_cached != 0 {
+            return;
+        }
+        let (min_leaf, max_leaf) = match self.root() {
+            Some(root) => (
+                self.find_min_max_from(root, false),
+                self.find_min_max_from(root, true),
+            ),
+            None => (0, 0),
+        };
+        let header = self.header_mut();
+        header.min_leaf = min_leaf;
+        header.max_leaf = max_leaf;
+        header.bounds_cached = 1;
+    }
+
     #[inline]
     pub fn find_min(&self) -> Option<NodeHandle> {
         self.find_min_max(false)
@@ -526,14 +602,18 @@ impl Slab {
         &mut self,
         new_leaf: &LeafNode,
     ) -> Result<(NodeHandle, Option<LeafNode>), SlabTreeError> {
+        self.cache_bounds();
         let mut root: NodeHandle = match self.root() {
             Some(h) => h,
             None => {
                 // create a new root if none exists
                 match self.insert(new_leaf.as_ref()) {
                     Ok(handle) => {
-                        self.header_mut().root_node = handle;
-                        self.header_mut().leaf_count = 1;
+                        let header = self.header_mut();
+                        header.root_node = handle;
+                        header.leaf_count = 1;
+                        header.min_leaf = handle;
+                        header.max_leaf = handle;
                         return Ok((handle, None));
                     }
                     Err(()) => return Err(SlabTreeError::OutOfSpace),
@@ -591,12 +671,35 @@ impl Slab {
 
             new_root.children[new_leaf_crit_bit as usize] = new_leaf_handle;
             new_root.children[old_root_crit_bit as usize] = moved_root_handle;
-            self.header_mut().leaf_count += 1;
+
+            let header = *self.header();
+            let mut min_leaf = header.min_leaf;
+            let mut max_leaf = header.max_leaf;
+            if min_leaf == root {
+                min_leaf = moved_root_handle;
+            }
+            if max_leaf == root {
+                max_leaf = moved_root_handle;
+            }
+            if new_leaf.key < self.get(min_leaf).unwrap().key().unwrap() {
+                min_leaf = new_leaf_handle;
+            }
+            if new_leaf.key > self.get(max_leaf).unwrap().key().unwrap() {
+                max_leaf = new_leaf_handle;
+            }
+            let header = self.header_mut();
+            header.min_leaf = min_leaf;
+            header.max_leaf = max_leaf;
+            header.leaf_count += 1;
             return Ok((new_leaf_handle, None));
         }
     }
 
-    #[cfg(test)]
+    /// The order with `order_id`, if it's on this side of the book.
+    pub fn find_order(&self, order_id: u128) -> Option<&LeafNode> {
+        self.get(self.find_by_key(order_id)?)?.as_leaf()
+    }
+
     fn find_by_key(&self, search_key: u128) -> Option<NodeHandle> {
         let mut node_handle: NodeHandle = self.root()?;
         loop {
@@ -621,6 +724,7 @@ impl Slab {
 
     #[inline]
     pub fn remove_by_key(&mut self, search_key: u128) -> Option<LeafNode> {
+        self.cache_bounds();
         let mut parent_h = self.root()?;
         let mut child_h;
         let mut crit_bit;
@@ -663,7 +767,27 @@ impl Slab {
         let other_child_h = self.get(parent_h).unwrap().children().unwrap()[!crit_bit as usize];
         let other_child_node_contents = self.remove(other_child_h).unwrap();
         *self.get_mut(parent_h).unwrap() = other_child_node_contents;
-        self.header_mut().leaf_count -= 1;
+
+        // The removed leaf's sibling subtree now sits at `parent_h`. If the
+        // leaf was the minimum or maximum, the new one is at the near edge of
+        // that subtree, which is usually only a few levels deep.
+        let header = *self.header();
+        let mut min_leaf = header.min_leaf;
+        let mut max_leaf = header.max_leaf;
+        if min_leaf == other_child_h {
+            min_leaf = parent_h;
+        } else if min_leaf == child_h {
+            min_leaf = self.find_min_max_from(parent_h, false);
+        }
+        if max_leaf == other_child_h {
+            max_leaf = parent_h;
+        } else if max_leaf == child_h {
+            max_leaf = self.find_min_max_from(parent_h, true);
+        }
+        let header = self.header_mut();
+        header.min_leaf = min_leaf;
+        header.max_leaf = max_leaf;
+        header.leaf_count -= 1;
         Some(cast(self.remove(child_h).unwrap()))
     }
 
@@ -677,6 +801,17 @@ impl Slab {
         self.remove_by_key(self.get(self.find_max()?)?.key()?)
     }
 
+    /// The orders in the slab by ascending key, or by descending key if
+    /// `descending`. The key starts with the price, so iterating bids
+    /// descending and asks ascending gives the best orders first.
+    pub fn iter(&self, descending: bool) -> SlabIter<'_> {
+        SlabIter {
+            slab: self,
+            stack: self.root().into_iter().collect(),
+            descending,
+        }
+    }
+
     #[cfg(test)]
     fn traverse(&self) -> Vec<&LeafNode> {
         fn walk_rec<'a>(slab: &'a Slab, sub_root: NodeHandle, buf: &mut Vec<&'a LeafNode>) {
@@ -748,9 +883,22 @@ impl Slab {
         }
         assert_eq!(
             count + self.header().free_list_len as u64,
-            identity(self.header().bump_index)
+            self.header().bump_index as u64
         );
 
+        if self.header().bounds_cached != 0 {
+            if let Some(root) = self.root() {
+                assert_eq!(
+                    identity(self.header().min_leaf),
+                    self.find_min_max_from(root, false)
+                );
+                assert_eq!(
+                    identity(self.header().max_leaf),
+                    self.find_min_max_from(root, true)
+                );
+            }
+        }
+
         let mut free_nodes_remaining = self.header().free_list_len;
         let mut next_free_node = self.header().free_list_head;
         loop {
@@ -802,7 +950,7 @@ mod tests {
                 let key = rng.gen();
                 let owner = rng.gen();
                 let qty = rng.gen();
-                let leaf = LeafNode::new(offset, key, owner, qty, FeeTier::Base, 0);
+                let leaf = LeafNode::new(offset, key, owner, qty, FeeTier::Base, 0, 0);
 
                 println!("{:x}", key);
                 println!("{}", i);
@@ -838,6 +986,39 @@ mod tests {
         }
     }
 
+    #[test]
+    fn caches_bounds_of_old_slabs() {
+        let mut aligned_buf = vec![0u64; 10_000];
+        let bytes: &mut [u8] = cast_slice_mut(aligned_buf.as_mut_slice());
+        let slab: &mut Slab = Slab::new(bytes);
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let mut keys: Vec<u128> = (0..100).map(|_| rng.gen()).collect();
+        for &key in &keys {
+            let leaf = LeafNode::new(rng.gen(), key, rng.gen(), rng.gen(), FeeTier::Base, 0, 0);
+            slab.insert_leaf(&leaf).unwrap();
+        }
+        keys.sort();
+
+        // As written before the header cached the best leaves.
+        {
+            let header = slab.header_mut();
+            header.min_leaf = 0;
+            header.max_leaf = 0;
+            header.bounds_cached = 0;
+        }
+        let min_key = |slab: &Slab| slab.get(slab.find_min().unwrap()).unwrap().key();
+        let max_key = |slab: &Slab| slab.get(slab.find_max().unwrap()).unwrap().key();
+        assert_eq!(min_key(slab), Some(keys[0]));
+        assert_eq!(max_key(slab), Some(keys[99]));
+
+        assert_eq!(slab.remove_min().unwrap().key, keys[0]);
+        assert_ne!(identity(slab.header().bounds_cached), 0);
+        slab.check_invariants();
+        assert_eq!(min_key(slab), Some(keys[1]));
+        assert_eq!(max_key(slab), Some(keys[99]));
+    }
+
     #[test]
     fn simulate_operations() {
         use rand::distributions::WeightedIndex;
@@ -897,7 +1078,7 @@ mod tests {
                         };
                         let owner = rng.gen();
                         let qty = rng.gen();
-                        let leaf = LeafNode::new(offset, key, owner, qty, FeeTier::SRM5, 5);
+                        let leaf = LeafNode::new(offset, key, owner, qty, FeeTier::SRM5, 5, 0);
 
                         println!("Insert {:x}", key);
 
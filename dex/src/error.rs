@@ -1,4 +1,4 @@
-use num_enum::{FromPrimitive, IntoPrimitive};
+use num_enum::{FromPrimitive, IntoPrimitive, TryFromPrimitive};
 use solana_program::program_error::ProgramError;
 use thiserror::Error;
 
@@ -106,6 +106,38 @@ pub enum DexErrorCode {
     WrongRentSysvarAccount,
     RentNotProvided,
     OrdersNotRentExempt,
+    RequestQueueNotEmpty,
+
+    OrderNotFound = 60,
+    OrderBookNotEmpty,
+    WrongTriggerOrderAccount,
+    TriggerPriceNotReached,
+    WrongDelegateAccount,
+    WrongSettleDestinationAccount,
+    InvalidFeeSchedule,
+    WrongRegistryMemberAccount,
+    MarketNotUpgraded,
+    InvalidLotSizes,
+    WrongFeeDestinationsAccount,
+    InvalidFeeDestinations,
+    WrongMarketStatsAccount,
+    WrongLockupVestingAccount,
+    WrongTraderStatsAccount,
+    InvalidMarketOptions,
+    DuplicateClientOrderId,
+    WrongMarketListingAccount,
+    MarketAlreadyListed,
+    WrongBlockTradeAccount,
+    InvalidBlockTrade,
+    BlockTradeNotFunded,
+    BlockTradePriceOutsideSpread,
+    WrongMarketParamsAccount,
+    MarketParamsNotProvided,
+    PriceOutsideBand,
+    OrderBelowMinNotional,
+    RequestQueueRetired,
+    TriggerOrdersPending,
+    MarketStatsNotProvided,
 
     Unknown = 1000,
 
@@ -116,7 +148,7 @@ pub enum DexErrorCode {
 }
 
 #[repr(u8)]
-#[derive(Error, Debug)]
+#[derive(Error, Debug, TryFromPrimitive)]
 pub enum SourceFileId {
     #[error("src/state.rs")]
     State = 1,
@@ -124,6 +156,8 @@ pub enum SourceFileId {
     Matching = 2,
     #[error("src/critbit.rs")]
     Critbit = 3,
+    #[error("src/client.rs")]
+    Client = 4,
 }
 
 #[macro_export]
@@ -4,7 +4,7 @@ use std::convert::TryInto;
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
-#[derive(Copy, Clone, IntoPrimitive, TryFromPrimitive, Debug)]
+#[derive(Copy, Clone, IntoPrimitive, TryFromPrimitive, Debug, PartialEq, Eq)]
 #[cfg_attr(test, derive(Arbitrary))]
 #[repr(u8)]
 pub enum FeeTier {
@@ -17,6 +17,12 @@ pub enum FeeTier {
     MSRM,
 }
 
+/// 30 day taker volume, in native pc, each raising the taker's fee tier by
+/// one step. Assumes a 6 decimal quote currency such as USDC: $1M, $10M and
+/// $100M.
+pub const TAKER_VOLUME_TIERS: [u64; 3] =
+    [1_000_000_000_000, 10_000_000_000_000, 100_000_000_000_000];
+
 #[repr(transparent)]
 #[derive(Copy, Clone)]
 struct U64F64(u128);
@@ -80,39 +86,115 @@ impl FeeTier {
         }
     }
 
+    /// Raises the tier one step for each threshold of `TAKER_VOLUME_TIERS`
+    /// met by `pc_volume_30d`, the owner's native pc traded as taker over
+    /// the last 30 days, up to SRM6. Volume alone never reaches MSRM, whose
+    /// maker bonus is paid for holding.
+    #[inline]
+    pub fn with_taker_volume(self, pc_volume_30d: u64) -> FeeTier {
+        if self == FeeTier::MSRM {
+            return self;
+        }
+        let steps = TAKER_VOLUME_TIERS
+            .iter()
+            .filter(|threshold| pc_volume_30d >= **threshold)
+            .count() as u8;
+        let tier = (u8::from(self) + steps).min(FeeTier::SRM6.into());
+        FeeTier::try_from_primitive(tier).unwrap()
+    }
+
     #[inline]
     pub fn maker_rebate(self, pc_qty: u64) -> u64 {
-        use FeeTier::*;
-        let rate: U64F64 = match self {
-            MSRM => rebate_bps(5),
-            Base | SRM2 | SRM3 | SRM4 | SRM5 | SRM6 => rebate_bps(3),
-        };
-        rate.mul_u64(pc_qty).floor()
+        FeeSchedule::DEFAULT.maker_rebate(self, pc_qty)
+    }
+
+    #[inline]
+    pub fn taker_fee(self, pc_qty: u64) -> u64 {
+        FeeSchedule::DEFAULT.taker_fee(self, pc_qty)
     }
 
-    fn taker_rate(self) -> U64F64 {
+    #[inline]
+    pub fn remove_taker_fee(self, pc_qty_incl_fee: u64) -> u64 {
+        FeeSchedule::DEFAULT.remove_taker_fee(self, pc_qty_incl_fee)
+    }
+
+    // Taken off the taker fee of the base tier.
+    fn taker_discount_bps(self) -> u16 {
         use FeeTier::*;
         match self {
-            Base => fee_bps(22),
-            SRM2 => fee_bps(20),
-            SRM3 => fee_bps(18),
-            SRM4 => fee_bps(16),
-            SRM5 => fee_bps(14),
-            SRM6 => fee_bps(12),
-            MSRM => fee_bps(10),
+            Base => 0,
+            SRM2 => 2,
+            SRM3 => 4,
+            SRM4 => 6,
+            SRM5 => 8,
+            SRM6 => 10,
+            MSRM => 12,
         }
     }
 
+    // Added to the maker rebate of the base tier.
+    fn maker_bonus_bps(self) -> u16 {
+        match self {
+            FeeTier::MSRM => 2,
+            _ => 0,
+        }
+    }
+}
+
+/// The fees of a market, in basis points of the pc traded by the base fee
+/// tier. Higher tiers get the same discounts and bonuses on every market.
+///
+/// Maker rebates are paid out of taker fees, so a schedule is only valid if
+/// the taker fee of every tier covers the maker rebate of every tier along
+/// with the referrer's cut.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct FeeSchedule {
+    pub taker_fee_bps: u16,
+    pub maker_rebate_bps: u16,
+}
+
+impl FeeSchedule {
+    pub const DEFAULT: FeeSchedule = FeeSchedule {
+        taker_fee_bps: 22,
+        maker_rebate_bps: 3,
+    };
+
+    pub const MAX_TAKER_FEE_BPS: u16 = 100;
+
+    pub fn is_valid(self) -> bool {
+        let min_taker_bps = match self
+            .taker_fee_bps
+            .checked_sub(FeeTier::MSRM.taker_discount_bps())
+        {
+            Some(bps) if self.taker_fee_bps <= Self::MAX_TAKER_FEE_BPS => bps as u64,
+            _ => return false,
+        };
+        let max_maker_bps = (self.maker_rebate_bps + FeeTier::MSRM.maker_bonus_bps()) as u64;
+        // The referrer gets a fifth of the taker fee.
+        max_maker_bps * 5 < min_taker_bps * 4
+    }
+
     #[inline]
-    pub fn taker_fee(self, pc_qty: u64) -> u64 {
-        let rate = self.taker_rate();
+    pub fn maker_rebate(self, tier: FeeTier, pc_qty: u64) -> u64 {
+        let rate = rebate_bps((self.maker_rebate_bps + tier.maker_bonus_bps()) as u64);
+        rate.mul_u64(pc_qty).floor()
+    }
+
+    #[inline]
+    fn taker_rate(self, tier: FeeTier) -> U64F64 {
+        fee_bps((self.taker_fee_bps - tier.taker_discount_bps()) as u64)
+    }
+
+    #[inline]
+    pub fn taker_fee(self, tier: FeeTier, pc_qty: u64) -> u64 {
+        let rate = self.taker_rate(tier);
         let exact_fee: U64F64 = rate.mul_u64(pc_qty);
         exact_fee.floor() + ((exact_fee.frac_part() != 0) as u64)
     }
 
     #[inline]
-    pub fn remove_taker_fee(self, pc_qty_incl_fee: u64) -> u64 {
-        let rate = self.taker_rate();
+    pub fn remove_taker_fee(self, tier: FeeTier, pc_qty_incl_fee: u64) -> u64 {
+        let rate = self.taker_rate(tier);
         U64F64::from_int(pc_qty_incl_fee)
             .div(U64F64::ONE.add(rate))
             .try_into()
@@ -130,6 +212,30 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[test]
+    fn taker_volume_raises_tier() {
+        let [million, ten_million, hundred_million] = TAKER_VOLUME_TIERS;
+        assert_eq!(FeeTier::Base.with_taker_volume(million - 1), FeeTier::Base);
+        assert_eq!(FeeTier::Base.with_taker_volume(million), FeeTier::SRM2);
+        assert_eq!(FeeTier::SRM2.with_taker_volume(ten_million), FeeTier::SRM4);
+        assert_eq!(
+            FeeTier::SRM3.with_taker_volume(hundred_million),
+            FeeTier::SRM6
+        );
+        assert_eq!(FeeTier::SRM5.with_taker_volume(u64::MAX), FeeTier::SRM6);
+        assert_eq!(FeeTier::MSRM.with_taker_volume(u64::MAX), FeeTier::MSRM);
+    }
+
+    #[test]
+    fn default_schedule_is_valid() {
+        assert!(FeeSchedule::DEFAULT.is_valid());
+        assert!(!FeeSchedule {
+            taker_fee_bps: 22,
+            maker_rebate_bps: 7,
+        }
+        .is_valid());
+    }
+
     proptest! {
         #[test]
         fn positive_net_fees(tt: FeeTier, mt: FeeTier, qty in 1..=std::u64::MAX) {
@@ -142,6 +248,22 @@ mod tests {
             assert!(net_bps_u64f64 + dust_qty_u64f64 > three_bps, "{:x}, {:x}, {:x}", qty, net_bps_u64f64, three_bps);
         }
 
+        #[test]
+        fn valid_schedules_fund_rebates(
+            taker_fee_bps in 0..=FeeSchedule::MAX_TAKER_FEE_BPS + 1,
+            maker_rebate_bps in 0..100u16,
+            tt: FeeTier,
+            mt: FeeTier,
+            qty in 1..=std::u64::MAX,
+        ) {
+            let schedule = FeeSchedule { taker_fee_bps, maker_rebate_bps };
+            if schedule.is_valid() {
+                let fee = schedule.taker_fee(tt, qty);
+                let rebate = schedule.maker_rebate(mt, qty) + referrer_rebate(fee);
+                assert!(fee >= rebate);
+            }
+        }
+
         #[test]
         fn fee_bps_approx(bps in 1..100u64) {
             let rate = fee_bps(bps);
@@ -33,6 +33,34 @@ pub mod disable_authority {
     declare_id!("5ZVJgwWxMsqXxRMYHXqMwH2hd4myX5Ef4Au2iUsuNQ7V");
 }
 
+/// The registry program whose members get fee discounts for their stake,
+/// the same as for SRM and MSRM held in a wallet.
+pub mod registry {
+    use solana_program::declare_id;
+    declare_id!("G4jdyFNL6kuYwVAbS1TsKWuszfaTFSMr1pZpYrSNARSV");
+}
+
+/// The registry `Registrar` staking SRM and MSRM. Only its members get fee
+/// discounts, since anyone can create a registrar with vaults of any mint.
+pub mod registry_srm_registrar {
+    use solana_program::declare_id;
+    declare_id!("HHVqQfrC6bRGNXDbAcJfbD4DLUDYgtdzCzE7qH4dCYWi");
+}
+
+/// The lockup program whose vesting accounts get fee discounts for the SRM
+/// they lock, so grants count the same as SRM held in a wallet.
+pub mod lockup {
+    use solana_program::declare_id;
+    declare_id!("LbBG6Q9CU24r8dxdwSH2VbKkQLzNANJnWdCegeXUR5B");
+}
+
+/// The lockup `Safe` holding SRM. Only its vesting accounts get fee
+/// discounts, since a vesting account doesn't record its mint.
+pub mod lockup_srm_safe {
+    use solana_program::declare_id;
+    declare_id!("FBi2b22o72fnCrdRLnoVPbK5rv6YC4Pdc8uiYbrhx6Ro");
+}
+
 pub mod fee_sweeper {
     use solana_program::declare_id;
     declare_id!("DeqYsmBd9BnrbgUwQjVH4sQWK71dEgE6eoZFw3Rp4ftE");
@@ -56,6 +84,9 @@ pub struct InitializeMarketInstruction {
     pub fee_rate_bps: u16,
     pub vault_signer_nonce: u64,
     pub pc_dust_threshold: u64,
+    // The smallest order the market takes, in native pc, or zero for none.
+    // Older clients leave it out.
+    pub min_notional: u64,
 }
 
 #[derive(
@@ -157,6 +188,39 @@ impl NewOrderInstructionV1 {
     }
 }
 
+impl NewOrderInstructionV2 {
+    fn unpack(data: &[u8; 36]) -> Option<Self> {
+        let (v1_data_arr, v2_data_arr) = array_refs![data, 32, 4];
+        let v1_instr = NewOrderInstructionV1::unpack(v1_data_arr)?;
+        let self_trade_behavior = SelfTradeBehavior::try_from_primitive(
+            u32::from_le_bytes(*v2_data_arr).try_into().ok()?,
+        )
+        .ok()?;
+        Some(v1_instr.add_self_trade_behavior(self_trade_behavior))
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct NewOrderInstructionV3 {
+    pub order: NewOrderInstructionV2,
+    // How far, in basis points of the order's price, the market's reference
+    // price may move through the order while it rests before it's cancelled
+    // instead of filled. Zero for no limit.
+    pub cancel_if_through_reference_bps: u16,
+}
+
+impl NewOrderInstructionV3 {
+    fn unpack(data: &[u8; 38]) -> Option<Self> {
+        let (v2_data_arr, v3_data_arr) = array_refs![data, 36, 2];
+        Some(NewOrderInstructionV3 {
+            order: NewOrderInstructionV2::unpack(v2_data_arr)?,
+            cancel_if_through_reference_bps: u16::from_le_bytes(*v3_data_arr),
+        })
+    }
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 #[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
@@ -167,6 +231,165 @@ pub struct CancelOrderInstruction {
     pub owner_slot: u8,
 }
 
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct CancelOrderInstructionV2 {
+    pub side: Side,
+    pub order_id: u128,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct UpdateLotSizesInstruction {
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct SetFeeScheduleInstruction {
+    pub taker_fee_bps: u16,
+    pub maker_rebate_bps: u16,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct ConsumeEventsFromInstruction {
+    pub start_seq_num: u64,
+    pub limit: u16,
+}
+
+/// The most OpenOrders accounts besides its own an order may treat as the
+/// same owner's when preventing self trades.
+pub const MAX_SELF_TRADE_ACCOUNTS: usize = 4;
+
+/// The most orders a `NewOrderBatch` may place, so the instruction and its
+/// accounts fit in one transaction.
+pub const MAX_BATCH_ORDERS: usize = 16;
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct NewOrderBatchInstruction {
+    #[cfg_attr(
+        test,
+        proptest(
+            strategy = "proptest::collection::vec(any::<NewOrderInstructionV2>(), 1..=MAX_BATCH_ORDERS)"
+        )
+    )]
+    pub orders: Vec<NewOrderInstructionV2>,
+}
+
+/// The most wallets `SweepFees` may split a market's fees between.
+pub const MAX_FEE_DESTINATIONS: usize = 8;
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct SetFeeDestinationsInstruction {
+    /// One weight per destination wallet, in the order of the accounts.
+    #[cfg_attr(
+        test,
+        proptest(strategy = "proptest::collection::vec(any::<u32>(), 1..=MAX_FEE_DESTINATIONS)")
+    )]
+    pub weights: Vec<u32>,
+}
+
+impl SetFeeDestinationsInstruction {
+    fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 || (data.len() - 8) % 4 != 0 {
+            return None;
+        }
+        let (count, weights_data) = data.split_at(8);
+        let count = u64::from_le_bytes(*array_ref![count, 0, 8]);
+        if count as usize != weights_data.len() / 4 {
+            return None;
+        }
+        let weights = weights_data
+            .chunks(4)
+            .map(|weight| u32::from_le_bytes(*array_ref![weight, 0, 4]))
+            .collect();
+        Some(SetFeeDestinationsInstruction { weights })
+    }
+}
+
+impl NewOrderBatchInstruction {
+    fn unpack(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 || (data.len() - 8) % 36 != 0 {
+            return None;
+        }
+        let (count, orders_data) = data.split_at(8);
+        let count = u64::from_le_bytes(*array_ref![count, 0, 8]);
+        if count as usize != orders_data.len() / 36 {
+            return None;
+        }
+        let orders = orders_data
+            .chunks(36)
+            .map(|order_data| NewOrderInstructionV2::unpack(array_ref![order_data, 0, 36]))
+            .collect::<Option<Vec<_>>>()?;
+        Some(NewOrderBatchInstruction { orders })
+    }
+}
+
+#[derive(
+    PartialEq, Eq, Copy, Clone, Debug, TryFromPrimitive, IntoPrimitive, Serialize, Deserialize,
+)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+#[repr(u8)]
+pub enum TriggerCondition {
+    PriceAtOrBelow = 0,
+    PriceAtOrAbove = 1,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct NewTriggerOrderInstruction {
+    pub order: NewOrderInstructionV2,
+    // In pc lots per coin lot, like limit_price.
+    pub trigger_price: u64,
+    pub trigger_condition: TriggerCondition,
+    // Paid to whoever executes the trigger, in the currency the order locks.
+    pub native_tip: u64,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct InitBlockTradeInstruction {
+    // In pc lots per coin lot, like limit_price. Both must be nonzero.
+    pub price: u64,
+    pub coin_qty: u64,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct DepositBlockTradeInstruction {
+    // The terms the depositor agrees to. They must match the block trade's,
+    // since anyone can init a block trade naming any OpenOrders accounts.
+    pub price: u64,
+    pub coin_qty: u64,
+}
+
+#[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
+pub struct SetMarketParamsInstruction {
+    // The signer of UpdateReferencePrice.
+    pub oracle: [u64; 4],
+    pub max_deviation_bps: u16,
+    // In native pc, or zero for none.
+    pub min_notional: u64,
+    // The signer of Prune, or zero for none.
+    pub prune_authority: [u64; 4],
+}
+
 #[derive(PartialEq, Eq, Debug, Clone, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 #[cfg_attr(feature = "fuzz", derive(arbitrary::Arbitrary))]
@@ -180,7 +403,16 @@ pub enum MarketInstruction {
     /// 6. `[writable]` spl-token account for the price currency
     /// 7. `[]` coin currency Mint
     /// 8. `[]` price currency Mint
+    /// 9. `[writable]` (optional) zeroed out MarketParams account, required
+    ///    for a nonzero `min_notional`
+    ///
+    /// With a MarketParams account, the market starts out with
+    /// `MarketOption::Params` set, as if `SetMarketParams` had been called
+    /// with no oracle and no price band.
     InitializeMarket(InitializeMarketInstruction),
+    /// Queues an order for `MatchOrders`. Fails with `RequestQueueRetired`
+    /// once the market is on layout version 3, see `NewOrderV3`.
+    ///
     /// 0. `[writable]` the market
     /// 1. `[writable]` the OpenOrders account to use
     /// 2. `[writable]` the request queue
@@ -190,7 +422,9 @@ pub enum MarketInstruction {
     /// 6. `[writable]` pc vault
     /// 7. `[]` spl token program
     /// 8. `[]` the rent sysvar
-    /// 9. `[writable]` (optional) the (M)SRM account used for fee discounts
+    /// 9. `[writable]` (optional) the (M)SRM account used for fee discounts,
+    ///    the owner's registry `Member` account staking it, or the owner's
+    ///    lockup `Vesting` account locking it
     NewOrder(NewOrderInstructionV1),
     /// 0. `[writable]` market
     /// 1. `[writable]` req_q
@@ -199,6 +433,9 @@ pub enum MarketInstruction {
     /// 4. `[writable]` asks
     /// 5. `[writable]` coin fee receivable account
     /// 6. `[writable]` pc fee receivable account
+    ///
+    /// Still drains the requests a market had queued when it retired its
+    /// request queue.
     MatchOrders(u16),
     /// ... `[writable]` OpenOrders
     /// accounts.len() - 4 `[writable]` market
@@ -206,6 +443,10 @@ pub enum MarketInstruction {
     /// accounts.len() - 2 `[writable]` coin fee receivable account
     /// accounts.len() - 1 `[writable]` pc fee receivable account
     ConsumeEvents(u16),
+    /// Queues a cancellation for `MatchOrders`. Fails with
+    /// `RequestQueueRetired` once the market is on layout version 3, see
+    /// `CancelOrderV2`.
+    ///
     /// 0. `[]` market
     /// 1. `[writable]` OpenOrders
     /// 2. `[writable]` the request queue
@@ -213,15 +454,23 @@ pub enum MarketInstruction {
     CancelOrder(CancelOrderInstruction),
     /// 0. `[writable]` market
     /// 1. `[writable]` OpenOrders
-    /// 2. `[signer]` the OpenOrders owner
+    /// 2. `[signer]` the OpenOrders owner, or `[]` when settling into the
+    ///    registered settle destination
     /// 3. `[writable]` coin vault
     /// 4. `[writable]` pc vault
     /// 5. `[writable]` coin wallet
     /// 6. `[writable]` pc wallet
     /// 7. `[]` vault signer
     /// 8. `[]` spl token program
-    /// 9. `[writable]` (optional) referrer pc wallet
+    /// 9. `[writable]` (optional) referrer pc wallet, only when the owner signs
+    /// 10. `[]` (optional) the SettleDestination account, required when the
+    ///     owner does not sign
+    ///
+    /// If the OpenOrders account has no resting orders, its entire balance
+    /// is settled, including any sub-lot dust still counted as locked.
     SettleFunds,
+    /// Like `CancelOrder`, see `CancelOrderByClientIdV2`.
+    ///
     /// 0. `[]` market
     /// 1. `[writable]` OpenOrders
     /// 2. `[writable]` the request queue
@@ -230,13 +479,21 @@ pub enum MarketInstruction {
     /// 0. `[writable]` market
     /// 1. `[signer]` disable authority
     DisableMarket,
+    /// Pays out the accrued pc fees. When a FeeDestinations account is
+    /// passed, the fees are split between its wallets by weight, and the
+    /// fee receivable account only gets what's left from rounding down.
+    ///
     /// 0. `[writable]` market
     /// 1. `[writable]` pc vault
     /// 2. `[signer]` fee sweeping authority
     /// 3. `[writable]` fee receivable account
     /// 4. `[]` vault signer
     /// 5. `[]` spl token program
+    /// 6. `[]` (optional) FeeDestinations account
+    /// 7.. `[writable]` the FeeDestinations wallets, in the order registered
     SweepFees,
+    /// Like `NewOrder`, with a `SelfTradeBehavior`.
+    ///
     /// 0. `[writable]` the market
     /// 1. `[writable]` the OpenOrders account to use
     /// 2. `[writable]` the request queue
@@ -246,8 +503,454 @@ pub enum MarketInstruction {
     /// 6. `[writable]` pc vault
     /// 7. `[]` spl token program
     /// 8. `[]` the rent sysvar
-    /// 9. `[writable]` (optional) the (M)SRM account used for fee discounts
+    /// 9. `[writable]` (optional) the (M)SRM account used for fee discounts,
+    ///    the owner's registry `Member` account staking it, or the owner's
+    ///    lockup `Vesting` account locking it
     NewOrderV2(NewOrderInstructionV2),
+    /// Places an order and matches it against the book in the same
+    /// instruction, rather than queueing it for `MatchOrders`. Fills are
+    /// still written to the event queue and settled by `ConsumeEvents`.
+    ///
+    /// The request queue must be empty. An existing market migrates with
+    /// `UpgradeMarket`, which stops `NewOrder` and `CancelOrder` from
+    /// queueing more requests, and drains those already queued with
+    /// `MatchOrders`. Markets listed on layout version 3 or later never
+    /// queue requests.
+    ///
+    /// 0. `[writable]` the market
+    /// 1. `[writable]` the OpenOrders account to use
+    /// 2. `[writable]` the request queue
+    /// 3. `[writable]` the (coin or price currency) account paying for the order
+    /// 4. `[signer]` owner of the OpenOrders account
+    /// 5. `[writable]` coin vault
+    /// 6. `[writable]` pc vault
+    /// 7. `[]` spl token program
+    /// 8. `[]` the rent sysvar
+    /// 9. `[writable]` event queue
+    /// 10. `[writable]` bids
+    /// 11. `[writable]` asks
+    /// 12. `[writable]` (optional) the (M)SRM account used for fee discounts,
+    ///     the signer's registry `Member` account staking it, or the
+    ///     signer's lockup `Vesting` account locking it
+    /// 13. `[]` (optional) the OpenOrdersDelegate account, when the signer is
+    ///     the delegate rather than the owner
+    /// 14.. `[]` (optional) up to `MAX_SELF_TRADE_ACCOUNTS` other OpenOrders
+    ///     accounts of the same owner on this market
+    /// .. `[writable]` (optional) a MarketStats account, zeroed and owned by
+    ///     the dex if new, required once the market has one registered
+    /// .. `[writable]` (optional) the owner's TraderStats account on this
+    ///     market, zeroed and owned by the dex if new
+    /// .. `[]` the clock sysvar, only with a MarketStats or TraderStats
+    ///     account
+    /// last. `[writable]` the market's MarketParams account, once it has
+    ///     one, recording the price of the order's last fill
+    ///
+    /// A delegate pays from its own token account, and any fee discount
+    /// account must belong to the delegate.
+    ///
+    /// Resting orders of the other OpenOrders accounts passed are treated as
+    /// the owner's own, so the order's SelfTradeBehavior applies to them as
+    /// it does to orders of the account placing it.
+    ///
+    /// A MarketStats account accumulates the volume and trade count of the
+    /// market, and its last trade's price and slot, over the matches of the
+    /// instructions it's passed to. It also counts the orders that reached a
+    /// resting order of the same OpenOrders account, or of another account
+    /// of the same owner passed with them, so wash trading can be told
+    /// apart from volume. Only the MarketStats account registered with
+    /// `SetMarketParams` is sure to see every trade.
+    ///
+    /// A TraderStats account accumulates the owner's taker volume over the
+    /// last 30 days, which lowers the order's taker fee by one tier for each
+    /// of `fees::TAKER_VOLUME_TIERS` it reaches, and the same counts for the
+    /// owner's orders.
+    NewOrderV3(NewOrderInstructionV2),
+    /// Removes an order from the book in the same instruction. The request
+    /// queue must be empty.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[writable]` OpenOrders
+    /// 2. `[writable]` the request queue
+    /// 3. `[signer]` the OpenOrders owner
+    /// 4. `[writable]` event queue
+    /// 5. `[writable]` bids
+    /// 6. `[writable]` asks
+    /// 7. `[]` (optional) the OpenOrdersDelegate account, when the signer is
+    ///     the delegate rather than the owner
+    CancelOrderV2(CancelOrderInstructionV2),
+    /// Same accounts as `CancelOrderV2`.
+    CancelOrderByClientIdV2(u64),
+    /// Moves the event queue and order book into larger accounts and points
+    /// the market at them. The new accounts must be owned by the dex, zeroed,
+    /// and at least as large as the ones they replace. The old accounts are
+    /// left in place but are no longer referenced by the market.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[writable]` event queue
+    /// 2. `[writable]` bids
+    /// 3. `[writable]` asks
+    /// 4. `[writable]` new event queue
+    /// 5. `[writable]` new bids
+    /// 6. `[writable]` new asks
+    /// 7. `[signer]` disable authority
+    ResizeMarketAccounts,
+    /// Changes the lot sizes of a market whose book has gone empty and
+    /// that has no trigger orders pending. Open orders balances are held in
+    /// native units, so they carry over as-is. The reference and last fill
+    /// prices are in lots, so they're cleared until the oracle next reports
+    /// and the market next trades.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[]` the request queue
+    /// 2. `[]` event queue
+    /// 3. `[]` bids
+    /// 4. `[]` asks
+    /// 5. `[signer]` disable authority
+    /// 6. `[writable]` the market's MarketParams account, once it has one
+    UpdateLotSizes(UpdateLotSizesInstruction),
+    /// Escrows the funds for an order that is placed once the market trades
+    /// through the trigger price. The escrow is sized for the base fee tier,
+    /// and the tip is taken from the payer on top of it.
+    ///
+    /// The trigger order account must be owned by the dex, rent exempt, and
+    /// either zeroed or left empty by a previous trigger. The OpenOrders
+    /// account is where the order will rest and settle. Only markets with a
+    /// MarketParams account take trigger orders, which it counts until they
+    /// execute or are cancelled.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[writable]` trigger order
+    /// 2. `[writable]` the OpenOrders account to use
+    /// 3. `[writable]` the (coin or price currency) account paying for the escrow
+    /// 4. `[signer]` owner of the OpenOrders account
+    /// 5. `[writable]` coin vault
+    /// 6. `[writable]` pc vault
+    /// 7. `[]` spl token program
+    /// 8. `[]` the rent sysvar
+    /// 9. `[writable]` the market's MarketParams account
+    NewTriggerOrder(NewTriggerOrderInstruction),
+    /// Permissionless. Places the escrowed order and matches it against the
+    /// book once the last fill price recorded in the market's MarketParams
+    /// meets the trigger condition, paying the tip to the caller's wallet. The
+    /// request queue must be empty, and the trigger order account is left
+    /// empty.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[writable]` trigger order
+    /// 2. `[writable]` OpenOrders
+    /// 3. `[writable]` the request queue
+    /// 4. `[writable]` event queue
+    /// 5. `[writable]` bids
+    /// 6. `[writable]` asks
+    /// 7. `[writable]` coin vault
+    /// 8. `[writable]` pc vault
+    /// 9. `[writable]` (coin or price currency) wallet receiving the tip
+    /// 10. `[]` vault signer
+    /// 11. `[]` spl token program
+    /// 12. `[writable]` the market's MarketParams account
+    /// 13. `[writable]` (optional) an initialized MarketStats account,
+    ///     required once the market has one registered
+    /// 14. `[]` (optional) the clock sysvar, with the MarketStats account
+    ExecuteTriggerOrder,
+    /// Returns the escrow and tip of a trigger order that has not executed,
+    /// leaving the trigger order account empty.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[writable]` trigger order
+    /// 2. `[signer]` owner of the trigger order
+    /// 3. `[writable]` (coin or price currency) wallet receiving the refund
+    /// 4. `[writable]` coin vault
+    /// 5. `[writable]` pc vault
+    /// 6. `[]` vault signer
+    /// 7. `[]` spl token program
+    /// 8. `[writable]` the market's MarketParams account
+    CancelTriggerOrder,
+    /// Lets the delegate place orders with `NewOrderV3` and cancel them with
+    /// `CancelOrderV2` or `CancelOrderByClientIdV2` on the owner's behalf.
+    /// Settlement still requires the owner. Replaces the delegate if the
+    /// account is already in use for the same OpenOrders.
+    ///
+    /// 0. `[]` market
+    /// 1. `[]` OpenOrders
+    /// 2. `[signer]` the OpenOrders owner
+    /// 3. `[writable]` OpenOrdersDelegate account, owned by the dex
+    /// 4. `[]` the delegate
+    /// 5. `[]` the rent sysvar
+    SetOpenOrdersDelegate,
+    /// Removes a delegate, leaving the OpenOrdersDelegate account empty.
+    ///
+    /// 0. `[]` market
+    /// 1. `[]` OpenOrders
+    /// 2. `[signer]` the OpenOrders owner
+    /// 3. `[writable]` OpenOrdersDelegate account
+    RevokeOpenOrdersDelegate,
+    /// Registers the wallets that `SettleFunds` may pay into without the
+    /// owner's signature, e.g. vaults of a program settling on a user's
+    /// behalf. Replaces the wallets if the account is already in use for
+    /// the same OpenOrders.
+    ///
+    /// 0. `[]` market
+    /// 1. `[]` OpenOrders
+    /// 2. `[signer]` the OpenOrders owner
+    /// 3. `[writable]` SettleDestination account, owned by the dex
+    /// 4. `[]` coin wallet
+    /// 5. `[]` pc wallet
+    /// 6. `[]` the rent sysvar
+    SetSettleDestination,
+    /// Removes the registered wallets, leaving the SettleDestination account
+    /// empty.
+    ///
+    /// 0. `[]` market
+    /// 1. `[]` OpenOrders
+    /// 2. `[signer]` the OpenOrders owner
+    /// 3. `[writable]` SettleDestination account
+    ClearSettleDestination,
+    /// Sets the taker fee and maker rebate of the base fee tier, in basis
+    /// points. Maker rebates are paid out of taker fees, so the schedule
+    /// must leave every tier's taker fee enough to cover them.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[signer]` disable authority
+    SetFeeSchedule(SetFeeScheduleInstruction),
+    /// Permissionless. Migrates a market listed under an older layout to
+    /// `MarketState::VERSION` in place, leaving the account's padding and
+    /// size as they are. Instructions relying on newer fields fail with
+    /// `MarketNotUpgraded` until it's run. Migrations keep the market's
+    /// behavior, except that version 3 retires the request queue, so the
+    /// queueing instructions fail with `RequestQueueRetired` afterwards.
+    ///
+    /// 0. `[writable]` market
+    UpgradeMarket,
+    /// Like `ConsumeEvents`, but starts at the event with sequence number
+    /// `start_seq_num`, or the front of the queue if that's already been
+    /// freed, and skips the events of OpenOrders accounts that weren't
+    /// passed rather than stopping at them. Skipped events stay queued, and
+    /// a consumer keeping its own cursor can resume from where it left off.
+    /// `limit` counts the events looked at, consumed or not.
+    ///
+    /// Events consumed ahead of their turn have their slots freed too, so an
+    /// unconsumed event at the front doesn't fill the queue up. Freeing them
+    /// moves the events still pending among the first
+    /// `EventQueue::RECLAIM_SPAN` back past them, which raises their
+    /// sequence numbers but never lowers one, so a cursor never skips an
+    /// event. Consumed events further back keep their slots until the front
+    /// catches up.
+    ///
+    /// ... `[writable]` OpenOrders
+    /// accounts.len() - 4 `[writable]` market
+    /// accounts.len() - 3 `[writable]` event queue
+    /// accounts.len() - 2 `[writable]` coin fee receivable account
+    /// accounts.len() - 1 `[writable]` pc fee receivable account
+    ConsumeEventsFrom(ConsumeEventsFromInstruction),
+    /// Places up to `MAX_BATCH_ORDERS` orders, of either side, for one
+    /// OpenOrders account, matching each against the book in turn as
+    /// `NewOrderV3` would. The funds to lock are taken in one transfer per
+    /// currency after the last order. The request queue must be empty.
+    ///
+    /// The batch succeeds or fails as a whole. For every order it logs a
+    /// line of `ORDER_LOG_TAG`, the order's index in the batch, the low
+    /// and high words of its order id, and the lots left resting on the
+    /// book, which `client::parse_order_logs` decodes.
+    ///
+    /// 0. `[writable]` the market
+    /// 1. `[writable]` the OpenOrders account to use
+    /// 2. `[writable]` the request queue
+    /// 3. `[writable]` the coin currency account paying for asks
+    /// 4. `[writable]` the price currency account paying for bids
+    /// 5. `[signer]` owner of the OpenOrders account
+    /// 6. `[writable]` coin vault
+    /// 7. `[writable]` pc vault
+    /// 8. `[]` spl token program
+    /// 9. `[]` the rent sysvar
+    /// 10. `[writable]` event queue
+    /// 11. `[writable]` bids
+    /// 12. `[writable]` asks
+    /// 13. `[writable]` (optional) the (M)SRM account used for fee discounts,
+    ///     the signer's registry `Member` account staking it, or the
+    ///     signer's lockup `Vesting` account locking it
+    /// 14. `[]` (optional) the OpenOrdersDelegate account, when the signer is
+    ///     the delegate rather than the owner
+    /// 15.. `[]` (optional) up to `MAX_SELF_TRADE_ACCOUNTS` other OpenOrders
+    ///     accounts of the same owner on this market, as for `NewOrderV3`
+    /// .. `[writable]` (optional) a MarketStats account, as for `NewOrderV3`
+    ///     and required likewise
+    /// .. `[writable]` (optional) a TraderStats account, as for `NewOrderV3`
+    /// .. `[]` the clock sysvar, only with a MarketStats or TraderStats
+    ///     account
+    /// last. `[writable]` the market's MarketParams account, once it has
+    ///     one, recording the price of the order's last fill
+    NewOrderBatch(NewOrderBatchInstruction),
+    /// Cancels every order of the OpenOrders account, then places the new
+    /// orders as `NewOrderBatch` does, all in one instruction, so the
+    /// account is never left half quoted. The ladder may be empty, which
+    /// only cancels.
+    ///
+    /// Cancelled orders leave the book, and their funds are unlocked, at
+    /// once, so the new orders are paid for from the free balance they
+    /// join before the payer accounts. Their slots are freed at once too,
+    /// except those of orders with fills still waiting for `ConsumeEvents`,
+    /// which frees them after those fills.
+    ///
+    /// Same accounts as `NewOrderBatch`.
+    MassQuote(NewOrderBatchInstruction),
+    /// Registers up to `MAX_FEE_DESTINATIONS` pc wallets for `SweepFees` to
+    /// split the market's fees between, in proportion to their weights.
+    /// Replaces the wallets if the account is already in use for the same
+    /// market. Weights must be nonzero.
+    ///
+    /// 0. `[]` market
+    /// 1. `[signer]` fee sweeping authority
+    /// 2. `[writable]` FeeDestinations account, owned by the dex
+    /// 3. `[]` the rent sysvar
+    /// 4.. `[]` the pc wallets, one per weight
+    SetFeeDestinations(SetFeeDestinationsInstruction),
+    /// Replaces the market's `MarketOption`s with the given bits. Unknown
+    /// bits are rejected, as are changes to `MarketOption::Params`, which
+    /// only `SetMarketParams` sets.
+    ///
+    /// With `MarketOption::UniqueClientIds`, a new order fails with
+    /// `DuplicateClientOrderId` if its nonzero client id is already used by
+    /// an order of the same OpenOrders account. An order's id stays in use
+    /// until `ConsumeEvents` frees its slot, cancelled or filled orders
+    /// included, so `CancelOrderByClientId` always finds the latest order.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[signer]` disable authority
+    SetMarketOptions(u8),
+    /// Records the market as the canonical market of its mint pair, in the
+    /// MarketListing account at the address `find_market_listing_address`
+    /// derives from the mints, with the nonce it returns. The listing is
+    /// created here, paid for by the signer, and a pair that already has
+    /// one fails with `MarketAlreadyListed`.
+    ///
+    /// 0. `[]` market
+    /// 1. `[writable, signer]` the lister, paying the listing's rent
+    /// 2. `[writable]` the MarketListing account
+    /// 3. `[]` the system program
+    /// 4. `[]` the rent sysvar
+    ListMarket(u8),
+    /// Points a mint pair's listing at another market of the same mints,
+    /// settling disputes over which market is canonical.
+    ///
+    /// 0. `[writable]` the MarketListing account
+    /// 1. `[]` the new market
+    /// 2. `[signer]` disable authority
+    ReassignMarketListing,
+    /// Records the terms of a pre-negotiated block trade between a buyer's
+    /// and a seller's OpenOrders accounts on the market. Permissionless, so
+    /// the terms bind no one until each side agrees to them by depositing
+    /// with `DepositBlockTrade`.
+    ///
+    /// The block trade account must be owned by the dex, rent exempt, and
+    /// either zeroed or left empty by a previous block trade.
+    ///
+    /// 0. `[]` market
+    /// 1. `[writable]` block trade
+    /// 2. `[]` the buyer's OpenOrders account
+    /// 3. `[]` the seller's OpenOrders account
+    /// 4. `[]` the rent sysvar
+    InitBlockTrade(InitBlockTradeInstruction),
+    /// Locks one side of a block trade in its OpenOrders account, as an
+    /// order outside the book: the buyer's pc, or the seller's coin. The
+    /// free balance is used first and the rest is paid from the payer. Block
+    /// trades pay no fees. Fails unless the price and quantity given match
+    /// the block trade's.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[writable]` block trade
+    /// 2. `[writable]` the buyer's or the seller's OpenOrders account
+    /// 3. `[writable]` the (coin or price currency) account paying for the deposit
+    /// 4. `[signer]` owner of the OpenOrders account
+    /// 5. `[writable]` coin vault
+    /// 6. `[writable]` pc vault
+    /// 7. `[]` spl token program
+    DepositBlockTrade(DepositBlockTradeInstruction),
+    /// Permissionless. Once both sides have deposited, prints the trade to
+    /// the event queue without walking the book, as a fill and an out for
+    /// each side, which `ConsumeEvents` applies like any other. The price
+    /// must be within the book's spread: at or above the best bid, and at or
+    /// below the best ask. The block trade account is left empty.
+    ///
+    /// 0. `[]` market
+    /// 1. `[writable]` block trade
+    /// 2. `[]` the buyer's OpenOrders account
+    /// 3. `[]` the seller's OpenOrders account
+    /// 4. `[writable]` event queue
+    /// 5. `[]` bids
+    /// 6. `[]` asks
+    SettleBlockTrade,
+    /// Unlocks the deposits of a block trade that hasn't settled, leaving
+    /// the block trade account empty. Either side may cancel.
+    ///
+    /// 0. `[]` market
+    /// 1. `[writable]` block trade
+    /// 2. `[writable]` the buyer's OpenOrders account
+    /// 3. `[writable]` the seller's OpenOrders account
+    /// 4. `[signer]` owner of either OpenOrders account
+    CancelBlockTrade,
+    /// Sets the oracle allowed to report the market's reference price, how
+    /// far from it, in basis points, orders may be priced, the smallest
+    /// order the market takes, in native pc, and the authority allowed to
+    /// `Prune` OpenOrders accounts. The first call turns the
+    /// params account into the market's only one and sets
+    /// `MarketOption::Params`, unless `InitializeMarket` already did; later
+    /// calls must pass that account, and keep its reference price.
+    ///
+    /// From then on, every new order must pass the params account and
+    /// fails with `PriceOutsideBand` if its limit price deviates from the
+    /// reference price by more than `max_deviation_bps`, and with
+    /// `OrderBelowMinNotional` if its `max_qty` at its limit price is worth
+    /// less than `min_notional`. The band is off while either the reference
+    /// price or `max_deviation_bps` is zero. `NewOrder` and `NewOrderV2`
+    /// can't pass the account, so they fail with `MarketParamsNotProvided`.
+    ///
+    /// A MarketStats account passed is registered as the market's, replacing
+    /// any registered before, and every matching instruction must pass it
+    /// from then on, so its stats count every trade on the market. Matching
+    /// instructions that leave it off fail with `MarketStatsNotProvided`.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[writable]` MarketParams account, owned by the dex, zeroed and
+    ///    rent exempt if new
+    /// 2. `[signer]` disable authority
+    /// 3. `[]` the rent sysvar
+    /// 4. `[writable]` (optional) the MarketStats account to register, owned
+    ///    by the dex, zeroed and rent exempt if new
+    SetMarketParams(SetMarketParamsInstruction),
+    /// Reports the market's reference price, in pc lots per coin lot like
+    /// order prices.
+    ///
+    /// 0. `[]` market
+    /// 1. `[writable]` MarketParams account
+    /// 2. `[signer]` the oracle
+    UpdateReferencePrice(u64),
+    /// Takes every order of an OpenOrders account off the book, e.g. one
+    /// whose owner may no longer trade on a permissioned market. As with
+    /// `CancelOrderV2`, the funds are unlocked, and the slots freed, by
+    /// `ConsumeEvents`. The request queue must be empty.
+    ///
+    /// 0. `[writable]` market
+    /// 1. `[]` MarketParams account
+    /// 2. `[signer]` the prune authority of the MarketParams account
+    /// 3. `[writable]` the OpenOrders account to prune
+    /// 4. `[]` the request queue
+    /// 5. `[writable]` event queue
+    /// 6. `[writable]` bids
+    /// 7. `[writable]` asks
+    Prune,
+    /// `NewOrderV3`, for an order that's cancelled instead of filled once
+    /// the market has moved through its price by more than
+    /// `cancel_if_through_reference_bps` while it rests, e.g. a quote left
+    /// on the book through an outage.
+    ///
+    /// The reference is the market's oracle price when the matching
+    /// instruction is passed a MarketParams account with one, the last fill
+    /// price it recorded when the oracle hasn't reported, and the price of
+    /// the last fill in the event queue without one. The order's own price
+    /// stands in for the price at placement.
+    ///
+    /// Takes the accounts of `NewOrderV3`.
+    NewOrderV4(NewOrderInstructionV3),
 }
 
 impl MarketInstruction {
@@ -256,7 +959,7 @@ impl MarketInstruction {
     }
 
     pub fn unpack(versioned_bytes: &[u8]) -> Option<Self> {
-        if versioned_bytes.len() < 5 || versioned_bytes.len() > 58 {
+        if versioned_bytes.len() < 5 || versioned_bytes.len() > 13 + 36 * MAX_BATCH_ORDERS {
             return None;
         }
         let (&[version], &discrim, data) = array_refs![versioned_bytes, 1, 4; ..;];
@@ -265,15 +968,20 @@ impl MarketInstruction {
         }
         let discrim = u32::from_le_bytes(discrim);
         Some(match (discrim, data.len()) {
-            (0, 34) => MarketInstruction::InitializeMarket({
+            (0, 34) | (0, 42) => MarketInstruction::InitializeMarket({
                 let data_array = array_ref![data, 0, 34];
                 let fields = array_refs![data_array, 8, 8, 2, 8, 8];
+                let min_notional = match data.len() {
+                    42 => u64::from_le_bytes(*array_ref![data, 34, 8]),
+                    _ => 0,
+                };
                 InitializeMarketInstruction {
                     coin_lot_size: u64::from_le_bytes(*fields.0),
                     pc_lot_size: u64::from_le_bytes(*fields.1),
                     fee_rate_bps: u16::from_le_bytes(*fields.2),
                     vault_signer_nonce: u64::from_le_bytes(*fields.3),
                     pc_dust_threshold: u64::from_le_bytes(*fields.4),
+                    min_notional,
                 }
             }),
             (1, 32) => MarketInstruction::NewOrder({
@@ -315,13 +1023,125 @@ impl MarketInstruction {
             (8, 0) => MarketInstruction::SweepFees,
             (9, 36) => MarketInstruction::NewOrderV2({
                 let data_arr = array_ref![data, 0, 36];
-                let (v1_data_arr, v2_data_arr) = array_refs![data_arr, 32, 4];
+                NewOrderInstructionV2::unpack(data_arr)?
+            }),
+            (10, 36) => MarketInstruction::NewOrderV3({
+                let data_arr = array_ref![data, 0, 36];
+                NewOrderInstructionV2::unpack(data_arr)?
+            }),
+            (11, 20) => MarketInstruction::CancelOrderV2({
+                let data_array = array_ref![data, 0, 20];
+                let fields = array_refs![data_array, 4, 16];
+                let side = match u32::from_le_bytes(*fields.0) {
+                    0 => Side::Bid,
+                    1 => Side::Ask,
+                    _ => return None,
+                };
+                let order_id = u128::from_le_bytes(*fields.1);
+                CancelOrderInstructionV2 { side, order_id }
+            }),
+            (12, 8) => {
+                let client_id = array_ref![data, 0, 8];
+                MarketInstruction::CancelOrderByClientIdV2(u64::from_le_bytes(*client_id))
+            }
+            (13, 0) => MarketInstruction::ResizeMarketAccounts,
+            (14, 16) => MarketInstruction::UpdateLotSizes({
+                let data_array = array_ref![data, 0, 16];
+                let fields = array_refs![data_array, 8, 8];
+                UpdateLotSizesInstruction {
+                    coin_lot_size: u64::from_le_bytes(*fields.0),
+                    pc_lot_size: u64::from_le_bytes(*fields.1),
+                }
+            }),
+            (15, 56) => MarketInstruction::NewTriggerOrder({
+                let data_arr = array_ref![data, 0, 56];
+                let (v1_data_arr, v2_data_arr, price_arr, condition_arr, tip_arr) =
+                    array_refs![data_arr, 32, 4, 8, 4, 8];
                 let v1_instr = NewOrderInstructionV1::unpack(v1_data_arr)?;
                 let self_trade_behavior = SelfTradeBehavior::try_from_primitive(
                     u32::from_le_bytes(*v2_data_arr).try_into().ok()?,
                 )
                 .ok()?;
-                v1_instr.add_self_trade_behavior(self_trade_behavior)
+                let trigger_condition = TriggerCondition::try_from_primitive(
+                    u32::from_le_bytes(*condition_arr).try_into().ok()?,
+                )
+                .ok()?;
+                NewTriggerOrderInstruction {
+                    order: v1_instr.add_self_trade_behavior(self_trade_behavior),
+                    trigger_price: u64::from_le_bytes(*price_arr),
+                    trigger_condition,
+                    native_tip: u64::from_le_bytes(*tip_arr),
+                }
+            }),
+            (16, 0) => MarketInstruction::ExecuteTriggerOrder,
+            (17, 0) => MarketInstruction::CancelTriggerOrder,
+            (18, 0) => MarketInstruction::SetOpenOrdersDelegate,
+            (19, 0) => MarketInstruction::RevokeOpenOrdersDelegate,
+            (20, 0) => MarketInstruction::SetSettleDestination,
+            (21, 0) => MarketInstruction::ClearSettleDestination,
+            (22, 4) => MarketInstruction::SetFeeSchedule({
+                let data_array = array_ref![data, 0, 4];
+                let fields = array_refs![data_array, 2, 2];
+                SetFeeScheduleInstruction {
+                    taker_fee_bps: u16::from_le_bytes(*fields.0),
+                    maker_rebate_bps: u16::from_le_bytes(*fields.1),
+                }
+            }),
+            (23, 0) => MarketInstruction::UpgradeMarket,
+            (24, 10) => MarketInstruction::ConsumeEventsFrom({
+                let data_array = array_ref![data, 0, 10];
+                let fields = array_refs![data_array, 8, 2];
+                ConsumeEventsFromInstruction {
+                    start_seq_num: u64::from_le_bytes(*fields.0),
+                    limit: u16::from_le_bytes(*fields.1),
+                }
+            }),
+            (25, len) if len > 8 => {
+                MarketInstruction::NewOrderBatch(NewOrderBatchInstruction::unpack(data)?)
+            }
+            (26, _) => MarketInstruction::MassQuote(NewOrderBatchInstruction::unpack(data)?),
+            (27, _) => {
+                MarketInstruction::SetFeeDestinations(SetFeeDestinationsInstruction::unpack(data)?)
+            }
+            (28, 1) => MarketInstruction::SetMarketOptions(data[0]),
+            (29, 1) => MarketInstruction::ListMarket(data[0]),
+            (30, 0) => MarketInstruction::ReassignMarketListing,
+            (31, 16) => MarketInstruction::InitBlockTrade({
+                let data_array = array_ref![data, 0, 16];
+                let fields = array_refs![data_array, 8, 8];
+                InitBlockTradeInstruction {
+                    price: u64::from_le_bytes(*fields.0),
+                    coin_qty: u64::from_le_bytes(*fields.1),
+                }
+            }),
+            (32, 16) => MarketInstruction::DepositBlockTrade({
+                let data_array = array_ref![data, 0, 16];
+                let fields = array_refs![data_array, 8, 8];
+                DepositBlockTradeInstruction {
+                    price: u64::from_le_bytes(*fields.0),
+                    coin_qty: u64::from_le_bytes(*fields.1),
+                }
+            }),
+            (33, 0) => MarketInstruction::SettleBlockTrade,
+            (34, 0) => MarketInstruction::CancelBlockTrade,
+            (35, 74) => MarketInstruction::SetMarketParams({
+                let data_array = array_ref![data, 0, 74];
+                let fields = array_refs![data_array, 32, 2, 8, 32];
+                SetMarketParamsInstruction {
+                    oracle: cast(*fields.0),
+                    max_deviation_bps: u16::from_le_bytes(*fields.1),
+                    min_notional: u64::from_le_bytes(*fields.2),
+                    prune_authority: cast(*fields.3),
+                }
+            }),
+            (36, 8) => {
+                let price = array_ref![data, 0, 8];
+                MarketInstruction::UpdateReferencePrice(u64::from_le_bytes(*price))
+            }
+            (37, 0) => MarketInstruction::Prune,
+            (38, 38) => MarketInstruction::NewOrderV4({
+                let data_arr = array_ref![data, 0, 38];
+                NewOrderInstructionV3::unpack(data_arr)?
             }),
             _ => return None,
         })
@@ -361,6 +1181,7 @@ pub fn initialize_market(
         fee_rate_bps: 0,
         vault_signer_nonce,
         pc_dust_threshold,
+        min_notional: 0,
     })
     .pack();
 
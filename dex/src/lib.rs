@@ -7,6 +7,8 @@ pub mod error;
 #[cfg(test)]
 mod tests;
 
+#[cfg(any(feature = "client", test))]
+pub mod client;
 pub mod critbit;
 pub mod fees;
 pub mod instruction;
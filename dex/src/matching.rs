@@ -14,7 +14,10 @@ use crate::{
     critbit::{LeafNode, NodeHandle, Slab, SlabView},
     error::DexError,
     fees::{self, FeeTier},
-    state::{Event, EventQueue, EventView, MarketState, Request, RequestQueue, RequestView},
+    state::{
+        Event, EventQueue, EventView, MarketParams, MarketState, MarketStats, Request,
+        RequestQueue, RequestView, TraderStats,
+    },
 };
 
 #[cfg(not(feature = "program"))]
@@ -46,7 +49,7 @@ pub enum OrderType {
     PostOnly = 2,
 }
 
-fn extract_price_from_order_id(order_id: u128) -> u64 {
+pub(crate) fn extract_price_from_order_id(order_id: u128) -> u64 {
     (order_id >> 64) as u64
 }
 
@@ -55,6 +58,19 @@ pub struct OrderBookState<'a> {
     pub bids: &'a mut Slab,
     pub asks: &'a mut Slab,
     pub market_state: &'a mut MarketState,
+    // Accounts whose resting orders count as the incoming order's own when
+    // applying its SelfTradeBehavior, besides its own OpenOrders account.
+    pub self_trade_accounts: &'a [[u64; 4]],
+    // Records every trade, at the given slot.
+    pub stats: Option<(&'a mut MarketStats, u64)>,
+    // Records the incoming order's taker volume, on the given unix day.
+    pub trader_stats: Option<(&'a mut TraderStats, u64)>,
+    // The market's params, if passed, which record the price of every
+    // trade. Resting orders with a `cancel_if_through_reference_bps` are
+    // checked against the oracle price they hold, or the last fill price
+    // until the oracle reports. Without them, against the last fill in the
+    // event queue.
+    pub params: Option<&'a mut MarketParams>,
 }
 
 impl<'ob> OrderBookState<'ob> {
@@ -65,6 +81,47 @@ impl<'ob> OrderBookState<'ob> {
         }
     }
 
+    fn record_trade(&mut self, qty: u64, price: NonZeroU64) {
+        let coin_lot_size = self.market_state.coin_lot_size;
+        let pc_lot_size = self.market_state.pc_lot_size;
+        if let Some((stats, slot)) = self.stats.as_mut() {
+            stats.record_trade(*slot, qty, price, coin_lot_size, pc_lot_size);
+        }
+        if let Some((trader_stats, day)) = self.trader_stats.as_mut() {
+            let native_pc_qty = qty.saturating_mul(price.get()).saturating_mul(pc_lot_size);
+            trader_stats.record_volume(*day, native_pc_qty);
+        }
+        if let Some(params) = self.params.as_mut() {
+            params.last_fill_price = price.get();
+        }
+    }
+
+    fn record_self_cross(&mut self, same_account: bool) {
+        if let Some((stats, _)) = self.stats.as_mut() {
+            stats.record_self_cross(same_account);
+        }
+        if let Some((trader_stats, _)) = self.trader_stats.as_mut() {
+            trader_stats.record_self_cross(same_account);
+        }
+    }
+
+    // Whether the reference price has moved through a resting order's price
+    // by more than the order allows, so it should be cancelled rather than
+    // filled.
+    fn is_through_reference(order: &LeafNode, side: Side, reference_price: Option<u64>) -> bool {
+        let bps = order.cancel_if_through_reference_bps();
+        let reference_price = match reference_price {
+            Some(price) if bps != 0 => price,
+            _ => return false,
+        };
+        let price = order.price().get();
+        let moved_through = match side {
+            Side::Bid => price.saturating_sub(reference_price),
+            Side::Ask => reference_price.saturating_sub(price),
+        };
+        moved_through as u128 * 10_000 > price as u128 * bps as u128
+    }
+
     fn find_bbo(&self, side: Side) -> Option<NodeHandle> {
         match side {
             Side::Bid => self.bids.find_max(),
@@ -97,6 +154,23 @@ impl<'ob> OrderBookState<'ob> {
         Ok(())
     }
 
+    pub fn process_request_directly(
+        &mut self,
+        request: &Request,
+        event_q: &mut EventQueue,
+    ) -> DexResult {
+        let mut limit_remaining = std::u16::MAX;
+        // Every step of matching writes an event, so a request only runs
+        // out of steps once the queue can't take any more.
+        if self
+            .process_orderbook_request(request, event_q, &mut limit_remaining)?
+            .is_some()
+        {
+            Err(DexErrorCode::EventQueueFull)?
+        }
+        Ok(())
+    }
+
     fn process_orderbook_request(
         &mut self,
         request: &Request,
@@ -115,6 +189,7 @@ impl<'ob> OrderBookState<'ob> {
                 native_pc_qty_locked,
                 client_order_id,
                 self_trade_behavior,
+                cancel_if_through_reference_bps,
             } => self
                 .new_order(
                     NewOrderParams {
@@ -128,6 +203,7 @@ impl<'ob> OrderBookState<'ob> {
                         native_pc_qty_locked,
                         client_order_id: client_order_id.map_or(0, NonZeroU64::get),
                         self_trade_behavior,
+                        cancel_if_through_reference_bps,
                     },
                     event_q,
                     limit,
@@ -144,6 +220,7 @@ impl<'ob> OrderBookState<'ob> {
                         native_pc_qty_locked: remaining.native_pc_qty_remaining,
                         client_order_id,
                         self_trade_behavior,
+                        cancel_if_through_reference_bps,
                     })
                 }),
             RequestView::CancelOrder {
@@ -180,6 +257,7 @@ struct NewOrderParams {
     native_pc_qty_locked: Option<NonZeroU64>,
     client_order_id: u64,
     self_trade_behavior: SelfTradeBehavior,
+    cancel_if_through_reference_bps: u16,
 }
 
 struct OrderRemaining {
@@ -207,6 +285,7 @@ impl<'ob> OrderBookState<'ob> {
             mut native_pc_qty_locked,
             client_order_id,
             self_trade_behavior,
+            cancel_if_through_reference_bps,
         } = params;
         let (post_only, post_allowed) = match order_type {
             OrderType::Limit => (false, true),
@@ -214,6 +293,17 @@ impl<'ob> OrderBookState<'ob> {
             OrderType::PostOnly => (true, true),
         };
         let limit_price = extract_price_from_order_id(order_id);
+        // Taken before matching, so the order's own fills don't move it.
+        let reference_price = self
+            .params
+            .as_ref()
+            .and_then(|params| {
+                params
+                    .reference_price()
+                    .or_else(|| params.last_fill_price())
+            })
+            .map(NonZeroU64::get)
+            .or_else(|| event_q.last_fill_price());
         while *limit > 0 {
             *limit -= 1;
             let remaining_order = match side {
@@ -230,6 +320,8 @@ impl<'ob> OrderBookState<'ob> {
                         post_allowed,
                         client_order_id,
                         self_trade_behavior,
+                        cancel_if_through_reference_bps,
+                        reference_price,
                     },
                     event_q,
                 ),
@@ -247,6 +339,8 @@ impl<'ob> OrderBookState<'ob> {
                             post_allowed,
                             client_order_id,
                             self_trade_behavior,
+                            cancel_if_through_reference_bps,
+                            reference_price,
                         },
                         event_q,
                     )
@@ -278,6 +372,8 @@ struct NewAskParams {
     post_allowed: bool,
     client_order_id: u64,
     self_trade_behavior: SelfTradeBehavior,
+    cancel_if_through_reference_bps: u16,
+    reference_price: Option<u64>,
 }
 
 impl<'ob> OrderBookState<'ob> {
@@ -297,12 +393,16 @@ impl<'ob> OrderBookState<'ob> {
             post_allowed,
             client_order_id,
             self_trade_behavior,
+            cancel_if_through_reference_bps,
+            reference_price,
         } = params;
         let mut unfilled_qty = max_qty.get();
         let mut accum_fill_price = 0;
 
         let pc_lot_size = self.market_state.pc_lot_size;
         let coin_lot_size = self.market_state.coin_lot_size;
+        let fee_schedule = self.market_state.fee_schedule();
+        let self_trade_accounts = self.self_trade_accounts;
 
         let mut accum_maker_rebates = 0;
         let crossed;
@@ -329,6 +429,27 @@ impl<'ob> OrderBookState<'ob> {
                 break true;
             }
 
+            if Self::is_through_reference(best_bid_ref, Side::Bid, reference_price) {
+                let best_bid_id = best_bid_ref.order_id();
+                event_q
+                    .push_back(Event::new(EventView::Out {
+                        side: Side::Bid,
+                        native_qty_unlocked: best_bid_ref.quantity()
+                            * trade_price.get()
+                            * pc_lot_size,
+                        native_qty_still_locked: 0,
+                        order_id: best_bid_id,
+                        owner: best_bid_ref.owner(),
+                        owner_slot: best_bid_ref.owner_slot(),
+                        client_order_id: NonZeroU64::new(best_bid_ref.client_order_id()),
+                    }))
+                    .map_err(|_| DexErrorCode::EventQueueFull)?;
+                self.orders_mut(Side::Bid)
+                    .remove_by_key(best_bid_id)
+                    .unwrap();
+                break false;
+            }
+
             let bid_size = best_bid_ref.quantity();
             let trade_qty = bid_size.min(unfilled_qty);
 
@@ -336,7 +457,9 @@ impl<'ob> OrderBookState<'ob> {
                 break true;
             }
 
-            let order_would_self_trade = owner == best_bid_ref.owner();
+            let same_account = owner == best_bid_ref.owner();
+            let order_would_self_trade =
+                same_account || self_trade_accounts.contains(&best_bid_ref.owner());
             if order_would_self_trade {
                 let best_bid_id = best_bid_ref.order_id();
                 let cancelled_provide_qty;
@@ -390,6 +513,8 @@ impl<'ob> OrderBookState<'ob> {
                     .push_back(take_out)
                     .map_err(|_| DexErrorCode::EventQueueFull)?;
 
+                self.record_self_cross(same_account);
+
                 let order_remaining =
                     NonZeroU64::new(unfilled_qty).map(|coin_qty_remaining| OrderRemaining {
                         coin_qty_remaining,
@@ -400,7 +525,8 @@ impl<'ob> OrderBookState<'ob> {
 
             let maker_fee_tier = best_bid_ref.fee_tier();
             let native_maker_pc_qty = trade_qty * trade_price.get() * pc_lot_size;
-            let native_maker_rebate = maker_fee_tier.maker_rebate(native_maker_pc_qty);
+            let native_maker_rebate =
+                fee_schedule.maker_rebate(maker_fee_tier, native_maker_pc_qty);
             accum_maker_rebates += native_maker_rebate;
 
             let maker_fill = Event::new(EventView::Fill {
@@ -440,12 +566,13 @@ impl<'ob> OrderBookState<'ob> {
                     .remove_by_key(best_bid_id)
                     .unwrap();
             }
+            self.record_trade(trade_qty, trade_price);
 
             break false;
         };
 
         let native_taker_pc_qty = accum_fill_price * pc_lot_size;
-        let native_taker_fee = fee_tier.taker_fee(native_taker_pc_qty);
+        let native_taker_fee = fee_schedule.taker_fee(fee_tier, native_taker_pc_qty);
         if native_taker_pc_qty > 0 {
             let taker_fill = Event::new(EventView::Fill {
                 side: Side::Ask,
@@ -490,6 +617,7 @@ impl<'ob> OrderBookState<'ob> {
                 unfilled_qty,
                 fee_tier,
                 client_order_id,
+                cancel_if_through_reference_bps,
             );
             let insert_result = offers.insert_leaf(&new_order);
             if let Err(SlabTreeError::OutOfSpace) = insert_result {
@@ -543,6 +671,8 @@ struct NewBidParams {
     post_allowed: bool,
     client_order_id: u64,
     self_trade_behavior: SelfTradeBehavior,
+    cancel_if_through_reference_bps: u16,
+    reference_price: Option<u64>,
 }
 
 impl<'ob> OrderBookState<'ob> {
@@ -563,6 +693,8 @@ impl<'ob> OrderBookState<'ob> {
             post_allowed,
             client_order_id,
             self_trade_behavior,
+            cancel_if_through_reference_bps,
+            reference_price,
         } = params;
         if post_allowed {
             check_assert!(limit_price.is_some())?;
@@ -570,8 +702,11 @@ impl<'ob> OrderBookState<'ob> {
 
         let pc_lot_size = self.market_state.pc_lot_size;
         let coin_lot_size = self.market_state.coin_lot_size;
+        let fee_schedule = self.market_state.fee_schedule();
+        let self_trade_accounts = self.self_trade_accounts;
 
-        let max_pc_qty = fee_tier.remove_taker_fee(native_pc_qty_locked.get()) / pc_lot_size;
+        let max_pc_qty =
+            fee_schedule.remove_taker_fee(fee_tier, native_pc_qty_locked.get()) / pc_lot_size;
 
         let mut coin_qty_remaining = max_coin_qty.get();
         let mut pc_qty_remaining = max_pc_qty;
@@ -602,6 +737,25 @@ impl<'ob> OrderBookState<'ob> {
                 break true;
             }
 
+            if Self::is_through_reference(best_offer_ref, Side::Ask, reference_price) {
+                let best_offer_id = best_offer_ref.order_id();
+                event_q
+                    .push_back(Event::new(EventView::Out {
+                        side: Side::Ask,
+                        native_qty_unlocked: best_offer_ref.quantity() * coin_lot_size,
+                        native_qty_still_locked: 0,
+                        order_id: best_offer_id,
+                        owner: best_offer_ref.owner(),
+                        owner_slot: best_offer_ref.owner_slot(),
+                        client_order_id: NonZeroU64::new(best_offer_ref.client_order_id()),
+                    }))
+                    .map_err(|_| DexErrorCode::EventQueueFull)?;
+                self.orders_mut(Side::Ask)
+                    .remove_by_key(best_offer_id)
+                    .unwrap();
+                break false;
+            }
+
             let offer_size = best_offer_ref.quantity();
             let trade_qty = offer_size
                 .min(coin_qty_remaining)
@@ -611,7 +765,9 @@ impl<'ob> OrderBookState<'ob> {
                 break true;
             }
 
-            let order_would_self_trade = owner == best_offer_ref.owner();
+            let same_account = owner == best_offer_ref.owner();
+            let order_would_self_trade =
+                same_account || self_trade_accounts.contains(&best_offer_ref.owner());
             if order_would_self_trade {
                 let best_offer_id = best_offer_ref.order_id();
 
@@ -691,12 +847,14 @@ impl<'ob> OrderBookState<'ob> {
                 event_q
                     .push_back(take_out)
                     .map_err(|_| DexErrorCode::EventQueueFull)?;
+                self.record_self_cross(same_account);
 
                 return Ok(order_remaining);
             }
             let maker_fee_tier = best_offer_ref.fee_tier();
             let native_maker_pc_qty = trade_qty * trade_price.get() * pc_lot_size;
-            let native_maker_rebate = maker_fee_tier.maker_rebate(native_maker_pc_qty);
+            let native_maker_rebate =
+                fee_schedule.maker_rebate(maker_fee_tier, native_maker_pc_qty);
             accum_maker_rebates += native_maker_rebate;
 
             let maker_fill = Event::new(EventView::Fill {
@@ -736,12 +894,13 @@ impl<'ob> OrderBookState<'ob> {
                     .remove_by_key(best_offer_id)
                     .unwrap();
             }
+            self.record_trade(trade_qty, trade_price);
 
             break false;
         };
 
         let native_accum_fill_price = (max_pc_qty - pc_qty_remaining) * pc_lot_size;
-        let native_taker_fee = fee_tier.taker_fee(native_accum_fill_price);
+        let native_taker_fee = fee_schedule.taker_fee(fee_tier, native_accum_fill_price);
         let native_pc_qty_remaining =
             native_pc_qty_locked.get() - native_accum_fill_price - native_taker_fee;
 
@@ -817,6 +976,7 @@ impl<'ob> OrderBookState<'ob> {
                 coin_qty_to_post,
                 fee_tier,
                 client_order_id,
+                cancel_if_through_reference_bps,
             );
             let insert_result = bids.insert_leaf(&new_leaf);
             if let Err(SlabTreeError::OutOfSpace) = insert_result {
@@ -854,35 +1014,49 @@ impl<'ob> OrderBookState<'ob> {
 
         event_q: &mut EventQueue,
     ) -> DexResult<()> {
-        if let Some(leaf_node) = self.orders_mut(side).remove_by_key(order_id) {
-            if leaf_node.owner() == expected_owner && leaf_node.owner_slot() == expected_owner_slot
-            {
-                if let Some(client_id) = client_order_id {
-                    debug_assert_eq!(client_id.get(), leaf_node.client_order_id());
-                }
-                let native_qty_unlocked = match side {
-                    Side::Bid => {
-                        leaf_node.quantity()
-                            * leaf_node.price().get()
-                            * self.market_state.pc_lot_size
-                    }
-                    Side::Ask => leaf_node.quantity() * self.market_state.coin_lot_size,
-                };
-                event_q
-                    .push_back(Event::new(EventView::Out {
-                        side,
-                        native_qty_unlocked,
-                        native_qty_still_locked: 0,
-                        order_id,
-                        owner: expected_owner,
-                        owner_slot: expected_owner_slot,
-                        client_order_id: NonZeroU64::new(leaf_node.client_order_id()),
-                    }))
-                    .map_err(|_| DexErrorCode::EventQueueFull)?;
-            } else {
-                self.orders_mut(side).insert_leaf(&leaf_node).unwrap();
+        if let Some((leaf_node, native_qty_unlocked)) =
+            self.remove_order_directly(side, order_id, expected_owner, expected_owner_slot)
+        {
+            if let Some(client_id) = client_order_id {
+                debug_assert_eq!(client_id.get(), leaf_node.client_order_id());
             }
+            event_q
+                .push_back(Event::new(EventView::Out {
+                    side,
+                    native_qty_unlocked,
+                    native_qty_still_locked: 0,
+                    order_id,
+                    owner: expected_owner,
+                    owner_slot: expected_owner_slot,
+                    client_order_id: NonZeroU64::new(leaf_node.client_order_id()),
+                }))
+                .map_err(|_| DexErrorCode::EventQueueFull)?;
         }
         Ok(())
     }
+
+    /// Takes the order off the book without an Out event, if it's still
+    /// there and belongs to the expected owner and slot, returning it with
+    /// the native quantity it had locked. The caller releases that quantity
+    /// and the slot itself.
+    pub fn remove_order_directly(
+        &mut self,
+        side: Side,
+        order_id: u128,
+        expected_owner: [u64; 4],
+        expected_owner_slot: u8,
+    ) -> Option<(LeafNode, u64)> {
+        let leaf_node = self.orders_mut(side).remove_by_key(order_id)?;
+        if leaf_node.owner() != expected_owner || leaf_node.owner_slot() != expected_owner_slot {
+            self.orders_mut(side).insert_leaf(&leaf_node).unwrap();
+            return None;
+        }
+        let native_qty_unlocked = match side {
+            Side::Bid => {
+                leaf_node.quantity() * leaf_node.price().get() * self.market_state.pc_lot_size
+            }
+            Side::Ask => leaf_node.quantity() * self.market_state.coin_lot_size,
+        };
+        Some((leaf_node, native_qty_unlocked))
+    }
 }
@@ -12,32 +12,53 @@ use bytemuck::{
 };
 use enumflags2::BitFlags;
 use num_traits::FromPrimitive;
-use safe_transmute::{self, to_bytes::transmute_to_bytes, trivial::TriviallyTransmutable};
+use static_assertions::const_assert_eq;
 
 use solana_program::{
     account_info::AccountInfo,
+    clock::Clock,
     info,
     program_error::ProgramError,
     program_pack::Pack,
     pubkey::Pubkey,
     rent::Rent,
+    system_instruction, system_program,
     sysvar::{Sysvar, SysvarId},
 };
 use spl_token::error::TokenError;
 
 use crate::{
-    critbit::Slab,
+    critbit::{NodeHandle, Slab, SlabView},
     error::{DexErrorCode, DexResult, SourceFileId},
-    fees::{self, FeeTier},
+    fees::{self, FeeSchedule, FeeTier},
     instruction::{
-        disable_authority, fee_sweeper, msrm_token, srm_token, CancelOrderInstruction,
-        InitializeMarketInstruction, MarketInstruction, NewOrderInstructionV2, SelfTradeBehavior,
+        disable_authority, fee_sweeper, lockup, lockup_srm_safe, msrm_token, registry,
+        registry_srm_registrar, srm_token, CancelOrderInstruction, CancelOrderInstructionV2,
+        ConsumeEventsFromInstruction, DepositBlockTradeInstruction, InitBlockTradeInstruction,
+        InitializeMarketInstruction, MarketInstruction, NewOrderBatchInstruction,
+        NewOrderInstructionV2, NewTriggerOrderInstruction, SelfTradeBehavior,
+        SetFeeDestinationsInstruction, SetFeeScheduleInstruction, SetMarketParamsInstruction,
+        TriggerCondition, UpdateLotSizesInstruction, MAX_FEE_DESTINATIONS, MAX_SELF_TRADE_ACCOUNTS,
     },
-    matching::{OrderBookState, OrderType, Side},
+    matching::{extract_price_from_order_id, OrderBookState, OrderType, Side},
 };
 
 declare_check_assert_macros!(SourceFileId::State);
 
+// Logs `compute: <label>` and the compute units left, for clients to split
+// an instruction's compute between its phases. The label names the work
+// done since the previous checkpoint. Only built with the `compute-units`
+// feature.
+macro_rules! compute_checkpoint {
+    ($label:expr) => {
+        #[cfg(feature = "compute-units")]
+        {
+            info!(concat!("compute: ", $label));
+            solana_program::log::sol_log_compute_units();
+        }
+    };
+}
+
 pub trait ToAlignedBytes {
     fn to_aligned_bytes(&self) -> [u64; 4];
 }
@@ -60,11 +81,29 @@ pub enum AccountFlag {
     Bids = 1u64 << 5,
     Asks = 1u64 << 6,
     Disabled = 1u64 << 7,
+    TriggerOrder = 1u64 << 8,
+    OpenOrdersDelegate = 1u64 << 9,
+    SettleDestination = 1u64 << 10,
+    FeeDestinations = 1u64 << 11,
+    MarketStats = 1u64 << 12,
+    TraderStats = 1u64 << 13,
+    MarketListing = 1u64 << 14,
+    BlockTrade = 1u64 << 15,
+    MarketParams = 1u64 << 16,
+}
+
+/// Behaviors a market opts into with `SetMarketOptions`.
+#[derive(Copy, Clone, BitFlags, Debug, Eq, PartialEq)]
+#[repr(u8)]
+pub enum MarketOption {
+    UniqueClientIds = 1u8 << 0,
+    // Set by `SetMarketParams` rather than `SetMarketOptions`.
+    Params = 1u8 << 1,
 }
 
 #[cfg_attr(target_endian = "little", derive(Debug))]
 #[derive(Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct MarketState {
     // 0
     pub account_flags: u64, // Initialized, Market
@@ -112,7 +151,15 @@ pub struct MarketState {
     pub pc_lot_size: u64,
 
     // 45
-    pub fee_rate_bps: u64,
+    pub fee_rate_bps: u16,
+    // Zero on markets listed before the layout was versioned, see
+    // `MarketState::VERSION`.
+    pub version: u8,
+    // MarketOption bits, none on markets that never set them.
+    pub options: u8,
+    // Zero on markets charging the default fee schedule.
+    pub taker_fee_bps: u16,
+    pub maker_rebate_bps: u16,
     // 46
     pub referrer_rebates_accrued: u64,
 }
@@ -120,8 +167,6 @@ pub struct MarketState {
 unsafe impl Zeroable for MarketState {}
 #[cfg(target_endian = "little")]
 unsafe impl Pod for MarketState {}
-#[cfg(target_endian = "little")]
-unsafe impl TriviallyTransmutable for MarketState {}
 
 pub const ACCOUNT_HEAD_PADDING: &[u8; 5] = b"serum";
 pub const ACCOUNT_TAIL_PADDING: &[u8; 7] = b"padding";
@@ -189,6 +234,20 @@ pub fn strip_header<'a, H: Pod, D: Pod>(
 }
 
 impl MarketState {
+    /// The layout `InitializeMarket` writes and `UpgradeMarket` migrates
+    /// older markets to.
+    ///
+    /// 1. The original layout. It predates the version byte, so it reads
+    ///    as 0.
+    /// 2. Adds the fee schedule, in bytes version 1 left zero.
+    /// 3. Retires the request queue. The layout is unchanged, but orders
+    ///    can only be placed and cancelled by the direct-matching
+    ///    instructions, see `check_request_queue_open`.
+    ///
+    /// An account can't grow, so new fields must fit in space older
+    /// versions left unused.
+    pub const VERSION: u8 = 3;
+
     #[inline]
     pub fn load<'a>(
         market_account: &'a AccountInfo,
@@ -207,6 +266,7 @@ impl MarketState {
         });
 
         state.check_flags()?;
+        check_assert!(state.version <= Self::VERSION)?;
         Ok(state)
     }
 
@@ -258,6 +318,297 @@ impl MarketState {
         Ok(open_orders)
     }
 
+    /// Loads the OpenOrders account for an order placed or cancelled by
+    /// `trader`, who must be either its owner or, when `delegate_account` is
+    /// given, the delegate recorded there.
+    fn load_orders_for_trader_mut<'a>(
+        &self,
+        orders_account: &'a AccountInfo,
+        trader: &AccountInfo,
+        delegate_account: Option<&AccountInfo>,
+        program_id: &Pubkey,
+        rent: Option<Rent>,
+    ) -> DexResult<RefMut<'a, OpenOrders>> {
+        let delegate_account = match delegate_account {
+            Some(a) => a,
+            None => return self.load_orders_mut(orders_account, Some(trader), program_id, rent),
+        };
+        {
+            let delegate =
+                self.load_open_orders_delegate_mut(delegate_account, program_id, None)?;
+            check_assert_eq!(
+                identity(delegate.open_orders),
+                orders_account.key.to_aligned_bytes()
+            )
+            .map_err(|_| DexErrorCode::WrongDelegateAccount)?;
+            check_assert_eq!(identity(delegate.delegate), trader.key.to_aligned_bytes())
+                .map_err(|_| DexErrorCode::WrongDelegateAccount)?;
+        }
+        // Only the owner can create an OpenOrders account.
+        self.load_orders_mut(orders_account, None, program_id, None)
+    }
+
+    fn load_open_orders_delegate_mut<'a>(
+        &self,
+        delegate_account: &'a AccountInfo,
+        program_id: &Pubkey,
+        rent: Option<Rent>,
+    ) -> DexResult<RefMut<'a, OpenOrdersDelegate>> {
+        check_assert_eq!(delegate_account.owner, program_id)?;
+        let delegate_data_len = delegate_account.data_len();
+        let delegate_lamports = delegate_account.lamports();
+        let (_, data) = strip_header::<[u8; 0], u8>(delegate_account, true)?;
+        let mut delegate: RefMut<'a, OpenOrdersDelegate> =
+            RefMut::map(data, |data| from_bytes_mut(data));
+
+        if delegate.account_flags == 0 {
+            let rent = rent.ok_or(DexErrorCode::WrongDelegateAccount)?;
+            if !rent.is_exempt(delegate_lamports, delegate_data_len) {
+                return Err(DexErrorCode::OrdersNotRentExempt)?;
+            }
+            delegate.account_flags =
+                (AccountFlag::Initialized | AccountFlag::OpenOrdersDelegate).bits();
+            delegate.market = identity(self.own_address);
+        }
+        delegate.check_flags()?;
+        check_assert_eq!(identity(delegate.market), identity(self.own_address))
+            .map_err(|_| DexErrorCode::WrongDelegateAccount)?;
+        Ok(delegate)
+    }
+
+    fn load_settle_destination_mut<'a>(
+        &self,
+        destination_account: &'a AccountInfo,
+        program_id: &Pubkey,
+        rent: Option<Rent>,
+    ) -> DexResult<RefMut<'a, SettleDestination>> {
+        check_assert_eq!(destination_account.owner, program_id)?;
+        let destination_data_len = destination_account.data_len();
+        let destination_lamports = destination_account.lamports();
+        let (_, data) = strip_header::<[u8; 0], u8>(destination_account, true)?;
+        let mut destination: RefMut<'a, SettleDestination> =
+            RefMut::map(data, |data| from_bytes_mut(data));
+
+        if destination.account_flags == 0 {
+            let rent = rent.ok_or(DexErrorCode::WrongSettleDestinationAccount)?;
+            if !rent.is_exempt(destination_lamports, destination_data_len) {
+                return Err(DexErrorCode::OrdersNotRentExempt)?;
+            }
+            destination.account_flags =
+                (AccountFlag::Initialized | AccountFlag::SettleDestination).bits();
+            destination.market = identity(self.own_address);
+        }
+        destination.check_flags()?;
+        check_assert_eq!(identity(destination.market), identity(self.own_address))
+            .map_err(|_| DexErrorCode::WrongSettleDestinationAccount)?;
+        Ok(destination)
+    }
+
+    pub fn load_fee_destinations_mut<'a>(
+        &self,
+        destinations_account: &'a AccountInfo,
+        program_id: &Pubkey,
+        rent: Option<Rent>,
+    ) -> DexResult<RefMut<'a, FeeDestinations>> {
+        check_assert_eq!(destinations_account.owner, program_id)?;
+        let destinations_data_len = destinations_account.data_len();
+        let destinations_lamports = destinations_account.lamports();
+        let (_, data) = strip_header::<[u8; 0], u8>(destinations_account, true)?;
+        let mut destinations: RefMut<'a, FeeDestinations> =
+            RefMut::map(data, |data| from_bytes_mut(data));
+
+        if destinations.account_flags == 0 {
+            let rent = rent.ok_or(DexErrorCode::WrongFeeDestinationsAccount)?;
+            if !rent.is_exempt(destinations_lamports, destinations_data_len) {
+                return Err(DexErrorCode::OrdersNotRentExempt)?;
+            }
+            destinations.account_flags =
+                (AccountFlag::Initialized | AccountFlag::FeeDestinations).bits();
+            destinations.market = identity(self.own_address);
+        }
+        destinations.check_flags()?;
+        check_assert_eq!(identity(destinations.market), identity(self.own_address))
+            .map_err(|_| DexErrorCode::WrongFeeDestinationsAccount)?;
+        Ok(destinations)
+    }
+
+    pub fn load_market_stats_mut<'a>(
+        &self,
+        stats_account: &'a AccountInfo,
+        program_id: &Pubkey,
+        rent: Option<Rent>,
+    ) -> DexResult<RefMut<'a, MarketStats>> {
+        check_assert_eq!(stats_account.owner, program_id)?;
+        let stats_data_len = stats_account.data_len();
+        let stats_lamports = stats_account.lamports();
+        let (_, data) = strip_header::<[u8; 0], u8>(stats_account, true)?;
+        let mut stats: RefMut<'a, MarketStats> = RefMut::map(data, |data| from_bytes_mut(data));
+
+        if stats.account_flags == 0 {
+            let rent = rent.ok_or(DexErrorCode::WrongMarketStatsAccount)?;
+            if !rent.is_exempt(stats_lamports, stats_data_len) {
+                return Err(DexErrorCode::OrdersNotRentExempt)?;
+            }
+            stats.account_flags = (AccountFlag::Initialized | AccountFlag::MarketStats).bits();
+            stats.market = identity(self.own_address);
+        }
+        stats.check_flags()?;
+        check_assert_eq!(identity(stats.market), identity(self.own_address))
+            .map_err(|_| DexErrorCode::WrongMarketStatsAccount)?;
+        Ok(stats)
+    }
+
+    pub fn load_trader_stats_mut<'a>(
+        &self,
+        stats_account: &'a AccountInfo,
+        owner: &[u64; 4],
+        program_id: &Pubkey,
+        rent: Option<Rent>,
+    ) -> DexResult<RefMut<'a, TraderStats>> {
+        check_assert_eq!(stats_account.owner, program_id)?;
+        let stats_data_len = stats_account.data_len();
+        let stats_lamports = stats_account.lamports();
+        let (_, data) = strip_header::<[u8; 0], u8>(stats_account, true)?;
+        let mut stats: RefMut<'a, TraderStats> = RefMut::map(data, |data| from_bytes_mut(data));
+
+        if stats.account_flags == 0 {
+            let rent = rent.ok_or(DexErrorCode::WrongTraderStatsAccount)?;
+            if !rent.is_exempt(stats_lamports, stats_data_len) {
+                return Err(DexErrorCode::OrdersNotRentExempt)?;
+            }
+            stats.account_flags = (AccountFlag::Initialized | AccountFlag::TraderStats).bits();
+            stats.market = identity(self.own_address);
+            stats.owner = *owner;
+        }
+        stats.check_flags()?;
+        check_assert_eq!(identity(stats.market), identity(self.own_address))
+            .map_err(|_| DexErrorCode::WrongTraderStatsAccount)?;
+        check_assert_eq!(&identity(stats.owner), owner)
+            .map_err(|_| DexErrorCode::WrongTraderStatsAccount)?;
+        Ok(stats)
+    }
+
+    fn init_trigger_order_mut<'a>(
+        &self,
+        trigger_account: &'a AccountInfo,
+        program_id: &Pubkey,
+        rent: Rent,
+    ) -> DexResult<RefMut<'a, TriggerOrder>> {
+        check_assert_eq!(trigger_account.owner, program_id)?;
+        if !rent.is_exempt(trigger_account.lamports(), trigger_account.data_len()) {
+            return Err(DexErrorCode::OrdersNotRentExempt)?;
+        }
+        let (_, data) = strip_header::<[u8; 0], u8>(trigger_account, true)?;
+        let trigger_order: RefMut<'a, TriggerOrder> =
+            RefMut::map(data, |data| from_bytes_mut(data));
+        if trigger_order.account_flags != 0 {
+            Err(DexErrorCode::WrongTriggerOrderAccount)?
+        }
+        Ok(trigger_order)
+    }
+
+    pub fn load_trigger_order_mut<'a>(
+        &self,
+        trigger_account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> DexResult<RefMut<'a, TriggerOrder>> {
+        check_assert_eq!(trigger_account.owner, program_id)?;
+        let (_, data) = strip_header::<[u8; 0], u8>(trigger_account, false)?;
+        let trigger_order: RefMut<'a, TriggerOrder> =
+            RefMut::map(data, |data| from_bytes_mut(data));
+        trigger_order.check_flags()?;
+        check_assert_eq!(identity(trigger_order.market), identity(self.own_address))
+            .map_err(|_| DexErrorCode::WrongTriggerOrderAccount)?;
+        Ok(trigger_order)
+    }
+
+    fn init_block_trade_mut<'a>(
+        &self,
+        block_trade_account: &'a AccountInfo,
+        program_id: &Pubkey,
+        rent: Rent,
+    ) -> DexResult<RefMut<'a, BlockTrade>> {
+        check_assert_eq!(block_trade_account.owner, program_id)?;
+        if !rent.is_exempt(
+            block_trade_account.lamports(),
+            block_trade_account.data_len(),
+        ) {
+            return Err(DexErrorCode::OrdersNotRentExempt)?;
+        }
+        let (_, data) = strip_header::<[u8; 0], u8>(block_trade_account, true)?;
+        let block_trade: RefMut<'a, BlockTrade> = RefMut::map(data, |data| from_bytes_mut(data));
+        if block_trade.account_flags != 0 {
+            Err(DexErrorCode::WrongBlockTradeAccount)?
+        }
+        Ok(block_trade)
+    }
+
+    pub fn load_block_trade_mut<'a>(
+        &self,
+        block_trade_account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> DexResult<RefMut<'a, BlockTrade>> {
+        check_assert_eq!(block_trade_account.owner, program_id)?;
+        let (_, data) = strip_header::<[u8; 0], u8>(block_trade_account, false)?;
+        let block_trade: RefMut<'a, BlockTrade> = RefMut::map(data, |data| from_bytes_mut(data));
+        block_trade.check_flags()?;
+        check_assert_eq!(identity(block_trade.market), identity(self.own_address))
+            .map_err(|_| DexErrorCode::WrongBlockTradeAccount)?;
+        Ok(block_trade)
+    }
+
+    fn init_market_params_mut<'a>(
+        &self,
+        params_account: &'a AccountInfo,
+        program_id: &Pubkey,
+        rent: Rent,
+    ) -> DexResult<RefMut<'a, MarketParams>> {
+        check_assert_eq!(params_account.owner, program_id)?;
+        if !rent.is_exempt(params_account.lamports(), params_account.data_len()) {
+            return Err(DexErrorCode::OrdersNotRentExempt)?;
+        }
+        let (_, data) = strip_header::<[u8; 0], u8>(params_account, true)?;
+        let mut params: RefMut<'a, MarketParams> = RefMut::map(data, |data| from_bytes_mut(data));
+        if params.account_flags != 0 {
+            Err(DexErrorCode::WrongMarketParamsAccount)?
+        }
+        params.account_flags = (AccountFlag::Initialized | AccountFlag::MarketParams).bits();
+        params.market = identity(self.own_address);
+        Ok(params)
+    }
+
+    pub fn load_market_params_mut<'a>(
+        &self,
+        params_account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> DexResult<RefMut<'a, MarketParams>> {
+        check_assert_eq!(params_account.owner, program_id)?;
+        let (_, data) = strip_header::<[u8; 0], u8>(params_account, false)?;
+        let params: RefMut<'a, MarketParams> = RefMut::map(data, |data| from_bytes_mut(data));
+        params.check_flags()?;
+        check_assert_eq!(identity(params.market), identity(self.own_address))
+            .map_err(|_| DexErrorCode::WrongMarketParamsAccount)?;
+        Ok(params)
+    }
+
+    fn load_block_trade_orders_mut<'a>(
+        &self,
+        block_trade: &BlockTrade,
+        bid_open_orders: &'a AccountInfo,
+        ask_open_orders: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> DexResult<(RefMut<'a, OpenOrders>, RefMut<'a, OpenOrders>)> {
+        for &(side, account) in &[(Side::Bid, bid_open_orders), (Side::Ask, ask_open_orders)] {
+            if account.key.to_aligned_bytes() != block_trade.open_orders(side) {
+                Err(DexErrorCode::WrongOrdersAccount)?
+            }
+        }
+        Ok((
+            self.load_orders_mut(bid_open_orders, None, program_id, None)?,
+            self.load_orders_mut(ask_open_orders, None, program_id, None)?,
+        ))
+    }
+
     fn load_bids_mut<'a>(&self, bids: &'a AccountInfo) -> DexResult<RefMut<'a, Slab>> {
         check_assert_eq!(&bids.key.to_aligned_bytes(), &identity(self.bids))
             .map_err(|_| DexErrorCode::WrongBidsAccount)?;
@@ -320,8 +671,7 @@ impl MarketState {
 
     #[inline]
     fn check_coin_payer(&self, payer: account_parser::TokenAccount) -> DexResult {
-        if &payer.inner().try_borrow_data()?[..32] != transmute_to_bytes(&identity(self.coin_mint))
-        {
+        if &payer.inner().try_borrow_data()?[..32] != bytes_of(&identity(self.coin_mint)) {
             Err(DexErrorCode::WrongCoinMint)?
         }
         Ok(())
@@ -329,7 +679,7 @@ impl MarketState {
 
     #[inline]
     fn check_pc_payer(&self, payer: account_parser::TokenAccount) -> DexResult {
-        if &payer.inner().try_borrow_data()?[..32] != transmute_to_bytes(&identity(self.pc_mint)) {
+        if &payer.inner().try_borrow_data()?[..32] != bytes_of(&identity(self.pc_mint)) {
             Err(DexErrorCode::WrongPcMint)?
         }
         Ok(())
@@ -339,10 +689,23 @@ impl MarketState {
     fn load_fee_tier(
         &self,
         expected_owner: &[u64; 4],
-        srm_or_msrm_account: Option<account_parser::TokenAccount>,
+        fee_discount_account: Option<account_parser::FeeDiscountAccount>,
     ) -> DexResult<FeeTier> {
-        let srm_or_msrm_account = match srm_or_msrm_account {
-            Some(a) => a,
+        let srm_or_msrm_account = match fee_discount_account {
+            Some(account_parser::FeeDiscountAccount::Wallet(a)) => a,
+            Some(account_parser::FeeDiscountAccount::Stake(member)) => {
+                if &member.beneficiary()? != expected_owner {
+                    Err(DexErrorCode::WrongRegistryMemberAccount)?
+                }
+                let (srm_staked, msrm_staked) = member.stake()?;
+                return Ok(FeeTier::from_srm_and_msrm_balances(srm_staked, msrm_staked));
+            }
+            Some(account_parser::FeeDiscountAccount::Locked(vesting)) => {
+                if &vesting.beneficiary()? != expected_owner {
+                    Err(DexErrorCode::WrongLockupVestingAccount)?
+                }
+                return Ok(FeeTier::from_srm_and_msrm_balances(vesting.locked()?, 0));
+            }
             None => return Ok(FeeTier::Base),
         };
         let data = srm_or_msrm_account.inner().try_borrow_data()?;
@@ -363,6 +726,67 @@ impl MarketState {
         Ok(FeeTier::from_srm_and_msrm_balances(0, 0))
     }
 
+    /// Fails once the market has retired its request queue. Otherwise
+    /// anyone could keep requests queued with `NewOrder` or `CancelOrder`,
+    /// and so block every instruction that matches directly, as those need
+    /// the queue to be empty.
+    pub fn check_request_queue_open(&self) -> DexResult {
+        if self.version >= 3 {
+            Err(DexErrorCode::RequestQueueRetired)?
+        }
+        Ok(())
+    }
+
+    /// The fees charged on the market.
+    pub fn fee_schedule(&self) -> FeeSchedule {
+        if self.version < 2 {
+            return FeeSchedule::DEFAULT;
+        }
+        match (self.taker_fee_bps, self.maker_rebate_bps) {
+            (0, 0) => FeeSchedule::DEFAULT,
+            (taker_fee_bps, maker_rebate_bps) => FeeSchedule {
+                taker_fee_bps,
+                maker_rebate_bps,
+            },
+        }
+    }
+
+    // The full size of an ask, or the full cost of a bid plus the taker fee.
+    fn native_qty_to_lock(
+        &self,
+        instruction: &NewOrderInstructionV2,
+        fee_tier: FeeTier,
+    ) -> DexResult<u64> {
+        Ok(match instruction.side {
+            Side::Bid => {
+                let lock_qty_lots = instruction
+                    .max_qty
+                    .get()
+                    .checked_mul(instruction.limit_price.get())
+                    .ok_or(DexErrorCode::InsufficientFunds)?;
+                let native_lock_qty_before_fee = lock_qty_lots
+                    .checked_mul(self.pc_lot_size)
+                    .ok_or(DexErrorCode::InsufficientFunds)?;
+                native_lock_qty_before_fee
+                    .checked_add(
+                        self.fee_schedule()
+                            .taker_fee(fee_tier, native_lock_qty_before_fee),
+                    )
+                    .ok_or(DexErrorCode::InsufficientFunds)?
+            }
+            Side::Ask => instruction
+                .max_qty
+                .get()
+                .checked_mul(self.coin_lot_size)
+                .ok_or(DexErrorCode::InsufficientFunds)?,
+        })
+    }
+
+    #[inline]
+    pub fn options(&self) -> BitFlags<MarketOption> {
+        BitFlags::from_bits_truncate(self.options)
+    }
+
     fn check_enabled(&self) -> DexResult {
         let flags = BitFlags::from_bits(self.account_flags).unwrap();
         if flags.contains(AccountFlag::Disabled) {
@@ -377,7 +801,7 @@ impl MarketState {
 }
 
 #[cfg_attr(feature = "fuzz", derive(Debug))]
-#[repr(packed)]
+#[repr(C, packed)]
 #[derive(Copy, Clone)]
 pub struct OpenOrders {
     pub account_flags: u64, // Initialized, OpenOrders
@@ -446,6 +870,19 @@ impl OpenOrders {
         self.native_pc_free = self.native_pc_free.checked_sub(native_pc_amount).unwrap();
     }
 
+    fn has_resting_orders(&self) -> bool {
+        self.free_slot_bits != std::u128::MAX
+    }
+
+    // Once every order is gone nothing should be locked, so whatever is left
+    // between the free and total balances is sub-lot rounding dust.
+    fn release_dust(&mut self) -> DexResult {
+        check_assert!(!self.has_resting_orders())?;
+        self.native_coin_free = self.native_coin_total;
+        self.native_pc_free = self.native_pc_total;
+        Ok(())
+    }
+
     fn slot_is_free(&self, slot: u8) -> bool {
         let slot_mask = 1u128 << slot;
         self.free_slot_bits & slot_mask != 0
@@ -462,6 +899,45 @@ impl OpenOrders {
         }
     }
 
+    /// Number of slots left for new orders.
+    pub fn free_slot_count(&self) -> u32 {
+        identity(self.free_slot_bits).count_ones()
+    }
+
+    /// Number of orders resting on `side` of the book.
+    pub fn resting_order_count(&self, side: Side) -> u32 {
+        let used = !identity(self.free_slot_bits);
+        let bids = identity(self.is_bid_bits);
+        match side {
+            Side::Bid => (used & bids).count_ones(),
+            Side::Ask => (used & !bids).count_ones(),
+        }
+    }
+
+    /// Native coin backing resting asks and unsettled fills.
+    pub fn locked_coin(&self) -> u64 {
+        identity(self.native_coin_total) - identity(self.native_coin_free)
+    }
+
+    /// Native pc backing resting bids and unsettled fills.
+    pub fn locked_pc(&self) -> u64 {
+        identity(self.native_pc_total) - identity(self.native_pc_free)
+    }
+
+    /// `(slot, order id, client order id, side)` of each resting order, in
+    /// slot order.
+    pub fn iter_orders(&self) -> impl Iterator<Item = (u8, u128, u64, Side)> + '_ {
+        (0..128u8).filter_map(move |slot| {
+            let side = self.slot_side(slot)?;
+            Some((
+                slot,
+                self.orders[slot as usize],
+                self.client_order_ids[slot as usize],
+                side,
+            ))
+        })
+    }
+
     fn remove_order(&mut self, slot: u8) -> DexResult {
         check_assert!(slot < 128)?;
         check_assert!(!self.slot_is_free(slot))?;
@@ -475,6 +951,12 @@ impl OpenOrders {
         Ok(())
     }
 
+    /// Whether an order in a used slot has the client id `client_order_id`.
+    pub fn has_client_order_id(&self, client_order_id: u64) -> bool {
+        self.iter_orders()
+            .any(|(_, _, id, _)| id == client_order_id)
+    }
+
     fn add_order(&mut self, id: u128, side: Side) -> DexResult<u8> {
         if self.free_slot_bits == 0 {
             Err(DexErrorCode::TooManyOpenOrders)?;
@@ -496,91 +978,658 @@ impl OpenOrders {
     }
 }
 
-pub trait QueueHeader: Pod {
-    type Item: Pod + Copy;
-
-    fn head(&self) -> u64;
-    fn set_head(&mut self, value: u64);
-    fn count(&self) -> u64;
-    fn set_count(&mut self, value: u64);
-
-    fn incr_event_id(&mut self);
-    fn decr_event_id(&mut self, n: u64);
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct TriggerOrder {
+    pub account_flags: u64, // Initialized, TriggerOrder
+    pub market: [u64; 4],
+    pub owner: [u64; 4],
+    pub open_orders: [u64; 4],
+
+    pub trigger_price: u64,
+    pub trigger_condition: u64,
+
+    pub side: u64,
+    pub limit_price: u64,
+    pub max_qty: u64,
+    pub order_type: u64,
+    pub self_trade_behavior: u64,
+    pub client_id: u64,
+
+    // Both held in the vault for the order's side until the trigger
+    // executes or is cancelled.
+    pub native_escrow: u64,
+    pub native_tip: u64,
 }
+unsafe impl Pod for TriggerOrder {}
+unsafe impl Zeroable for TriggerOrder {}
 
-pub struct Queue<'a, H: QueueHeader> {
-    header: RefMut<'a, H>,
-    buf: RefMut<'a, [H::Item]>,
+fn decode_u8_enum<T: TryFromPrimitive<Primitive = u8>>(value: u64) -> DexResult<T> {
+    let value: u8 = value.try_into().or(check_unreachable!())?;
+    Ok(T::try_from_primitive(value).or(check_unreachable!())?)
 }
 
-impl<'a, H: QueueHeader> Queue<'a, H> {
-    pub fn new(header: RefMut<'a, H>, buf: RefMut<'a, [H::Item]>) -> Self {
-        Self { header, buf }
+impl TriggerOrder {
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongTriggerOrderAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::TriggerOrder;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongTriggerOrderAccount)?
+        }
+        Ok(())
     }
 
-    #[inline]
-    pub fn len(&self) -> u64 {
-        self.header.count()
+    pub fn side(&self) -> DexResult<Side> {
+        decode_u8_enum(self.side)
     }
 
-    #[inline]
-    pub fn full(&self) -> bool {
-        self.header.count() as usize == self.buf.len()
+    pub fn order(&self) -> DexResult<NewOrderInstructionV2> {
+        Ok(NewOrderInstructionV2 {
+            side: self.side()?,
+            limit_price: NonZeroU64::new(self.limit_price).ok_or(assertion_error!())?,
+            max_qty: NonZeroU64::new(self.max_qty).ok_or(assertion_error!())?,
+            order_type: decode_u8_enum(self.order_type)?,
+            client_id: self.client_id,
+            self_trade_behavior: decode_u8_enum(self.self_trade_behavior)?,
+        })
     }
 
-    #[inline]
-    pub fn empty(&self) -> bool {
-        self.header.count() == 0
+    pub fn is_triggered_at(&self, price: u64) -> DexResult<bool> {
+        let trigger_price = self.trigger_price;
+        Ok(match decode_u8_enum(self.trigger_condition)? {
+            TriggerCondition::PriceAtOrBelow => price <= trigger_price,
+            TriggerCondition::PriceAtOrAbove => price >= trigger_price,
+        })
     }
+}
 
-    #[inline]
-    pub fn push_back(&mut self, value: H::Item) -> Result<(), H::Item> {
-        if self.full() {
-            return Err(value);
-        }
-        let slot = ((self.header.head() + self.header.count()) as usize) % self.buf.len();
-        self.buf[slot] = value;
-
-        let count = self.header.count();
-        self.header.set_count(count + 1);
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct OpenOrdersDelegate {
+    pub account_flags: u64, // Initialized, OpenOrdersDelegate
+    pub market: [u64; 4],
+    pub open_orders: [u64; 4],
+    // May place and cancel orders for `open_orders`, but not settle.
+    pub delegate: [u64; 4],
+}
+unsafe impl Pod for OpenOrdersDelegate {}
+unsafe impl Zeroable for OpenOrdersDelegate {}
 
-        self.header.incr_event_id();
+impl OpenOrdersDelegate {
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongDelegateAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::OpenOrdersDelegate;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongDelegateAccount)?
+        }
         Ok(())
     }
+}
 
-    #[inline]
-    pub fn peek_front(&self) -> Option<&H::Item> {
-        if self.empty() {
-            return None;
-        }
-        Some(&self.buf[self.header.head() as usize])
-    }
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct SettleDestination {
+    pub account_flags: u64, // Initialized, SettleDestination
+    pub market: [u64; 4],
+    pub open_orders: [u64; 4],
+    // Anyone may settle `open_orders` into these wallets.
+    pub coin_wallet: [u64; 4],
+    pub pc_wallet: [u64; 4],
+}
+unsafe impl Pod for SettleDestination {}
+unsafe impl Zeroable for SettleDestination {}
 
-    #[inline]
-    pub fn peek_front_mut(&mut self) -> Option<&mut H::Item> {
-        if self.empty() {
-            return None;
+impl SettleDestination {
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongSettleDestinationAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::SettleDestination;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongSettleDestinationAccount)?
         }
-        Some(&mut self.buf[self.header.head() as usize])
+        Ok(())
     }
+}
 
-    #[inline]
-    pub fn pop_front(&mut self) -> Result<H::Item, ()> {
-        if self.empty() {
-            return Err(());
-        }
-        let value = self.buf[self.header.head() as usize];
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct FeeDestinations {
+    pub account_flags: u64, // Initialized, FeeDestinations
+    pub market: [u64; 4],
+    pub count: u64,
+    // Only the first `count` wallets and weights are in use.
+    pub wallets: [[u64; 4]; MAX_FEE_DESTINATIONS],
+    pub weights: [u32; MAX_FEE_DESTINATIONS],
+}
+unsafe impl Pod for FeeDestinations {}
+unsafe impl Zeroable for FeeDestinations {}
 
-        let count = self.header.count();
-        self.header.set_count(count - 1);
+impl FeeDestinations {
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongFeeDestinationsAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::FeeDestinations;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongFeeDestinationsAccount)?
+        }
+        Ok(())
+    }
 
-        let head = self.header.head();
-        self.header.set_head((head + 1) % self.buf.len() as u64);
+    pub fn wallets(&self) -> Vec<[u64; 4]> {
+        identity(self.wallets)[..self.count as usize].to_vec()
+    }
 
-        Ok(value)
+    /// Each wallet's share of `native_amount`, rounded down, in the order
+    /// the wallets were registered.
+    pub fn split(&self, native_amount: u64) -> Vec<u64> {
+        let weights = identity(self.weights);
+        let weights = &weights[..self.count as usize];
+        let total_weight: u128 = weights.iter().map(|&w| w as u128).sum();
+        weights
+            .iter()
+            .map(|&weight| {
+                if total_weight == 0 {
+                    return 0;
+                }
+                (native_amount as u128 * weight as u128 / total_weight) as u64
+            })
+            .collect()
     }
+}
 
-    #[inline]
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct MarketStats {
+    pub account_flags: u64, // Initialized, MarketStats
+    pub market: [u64; 4],
+    // Native quantities, saturating.
+    pub coin_volume: u64,
+    pub pc_volume: u64,
+    pub trade_count: u64,
+    // In pc lots per coin lot, like order prices.
+    pub last_price: u64,
+    pub last_trade_slot: u64,
+    // Incoming orders that reached a resting order of their own OpenOrders
+    // account, or of another account of the same owner passed with them.
+    // SelfTradeBehavior keeps either from trading.
+    pub self_cross_count: u64,
+    pub same_owner_cross_count: u64,
+}
+unsafe impl Pod for MarketStats {}
+unsafe impl Zeroable for MarketStats {}
+
+impl MarketStats {
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongMarketStatsAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::MarketStats;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongMarketStatsAccount)?
+        }
+        Ok(())
+    }
+
+    /// Records a trade of `qty` coin lots at `price`.
+    pub fn record_trade(
+        &mut self,
+        slot: u64,
+        qty: u64,
+        price: NonZeroU64,
+        coin_lot_size: u64,
+        pc_lot_size: u64,
+    ) {
+        let native_coin_qty = qty.saturating_mul(coin_lot_size);
+        let native_pc_qty = qty.saturating_mul(price.get()).saturating_mul(pc_lot_size);
+        self.coin_volume = self.coin_volume.saturating_add(native_coin_qty);
+        self.pc_volume = self.pc_volume.saturating_add(native_pc_qty);
+        self.trade_count = self.trade_count.saturating_add(1);
+        self.last_price = price.get();
+        self.last_trade_slot = slot;
+    }
+
+    /// Records an incoming order reaching a resting order of its own
+    /// OpenOrders account, or of another account of the same owner.
+    pub fn record_self_cross(&mut self, same_account: bool) {
+        if same_account {
+            self.self_cross_count = self.self_cross_count.saturating_add(1);
+        } else {
+            self.same_owner_cross_count = self.same_owner_cross_count.saturating_add(1);
+        }
+    }
+}
+
+/// Days of taker volume a TraderStats account keeps.
+pub const TRADER_STATS_DAYS: usize = 30;
+
+/// The taker volume of one owner on one market over the last 30 days, in
+/// daily buckets. Counts toward `FeeTier::with_taker_volume`.
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct TraderStats {
+    pub account_flags: u64, // Initialized, TraderStats
+    pub market: [u64; 4],
+    pub owner: [u64; 4],
+    // Unix day, i.e. timestamp / 86400, of the latest bucket.
+    pub last_day: u64,
+    // Native pc traded as taker, saturating, indexed by day modulo
+    // TRADER_STATS_DAYS.
+    pub daily_pc_volume: [u64; TRADER_STATS_DAYS],
+    // The owner's orders counted in MarketStats' fields of the same names.
+    pub self_cross_count: u64,
+    pub same_owner_cross_count: u64,
+}
+unsafe impl Pod for TraderStats {}
+unsafe impl Zeroable for TraderStats {}
+
+impl TraderStats {
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongTraderStatsAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::TraderStats;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongTraderStatsAccount)?
+        }
+        Ok(())
+    }
+
+    /// The unix day of `unix_timestamp`, as used by `record_volume`.
+    pub fn day(unix_timestamp: i64) -> u64 {
+        (unix_timestamp.max(0) / 86_400) as u64
+    }
+
+    /// Adds `native_pc_qty` traded as taker on `day`.
+    pub fn record_volume(&mut self, day: u64, native_pc_qty: u64) {
+        self.roll(day);
+        let last_day = self.last_day;
+        let mut daily_pc_volume = self.daily_pc_volume;
+        let bucket = &mut daily_pc_volume[last_day as usize % TRADER_STATS_DAYS];
+        *bucket = bucket.saturating_add(native_pc_qty);
+        self.daily_pc_volume = daily_pc_volume;
+    }
+
+    /// Records one of the owner's orders reaching a resting order of its own
+    /// OpenOrders account, or of another of the owner's accounts.
+    pub fn record_self_cross(&mut self, same_account: bool) {
+        if same_account {
+            self.self_cross_count = self.self_cross_count.saturating_add(1);
+        } else {
+            self.same_owner_cross_count = self.same_owner_cross_count.saturating_add(1);
+        }
+    }
+
+    /// The native pc traded as taker over the 30 days up to and including
+    /// `day`.
+    pub fn volume_30d(&self, day: u64) -> u64 {
+        let mut stats = *self;
+        stats.roll(day);
+        let daily_pc_volume = stats.daily_pc_volume;
+        daily_pc_volume
+            .iter()
+            .fold(0u64, |total, volume| total.saturating_add(*volume))
+    }
+
+    // Moves the latest bucket forward to `day`, clearing the buckets of the
+    // days skipped and of the day itself. Days before the latest count
+    // toward it, e.g. if the clock goes back.
+    fn roll(&mut self, day: u64) {
+        let last_day = self.last_day;
+        if day <= last_day {
+            return;
+        }
+        let mut daily_pc_volume = self.daily_pc_volume;
+        let cleared = (day - last_day).min(TRADER_STATS_DAYS as u64);
+        for d in (day + 1 - cleared)..=day {
+            daily_pc_volume[d as usize % TRADER_STATS_DAYS] = 0;
+        }
+        self.daily_pc_volume = daily_pc_volume;
+        self.last_day = day;
+    }
+}
+
+/// The canonical market of a mint pair, at the address
+/// `find_market_listing_address` derives from the mints. The first market
+/// listed for a pair holds it until the disable authority reassigns it.
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct MarketListing {
+    pub account_flags: u64, // Initialized, MarketListing
+    pub coin_mint: [u64; 4],
+    pub pc_mint: [u64; 4],
+    pub market: [u64; 4],
+    // The signer of ListMarket, kept when the listing is reassigned.
+    pub lister: [u64; 4],
+    pub nonce: u64,
+}
+unsafe impl Pod for MarketListing {}
+unsafe impl Zeroable for MarketListing {}
+
+impl MarketListing {
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongMarketListingAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::MarketListing;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongMarketListingAccount)?
+        }
+        Ok(())
+    }
+
+    /// Loads an initialized listing.
+    pub fn load_mut<'a>(
+        listing_account: &'a AccountInfo,
+        program_id: &Pubkey,
+    ) -> DexResult<RefMut<'a, Self>> {
+        if listing_account.owner != program_id {
+            Err(DexErrorCode::WrongMarketListingAccount)?
+        }
+        let (_, data) = strip_header::<[u8; 0], u8>(listing_account, false)?;
+        let listing: RefMut<'a, Self> = RefMut::map(data, |data| from_bytes_mut(data));
+        listing.check_flags()?;
+        Ok(listing)
+    }
+}
+
+const MARKET_LISTING_SEED: &[u8] = b"listing";
+
+fn gen_market_listing_seeds<'a>(
+    coin_mint: &'a [u8],
+    pc_mint: &'a [u8],
+    nonce: &'a [u8; 1],
+) -> [&'a [u8]; 4] {
+    [MARKET_LISTING_SEED, coin_mint, pc_mint, nonce]
+}
+
+/// The address of the MarketListing of a mint pair, and the nonce to pass
+/// to `ListMarket`.
+pub fn find_market_listing_address(
+    coin_mint: &Pubkey,
+    pc_mint: &Pubkey,
+    program_id: &Pubkey,
+) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[MARKET_LISTING_SEED, coin_mint.as_ref(), pc_mint.as_ref()],
+        program_id,
+    )
+}
+
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct BlockTrade {
+    pub account_flags: u64, // Initialized, BlockTrade
+    pub market: [u64; 4],
+    pub bid_open_orders: [u64; 4],
+    pub ask_open_orders: [u64; 4],
+
+    // In lots, like an order's limit price and quantity.
+    pub price: u64,
+    pub coin_qty: u64,
+
+    // Each side's deposit is locked in its OpenOrders account as an order
+    // with this id, in the slot recorded here, or NO_DEPOSIT until made.
+    pub order_id: u128,
+    pub bid_slot: u64,
+    pub ask_slot: u64,
+}
+unsafe impl Pod for BlockTrade {}
+unsafe impl Zeroable for BlockTrade {}
+
+impl BlockTrade {
+    /// Slot of a side that hasn't deposited yet.
+    pub const NO_DEPOSIT: u64 = 128;
+
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongBlockTradeAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::BlockTrade;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongBlockTradeAccount)?
+        }
+        Ok(())
+    }
+
+    pub fn open_orders(&self, side: Side) -> [u64; 4] {
+        match side {
+            Side::Bid => identity(self.bid_open_orders),
+            Side::Ask => identity(self.ask_open_orders),
+        }
+    }
+
+    /// The slot holding `side`'s deposit, once made.
+    pub fn slot(&self, side: Side) -> Option<u8> {
+        let slot = match side {
+            Side::Bid => self.bid_slot,
+            Side::Ask => self.ask_slot,
+        };
+        if slot == Self::NO_DEPOSIT {
+            None
+        } else {
+            Some(slot as u8)
+        }
+    }
+
+    fn set_slot(&mut self, side: Side, slot: u8) {
+        match side {
+            Side::Bid => self.bid_slot = slot.into(),
+            Side::Ask => self.ask_slot = slot.into(),
+        }
+    }
+
+    /// The native coin the seller deposits and the native pc the buyer
+    /// does.
+    pub fn native_qtys(&self, coin_lot_size: u64, pc_lot_size: u64) -> DexResult<(u64, u64)> {
+        let coin_qty = self.coin_qty;
+        let native_coin_qty = coin_qty
+            .checked_mul(coin_lot_size)
+            .ok_or(DexErrorCode::InvalidBlockTrade)?;
+        let native_pc_qty = coin_qty
+            .checked_mul(self.price)
+            .and_then(|pc_lots| pc_lots.checked_mul(pc_lot_size))
+            .ok_or(DexErrorCode::InvalidBlockTrade)?;
+        Ok((native_coin_qty, native_pc_qty))
+    }
+}
+
+/// The parameters orders on a market are checked against, set by the
+/// disable authority with `SetMarketParams`. A market has one at most, and
+/// once it has one, `MarketOption::Params` is set and every new order must
+/// pass it.
+#[cfg_attr(feature = "fuzz", derive(Debug))]
+#[repr(C, packed)]
+#[derive(Copy, Clone)]
+pub struct MarketParams {
+    pub account_flags: u64, // Initialized, MarketParams
+    pub market: [u64; 4],
+    // The signer of UpdateReferencePrice.
+    pub oracle: [u64; 4],
+    // In pc lots per coin lot, like order prices. Zero until the oracle
+    // first reports.
+    pub reference_price: u64,
+    // How far an order's limit price may be from the reference price, in
+    // basis points. Zero turns the band off.
+    pub max_deviation_bps: u16,
+    pub padding0: u16,
+    // Trigger orders placed and not yet executed or cancelled. Their prices
+    // are in lots, so `UpdateLotSizes` waits for none to be left. Laid out
+    // in bytes a u64 `max_deviation_bps` used to leave zero.
+    pub trigger_order_count: u32,
+    // The smallest order the market takes, in native pc. Zero for none.
+    pub min_notional: u64,
+    // The signer of Prune, or zero for none.
+    pub prune_authority: [u64; 4],
+    // The price of the market's last fill, in pc lots per coin lot like
+    // order prices. Zero until it trades with the params passed.
+    pub last_fill_price: u64,
+    // The MarketStats account every matching instruction must pass, or
+    // zero for none.
+    pub market_stats: [u64; 4],
+}
+unsafe impl Pod for MarketParams {}
+unsafe impl Zeroable for MarketParams {}
+
+impl MarketParams {
+    fn check_flags(&self) -> DexResult {
+        let flags = BitFlags::from_bits(self.account_flags)
+            .map_err(|_| DexErrorCode::WrongMarketParamsAccount)?;
+        let required_flags = AccountFlag::Initialized | AccountFlag::MarketParams;
+        if flags != required_flags {
+            Err(DexErrorCode::WrongMarketParamsAccount)?
+        }
+        Ok(())
+    }
+
+    /// The oracle's last reported price, if it has reported one.
+    pub fn reference_price(&self) -> Option<NonZeroU64> {
+        NonZeroU64::new(self.reference_price)
+    }
+
+    /// The price of the market's last fill, if it has traded since it got
+    /// its params.
+    pub fn last_fill_price(&self) -> Option<NonZeroU64> {
+        NonZeroU64::new(self.last_fill_price)
+    }
+
+    /// Fails with `MarketStatsNotProvided` if the market has a MarketStats
+    /// account registered and `stats_acc` is None, and with
+    /// `WrongMarketStatsAccount` if it's another account.
+    pub fn check_market_stats(&self, stats_acc: Option<&AccountInfo>) -> DexResult {
+        let registered = identity(self.market_stats);
+        if registered == [0; 4] {
+            return Ok(());
+        }
+        match stats_acc {
+            None => Err(DexErrorCode::MarketStatsNotProvided)?,
+            Some(account) if account.key.to_aligned_bytes() != registered => {
+                Err(DexErrorCode::WrongMarketStatsAccount)?
+            }
+            Some(_) => Ok(()),
+        }
+    }
+
+    /// Fails with `OrderBelowMinNotional` if the order's `max_qty` at its
+    /// limit price is worth less than `min_notional`, and with
+    /// `PriceOutsideBand` if its limit price is further from the reference
+    /// price than `max_deviation_bps` allows.
+    pub fn check_order(&self, instruction: &NewOrderInstructionV2, pc_lot_size: u64) -> DexResult {
+        let notional = instruction.max_qty.get() as u128
+            * instruction.limit_price.get() as u128
+            * pc_lot_size as u128;
+        if notional < self.min_notional as u128 {
+            Err(DexErrorCode::OrderBelowMinNotional)?
+        }
+
+        let reference_price = self.reference_price;
+        let max_deviation_bps = self.max_deviation_bps;
+        if reference_price == 0 || max_deviation_bps == 0 {
+            return Ok(());
+        }
+        let limit_price = instruction.limit_price.get();
+        let deviation = if limit_price > reference_price {
+            limit_price - reference_price
+        } else {
+            reference_price - limit_price
+        };
+        if deviation as u128 * 10_000 > reference_price as u128 * max_deviation_bps as u128 {
+            Err(DexErrorCode::PriceOutsideBand)?
+        }
+        Ok(())
+    }
+}
+
+pub trait QueueHeader: Pod {
+    type Item: Pod + Copy;
+
+    fn head(&self) -> u64;
+    fn set_head(&mut self, value: u64);
+    fn count(&self) -> u64;
+    fn set_count(&mut self, value: u64);
+
+    fn incr_event_id(&mut self);
+    fn decr_event_id(&mut self, n: u64);
+}
+
+pub struct Queue<'a, H: QueueHeader> {
+    header: RefMut<'a, H>,
+    buf: RefMut<'a, [H::Item]>,
+}
+
+impl<'a, H: QueueHeader> Queue<'a, H> {
+    pub fn new(header: RefMut<'a, H>, buf: RefMut<'a, [H::Item]>) -> Self {
+        Self { header, buf }
+    }
+
+    #[inline]
+    pub fn len(&self) -> u64 {
+        self.header.count()
+    }
+
+    #[inline]
+    pub fn full(&self) -> bool {
+        self.header.count() as usize == self.buf.len()
+    }
+
+    #[inline]
+    pub fn empty(&self) -> bool {
+        self.header.count() == 0
+    }
+
+    #[inline]
+    pub fn push_back(&mut self, value: H::Item) -> Result<(), H::Item> {
+        if self.full() {
+            return Err(value);
+        }
+        let slot = ((self.header.head() + self.header.count()) as usize) % self.buf.len();
+        self.buf[slot] = value;
+
+        let count = self.header.count();
+        self.header.set_count(count + 1);
+
+        self.header.incr_event_id();
+        Ok(())
+    }
+
+    #[inline]
+    pub fn peek_front(&self) -> Option<&H::Item> {
+        if self.empty() {
+            return None;
+        }
+        Some(&self.buf[self.header.head() as usize])
+    }
+
+    #[inline]
+    pub fn peek_front_mut(&mut self) -> Option<&mut H::Item> {
+        if self.empty() {
+            return None;
+        }
+        Some(&mut self.buf[self.header.head() as usize])
+    }
+
+    #[inline]
+    pub fn pop_front(&mut self) -> Result<H::Item, ()> {
+        if self.empty() {
+            return Err(());
+        }
+        let value = self.buf[self.header.head() as usize];
+
+        let count = self.header.count();
+        self.header.set_count(count - 1);
+
+        let head = self.header.head();
+        self.header.set_head((head + 1) % self.buf.len() as u64);
+
+        Ok(value)
+    }
+
+    #[inline]
     pub fn revert_pushes(&mut self, desired_len: u64) -> DexResult<()> {
         check_assert!(desired_len <= self.header.count())?;
         let len_diff = self.header.count() - desired_len;
@@ -589,6 +1638,25 @@ impl<'a, H: QueueHeader> Queue<'a, H> {
         Ok(())
     }
 
+    /// The item `index` places behind the front of the queue.
+    #[inline]
+    pub fn get(&self, index: u64) -> Option<&H::Item> {
+        if index >= self.len() {
+            return None;
+        }
+        let slot = ((self.header.head() + index) as usize) % self.buf.len();
+        Some(&self.buf[slot])
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, index: u64) -> Option<&mut H::Item> {
+        if index >= self.len() {
+            return None;
+        }
+        let slot = ((self.header.head() + index) as usize) % self.buf.len();
+        Some(&mut self.buf[slot])
+    }
+
     pub fn iter(&self) -> impl Iterator<Item = &H::Item> {
         QueueIterator {
             queue: self,
@@ -617,7 +1685,7 @@ impl<'a, 'b, H: QueueHeader> Iterator for QueueIterator<'a, 'b, H> {
 }
 
 #[derive(Copy, Clone, Debug)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct RequestQueueHeader {
     account_flags: u64, // Initialized, RequestQueue
     head: u64,
@@ -680,13 +1748,14 @@ enum RequestFlag {
 }
 
 #[derive(Copy, Clone, Debug)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Request {
     request_flags: u8,
     owner_slot: u8,
     fee_tier: u8,
     self_trade_behavior: u8,
-    padding: [u8; 4],
+    cancel_if_through_reference_bps: u16,
+    padding: [u8; 2],
     max_coin_qty_or_cancel_id: u64,
     native_pc_qty_locked: u64,
     order_id: u128,
@@ -709,6 +1778,7 @@ pub enum RequestView {
         owner: [u64; 4],
         client_order_id: Option<NonZeroU64>,
         self_trade_behavior: SelfTradeBehavior,
+        cancel_if_through_reference_bps: u16,
     },
     CancelOrder {
         side: Side,
@@ -735,6 +1805,7 @@ impl Request {
                 native_pc_qty_locked,
                 client_order_id,
                 self_trade_behavior,
+                cancel_if_through_reference_bps,
             } => {
                 let mut flags = BitFlags::from_flag(RequestFlag::NewOrder);
                 if side == Side::Bid {
@@ -751,6 +1822,7 @@ impl Request {
                     owner_slot,
                     fee_tier: fee_tier.into(),
                     self_trade_behavior: self_trade_behavior.into(),
+                    cancel_if_through_reference_bps,
                     padding: Zeroable::zeroed(),
                     order_id,
                     owner,
@@ -778,6 +1850,7 @@ impl Request {
                     owner_slot: expected_owner_slot,
                     fee_tier: 0,
                     self_trade_behavior: 0,
+                    cancel_if_through_reference_bps: 0,
                     owner: expected_owner,
                     native_pc_qty_locked: 0,
                     padding: Zeroable::zeroed(),
@@ -824,6 +1897,7 @@ impl Request {
                 max_coin_qty: NonZeroU64::new(self.max_coin_qty_or_cancel_id).unwrap(),
                 native_pc_qty_locked: NonZeroU64::new(self.native_pc_qty_locked),
                 client_order_id: NonZeroU64::new(self.client_order_id),
+                cancel_if_through_reference_bps: self.cancel_if_through_reference_bps,
             })
         } else {
             check_assert!(flags.contains(RequestFlag::CancelOrder))?;
@@ -845,7 +1919,7 @@ impl Request {
 }
 
 #[derive(Copy, Clone, Debug)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct EventQueueHeader {
     account_flags: u64, // Initialized, EventQueue
     head: u64,
@@ -855,8 +1929,14 @@ pub struct EventQueueHeader {
 unsafe impl Zeroable for EventQueueHeader {}
 unsafe impl Pod for EventQueueHeader {}
 
-unsafe impl TriviallyTransmutable for EventQueueHeader {}
-unsafe impl TriviallyTransmutable for RequestQueueHeader {}
+impl EventQueueHeader {
+    pub fn account_flags(&self) -> u64 {
+        self.account_flags
+    }
+    pub fn seq_num(&self) -> u64 {
+        self.seq_num
+    }
+}
 
 impl QueueHeader for EventQueueHeader {
     type Item = Event;
@@ -883,6 +1963,72 @@ impl QueueHeader for EventQueueHeader {
 
 pub type EventQueue<'a> = Queue<'a, EventQueueHeader>;
 
+impl EventQueue<'_> {
+    /// Number of events at the front of the queue `pop_consumed` looks
+    /// through, which bounds the events it moves.
+    pub const RECLAIM_SPAN: u64 = 256;
+
+    /// Sequence number of the event at the front of the queue.
+    #[inline]
+    pub fn head_seq_num(&self) -> u64 {
+        self.header.seq_num - self.header.count
+    }
+
+    /// Frees the slots of the consumed events among the first
+    /// `RECLAIM_SPAN`, including those consumed ahead of their turn, so
+    /// consuming out of order makes room in the queue too. The pending
+    /// events among them keep their order and move back past the consumed
+    /// ones, which then leave from the front.
+    ///
+    /// A sequence number is a place in the queue, so this raises those of
+    /// the pending events moved, by the number of consumed events that were
+    /// behind them, and lowers none. A consumer's cursor never skips one.
+    pub fn pop_consumed(&mut self) {
+        let span = self.len().min(Self::RECLAIM_SPAN);
+        let head = self.header.head() as usize;
+        let len = self.buf.len();
+        let mut pending_start = span;
+        for index in (0..span).rev() {
+            if self.get(index).map_or(true, Event::is_consumed) {
+                continue;
+            }
+            pending_start -= 1;
+            if pending_start != index {
+                self.buf.swap(
+                    (head + index as usize) % len,
+                    (head + pending_start as usize) % len,
+                );
+            }
+        }
+        for _ in 0..pending_start {
+            self.pop_front().unwrap();
+        }
+    }
+
+    // Consumed events stay in the buffer until they are overwritten, so this
+    // looks back through those as well as the pending ones. Only maker fills
+    // are considered, since they carry the price the trade happened at.
+    pub(crate) fn last_fill_price(&self) -> Option<u64> {
+        let len = self.buf.len() as u64;
+        if len == 0 {
+            return None;
+        }
+        let next = (self.header.head + self.header.count) % len;
+        let written = self.header.seq_num.min(len);
+        (1..=written)
+            .map(|back| &self.buf[((next + len - back) % len) as usize])
+            .filter_map(|event| event.as_view().ok())
+            .find_map(|view| match view {
+                EventView::Fill {
+                    maker: true,
+                    order_id,
+                    ..
+                } => Some(extract_price_from_order_id(order_id)),
+                _ => None,
+            })
+    }
+}
+
 #[derive(Copy, Clone, BitFlags, Debug)]
 #[repr(u8)]
 enum EventFlag {
@@ -890,6 +2036,9 @@ enum EventFlag {
     Out = 0x2,
     Bid = 0x4,
     Maker = 0x8,
+    // Consumed ahead of the events in front of it, and waiting for
+    // `EventQueue::pop_consumed` to free its slot.
+    Consumed = 0x10,
 }
 
 impl EventFlag {
@@ -912,7 +2061,7 @@ impl EventFlag {
 }
 
 #[derive(Copy, Clone, Debug)]
-#[repr(packed)]
+#[repr(C, packed)]
 pub struct Event {
     event_flags: u8,
     owner_slot: u8,
@@ -932,9 +2081,6 @@ pub struct Event {
 unsafe impl Zeroable for Event {}
 unsafe impl Pod for Event {}
 
-unsafe impl TriviallyTransmutable for Event {}
-unsafe impl TriviallyTransmutable for Request {}
-
 impl Event {
     #[inline(always)]
     pub fn new(view: EventView) -> Self {
@@ -1013,7 +2159,7 @@ impl Event {
         if flags.contains(EventFlag::Fill) {
             let allowed_flags = {
                 use EventFlag::*;
-                Fill | Bid | Maker
+                Fill | Bid | Maker | Consumed
             };
             check_assert!(allowed_flags.contains(flags))?;
 
@@ -1034,7 +2180,7 @@ impl Event {
         }
         let allowed_flags = {
             use EventFlag::*;
-            Out | Bid | Maker
+            Out | Bid | Maker | Consumed
         };
         check_assert!(allowed_flags.contains(flags))?;
         Ok(EventView::Out {
@@ -1049,11 +2195,38 @@ impl Event {
             client_order_id,
         })
     }
-}
 
-#[derive(Debug)]
-pub enum EventView {
-    Fill {
+    #[inline]
+    pub fn is_consumed(&self) -> bool {
+        BitFlags::<EventFlag>::from_bits_truncate(self.event_flags).contains(EventFlag::Consumed)
+    }
+
+    #[inline]
+    fn mark_consumed(&mut self) {
+        self.event_flags |= BitFlags::from_flag(EventFlag::Consumed).bits();
+    }
+
+    /// The event as the three lines of five words `ConsumeEvents` logs for
+    /// each fill, the first starting with `FILL_LOG_TAG`.
+    pub fn log_words(&self) -> [[u64; 5]; 3] {
+        let words: [u64; 11] = cast(*self);
+        [
+            [FILL_LOG_TAG, words[0], words[1], words[2], words[3]],
+            [words[4], words[5], words[6], words[7], words[8]],
+            [words[9], words[10], 0, 0, 0],
+        ]
+    }
+}
+
+/// "fill" in ASCII.
+pub const FILL_LOG_TAG: u64 = 0x6669_6c6c;
+
+/// "order" in ASCII. Starts the line `NewOrderBatch` logs for each order.
+pub const ORDER_LOG_TAG: u64 = 0x6f72_6465_72;
+
+#[derive(Debug)]
+pub enum EventView {
+    Fill {
         side: Side,
         maker: bool,
         native_qty_paid: u64,
@@ -1085,15 +2258,65 @@ impl EventView {
 }
 
 #[derive(Copy, Clone)]
-#[repr(packed)]
+#[repr(C, packed)]
 struct OrderBookStateHeader {
     account_flags: u64, // Initialized, (Bids or Asks)
 }
 unsafe impl Zeroable for OrderBookStateHeader {}
 unsafe impl Pod for OrderBookStateHeader {}
 
+// On-chain account layouts. Every type is packed so it can be viewed
+// in place at any alignment, and these sizes must never change.
+const_assert_eq!(size_of::<MarketState>(), 376);
+const_assert_eq!(size_of::<OpenOrders>(), 3216);
+const_assert_eq!(size_of::<TriggerOrder>(), 184);
+const_assert_eq!(size_of::<OpenOrdersDelegate>(), 104);
+const_assert_eq!(size_of::<SettleDestination>(), 136);
+const_assert_eq!(size_of::<FeeDestinations>(), 336);
+const_assert_eq!(size_of::<MarketStats>(), 96);
+const_assert_eq!(size_of::<TraderStats>(), 336);
+const_assert_eq!(size_of::<MarketListing>(), 144);
+const_assert_eq!(size_of::<MarketParams>(), 168);
+const_assert_eq!(size_of::<RequestQueueHeader>(), 32);
+const_assert_eq!(size_of::<Request>(), 80);
+const_assert_eq!(size_of::<EventQueueHeader>(), 32);
+const_assert_eq!(size_of::<Event>(), 88);
+const_assert_eq!(size_of::<OrderBookStateHeader>(), 8);
+const_assert_eq!(std::mem::align_of::<MarketState>(), 1);
+const_assert_eq!(std::mem::align_of::<OpenOrders>(), 1);
+const_assert_eq!(std::mem::align_of::<TriggerOrder>(), 1);
+const_assert_eq!(std::mem::align_of::<OpenOrdersDelegate>(), 1);
+const_assert_eq!(std::mem::align_of::<SettleDestination>(), 1);
+const_assert_eq!(std::mem::align_of::<FeeDestinations>(), 1);
+const_assert_eq!(std::mem::align_of::<Request>(), 1);
+const_assert_eq!(std::mem::align_of::<Event>(), 1);
+
 pub enum State {}
 
+/// Rejects lot sizes no market can trade with. Lots must be nonzero, and
+/// converting a price between lots and whole coins must fit in a u64: the
+/// mints can't have more than 19 decimals, and a tick's worth of native pc
+/// per whole coin, `pc_lot_size * 10^coin_decimals`, can't overflow.
+pub fn validate_lot_sizes(
+    coin_lot_size: u64,
+    pc_lot_size: u64,
+    coin_decimals: u8,
+    pc_decimals: u8,
+) -> DexResult {
+    let coin_unit = 10u64.checked_pow(coin_decimals.into());
+    let pc_unit = 10u64.checked_pow(pc_decimals.into());
+    let valid = match (coin_unit, pc_unit) {
+        (Some(coin_unit), Some(_)) => {
+            coin_lot_size > 0 && pc_lot_size > 0 && pc_lot_size.checked_mul(coin_unit).is_some()
+        }
+        _ => false,
+    };
+    if !valid {
+        Err(DexErrorCode::InvalidLotSizes)?
+    }
+    Ok(())
+}
+
 fn gen_vault_signer_seeds<'a>(nonce: &'a u64, market: &'a Pubkey) -> [&'a [u8]; 2] {
     [market.as_ref(), bytes_of(nonce)]
 }
@@ -1125,7 +2348,10 @@ fn invoke_spl_token(
     account_infos: &[AccountInfo],
     signers_seeds: &[&[&[u8]]],
 ) -> solana_program::entrypoint::ProgramResult {
-    solana_program::program::invoke_signed(instruction, account_infos, signers_seeds)
+    compute_checkpoint!("before token invoke");
+    solana_program::program::invoke_signed(instruction, account_infos, signers_seeds)?;
+    compute_checkpoint!("token invoke");
+    Ok(())
 }
 
 #[cfg(feature = "fuzz")]
@@ -1156,6 +2382,38 @@ fn invoke_spl_token(
     Ok(())
 }
 
+// Creates a mint pair's MarketListing account, rent exempt and owned by the
+// dex, at its derived address.
+#[cfg(not(feature = "client"))]
+fn create_market_listing_account<'a, 'b: 'a>(
+    listing: &'a AccountInfo<'b>,
+    lister: account_parser::SignerAccount<'a, 'b>,
+    system_program: account_parser::SystemProgram<'a, 'b>,
+    rent: Rent,
+    program_id: &Pubkey,
+    listing_seeds: &[&[u8]],
+) -> DexResult {
+    let space =
+        size_of::<MarketListing>() + ACCOUNT_HEAD_PADDING.len() + ACCOUNT_TAIL_PADDING.len();
+    let create_instruction = system_instruction::create_account(
+        lister.inner().key,
+        listing.key,
+        rent.minimum_balance(space),
+        space as u64,
+        program_id,
+    );
+    solana_program::program::invoke_signed(
+        &create_instruction,
+        &[
+            lister.inner().clone(),
+            listing.clone(),
+            system_program.inner().clone(),
+        ],
+        &[listing_seeds],
+    )?;
+    Ok(())
+}
+
 #[cfg(not(feature = "client"))]
 fn send_from_vault<'a, 'b: 'a>(
     native_amount: u64,
@@ -1184,6 +2442,46 @@ fn send_from_vault<'a, 'b: 'a>(
     Ok(())
 }
 
+// Pulls funds from the payer into the vault, signed by the owner.
+#[cfg(not(feature = "client"))]
+fn deposit_into_vault<'a, 'b: 'a>(
+    native_amount: u64,
+    payer: account_parser::TokenAccount<'a, 'b>,
+    vault: account_parser::TokenAccount<'a, 'b>,
+    owner: account_parser::SignerAccount<'a, 'b>,
+    spl_token_program: account_parser::SplTokenProgram<'a, 'b>,
+) -> DexResult {
+    let deposit_instruction = spl_token::instruction::transfer(
+        &spl_token::ID,
+        payer.inner().key,
+        vault.inner().key,
+        owner.inner().key,
+        &[],
+        native_amount,
+    )
+    .unwrap();
+    assert_eq!(*spl_token_program.inner().key, spl_token::ID);
+
+    invoke_spl_token(
+        &deposit_instruction,
+        &[
+            payer.inner().clone(),
+            vault.inner().clone(),
+            owner.inner().clone(),
+            spl_token_program.inner().clone(),
+        ],
+        &[],
+    )
+    .map_err(|err| match err {
+        ProgramError::Custom(i) => match TokenError::from_u32(i) {
+            Some(TokenError::InsufficientFunds) => DexErrorCode::InsufficientFunds,
+            _ => DexErrorCode::TransferFailed,
+        },
+        _ => DexErrorCode::TransferFailed,
+    })?;
+    Ok(())
+}
+
 pub mod account_parser {
     use super::*;
 
@@ -1221,6 +2519,13 @@ pub mod account_parser {
         Ok(())
     });
 
+    impl<'a, 'b: 'a> TokenMint<'a, 'b> {
+        pub fn decimals(self) -> DexResult<u8> {
+            let data = self.inner().try_borrow_data()?;
+            Ok(data[0x2c])
+        }
+    }
+
     declare_validated_account_wrapper!(TokenAccount, |account: &AccountInfo| {
         check_assert_eq!(*account.owner, spl_token::ID)?;
         let data = account.try_borrow_data()?;
@@ -1231,6 +2536,108 @@ pub mod account_parser {
         Ok(())
     });
 
+    // Offsets into the registry's bincode `Member` account: an initialized
+    // flag, then the entity, beneficiary and delegate keys, then the SRM and
+    // MSRM staked. The member's registrar comes after the memo and rewards.
+    const MEMBER_BENEFICIARY_OFFSET: usize = 33;
+    const MEMBER_AMOUNT_OFFSET: usize = 97;
+    const MEMBER_MEGA_AMOUNT_OFFSET: usize = 105;
+    const MEMBER_REGISTRAR_OFFSET: usize = 169;
+
+    declare_validated_account_wrapper!(RegistryMember, |account: &AccountInfo| {
+        if *account.owner != registry::ID {
+            Err(DexErrorCode::WrongRegistryMemberAccount)?
+        }
+        let data = account.try_borrow_data()?;
+        check_assert!(data.len() >= MEMBER_REGISTRAR_OFFSET + 32)?;
+        check_assert_eq!(data[0], 1u8)?;
+        let registrar = array_ref![data, MEMBER_REGISTRAR_OFFSET, 32];
+        if registrar != registry_srm_registrar::ID.as_ref() {
+            Err(DexErrorCode::WrongRegistryMemberAccount)?
+        }
+        Ok(())
+    });
+
+    impl<'a, 'b: 'a> RegistryMember<'a, 'b> {
+        pub fn beneficiary(self) -> DexResult<[u64; 4]> {
+            let data = self.inner().try_borrow_data()?;
+            let key = array_ref![data, MEMBER_BENEFICIARY_OFFSET, 32];
+            Ok(Pubkey::new(key).to_aligned_bytes())
+        }
+
+        /// The SRM and MSRM staked.
+        pub fn stake(self) -> DexResult<(u64, u64)> {
+            let data = self.inner().try_borrow_data()?;
+            Ok((
+                u64::from_le_bytes(*array_ref![data, MEMBER_AMOUNT_OFFSET, 8]),
+                u64::from_le_bytes(*array_ref![data, MEMBER_MEGA_AMOUNT_OFFSET, 8]),
+            ))
+        }
+    }
+
+    // Offsets into the lockup's bincode `Vesting` account: initialized and
+    // claimed flags, then the safe and beneficiary keys, then the balance.
+    // The part of the balance sent to whitelisted programs, e.g. staked
+    // with the registry, is at the end.
+    const VESTING_SAFE_OFFSET: usize = 2;
+    const VESTING_BENEFICIARY_OFFSET: usize = 34;
+    const VESTING_BALANCE_OFFSET: usize = 66;
+    const VESTING_WHITELIST_OWNED_OFFSET: usize = 170;
+
+    declare_validated_account_wrapper!(LockupVesting, |account: &AccountInfo| {
+        if *account.owner != lockup::ID {
+            Err(DexErrorCode::WrongLockupVestingAccount)?
+        }
+        let data = account.try_borrow_data()?;
+        check_assert!(data.len() >= VESTING_WHITELIST_OWNED_OFFSET + 8)?;
+        check_assert_eq!(data[0], 1u8)?;
+        let safe = array_ref![data, VESTING_SAFE_OFFSET, 32];
+        if safe != lockup_srm_safe::ID.as_ref() {
+            Err(DexErrorCode::WrongLockupVestingAccount)?
+        }
+        Ok(())
+    });
+
+    impl<'a, 'b: 'a> LockupVesting<'a, 'b> {
+        pub fn beneficiary(self) -> DexResult<[u64; 4]> {
+            let data = self.inner().try_borrow_data()?;
+            let key = array_ref![data, VESTING_BENEFICIARY_OFFSET, 32];
+            Ok(Pubkey::new(key).to_aligned_bytes())
+        }
+
+        /// The SRM locked, less what's been sent to whitelisted programs so
+        /// that SRM staked from a grant doesn't count twice.
+        pub fn locked(self) -> DexResult<u64> {
+            let data = self.inner().try_borrow_data()?;
+            let balance = u64::from_le_bytes(*array_ref![data, VESTING_BALANCE_OFFSET, 8]);
+            let whitelist_owned =
+                u64::from_le_bytes(*array_ref![data, VESTING_WHITELIST_OWNED_OFFSET, 8]);
+            Ok(balance.saturating_sub(whitelist_owned))
+        }
+    }
+
+    /// An account proving the SRM or MSRM backing a fee discount: a wallet
+    /// holding it, a registry member staking it or a lockup vesting account
+    /// locking it.
+    #[derive(Copy, Clone)]
+    pub enum FeeDiscountAccount<'a, 'b: 'a> {
+        Wallet(TokenAccount<'a, 'b>),
+        Stake(RegistryMember<'a, 'b>),
+        Locked(LockupVesting<'a, 'b>),
+    }
+
+    impl<'a, 'b: 'a> FeeDiscountAccount<'a, 'b> {
+        fn new(account: &'a AccountInfo<'b>) -> DexResult<Self> {
+            Ok(if *account.owner == registry::ID {
+                FeeDiscountAccount::Stake(RegistryMember::new(account)?)
+            } else if *account.owner == lockup::ID {
+                FeeDiscountAccount::Locked(LockupVesting::new(account)?)
+            } else {
+                FeeDiscountAccount::Wallet(TokenAccount::new(account)?)
+            })
+        }
+    }
+
     macro_rules! declare_validated_token_account_wrapper {
         ($WrapperT:ident, $validate:expr $(, $a:ident : $t:ty)*) => {
             #[derive(Copy, Clone)]
@@ -1265,6 +2672,16 @@ pub mod account_parser {
         Ok(())
     });
 
+    declare_validated_account_wrapper!(ClockSysvarAccount, |account: &AccountInfo| {
+        check_assert!(Clock::check_id(account.key))?;
+        Ok(())
+    });
+
+    declare_validated_account_wrapper!(SystemProgram, |account: &AccountInfo| {
+        check_assert_eq!(*account.key, system_program::ID)?;
+        Ok(())
+    });
+
     declare_validated_account_wrapper!(SignerAccount, |account: &AccountInfo| {
         check_assert!(account.is_signer)?;
         Ok(())
@@ -1354,12 +2771,25 @@ pub mod account_parser {
         }
     }
 
+    fn check_uninitialized(account: &AccountInfo, program_id: &Pubkey) -> DexResult {
+        check_assert_eq!(account.owner, program_id)?;
+        let data = account.try_borrow_data()?;
+        check_assert_eq!(data.len() % 8, 4)?;
+        check_assert!(data.len() >= 20)?;
+        let (padding5, header, _, padding7) = array_refs![&data, 5, 8; .. ; 7];
+        check_assert_eq!(*padding5, [0u8; 5])?;
+        check_assert_eq!(*header, [0u8; 8])?;
+        check_assert_eq!(*padding7, [0u8; 7])?;
+        Ok(())
+    }
+
     pub struct InitializeMarketArgs<'a, 'b: 'a> {
         pub program_id: &'a Pubkey,
         pub instruction: &'a InitializeMarketInstruction,
         serum_dex_accounts: &'a [AccountInfo<'b>; 5],
         pub coin_vault_and_mint: TokenAccountAndMint<'a, 'b>,
         pub pc_vault_and_mint: TokenAccountAndMint<'a, 'b>,
+        pub params: Option<&'a AccountInfo<'b>>,
     }
 
     impl<'a, 'b: 'a> InitializeMarketArgs<'a, 'b> {
@@ -1368,22 +2798,32 @@ pub mod account_parser {
             instruction: &'a InitializeMarketInstruction,
             accounts: &'a [AccountInfo<'b>],
         ) -> DexResult<Self> {
-            check_assert_eq!(accounts.len(), 9)?;
+            check_assert!(accounts.len() == 9 || accounts.len() == 10)?;
+            let params = accounts.get(9);
             let accounts = array_ref![accounts, 0, 9];
             let (unchecked_serum_dex_accounts, unchecked_vaults, unchecked_mints) =
                 array_refs![accounts, 5, 2, 2];
             let mut checked_vaults = [None, None];
             for account in unchecked_serum_dex_accounts {
-                check_assert_eq!(account.owner, program_id)?;
-                let data = account.try_borrow_data()?;
-                check_assert_eq!(data.len() % 8, 4)?;
-                check_assert!(data.len() >= 20)?;
-                let (padding5, header, _, padding7) = array_refs![&data, 5, 8; .. ; 7];
-                check_assert_eq!(*padding5, [0u8; 5])?;
-                check_assert_eq!(*header, [0u8; 8])?;
-                check_assert_eq!(*padding7, [0u8; 7])?;
+                check_uninitialized(account, program_id)?;
             }
             let serum_dex_accounts = unchecked_serum_dex_accounts;
+            match params {
+                Some(params) => {
+                    check_uninitialized(params, program_id)?;
+                    check_assert_eq!(
+                        params.data_len(),
+                        size_of::<MarketParams>()
+                            + ACCOUNT_HEAD_PADDING.len()
+                            + ACCOUNT_TAIL_PADDING.len()
+                    )
+                    .map_err(|_| DexErrorCode::WrongMarketParamsAccount)?;
+                }
+                None if instruction.min_notional != 0 => {
+                    Err(DexErrorCode::MarketParamsNotProvided)?
+                }
+                None => {}
+            }
             let vault_owner_key_bytes = gen_vault_signer_key(
                 instruction.vault_signer_nonce,
                 serum_dex_accounts[0].key,
@@ -1416,6 +2856,7 @@ pub mod account_parser {
                 serum_dex_accounts,
                 coin_vault_and_mint,
                 pc_vault_and_mint,
+                params,
             })
         }
 
@@ -1456,6 +2897,8 @@ pub mod account_parser {
         pub pc_vault: PcVault<'a, 'b>,
         pub spl_token_program: SplTokenProgram<'a, 'b>,
         pub fee_tier: FeeTier,
+        pub params: Option<&'a mut MarketParams>,
+        pub cancel_if_through_reference_bps: u16,
     }
     impl<'a, 'b: 'a> NewOrderArgs<'a, 'b> {
         pub fn with_parsed_args<T>(
@@ -1480,9 +2923,9 @@ pub mod account_parser {
                 ref spl_token_program_acc,
                 ref rent_sysvar_acc,
             ]: &'a [AccountInfo<'b>; 9] = fixed_accounts;
-            let srm_or_msrm_account = match fee_discount_account {
+            let fee_discount_account = match fee_discount_account {
                 &[] => None,
-                &[ref account] => Some(TokenAccount::new(account)?),
+                &[ref account] => Some(FeeDiscountAccount::new(account)?),
                 _ => check_unreachable!()?,
             };
 
@@ -1492,8 +2935,8 @@ pub mod account_parser {
                 Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
             };
             let owner = SignerAccount::new(owner_acc)?;
-            let fee_tier =
-                market.load_fee_tier(&owner.inner().key.to_aligned_bytes(), srm_or_msrm_account)?;
+            let fee_tier = market
+                .load_fee_tier(&owner.inner().key.to_aligned_bytes(), fee_discount_account)?;
             let mut open_orders = market.load_orders_mut(
                 open_orders_acc,
                 Some(owner.inner()),
@@ -1524,133 +2967,558 @@ pub mod account_parser {
                 pc_vault,
                 spl_token_program,
                 fee_tier,
+                params: None,
+                cancel_if_through_reference_bps: 0,
             };
             f(args)
         }
     }
 
-    pub struct MatchOrdersArgs<'a> {
-        pub limit: u16,
-        pub order_book_state: OrderBookState<'a>,
-        pub req_q: RequestQueue<'a>,
+    pub struct NewOrderV3Args<'a, 'b: 'a> {
+        pub new_order: NewOrderArgs<'a, 'b>,
         pub event_q: EventQueue<'a>,
+        pub bids: &'a mut Slab,
+        pub asks: &'a mut Slab,
+        pub self_trade_accounts: Vec<[u64; 4]>,
+        pub stats: Option<(&'a mut MarketStats, u64)>,
+        pub trader_stats: Option<(&'a mut TraderStats, u64)>,
     }
-    impl<'a> MatchOrdersArgs<'a> {
-        pub fn with_parsed_args<'b, T>(
+    impl<'a, 'b: 'a> NewOrderV3Args<'a, 'b> {
+        pub fn with_parsed_args<T>(
             program_id: &'a Pubkey,
+            instruction: &'a NewOrderInstructionV2,
             accounts: &'a [AccountInfo<'b>],
-            limit: u16,
-            f: impl FnOnce(MatchOrdersArgs) -> DexResult<T>,
+            f: impl FnOnce(NewOrderV3Args) -> DexResult<T>,
         ) -> DexResult<T> {
-            check_assert!(accounts.len() >= 5)?;
-            #[rustfmt::skip]
+            check_assert!(accounts.len() >= 12 && accounts.len() <= 18 + MAX_SELF_TRADE_ACCOUNTS)?;
+            let (fixed_accounts, optional_accounts): (
+                &'a [AccountInfo<'b>; 12],
+                &'a [AccountInfo<'b>],
+            ) = array_refs![accounts, 12; .. ;];
             let &[
                 ref market_acc,
+                ref open_orders_acc,
                 ref req_q_acc,
+                ref payer_acc,
+                ref owner_acc,
+                ref coin_vault_acc,
+                ref pc_vault_acc,
+                ref spl_token_program_acc,
+                ref rent_sysvar_acc,
                 ref event_q_acc,
                 ref bids_acc,
-                ref asks_acc
-            ] = array_ref![accounts, 0, 5];
-            let mut market = MarketState::load(market_acc, program_id).or(check_unreachable!())?;
-            let req_q = market
-                .load_request_queue_mut(req_q_acc)
-                .or(check_unreachable!())?;
-            let event_q = market
-                .load_event_queue_mut(event_q_acc)
-                .or(check_unreachable!())?;
-            let mut bids = market.load_bids_mut(bids_acc).or(check_unreachable!())?;
-            let mut asks = market.load_asks_mut(asks_acc).or(check_unreachable!())?;
+                ref asks_acc,
+            ]: &'a [AccountInfo<'b>; 12] = fixed_accounts;
+            let (fee_discount_account, delegate_acc, self_trade_accs, stats_accs, params_acc) =
+                split_optional_order_accounts(program_id, optional_accounts)?;
 
-            let order_book_state = OrderBookState {
-                bids: bids.deref_mut(),
-                asks: asks.deref_mut(),
-                market_state: market.deref_mut(),
+            let mut market: RefMut<'a, MarketState> = MarketState::load(market_acc, program_id)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
             };
-
-            let args = MatchOrdersArgs {
-                limit,
-                order_book_state,
-                req_q,
-                event_q,
+            let owner = SignerAccount::new(owner_acc)?;
+            let fee_tier = market
+                .load_fee_tier(&owner.inner().key.to_aligned_bytes(), fee_discount_account)?;
+            let mut open_orders = market.load_orders_for_trader_mut(
+                open_orders_acc,
+                owner.inner(),
+                delegate_acc,
+                program_id,
+                Some(rent),
+            )?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            let self_trade_accounts =
+                load_self_trade_accounts(&market, &open_orders, self_trade_accs, program_id)?;
+            let mut params = load_params(&market, params_acc, program_id)?;
+            if let Some(params) = &params {
+                params.check_market_stats(
+                    stats_accs
+                        .as_ref()
+                        .and_then(|accounts| accounts.market_stats),
+                )?;
+            }
+            let (mut stats, mut trader_stats) = load_stats(
+                &market,
+                &identity(open_orders.owner),
+                stats_accs,
+                program_id,
+                rent,
+            )?;
+            let fee_tier = match &trader_stats {
+                Some((trader_stats, day)) => {
+                    fee_tier.with_taker_volume(trader_stats.volume_30d(*day))
+                }
+                None => fee_tier,
             };
-            f(args)
-        }
-    }
-
-    pub struct ConsumeEventsArgs<'a, 'b: 'a> {
-        pub limit: u16,
-        pub program_id: &'a Pubkey,
-        pub open_orders_accounts: &'a [AccountInfo<'b>],
-        pub market: &'a mut MarketState,
-        pub event_q: EventQueue<'a>,
-    }
-    impl<'a, 'b: 'a> ConsumeEventsArgs<'a, 'b> {
-        pub fn with_parsed_args<T>(
-            program_id: &'a Pubkey,
-            accounts: &'a [AccountInfo<'b>],
-            limit: u16,
-            f: impl FnOnce(ConsumeEventsArgs) -> DexResult<T>,
-        ) -> DexResult<T> {
-            check_assert!(accounts.len() >= 5)?;
-            #[rustfmt::skip]
-            let (
-                &[],
-                open_orders_accounts,
-                &[ref market_acc],
-                &[ref event_q_acc],
-                _unused
-            ) = array_refs![accounts, 0; .. ; 1, 1, 2];
-            let mut market = MarketState::load(market_acc, program_id)?;
+            let req_q = market.load_request_queue_mut(req_q_acc)?;
             let event_q = market.load_event_queue_mut(event_q_acc)?;
-            let args = ConsumeEventsArgs {
-                limit,
-                program_id,
-                open_orders_accounts,
-                market: market.deref_mut(),
+            let mut bids = market.load_bids_mut(bids_acc)?;
+            let mut asks = market.load_asks_mut(asks_acc)?;
+
+            let payer = TokenAccount::new(payer_acc)?;
+            match instruction.side {
+                Side::Bid => market.check_pc_payer(payer).or(check_unreachable!())?,
+                Side::Ask => market.check_coin_payer(payer).or(check_unreachable!())?,
+            };
+            let coin_vault = CoinVault::from_account(coin_vault_acc, &market)?;
+            let pc_vault = PcVault::from_account(pc_vault_acc, &market)?;
+            market.check_enabled()?;
+            let spl_token_program = SplTokenProgram::new(spl_token_program_acc)?;
+            let args = NewOrderV3Args {
+                new_order: NewOrderArgs {
+                    instruction,
+                    market: market.deref_mut(),
+                    open_orders: open_orders.deref_mut(),
+                    open_orders_address,
+                    owner,
+                    req_q,
+                    payer,
+                    coin_vault,
+                    pc_vault,
+                    spl_token_program,
+                    fee_tier,
+                    params: params.as_mut().map(|params| params.deref_mut()),
+                    cancel_if_through_reference_bps: 0,
+                },
                 event_q,
+                bids: bids.deref_mut(),
+                asks: asks.deref_mut(),
+                self_trade_accounts,
+                stats: stats
+                    .as_mut()
+                    .map(|(stats, slot)| (stats.deref_mut(), *slot)),
+                trader_stats: trader_stats
+                    .as_mut()
+                    .map(|(stats, day)| (stats.deref_mut(), *day)),
             };
             f(args)
         }
     }
 
-    pub struct CancelOrderArgs<'a, 'b: 'a> {
-        pub instruction: &'a CancelOrderInstruction,
+    pub struct NewOrderBatchArgs<'a, 'b: 'a> {
+        pub orders: &'a [NewOrderInstructionV2],
+        pub market: &'a mut MarketState,
         pub open_orders: &'a mut OpenOrders,
         pub open_orders_address: [u64; 4],
+        pub owner: SignerAccount<'a, 'b>,
         pub req_q: RequestQueue<'a>,
-        pub orders_owner: SignerAccount<'a, 'b>,
+        pub event_q: EventQueue<'a>,
+        pub bids: &'a mut Slab,
+        pub asks: &'a mut Slab,
+        pub coin_payer: TokenAccount<'a, 'b>,
+        pub pc_payer: TokenAccount<'a, 'b>,
+        pub coin_vault: CoinVault<'a, 'b>,
+        pub pc_vault: PcVault<'a, 'b>,
+        pub spl_token_program: SplTokenProgram<'a, 'b>,
+        pub fee_tier: FeeTier,
+        pub params: Option<&'a mut MarketParams>,
+        pub self_trade_accounts: Vec<[u64; 4]>,
+        pub stats: Option<(&'a mut MarketStats, u64)>,
+        pub trader_stats: Option<(&'a mut TraderStats, u64)>,
     }
-    impl<'a, 'b: 'a> CancelOrderArgs<'a, 'b> {
+    impl<'a, 'b: 'a> NewOrderBatchArgs<'a, 'b> {
         pub fn with_parsed_args<T>(
             program_id: &'a Pubkey,
+            instruction: &'a NewOrderBatchInstruction,
             accounts: &'a [AccountInfo<'b>],
-            instruction: &'a CancelOrderInstruction,
-            f: impl FnOnce(CancelOrderArgs) -> DexResult<T>,
+            f: impl FnOnce(NewOrderBatchArgs) -> DexResult<T>,
         ) -> DexResult<T> {
-            check_assert_eq!(accounts.len(), 4)?;
-            #[rustfmt::skip]
+            check_assert!(accounts.len() >= 13 && accounts.len() <= 19 + MAX_SELF_TRADE_ACCOUNTS)?;
+            let (fixed_accounts, optional_accounts): (
+                &'a [AccountInfo<'b>; 13],
+                &'a [AccountInfo<'b>],
+            ) = array_refs![accounts, 13; .. ;];
             let &[
                 ref market_acc,
                 ref open_orders_acc,
                 ref req_q_acc,
-                ref owner_acc
-            ] = array_ref![accounts, 0, 4];
-            let market: RefMut<'a, MarketState> = MarketState::load(market_acc, program_id)?;
+                ref coin_payer_acc,
+                ref pc_payer_acc,
+                ref owner_acc,
+                ref coin_vault_acc,
+                ref pc_vault_acc,
+                ref spl_token_program_acc,
+                ref rent_sysvar_acc,
+                ref event_q_acc,
+                ref bids_acc,
+                ref asks_acc,
+            ]: &'a [AccountInfo<'b>; 13] = fixed_accounts;
+            let (fee_discount_account, delegate_acc, self_trade_accs, stats_accs, params_acc) =
+                split_optional_order_accounts(program_id, optional_accounts)?;
+
+            let mut market: RefMut<'a, MarketState> = MarketState::load(market_acc, program_id)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
+            };
             let owner = SignerAccount::new(owner_acc)?;
-            let mut open_orders =
-                market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
+            let fee_tier = market
+                .load_fee_tier(&owner.inner().key.to_aligned_bytes(), fee_discount_account)?;
+            let mut open_orders = market.load_orders_for_trader_mut(
+                open_orders_acc,
+                owner.inner(),
+                delegate_acc,
+                program_id,
+                Some(rent),
+            )?;
             let open_orders_address = open_orders_acc.key.to_aligned_bytes();
-            let req_q = market.load_request_queue_mut(req_q_acc)?;
-            let args = CancelOrderArgs {
-                instruction,
-                open_orders: open_orders.deref_mut(),
-                open_orders_address,
-                req_q,
-                orders_owner: owner,
+            let self_trade_accounts =
+                load_self_trade_accounts(&market, &open_orders, self_trade_accs, program_id)?;
+            let mut params = load_params(&market, params_acc, program_id)?;
+            if let Some(params) = &params {
+                params.check_market_stats(
+                    stats_accs
+                        .as_ref()
+                        .and_then(|accounts| accounts.market_stats),
+                )?;
+            }
+            let (mut stats, mut trader_stats) = load_stats(
+                &market,
+                &identity(open_orders.owner),
+                stats_accs,
+                program_id,
+                rent,
+            )?;
+            let fee_tier = match &trader_stats {
+                Some((trader_stats, day)) => {
+                    fee_tier.with_taker_volume(trader_stats.volume_30d(*day))
+                }
+                None => fee_tier,
             };
-            f(args)
-        }
-    }
+            let req_q = market.load_request_queue_mut(req_q_acc)?;
+            let event_q = market.load_event_queue_mut(event_q_acc)?;
+            let mut bids = market.load_bids_mut(bids_acc)?;
+            let mut asks = market.load_asks_mut(asks_acc)?;
+
+            let coin_payer = TokenAccount::new(coin_payer_acc)?;
+            market
+                .check_coin_payer(coin_payer)
+                .or(check_unreachable!())?;
+            let pc_payer = TokenAccount::new(pc_payer_acc)?;
+            market.check_pc_payer(pc_payer).or(check_unreachable!())?;
+            let coin_vault = CoinVault::from_account(coin_vault_acc, &market)?;
+            let pc_vault = PcVault::from_account(pc_vault_acc, &market)?;
+            market.check_enabled()?;
+            let spl_token_program = SplTokenProgram::new(spl_token_program_acc)?;
+            let args = NewOrderBatchArgs {
+                orders: &instruction.orders,
+                market: market.deref_mut(),
+                open_orders: open_orders.deref_mut(),
+                open_orders_address,
+                owner,
+                req_q,
+                event_q,
+                bids: bids.deref_mut(),
+                asks: asks.deref_mut(),
+                coin_payer,
+                pc_payer,
+                coin_vault,
+                pc_vault,
+                spl_token_program,
+                fee_tier,
+                params: params.as_mut().map(|params| params.deref_mut()),
+                self_trade_accounts,
+                stats: stats
+                    .as_mut()
+                    .map(|(stats, slot)| (stats.deref_mut(), *slot)),
+                trader_stats: trader_stats
+                    .as_mut()
+                    .map(|(stats, day)| (stats.deref_mut(), *day)),
+            };
+            f(args)
+        }
+    }
+
+    // The accounts recording the stats of an order's trades, followed by the
+    // clock sysvar. Either can be omitted, but not both.
+    struct StatsAccounts<'a, 'b: 'a> {
+        market_stats: Option<&'a AccountInfo<'b>>,
+        trader_stats: Option<&'a AccountInfo<'b>>,
+        clock: &'a AccountInfo<'b>,
+    }
+
+    // The MarketStats and TraderStats accounts, initialized if new, with the
+    // current slot and unix day respectively.
+    type LoadedStats<'a> = (
+        Option<(RefMut<'a, MarketStats>, u64)>,
+        Option<(RefMut<'a, TraderStats>, u64)>,
+    );
+
+    // Splits the optional accounts of NewOrderV3 and NewOrderBatch into the
+    // fee discount account, the delegate account, the owner's other
+    // OpenOrders accounts, the stats accounts with the clock sysvar and the
+    // MarketParams account, each part being optional. The dex doesn't own
+    // fee discount accounts, delegate accounts are smaller than OpenOrders,
+    // the MarketParams account comes last and the clock sysvar before it,
+    // after the stats accounts, which are told apart by size.
+    fn split_optional_order_accounts<'a, 'b: 'a>(
+        program_id: &Pubkey,
+        accounts: &'a [AccountInfo<'b>],
+    ) -> DexResult<(
+        Option<FeeDiscountAccount<'a, 'b>>,
+        Option<&'a AccountInfo<'b>>,
+        &'a [AccountInfo<'b>],
+        Option<StatsAccounts<'a, 'b>>,
+        Option<&'a AccountInfo<'b>>,
+    )> {
+        let account_len =
+            |size: usize| size + ACCOUNT_HEAD_PADDING.len() + ACCOUNT_TAIL_PADDING.len();
+        let (params_acc, accounts) = match accounts {
+            [rest @ .., account]
+                if account.owner == program_id
+                    && account.data_len() == account_len(size_of::<MarketParams>()) =>
+            {
+                (Some(account), rest)
+            }
+            _ => (None, accounts),
+        };
+        let (stats_accounts, accounts) = match accounts {
+            [rest @ .., clock] if Clock::check_id(clock.key) => {
+                let (trader_stats, rest) = match rest {
+                    [rest @ .., account]
+                        if account.data_len() == account_len(size_of::<TraderStats>()) =>
+                    {
+                        (Some(account), rest)
+                    }
+                    _ => (None, rest),
+                };
+                let (market_stats, rest) = match rest {
+                    [rest @ .., account]
+                        if account.data_len() == account_len(size_of::<MarketStats>()) =>
+                    {
+                        (Some(account), rest)
+                    }
+                    _ => (None, rest),
+                };
+                check_assert!(market_stats.is_some() || trader_stats.is_some())?;
+                let stats_accounts = StatsAccounts {
+                    market_stats,
+                    trader_stats,
+                    clock,
+                };
+                (Some(stats_accounts), rest)
+            }
+            _ => (None, accounts),
+        };
+        let (fee_discount_account, accounts) = match accounts.split_first() {
+            Some((account, rest)) if account.owner != program_id => {
+                (Some(FeeDiscountAccount::new(account)?), rest)
+            }
+            _ => (None, accounts),
+        };
+        let open_orders_len = account_len(size_of::<OpenOrders>());
+        let (delegate_acc, accounts) = match accounts.split_first() {
+            Some((account, rest)) if account.data_len() != open_orders_len => (Some(account), rest),
+            _ => (None, accounts),
+        };
+        check_assert!(accounts.len() <= MAX_SELF_TRADE_ACCOUNTS)?;
+        Ok((
+            fee_discount_account,
+            delegate_acc,
+            accounts,
+            stats_accounts,
+            params_acc,
+        ))
+    }
+
+    // Loads the market's params, if passed.
+    fn load_params<'a>(
+        market: &MarketState,
+        params_acc: Option<&'a AccountInfo>,
+        program_id: &Pubkey,
+    ) -> DexResult<Option<RefMut<'a, MarketParams>>> {
+        match params_acc {
+            None => Ok(None),
+            Some(account) => Ok(Some(market.load_market_params_mut(account, program_id)?)),
+        }
+    }
+
+    fn load_stats<'a>(
+        market: &MarketState,
+        owner: &[u64; 4],
+        stats_accounts: Option<StatsAccounts<'a, '_>>,
+        program_id: &Pubkey,
+        rent: Rent,
+    ) -> DexResult<LoadedStats<'a>> {
+        let StatsAccounts {
+            market_stats,
+            trader_stats,
+            clock,
+        } = match stats_accounts {
+            None => return Ok((None, None)),
+            Some(accounts) => accounts,
+        };
+        let clock = {
+            let clock_sysvar = ClockSysvarAccount::new(clock)?;
+            Clock::from_account_info(clock_sysvar.inner()).or(check_unreachable!())?
+        };
+        let market_stats = match market_stats {
+            Some(account) => Some((
+                market.load_market_stats_mut(account, program_id, Some(rent))?,
+                clock.slot,
+            )),
+            None => None,
+        };
+        let trader_stats = match trader_stats {
+            Some(account) => Some((
+                market.load_trader_stats_mut(account, owner, program_id, Some(rent))?,
+                TraderStats::day(clock.unix_timestamp),
+            )),
+            None => None,
+        };
+        Ok((market_stats, trader_stats))
+    }
+
+    // The addresses of other OpenOrders accounts on the market with the same
+    // owner as `open_orders`.
+    fn load_self_trade_accounts(
+        market: &MarketState,
+        open_orders: &OpenOrders,
+        accounts: &[AccountInfo],
+        program_id: &Pubkey,
+    ) -> DexResult<Vec<[u64; 4]>> {
+        accounts
+            .iter()
+            .map(|account| {
+                let other = market.load_orders_mut(account, None, program_id, None)?;
+                check_assert_eq!(identity(other.owner), identity(open_orders.owner))
+                    .map_err(|_| DexErrorCode::WrongOrdersAccount)?;
+                Ok(account.key.to_aligned_bytes())
+            })
+            .collect()
+    }
+
+    pub struct MatchOrdersArgs<'a> {
+        pub limit: u16,
+        pub order_book_state: OrderBookState<'a>,
+        pub req_q: RequestQueue<'a>,
+        pub event_q: EventQueue<'a>,
+    }
+    impl<'a> MatchOrdersArgs<'a> {
+        pub fn with_parsed_args<'b, T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            limit: u16,
+            f: impl FnOnce(MatchOrdersArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert!(accounts.len() >= 5)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref req_q_acc,
+                ref event_q_acc,
+                ref bids_acc,
+                ref asks_acc
+            ] = array_ref![accounts, 0, 5];
+            let mut market = MarketState::load(market_acc, program_id).or(check_unreachable!())?;
+            let req_q = market
+                .load_request_queue_mut(req_q_acc)
+                .or(check_unreachable!())?;
+            let event_q = market
+                .load_event_queue_mut(event_q_acc)
+                .or(check_unreachable!())?;
+            let mut bids = market.load_bids_mut(bids_acc).or(check_unreachable!())?;
+            let mut asks = market.load_asks_mut(asks_acc).or(check_unreachable!())?;
+
+            let order_book_state = OrderBookState {
+                bids: bids.deref_mut(),
+                asks: asks.deref_mut(),
+                market_state: market.deref_mut(),
+                self_trade_accounts: &[],
+                stats: None,
+                trader_stats: None,
+                params: None,
+            };
+
+            let args = MatchOrdersArgs {
+                limit,
+                order_book_state,
+                req_q,
+                event_q,
+            };
+            f(args)
+        }
+    }
+
+    pub struct ConsumeEventsArgs<'a, 'b: 'a> {
+        pub limit: u16,
+        pub start_seq_num: Option<u64>,
+        pub program_id: &'a Pubkey,
+        pub open_orders_accounts: &'a [AccountInfo<'b>],
+        pub market: &'a mut MarketState,
+        pub event_q: EventQueue<'a>,
+    }
+    impl<'a, 'b: 'a> ConsumeEventsArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            limit: u16,
+            start_seq_num: Option<u64>,
+            f: impl FnOnce(ConsumeEventsArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert!(accounts.len() >= 5)?;
+            #[rustfmt::skip]
+            let (
+                &[],
+                open_orders_accounts,
+                &[ref market_acc],
+                &[ref event_q_acc],
+                _unused
+            ) = array_refs![accounts, 0; .. ; 1, 1, 2];
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let event_q = market.load_event_queue_mut(event_q_acc)?;
+            let args = ConsumeEventsArgs {
+                limit,
+                start_seq_num,
+                program_id,
+                open_orders_accounts,
+                market: market.deref_mut(),
+                event_q,
+            };
+            f(args)
+        }
+    }
+
+    pub struct CancelOrderArgs<'a, 'b: 'a> {
+        pub instruction: &'a CancelOrderInstruction,
+        pub open_orders: &'a mut OpenOrders,
+        pub open_orders_address: [u64; 4],
+        pub req_q: RequestQueue<'a>,
+        pub orders_owner: SignerAccount<'a, 'b>,
+    }
+    impl<'a, 'b: 'a> CancelOrderArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            instruction: &'a CancelOrderInstruction,
+            f: impl FnOnce(CancelOrderArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 4)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref open_orders_acc,
+                ref req_q_acc,
+                ref owner_acc
+            ] = array_ref![accounts, 0, 4];
+            let market: RefMut<'a, MarketState> = MarketState::load(market_acc, program_id)?;
+            market.check_request_queue_open()?;
+            let owner = SignerAccount::new(owner_acc)?;
+            let mut open_orders =
+                market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            let req_q = market.load_request_queue_mut(req_q_acc)?;
+            let args = CancelOrderArgs {
+                instruction,
+                open_orders: open_orders.deref_mut(),
+                open_orders_address,
+                req_q,
+                orders_owner: owner,
+            };
+            f(args)
+        }
+    }
 
     pub struct CancelOrderByClientIdArgs<'a, 'b: 'a> {
         pub client_order_id: NonZeroU64,
@@ -1676,6 +3544,7 @@ pub mod account_parser {
                 ref owner_acc
             ] = array_ref![accounts, 0, 4];
             let market: RefMut<'a, MarketState> = MarketState::load(market_acc, program_id)?;
+            market.check_request_queue_open()?;
             let owner = SignerAccount::new(owner_acc)?;
             let mut open_orders =
                 market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
@@ -1692,6 +3561,72 @@ pub mod account_parser {
         }
     }
 
+    pub struct CancelOrderV2Args<'a, 'b: 'a> {
+        pub open_orders: &'a mut OpenOrders,
+        pub open_orders_address: [u64; 4],
+        pub req_q: RequestQueue<'a>,
+        pub event_q: EventQueue<'a>,
+        pub order_book_state: OrderBookState<'a>,
+        pub orders_owner: SignerAccount<'a, 'b>,
+    }
+    impl<'a, 'b: 'a> CancelOrderV2Args<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(CancelOrderV2Args) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert!(accounts.len() == 7 || accounts.len() == 8)?;
+            #[rustfmt::skip]
+            let (&[
+                ref market_acc,
+                ref open_orders_acc,
+                ref req_q_acc,
+                ref owner_acc,
+                ref event_q_acc,
+                ref bids_acc,
+                ref asks_acc
+            ], delegate_acc) = array_refs![accounts, 7; ..;];
+            let delegate_acc = match delegate_acc {
+                &[] => None,
+                &[ref account] => Some(account),
+                _ => check_unreachable!()?,
+            };
+            let mut market: RefMut<'a, MarketState> = MarketState::load(market_acc, program_id)?;
+            let owner = SignerAccount::new(owner_acc)?;
+            let mut open_orders = market.load_orders_for_trader_mut(
+                open_orders_acc,
+                owner.inner(),
+                delegate_acc,
+                program_id,
+                None,
+            )?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            let req_q = market.load_request_queue_mut(req_q_acc)?;
+            let event_q = market.load_event_queue_mut(event_q_acc)?;
+            let mut bids = market.load_bids_mut(bids_acc)?;
+            let mut asks = market.load_asks_mut(asks_acc)?;
+
+            let order_book_state = OrderBookState {
+                bids: bids.deref_mut(),
+                asks: asks.deref_mut(),
+                market_state: market.deref_mut(),
+                self_trade_accounts: &[],
+                stats: None,
+                trader_stats: None,
+                params: None,
+            };
+            let args = CancelOrderV2Args {
+                open_orders: open_orders.deref_mut(),
+                open_orders_address,
+                req_q,
+                event_q,
+                order_book_state,
+                orders_owner: owner,
+            };
+            f(args)
+        }
+    }
+
     pub struct SettleFundsArgs<'a, 'b: 'a> {
         pub market: &'a mut MarketState,
         pub open_orders: &'a mut OpenOrders,
@@ -1709,7 +3644,7 @@ pub mod account_parser {
             accounts: &'a [AccountInfo<'b>],
             f: impl FnOnce(SettleFundsArgs) -> DexResult<T>,
         ) -> DexResult<T> {
-            check_assert!(accounts.len() == 9 || accounts.len() == 10)?;
+            check_assert!(accounts.len() >= 9 && accounts.len() <= 11)?;
             #[rustfmt::skip]
             let (&[
                 ref market_acc,
@@ -1724,8 +3659,6 @@ pub mod account_parser {
             ], remaining_accounts) = array_refs![accounts, 9; ..;];
             let spl_token_program = SplTokenProgram::new(spl_token_program_acc)?;
             let mut market = MarketState::load(market_acc, program_id)?;
-            let owner = SignerAccount::new(owner_acc).or(check_unreachable!())?;
-
             let coin_vault =
                 CoinVault::from_account(coin_vault_acc, &market).or(check_unreachable!())?;
             let pc_vault = PcVault::from_account(pc_vault_acc, &market).or(check_unreachable!())?;
@@ -1734,18 +3667,54 @@ pub mod account_parser {
             let pc_wallet =
                 PcWallet::from_account(pc_wallet_acc, &market).or(check_unreachable!())?;
 
-            let referrer = match remaining_accounts {
-                &[] => None,
-                &[ref referrer_acc] => {
-                    Some(PcWallet::from_account(referrer_acc, &market).or(check_unreachable!())?)
+            // A lone optional account is told apart by its owner: the dex
+            // owns settle destinations, the token program referrer wallets.
+            let (referrer_acc, destination_acc) = match remaining_accounts {
+                &[] => (None, None),
+                &[ref account] if account.owner == program_id => (None, Some(account)),
+                &[ref account] => (Some(account), None),
+                &[ref referrer_acc, ref destination_acc] => {
+                    (Some(referrer_acc), Some(destination_acc))
                 }
                 _ => check_unreachable!()?,
             };
+            let referrer = match referrer_acc {
+                None => None,
+                Some(referrer_acc) => {
+                    Some(PcWallet::from_account(referrer_acc, &market).or(check_unreachable!())?)
+                }
+            };
 
             let vault_signer = VaultSigner::new(vault_signer_acc, &market, program_id)?;
 
+            // Without the owner's signature, funds may only go to the
+            // wallets the owner registered in advance, and no referrer can
+            // claim the accrued rebates.
+            if !owner_acc.is_signer {
+                check_assert!(referrer_acc.is_none())?;
+                let destination_acc =
+                    destination_acc.ok_or(DexErrorCode::WrongSettleDestinationAccount)?;
+                let destination =
+                    market.load_settle_destination_mut(destination_acc, program_id, None)?;
+                check_assert_eq!(
+                    identity(destination.open_orders),
+                    open_orders_acc.key.to_aligned_bytes()
+                )
+                .map_err(|_| DexErrorCode::WrongSettleDestinationAccount)?;
+                check_assert_eq!(
+                    identity(destination.coin_wallet),
+                    coin_wallet_acc.key.to_aligned_bytes()
+                )
+                .map_err(|_| DexErrorCode::WrongSettleDestinationAccount)?;
+                check_assert_eq!(
+                    identity(destination.pc_wallet),
+                    pc_wallet_acc.key.to_aligned_bytes()
+                )
+                .map_err(|_| DexErrorCode::WrongSettleDestinationAccount)?;
+            }
+
             let mut open_orders =
-                market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
+                market.load_orders_mut(open_orders_acc, Some(owner_acc), program_id, None)?;
 
             let args = SettleFundsArgs {
                 market: market.deref_mut(),
@@ -1785,453 +3754,2602 @@ pub mod account_parser {
         }
     }
 
-    pub struct SweepFeesArgs<'a, 'b: 'a> {
+    pub struct ResizeMarketAccountsArgs<'a, 'b: 'a> {
         pub market: &'a mut MarketState,
-        pub pc_vault: PcVault<'a, 'b>,
-        pub fee_receiver: PcWallet<'a, 'b>,
-        pub vault_signer: VaultSigner<'a, 'b>,
-        pub spl_token_program: SplTokenProgram<'a, 'b>,
-        pub authorization: SigningFeeSweeper<'a, 'b>,
+        pub event_q: EventQueue<'a>,
+        pub bids: &'a Slab,
+        pub asks: &'a Slab,
+        pub new_event_q: &'a AccountInfo<'b>,
+        pub new_bids: &'a AccountInfo<'b>,
+        pub new_asks: &'a AccountInfo<'b>,
+        pub authorization: SigningDisableAuthority<'a, 'b>,
     }
-    impl<'a, 'b: 'a> SweepFeesArgs<'a, 'b> {
+    impl<'a, 'b: 'a> ResizeMarketAccountsArgs<'a, 'b> {
         pub fn with_parsed_args<T>(
             program_id: &'a Pubkey,
             accounts: &'a [AccountInfo<'b>],
-            f: impl FnOnce(SweepFeesArgs) -> DexResult<T>,
+            f: impl FnOnce(ResizeMarketAccountsArgs) -> DexResult<T>,
         ) -> DexResult<T> {
-            check_assert_eq!(accounts.len(), 6)?;
+            check_assert_eq!(accounts.len(), 8)?;
             #[rustfmt::skip]
             let &[
                 ref market_acc,
-                ref pc_vault_acc,
-                ref sweep_authority_acc,
-                ref pc_wallet_acc,
-                ref vault_signer_acc,
-                ref spl_token_program
+                ref event_q_acc,
+                ref bids_acc,
+                ref asks_acc,
+                ref new_event_q_acc,
+                ref new_bids_acc,
+                ref new_asks_acc,
+                ref signer_acc,
+            ] = array_ref![accounts, 0, 8];
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let event_q = market.load_event_queue_mut(event_q_acc)?;
+            let bids = market.load_bids_mut(bids_acc)?;
+            let asks = market.load_asks_mut(asks_acc)?;
+            for account in &[new_event_q_acc, new_bids_acc, new_asks_acc] {
+                check_uninitialized(account, program_id)?;
+            }
+            let authorization = SigningDisableAuthority::new(signer_acc)?;
+
+            let args = ResizeMarketAccountsArgs {
+                market: market.deref_mut(),
+                event_q,
+                bids: &*bids,
+                asks: &*asks,
+                new_event_q: new_event_q_acc,
+                new_bids: new_bids_acc,
+                new_asks: new_asks_acc,
+                authorization,
+            };
+            f(args)
+        }
+    }
+
+    pub struct UpdateLotSizesArgs<'a, 'b: 'a> {
+        pub instruction: &'a UpdateLotSizesInstruction,
+        pub market: &'a mut MarketState,
+        pub req_q: RequestQueue<'a>,
+        pub event_q: EventQueue<'a>,
+        pub bids: &'a Slab,
+        pub asks: &'a Slab,
+        pub authorization: SigningDisableAuthority<'a, 'b>,
+        pub params: Option<&'a mut MarketParams>,
+    }
+    impl<'a, 'b: 'a> UpdateLotSizesArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            instruction: &'a UpdateLotSizesInstruction,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(UpdateLotSizesArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert!(accounts.len() == 6 || accounts.len() == 7)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref req_q_acc,
+                ref event_q_acc,
+                ref bids_acc,
+                ref asks_acc,
+                ref signer_acc,
             ] = array_ref![accounts, 0, 6];
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let req_q = market.load_request_queue_mut(req_q_acc)?;
+            let event_q = market.load_event_queue_mut(event_q_acc)?;
+            let bids = market.load_bids_mut(bids_acc)?;
+            let asks = market.load_asks_mut(asks_acc)?;
+            let authorization = SigningDisableAuthority::new(signer_acc)?;
+            let mut params = match accounts.get(6) {
+                Some(params_acc) => Some(market.load_market_params_mut(params_acc, program_id)?),
+                None if market.options().contains(MarketOption::Params) => {
+                    Err(DexErrorCode::MarketParamsNotProvided)?
+                }
+                None => None,
+            };
+
+            let args = UpdateLotSizesArgs {
+                instruction,
+                market: market.deref_mut(),
+                req_q,
+                event_q,
+                bids: &*bids,
+                asks: &*asks,
+                authorization,
+                params: params.as_mut().map(|params| params.deref_mut()),
+            };
+            f(args)
+        }
+    }
+
+    pub struct UpgradeMarketArgs<'a> {
+        pub market: &'a mut MarketState,
+    }
+    impl<'a> UpgradeMarketArgs<'a> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo],
+            f: impl FnOnce(UpgradeMarketArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 1)?;
+            let mut market = MarketState::load(&accounts[0], program_id)?;
+            f(UpgradeMarketArgs {
+                market: market.deref_mut(),
+            })
+        }
+    }
+
+    pub struct SetFeeScheduleArgs<'a, 'b: 'a> {
+        pub instruction: &'a SetFeeScheduleInstruction,
+        pub market: &'a mut MarketState,
+        pub authorization: SigningDisableAuthority<'a, 'b>,
+    }
+    impl<'a, 'b: 'a> SetFeeScheduleArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            instruction: &'a SetFeeScheduleInstruction,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(SetFeeScheduleArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 2)?;
+            let &[ref market_acc, ref signer_acc] = array_ref![accounts, 0, 2];
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let authorization = SigningDisableAuthority::new(signer_acc)?;
+
+            let args = SetFeeScheduleArgs {
+                instruction,
+                market: market.deref_mut(),
+                authorization,
+            };
+            f(args)
+        }
+    }
+
+    pub struct SetMarketOptionsArgs<'a, 'b: 'a> {
+        pub options: u8,
+        pub market: &'a mut MarketState,
+        pub authorization: SigningDisableAuthority<'a, 'b>,
+    }
+    impl<'a, 'b: 'a> SetMarketOptionsArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            options: u8,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(SetMarketOptionsArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 2)?;
+            let &[ref market_acc, ref signer_acc] = array_ref![accounts, 0, 2];
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let authorization = SigningDisableAuthority::new(signer_acc)?;
+
+            let args = SetMarketOptionsArgs {
+                options,
+                market: market.deref_mut(),
+                authorization,
+            };
+            f(args)
+        }
+    }
+
+    pub struct SetMarketParamsArgs<'a, 'b: 'a> {
+        pub instruction: &'a SetMarketParamsInstruction,
+        pub market: &'a mut MarketState,
+        pub params: &'a mut MarketParams,
+        pub authorization: SigningDisableAuthority<'a, 'b>,
+        pub market_stats: Option<[u64; 4]>,
+    }
+    impl<'a, 'b: 'a> SetMarketParamsArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            instruction: &'a SetMarketParamsInstruction,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(SetMarketParamsArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert!(accounts.len() == 4 || accounts.len() == 5)?;
+            let (fixed_accounts, stats_acc): (&'a [AccountInfo<'b>; 4], &'a [AccountInfo<'b>]) =
+                array_refs![accounts, 4; .. ;];
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref params_acc,
+                ref signer_acc,
+                ref rent_sysvar_acc,
+            ] = fixed_accounts;
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let authorization = SigningDisableAuthority::new(signer_acc)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
+            };
+            // The first params account becomes the market's only one.
+            let mut params = if market.options().contains(MarketOption::Params) {
+                market.load_market_params_mut(params_acc, program_id)?
+            } else {
+                market.init_market_params_mut(params_acc, program_id, rent)?
+            };
+            // Initialized now, so matching instructions can't be left without
+            // a valid account to pass.
+            let market_stats = match stats_acc {
+                &[] => None,
+                &[ref stats_acc] => {
+                    market.load_market_stats_mut(stats_acc, program_id, Some(rent))?;
+                    Some(stats_acc.key.to_aligned_bytes())
+                }
+                _ => check_unreachable!()?,
+            };
+
+            let args = SetMarketParamsArgs {
+                instruction,
+                market: market.deref_mut(),
+                params: params.deref_mut(),
+                authorization,
+                market_stats,
+            };
+            f(args)
+        }
+    }
+
+    pub struct PruneArgs<'a, 'b: 'a> {
+        pub open_orders: &'a mut OpenOrders,
+        pub open_orders_address: [u64; 4],
+        pub req_q: RequestQueue<'a>,
+        pub event_q: EventQueue<'a>,
+        pub order_book_state: OrderBookState<'a>,
+        pub authorization: SignerAccount<'a, 'b>,
+    }
+    impl<'a, 'b: 'a> PruneArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(PruneArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 8)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref params_acc,
+                ref signer_acc,
+                ref open_orders_acc,
+                ref req_q_acc,
+                ref event_q_acc,
+                ref bids_acc,
+                ref asks_acc,
+            ] = array_ref![accounts, 0, 8];
+            let mut market: RefMut<'a, MarketState> = MarketState::load(market_acc, program_id)?;
+            let params = market.load_market_params_mut(params_acc, program_id)?;
+            let authorization = SignerAccount::new(signer_acc)?;
+            check_assert_eq!(
+                authorization.inner().key.to_aligned_bytes(),
+                identity(params.prune_authority)
+            )
+            .map_err(|_| DexErrorCode::WrongSigner)?;
+            let mut open_orders =
+                market.load_orders_mut(open_orders_acc, None, program_id, None)?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            let req_q = market.load_request_queue_mut(req_q_acc)?;
+            let event_q = market.load_event_queue_mut(event_q_acc)?;
+            let mut bids = market.load_bids_mut(bids_acc)?;
+            let mut asks = market.load_asks_mut(asks_acc)?;
+
+            let order_book_state = OrderBookState {
+                bids: bids.deref_mut(),
+                asks: asks.deref_mut(),
+                market_state: market.deref_mut(),
+                self_trade_accounts: &[],
+                stats: None,
+                trader_stats: None,
+                params: None,
+            };
+            let args = PruneArgs {
+                open_orders: open_orders.deref_mut(),
+                open_orders_address,
+                req_q,
+                event_q,
+                order_book_state,
+                authorization,
+            };
+            f(args)
+        }
+    }
+
+    pub struct UpdateReferencePriceArgs<'a> {
+        pub price: u64,
+        pub params: &'a mut MarketParams,
+    }
+    impl<'a> UpdateReferencePriceArgs<'a> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            price: u64,
+            accounts: &'a [AccountInfo],
+            f: impl FnOnce(UpdateReferencePriceArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 3)?;
+            let &[ref market_acc, ref params_acc, ref oracle_acc] = array_ref![accounts, 0, 3];
+            let market = MarketState::load(market_acc, program_id)?;
+            let mut params = market.load_market_params_mut(params_acc, program_id)?;
+            let oracle = SignerAccount::new(oracle_acc)?;
+            check_assert_eq!(
+                oracle.inner().key.to_aligned_bytes(),
+                identity(params.oracle)
+            )
+            .map_err(|_| DexErrorCode::WrongSigner)?;
+
+            let args = UpdateReferencePriceArgs {
+                price,
+                params: params.deref_mut(),
+            };
+            f(args)
+        }
+    }
+
+    pub struct ListMarketArgs<'a, 'b: 'a> {
+        pub program_id: &'a Pubkey,
+        pub market: &'a MarketState,
+        pub lister: SignerAccount<'a, 'b>,
+        pub listing: &'a AccountInfo<'b>,
+        pub nonce: u8,
+        pub system_program: SystemProgram<'a, 'b>,
+        pub rent: Rent,
+    }
+    impl<'a, 'b: 'a> ListMarketArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            nonce: u8,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(ListMarketArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 5)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref lister_acc,
+                ref listing_acc,
+                ref system_program_acc,
+                ref rent_sysvar_acc,
+            ] = array_ref![accounts, 0, 5];
+            let market = MarketState::load(market_acc, program_id)?;
+            market.check_enabled()?;
+            let lister = SignerAccount::new(lister_acc)?;
+            let system_program = SystemProgram::new(system_program_acc)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
+            };
+
+            let coin_mint = identity(market.coin_mint);
+            let pc_mint = identity(market.pc_mint);
+            let nonce_bytes = [nonce];
+            let listing_seeds =
+                gen_market_listing_seeds(bytes_of(&coin_mint), bytes_of(&pc_mint), &nonce_bytes);
+            let listing_address = Pubkey::create_program_address(&listing_seeds, program_id)
+                .map_err(|_| DexErrorCode::WrongMarketListingAccount)?;
+            if listing_address != *listing_acc.key {
+                Err(DexErrorCode::WrongMarketListingAccount)?
+            }
+
+            let args = ListMarketArgs {
+                program_id,
+                market: &market,
+                lister,
+                listing: listing_acc,
+                nonce,
+                system_program,
+                rent,
+            };
+            f(args)
+        }
+    }
+
+    pub struct ReassignMarketListingArgs<'a, 'b: 'a> {
+        pub listing: &'a mut MarketListing,
+        pub market: &'a MarketState,
+        pub authorization: SigningDisableAuthority<'a, 'b>,
+    }
+    impl<'a, 'b: 'a> ReassignMarketListingArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(ReassignMarketListingArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 3)?;
+            let &[ref listing_acc, ref market_acc, ref signer_acc] = array_ref![accounts, 0, 3];
+            let mut listing = MarketListing::load_mut(listing_acc, program_id)?;
+            let market = MarketState::load(market_acc, program_id)?;
+            let authorization = SigningDisableAuthority::new(signer_acc)?;
+
+            let args = ReassignMarketListingArgs {
+                listing: listing.deref_mut(),
+                market: &market,
+                authorization,
+            };
+            f(args)
+        }
+    }
+
+    pub struct InitBlockTradeArgs<'a> {
+        pub instruction: &'a InitBlockTradeInstruction,
+        pub market: &'a MarketState,
+        pub block_trade: &'a mut BlockTrade,
+        pub block_trade_address: [u64; 4],
+        pub bid_open_orders: [u64; 4],
+        pub ask_open_orders: [u64; 4],
+    }
+    impl<'a> InitBlockTradeArgs<'a> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            instruction: &'a InitBlockTradeInstruction,
+            accounts: &'a [AccountInfo],
+            f: impl FnOnce(InitBlockTradeArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 5)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref block_trade_acc,
+                ref bid_open_orders_acc,
+                ref ask_open_orders_acc,
+                ref rent_sysvar_acc,
+            ] = array_ref![accounts, 0, 5];
+            let market = MarketState::load(market_acc, program_id)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
+            };
+            let mut block_trade = market.init_block_trade_mut(block_trade_acc, program_id, rent)?;
+            if bid_open_orders_acc.key == ask_open_orders_acc.key {
+                Err(DexErrorCode::InvalidBlockTrade)?
+            }
+            market.load_orders_mut(bid_open_orders_acc, None, program_id, None)?;
+            market.load_orders_mut(ask_open_orders_acc, None, program_id, None)?;
+
+            let args = InitBlockTradeArgs {
+                instruction,
+                market: &market,
+                block_trade: block_trade.deref_mut(),
+                block_trade_address: block_trade_acc.key.to_aligned_bytes(),
+                bid_open_orders: bid_open_orders_acc.key.to_aligned_bytes(),
+                ask_open_orders: ask_open_orders_acc.key.to_aligned_bytes(),
+            };
+            f(args)
+        }
+    }
+
+    pub struct DepositBlockTradeArgs<'a, 'b: 'a> {
+        pub instruction: &'a DepositBlockTradeInstruction,
+        pub market: &'a mut MarketState,
+        pub block_trade: &'a mut BlockTrade,
+        pub side: Side,
+        pub open_orders: &'a mut OpenOrders,
+        pub payer: TokenAccount<'a, 'b>,
+        pub owner: SignerAccount<'a, 'b>,
+        pub coin_vault: CoinVault<'a, 'b>,
+        pub pc_vault: PcVault<'a, 'b>,
+        pub spl_token_program: SplTokenProgram<'a, 'b>,
+    }
+    impl<'a, 'b: 'a> DepositBlockTradeArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            instruction: &'a DepositBlockTradeInstruction,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(DepositBlockTradeArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 8)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref block_trade_acc,
+                ref open_orders_acc,
+                ref payer_acc,
+                ref owner_acc,
+                ref coin_vault_acc,
+                ref pc_vault_acc,
+                ref spl_token_program_acc,
+            ] = array_ref![accounts, 0, 8];
+            let mut market = MarketState::load(market_acc, program_id)?;
+            market.check_enabled()?;
+            let mut block_trade = market.load_block_trade_mut(block_trade_acc, program_id)?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            let side = if open_orders_address == block_trade.open_orders(Side::Bid) {
+                Side::Bid
+            } else if open_orders_address == block_trade.open_orders(Side::Ask) {
+                Side::Ask
+            } else {
+                Err(DexErrorCode::WrongOrdersAccount)?
+            };
+            let owner = SignerAccount::new(owner_acc)?;
+            let mut open_orders =
+                market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
+
+            let payer = TokenAccount::new(payer_acc)?;
+            match side {
+                Side::Bid => market.check_pc_payer(payer).or(check_unreachable!())?,
+                Side::Ask => market.check_coin_payer(payer).or(check_unreachable!())?,
+            };
+            let coin_vault = CoinVault::from_account(coin_vault_acc, &market)?;
+            let pc_vault = PcVault::from_account(pc_vault_acc, &market)?;
+            let spl_token_program = SplTokenProgram::new(spl_token_program_acc)?;
+            let args = DepositBlockTradeArgs {
+                instruction,
+                market: market.deref_mut(),
+                block_trade: block_trade.deref_mut(),
+                side,
+                open_orders: open_orders.deref_mut(),
+                payer,
+                owner,
+                coin_vault,
+                pc_vault,
+                spl_token_program,
+            };
+            f(args)
+        }
+    }
+
+    pub struct SettleBlockTradeArgs<'a> {
+        pub market: &'a MarketState,
+        pub block_trade: &'a mut BlockTrade,
+        pub bid_open_orders: &'a OpenOrders,
+        pub ask_open_orders: &'a OpenOrders,
+        pub event_q: EventQueue<'a>,
+        pub bids: &'a Slab,
+        pub asks: &'a Slab,
+    }
+    impl<'a> SettleBlockTradeArgs<'a> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo],
+            f: impl FnOnce(SettleBlockTradeArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 7)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref block_trade_acc,
+                ref bid_open_orders_acc,
+                ref ask_open_orders_acc,
+                ref event_q_acc,
+                ref bids_acc,
+                ref asks_acc,
+            ] = array_ref![accounts, 0, 7];
+            let market = MarketState::load(market_acc, program_id)?;
+            market.check_enabled()?;
+            let mut block_trade = market.load_block_trade_mut(block_trade_acc, program_id)?;
+            let (bid_open_orders, ask_open_orders) = market.load_block_trade_orders_mut(
+                &block_trade,
+                bid_open_orders_acc,
+                ask_open_orders_acc,
+                program_id,
+            )?;
+            let event_q = market.load_event_queue_mut(event_q_acc)?;
+            let bids = market.load_bids_mut(bids_acc)?;
+            let asks = market.load_asks_mut(asks_acc)?;
+
+            let args = SettleBlockTradeArgs {
+                market: &market,
+                block_trade: block_trade.deref_mut(),
+                bid_open_orders: &bid_open_orders,
+                ask_open_orders: &ask_open_orders,
+                event_q,
+                bids: &bids,
+                asks: &asks,
+            };
+            f(args)
+        }
+    }
+
+    pub struct CancelBlockTradeArgs<'a, 'b: 'a> {
+        pub market: &'a MarketState,
+        pub block_trade: &'a mut BlockTrade,
+        pub bid_open_orders: &'a mut OpenOrders,
+        pub ask_open_orders: &'a mut OpenOrders,
+        pub signer: SignerAccount<'a, 'b>,
+    }
+    impl<'a, 'b: 'a> CancelBlockTradeArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(CancelBlockTradeArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 5)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref block_trade_acc,
+                ref bid_open_orders_acc,
+                ref ask_open_orders_acc,
+                ref signer_acc,
+            ] = array_ref![accounts, 0, 5];
+            let market = MarketState::load(market_acc, program_id)?;
+            let mut block_trade = market.load_block_trade_mut(block_trade_acc, program_id)?;
+            let (mut bid_open_orders, mut ask_open_orders) = market.load_block_trade_orders_mut(
+                &block_trade,
+                bid_open_orders_acc,
+                ask_open_orders_acc,
+                program_id,
+            )?;
+            let signer = SignerAccount::new(signer_acc)?;
+            let signer_key = signer.inner().key.to_aligned_bytes();
+            if identity(bid_open_orders.owner) != signer_key
+                && identity(ask_open_orders.owner) != signer_key
+            {
+                Err(DexErrorCode::WrongOrdersAccount)?
+            }
+
+            let args = CancelBlockTradeArgs {
+                market: &market,
+                block_trade: block_trade.deref_mut(),
+                bid_open_orders: bid_open_orders.deref_mut(),
+                ask_open_orders: ask_open_orders.deref_mut(),
+                signer,
+            };
+            f(args)
+        }
+    }
+
+    pub struct NewTriggerOrderArgs<'a, 'b: 'a> {
+        pub instruction: &'a NewTriggerOrderInstruction,
+        pub market: &'a mut MarketState,
+        pub trigger_order: &'a mut TriggerOrder,
+        pub open_orders_address: [u64; 4],
+        pub owner: SignerAccount<'a, 'b>,
+        pub payer: TokenAccount<'a, 'b>,
+        pub coin_vault: CoinVault<'a, 'b>,
+        pub pc_vault: PcVault<'a, 'b>,
+        pub spl_token_program: SplTokenProgram<'a, 'b>,
+        pub params: &'a mut MarketParams,
+    }
+    impl<'a, 'b: 'a> NewTriggerOrderArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            instruction: &'a NewTriggerOrderInstruction,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(NewTriggerOrderArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 10)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref trigger_order_acc,
+                ref open_orders_acc,
+                ref payer_acc,
+                ref owner_acc,
+                ref coin_vault_acc,
+                ref pc_vault_acc,
+                ref spl_token_program_acc,
+                ref rent_sysvar_acc,
+                ref params_acc,
+            ] = array_ref![accounts, 0, 10];
+            let mut market = MarketState::load(market_acc, program_id)?;
+            // The params count the market's trigger orders, see
+            // `process_update_lot_sizes`.
+            if !market.options().contains(MarketOption::Params) {
+                Err(DexErrorCode::MarketParamsNotProvided)?
+            }
+            let mut params = market.load_market_params_mut(params_acc, program_id)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
+            };
+            let owner = SignerAccount::new(owner_acc)?;
+            market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, Some(rent))?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            let mut trigger_order =
+                market.init_trigger_order_mut(trigger_order_acc, program_id, rent)?;
+
+            let payer = TokenAccount::new(payer_acc)?;
+            match instruction.order.side {
+                Side::Bid => market.check_pc_payer(payer).or(check_unreachable!())?,
+                Side::Ask => market.check_coin_payer(payer).or(check_unreachable!())?,
+            };
+            let coin_vault = CoinVault::from_account(coin_vault_acc, &market)?;
+            let pc_vault = PcVault::from_account(pc_vault_acc, &market)?;
+            market.check_enabled()?;
+            let spl_token_program = SplTokenProgram::new(spl_token_program_acc)?;
+            let args = NewTriggerOrderArgs {
+                instruction,
+                market: market.deref_mut(),
+                trigger_order: trigger_order.deref_mut(),
+                open_orders_address,
+                owner,
+                payer,
+                coin_vault,
+                pc_vault,
+                spl_token_program,
+                params: params.deref_mut(),
+            };
+            f(args)
+        }
+    }
+
+    pub struct ExecuteTriggerOrderArgs<'a, 'b: 'a> {
+        pub market: &'a mut MarketState,
+        pub trigger_order: &'a mut TriggerOrder,
+        pub open_orders: &'a mut OpenOrders,
+        pub open_orders_address: [u64; 4],
+        pub req_q: RequestQueue<'a>,
+        pub event_q: EventQueue<'a>,
+        pub bids: &'a mut Slab,
+        pub asks: &'a mut Slab,
+        pub coin_vault: CoinVault<'a, 'b>,
+        pub pc_vault: PcVault<'a, 'b>,
+        pub tip_wallet: TokenAccount<'a, 'b>,
+        pub vault_signer: VaultSigner<'a, 'b>,
+        pub spl_token_program: SplTokenProgram<'a, 'b>,
+        pub params: &'a mut MarketParams,
+        pub stats: Option<(&'a mut MarketStats, u64)>,
+    }
+    impl<'a, 'b: 'a> ExecuteTriggerOrderArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(ExecuteTriggerOrderArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert!(accounts.len() == 13 || accounts.len() == 15)?;
+            let (fixed_accounts, stats_accounts): (
+                &'a [AccountInfo<'b>; 13],
+                &'a [AccountInfo<'b>],
+            ) = array_refs![accounts, 13; .. ;];
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref trigger_order_acc,
+                ref open_orders_acc,
+                ref req_q_acc,
+                ref event_q_acc,
+                ref bids_acc,
+                ref asks_acc,
+                ref coin_vault_acc,
+                ref pc_vault_acc,
+                ref tip_wallet_acc,
+                ref vault_signer_acc,
+                ref spl_token_program_acc,
+                ref params_acc,
+            ] = fixed_accounts;
+            let stats_accounts = match stats_accounts {
+                &[] => None,
+                &[ref stats_acc, ref clock_acc] => Some((stats_acc, clock_acc)),
+                _ => check_unreachable!()?,
+            };
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let mut trigger_order = market.load_trigger_order_mut(trigger_order_acc, program_id)?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            check_assert_eq!(identity(trigger_order.open_orders), open_orders_address)
+                .map_err(|_| DexErrorCode::WrongOrdersAccount)?;
+            let mut open_orders =
+                market.load_orders_mut(open_orders_acc, None, program_id, None)?;
+            let req_q = market.load_request_queue_mut(req_q_acc)?;
+            let event_q = market.load_event_queue_mut(event_q_acc)?;
+            let mut bids = market.load_bids_mut(bids_acc)?;
+            let mut asks = market.load_asks_mut(asks_acc)?;
+
+            let coin_vault = CoinVault::from_account(coin_vault_acc, &market)?;
+            let pc_vault = PcVault::from_account(pc_vault_acc, &market)?;
+            let tip_wallet = TokenAccount::new(tip_wallet_acc)?;
+            match trigger_order.side()? {
+                Side::Bid => market.check_pc_payer(tip_wallet)?,
+                Side::Ask => market.check_coin_payer(tip_wallet)?,
+            };
+            let vault_signer = VaultSigner::new(vault_signer_acc, &market, program_id)?;
+            market.check_enabled()?;
+            let spl_token_program = SplTokenProgram::new(spl_token_program_acc)?;
+            let mut params = market.load_market_params_mut(params_acc, program_id)?;
+            params.check_market_stats(stats_accounts.map(|(stats_acc, _)| stats_acc))?;
+            let mut stats = match stats_accounts {
+                Some((stats_acc, clock_acc)) => {
+                    let clock = {
+                        let clock_sysvar = ClockSysvarAccount::new(clock_acc)?;
+                        Clock::from_account_info(clock_sysvar.inner()).or(check_unreachable!())?
+                    };
+                    Some((
+                        market.load_market_stats_mut(stats_acc, program_id, None)?,
+                        clock.slot,
+                    ))
+                }
+                None => None,
+            };
+            let args = ExecuteTriggerOrderArgs {
+                market: market.deref_mut(),
+                trigger_order: trigger_order.deref_mut(),
+                open_orders: open_orders.deref_mut(),
+                open_orders_address,
+                req_q,
+                event_q,
+                bids: bids.deref_mut(),
+                asks: asks.deref_mut(),
+                coin_vault,
+                pc_vault,
+                tip_wallet,
+                vault_signer,
+                spl_token_program,
+                params: params.deref_mut(),
+                stats: stats
+                    .as_mut()
+                    .map(|(stats, slot)| (stats.deref_mut(), *slot)),
+            };
+            f(args)
+        }
+    }
+
+    pub struct CancelTriggerOrderArgs<'a, 'b: 'a> {
+        pub market: &'a mut MarketState,
+        pub trigger_order: &'a mut TriggerOrder,
+        pub owner: SignerAccount<'a, 'b>,
+        pub refund_wallet: TokenAccount<'a, 'b>,
+        pub coin_vault: CoinVault<'a, 'b>,
+        pub pc_vault: PcVault<'a, 'b>,
+        pub vault_signer: VaultSigner<'a, 'b>,
+        pub spl_token_program: SplTokenProgram<'a, 'b>,
+        pub params: &'a mut MarketParams,
+    }
+    impl<'a, 'b: 'a> CancelTriggerOrderArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(CancelTriggerOrderArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 9)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref trigger_order_acc,
+                ref owner_acc,
+                ref refund_wallet_acc,
+                ref coin_vault_acc,
+                ref pc_vault_acc,
+                ref vault_signer_acc,
+                ref spl_token_program_acc,
+                ref params_acc,
+            ] = array_ref![accounts, 0, 9];
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let mut trigger_order = market.load_trigger_order_mut(trigger_order_acc, program_id)?;
+            let owner = SignerAccount::new(owner_acc)?;
+            check_assert_eq!(
+                identity(trigger_order.owner),
+                owner.inner().key.to_aligned_bytes()
+            )
+            .map_err(|_| DexErrorCode::WrongTriggerOrderAccount)?;
+
+            let refund_wallet = TokenAccount::new(refund_wallet_acc)?;
+            match trigger_order.side()? {
+                Side::Bid => market.check_pc_payer(refund_wallet)?,
+                Side::Ask => market.check_coin_payer(refund_wallet)?,
+            };
+            let coin_vault = CoinVault::from_account(coin_vault_acc, &market)?;
+            let pc_vault = PcVault::from_account(pc_vault_acc, &market)?;
+            let vault_signer = VaultSigner::new(vault_signer_acc, &market, program_id)?;
+            let spl_token_program = SplTokenProgram::new(spl_token_program_acc)?;
+            let mut params = market.load_market_params_mut(params_acc, program_id)?;
+            let args = CancelTriggerOrderArgs {
+                market: market.deref_mut(),
+                trigger_order: trigger_order.deref_mut(),
+                owner,
+                refund_wallet,
+                coin_vault,
+                pc_vault,
+                vault_signer,
+                spl_token_program,
+                params: params.deref_mut(),
+            };
+            f(args)
+        }
+    }
+
+    pub struct SetOpenOrdersDelegateArgs<'a> {
+        pub open_orders_address: [u64; 4],
+        pub open_orders_delegate: &'a mut OpenOrdersDelegate,
+        pub delegate: [u64; 4],
+    }
+    impl<'a> SetOpenOrdersDelegateArgs<'a> {
+        pub fn with_parsed_args<'b, T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(SetOpenOrdersDelegateArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 6)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref open_orders_acc,
+                ref owner_acc,
+                ref open_orders_delegate_acc,
+                ref delegate_acc,
+                ref rent_sysvar_acc,
+            ] = array_ref![accounts, 0, 6];
+            let market = MarketState::load(market_acc, program_id)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
+            };
+            let owner = SignerAccount::new(owner_acc)?;
+            market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            let mut open_orders_delegate = market.load_open_orders_delegate_mut(
+                open_orders_delegate_acc,
+                program_id,
+                Some(rent),
+            )?;
+            // A freshly initialized account has no OpenOrders yet.
+            let bound_open_orders = identity(open_orders_delegate.open_orders);
+            if bound_open_orders != [0; 4] {
+                check_assert_eq!(bound_open_orders, open_orders_address)
+                    .map_err(|_| DexErrorCode::WrongDelegateAccount)?;
+            }
+            let args = SetOpenOrdersDelegateArgs {
+                open_orders_address,
+                open_orders_delegate: open_orders_delegate.deref_mut(),
+                delegate: delegate_acc.key.to_aligned_bytes(),
+            };
+            f(args)
+        }
+    }
+
+    pub struct SetSettleDestinationArgs<'a> {
+        pub open_orders_address: [u64; 4],
+        pub settle_destination: &'a mut SettleDestination,
+        pub coin_wallet: [u64; 4],
+        pub pc_wallet: [u64; 4],
+    }
+    impl<'a> SetSettleDestinationArgs<'a> {
+        pub fn with_parsed_args<'b, T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(SetSettleDestinationArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 7)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref open_orders_acc,
+                ref owner_acc,
+                ref settle_destination_acc,
+                ref coin_wallet_acc,
+                ref pc_wallet_acc,
+                ref rent_sysvar_acc,
+            ] = array_ref![accounts, 0, 7];
+            let market = MarketState::load(market_acc, program_id)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
+            };
+            let owner = SignerAccount::new(owner_acc)?;
+            market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
+            let open_orders_address = open_orders_acc.key.to_aligned_bytes();
+            CoinWallet::from_account(coin_wallet_acc, &market)?;
+            PcWallet::from_account(pc_wallet_acc, &market)?;
+            let mut settle_destination = market.load_settle_destination_mut(
+                settle_destination_acc,
+                program_id,
+                Some(rent),
+            )?;
+            // A freshly initialized account has no OpenOrders yet.
+            let bound_open_orders = identity(settle_destination.open_orders);
+            if bound_open_orders != [0; 4] {
+                check_assert_eq!(bound_open_orders, open_orders_address)
+                    .map_err(|_| DexErrorCode::WrongSettleDestinationAccount)?;
+            }
+            let args = SetSettleDestinationArgs {
+                open_orders_address,
+                settle_destination: settle_destination.deref_mut(),
+                coin_wallet: coin_wallet_acc.key.to_aligned_bytes(),
+                pc_wallet: pc_wallet_acc.key.to_aligned_bytes(),
+            };
+            f(args)
+        }
+    }
+
+    pub struct SetFeeDestinationsArgs<'a> {
+        pub instruction: &'a SetFeeDestinationsInstruction,
+        pub fee_destinations: &'a mut FeeDestinations,
+        pub wallets: Vec<[u64; 4]>,
+    }
+    impl<'a> SetFeeDestinationsArgs<'a> {
+        pub fn with_parsed_args<'b, T>(
+            program_id: &'a Pubkey,
+            instruction: &'a SetFeeDestinationsInstruction,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(SetFeeDestinationsArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert!(accounts.len() > 4)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref sweep_authority_acc,
+                ref fee_destinations_acc,
+                ref rent_sysvar_acc,
+            ] = array_ref![accounts, 0, 4];
+            let wallet_accs = &accounts[4..];
+            let market = MarketState::load(market_acc, program_id)?;
+            SigningFeeSweeper::new(sweep_authority_acc)?;
+            let rent = {
+                let rent_sysvar = RentSysvarAccount::new(rent_sysvar_acc)?;
+                Rent::from_account_info(rent_sysvar.inner()).or(check_unreachable!())?
+            };
+            if wallet_accs.len() != instruction.weights.len() {
+                Err(DexErrorCode::InvalidFeeDestinations)?
+            }
+            let wallets = wallet_accs
+                .iter()
+                .map(|wallet_acc| {
+                    PcWallet::from_account(wallet_acc, &market)?;
+                    Ok(wallet_acc.key.to_aligned_bytes())
+                })
+                .collect::<DexResult<Vec<_>>>()?;
+            let mut fee_destinations =
+                market.load_fee_destinations_mut(fee_destinations_acc, program_id, Some(rent))?;
+            let args = SetFeeDestinationsArgs {
+                instruction,
+                fee_destinations: fee_destinations.deref_mut(),
+                wallets,
+            };
+            f(args)
+        }
+    }
+
+    pub struct ClearSettleDestinationArgs<'a> {
+        pub settle_destination: &'a mut SettleDestination,
+    }
+    impl<'a> ClearSettleDestinationArgs<'a> {
+        pub fn with_parsed_args<'b, T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(ClearSettleDestinationArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 4)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref open_orders_acc,
+                ref owner_acc,
+                ref settle_destination_acc,
+            ] = array_ref![accounts, 0, 4];
+            let market = MarketState::load(market_acc, program_id)?;
+            let owner = SignerAccount::new(owner_acc)?;
+            market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
+            let mut settle_destination =
+                market.load_settle_destination_mut(settle_destination_acc, program_id, None)?;
+            check_assert_eq!(
+                identity(settle_destination.open_orders),
+                open_orders_acc.key.to_aligned_bytes()
+            )
+            .map_err(|_| DexErrorCode::WrongSettleDestinationAccount)?;
+            let args = ClearSettleDestinationArgs {
+                settle_destination: settle_destination.deref_mut(),
+            };
+            f(args)
+        }
+    }
+
+    pub struct RevokeOpenOrdersDelegateArgs<'a> {
+        pub open_orders_delegate: &'a mut OpenOrdersDelegate,
+    }
+    impl<'a> RevokeOpenOrdersDelegateArgs<'a> {
+        pub fn with_parsed_args<'b, T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(RevokeOpenOrdersDelegateArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert_eq!(accounts.len(), 4)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref open_orders_acc,
+                ref owner_acc,
+                ref open_orders_delegate_acc,
+            ] = array_ref![accounts, 0, 4];
+            let market = MarketState::load(market_acc, program_id)?;
+            let owner = SignerAccount::new(owner_acc)?;
+            market.load_orders_mut(open_orders_acc, Some(owner.inner()), program_id, None)?;
+            let mut open_orders_delegate =
+                market.load_open_orders_delegate_mut(open_orders_delegate_acc, program_id, None)?;
+            check_assert_eq!(
+                identity(open_orders_delegate.open_orders),
+                open_orders_acc.key.to_aligned_bytes()
+            )
+            .map_err(|_| DexErrorCode::WrongDelegateAccount)?;
+            let args = RevokeOpenOrdersDelegateArgs {
+                open_orders_delegate: open_orders_delegate.deref_mut(),
+            };
+            f(args)
+        }
+    }
+
+    pub struct SweepFeesArgs<'a, 'b: 'a> {
+        pub market: &'a mut MarketState,
+        pub pc_vault: PcVault<'a, 'b>,
+        pub fee_receiver: PcWallet<'a, 'b>,
+        pub vault_signer: VaultSigner<'a, 'b>,
+        pub spl_token_program: SplTokenProgram<'a, 'b>,
+        pub authorization: SigningFeeSweeper<'a, 'b>,
+        // The registered split and its wallets, if one was passed.
+        pub fee_destinations: Option<(FeeDestinations, Vec<PcWallet<'a, 'b>>)>,
+    }
+    impl<'a, 'b: 'a> SweepFeesArgs<'a, 'b> {
+        pub fn with_parsed_args<T>(
+            program_id: &'a Pubkey,
+            accounts: &'a [AccountInfo<'b>],
+            f: impl FnOnce(SweepFeesArgs) -> DexResult<T>,
+        ) -> DexResult<T> {
+            check_assert!(accounts.len() == 6 || accounts.len() > 7)?;
+            #[rustfmt::skip]
+            let &[
+                ref market_acc,
+                ref pc_vault_acc,
+                ref sweep_authority_acc,
+                ref pc_wallet_acc,
+                ref vault_signer_acc,
+                ref spl_token_program
+            ] = array_ref![accounts, 0, 6];
+
+            let mut market = MarketState::load(market_acc, program_id)?;
+            let pc_vault = PcVault::from_account(pc_vault_acc, &market)?;
+            let fee_receiver = PcWallet::from_account(pc_wallet_acc, &market)?;
+            let vault_signer = VaultSigner::new(vault_signer_acc, &market, program_id)?;
+            let spl_token_program = SplTokenProgram::new(spl_token_program)?;
+            let authorization = SigningFeeSweeper::new(sweep_authority_acc)?;
+
+            let fee_destinations = match accounts[6..].split_first() {
+                None => None,
+                Some((destinations_acc, wallet_accs)) => {
+                    let destinations =
+                        *market.load_fee_destinations_mut(destinations_acc, program_id, None)?;
+                    let registered = destinations.wallets();
+                    check_assert_eq!(wallet_accs.len(), registered.len())
+                        .map_err(|_| DexErrorCode::WrongFeeDestinationsAccount)?;
+                    let wallets = wallet_accs
+                        .iter()
+                        .zip(registered.iter())
+                        .map(|(wallet_acc, &registered)| {
+                            check_assert_eq!(wallet_acc.key.to_aligned_bytes(), registered)
+                                .map_err(|_| DexErrorCode::WrongFeeDestinationsAccount)?;
+                            PcWallet::from_account(wallet_acc, &market)
+                        })
+                        .collect::<DexResult<Vec<_>>>()?;
+                    Some((destinations, wallets))
+                }
+            };
+
+            let args = SweepFeesArgs {
+                market: market.deref_mut(),
+                pc_vault,
+                fee_receiver,
+                vault_signer,
+                spl_token_program,
+                authorization,
+                fee_destinations,
+            };
+            f(args)
+        }
+    }
+}
+
+#[inline]
+fn remove_slop<T: Pod>(bytes: &[u8]) -> &[T] {
+    let slop = bytes.len() % size_of::<T>();
+    let new_len = bytes.len() - slop;
+    cast_slice(&bytes[..new_len])
+}
+
+#[inline]
+fn remove_slop_mut<T: Pod>(bytes: &mut [u8]) -> &mut [T] {
+    let slop = bytes.len() % size_of::<T>();
+    let new_len = bytes.len() - slop;
+    cast_slice_mut(&mut bytes[..new_len])
+}
+
+#[cfg_attr(not(feature = "program"), allow(unused))]
+impl State {
+    #[cfg(feature = "program")]
+    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> DexResult {
+        let instruction = MarketInstruction::unpack(input).ok_or(ProgramError::InvalidArgument)?;
+        compute_checkpoint!("unpack");
+        match instruction {
+            MarketInstruction::InitializeMarket(ref inner) => Self::process_initialize_market(
+                account_parser::InitializeMarketArgs::new(program_id, inner, accounts)?,
+            )?,
+            MarketInstruction::NewOrder(inner) => {
+                let new_order_v2 = inner.add_self_trade_behavior(SelfTradeBehavior::DecrementTake);
+                account_parser::NewOrderArgs::with_parsed_args(
+                    program_id,
+                    &new_order_v2,
+                    accounts,
+                    Self::process_new_order,
+                )?
+            }
+            MarketInstruction::NewOrderV2(ref inner) => {
+                account_parser::NewOrderArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_new_order,
+                )?
+            }
+            MarketInstruction::MatchOrders(limit) => {
+                account_parser::MatchOrdersArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    limit,
+                    Self::process_match_orders,
+                )?
+            }
+            MarketInstruction::ConsumeEvents(limit) => {
+                account_parser::ConsumeEventsArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    limit,
+                    None,
+                    Self::process_consume_events,
+                )?
+            }
+            MarketInstruction::ConsumeEventsFrom(ConsumeEventsFromInstruction {
+                start_seq_num,
+                limit,
+            }) => account_parser::ConsumeEventsArgs::with_parsed_args(
+                program_id,
+                accounts,
+                limit,
+                Some(start_seq_num),
+                Self::process_consume_events,
+            )?,
+            MarketInstruction::CancelOrder(ref inner) => {
+                account_parser::CancelOrderArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    inner,
+                    Self::process_cancel_order,
+                )?
+            }
+            MarketInstruction::SettleFunds => account_parser::SettleFundsArgs::with_parsed_args(
+                program_id,
+                accounts,
+                Self::process_settle_funds,
+            )?,
+            MarketInstruction::CancelOrderByClientId(client_id) => {
+                account_parser::CancelOrderByClientIdArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    client_id,
+                    Self::process_cancel_order_by_client_id,
+                )?
+            }
+            MarketInstruction::DisableMarket => {
+                account_parser::DisableMarketArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_disable_market,
+                )?
+            }
+            MarketInstruction::SweepFees => account_parser::SweepFeesArgs::with_parsed_args(
+                program_id,
+                accounts,
+                Self::process_sweep_fees,
+            )?,
+            MarketInstruction::NewOrderV3(ref inner) => {
+                account_parser::NewOrderV3Args::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_new_order_v3,
+                )?
+            }
+            MarketInstruction::CancelOrderV2(ref inner) => {
+                account_parser::CancelOrderV2Args::with_parsed_args(program_id, accounts, |args| {
+                    Self::process_cancel_order_v2(args, inner)
+                })?
+            }
+            MarketInstruction::CancelOrderByClientIdV2(client_id) => {
+                account_parser::CancelOrderV2Args::with_parsed_args(program_id, accounts, |args| {
+                    Self::process_cancel_order_by_client_id_v2(args, client_id)
+                })?
+            }
+            MarketInstruction::ResizeMarketAccounts => {
+                account_parser::ResizeMarketAccountsArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_resize_market_accounts,
+                )?
+            }
+            MarketInstruction::UpdateLotSizes(ref inner) => {
+                account_parser::UpdateLotSizesArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_update_lot_sizes,
+                )?
+            }
+            MarketInstruction::NewTriggerOrder(ref inner) => {
+                account_parser::NewTriggerOrderArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_new_trigger_order,
+                )?
+            }
+            MarketInstruction::ExecuteTriggerOrder => {
+                account_parser::ExecuteTriggerOrderArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_execute_trigger_order,
+                )?
+            }
+            MarketInstruction::CancelTriggerOrder => {
+                account_parser::CancelTriggerOrderArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_cancel_trigger_order,
+                )?
+            }
+            MarketInstruction::SetOpenOrdersDelegate => {
+                account_parser::SetOpenOrdersDelegateArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_set_open_orders_delegate,
+                )?
+            }
+            MarketInstruction::RevokeOpenOrdersDelegate => {
+                account_parser::RevokeOpenOrdersDelegateArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_revoke_open_orders_delegate,
+                )?
+            }
+            MarketInstruction::SetSettleDestination => {
+                account_parser::SetSettleDestinationArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_set_settle_destination,
+                )?
+            }
+            MarketInstruction::ClearSettleDestination => {
+                account_parser::ClearSettleDestinationArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_clear_settle_destination,
+                )?
+            }
+            MarketInstruction::SetFeeDestinations(ref inner) => {
+                account_parser::SetFeeDestinationsArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_set_fee_destinations,
+                )?
+            }
+            MarketInstruction::SetFeeSchedule(ref inner) => {
+                account_parser::SetFeeScheduleArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_set_fee_schedule,
+                )?
+            }
+            MarketInstruction::UpgradeMarket => {
+                account_parser::UpgradeMarketArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_upgrade_market,
+                )?
+            }
+            MarketInstruction::NewOrderBatch(ref inner) => {
+                account_parser::NewOrderBatchArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_new_order_batch,
+                )?
+            }
+            MarketInstruction::MassQuote(ref inner) => {
+                account_parser::NewOrderBatchArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_mass_quote,
+                )?
+            }
+            MarketInstruction::ListMarket(nonce) => {
+                account_parser::ListMarketArgs::with_parsed_args(
+                    program_id,
+                    nonce,
+                    accounts,
+                    Self::process_list_market,
+                )?
+            }
+            MarketInstruction::ReassignMarketListing => {
+                account_parser::ReassignMarketListingArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_reassign_market_listing,
+                )?
+            }
+            MarketInstruction::SetMarketOptions(options) => {
+                account_parser::SetMarketOptionsArgs::with_parsed_args(
+                    program_id,
+                    options,
+                    accounts,
+                    Self::process_set_market_options,
+                )?
+            }
+            MarketInstruction::InitBlockTrade(ref inner) => {
+                account_parser::InitBlockTradeArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_init_block_trade,
+                )?
+            }
+            MarketInstruction::DepositBlockTrade(ref inner) => {
+                account_parser::DepositBlockTradeArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_deposit_block_trade,
+                )?
+            }
+            MarketInstruction::SettleBlockTrade => {
+                account_parser::SettleBlockTradeArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_settle_block_trade,
+                )?
+            }
+            MarketInstruction::CancelBlockTrade => {
+                account_parser::CancelBlockTradeArgs::with_parsed_args(
+                    program_id,
+                    accounts,
+                    Self::process_cancel_block_trade,
+                )?
+            }
+            MarketInstruction::SetMarketParams(ref inner) => {
+                account_parser::SetMarketParamsArgs::with_parsed_args(
+                    program_id,
+                    inner,
+                    accounts,
+                    Self::process_set_market_params,
+                )?
+            }
+            MarketInstruction::UpdateReferencePrice(price) => {
+                account_parser::UpdateReferencePriceArgs::with_parsed_args(
+                    program_id,
+                    price,
+                    accounts,
+                    Self::process_update_reference_price,
+                )?
+            }
+            MarketInstruction::Prune => account_parser::PruneArgs::with_parsed_args(
+                program_id,
+                accounts,
+                Self::process_prune,
+            )?,
+            MarketInstruction::NewOrderV4(ref inner) => {
+                account_parser::NewOrderV3Args::with_parsed_args(
+                    program_id,
+                    &inner.order,
+                    accounts,
+                    |args| Self::process_new_order_v4(args, inner.cancel_if_through_reference_bps),
+                )?
+            }
+        };
+        compute_checkpoint!("process");
+        Ok(())
+    }
+
+    #[cfg(feature = "program")]
+    fn process_settle_funds(args: account_parser::SettleFundsArgs) -> DexResult {
+        let account_parser::SettleFundsArgs {
+            market,
+            mut open_orders,
+            coin_vault,
+            pc_vault,
+            coin_wallet,
+            pc_wallet,
+            vault_signer,
+            spl_token_program,
+            referrer,
+        } = args;
+
+        if !open_orders.has_resting_orders() {
+            open_orders.release_dust()?;
+        }
+
+        let native_coin_amount = open_orders.native_coin_free;
+        let native_pc_amount = open_orders.native_pc_free;
+
+        market.coin_deposits_total -= native_coin_amount;
+        market.pc_deposits_total -= native_pc_amount;
+
+        open_orders.native_coin_free = 0;
+        open_orders.native_pc_free = 0;
+
+        open_orders.native_coin_total = open_orders
+            .native_coin_total
+            .checked_sub(native_coin_amount)
+            .unwrap();
+        open_orders.native_pc_total = open_orders
+            .native_pc_total
+            .checked_sub(native_pc_amount)
+            .unwrap();
+
+        let token_infos: [(
+            u64,
+            account_parser::TokenAccount,
+            account_parser::TokenAccount,
+        ); 2] = [
+            (
+                native_coin_amount,
+                coin_wallet.token_account(),
+                coin_vault.token_account(),
+            ),
+            (
+                native_pc_amount,
+                pc_wallet.token_account(),
+                pc_vault.token_account(),
+            ),
+        ];
+
+        let nonce = market.vault_signer_nonce;
+        let market_pubkey = market.pubkey();
+        let vault_signer_seeds = gen_vault_signer_seeds(&nonce, &market_pubkey);
+
+        for &(token_amount, wallet_account, vault) in token_infos.iter() {
+            send_from_vault(
+                token_amount,
+                wallet_account,
+                vault,
+                spl_token_program,
+                vault_signer,
+                &vault_signer_seeds,
+            )?;
+        }
+
+        match referrer {
+            Some(referrer_pc_wallet) if open_orders.referrer_rebates_accrued > 0 => {
+                send_from_vault(
+                    open_orders.referrer_rebates_accrued,
+                    referrer_pc_wallet.token_account(),
+                    pc_vault.token_account(),
+                    spl_token_program,
+                    vault_signer,
+                    &vault_signer_seeds,
+                )?;
+            }
+            _ => {
+                market.pc_fees_accrued += open_orders.referrer_rebates_accrued;
+            }
+        };
+        market.referrer_rebates_accrued -= open_orders.referrer_rebates_accrued;
+        open_orders.referrer_rebates_accrued = 0;
+
+        Ok(())
+    }
+
+    fn process_cancel_order_by_client_id(
+        args: account_parser::CancelOrderByClientIdArgs,
+    ) -> DexResult {
+        let account_parser::CancelOrderByClientIdArgs {
+            client_order_id,
+            open_orders,
+            open_orders_address,
+            mut req_q,
+            orders_owner: _,
+        } = args;
+        let mut slot = None;
+        for i in 0..128 {
+            if open_orders.client_order_ids[i] == client_order_id.get()
+                && !open_orders.slot_is_free(i as u8)
+            {
+                slot = Some(i);
+                break;
+            }
+        }
+        let expected_open_orders_slot = slot.ok_or(DexErrorCode::ClientIdNotFound)? as u8;
+        let side = open_orders
+            .slot_side(expected_open_orders_slot)
+            .ok_or(DexErrorCode::ClientIdNotFound)?;
+        let order_id = open_orders.orders[expected_open_orders_slot as usize];
+        let request = Request::new(RequestView::CancelOrder {
+            cancel_id: req_q.gen_seq_num(),
+            expected_owner: open_orders_address,
+            expected_owner_slot: expected_open_orders_slot,
+            order_id,
+            side,
+            client_order_id: Some(client_order_id),
+        });
+        req_q
+            .push_back(request)
+            .map_err(|_| DexErrorCode::RequestQueueFull)?;
+        Ok(())
+    }
+
+    fn process_cancel_order_v2(
+        args: account_parser::CancelOrderV2Args,
+        instruction: &CancelOrderInstructionV2,
+    ) -> DexResult {
+        let slot = (0..128u8)
+            .find(|&slot| {
+                args.open_orders.slot_side(slot) == Some(instruction.side)
+                    && args.open_orders.orders[slot as usize] == instruction.order_id
+            })
+            .ok_or(DexErrorCode::OrderNotFound)?;
+        Self::cancel_order_directly(args, slot)
+    }
+
+    fn process_cancel_order_by_client_id_v2(
+        args: account_parser::CancelOrderV2Args,
+        client_id: u64,
+    ) -> DexResult {
+        if client_id == 0 {
+            Err(DexErrorCode::ClientOrderIdIsZero)?
+        }
+        let slot = (0..128u8)
+            .find(|&slot| {
+                !args.open_orders.slot_is_free(slot)
+                    && args.open_orders.client_order_ids[slot as usize] == client_id
+            })
+            .ok_or(DexErrorCode::ClientIdNotFound)?;
+        Self::cancel_order_directly(args, slot)
+    }
+
+    fn cancel_order_directly(args: account_parser::CancelOrderV2Args, owner_slot: u8) -> DexResult {
+        let account_parser::CancelOrderV2Args {
+            open_orders,
+            open_orders_address,
+            req_q,
+            mut event_q,
+            mut order_book_state,
+            orders_owner: _,
+        } = args;
+        if !req_q.empty() {
+            Err(DexErrorCode::RequestQueueNotEmpty)?
+        }
+        let side = open_orders
+            .slot_side(owner_slot)
+            .ok_or(DexErrorCode::OrderNotFound)?;
+        let request = Request::new(RequestView::CancelOrder {
+            cancel_id: 0,
+            expected_owner: open_orders_address,
+            expected_owner_slot: owner_slot,
+            order_id: open_orders.orders[owner_slot as usize],
+            side,
+            client_order_id: NonZeroU64::new(open_orders.client_order_ids[owner_slot as usize]),
+        });
+        order_book_state.process_request_directly(&request, &mut event_q)
+    }
+
+    fn process_cancel_order(args: account_parser::CancelOrderArgs) -> DexResult {
+        let account_parser::CancelOrderArgs {
+            instruction,
+            open_orders: _,
+            open_orders_address,
+            mut req_q,
+            orders_owner: _,
+        } = args;
+
+        let request = Request::new(RequestView::CancelOrder {
+            cancel_id: req_q.gen_seq_num(),
+            expected_owner: open_orders_address,
+            expected_owner_slot: instruction.owner_slot,
+            order_id: instruction.order_id,
+            side: instruction.side,
+            client_order_id: None,
+        });
+        req_q
+            .push_back(request)
+            .map_err(|_| DexErrorCode::RequestQueueFull)?;
+        Ok(())
+    }
+
+    fn process_consume_events(args: account_parser::ConsumeEventsArgs) -> DexResult {
+        let account_parser::ConsumeEventsArgs {
+            limit,
+            start_seq_num,
+            program_id,
+            open_orders_accounts,
+            market,
+            mut event_q,
+        } = args;
+
+        // Without a starting point events are consumed in order, stopping at
+        // the first one whose OpenOrders account wasn't passed. With one, the
+        // events of other accounts are skipped and left for their own
+        // consumers, so a busy account's events don't wait on idle ones.
+        let mut index = match start_seq_num {
+            None => 0,
+            Some(seq_num) => seq_num.saturating_sub(event_q.head_seq_num()),
+        };
+        for _i in 0u16..limit {
+            let event = match event_q.get(index) {
+                None => break,
+                Some(e) => *e,
+            };
+            index += 1;
+            if event.is_consumed() {
+                continue;
+            }
+
+            let view = event.as_view()?;
+            let owner: [u64; 4] = event.owner;
+            let owner_index: Result<usize, usize> = open_orders_accounts
+                .binary_search_by_key(&owner, |account_info| account_info.key.to_aligned_bytes());
+            let mut open_orders: RefMut<OpenOrders> = match owner_index {
+                Err(_) if start_seq_num.is_some() => continue,
+                Err(_) => break,
+                Ok(i) => {
+                    market.load_orders_mut(&open_orders_accounts[i], None, program_id, None)?
+                }
+            };
+
+            check_assert!(event.owner_slot < 128)?;
+            check_assert_eq!(&open_orders.slot_side(event.owner_slot), &Some(view.side()))?;
+            check_assert_eq!(
+                open_orders.orders[event.owner_slot as usize],
+                event.order_id
+            )?;
+
+            match event.as_view()? {
+                EventView::Fill {
+                    side,
+                    maker,
+                    native_qty_paid,
+                    native_qty_received,
+                    native_fee_or_rebate,
+                    fee_tier: _,
+                    order_id: _,
+                    owner: _,
+                    owner_slot,
+                    client_order_id,
+                } => {
+                    // Fills are only kept in the queue until consumed, so
+                    // log them for indexers reading transaction history.
+                    for line in event.log_words().iter() {
+                        info!(line[0], line[1], line[2], line[3], line[4]);
+                    }
+                    match side {
+                        Side::Bid => {
+                            open_orders.native_pc_total -= native_qty_paid;
+                            open_orders.native_coin_total += native_qty_received;
+                            open_orders.native_coin_free += native_qty_received;
+
+                            if maker {
+                                open_orders.native_pc_free += native_fee_or_rebate;
+                            }
+                        }
+                        Side::Ask => {
+                            open_orders.native_coin_total -= native_qty_paid;
+                            open_orders.native_pc_total += native_qty_received;
+                            open_orders.native_pc_free += native_qty_received;
+                        }
+                    };
+                    if !maker {
+                        let referrer_rebate = fees::referrer_rebate(native_fee_or_rebate);
+                        open_orders.referrer_rebates_accrued += referrer_rebate;
+                    }
+                    if let Some(client_id) = client_order_id {
+                        debug_assert_eq!(
+                            client_id.get(),
+                            identity(open_orders.client_order_ids[owner_slot as usize])
+                        );
+                    }
+                }
+                EventView::Out {
+                    side,
+                    native_qty_unlocked,
+                    native_qty_still_locked,
+                    order_id: _,
+                    owner: _,
+                    owner_slot,
+                    client_order_id,
+                } => {
+                    let fully_out = native_qty_still_locked == 0;
+
+                    match side {
+                        Side::Bid => {
+                            open_orders.native_pc_free += native_qty_unlocked;
+                            check_assert!(
+                                open_orders.native_pc_free <= open_orders.native_pc_total
+                            )?;
+                        }
+                        Side::Ask => {
+                            open_orders.native_coin_free += native_qty_unlocked;
+                            check_assert!(
+                                open_orders.native_coin_free <= open_orders.native_coin_total
+                            )?;
+                        }
+                    };
+                    if let Some(client_id) = client_order_id {
+                        debug_assert_eq!(
+                            client_id.get(),
+                            identity(open_orders.client_order_ids[owner_slot as usize])
+                        );
+                    }
+                    if fully_out {
+                        open_orders.remove_order(owner_slot)?;
+                    }
+                }
+            };
+
+            event_q
+                .get_mut(index - 1)
+                .ok_or(DexErrorCode::ConsumeEventsQueueFailure)?
+                .mark_consumed();
+        }
+        event_q.pop_consumed();
+        Ok(())
+    }
+
+    fn process_match_orders(args: account_parser::MatchOrdersArgs) -> DexResult {
+        let account_parser::MatchOrdersArgs {
+            mut order_book_state,
+            mut req_q,
+            mut event_q,
+            limit,
+        } = args;
+        order_book_state.process_requests(&mut req_q, &mut event_q, limit)
+    }
+
+    #[cfg(feature = "program")]
+    fn process_new_order(mut args: account_parser::NewOrderArgs) -> DexResult {
+        args.market.check_request_queue_open()?;
+        let request = Self::deposit_new_order(&mut args)?;
+        args.req_q
+            .push_back(request)
+            .map_err(|_| DexErrorCode::RequestQueueFull)?;
+        Ok(())
+    }
+
+    #[cfg(feature = "program")]
+    fn process_new_order_v3(args: account_parser::NewOrderV3Args) -> DexResult {
+        let account_parser::NewOrderV3Args {
+            mut new_order,
+            mut event_q,
+            bids,
+            asks,
+            self_trade_accounts,
+            stats,
+            trader_stats,
+        } = args;
+        if !new_order.req_q.empty() {
+            Err(DexErrorCode::RequestQueueNotEmpty)?
+        }
+        let request = Self::deposit_new_order(&mut new_order)?;
+        let mut order_book_state = OrderBookState {
+            bids,
+            asks,
+            market_state: new_order.market,
+            self_trade_accounts: &self_trade_accounts,
+            stats,
+            trader_stats,
+            params: new_order.params,
+        };
+        order_book_state.process_request_directly(&request, &mut event_q)
+    }
+
+    #[cfg(feature = "program")]
+    fn process_new_order_v4(
+        mut args: account_parser::NewOrderV3Args,
+        cancel_if_through_reference_bps: u16,
+    ) -> DexResult {
+        args.new_order.cancel_if_through_reference_bps = cancel_if_through_reference_bps;
+        Self::process_new_order_v3(args)
+    }
+
+    #[cfg(feature = "program")]
+    fn process_new_order_batch(args: account_parser::NewOrderBatchArgs) -> DexResult {
+        let account_parser::NewOrderBatchArgs {
+            orders,
+            market,
+            open_orders,
+            open_orders_address,
+            owner,
+            mut req_q,
+            mut event_q,
+            bids,
+            asks,
+            coin_payer,
+            pc_payer,
+            coin_vault,
+            pc_vault,
+            spl_token_program,
+            fee_tier,
+            mut params,
+            self_trade_accounts,
+            mut stats,
+            mut trader_stats,
+        } = args;
+        if !req_q.empty() {
+            Err(DexErrorCode::RequestQueueNotEmpty)?
+        }
+
+        // Free balances are used up first, in order, and the rest is
+        // deposited once every order has been matched.
+        let mut coin_deposit: u64 = 0;
+        let mut pc_deposit: u64 = 0;
+        for (index, instruction) in orders.iter().enumerate() {
+            let lock_qty_native = market.native_qty_to_lock(instruction, fee_tier)?;
+            let native_pc_qty_locked = match instruction.side {
+                Side::Bid => {
+                    let free_qty_to_lock = lock_qty_native.min(open_orders.native_pc_free);
+                    let deposit_amount = lock_qty_native - free_qty_to_lock;
+                    open_orders.lock_free_pc(free_qty_to_lock);
+                    open_orders.credit_locked_pc(deposit_amount);
+                    pc_deposit = pc_deposit.checked_add(deposit_amount).unwrap();
+                    Some(NonZeroU64::new(lock_qty_native).unwrap())
+                }
+                Side::Ask => {
+                    let free_qty_to_lock = lock_qty_native.min(open_orders.native_coin_free);
+                    let deposit_amount = lock_qty_native - free_qty_to_lock;
+                    open_orders.lock_free_coin(free_qty_to_lock);
+                    open_orders.credit_locked_coin(deposit_amount);
+                    coin_deposit = coin_deposit.checked_add(deposit_amount).unwrap();
+                    None
+                }
+            };
+            let request = Self::record_new_order(
+                market,
+                params.as_deref(),
+                open_orders,
+                open_orders_address,
+                &mut req_q,
+                instruction,
+                fee_tier,
+                native_pc_qty_locked,
+                0,
+            )?;
+            let order_id = match request.as_view()? {
+                RequestView::NewOrder { order_id, .. } => order_id,
+                _ => check_unreachable!()?,
+            };
+            let mut order_book_state = OrderBookState {
+                bids: &mut *bids,
+                asks: &mut *asks,
+                market_state: &mut *market,
+                self_trade_accounts: &self_trade_accounts,
+                stats: stats.as_mut().map(|(stats, slot)| (&mut **stats, *slot)),
+                trader_stats: trader_stats
+                    .as_mut()
+                    .map(|(stats, day)| (&mut **stats, *day)),
+                params: params.as_deref_mut(),
+            };
+            order_book_state.process_request_directly(&request, &mut event_q)?;
 
-            let mut market = MarketState::load(market_acc, program_id)?;
-            let pc_vault = PcVault::from_account(pc_vault_acc, &market)?;
-            let fee_receiver = PcWallet::from_account(pc_wallet_acc, &market)?;
-            let vault_signer = VaultSigner::new(vault_signer_acc, &market, program_id)?;
-            let spl_token_program = SplTokenProgram::new(spl_token_program)?;
-            let authorization = SigningFeeSweeper::new(sweep_authority_acc)?;
+            let book: &Slab = match instruction.side {
+                Side::Bid => &*bids,
+                Side::Ask => &*asks,
+            };
+            let resting_qty = book
+                .find_order(order_id)
+                .map_or(0, |order| order.quantity());
+            info!(
+                ORDER_LOG_TAG,
+                index as u64,
+                order_id as u64,
+                (order_id >> 64) as u64,
+                resting_qty
+            );
+        }
 
-            let args = SweepFeesArgs {
-                market: market.deref_mut(),
-                pc_vault,
-                fee_receiver,
-                vault_signer,
+        market.coin_deposits_total = market
+            .coin_deposits_total
+            .checked_add(coin_deposit)
+            .unwrap();
+        market.pc_deposits_total = market.pc_deposits_total.checked_add(pc_deposit).unwrap();
+        if coin_deposit > 0 {
+            deposit_into_vault(
+                coin_deposit,
+                coin_payer,
+                coin_vault.token_account(),
+                owner,
                 spl_token_program,
-                authorization,
-            };
-            f(args)
+            )?;
+        }
+        if pc_deposit > 0 {
+            deposit_into_vault(
+                pc_deposit,
+                pc_payer,
+                pc_vault.token_account(),
+                owner,
+                spl_token_program,
+            )?;
         }
+        Ok(())
     }
-}
-
-#[inline]
-fn remove_slop<T: Pod>(bytes: &[u8]) -> &[T] {
-    let slop = bytes.len() % size_of::<T>();
-    let new_len = bytes.len() - slop;
-    cast_slice(&bytes[..new_len])
-}
 
-#[inline]
-fn remove_slop_mut<T: Pod>(bytes: &mut [u8]) -> &mut [T] {
-    let slop = bytes.len() % size_of::<T>();
-    let new_len = bytes.len() - slop;
-    cast_slice_mut(&mut bytes[..new_len])
-}
+    #[cfg(feature = "program")]
+    fn process_mass_quote(mut args: account_parser::NewOrderBatchArgs) -> DexResult {
+        if !args.req_q.empty() {
+            Err(DexErrorCode::RequestQueueNotEmpty)?
+        }
+        let mut order_book_state = OrderBookState {
+            bids: &mut *args.bids,
+            asks: &mut *args.asks,
+            market_state: &mut *args.market,
+            self_trade_accounts: &[],
+            stats: None,
+            trader_stats: None,
+            params: None,
+        };
+        Self::cancel_and_release_all_orders(
+            &mut order_book_state,
+            args.open_orders,
+            args.open_orders_address,
+            &mut args.event_q,
+        )?;
+        Self::process_new_order_batch(args)
+    }
 
-#[cfg_attr(not(feature = "program"), allow(unused))]
-impl State {
     #[cfg(feature = "program")]
-    pub fn process(program_id: &Pubkey, accounts: &[AccountInfo], input: &[u8]) -> DexResult {
-        let instruction = MarketInstruction::unpack(input).ok_or(ProgramError::InvalidArgument)?;
-        match instruction {
-            MarketInstruction::InitializeMarket(ref inner) => Self::process_initialize_market(
-                account_parser::InitializeMarketArgs::new(program_id, inner, accounts)?,
-            )?,
-            MarketInstruction::NewOrder(inner) => {
-                let new_order_v2 = inner.add_self_trade_behavior(SelfTradeBehavior::DecrementTake);
-                account_parser::NewOrderArgs::with_parsed_args(
-                    program_id,
-                    &new_order_v2,
-                    accounts,
-                    Self::process_new_order,
-                )?
-            }
-            MarketInstruction::NewOrderV2(ref inner) => {
-                account_parser::NewOrderArgs::with_parsed_args(
-                    program_id,
-                    inner,
-                    accounts,
-                    Self::process_new_order,
-                )?
-            }
-            MarketInstruction::MatchOrders(limit) => {
-                account_parser::MatchOrdersArgs::with_parsed_args(
-                    program_id,
-                    accounts,
-                    limit,
-                    Self::process_match_orders,
-                )?
-            }
-            MarketInstruction::ConsumeEvents(limit) => {
-                account_parser::ConsumeEventsArgs::with_parsed_args(
-                    program_id,
-                    accounts,
-                    limit,
-                    Self::process_consume_events,
-                )?
-            }
-            MarketInstruction::CancelOrder(ref inner) => {
-                account_parser::CancelOrderArgs::with_parsed_args(
-                    program_id,
-                    accounts,
-                    inner,
-                    Self::process_cancel_order,
-                )?
-            }
-            MarketInstruction::SettleFunds => account_parser::SettleFundsArgs::with_parsed_args(
-                program_id,
-                accounts,
-                Self::process_settle_funds,
-            )?,
-            MarketInstruction::CancelOrderByClientId(client_id) => {
-                account_parser::CancelOrderByClientIdArgs::with_parsed_args(
-                    program_id,
-                    accounts,
-                    client_id,
-                    Self::process_cancel_order_by_client_id,
-                )?
-            }
-            MarketInstruction::DisableMarket => {
-                account_parser::DisableMarketArgs::with_parsed_args(
-                    program_id,
-                    accounts,
-                    Self::process_disable_market,
-                )?
+    fn process_prune(args: account_parser::PruneArgs) -> DexResult {
+        let account_parser::PruneArgs {
+            open_orders,
+            open_orders_address,
+            req_q,
+            mut event_q,
+            mut order_book_state,
+            authorization: _,
+        } = args;
+        if !req_q.empty() {
+            Err(DexErrorCode::RequestQueueNotEmpty)?
+        }
+        Self::cancel_all_orders_directly(
+            &mut order_book_state,
+            open_orders,
+            open_orders_address,
+            &mut event_q,
+        )
+    }
+
+    // Takes every order of the OpenOrders account off the book.
+    fn cancel_all_orders_directly(
+        order_book_state: &mut OrderBookState,
+        open_orders: &OpenOrders,
+        open_orders_address: [u64; 4],
+        event_q: &mut EventQueue,
+    ) -> DexResult {
+        for owner_slot in 0..128u8 {
+            let side = match open_orders.slot_side(owner_slot) {
+                Some(side) => side,
+                None => continue,
+            };
+            // Orders already taken off the book, e.g. filled ones waiting
+            // for ConsumeEvents, are skipped by the cancel.
+            let request = Request::new(RequestView::CancelOrder {
+                cancel_id: 0,
+                expected_owner: open_orders_address,
+                expected_owner_slot: owner_slot,
+                order_id: open_orders.orders[owner_slot as usize],
+                side,
+                client_order_id: NonZeroU64::new(open_orders.client_order_ids[owner_slot as usize]),
+            });
+            order_book_state.process_request_directly(&request, event_q)?;
+        }
+        Ok(())
+    }
+
+    // Like `cancel_all_orders_directly`, but releases the funds and slots of
+    // the cancelled orders at once rather than through Out events, so they
+    // can back new orders in the same instruction. ConsumeEvents checks each
+    // event against the order in its slot, so a slot with events still
+    // pending is freed by an Out event behind them instead. Orders already
+    // taken off the book keep their slots until ConsumeEvents as well.
+    fn cancel_and_release_all_orders(
+        order_book_state: &mut OrderBookState,
+        open_orders: &mut OpenOrders,
+        open_orders_address: [u64; 4],
+        event_q: &mut EventQueue,
+    ) -> DexResult {
+        let pending_slots = event_q
+            .iter()
+            .filter(|event| !event.is_consumed() && identity(event.owner) == open_orders_address)
+            .fold(0u128, |slots, event| slots | (1u128 << event.owner_slot));
+        for owner_slot in 0..128u8 {
+            let side = match open_orders.slot_side(owner_slot) {
+                Some(side) => side,
+                None => continue,
+            };
+            let order_id = open_orders.orders[owner_slot as usize];
+            let (leaf_node, native_qty_unlocked) = match order_book_state.remove_order_directly(
+                side,
+                order_id,
+                open_orders_address,
+                owner_slot,
+            ) {
+                Some(removed) => removed,
+                None => continue,
+            };
+            match side {
+                Side::Bid => {
+                    open_orders.native_pc_free += native_qty_unlocked;
+                    check_assert!(open_orders.native_pc_free <= open_orders.native_pc_total)?;
+                }
+                Side::Ask => {
+                    open_orders.native_coin_free += native_qty_unlocked;
+                    check_assert!(open_orders.native_coin_free <= open_orders.native_coin_total)?;
+                }
+            };
+            if pending_slots & (1u128 << owner_slot) == 0 {
+                open_orders.remove_order(owner_slot)?;
+                continue;
             }
-            MarketInstruction::SweepFees => account_parser::SweepFeesArgs::with_parsed_args(
-                program_id,
-                accounts,
-                Self::process_sweep_fees,
-            )?,
-        };
+            event_q
+                .push_back(Event::new(EventView::Out {
+                    side,
+                    native_qty_unlocked: 0,
+                    native_qty_still_locked: 0,
+                    order_id,
+                    owner: open_orders_address,
+                    owner_slot,
+                    client_order_id: NonZeroU64::new(leaf_node.client_order_id()),
+                }))
+                .map_err(|_| DexErrorCode::EventQueueFull)?;
+        }
         Ok(())
     }
 
+    /// Locks the funds for a new order, records it in the OpenOrders
+    /// account, and returns the request to match against the book.
     #[cfg(feature = "program")]
-    fn process_settle_funds(args: account_parser::SettleFundsArgs) -> DexResult {
-        let account_parser::SettleFundsArgs {
-            market,
-            mut open_orders,
+    fn deposit_new_order(args: &mut account_parser::NewOrderArgs) -> DexResult<Request> {
+        let account_parser::NewOrderArgs {
+            instruction,
+            ref mut market,
+            ref mut open_orders,
+            open_orders_address,
+            ref mut req_q,
+            payer,
+            owner,
             coin_vault,
             pc_vault,
-            coin_wallet,
-            pc_wallet,
-            vault_signer,
             spl_token_program,
-            referrer,
-        } = args;
+            fee_tier,
+            ref params,
+            cancel_if_through_reference_bps,
+        } = *args;
 
-        let native_coin_amount = open_orders.native_coin_free;
-        let native_pc_amount = open_orders.native_pc_free;
+        let deposit_amount;
+        let deposit_vault;
 
-        market.coin_deposits_total -= native_coin_amount;
-        market.pc_deposits_total -= native_pc_amount;
+        let lock_qty_native = market.native_qty_to_lock(instruction, fee_tier)?;
+        let native_pc_qty_locked;
+        match instruction.side {
+            Side::Bid => {
+                native_pc_qty_locked = Some(NonZeroU64::new(lock_qty_native).unwrap());
+                let free_qty_to_lock = lock_qty_native.min(open_orders.native_pc_free);
+                deposit_amount = lock_qty_native - free_qty_to_lock;
+                deposit_vault = pc_vault.token_account();
+                open_orders.lock_free_pc(free_qty_to_lock);
+                open_orders.credit_locked_pc(deposit_amount);
+                market.pc_deposits_total = market
+                    .pc_deposits_total
+                    .checked_add(deposit_amount)
+                    .unwrap();
+            }
+            Side::Ask => {
+                let free_qty_to_lock = lock_qty_native.min(open_orders.native_coin_free);
+                deposit_amount = lock_qty_native - free_qty_to_lock;
+                deposit_vault = coin_vault.token_account();
+                open_orders.lock_free_coin(free_qty_to_lock);
+                open_orders.credit_locked_coin(deposit_amount);
+                native_pc_qty_locked = None;
+                market.coin_deposits_total = market
+                    .coin_deposits_total
+                    .checked_add(deposit_amount)
+                    .unwrap();
+            }
+        };
 
-        open_orders.native_coin_free = 0;
-        open_orders.native_pc_free = 0;
+        deposit_into_vault(
+            deposit_amount,
+            payer,
+            deposit_vault,
+            owner,
+            spl_token_program,
+        )?;
 
-        open_orders.native_coin_total = open_orders
-            .native_coin_total
-            .checked_sub(native_coin_amount)
-            .unwrap();
-        open_orders.native_pc_total = open_orders
-            .native_pc_total
-            .checked_sub(native_pc_amount)
-            .unwrap();
+        Self::record_new_order(
+            market,
+            params.as_deref(),
+            open_orders,
+            open_orders_address,
+            req_q,
+            instruction,
+            fee_tier,
+            native_pc_qty_locked,
+            cancel_if_through_reference_bps,
+        )
+    }
+
+    // Records an order whose funds are already locked in the user account
+    // and returns the request to match against the book.
+    fn record_new_order(
+        market: &MarketState,
+        params: Option<&MarketParams>,
+        open_orders: &mut OpenOrders,
+        open_orders_address: [u64; 4],
+        req_q: &mut RequestQueue,
+        instruction: &NewOrderInstructionV2,
+        fee_tier: FeeTier,
+        native_pc_qty_locked: Option<NonZeroU64>,
+        cancel_if_through_reference_bps: u16,
+    ) -> DexResult<Request> {
+        let options = market.options();
+        if options.contains(MarketOption::Params) {
+            params
+                .ok_or(DexErrorCode::MarketParamsNotProvided)?
+                .check_order(instruction, market.pc_lot_size)?;
+        }
+        if options.contains(MarketOption::UniqueClientIds)
+            && instruction.client_id != 0
+            && open_orders.has_client_order_id(instruction.client_id)
+        {
+            Err(DexErrorCode::DuplicateClientOrderId)?
+        }
+        let order_id = req_q.gen_order_id(instruction.limit_price.get(), instruction.side);
+        let owner_slot = open_orders.add_order(order_id, instruction.side)?;
+        open_orders.client_order_ids[owner_slot as usize] = instruction.client_id;
+
+        Ok(Request::new(RequestView::NewOrder {
+            side: instruction.side,
+            order_type: instruction.order_type,
+            order_id,
+            fee_tier,
+            self_trade_behavior: instruction.self_trade_behavior,
+            owner: open_orders_address,
+            owner_slot,
+            max_coin_qty: instruction.max_qty,
+            native_pc_qty_locked,
+            client_order_id: NonZeroU64::new(instruction.client_id),
+            cancel_if_through_reference_bps,
+        }))
+    }
+
+    fn process_disable_market(args: account_parser::DisableMarketArgs) -> DexResult {
+        let account_parser::DisableMarketArgs {
+            market,
+            authorization: _,
+        } = args;
+        market.account_flags = market.account_flags | (AccountFlag::Disabled as u64);
+        Ok(())
+    }
 
-        let token_infos: [(
-            u64,
-            account_parser::TokenAccount,
-            account_parser::TokenAccount,
-        ); 2] = [
-            (
-                native_coin_amount,
-                coin_wallet.token_account(),
-                coin_vault.token_account(),
-            ),
-            (
-                native_pc_amount,
-                pc_wallet.token_account(),
-                pc_vault.token_account(),
-            ),
-        ];
+    #[cfg(feature = "program")]
+    fn process_sweep_fees(args: account_parser::SweepFeesArgs) -> DexResult {
+        let account_parser::SweepFeesArgs {
+            mut market,
+            pc_vault,
+            fee_receiver,
+            vault_signer,
+            spl_token_program,
+            authorization: _,
+            fee_destinations,
+        } = args;
+        let mut token_amount = market.pc_fees_accrued;
+        market.pc_fees_accrued = 0;
 
         let nonce = market.vault_signer_nonce;
         let market_pubkey = market.pubkey();
         let vault_signer_seeds = gen_vault_signer_seeds(&nonce, &market_pubkey);
-
-        for &(token_amount, wallet_account, vault) in token_infos.iter() {
-            send_from_vault(
-                token_amount,
-                wallet_account,
-                vault,
-                spl_token_program,
-                vault_signer,
-                &vault_signer_seeds,
-            )?;
-        }
-
-        match referrer {
-            Some(referrer_pc_wallet) if open_orders.referrer_rebates_accrued > 0 => {
+        if let Some((destinations, wallets)) = fee_destinations {
+            let shares = destinations.split(token_amount);
+            for (wallet, share) in wallets.into_iter().zip(shares) {
+                if share == 0 {
+                    continue;
+                }
                 send_from_vault(
-                    open_orders.referrer_rebates_accrued,
-                    referrer_pc_wallet.token_account(),
+                    share,
+                    wallet.token_account(),
                     pc_vault.token_account(),
                     spl_token_program,
                     vault_signer,
                     &vault_signer_seeds,
                 )?;
+                token_amount -= share;
             }
-            _ => {
-                market.pc_fees_accrued += open_orders.referrer_rebates_accrued;
-            }
+        }
+        send_from_vault(
+            token_amount,
+            fee_receiver.token_account(),
+            pc_vault.token_account(),
+            spl_token_program,
+            vault_signer,
+            &vault_signer_seeds,
+        )
+    }
+
+    fn process_set_fee_destinations(args: account_parser::SetFeeDestinationsArgs) -> DexResult {
+        let account_parser::SetFeeDestinationsArgs {
+            instruction,
+            fee_destinations,
+            wallets,
+        } = args;
+        let weights = &instruction.weights;
+        if weights.is_empty()
+            || weights.len() > MAX_FEE_DESTINATIONS
+            || weights.iter().any(|&weight| weight == 0)
+        {
+            Err(DexErrorCode::InvalidFeeDestinations)?
+        }
+        let mut registered_wallets = [[0; 4]; MAX_FEE_DESTINATIONS];
+        let mut registered_weights = [0; MAX_FEE_DESTINATIONS];
+        registered_wallets[..wallets.len()].copy_from_slice(&wallets);
+        registered_weights[..weights.len()].copy_from_slice(weights);
+        fee_destinations.count = weights.len() as u64;
+        fee_destinations.wallets = registered_wallets;
+        fee_destinations.weights = registered_weights;
+        Ok(())
+    }
+
+    fn process_resize_market_accounts(args: account_parser::ResizeMarketAccountsArgs) -> DexResult {
+        let account_parser::ResizeMarketAccountsArgs {
+            market,
+            event_q,
+            bids,
+            asks,
+            new_event_q,
+            new_bids,
+            new_asks,
+            authorization: _,
+        } = args;
+
+        // unwrap the ring buffer into the front of the new queue
+        let (mut eq_hdr, mut eq_buf) = strip_header::<EventQueueHeader, Event>(new_event_q, true)?;
+        if eq_buf.len() < event_q.buf.len() {
+            Err(DexErrorCode::EventQueueTooSmall)?
+        }
+        for (slot, event) in eq_buf.iter_mut().zip(event_q.iter()) {
+            *slot = *event;
+        }
+        *eq_hdr = EventQueueHeader {
+            account_flags: (AccountFlag::Initialized | AccountFlag::EventQueue).bits(),
+            head: 0,
+            count: event_q.header.count,
+            seq_num: event_q.header.seq_num,
         };
-        market.referrer_rebates_accrued -= open_orders.referrer_rebates_accrued;
-        open_orders.referrer_rebates_accrued = 0;
 
+        for &(flag, slab, account) in &[
+            (AccountFlag::Bids, bids, new_bids),
+            (AccountFlag::Asks, asks, new_asks),
+        ] {
+            let (mut ob_hdr, mut ob_buf) = strip_header::<OrderBookStateHeader, u8>(account, true)?;
+            *ob_hdr = OrderBookStateHeader {
+                account_flags: (AccountFlag::Initialized | flag).bits(),
+            };
+            slab.copy_into(Slab::new(&mut ob_buf))?;
+        }
+
+        market.event_q = new_event_q.key.to_aligned_bytes();
+        market.bids = new_bids.key.to_aligned_bytes();
+        market.asks = new_asks.key.to_aligned_bytes();
         Ok(())
     }
 
-    fn process_cancel_order_by_client_id(
-        args: account_parser::CancelOrderByClientIdArgs,
-    ) -> DexResult {
-        let account_parser::CancelOrderByClientIdArgs {
-            client_order_id,
-            open_orders,
-            open_orders_address,
-            mut req_q,
-            orders_owner: _,
+    fn process_update_lot_sizes(args: account_parser::UpdateLotSizesArgs) -> DexResult {
+        let account_parser::UpdateLotSizesArgs {
+            instruction,
+            market,
+            req_q,
+            event_q,
+            bids,
+            asks,
+            authorization: _,
+            params,
         } = args;
-        let mut slot = None;
-        for i in 0..128 {
-            if open_orders.client_order_ids[i] == client_order_id.get()
-                && !open_orders.slot_is_free(i as u8)
-            {
-                slot = Some(i);
-                break;
+        let &UpdateLotSizesInstruction {
+            coin_lot_size,
+            pc_lot_size,
+        } = instruction;
+        check_assert!(coin_lot_size > 0)?;
+        check_assert!(pc_lot_size > 0)?;
+
+        // Orders and queued requests are denominated in lots, so they would
+        // be misread under the new sizes. Everything else is native.
+        if !(req_q.empty() && event_q.empty() && bids.is_empty() && asks.is_empty()) {
+            Err(DexErrorCode::OrderBookNotEmpty)?
+        }
+        // So are trigger orders, which only markets with params take.
+        if let Some(params) = params {
+            if params.trigger_order_count != 0 {
+                Err(DexErrorCode::TriggerOrdersPending)?
             }
+            // The oracle's and last fill prices are in lots as well, so the
+            // band waits for the next report and triggers for the next fill.
+            params.reference_price = 0;
+            params.last_fill_price = 0;
         }
-        let expected_open_orders_slot = slot.ok_or(DexErrorCode::ClientIdNotFound)? as u8;
-        let side = open_orders
-            .slot_side(expected_open_orders_slot)
-            .ok_or(DexErrorCode::ClientIdNotFound)?;
-        let order_id = open_orders.orders[expected_open_orders_slot as usize];
-        let request = Request::new(RequestView::CancelOrder {
-            cancel_id: req_q.gen_seq_num(),
-            expected_owner: open_orders_address,
-            expected_owner_slot: expected_open_orders_slot,
-            order_id,
-            side,
-            client_order_id: Some(client_order_id),
-        });
-        req_q
-            .push_back(request)
-            .map_err(|_| DexErrorCode::RequestQueueFull)?;
+
+        market.coin_lot_size = coin_lot_size;
+        market.pc_lot_size = pc_lot_size;
         Ok(())
     }
 
-    fn process_cancel_order(args: account_parser::CancelOrderArgs) -> DexResult {
-        let account_parser::CancelOrderArgs {
+    fn process_set_fee_schedule(args: account_parser::SetFeeScheduleArgs) -> DexResult {
+        let account_parser::SetFeeScheduleArgs {
             instruction,
-            open_orders: _,
-            open_orders_address,
-            mut req_q,
-            orders_owner: _,
+            market,
+            authorization: _,
         } = args;
+        let &SetFeeScheduleInstruction {
+            taker_fee_bps,
+            maker_rebate_bps,
+        } = instruction;
+        let schedule = FeeSchedule {
+            taker_fee_bps,
+            maker_rebate_bps,
+        };
+        if market.version < 2 {
+            Err(DexErrorCode::MarketNotUpgraded)?
+        }
+        if !schedule.is_valid() {
+            Err(DexErrorCode::InvalidFeeSchedule)?
+        }
+        market.taker_fee_bps = taker_fee_bps;
+        market.maker_rebate_bps = maker_rebate_bps;
+        Ok(())
+    }
 
-        let request = Request::new(RequestView::CancelOrder {
-            cancel_id: req_q.gen_seq_num(),
-            expected_owner: open_orders_address,
-            expected_owner_slot: instruction.owner_slot,
-            order_id: instruction.order_id,
-            side: instruction.side,
-            client_order_id: None,
-        });
-        req_q
-            .push_back(request)
-            .map_err(|_| DexErrorCode::RequestQueueFull)?;
+    fn process_set_market_options(args: account_parser::SetMarketOptionsArgs) -> DexResult {
+        let account_parser::SetMarketOptionsArgs {
+            options,
+            market,
+            authorization: _,
+        } = args;
+        if market.version < 2 {
+            Err(DexErrorCode::MarketNotUpgraded)?
+        }
+        let options: BitFlags<MarketOption> =
+            BitFlags::from_bits(options).map_err(|_| DexErrorCode::InvalidMarketOptions)?;
+        if options.contains(MarketOption::Params) != market.options().contains(MarketOption::Params)
+        {
+            Err(DexErrorCode::InvalidMarketOptions)?
+        }
+        market.options = options.bits();
         Ok(())
     }
 
-    fn process_consume_events(args: account_parser::ConsumeEventsArgs) -> DexResult {
-        let account_parser::ConsumeEventsArgs {
-            limit,
+    #[cfg(feature = "program")]
+    fn process_list_market(args: account_parser::ListMarketArgs) -> DexResult {
+        let account_parser::ListMarketArgs {
             program_id,
-            open_orders_accounts,
             market,
-            mut event_q,
+            lister,
+            listing,
+            nonce,
+            system_program,
+            rent,
         } = args;
+        let coin_mint = identity(market.coin_mint);
+        let pc_mint = identity(market.pc_mint);
+        let nonce_bytes = [nonce];
+        let listing_seeds =
+            gen_market_listing_seeds(bytes_of(&coin_mint), bytes_of(&pc_mint), &nonce_bytes);
+        // The runtime refuses to create an account twice, so a listed pair
+        // fails either here or at the flags check below.
+        if listing.owner != program_id {
+            create_market_listing_account(
+                listing,
+                lister,
+                system_program,
+                rent,
+                program_id,
+                &listing_seeds,
+            )?;
+        }
 
-        for _i in 0u16..limit {
-            let event = match event_q.peek_front() {
-                None => break,
-                Some(e) => e,
-            };
-
-            let view = event.as_view()?;
-            let owner: [u64; 4] = event.owner;
-            let owner_index: Result<usize, usize> = open_orders_accounts
-                .binary_search_by_key(&owner, |account_info| account_info.key.to_aligned_bytes());
-            let mut open_orders: RefMut<OpenOrders> = match owner_index {
-                Err(_) => break,
-                Ok(i) => {
-                    market.load_orders_mut(&open_orders_accounts[i], None, program_id, None)?
-                }
-            };
-
-            check_assert!(event.owner_slot < 128)?;
-            check_assert_eq!(&open_orders.slot_side(event.owner_slot), &Some(view.side()))?;
-            check_assert_eq!(
-                open_orders.orders[event.owner_slot as usize],
-                event.order_id
-            )?;
-
-            match event.as_view()? {
-                EventView::Fill {
-                    side,
-                    maker,
-                    native_qty_paid,
-                    native_qty_received,
-                    native_fee_or_rebate,
-                    fee_tier: _,
-                    order_id: _,
-                    owner: _,
-                    owner_slot,
-                    client_order_id,
-                } => {
-                    match side {
-                        Side::Bid => {
-                            open_orders.native_pc_total -= native_qty_paid;
-                            open_orders.native_coin_total += native_qty_received;
-                            open_orders.native_coin_free += native_qty_received;
-
-                            if maker {
-                                open_orders.native_pc_free += native_fee_or_rebate;
-                            }
-                        }
-                        Side::Ask => {
-                            open_orders.native_coin_total -= native_qty_paid;
-                            open_orders.native_pc_total += native_qty_received;
-                            open_orders.native_pc_free += native_qty_received;
-                        }
-                    };
-                    if !maker {
-                        let referrer_rebate = fees::referrer_rebate(native_fee_or_rebate);
-                        open_orders.referrer_rebates_accrued += referrer_rebate;
-                    }
-                    if let Some(client_id) = client_order_id {
-                        debug_assert_eq!(
-                            client_id.get(),
-                            identity(open_orders.client_order_ids[owner_slot as usize])
-                        );
-                    }
-                }
-                EventView::Out {
-                    side,
-                    native_qty_unlocked,
-                    native_qty_still_locked,
-                    order_id: _,
-                    owner: _,
-                    owner_slot,
-                    client_order_id,
-                } => {
-                    let fully_out = native_qty_still_locked == 0;
-
-                    match side {
-                        Side::Bid => {
-                            open_orders.native_pc_free += native_qty_unlocked;
-                            check_assert!(
-                                open_orders.native_pc_free <= open_orders.native_pc_total
-                            )?;
-                        }
-                        Side::Ask => {
-                            open_orders.native_coin_free += native_qty_unlocked;
-                            check_assert!(
-                                open_orders.native_coin_free <= open_orders.native_coin_total
-                            )?;
-                        }
-                    };
-                    if let Some(client_id) = client_order_id {
-                        debug_assert_eq!(
-                            client_id.get(),
-                            identity(open_orders.client_order_ids[owner_slot as usize])
-                        );
-                    }
-                    if fully_out {
-                        open_orders.remove_order(owner_slot)?;
-                    }
-                }
-            };
-
-            event_q
-                .pop_front()
-                .map_err(|()| DexErrorCode::ConsumeEventsQueueFailure)?;
+        let (_, data) = strip_header::<[u8; 0], u8>(listing, true)?;
+        let mut listing: RefMut<MarketListing> = RefMut::map(data, |data| from_bytes_mut(data));
+        if listing.account_flags != 0 {
+            Err(DexErrorCode::MarketAlreadyListed)?
         }
+        *listing = MarketListing {
+            account_flags: (AccountFlag::Initialized | AccountFlag::MarketListing).bits(),
+            coin_mint,
+            pc_mint,
+            market: identity(market.own_address),
+            lister: lister.inner().key.to_aligned_bytes(),
+            nonce: nonce as u64,
+        };
         Ok(())
     }
 
-    fn process_match_orders(args: account_parser::MatchOrdersArgs) -> DexResult {
-        let account_parser::MatchOrdersArgs {
-            mut order_book_state,
-            mut req_q,
-            mut event_q,
-            limit,
+    fn process_set_market_params(args: account_parser::SetMarketParamsArgs) -> DexResult {
+        let account_parser::SetMarketParamsArgs {
+            instruction,
+            market,
+            params,
+            authorization: _,
+            market_stats,
         } = args;
-        order_book_state.process_requests(&mut req_q, &mut event_q, limit)
+        if market.version < 2 {
+            Err(DexErrorCode::MarketNotUpgraded)?
+        }
+        params.oracle = instruction.oracle;
+        params.max_deviation_bps = instruction.max_deviation_bps;
+        params.min_notional = instruction.min_notional;
+        params.prune_authority = instruction.prune_authority;
+        if let Some(market_stats) = market_stats {
+            params.market_stats = market_stats;
+        }
+        market.options = (market.options() | MarketOption::Params).bits();
+        Ok(())
+    }
+
+    fn process_update_reference_price(args: account_parser::UpdateReferencePriceArgs) -> DexResult {
+        let account_parser::UpdateReferencePriceArgs { price, params } = args;
+        params.reference_price = price;
+        Ok(())
+    }
+
+    fn process_reassign_market_listing(
+        args: account_parser::ReassignMarketListingArgs,
+    ) -> DexResult {
+        let account_parser::ReassignMarketListingArgs {
+            listing,
+            market,
+            authorization: _,
+        } = args;
+        if identity(market.coin_mint) != identity(listing.coin_mint)
+            || identity(market.pc_mint) != identity(listing.pc_mint)
+        {
+            Err(DexErrorCode::WrongMarketListingAccount)?
+        }
+        listing.market = identity(market.own_address);
+        Ok(())
+    }
+
+    fn process_init_block_trade(args: account_parser::InitBlockTradeArgs) -> DexResult {
+        let account_parser::InitBlockTradeArgs {
+            instruction,
+            market,
+            block_trade,
+            block_trade_address,
+            bid_open_orders,
+            ask_open_orders,
+        } = args;
+        if instruction.price == 0 || instruction.coin_qty == 0 {
+            Err(DexErrorCode::InvalidBlockTrade)?
+        }
+        *block_trade = BlockTrade {
+            account_flags: (AccountFlag::Initialized | AccountFlag::BlockTrade).bits(),
+            market: identity(market.own_address),
+            bid_open_orders,
+            ask_open_orders,
+
+            price: instruction.price,
+            coin_qty: instruction.coin_qty,
+
+            // Priced like an order id, and unique to the block trade account.
+            order_id: (u128::from(instruction.price) << 64) | u128::from(block_trade_address[0]),
+            bid_slot: BlockTrade::NO_DEPOSIT,
+            ask_slot: BlockTrade::NO_DEPOSIT,
+        };
+        // Fails on terms too large to deposit.
+        block_trade.native_qtys(market.coin_lot_size, market.pc_lot_size)?;
+        Ok(())
     }
 
     #[cfg(feature = "program")]
-    fn process_new_order(args: account_parser::NewOrderArgs) -> DexResult {
-        let account_parser::NewOrderArgs {
+    fn process_deposit_block_trade(args: account_parser::DepositBlockTradeArgs) -> DexResult {
+        let account_parser::DepositBlockTradeArgs {
             instruction,
             market,
+            block_trade,
+            side,
             open_orders,
-            open_orders_address,
-            mut req_q,
             payer,
             owner,
             coin_vault,
             pc_vault,
             spl_token_program,
-            fee_tier,
         } = args;
+        if block_trade.slot(side).is_some()
+            || identity(block_trade.price) != instruction.price
+            || identity(block_trade.coin_qty) != instruction.coin_qty
+        {
+            Err(DexErrorCode::InvalidBlockTrade)?
+        }
+        let (native_coin_qty, native_pc_qty) =
+            block_trade.native_qtys(market.coin_lot_size, market.pc_lot_size)?;
 
         let deposit_amount;
         let deposit_vault;
-
-        let native_pc_qty_locked;
-        match instruction.side {
+        match side {
             Side::Bid => {
-                let lock_qty_lots = instruction
-                    .max_qty
-                    .get()
-                    .checked_mul(instruction.limit_price.get())
-                    .ok_or(DexErrorCode::InsufficientFunds)?;
-                let native_lock_qty_before_fee = lock_qty_lots
-                    .checked_mul(market.pc_lot_size)
-                    .ok_or(DexErrorCode::InsufficientFunds)?;
-                let lock_qty_native = native_lock_qty_before_fee
-                    .checked_add(fee_tier.taker_fee(native_lock_qty_before_fee))
-                    .ok_or(DexErrorCode::InsufficientFunds)?;
-                native_pc_qty_locked = Some(NonZeroU64::new(lock_qty_native).unwrap());
-                let free_qty_to_lock = lock_qty_native.min(open_orders.native_pc_free);
-                deposit_amount = lock_qty_native - free_qty_to_lock;
+                let free_qty_to_lock = native_pc_qty.min(open_orders.native_pc_free);
+                deposit_amount = native_pc_qty - free_qty_to_lock;
                 deposit_vault = pc_vault.token_account();
                 open_orders.lock_free_pc(free_qty_to_lock);
                 open_orders.credit_locked_pc(deposit_amount);
@@ -2241,17 +6359,11 @@ impl State {
                     .unwrap();
             }
             Side::Ask => {
-                let lock_qty_native = instruction
-                    .max_qty
-                    .get()
-                    .checked_mul((*market).coin_lot_size)
-                    .ok_or(DexErrorCode::InsufficientFunds)?;
-                let free_qty_to_lock = lock_qty_native.min(open_orders.native_coin_free);
-                deposit_amount = lock_qty_native - free_qty_to_lock;
+                let free_qty_to_lock = native_coin_qty.min(open_orders.native_coin_free);
+                deposit_amount = native_coin_qty - free_qty_to_lock;
                 deposit_vault = coin_vault.token_account();
                 open_orders.lock_free_coin(free_qty_to_lock);
                 open_orders.credit_locked_coin(deposit_amount);
-                native_pc_qty_locked = None;
                 market.coin_deposits_total = market
                     .coin_deposits_total
                     .checked_add(deposit_amount)
@@ -2259,97 +6371,410 @@ impl State {
             }
         };
 
-        // pull balances from payer, signed by owner
-        let deposit_instruction = spl_token::instruction::transfer(
-            &spl_token::ID,
-            payer.inner().key,
-            deposit_vault.inner().key,
-            owner.inner().key,
-            &[],
+        deposit_into_vault(
             deposit_amount,
-        )
-        .unwrap();
-        assert_eq!(*spl_token_program.inner().key, spl_token::ID);
-
-        // pull the deposited funds
-        invoke_spl_token(
-            &deposit_instruction,
-            &[
-                payer.inner().clone(),
-                deposit_vault.inner().clone(),
-                owner.inner().clone(),
-                spl_token_program.inner().clone(),
-            ],
-            &[],
-        )
-        .map_err(|err| match err {
-            ProgramError::Custom(i) => match TokenError::from_u32(i) {
-                Some(TokenError::InsufficientFunds) => DexErrorCode::InsufficientFunds,
-                _ => DexErrorCode::TransferFailed,
-            },
-            _ => DexErrorCode::TransferFailed,
-        })?;
+            payer,
+            deposit_vault,
+            owner,
+            spl_token_program,
+        )?;
 
-        // record the open order in the user account
-        let order_id = req_q.gen_order_id(instruction.limit_price.get(), instruction.side);
-        let owner_slot = open_orders.add_order(order_id, instruction.side)?;
-        open_orders.client_order_ids[owner_slot as usize] = instruction.client_id;
+        let slot = open_orders.add_order(identity(block_trade.order_id), side)?;
+        block_trade.set_slot(side, slot);
+        Ok(())
+    }
 
-        // add the request to the queue
-        let request = Request::new(RequestView::NewOrder {
-            side: instruction.side,
-            order_type: instruction.order_type,
-            order_id,
-            fee_tier,
-            self_trade_behavior: instruction.self_trade_behavior,
-            owner: open_orders_address,
-            owner_slot,
-            max_coin_qty: instruction.max_qty,
-            native_pc_qty_locked,
-            client_order_id: NonZeroU64::new(instruction.client_id),
-        });
+    fn process_settle_block_trade(args: account_parser::SettleBlockTradeArgs) -> DexResult {
+        let account_parser::SettleBlockTradeArgs {
+            market,
+            block_trade,
+            bid_open_orders,
+            ask_open_orders,
+            mut event_q,
+            bids,
+            asks,
+        } = args;
+        let (bid_slot, ask_slot) = match (block_trade.slot(Side::Bid), block_trade.slot(Side::Ask))
+        {
+            (Some(bid_slot), Some(ask_slot)) => (bid_slot, ask_slot),
+            _ => Err(DexErrorCode::BlockTradeNotFunded)?,
+        };
 
-        req_q
-            .push_back(request)
-            .map_err(|_| DexErrorCode::RequestQueueFull)?;
+        let price = block_trade.price;
+        let best_price = |slab: &Slab, handle: Option<NodeHandle>| {
+            slab.get(handle?)?.as_leaf().map(|leaf| leaf.price().get())
+        };
+        if best_price(bids, bids.find_max()).map_or(false, |best_bid| price < best_bid)
+            || best_price(asks, asks.find_min()).map_or(false, |best_ask| price > best_ask)
+        {
+            Err(DexErrorCode::BlockTradePriceOutsideSpread)?
+        }
+
+        let order_id = identity(block_trade.order_id);
+        check_assert_eq!(
+            identity(bid_open_orders.orders[bid_slot as usize]),
+            order_id
+        )?;
+        check_assert_eq!(
+            identity(ask_open_orders.orders[ask_slot as usize]),
+            order_id
+        )?;
+
+        let (native_coin_qty, native_pc_qty) =
+            block_trade.native_qtys(market.coin_lot_size, market.pc_lot_size)?;
+        let sides = [
+            (Side::Bid, bid_slot, native_pc_qty, native_coin_qty),
+            (Side::Ask, ask_slot, native_coin_qty, native_pc_qty),
+        ];
+        // Each deposit pays for the other side's, and the out frees its slot.
+        for &(side, owner_slot, native_qty_paid, native_qty_received) in sides.iter() {
+            let owner = block_trade.open_orders(side);
+            let fill = Event::new(EventView::Fill {
+                side,
+                maker: false,
+                native_qty_paid,
+                native_qty_received,
+                native_fee_or_rebate: 0,
+                order_id,
+                owner,
+                owner_slot,
+                fee_tier: FeeTier::Base,
+                client_order_id: None,
+            });
+            let out = Event::new(EventView::Out {
+                side,
+                native_qty_unlocked: 0,
+                native_qty_still_locked: 0,
+                order_id,
+                owner,
+                owner_slot,
+                client_order_id: None,
+            });
+            for event in [fill, out].iter() {
+                event_q
+                    .push_back(*event)
+                    .map_err(|_| DexErrorCode::EventQueueFull)?;
+            }
+        }
+
+        *block_trade = Zeroable::zeroed();
         Ok(())
     }
 
-    fn process_disable_market(args: account_parser::DisableMarketArgs) -> DexResult {
-        let account_parser::DisableMarketArgs {
+    fn process_cancel_block_trade(args: account_parser::CancelBlockTradeArgs) -> DexResult {
+        let account_parser::CancelBlockTradeArgs {
             market,
-            authorization: _,
+            block_trade,
+            bid_open_orders,
+            ask_open_orders,
+            signer: _,
         } = args;
-        market.account_flags = market.account_flags | (AccountFlag::Disabled as u64);
+        let order_id = identity(block_trade.order_id);
+        let (native_coin_qty, native_pc_qty) =
+            block_trade.native_qtys(market.coin_lot_size, market.pc_lot_size)?;
+        if let Some(slot) = block_trade.slot(Side::Bid) {
+            check_assert_eq!(identity(bid_open_orders.orders[slot as usize]), order_id)?;
+            bid_open_orders.remove_order(slot)?;
+            bid_open_orders.native_pc_free += native_pc_qty;
+        }
+        if let Some(slot) = block_trade.slot(Side::Ask) {
+            check_assert_eq!(identity(ask_open_orders.orders[slot as usize]), order_id)?;
+            ask_open_orders.remove_order(slot)?;
+            ask_open_orders.native_coin_free += native_coin_qty;
+        }
+        *block_trade = Zeroable::zeroed();
+        Ok(())
+    }
+
+    fn process_upgrade_market(args: account_parser::UpgradeMarketArgs) -> DexResult {
+        let account_parser::UpgradeMarketArgs { market } = args;
+        // Each step migrates from the version before it, so a market catches
+        // up however far behind it is.
+        if market.version < 2 {
+            // Version 1 stored the fee rate as a u64. InitializeMarket only
+            // ever wrote a u16 there, so the bytes now holding the version
+            // and fee schedule are already zero.
+            market.taker_fee_bps = 0;
+            market.maker_rebate_bps = 0;
+            market.version = 2;
+        }
+        if market.version < 3 {
+            // Requests already queued are still matched by MatchOrders, but
+            // no more can be added.
+            market.version = 3;
+        }
+        check_assert_eq!(market.version, MarketState::VERSION)?;
         Ok(())
     }
 
     #[cfg(feature = "program")]
-    fn process_sweep_fees(args: account_parser::SweepFeesArgs) -> DexResult {
-        let account_parser::SweepFeesArgs {
-            mut market,
+    fn process_new_trigger_order(args: account_parser::NewTriggerOrderArgs) -> DexResult {
+        let account_parser::NewTriggerOrderArgs {
+            instruction,
+            market,
+            trigger_order,
+            open_orders_address,
+            owner,
+            payer,
+            coin_vault,
             pc_vault,
-            fee_receiver,
+            spl_token_program,
+            params,
+        } = args;
+        let NewTriggerOrderInstruction {
+            ref order,
+            trigger_price,
+            trigger_condition,
+            native_tip,
+        } = *instruction;
+        check_assert!(trigger_price > 0)?;
+
+        // Executed orders pay the base fee tier, since the owner's fee
+        // discount account can't be checked without their signature.
+        let native_escrow = market.native_qty_to_lock(order, FeeTier::Base)?;
+        let deposit_amount = native_escrow
+            .checked_add(native_tip)
+            .ok_or(DexErrorCode::InsufficientFunds)?;
+        let deposit_vault = match order.side {
+            Side::Bid => {
+                market.pc_deposits_total = market
+                    .pc_deposits_total
+                    .checked_add(deposit_amount)
+                    .ok_or(assertion_error!())?;
+                pc_vault.token_account()
+            }
+            Side::Ask => {
+                market.coin_deposits_total = market
+                    .coin_deposits_total
+                    .checked_add(deposit_amount)
+                    .ok_or(assertion_error!())?;
+                coin_vault.token_account()
+            }
+        };
+        deposit_into_vault(
+            deposit_amount,
+            payer,
+            deposit_vault,
+            owner,
+            spl_token_program,
+        )?;
+
+        *trigger_order = TriggerOrder {
+            account_flags: (AccountFlag::Initialized | AccountFlag::TriggerOrder).bits(),
+            market: identity(market.own_address),
+            owner: owner.inner().key.to_aligned_bytes(),
+            open_orders: open_orders_address,
+
+            trigger_price,
+            trigger_condition: u8::from(trigger_condition).into(),
+
+            side: u8::from(order.side).into(),
+            limit_price: order.limit_price.get(),
+            max_qty: order.max_qty.get(),
+            order_type: u8::from(order.order_type).into(),
+            self_trade_behavior: u8::from(order.self_trade_behavior).into(),
+            client_id: order.client_id,
+
+            native_escrow,
+            native_tip,
+        };
+        params.trigger_order_count = params
+            .trigger_order_count
+            .checked_add(1)
+            .ok_or(assertion_error!())?;
+        Ok(())
+    }
+
+    #[cfg(feature = "program")]
+    fn process_execute_trigger_order(args: account_parser::ExecuteTriggerOrderArgs) -> DexResult {
+        let account_parser::ExecuteTriggerOrderArgs {
+            market,
+            trigger_order,
+            open_orders,
+            open_orders_address,
+            mut req_q,
+            mut event_q,
+            bids,
+            asks,
+            coin_vault,
+            pc_vault,
+            tip_wallet,
             vault_signer,
             spl_token_program,
-            authorization: _,
+            params,
+            stats,
         } = args;
-        let token_amount = market.pc_fees_accrued;
-        market.pc_fees_accrued = 0;
+        if !req_q.empty() {
+            Err(DexErrorCode::RequestQueueNotEmpty)?
+        }
+        // Recorded on the params rather than read from the event queue, which
+        // forgets its fills once they are consumed and overwritten.
+        let last_price = params
+            .last_fill_price()
+            .ok_or(DexErrorCode::TriggerPriceNotReached)?
+            .get();
+        if !trigger_order.is_triggered_at(last_price)? {
+            Err(DexErrorCode::TriggerPriceNotReached)?
+        }
+
+        let instruction = trigger_order.order()?;
+        let native_escrow = trigger_order.native_escrow;
+        let native_tip = trigger_order.native_tip;
+        *trigger_order = Zeroable::zeroed();
+        params.trigger_order_count = params
+            .trigger_order_count
+            .checked_sub(1)
+            .ok_or(assertion_error!())?;
+
+        // The escrow moves into the user account already locked.
+        let (native_pc_qty_locked, tip_vault) = match instruction.side {
+            Side::Bid => {
+                open_orders.credit_locked_pc(native_escrow);
+                market.pc_deposits_total = market
+                    .pc_deposits_total
+                    .checked_sub(native_tip)
+                    .ok_or(assertion_error!())?;
+                (NonZeroU64::new(native_escrow), pc_vault.token_account())
+            }
+            Side::Ask => {
+                open_orders.credit_locked_coin(native_escrow);
+                market.coin_deposits_total = market
+                    .coin_deposits_total
+                    .checked_sub(native_tip)
+                    .ok_or(assertion_error!())?;
+                (None, coin_vault.token_account())
+            }
+        };
 
         let nonce = market.vault_signer_nonce;
         let market_pubkey = market.pubkey();
         let vault_signer_seeds = gen_vault_signer_seeds(&nonce, &market_pubkey);
         send_from_vault(
-            token_amount,
-            fee_receiver.token_account(),
-            pc_vault.token_account(),
+            native_tip,
+            tip_wallet,
+            tip_vault,
+            spl_token_program,
+            vault_signer,
+            &vault_signer_seeds,
+        )?;
+
+        let request = Self::record_new_order(
+            market,
+            Some(&*params),
+            open_orders,
+            open_orders_address,
+            &mut req_q,
+            &instruction,
+            FeeTier::Base,
+            native_pc_qty_locked,
+            0,
+        )?;
+        let mut order_book_state = OrderBookState {
+            bids,
+            asks,
+            market_state: market,
+            self_trade_accounts: &[],
+            stats,
+            trader_stats: None,
+            params: Some(params),
+        };
+        order_book_state.process_request_directly(&request, &mut event_q)
+    }
+
+    #[cfg(feature = "program")]
+    fn process_cancel_trigger_order(args: account_parser::CancelTriggerOrderArgs) -> DexResult {
+        let account_parser::CancelTriggerOrderArgs {
+            market,
+            trigger_order,
+            owner: _,
+            refund_wallet,
+            coin_vault,
+            pc_vault,
+            vault_signer,
+            spl_token_program,
+            params,
+        } = args;
+        let side = trigger_order.side()?;
+        let native_refund = trigger_order
+            .native_escrow
+            .checked_add(trigger_order.native_tip)
+            .ok_or(assertion_error!())?;
+        *trigger_order = Zeroable::zeroed();
+        params.trigger_order_count = params
+            .trigger_order_count
+            .checked_sub(1)
+            .ok_or(assertion_error!())?;
+
+        let vault = match side {
+            Side::Bid => {
+                market.pc_deposits_total = market
+                    .pc_deposits_total
+                    .checked_sub(native_refund)
+                    .ok_or(assertion_error!())?;
+                pc_vault.token_account()
+            }
+            Side::Ask => {
+                market.coin_deposits_total = market
+                    .coin_deposits_total
+                    .checked_sub(native_refund)
+                    .ok_or(assertion_error!())?;
+                coin_vault.token_account()
+            }
+        };
+
+        let nonce = market.vault_signer_nonce;
+        let market_pubkey = market.pubkey();
+        let vault_signer_seeds = gen_vault_signer_seeds(&nonce, &market_pubkey);
+        send_from_vault(
+            native_refund,
+            refund_wallet,
+            vault,
             spl_token_program,
             vault_signer,
             &vault_signer_seeds,
         )
     }
 
+    fn process_set_open_orders_delegate(
+        args: account_parser::SetOpenOrdersDelegateArgs,
+    ) -> DexResult {
+        let account_parser::SetOpenOrdersDelegateArgs {
+            open_orders_address,
+            open_orders_delegate,
+            delegate,
+        } = args;
+        open_orders_delegate.open_orders = open_orders_address;
+        open_orders_delegate.delegate = delegate;
+        Ok(())
+    }
+
+    fn process_revoke_open_orders_delegate(
+        args: account_parser::RevokeOpenOrdersDelegateArgs,
+    ) -> DexResult {
+        *args.open_orders_delegate = Zeroable::zeroed();
+        Ok(())
+    }
+
+    fn process_set_settle_destination(args: account_parser::SetSettleDestinationArgs) -> DexResult {
+        let account_parser::SetSettleDestinationArgs {
+            open_orders_address,
+            settle_destination,
+            coin_wallet,
+            pc_wallet,
+        } = args;
+        settle_destination.open_orders = open_orders_address;
+        settle_destination.coin_wallet = coin_wallet;
+        settle_destination.pc_wallet = pc_wallet;
+        Ok(())
+    }
+
+    fn process_clear_settle_destination(
+        args: account_parser::ClearSettleDestinationArgs,
+    ) -> DexResult {
+        *args.settle_destination = Zeroable::zeroed();
+        Ok(())
+    }
+
     fn process_initialize_market(args: account_parser::InitializeMarketArgs) -> DexResult {
         let &InitializeMarketInstruction {
             coin_lot_size,
@@ -2357,6 +6782,7 @@ impl State {
             fee_rate_bps,
             vault_signer_nonce,
             pc_dust_threshold,
+            min_notional,
         } = args.instruction;
 
         let market = args.get_market();
@@ -2369,6 +6795,13 @@ impl State {
         let pc_vault = args.pc_vault_and_mint.get_account().inner();
         let pc_mint = args.pc_vault_and_mint.get_mint().inner();
 
+        validate_lot_sizes(
+            coin_lot_size,
+            pc_lot_size,
+            args.coin_vault_and_mint.get_mint().decimals()?,
+            args.pc_vault_and_mint.get_mint().decimals()?,
+        )?;
+
         // initialize request queue
         let mut rq_data = req_q.try_borrow_mut_data()?;
         const RQ_HEADER_WORDS: usize = size_of::<RequestQueueHeader>() / size_of::<u64>();
@@ -2447,9 +6880,21 @@ impl State {
             vault_signer_nonce,
 
             pc_dust_threshold,
-            fee_rate_bps: fee_rate_bps as u64,
+            fee_rate_bps,
+            version: MarketState::VERSION,
+            options: 0,
+            taker_fee_bps: 0,
+            maker_rebate_bps: 0,
             referrer_rebates_accrued: 0,
         };
+        if let Some(params_acc) = args.params {
+            let (_, mut data) = strip_header::<[u8; 0], u8>(params_acc, true)?;
+            let params: &mut MarketParams = from_bytes_mut(&mut data[..]);
+            params.account_flags = (AccountFlag::Initialized | AccountFlag::MarketParams).bits();
+            params.market = market.key.to_aligned_bytes();
+            params.min_notional = min_notional;
+            market_hdr.options = MarketOption::Params as u8;
+        }
         Ok(())
     }
 }
@@ -3,8 +3,8 @@ use std::mem::size_of;
 use std::num::NonZeroU64;
 
 use bumpalo::{collections::Vec as BumpVec, vec as bump_vec, Bump};
+use bytemuck::{bytes_of, cast_slice_mut};
 use rand::prelude::*;
-use safe_transmute::to_bytes::{transmute_to_bytes, transmute_to_bytes_mut};
 use solana_program::account_info::AccountInfo;
 use solana_program::bpf_loader;
 use solana_program::clock::Epoch;
@@ -16,15 +16,23 @@ use solana_program::sysvar;
 use solana_program::sysvar::Sysvar;
 use spl_token::state::{Account, AccountState, Mint};
 
-use instruction::{initialize_market, MarketInstruction, NewOrderInstructionV1};
+use error::{DexErrorCode, DexResult};
+use instruction::{
+    disable_authority, initialize_market, DepositBlockTradeInstruction, InitBlockTradeInstruction,
+    MarketInstruction, NewOrderInstructionV1, NewOrderInstructionV2, NewTriggerOrderInstruction,
+    SelfTradeBehavior, TriggerCondition, UpdateLotSizesInstruction,
+};
 use matching::{OrderType, Side};
-use state::gen_vault_signer_key;
-use state::{MarketState, OpenOrders, State, ToAlignedBytes};
+use state::{find_market_listing_address, gen_vault_signer_key};
+use state::{
+    BlockTrade, MarketListing, MarketOption, MarketParams, MarketState, OpenOrders,
+    OpenOrdersDelegate, SettleDestination, State, ToAlignedBytes, TriggerOrder,
+};
 
 use super::*;
 
 fn random_pubkey<'bump, G: rand::Rng>(_rng: &mut G, bump: &'bump Bump) -> &'bump Pubkey {
-    bump.alloc(Pubkey::new(transmute_to_bytes(&rand::random::<[u64; 4]>())))
+    bump.alloc(Pubkey::new(bytes_of(&rand::random::<[u64; 4]>())))
 }
 
 struct MarketAccounts<'bump> {
@@ -48,7 +56,7 @@ fn allocate_dex_owned_account(unpadded_size: usize, bump: &Bump) -> &mut [u8] {
     let aligned_len_bytes = top.wrapping_sub(bottom);
 
     let data_vec: BumpVec<'_, u64> = bump_vec![in bump; 0u64; aligned_len_bytes >> 3];
-    let data = &mut transmute_to_bytes_mut(data_vec.into_bump_slice_mut())[3..padded_size + 3];
+    let data = &mut cast_slice_mut(data_vec.into_bump_slice_mut())[3..padded_size + 3];
     data
 }
 
@@ -162,6 +170,16 @@ fn new_spl_token_program<'bump>(bump: &'bump Bump) -> AccountInfo<'bump> {
 }
 
 fn setup_market<'bump, R: Rng>(rng: &mut R, bump: &'bump Bump) -> MarketAccounts<'bump> {
+    setup_market_with_params(rng, bump, None).0
+}
+
+// Sets up a market, passing InitializeMarket a MarketParams account with the
+// given minimum notional, if any.
+fn setup_market_with_params<'bump, R: Rng>(
+    rng: &mut R,
+    bump: &'bump Bump,
+    min_notional: Option<u64>,
+) -> (MarketAccounts<'bump>, Option<AccountInfo<'bump>>) {
     let program_id = random_pubkey(rng, bump);
     let market = new_dex_owned_account(rng, size_of::<MarketState>(), program_id, bump);
     let bids = new_dex_owned_account(rng, 1 << 23, program_id, bump);
@@ -191,7 +209,7 @@ fn setup_market<'bump, R: Rng>(rng: &mut R, bump: &'bump Bump) -> MarketAccounts
 
     let pc_dust_threshold = 5;
 
-    let init_instruction = initialize_market(
+    let mut init_instruction = initialize_market(
         &market.key,
         &program_id,
         &coin_mint.key,
@@ -209,8 +227,18 @@ fn setup_market<'bump, R: Rng>(rng: &mut R, bump: &'bump Bump) -> MarketAccounts
     )
     .unwrap();
 
+    let params = min_notional.map(|min_notional| {
+        let mut instruction = match MarketInstruction::unpack(&init_instruction.data) {
+            Some(MarketInstruction::InitializeMarket(instruction)) => instruction,
+            _ => unreachable!(),
+        };
+        instruction.min_notional = min_notional;
+        init_instruction.data = MarketInstruction::InitializeMarket(instruction).pack();
+        new_dex_owned_account(rng, size_of::<MarketParams>(), program_id, bump)
+    });
+
     {
-        let accounts: &'bump [AccountInfo<'bump>] = bump_vec![in bump;
+        let mut accounts = bump_vec![in bump;
             market.clone(),
             req_q.clone(),
             event_q.clone(),
@@ -220,12 +248,17 @@ fn setup_market<'bump, R: Rng>(rng: &mut R, bump: &'bump Bump) -> MarketAccounts
             pc_vault.clone(),
             coin_mint.clone(),
             pc_mint.clone(),
-        ]
-        .into_bump_slice_mut();
-        State::process(&program_id, accounts, &init_instruction.data).unwrap();
+        ];
+        accounts.extend(params.clone());
+        State::process(
+            &program_id,
+            accounts.into_bump_slice(),
+            &init_instruction.data,
+        )
+        .unwrap();
     }
 
-    MarketAccounts {
+    let accounts = MarketAccounts {
         market,
         req_q,
         event_q,
@@ -236,9 +269,116 @@ fn setup_market<'bump, R: Rng>(rng: &mut R, bump: &'bump Bump) -> MarketAccounts
         coin_mint,
         pc_mint,
         rent_sysvar,
+    };
+    (accounts, params)
+}
+
+// A wallet trading on a market through one OpenOrders account.
+struct Trader<'bump> {
+    owner: AccountInfo<'bump>,
+    orders_account: AccountInfo<'bump>,
+    coin_account: AccountInfo<'bump>,
+    pc_account: AccountInfo<'bump>,
+}
+
+fn new_trader<'bump, R: Rng>(
+    rng: &mut R,
+    accounts: &MarketAccounts<'bump>,
+    bump: &'bump Bump,
+) -> Trader<'bump> {
+    let dex_program_id = accounts.market.owner;
+    let owner = new_sol_account(rng, 1_000_000_000, bump);
+    let orders_account = new_dex_owned_account(rng, size_of::<OpenOrders>(), dex_program_id, bump);
+    let coin_account = new_token_account(rng, accounts.coin_mint.key, owner.key, bump);
+    let pc_account = new_token_account(rng, accounts.pc_mint.key, owner.key, bump);
+    Trader {
+        owner,
+        orders_account,
+        coin_account,
+        pc_account,
+    }
+}
+
+fn limit_order(side: Side, limit_price: u64, max_qty: u64) -> NewOrderInstructionV2 {
+    NewOrderInstructionV2 {
+        side,
+        limit_price: NonZeroU64::new(limit_price).unwrap(),
+        max_qty: NonZeroU64::new(max_qty).unwrap(),
+        order_type: OrderType::Limit,
+        client_id: 0,
+        self_trade_behavior: SelfTradeBehavior::DecrementTake,
     }
 }
 
+// Places the trader's order with NewOrderV3, paying from the account of the
+// currency it locks, and passing `optional_accounts` after the fixed ones.
+fn new_order_v3<'bump>(
+    accounts: &MarketAccounts<'bump>,
+    trader: &Trader<'bump>,
+    order: NewOrderInstructionV2,
+    optional_accounts: &[&AccountInfo<'bump>],
+    bump: &'bump Bump,
+) -> DexResult {
+    let payer = match order.side {
+        Side::Bid => &trader.pc_account,
+        Side::Ask => &trader.coin_account,
+    };
+    let mut instruction_accounts = bump_vec![in bump;
+        accounts.market.clone(),
+        trader.orders_account.clone(),
+        accounts.req_q.clone(),
+        payer.clone(),
+        trader.owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        new_spl_token_program(bump),
+        accounts.rent_sysvar.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ];
+    instruction_accounts.extend(optional_accounts.iter().map(|&account| account.clone()));
+    let instruction_data = MarketInstruction::NewOrderV3(order).pack();
+    State::process(
+        accounts.market.owner,
+        instruction_accounts.into_bump_slice(),
+        &instruction_data,
+    )
+}
+
+// Cranks ConsumeEvents with the traders' OpenOrders accounts.
+fn consume_events<'bump>(
+    accounts: &MarketAccounts<'bump>,
+    traders: &[&Trader<'bump>],
+    bump: &'bump Bump,
+) {
+    let mut crank_accounts = BumpVec::new_in(bump);
+    crank_accounts.extend(traders.iter().map(|trader| trader.orders_account.clone()));
+    crank_accounts.sort_by_key(|account_info| account_info.key.to_aligned_bytes());
+    crank_accounts.extend(vec![
+        accounts.market.clone(),
+        accounts.event_q.clone(),
+        traders[0].coin_account.clone(),
+        traders[0].pc_account.clone(),
+    ]);
+    let instruction_data = MarketInstruction::ConsumeEvents(200).pack();
+    State::process(
+        accounts.market.owner,
+        crank_accounts.into_bump_slice(),
+        &instruction_data,
+    )
+    .unwrap();
+}
+
+fn load_open_orders(accounts: &MarketAccounts, trader: &Trader) -> OpenOrders {
+    let dex_program_id = accounts.market.owner;
+    let market = MarketState::load(&accounts.market, dex_program_id).unwrap();
+    let open_orders = market
+        .load_orders_mut(&trader.orders_account, None, dex_program_id, None)
+        .unwrap();
+    *open_orders
+}
+
 #[test]
 fn test_initialize_market() {
     let mut rng = StdRng::seed_from_u64(0);
@@ -256,6 +396,11 @@ fn test_new_order() {
 
     let dex_program_id = accounts.market.owner;
 
+    // A market listed before the request queue was retired.
+    MarketState::load(&accounts.market, &dex_program_id)
+        .unwrap()
+        .version = 2;
+
     let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
     let orders_account_buyer =
         new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
@@ -413,4 +558,3213 @@ fn test_new_order() {
         assert_eq!(identity(open_orders_seller.native_pc_free), 399_120);
         assert_eq!(identity(open_orders_seller.native_pc_total), 399_120);
     }
+
+    // Upgrading retires the request queue, so nothing can be queued behind
+    // the direct-matching instructions anymore.
+    State::process(
+        dex_program_id,
+        bump_vec![in &bump; accounts.market.clone()].into_bump_slice(),
+        &MarketInstruction::UpgradeMarket.pack(),
+    )
+    .unwrap();
+    let instruction_data = MarketInstruction::NewOrder(NewOrderInstructionV1 {
+        side: Side::Ask,
+        limit_price: NonZeroU64::new(99_000).unwrap(),
+        max_qty: NonZeroU64::new(1).unwrap(),
+        order_type: OrderType::Limit,
+        client_id: 0,
+    })
+    .pack();
+    assert_eq!(
+        State::process(dex_program_id, instruction_accounts, &instruction_data),
+        Err(DexErrorCode::RequestQueueRetired.into())
+    );
+    let instruction_data = MarketInstruction::CancelOrderByClientId(0xabcd).pack();
+    assert_eq!(
+        State::process(
+            dex_program_id,
+            bump_vec![in &bump;
+                accounts.market.clone(),
+                orders_account_buyer.clone(),
+                accounts.req_q.clone(),
+                owner.clone(),
+            ]
+            .into_bump_slice(),
+            &instruction_data,
+        ),
+        Err(DexErrorCode::RequestQueueRetired.into())
+    );
+}
+
+#[test]
+fn test_new_order_v3_matches_directly() {
+    let mut rng = StdRng::seed_from_u64(1);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let buyer = new_trader(&mut rng, &accounts, &bump);
+    let seller = new_trader(&mut rng, &accounts, &bump);
+
+    let bid = NewOrderInstructionV2 {
+        client_id: 0xabcd,
+        ..limit_order(Side::Bid, 100_000, 5)
+    };
+    new_order_v3(&accounts, &buyer, bid, &[], &bump).unwrap();
+    new_order_v3(
+        &accounts,
+        &seller,
+        limit_order(Side::Ask, 99_000, 4),
+        &[],
+        &bump,
+    )
+    .unwrap();
+
+    // Matched without a MatchOrders crank.
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(identity(market.referrer_rebates_accrued), 176);
+        assert_eq!(identity(market.pc_fees_accrued), 584);
+        assert_eq!(identity(market.pc_deposits_total), 500_340);
+    }
+
+    consume_events(&accounts, &[&buyer, &seller], &bump);
+    {
+        let open_orders_buyer = load_open_orders(&accounts, &buyer);
+        assert_eq!(identity(open_orders_buyer.native_coin_free), 4_000);
+        assert_eq!(identity(open_orders_buyer.native_pc_free), 1_220);
+        assert_eq!(identity(open_orders_buyer.native_pc_total), 101_220);
+        let open_orders_seller = load_open_orders(&accounts, &seller);
+        assert_eq!(identity(open_orders_seller.native_coin_total), 0);
+        assert_eq!(identity(open_orders_seller.native_pc_free), 399_120);
+    }
+
+    // Cancel the remaining bid straight off the book.
+    let instruction_data = MarketInstruction::CancelOrderByClientIdV2(0xabcd).pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        buyer.orders_account.clone(),
+        accounts.req_q.clone(),
+        buyer.owner.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    consume_events(&accounts, &[&buyer], &bump);
+    {
+        let open_orders_buyer = load_open_orders(&accounts, &buyer);
+        assert_eq!(identity(open_orders_buyer.native_pc_free), 101_220);
+        assert_eq!(identity(open_orders_buyer.free_slot_bits), std::u128::MAX);
+    }
+}
+
+#[test]
+fn test_self_trade_across_open_orders() {
+    use client::OrderBookReader;
+
+    let mut rng = StdRng::seed_from_u64(8);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let other_owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account_bids =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let orders_account_asks =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let orders_account_other =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let other_pc_account =
+        new_token_account(&mut rng, accounts.pc_mint.key, other_owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+
+    let new_order = |side: Side,
+                     orders_account: &AccountInfo<'_>,
+                     payer: &AccountInfo<'_>,
+                     signer: &AccountInfo<'_>,
+                     self_trade_accounts: &[&AccountInfo<'_>]| {
+        let instruction_data = MarketInstruction::NewOrderV3(
+            NewOrderInstructionV1 {
+                side,
+                limit_price: NonZeroU64::new(100_000).unwrap(),
+                max_qty: NonZeroU64::new(2).unwrap(),
+                order_type: OrderType::Limit,
+                client_id: 0,
+            }
+            .add_self_trade_behavior(SelfTradeBehavior::CancelProvide),
+        )
+        .pack();
+        let mut instruction_accounts = bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            accounts.req_q.clone(),
+            payer.clone(),
+            signer.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            spl_token_program.clone(),
+            accounts.rent_sysvar.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+        ];
+        instruction_accounts.extend(self_trade_accounts.iter().map(|&account| account.clone()));
+        State::process(
+            dex_program_id,
+            instruction_accounts.into_bump_slice(),
+            &instruction_data,
+        )
+    };
+    let book_quantities = |book: &AccountInfo<'_>| -> Vec<u64> {
+        let data = book.try_borrow_data().unwrap();
+        OrderBookReader::new(&data)
+            .unwrap()
+            .orders()
+            .map(|order| order.quantity())
+            .collect()
+    };
+
+    new_order(Side::Bid, &orders_account_bids, &pc_account, &owner, &[]).unwrap();
+    new_order(
+        Side::Bid,
+        &orders_account_other,
+        &other_pc_account,
+        &other_owner,
+        &[],
+    )
+    .unwrap();
+    assert_eq!(book_quantities(&accounts.bids), vec![2, 2]);
+
+    // Only OpenOrders accounts of the same owner may be passed.
+    assert_eq!(
+        new_order(
+            Side::Ask,
+            &orders_account_asks,
+            &coin_account,
+            &owner,
+            &[&orders_account_other]
+        ),
+        Err(DexErrorCode::WrongOrdersAccount.into())
+    );
+
+    // The owner's own bid is cancelled instead of filled, and the ask then
+    // trades with the other owner's bid.
+    new_order(
+        Side::Ask,
+        &orders_account_asks,
+        &coin_account,
+        &owner,
+        &[&orders_account_bids],
+    )
+    .unwrap();
+    assert_eq!(book_quantities(&accounts.bids), Vec::<u64>::new());
+    assert_eq!(book_quantities(&accounts.asks), Vec::<u64>::new());
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert!(identity(market.pc_fees_accrued) > 0);
+    }
+}
+
+#[test]
+fn test_market_stats() {
+    use solana_program::clock::Clock;
+    use state::MarketStats;
+
+    let mut rng = StdRng::seed_from_u64(10);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let bidder = new_trader(&mut rng, &accounts, &bump);
+    let asker = new_trader(&mut rng, &accounts, &bump);
+    let stats_account =
+        new_dex_owned_account(&mut rng, size_of::<MarketStats>(), dex_program_id, &bump);
+    let clock_sysvar = {
+        let data = bump_vec![in &bump; 0u8; size_of::<Clock>()].into_bump_slice_mut();
+        let mut account_info = AccountInfo::new(
+            &sysvar::clock::ID,
+            false,
+            false,
+            bump.alloc(1_000_000),
+            data,
+            &sysvar::ID,
+            false,
+            Epoch::default(),
+        );
+        let clock = Clock {
+            slot: 77,
+            ..Clock::default()
+        };
+        clock.to_account_info(&mut account_info).unwrap();
+        account_info
+    };
+
+    let new_order = |side: Side, limit_price: u64, max_qty: u64| {
+        let trader = match side {
+            Side::Bid => &bidder,
+            Side::Ask => &asker,
+        };
+        new_order_v3(
+            &accounts,
+            trader,
+            limit_order(side, limit_price, max_qty),
+            &[&stats_account, &clock_sysvar],
+            &bump,
+        )
+    };
+    let stats = || -> MarketStats {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        let stats = market
+            .load_market_stats_mut(&stats_account, &dex_program_id, None)
+            .unwrap();
+        *stats
+    };
+
+    // The first order initializes the account. Nothing trades.
+    new_order(Side::Bid, 100, 3).unwrap();
+    let initial = stats();
+    assert_eq!(
+        identity(initial.market),
+        accounts.market.key.to_aligned_bytes()
+    );
+    assert_eq!(identity(initial.trade_count), 0);
+
+    // The ask trades with both resting bids, best price first.
+    new_order(Side::Bid, 120, 1).unwrap();
+    new_order(Side::Ask, 90, 3).unwrap();
+    let after = stats();
+    assert_eq!(identity(after.trade_count), 2);
+    assert_eq!(identity(after.coin_volume), 3 * 1_000);
+    assert_eq!(identity(after.pc_volume), 120 + 2 * 100);
+    assert_eq!(identity(after.last_price), 100);
+    assert_eq!(identity(after.last_trade_slot), 77);
+    assert_eq!(identity(after.self_cross_count), 0);
+
+    // Crossing the owner's own bid, from the same OpenOrders account and
+    // then from another one passed with the order, counts but doesn't trade.
+    let sibling = Trader {
+        owner: bidder.owner.clone(),
+        orders_account: new_dex_owned_account(
+            &mut rng,
+            size_of::<OpenOrders>(),
+            dex_program_id,
+            &bump,
+        ),
+        coin_account: bidder.coin_account.clone(),
+        pc_account: bidder.pc_account.clone(),
+    };
+    for &(trader, self_trade_accounts) in &[
+        (&bidder, &[][..]),
+        (&sibling, &[&bidder.orders_account][..]),
+    ] {
+        new_order(Side::Bid, 100, 1).unwrap();
+        let mut optional_accounts = self_trade_accounts.to_vec();
+        optional_accounts.extend(&[&stats_account, &clock_sysvar]);
+        new_order_v3(
+            &accounts,
+            trader,
+            limit_order(Side::Ask, 100, 1),
+            &optional_accounts,
+            &bump,
+        )
+        .unwrap();
+    }
+    let after = stats();
+    assert_eq!(identity(after.trade_count), 2);
+    assert_eq!(identity(after.self_cross_count), 1);
+    assert_eq!(identity(after.same_owner_cross_count), 1);
+}
+
+#[test]
+fn test_new_order_batch() {
+    use client::OrderBookReader;
+    use instruction::NewOrderBatchInstruction;
+
+    let mut rng = StdRng::seed_from_u64(1);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account_maker =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let orders_account_seller =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+
+    let instruction_data = MarketInstruction::NewOrderV3(
+        NewOrderInstructionV1 {
+            side: Side::Ask,
+            limit_price: NonZeroU64::new(99_500).unwrap(),
+            max_qty: NonZeroU64::new(4).unwrap(),
+            order_type: OrderType::Limit,
+            client_id: 0,
+        }
+        .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+    )
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account_seller.clone(),
+        accounts.req_q.clone(),
+        coin_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    // The first bid takes the resting ask, the rest are quotes.
+    let orders = [
+        (Side::Bid, 100_000, 5, 1),
+        (Side::Bid, 99_000, 2, 2),
+        (Side::Ask, 105_000, 3, 3),
+    ]
+    .iter()
+    .map(
+        |&(side, limit_price, max_qty, client_id)| NewOrderInstructionV2 {
+            side,
+            limit_price: NonZeroU64::new(limit_price).unwrap(),
+            max_qty: NonZeroU64::new(max_qty).unwrap(),
+            order_type: OrderType::Limit,
+            client_id,
+            self_trade_behavior: SelfTradeBehavior::DecrementTake,
+        },
+    )
+    .collect();
+    let instruction_data =
+        MarketInstruction::NewOrderBatch(NewOrderBatchInstruction { orders }).pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account_maker.clone(),
+        accounts.req_q.clone(),
+        coin_account.clone(),
+        pc_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(identity(market.coin_deposits_total), 7_000);
+        let open_orders = market
+            .load_orders_mut(&orders_account_maker, None, &dex_program_id, None)
+            .unwrap();
+        assert_eq!(open_orders.free_slot_count(), 125);
+        assert_eq!(open_orders.resting_order_count(Side::Bid), 2);
+        assert_eq!(open_orders.resting_order_count(Side::Ask), 1);
+        let resting: Vec<(u8, u64, Side)> = open_orders
+            .iter_orders()
+            .map(|(slot, _, client_order_id, side)| (slot, client_order_id, side))
+            .collect();
+        assert_eq!(
+            resting,
+            vec![(0, 1, Side::Bid), (1, 2, Side::Bid), (2, 3, Side::Ask)]
+        );
+        assert_eq!(identity(open_orders.native_coin_total), 3_000);
+    }
+
+    let bids_data = accounts.bids.try_borrow_data().unwrap();
+    let bids = OrderBookReader::new(&bids_data).unwrap();
+    let bid_quantities: Vec<(u64, u64)> = bids
+        .orders()
+        .map(|order| (order.price().get(), order.quantity()))
+        .collect();
+    assert_eq!(bid_quantities, vec![(100_000, 1), (99_000, 2)]);
+    let asks_data = accounts.asks.try_borrow_data().unwrap();
+    let asks = OrderBookReader::new(&asks_data).unwrap();
+    assert_eq!(asks.orders().count(), 1);
+
+    // Batches must hold between one and MAX_BATCH_ORDERS orders.
+    let empty = MarketInstruction::NewOrderBatch(NewOrderBatchInstruction { orders: vec![] });
+    assert_eq!(MarketInstruction::unpack(&empty.pack()), None);
+    let order = NewOrderInstructionV2 {
+        side: Side::Bid,
+        limit_price: NonZeroU64::new(1).unwrap(),
+        max_qty: NonZeroU64::new(1).unwrap(),
+        order_type: OrderType::PostOnly,
+        client_id: 0,
+        self_trade_behavior: SelfTradeBehavior::DecrementTake,
+    };
+    let full = MarketInstruction::NewOrderBatch(NewOrderBatchInstruction {
+        orders: vec![order.clone(); instruction::MAX_BATCH_ORDERS],
+    });
+    assert_eq!(MarketInstruction::unpack(&full.pack()), Some(full));
+    let too_many = MarketInstruction::NewOrderBatch(NewOrderBatchInstruction {
+        orders: vec![order; instruction::MAX_BATCH_ORDERS + 1],
+    });
+    assert_eq!(MarketInstruction::unpack(&too_many.pack()), None);
+}
+
+#[test]
+fn test_mass_quote() {
+    use client::OrderBookReader;
+    use instruction::NewOrderBatchInstruction;
+
+    let mut rng = StdRng::seed_from_u64(1);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account.clone(),
+        accounts.req_q.clone(),
+        coin_account.clone(),
+        pc_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+    let ladder = |orders: &[(Side, u64, u64)]| NewOrderBatchInstruction {
+        orders: orders
+            .iter()
+            .map(|&(side, limit_price, client_id)| NewOrderInstructionV2 {
+                side,
+                limit_price: NonZeroU64::new(limit_price).unwrap(),
+                max_qty: NonZeroU64::new(2).unwrap(),
+                order_type: OrderType::PostOnly,
+                client_id,
+                self_trade_behavior: SelfTradeBehavior::DecrementTake,
+            })
+            .collect(),
+    };
+    let book_prices = |account: &AccountInfo| -> Vec<u64> {
+        let data = account.try_borrow_data().unwrap();
+        let book = OrderBookReader::new(&data).unwrap();
+        book.orders().map(|order| order.price().get()).collect()
+    };
+
+    let instruction_data = MarketInstruction::NewOrderBatch(ladder(&[
+        (Side::Bid, 99_000, 1),
+        (Side::Bid, 98_000, 2),
+        (Side::Ask, 101_000, 3),
+    ]))
+    .pack();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    let open_orders = || {
+        *MarketState::load(&accounts.market, &dex_program_id)
+            .unwrap()
+            .load_orders_mut(&orders_account, None, &dex_program_id, None)
+            .unwrap()
+    };
+    let used_slots = || identity(open_orders().free_slot_bits).count_zeros();
+    let totals = || {
+        let open_orders = open_orders();
+        (
+            identity(open_orders.native_coin_total),
+            identity(open_orders.native_pc_total),
+        )
+    };
+
+    // Requoting replaces the whole ladder, in the cancelled orders' slots
+    // and paid for with their funds, so nothing more is deposited.
+    let totals_before = totals();
+    let instruction_data =
+        MarketInstruction::MassQuote(ladder(&[(Side::Bid, 99_500, 4), (Side::Ask, 100_500, 5)]))
+            .pack();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    assert_eq!(book_prices(&accounts.bids), vec![99_500]);
+    assert_eq!(book_prices(&accounts.asks), vec![100_500]);
+    assert_eq!(used_slots(), 2);
+    assert_eq!(totals(), totals_before);
+
+    // A quote with a fill waiting for ConsumeEvents keeps its slot until
+    // the fill is consumed.
+    let quoter = Trader {
+        owner: owner.clone(),
+        orders_account: orders_account.clone(),
+        coin_account: coin_account.clone(),
+        pc_account: pc_account.clone(),
+    };
+    let taker = new_trader(&mut rng, &accounts, &bump);
+    new_order_v3(
+        &accounts,
+        &taker,
+        limit_order(Side::Ask, 99_500, 1),
+        &[],
+        &bump,
+    )
+    .unwrap();
+
+    // An empty ladder only cancels.
+    let instruction_data =
+        MarketInstruction::MassQuote(NewOrderBatchInstruction { orders: vec![] }).pack();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    assert!(book_prices(&accounts.bids).is_empty());
+    assert!(book_prices(&accounts.asks).is_empty());
+    assert_eq!(used_slots(), 1);
+    consume_events(&accounts, &[&quoter, &taker], &bump);
+    assert_eq!(used_slots(), 0);
+    let open_orders = open_orders();
+    assert_eq!(
+        identity(open_orders.native_coin_free),
+        identity(open_orders.native_coin_total)
+    );
+    assert_eq!(
+        identity(open_orders.native_pc_free),
+        identity(open_orders.native_pc_total)
+    );
+}
+
+#[test]
+fn test_parse_order_logs() {
+    use client::{parse_order_logs, PlacedOrder};
+    use state::ORDER_LOG_TAG;
+
+    let order_id = (99_000u128 << 64) | 7;
+    let logs = vec![
+        "Program log: Instruction: NewOrderBatch".to_string(),
+        format!(
+            "Program log: {:#x}, 0x1, {:#x}, {:#x}, 0x2",
+            ORDER_LOG_TAG,
+            order_id as u64,
+            (order_id >> 64) as u64
+        ),
+        "Program log: 0x1, 0x2, 0x3, 0x4, 0x5".to_string(),
+    ];
+    assert_eq!(
+        parse_order_logs(logs.iter().map(String::as_str)),
+        vec![PlacedOrder {
+            index: 1,
+            order_id,
+            resting_qty: 2,
+        }]
+    );
+}
+
+#[test]
+fn test_consume_events_from() {
+    use client::EventQueueReader;
+    use instruction::ConsumeEventsFromInstruction;
+
+    let mut rng = StdRng::seed_from_u64(1);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account_buyer =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let orders_account_seller =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+
+    for &(side, limit_price, max_qty, orders_account, payer) in &[
+        (Side::Bid, 100_000, 5, &orders_account_buyer, &pc_account),
+        (Side::Ask, 99_000, 4, &orders_account_seller, &coin_account),
+    ] {
+        let instruction_data = MarketInstruction::NewOrderV3(
+            NewOrderInstructionV1 {
+                side,
+                limit_price: NonZeroU64::new(limit_price).unwrap(),
+                max_qty: NonZeroU64::new(max_qty).unwrap(),
+                order_type: OrderType::Limit,
+                client_id: 0,
+            }
+            .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+        )
+        .pack();
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            accounts.req_q.clone(),
+            payer.clone(),
+            owner.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            spl_token_program.clone(),
+            accounts.rent_sysvar.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+        ]
+        .into_bump_slice();
+        State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    }
+
+    let buyer_crank_accounts: &[AccountInfo] = bump_vec![in &bump;
+        orders_account_buyer.clone(),
+        accounts.market.clone(),
+        accounts.event_q.clone(),
+        coin_account.clone(),
+        pc_account.clone(),
+    ]
+    .into_bump_slice();
+    let seller_crank_accounts: &[AccountInfo] = bump_vec![in &bump;
+        orders_account_seller.clone(),
+        accounts.market.clone(),
+        accounts.event_q.clone(),
+        coin_account.clone(),
+        pc_account.clone(),
+    ]
+    .into_bump_slice();
+    let consume = |crank_accounts: &[AccountInfo], instruction: MarketInstruction| {
+        State::process(dex_program_id, crank_accounts, &instruction.pack()).unwrap();
+    };
+    let queue_len = || {
+        let data = accounts.event_q.try_borrow_data().unwrap();
+        EventQueueReader::new(&data).unwrap().len()
+    };
+    let pc_free = |orders_account: &AccountInfo| {
+        let open_orders = MarketState::load(&accounts.market, &dex_program_id)
+            .unwrap()
+            .load_orders_mut(orders_account, None, &dex_program_id, None)
+            .unwrap();
+        identity(open_orders.native_pc_free)
+    };
+
+    // The buyer's maker fill is at the front, so consuming in order stops
+    // right away without the buyer's account.
+    let queued = queue_len();
+    consume(seller_crank_accounts, MarketInstruction::ConsumeEvents(200));
+    assert_eq!(queue_len(), queued);
+    assert_eq!(pc_free(&orders_account_seller), 0);
+
+    let pending_seq_nums = |owner: &Pubkey| -> Vec<u64> {
+        let data = accounts.event_q.try_borrow_data().unwrap();
+        EventQueueReader::new(&data)
+            .unwrap()
+            .iter()
+            .map(Result::unwrap)
+            .filter(|(_seq_num, event)| event.owner() == owner)
+            .map(|(seq_num, _event)| seq_num)
+            .collect()
+    };
+    let buyer_seq_nums = pending_seq_nums(orders_account_buyer.key);
+
+    // Skipping past it consumes the seller's events, and frees their slots
+    // even though the buyer's are still in front.
+    consume(
+        seller_crank_accounts,
+        MarketInstruction::ConsumeEventsFrom(ConsumeEventsFromInstruction {
+            start_seq_num: 0,
+            limit: 200,
+        }),
+    );
+    assert_eq!(pc_free(&orders_account_seller), 399_120);
+    assert!(pending_seq_nums(orders_account_seller.key).is_empty());
+    assert!(!buyer_seq_nums.is_empty() && buyer_seq_nums.len() < queued);
+    assert_eq!(queue_len(), buyer_seq_nums.len());
+    // The buyer's events moved back past the freed slots, so a cursor kept
+    // on them can only be behind.
+    for (before, after) in buyer_seq_nums
+        .iter()
+        .zip(pending_seq_nums(orders_account_buyer.key))
+    {
+        assert!(after >= *before);
+    }
+
+    // Consuming them again is a no-op.
+    consume(
+        seller_crank_accounts,
+        MarketInstruction::ConsumeEventsFrom(ConsumeEventsFromInstruction {
+            start_seq_num: 0,
+            limit: 200,
+        }),
+    );
+    assert_eq!(pc_free(&orders_account_seller), 399_120);
+
+    consume(buyer_crank_accounts, MarketInstruction::ConsumeEvents(200));
+    assert_eq!(pc_free(&orders_account_buyer), 1_220);
+    assert_eq!(queue_len(), 0);
+}
+
+#[test]
+fn test_registry_stake_fee_tier() {
+    use client::OrderBookReader;
+    use fees::FeeTier;
+    use instruction::{registry, registry_srm_registrar};
+
+    let mut rng = StdRng::seed_from_u64(5);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+
+    // A registry member staking 1 MSRM.
+    let new_member = |rng: &mut StdRng, registrar: &Pubkey, beneficiary: &Pubkey| {
+        let mut data = [0u8; 213];
+        data[0] = 1;
+        data[33..65].copy_from_slice(beneficiary.as_ref());
+        data[105..113].copy_from_slice(&1u64.to_le_bytes());
+        data[169..201].copy_from_slice(registrar.as_ref());
+        AccountInfo::new(
+            random_pubkey(rng, &bump),
+            false,
+            false,
+            bump.alloc(0),
+            bump.alloc_slice_copy(&data),
+            &registry::ID,
+            false,
+            Epoch::default(),
+        )
+    };
+    let member = new_member(&mut rng, &registry_srm_registrar::ID, owner.key);
+    let other_member = new_member(&mut rng, &registry_srm_registrar::ID, accounts.market.key);
+    let foreign_registrar = random_pubkey(&mut rng, &bump);
+    let foreign_member = new_member(&mut rng, foreign_registrar, owner.key);
+
+    let instruction_data = MarketInstruction::NewOrderV3(
+        NewOrderInstructionV1 {
+            side: Side::Bid,
+            limit_price: NonZeroU64::new(100_000).unwrap(),
+            max_qty: NonZeroU64::new(5).unwrap(),
+            order_type: OrderType::Limit,
+            client_id: 0,
+        }
+        .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+    )
+    .pack();
+    let new_order_accounts = |member: &AccountInfo<'_>| -> &[AccountInfo] {
+        bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            accounts.req_q.clone(),
+            pc_account.clone(),
+            owner.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            spl_token_program.clone(),
+            accounts.rent_sysvar.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+            member.clone(),
+        ]
+        .into_bump_slice()
+    };
+
+    // Another wallet's stake doesn't count, nor does stake in a registrar
+    // other than the SRM one.
+    for member in &[&other_member, &foreign_member] {
+        assert_eq!(
+            State::process(
+                dex_program_id,
+                new_order_accounts(member),
+                &instruction_data
+            ),
+            Err(DexErrorCode::WrongRegistryMemberAccount.into())
+        );
+    }
+
+    State::process(
+        dex_program_id,
+        new_order_accounts(&member),
+        &instruction_data,
+    )
+    .unwrap();
+    let bids_data = accounts.bids.try_borrow_data().unwrap();
+    let bids = OrderBookReader::new(&bids_data).unwrap();
+    let order = bids.orders().next().unwrap();
+    assert_eq!(order.fee_tier(), FeeTier::MSRM);
+}
+
+#[test]
+fn test_lockup_fee_tier() {
+    use client::OrderBookReader;
+    use fees::FeeTier;
+    use instruction::{lockup, lockup_srm_safe};
+
+    let mut rng = StdRng::seed_from_u64(6);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+
+    // A vesting account locking 150,000 SRM, 50,000 of which are staked
+    // through the whitelist and don't count.
+    let new_vesting = |rng: &mut StdRng, safe: &Pubkey, beneficiary: &Pubkey| {
+        let mut data = [0u8; 178];
+        data[0] = 1;
+        data[2..34].copy_from_slice(safe.as_ref());
+        data[34..66].copy_from_slice(beneficiary.as_ref());
+        data[66..74].copy_from_slice(&150_000_000_000u64.to_le_bytes());
+        data[170..178].copy_from_slice(&50_000_000_000u64.to_le_bytes());
+        AccountInfo::new(
+            random_pubkey(rng, &bump),
+            false,
+            false,
+            bump.alloc(0),
+            bump.alloc_slice_copy(&data),
+            &lockup::ID,
+            false,
+            Epoch::default(),
+        )
+    };
+    let vesting = new_vesting(&mut rng, &lockup_srm_safe::ID, owner.key);
+    let other_vesting = new_vesting(&mut rng, &lockup_srm_safe::ID, accounts.market.key);
+    let other_safe = random_pubkey(&mut rng, &bump);
+    let other_safe_vesting = new_vesting(&mut rng, other_safe, owner.key);
+
+    let instruction_data = MarketInstruction::NewOrderV3(
+        NewOrderInstructionV1 {
+            side: Side::Bid,
+            limit_price: NonZeroU64::new(100_000).unwrap(),
+            max_qty: NonZeroU64::new(5).unwrap(),
+            order_type: OrderType::Limit,
+            client_id: 0,
+        }
+        .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+    )
+    .pack();
+    let new_order_accounts = |vesting: &AccountInfo<'_>| -> &[AccountInfo] {
+        bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            accounts.req_q.clone(),
+            pc_account.clone(),
+            owner.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            spl_token_program.clone(),
+            accounts.rent_sysvar.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+            vesting.clone(),
+        ]
+        .into_bump_slice()
+    };
+
+    // Another wallet's grant doesn't count, nor does a safe of another mint.
+    for vesting in &[&other_vesting, &other_safe_vesting] {
+        assert_eq!(
+            State::process(
+                dex_program_id,
+                new_order_accounts(vesting),
+                &instruction_data
+            ),
+            Err(DexErrorCode::WrongLockupVestingAccount.into())
+        );
+    }
+
+    State::process(
+        dex_program_id,
+        new_order_accounts(&vesting),
+        &instruction_data,
+    )
+    .unwrap();
+    let bids_data = accounts.bids.try_borrow_data().unwrap();
+    let bids = OrderBookReader::new(&bids_data).unwrap();
+    let order = bids.orders().next().unwrap();
+    assert_eq!(order.fee_tier(), FeeTier::SRM5);
+}
+
+#[test]
+fn test_resize_market_accounts() {
+    let mut rng = StdRng::seed_from_u64(2);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account_buyer =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let orders_account_seller =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+
+    let instruction_data = MarketInstruction::NewOrderV3(
+        NewOrderInstructionV1 {
+            side: Side::Bid,
+            limit_price: NonZeroU64::new(100_000).unwrap(),
+            max_qty: NonZeroU64::new(5).unwrap(),
+            order_type: OrderType::Limit,
+            client_id: 0xabcd,
+        }
+        .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+    )
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account_buyer.clone(),
+        accounts.req_q.clone(),
+        pc_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    let new_event_q = new_dex_owned_account(&mut rng, 1 << 17, dex_program_id, &bump);
+    let new_bids = new_dex_owned_account(&mut rng, 1 << 24, dex_program_id, &bump);
+    let new_asks = new_dex_owned_account(&mut rng, 1 << 24, dex_program_id, &bump);
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let instruction_data = MarketInstruction::ResizeMarketAccounts.pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+        new_event_q.clone(),
+        new_bids.clone(),
+        new_asks.clone(),
+        authority.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    // The old accounts are no longer part of the market.
+    let instruction_data = MarketInstruction::CancelOrderByClientIdV2(0xabcd).pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account_buyer.clone(),
+        accounts.req_q.clone(),
+        owner.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+    assert!(State::process(dex_program_id, instruction_accounts, &instruction_data).is_err());
+
+    // The resting bid was carried over and matches against the new book.
+    let instruction_data = MarketInstruction::NewOrderV3(
+        NewOrderInstructionV1 {
+            side: Side::Ask,
+            limit_price: NonZeroU64::new(99_000).unwrap(),
+            max_qty: NonZeroU64::new(4).unwrap(),
+            order_type: OrderType::Limit,
+            client_id: 0,
+        }
+        .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+    )
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account_seller.clone(),
+        accounts.req_q.clone(),
+        coin_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        new_event_q.clone(),
+        new_bids.clone(),
+        new_asks.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(identity(market.event_q), new_event_q.key.to_aligned_bytes());
+        assert_eq!(identity(market.referrer_rebates_accrued), 176);
+        assert_eq!(identity(market.pc_fees_accrued), 584);
+    }
+}
+
+#[test]
+fn test_market_state_golden() {
+    // Each word holds its own offset, so a reordered or resized field shows
+    // up as a shifted run of values in the fixture diff.
+    let market = MarketState {
+        account_flags: 0,
+        own_address: [1, 2, 3, 4],
+        vault_signer_nonce: 5,
+        coin_mint: [6, 7, 8, 9],
+        pc_mint: [10, 11, 12, 13],
+        coin_vault: [14, 15, 16, 17],
+        coin_deposits_total: 18,
+        coin_fees_accrued: 19,
+        pc_vault: [20, 21, 22, 23],
+        pc_deposits_total: 24,
+        pc_fees_accrued: 25,
+        pc_dust_threshold: 26,
+        req_q: [27, 28, 29, 30],
+        event_q: [31, 32, 33, 34],
+        bids: [35, 36, 37, 38],
+        asks: [39, 40, 41, 42],
+        coin_lot_size: 43,
+        pc_lot_size: 44,
+        fee_rate_bps: 45,
+        version: 0,
+        options: 0,
+        taker_fee_bps: 0,
+        maker_rebate_bps: 0,
+        referrer_rebates_accrued: 46,
+    };
+    let hex: String = bytes_of(&market)
+        .chunks(32)
+        .map(|line| {
+            let mut line: String = line.iter().map(|b| format!("{:02x}", b)).collect();
+            line.push('\n');
+            line
+        })
+        .collect();
+    assert_eq!(hex, include_str!("../tests/fixtures/market_state.hex"));
+}
+
+#[test]
+fn test_update_lot_sizes() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let update_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        accounts.req_q.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+        authority.clone(),
+    ]
+    .into_bump_slice();
+    let instruction_data = MarketInstruction::UpdateLotSizes(UpdateLotSizesInstruction {
+        coin_lot_size: 100,
+        pc_lot_size: 10,
+    })
+    .pack();
+    State::process(dex_program_id, update_accounts, &instruction_data).unwrap();
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(identity(market.coin_lot_size), 100);
+        assert_eq!(identity(market.pc_lot_size), 10);
+    }
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+    // Queued on a market that hasn't retired its request queue yet.
+    MarketState::load(&accounts.market, &dex_program_id)
+        .unwrap()
+        .version = 2;
+    let instruction_data = MarketInstruction::NewOrder(NewOrderInstructionV1 {
+        side: Side::Bid,
+        limit_price: NonZeroU64::new(10).unwrap(),
+        max_qty: NonZeroU64::new(5).unwrap(),
+        order_type: OrderType::Limit,
+        client_id: 0,
+    })
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account.clone(),
+        accounts.req_q.clone(),
+        pc_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    let instruction_data = MarketInstruction::UpdateLotSizes(UpdateLotSizesInstruction {
+        coin_lot_size: 1_000,
+        pc_lot_size: 1,
+    })
+    .pack();
+    assert_eq!(
+        State::process(dex_program_id, update_accounts, &instruction_data),
+        Err(DexErrorCode::OrderBookNotEmpty.into())
+    );
+}
+
+#[test]
+fn test_update_lot_sizes_with_trigger_order() {
+    let mut rng = StdRng::seed_from_u64(3);
+    let bump = Bump::new();
+
+    let (accounts, params_account) = setup_market_with_params(&mut rng, &bump, Some(0));
+    let params_account = params_account.unwrap();
+
+    let dex_program_id = accounts.market.owner;
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let trigger_account =
+        new_dex_owned_account(&mut rng, size_of::<TriggerOrder>(), dex_program_id, &bump);
+    let coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+    let vault_signer = AccountInfo::new(
+        bump.alloc(gen_vault_signer_key(0, accounts.market.key, dex_program_id).unwrap()),
+        false,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+
+    let new_trigger_data = MarketInstruction::NewTriggerOrder(NewTriggerOrderInstruction {
+        order: limit_order(Side::Ask, 100_000, 1),
+        trigger_price: 100_000,
+        trigger_condition: TriggerCondition::PriceAtOrBelow,
+        native_tip: 0,
+    })
+    .pack();
+    let new_trigger_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        trigger_account.clone(),
+        orders_account.clone(),
+        coin_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        params_account.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, new_trigger_accounts, &new_trigger_data).unwrap();
+
+    let update_lot_sizes = |params: Option<&AccountInfo>| {
+        let mut update_accounts = bump_vec![in &bump;
+            accounts.market.clone(),
+            accounts.req_q.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+            authority.clone(),
+        ];
+        update_accounts.extend(params.cloned());
+        let instruction_data = MarketInstruction::UpdateLotSizes(UpdateLotSizesInstruction {
+            coin_lot_size: 100,
+            pc_lot_size: 10,
+        })
+        .pack();
+        State::process(
+            dex_program_id,
+            update_accounts.into_bump_slice(),
+            &instruction_data,
+        )
+    };
+
+    // The book is empty, but the trigger order's prices are in lots.
+    assert_eq!(
+        update_lot_sizes(None),
+        Err(DexErrorCode::MarketParamsNotProvided.into())
+    );
+    assert_eq!(
+        update_lot_sizes(Some(&params_account)),
+        Err(DexErrorCode::TriggerOrdersPending.into())
+    );
+
+    let cancel_data = MarketInstruction::CancelTriggerOrder.pack();
+    let cancel_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        trigger_account.clone(),
+        owner.clone(),
+        coin_account.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        vault_signer.clone(),
+        spl_token_program.clone(),
+        params_account.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, cancel_accounts, &cancel_data).unwrap();
+    update_lot_sizes(Some(&params_account)).unwrap();
+    let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+    assert_eq!(identity(market.coin_lot_size), 100);
+    assert_eq!(identity(market.pc_lot_size), 10);
+}
+
+#[test]
+fn test_lot_size_derivation() {
+    use client::LotSizes;
+    use state::validate_lot_sizes;
+
+    // BTC/USDC style: sizes to 0.0001 BTC, prices to 0.1 USDC.
+    assert_eq!(
+        LotSizes::derive(6, 6, 4, 1).unwrap(),
+        LotSizes {
+            coin_lot_size: 100,
+            pc_lot_size: 10,
+        }
+    );
+    assert_eq!(
+        LotSizes::derive(9, 6, 0, 3).unwrap(),
+        LotSizes {
+            coin_lot_size: 1_000_000_000,
+            pc_lot_size: 1_000,
+        }
+    );
+    // More precision than the mints have.
+    assert!(LotSizes::derive(6, 6, 7, 0).is_err());
+    assert!(LotSizes::derive(6, 6, 4, 3).is_err());
+
+    assert!(validate_lot_sizes(100, 10, 6, 6).is_ok());
+    assert!(validate_lot_sizes(0, 10, 6, 6).is_err());
+    assert!(validate_lot_sizes(100, 0, 6, 6).is_err());
+    assert!(validate_lot_sizes(100, 10, 20, 6).is_err());
+    assert!(validate_lot_sizes(100, 10, 6, 20).is_err());
+    assert!(validate_lot_sizes(100, std::u64::MAX / 100, 6, 6).is_err());
+}
+
+#[test]
+fn test_set_fee_schedule() {
+    use fees::{FeeSchedule, FeeTier};
+    use instruction::SetFeeScheduleInstruction;
+
+    let mut rng = StdRng::seed_from_u64(4);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let instruction_accounts: &[AccountInfo] =
+        bump_vec![in &bump; accounts.market.clone(), authority.clone()].into_bump_slice();
+    let set_fee_schedule = |taker_fee_bps, maker_rebate_bps| {
+        let instruction_data = MarketInstruction::SetFeeSchedule(SetFeeScheduleInstruction {
+            taker_fee_bps,
+            maker_rebate_bps,
+        })
+        .pack();
+        State::process(dex_program_id, instruction_accounts, &instruction_data)
+    };
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(market.fee_schedule(), FeeSchedule::DEFAULT);
+    }
+
+    set_fee_schedule(30, 8).unwrap();
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        let schedule = market.fee_schedule();
+        assert_eq!(schedule.taker_fee(FeeTier::Base, 10_000), 30);
+        assert_eq!(schedule.taker_fee(FeeTier::MSRM, 10_000), 18);
+        assert_eq!(schedule.maker_rebate(FeeTier::Base, 10_000), 8);
+        assert_eq!(schedule.maker_rebate(FeeTier::MSRM, 10_000), 10);
+    }
+
+    // Rebates the taker fees can't fund are rejected.
+    assert_eq!(
+        set_fee_schedule(30, 13),
+        Err(DexErrorCode::InvalidFeeSchedule.into())
+    );
+    assert_eq!(
+        set_fee_schedule(FeeSchedule::MAX_TAKER_FEE_BPS + 1, 0),
+        Err(DexErrorCode::InvalidFeeSchedule.into())
+    );
+    let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+    assert_eq!(identity(market.taker_fee_bps), 30);
+}
+
+#[test]
+fn test_unique_client_ids() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let set_options_accounts: &[AccountInfo] =
+        bump_vec![in &bump; accounts.market.clone(), authority.clone()].into_bump_slice();
+    let set_market_options = |options| {
+        let instruction_data = MarketInstruction::SetMarketOptions(options).pack();
+        State::process(dex_program_id, set_options_accounts, &instruction_data)
+    };
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+    let order_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account.clone(),
+        accounts.req_q.clone(),
+        pc_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+    let new_bid = |client_id| {
+        let instruction_data = MarketInstruction::NewOrderV3(
+            NewOrderInstructionV1 {
+                side: Side::Bid,
+                limit_price: NonZeroU64::new(100_000).unwrap(),
+                max_qty: NonZeroU64::new(1).unwrap(),
+                order_type: OrderType::Limit,
+                client_id,
+            }
+            .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+        )
+        .pack();
+        State::process(dex_program_id, order_accounts, &instruction_data)
+    };
+
+    // Duplicates coexist until the market opts in.
+    new_bid(7).unwrap();
+    new_bid(7).unwrap();
+
+    assert_eq!(
+        set_market_options(0b10),
+        Err(DexErrorCode::InvalidMarketOptions.into())
+    );
+    set_market_options(MarketOption::UniqueClientIds as u8).unwrap();
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert!(market.options().contains(MarketOption::UniqueClientIds));
+    }
+
+    assert_eq!(new_bid(7), Err(DexErrorCode::DuplicateClientOrderId.into()));
+    new_bid(8).unwrap();
+    assert_eq!(new_bid(8), Err(DexErrorCode::DuplicateClientOrderId.into()));
+    // Zero means no client id.
+    new_bid(0).unwrap();
+    new_bid(0).unwrap();
+}
+
+#[test]
+fn test_market_params() {
+    use instruction::SetMarketParamsInstruction;
+
+    let mut rng = StdRng::seed_from_u64(11);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let oracle = new_sol_account(&mut rng, 0, &bump);
+    let params_account =
+        new_dex_owned_account(&mut rng, size_of::<MarketParams>(), dex_program_id, &bump);
+    let other_params_account =
+        new_dex_owned_account(&mut rng, size_of::<MarketParams>(), dex_program_id, &bump);
+    let set_market_params = |other_account: bool, max_deviation_bps| {
+        let instruction_data = MarketInstruction::SetMarketParams(SetMarketParamsInstruction {
+            oracle: oracle.key.to_aligned_bytes(),
+            max_deviation_bps,
+            min_notional: 0,
+            prune_authority: [0; 4],
+        })
+        .pack();
+        let params_account = if other_account {
+            &other_params_account
+        } else {
+            &params_account
+        };
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            params_account.clone(),
+            authority.clone(),
+            accounts.rent_sysvar.clone(),
+        ]
+        .into_bump_slice();
+        State::process(dex_program_id, instruction_accounts, &instruction_data)
+    };
+    let set_market_options = |options| {
+        let instruction_data = MarketInstruction::SetMarketOptions(options).pack();
+        let instruction_accounts: &[AccountInfo] =
+            bump_vec![in &bump; accounts.market.clone(), authority.clone()].into_bump_slice();
+        State::process(dex_program_id, instruction_accounts, &instruction_data)
+    };
+
+    let trader = new_trader(&mut rng, &accounts, &bump);
+    let new_bid = |limit_price| {
+        new_order_v3(
+            &accounts,
+            &trader,
+            limit_order(Side::Bid, limit_price, 1),
+            &[&params_account],
+            &bump,
+        )
+    };
+    let params = || -> MarketParams {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        let params = market
+            .load_market_params_mut(&params_account, &dex_program_id)
+            .unwrap();
+        *params
+    };
+
+    // A 10% band, off until the oracle reports.
+    set_market_params(false, 1_000).unwrap();
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert!(market.options().contains(MarketOption::Params));
+    }
+    assert_eq!(identity(params().oracle), oracle.key.to_aligned_bytes());
+    assert_eq!(identity(params().max_deviation_bps), 1_000);
+    assert_eq!(
+        new_order_v3(&accounts, &trader, limit_order(Side::Bid, 1, 1), &[], &bump),
+        Err(DexErrorCode::MarketParamsNotProvided.into())
+    );
+    new_bid(1).unwrap();
+
+    // Only the oracle reports.
+    let reports: Vec<(&AccountInfo, DexResult)> = vec![
+        (&trader.owner, Err(DexErrorCode::WrongSigner.into())),
+        (&oracle, Ok(())),
+    ];
+    for (signer, result) in reports {
+        let instruction_data = MarketInstruction::UpdateReferencePrice(100).pack();
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            params_account.clone(),
+            signer.clone(),
+        ]
+        .into_bump_slice();
+        assert_eq!(
+            State::process(dex_program_id, instruction_accounts, &instruction_data),
+            result
+        );
+    }
+    assert_eq!(new_bid(89), Err(DexErrorCode::PriceOutsideBand.into()));
+    new_bid(90).unwrap();
+    new_bid(110).unwrap();
+    assert_eq!(new_bid(111), Err(DexErrorCode::PriceOutsideBand.into()));
+
+    // The market keeps its first params account, and the option with it.
+    assert!(set_market_params(true, 0).is_err());
+    assert_eq!(
+        set_market_options(MarketOption::UniqueClientIds as u8),
+        Err(DexErrorCode::InvalidMarketOptions.into())
+    );
+    set_market_options((MarketOption::UniqueClientIds | MarketOption::Params).bits()).unwrap();
+
+    // Turning the band off keeps the reference price.
+    set_market_params(false, 0).unwrap();
+    new_bid(1).unwrap();
+    assert_eq!(identity(params().reference_price), 100);
+}
+
+#[test]
+fn test_min_notional() {
+    use instruction::SetMarketParamsInstruction;
+
+    let mut rng = StdRng::seed_from_u64(12);
+    let bump = Bump::new();
+
+    let (accounts, params_account) = setup_market_with_params(&mut rng, &bump, Some(10_000));
+    let params_account = params_account.unwrap();
+
+    let dex_program_id = accounts.market.owner;
+
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(identity(market.options), MarketOption::Params as u8);
+        let params = market
+            .load_market_params_mut(&params_account, &dex_program_id)
+            .unwrap();
+        assert_eq!(identity(params.min_notional), 10_000);
+        assert_eq!(identity(params.oracle), [0; 4]);
+    }
+
+    let trader = new_trader(&mut rng, &accounts, &bump);
+    let new_bid = |max_qty| {
+        new_order_v3(
+            &accounts,
+            &trader,
+            limit_order(Side::Bid, 100, max_qty),
+            &[&params_account],
+            &bump,
+        )
+    };
+
+    // 99 lots at 100 is 9_900 native pc.
+    assert_eq!(new_bid(99), Err(DexErrorCode::OrderBelowMinNotional.into()));
+    new_bid(100).unwrap();
+
+    // The disable authority lowers it.
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let instruction_data = MarketInstruction::SetMarketParams(SetMarketParamsInstruction {
+        oracle: [0; 4],
+        max_deviation_bps: 0,
+        min_notional: 100,
+        prune_authority: [0; 4],
+    })
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        params_account.clone(),
+        authority.clone(),
+        accounts.rent_sysvar.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    new_bid(1).unwrap();
+}
+
+#[test]
+fn test_registered_market_stats() {
+    use instruction::SetMarketParamsInstruction;
+    use solana_program::clock::Clock;
+    use state::MarketStats;
+
+    let mut rng = StdRng::seed_from_u64(15);
+    let bump = Bump::new();
+
+    let (accounts, params_account) = setup_market_with_params(&mut rng, &bump, Some(0));
+    let params_account = params_account.unwrap();
+
+    let dex_program_id = accounts.market.owner;
+
+    let stats_account =
+        new_dex_owned_account(&mut rng, size_of::<MarketStats>(), dex_program_id, &bump);
+    let other_stats_account =
+        new_dex_owned_account(&mut rng, size_of::<MarketStats>(), dex_program_id, &bump);
+    let clock_sysvar = {
+        let data = bump_vec![in &bump; 0u8; size_of::<Clock>()].into_bump_slice_mut();
+        let mut account_info = AccountInfo::new(
+            &sysvar::clock::ID,
+            false,
+            false,
+            bump.alloc(1_000_000),
+            data,
+            &sysvar::ID,
+            false,
+            Epoch::default(),
+        );
+        Clock::default().to_account_info(&mut account_info).unwrap();
+        account_info
+    };
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let instruction_data = MarketInstruction::SetMarketParams(SetMarketParamsInstruction {
+        oracle: [0; 4],
+        max_deviation_bps: 0,
+        min_notional: 0,
+        prune_authority: [0; 4],
+    })
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        params_account.clone(),
+        authority.clone(),
+        accounts.rent_sysvar.clone(),
+        stats_account.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    let trade_count = || {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        let params = market
+            .load_market_params_mut(&params_account, &dex_program_id)
+            .unwrap();
+        assert_eq!(
+            identity(params.market_stats),
+            stats_account.key.to_aligned_bytes()
+        );
+        let stats = market
+            .load_market_stats_mut(&stats_account, &dex_program_id, None)
+            .unwrap();
+        identity(stats.trade_count)
+    };
+    assert_eq!(trade_count(), 0);
+
+    // Orders must pass the registered account to match.
+    let bidder = new_trader(&mut rng, &accounts, &bump);
+    let asker = new_trader(&mut rng, &accounts, &bump);
+    new_order_v3(
+        &accounts,
+        &bidder,
+        limit_order(Side::Bid, 100, 1),
+        &[&stats_account, &clock_sysvar, &params_account],
+        &bump,
+    )
+    .unwrap();
+    assert_eq!(
+        new_order_v3(
+            &accounts,
+            &asker,
+            limit_order(Side::Ask, 100, 1),
+            &[&params_account],
+            &bump,
+        ),
+        Err(DexErrorCode::MarketStatsNotProvided.into())
+    );
+    assert_eq!(
+        new_order_v3(
+            &accounts,
+            &asker,
+            limit_order(Side::Ask, 100, 1),
+            &[&other_stats_account, &clock_sysvar, &params_account],
+            &bump,
+        ),
+        Err(DexErrorCode::WrongMarketStatsAccount.into())
+    );
+    new_order_v3(
+        &accounts,
+        &asker,
+        limit_order(Side::Ask, 100, 1),
+        &[&stats_account, &clock_sysvar, &params_account],
+        &bump,
+    )
+    .unwrap();
+    assert_eq!(trade_count(), 1);
+}
+
+#[test]
+fn test_prune() {
+    use client::OrderBookReader;
+    use instruction::SetMarketParamsInstruction;
+
+    let mut rng = StdRng::seed_from_u64(13);
+    let bump = Bump::new();
+
+    let (accounts, params_account) = setup_market_with_params(&mut rng, &bump, Some(0));
+    let params_account = params_account.unwrap();
+
+    let dex_program_id = accounts.market.owner;
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let prune_authority = new_sol_account(&mut rng, 0, &bump);
+    let instruction_data = MarketInstruction::SetMarketParams(SetMarketParamsInstruction {
+        oracle: [0; 4],
+        max_deviation_bps: 0,
+        min_notional: 0,
+        prune_authority: prune_authority.key.to_aligned_bytes(),
+    })
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        params_account.clone(),
+        authority.clone(),
+        accounts.rent_sysvar.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    let trader = new_trader(&mut rng, &accounts, &bump);
+    for &(side, limit_price) in &[(Side::Bid, 99), (Side::Bid, 98), (Side::Ask, 101)] {
+        new_order_v3(
+            &accounts,
+            &trader,
+            limit_order(side, limit_price, 1),
+            &[&params_account],
+            &bump,
+        )
+        .unwrap();
+    }
+    let book_is_empty = |account: &AccountInfo| {
+        let data = account.try_borrow_data().unwrap();
+        let book = OrderBookReader::new(&data).unwrap();
+        book.orders().next().is_none()
+    };
+
+    // Only the prune authority prunes.
+    let signers: Vec<(&AccountInfo, DexResult)> = vec![
+        (&trader.owner, Err(DexErrorCode::WrongSigner.into())),
+        (&prune_authority, Ok(())),
+    ];
+    for (signer, result) in signers {
+        let instruction_data = MarketInstruction::Prune.pack();
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            params_account.clone(),
+            signer.clone(),
+            trader.orders_account.clone(),
+            accounts.req_q.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+        ]
+        .into_bump_slice();
+        assert_eq!(
+            State::process(dex_program_id, instruction_accounts, &instruction_data),
+            result
+        );
+    }
+    assert!(book_is_empty(&accounts.bids));
+    assert!(book_is_empty(&accounts.asks));
+
+    // The funds unlock once the cancels are consumed.
+    consume_events(&accounts, &[&trader], &bump);
+    let open_orders = load_open_orders(&accounts, &trader);
+    assert_eq!(identity(open_orders.free_slot_bits), !0);
+    assert_eq!(
+        identity(open_orders.native_coin_free),
+        identity(open_orders.native_coin_total)
+    );
+    assert_eq!(
+        identity(open_orders.native_pc_free),
+        identity(open_orders.native_pc_total)
+    );
+}
+
+#[test]
+fn test_cancel_if_through_reference() {
+    use instruction::{NewOrderInstructionV3, SetMarketParamsInstruction};
+
+    let mut rng = StdRng::seed_from_u64(14);
+    let bump = Bump::new();
+
+    let (accounts, params_account) = setup_market_with_params(&mut rng, &bump, Some(0));
+    let params_account = params_account.unwrap();
+
+    let dex_program_id = accounts.market.owner;
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let oracle = new_sol_account(&mut rng, 0, &bump);
+    let instruction_data = MarketInstruction::SetMarketParams(SetMarketParamsInstruction {
+        oracle: oracle.key.to_aligned_bytes(),
+        max_deviation_bps: 0,
+        min_notional: 0,
+        prune_authority: [0; 4],
+    })
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        params_account.clone(),
+        authority.clone(),
+        accounts.rent_sysvar.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    let report = |price| {
+        let instruction_data = MarketInstruction::UpdateReferencePrice(price).pack();
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            params_account.clone(),
+            oracle.clone(),
+        ]
+        .into_bump_slice();
+        State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    };
+
+    let maker = new_trader(&mut rng, &accounts, &bump);
+    let taker = new_trader(&mut rng, &accounts, &bump);
+    let new_bid_v4 = |limit_price, cancel_if_through_reference_bps| {
+        let instruction_data = MarketInstruction::NewOrderV4(NewOrderInstructionV3 {
+            order: limit_order(Side::Bid, limit_price, 1),
+            cancel_if_through_reference_bps,
+        })
+        .pack();
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            maker.orders_account.clone(),
+            accounts.req_q.clone(),
+            maker.pc_account.clone(),
+            maker.owner.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            new_spl_token_program(&bump),
+            accounts.rent_sysvar.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+            params_account.clone(),
+        ]
+        .into_bump_slice();
+        State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    };
+
+    // Both bids are placed with the market at 100.
+    report(100);
+    new_bid_v4(100, 500);
+    new_bid_v4(99, 1_000);
+
+    // At 94, the market is 6% through the first bid, which allows 5%, and
+    // about 5% through the second, which allows 10%.
+    report(94);
+    new_order_v3(
+        &accounts,
+        &taker,
+        limit_order(Side::Ask, 90, 1),
+        &[&params_account],
+        &bump,
+    )
+    .unwrap();
+    for book in &[&accounts.bids, &accounts.asks] {
+        let data = book.try_borrow_data().unwrap();
+        let book = client::OrderBookReader::new(&data).unwrap();
+        assert_eq!(book.orders().count(), 0);
+    }
+
+    // The first bid is refunded and the second filled at its own price.
+    consume_events(&accounts, &[&maker, &taker], &bump);
+    let open_orders = load_open_orders(&accounts, &maker);
+    assert_eq!(identity(open_orders.free_slot_bits), !0);
+    assert_eq!(identity(open_orders.native_coin_total), 1_000);
+    assert_eq!(
+        identity(open_orders.native_pc_free),
+        identity(open_orders.native_pc_total)
+    );
+}
+
+#[test]
+fn test_market_listing() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let lister = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let system_program = AccountInfo::new(
+        &system_program::ID,
+        false,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &bpf_loader::ID,
+        true,
+        Epoch::default(),
+    );
+    let (listing_address, nonce) =
+        find_market_listing_address(accounts.coin_mint.key, accounts.pc_mint.key, dex_program_id);
+    // Stands in for the account the system program would create.
+    let listing = AccountInfo::new(
+        bump.alloc(listing_address),
+        false,
+        true,
+        bump.alloc(100_000_000),
+        allocate_dex_owned_account(size_of::<MarketListing>(), &bump),
+        dex_program_id,
+        false,
+        Epoch::default(),
+    );
+    let list_market = |nonce| {
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            lister.clone(),
+            listing.clone(),
+            system_program.clone(),
+            accounts.rent_sysvar.clone(),
+        ]
+        .into_bump_slice();
+        let instruction_data = MarketInstruction::ListMarket(nonce).pack();
+        State::process(dex_program_id, instruction_accounts, &instruction_data)
+    };
+
+    assert_eq!(
+        list_market(nonce.wrapping_add(1)),
+        Err(DexErrorCode::WrongMarketListingAccount.into())
+    );
+    list_market(nonce).unwrap();
+    {
+        let listing = MarketListing::load_mut(&listing, dex_program_id).unwrap();
+        assert_eq!(
+            identity(listing.market),
+            accounts.market.key.to_aligned_bytes()
+        );
+        assert_eq!(identity(listing.lister), lister.key.to_aligned_bytes());
+    }
+    assert_eq!(
+        list_market(nonce),
+        Err(DexErrorCode::MarketAlreadyListed.into())
+    );
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        listing.clone(),
+        accounts.market.clone(),
+        authority.clone(),
+    ]
+    .into_bump_slice();
+    let instruction_data = MarketInstruction::ReassignMarketListing.pack();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+}
+
+#[test]
+fn test_upgrade_market() {
+    use fees::FeeSchedule;
+    use instruction::SetFeeScheduleInstruction;
+
+    let mut rng = StdRng::seed_from_u64(4);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let authority = AccountInfo::new(
+        &disable_authority::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let set_fee_schedule_accounts: &[AccountInfo] =
+        bump_vec![in &bump; accounts.market.clone(), authority.clone()].into_bump_slice();
+    let set_fee_schedule = || {
+        let instruction_data = MarketInstruction::SetFeeSchedule(SetFeeScheduleInstruction {
+            taker_fee_bps: 30,
+            maker_rebate_bps: 8,
+        })
+        .pack();
+        State::process(dex_program_id, set_fee_schedule_accounts, &instruction_data)
+    };
+    let upgrade_market_accounts: &[AccountInfo] =
+        bump_vec![in &bump; accounts.market.clone()].into_bump_slice();
+    let upgrade_market = || {
+        let instruction_data = MarketInstruction::UpgradeMarket.pack();
+        State::process(dex_program_id, upgrade_market_accounts, &instruction_data)
+    };
+
+    // A market listed before the layout was versioned.
+    {
+        let mut market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(market.version, MarketState::VERSION);
+        market.version = 0;
+        assert_eq!(market.fee_schedule(), FeeSchedule::DEFAULT);
+    }
+    assert_eq!(
+        set_fee_schedule(),
+        Err(DexErrorCode::MarketNotUpgraded.into())
+    );
+
+    let data_before = accounts.market.try_borrow_data().unwrap().to_vec();
+    upgrade_market().unwrap();
+    {
+        let data = accounts.market.try_borrow_data().unwrap();
+        assert_eq!(data.len(), data_before.len());
+        assert_eq!(data[..5], data_before[..5]);
+        assert_eq!(data[data.len() - 7..], data_before[data.len() - 7..]);
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(market.version, MarketState::VERSION);
+        assert_eq!(market.fee_schedule(), FeeSchedule::DEFAULT);
+    }
+    set_fee_schedule().unwrap();
+
+    // Upgrading a market that's already on the latest layout does nothing.
+    let data_before = accounts.market.try_borrow_data().unwrap().to_vec();
+    upgrade_market().unwrap();
+    assert_eq!(
+        accounts.market.try_borrow_data().unwrap()[..],
+        data_before[..]
+    );
+}
+
+#[test]
+fn test_trigger_order() {
+    let mut rng = StdRng::seed_from_u64(4);
+    let bump = Bump::new();
+
+    let (accounts, params_account) = setup_market_with_params(&mut rng, &bump, Some(0));
+    let params_account = params_account.unwrap();
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account_buyer =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let orders_account_seller =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let orders_account_trigger =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let trigger_account =
+        new_dex_owned_account(&mut rng, size_of::<TriggerOrder>(), dex_program_id, &bump);
+    let coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+    let vault_signer = AccountInfo::new(
+        bump.alloc(gen_vault_signer_key(0, accounts.market.key, dex_program_id).unwrap()),
+        false,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+
+    let coin_deposits_total = |market: &AccountInfo| {
+        identity(
+            MarketState::load(market, &dex_program_id)
+                .unwrap()
+                .coin_deposits_total,
+        )
+    };
+
+    // Sell one lot once the market trades at or below 100_000.
+    let new_trigger_data = MarketInstruction::NewTriggerOrder(NewTriggerOrderInstruction {
+        order: NewOrderInstructionV1 {
+            side: Side::Ask,
+            limit_price: NonZeroU64::new(100_000).unwrap(),
+            max_qty: NonZeroU64::new(1).unwrap(),
+            order_type: OrderType::ImmediateOrCancel,
+            client_id: 7,
+        }
+        .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+        trigger_price: 100_000,
+        trigger_condition: TriggerCondition::PriceAtOrBelow,
+        native_tip: 50,
+    })
+    .pack();
+    let new_trigger_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        trigger_account.clone(),
+        orders_account_trigger.clone(),
+        coin_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        params_account.clone(),
+    ]
+    .into_bump_slice();
+    let load_params = || {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        let params = market
+            .load_market_params_mut(&params_account, &dex_program_id)
+            .unwrap();
+        *params
+    };
+    let trigger_order_count = || identity(load_params().trigger_order_count);
+    let deposits_before = coin_deposits_total(&accounts.market);
+    State::process(dex_program_id, new_trigger_accounts, &new_trigger_data).unwrap();
+    assert_eq!(trigger_order_count(), 1);
+    assert_eq!(
+        coin_deposits_total(&accounts.market),
+        deposits_before + 1_050
+    );
+
+    let execute_data = MarketInstruction::ExecuteTriggerOrder.pack();
+    let execute_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        trigger_account.clone(),
+        orders_account_trigger.clone(),
+        accounts.req_q.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        coin_account.clone(),
+        vault_signer.clone(),
+        spl_token_program.clone(),
+        params_account.clone(),
+    ]
+    .into_bump_slice();
+
+    // Nothing has traded yet.
+    assert_eq!(
+        State::process(dex_program_id, execute_accounts, &execute_data),
+        Err(DexErrorCode::TriggerPriceNotReached.into())
+    );
+
+    for &(side, limit_price, max_qty, orders_account, payer) in &[
+        (Side::Bid, 100_000, 5, &orders_account_buyer, &pc_account),
+        (Side::Ask, 99_000, 4, &orders_account_seller, &coin_account),
+    ] {
+        let instruction_data = MarketInstruction::NewOrderV3(
+            NewOrderInstructionV1 {
+                side,
+                limit_price: NonZeroU64::new(limit_price).unwrap(),
+                max_qty: NonZeroU64::new(max_qty).unwrap(),
+                order_type: OrderType::Limit,
+                client_id: 0,
+            }
+            .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+        )
+        .pack();
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            accounts.req_q.clone(),
+            payer.clone(),
+            owner.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            spl_token_program.clone(),
+            accounts.rent_sysvar.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+            params_account.clone(),
+        ]
+        .into_bump_slice();
+        State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    }
+    assert_eq!(identity(load_params().last_fill_price), 100_000);
+
+    // Cranking the fill out of the event queue leaves the recorded price.
+    {
+        let crank_accounts = bump_vec![in &bump;
+            orders_account_buyer.clone(),
+            orders_account_seller.clone(),
+            accounts.market.clone(),
+            accounts.event_q.clone(),
+            coin_account.clone(),
+            pc_account.clone(),
+        ]
+        .into_bump_slice_mut();
+        crank_accounts[0..2].sort_by_key(|account_info| account_info.key.to_aligned_bytes());
+        let instruction_data = MarketInstruction::ConsumeEvents(200).pack();
+        State::process(dex_program_id, crank_accounts, &instruction_data).unwrap();
+    }
+
+    // The resting bid filled at 100_000, which meets the trigger. Only the
+    // tip leaves the vault; the escrow now backs the order.
+    let deposits_before = coin_deposits_total(&accounts.market);
+    State::process(dex_program_id, execute_accounts, &execute_data).unwrap();
+    assert_eq!(coin_deposits_total(&accounts.market), deposits_before - 50);
+    assert_eq!(trigger_order_count(), 0);
+    assert_eq!(
+        State::process(dex_program_id, execute_accounts, &execute_data),
+        Err(DexErrorCode::WrongTriggerOrderAccount.into())
+    );
+
+    // The emptied account can hold a new trigger, which can be cancelled.
+    let deposits_before = coin_deposits_total(&accounts.market);
+    State::process(dex_program_id, new_trigger_accounts, &new_trigger_data).unwrap();
+    let cancel_data = MarketInstruction::CancelTriggerOrder.pack();
+    let cancel_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        trigger_account.clone(),
+        owner.clone(),
+        coin_account.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        vault_signer.clone(),
+        spl_token_program.clone(),
+        params_account.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, cancel_accounts, &cancel_data).unwrap();
+    assert_eq!(coin_deposits_total(&accounts.market), deposits_before);
+    assert_eq!(trigger_order_count(), 0);
+    assert!(MarketState::load(&accounts.market, &dex_program_id)
+        .unwrap()
+        .load_trigger_order_mut(&trigger_account, &dex_program_id)
+        .is_err());
+}
+
+#[test]
+fn test_block_trade() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account_buyer =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let orders_account_seller =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let block_trade_account =
+        new_dex_owned_account(&mut rng, size_of::<BlockTrade>(), dex_program_id, &bump);
+    let coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+
+    // Open a spread of 99_000 to 101_000.
+    for &(side, limit_price, orders_account, payer) in &[
+        (Side::Bid, 99_000, &orders_account_buyer, &pc_account),
+        (Side::Ask, 101_000, &orders_account_seller, &coin_account),
+    ] {
+        let instruction_data = MarketInstruction::NewOrderV3(
+            NewOrderInstructionV1 {
+                side,
+                limit_price: NonZeroU64::new(limit_price).unwrap(),
+                max_qty: NonZeroU64::new(1).unwrap(),
+                order_type: OrderType::Limit,
+                client_id: 0,
+            }
+            .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+        )
+        .pack();
+        let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            accounts.req_q.clone(),
+            payer.clone(),
+            owner.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            spl_token_program.clone(),
+            accounts.rent_sysvar.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+        ]
+        .into_bump_slice();
+        State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+    }
+
+    // Two lots at 100_000, inside the spread.
+    let init_data = MarketInstruction::InitBlockTrade(InitBlockTradeInstruction {
+        price: 100_000,
+        coin_qty: 2,
+    })
+    .pack();
+    let init_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        block_trade_account.clone(),
+        orders_account_buyer.clone(),
+        orders_account_seller.clone(),
+        accounts.rent_sysvar.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, init_accounts, &init_data).unwrap();
+
+    let deposit_data = MarketInstruction::DepositBlockTrade(DepositBlockTradeInstruction {
+        price: 100_000,
+        coin_qty: 2,
+    })
+    .pack();
+    let buyer_deposit: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        block_trade_account.clone(),
+        orders_account_buyer.clone(),
+        pc_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+    ]
+    .into_bump_slice();
+    let seller_deposit: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        block_trade_account.clone(),
+        orders_account_seller.clone(),
+        coin_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+    ]
+    .into_bump_slice();
+    let settle_data = MarketInstruction::SettleBlockTrade.pack();
+    let settle_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        block_trade_account.clone(),
+        orders_account_buyer.clone(),
+        orders_account_seller.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+
+    // Neither side can be made to deposit on terms it didn't agree to.
+    let other_terms_data = MarketInstruction::DepositBlockTrade(DepositBlockTradeInstruction {
+        price: 100_000,
+        coin_qty: 1,
+    })
+    .pack();
+    assert_eq!(
+        State::process(dex_program_id, buyer_deposit, &other_terms_data),
+        Err(DexErrorCode::InvalidBlockTrade.into())
+    );
+
+    State::process(dex_program_id, buyer_deposit, &deposit_data).unwrap();
+    assert_eq!(
+        State::process(dex_program_id, settle_accounts, &settle_data),
+        Err(DexErrorCode::BlockTradeNotFunded.into())
+    );
+    State::process(dex_program_id, seller_deposit, &deposit_data).unwrap();
+    State::process(dex_program_id, settle_accounts, &settle_data).unwrap();
+    assert!(MarketState::load(&accounts.market, &dex_program_id)
+        .unwrap()
+        .load_block_trade_mut(&block_trade_account, &dex_program_id)
+        .is_err());
+
+    {
+        let crank_accounts = bump_vec![in &bump;
+            orders_account_buyer.clone(),
+            orders_account_seller.clone(),
+            accounts.market.clone(),
+            accounts.event_q.clone(),
+            coin_account.clone(),
+            pc_account.clone(),
+        ]
+        .into_bump_slice_mut();
+        crank_accounts[0..2].sort_by_key(|account_info| account_info.key.to_aligned_bytes());
+        let instruction_data = MarketInstruction::ConsumeEvents(200).pack();
+        State::process(dex_program_id, crank_accounts, &instruction_data).unwrap();
+    }
+
+    // Each side received the other's deposit, fee-free, and only the resting
+    // orders are left.
+    let load_orders = |orders_account: &AccountInfo| {
+        MarketState::load(&accounts.market, &dex_program_id)
+            .unwrap()
+            .load_orders_mut(orders_account, None, &dex_program_id, None)
+            .map(|open_orders| *open_orders)
+            .unwrap()
+    };
+    let open_orders_buyer = load_orders(&orders_account_buyer);
+    assert_eq!(identity(open_orders_buyer.native_coin_free), 2_000);
+    assert_eq!(identity(open_orders_buyer.native_pc_total), 99_000);
+    assert_eq!(open_orders_buyer.free_slot_count(), 127);
+    let open_orders_seller = load_orders(&orders_account_seller);
+    assert_eq!(identity(open_orders_seller.native_pc_free), 200_000);
+    assert_eq!(identity(open_orders_seller.native_coin_total), 1_000);
+    assert_eq!(open_orders_seller.free_slot_count(), 127);
+
+    // A block trade funded by one side can be cancelled, refunding it.
+    State::process(dex_program_id, init_accounts, &init_data).unwrap();
+    State::process(dex_program_id, buyer_deposit, &deposit_data).unwrap();
+    let cancel_data = MarketInstruction::CancelBlockTrade.pack();
+    let cancel_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        block_trade_account.clone(),
+        orders_account_buyer.clone(),
+        orders_account_seller.clone(),
+        owner.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, cancel_accounts, &cancel_data).unwrap();
+    let open_orders_buyer = load_orders(&orders_account_buyer);
+    assert_eq!(identity(open_orders_buyer.native_pc_free), 200_000);
+    assert_eq!(open_orders_buyer.free_slot_count(), 127);
+}
+
+#[test]
+fn test_open_orders_delegate() {
+    let mut rng = StdRng::seed_from_u64(5);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let delegate = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let delegate_account = new_dex_owned_account(
+        &mut rng,
+        size_of::<OpenOrdersDelegate>(),
+        dex_program_id,
+        &bump,
+    );
+    let owner_pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let delegate_pc_account =
+        new_token_account(&mut rng, accounts.pc_mint.key, delegate.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+
+    let new_order = |client_id: u64, signer: &AccountInfo<'_>, payer: &AccountInfo<'_>| {
+        let instruction_data = MarketInstruction::NewOrderV3(
+            NewOrderInstructionV1 {
+                side: Side::Bid,
+                limit_price: NonZeroU64::new(10_000).unwrap(),
+                max_qty: NonZeroU64::new(1).unwrap(),
+                order_type: OrderType::Limit,
+                client_id,
+            }
+            .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+        )
+        .pack();
+        let mut instruction_accounts = bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            accounts.req_q.clone(),
+            payer.clone(),
+            signer.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            spl_token_program.clone(),
+            accounts.rent_sysvar.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+        ];
+        if signer.key != owner.key {
+            instruction_accounts.push(delegate_account.clone());
+        }
+        State::process(
+            dex_program_id,
+            instruction_accounts.into_bump_slice(),
+            &instruction_data,
+        )
+    };
+
+    // The owner creates the OpenOrders account; a delegate cannot.
+    new_order(1, &owner, &owner_pc_account).unwrap();
+    assert_eq!(
+        new_order(2, &delegate, &delegate_pc_account),
+        Err(DexErrorCode::WrongDelegateAccount.into())
+    );
+
+    let set_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account.clone(),
+        owner.clone(),
+        delegate_account.clone(),
+        delegate.clone(),
+        accounts.rent_sysvar.clone(),
+    ]
+    .into_bump_slice();
+    State::process(
+        dex_program_id,
+        set_accounts,
+        &MarketInstruction::SetOpenOrdersDelegate.pack(),
+    )
+    .unwrap();
+    new_order(2, &delegate, &delegate_pc_account).unwrap();
+
+    let cancel = |signer: &AccountInfo<'_>| {
+        let cancel_accounts: &[AccountInfo] = bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            accounts.req_q.clone(),
+            signer.clone(),
+            accounts.event_q.clone(),
+            accounts.bids.clone(),
+            accounts.asks.clone(),
+            delegate_account.clone(),
+        ]
+        .into_bump_slice();
+        State::process(
+            dex_program_id,
+            cancel_accounts,
+            &MarketInstruction::CancelOrderByClientIdV2(2).pack(),
+        )
+    };
+    let stranger = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    assert_eq!(
+        cancel(&stranger),
+        Err(DexErrorCode::WrongDelegateAccount.into())
+    );
+    cancel(&delegate).unwrap();
+
+    let revoke_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account.clone(),
+        owner.clone(),
+        delegate_account.clone(),
+    ]
+    .into_bump_slice();
+    State::process(
+        dex_program_id,
+        revoke_accounts,
+        &MarketInstruction::RevokeOpenOrdersDelegate.pack(),
+    )
+    .unwrap();
+    assert_eq!(
+        new_order(3, &delegate, &delegate_pc_account),
+        Err(DexErrorCode::WrongDelegateAccount.into())
+    );
+}
+
+#[test]
+fn test_settle_destination() {
+    let mut rng = StdRng::seed_from_u64(6);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let owner = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let vault_program = new_sol_account(&mut rng, 1_000_000_000, &bump);
+    let orders_account =
+        new_dex_owned_account(&mut rng, size_of::<OpenOrders>(), dex_program_id, &bump);
+    let destination_account = new_dex_owned_account(
+        &mut rng,
+        size_of::<SettleDestination>(),
+        dex_program_id,
+        &bump,
+    );
+    let owner_coin_account = new_token_account(&mut rng, accounts.coin_mint.key, owner.key, &bump);
+    let owner_pc_account = new_token_account(&mut rng, accounts.pc_mint.key, owner.key, &bump);
+    let vault_coin_account =
+        new_token_account(&mut rng, accounts.coin_mint.key, vault_program.key, &bump);
+    let vault_pc_account =
+        new_token_account(&mut rng, accounts.pc_mint.key, vault_program.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+    let vault_signer = AccountInfo::new(
+        bump.alloc(gen_vault_signer_key(0, accounts.market.key, dex_program_id).unwrap()),
+        false,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    // The owner's key, without its signature.
+    let mut unsigned_owner = owner.clone();
+    unsigned_owner.is_signer = false;
+
+    // Nothing rests on the book, so the unfilled bid is freed right away.
+    let instruction_data = MarketInstruction::NewOrderV3(
+        NewOrderInstructionV1 {
+            side: Side::Bid,
+            limit_price: NonZeroU64::new(10_000).unwrap(),
+            max_qty: NonZeroU64::new(1).unwrap(),
+            order_type: OrderType::ImmediateOrCancel,
+            client_id: 0,
+        }
+        .add_self_trade_behavior(SelfTradeBehavior::DecrementTake),
+    )
+    .pack();
+    let instruction_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account.clone(),
+        accounts.req_q.clone(),
+        owner_pc_account.clone(),
+        owner.clone(),
+        accounts.coin_vault.clone(),
+        accounts.pc_vault.clone(),
+        spl_token_program.clone(),
+        accounts.rent_sysvar.clone(),
+        accounts.event_q.clone(),
+        accounts.bids.clone(),
+        accounts.asks.clone(),
+    ]
+    .into_bump_slice();
+    State::process(dex_program_id, instruction_accounts, &instruction_data).unwrap();
+
+    let settle = |coin_wallet: &AccountInfo<'_>, pc_wallet: &AccountInfo<'_>, destination| {
+        let mut settle_accounts = bump_vec![in &bump;
+            accounts.market.clone(),
+            orders_account.clone(),
+            unsigned_owner.clone(),
+            accounts.coin_vault.clone(),
+            accounts.pc_vault.clone(),
+            coin_wallet.clone(),
+            pc_wallet.clone(),
+            vault_signer.clone(),
+            spl_token_program.clone(),
+        ];
+        if destination {
+            settle_accounts.push(destination_account.clone());
+        }
+        State::process(
+            dex_program_id,
+            settle_accounts.into_bump_slice(),
+            &MarketInstruction::SettleFunds.pack(),
+        )
+    };
+
+    assert_eq!(
+        settle(&vault_coin_account, &vault_pc_account, false),
+        Err(DexErrorCode::WrongSettleDestinationAccount.into())
+    );
+
+    let set_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account.clone(),
+        owner.clone(),
+        destination_account.clone(),
+        vault_coin_account.clone(),
+        vault_pc_account.clone(),
+        accounts.rent_sysvar.clone(),
+    ]
+    .into_bump_slice();
+    State::process(
+        dex_program_id,
+        set_accounts,
+        &MarketInstruction::SetSettleDestination.pack(),
+    )
+    .unwrap();
+
+    assert_eq!(
+        settle(&owner_coin_account, &owner_pc_account, true),
+        Err(DexErrorCode::WrongSettleDestinationAccount.into())
+    );
+    let (pc_free, pc_deposits_before) = {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        let open_orders = market
+            .load_orders_mut(&orders_account, None, &dex_program_id, None)
+            .unwrap();
+        (open_orders.native_pc_free, market.pc_deposits_total)
+    };
+    settle(&vault_coin_account, &vault_pc_account, true).unwrap();
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        let open_orders = market
+            .load_orders_mut(&orders_account, None, &dex_program_id, None)
+            .unwrap();
+        assert_eq!(identity(open_orders.native_pc_free), 0);
+        assert_eq!(
+            identity(market.pc_deposits_total),
+            pc_deposits_before - pc_free
+        );
+    }
+
+    let clear_accounts: &[AccountInfo] = bump_vec![in &bump;
+        accounts.market.clone(),
+        orders_account.clone(),
+        owner.clone(),
+        destination_account.clone(),
+    ]
+    .into_bump_slice();
+    State::process(
+        dex_program_id,
+        clear_accounts,
+        &MarketInstruction::ClearSettleDestination.pack(),
+    )
+    .unwrap();
+    assert_eq!(
+        settle(&vault_coin_account, &vault_pc_account, true),
+        Err(DexErrorCode::WrongSettleDestinationAccount.into())
+    );
+}
+
+#[test]
+fn test_fee_destinations() {
+    use instruction::{fee_sweeper, SetFeeDestinationsInstruction};
+    use state::FeeDestinations;
+
+    let mut rng = StdRng::seed_from_u64(7);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+
+    let dex_program_id = accounts.market.owner;
+
+    let sweeper = AccountInfo::new(
+        &fee_sweeper::ID,
+        true,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+    let destinations_account = new_dex_owned_account(
+        &mut rng,
+        size_of::<FeeDestinations>(),
+        dex_program_id,
+        &bump,
+    );
+    let receiver = new_token_account(&mut rng, accounts.pc_mint.key, sweeper.key, &bump);
+    let wallets: Vec<AccountInfo> = (0..3)
+        .map(|_| {
+            let owner = random_pubkey(&mut rng, &bump);
+            new_token_account(&mut rng, accounts.pc_mint.key, owner, &bump)
+        })
+        .collect();
+    let coin_wallet = new_token_account(&mut rng, accounts.coin_mint.key, sweeper.key, &bump);
+    let spl_token_program = new_spl_token_program(&bump);
+    let vault_signer = AccountInfo::new(
+        bump.alloc(gen_vault_signer_key(0, accounts.market.key, dex_program_id).unwrap()),
+        false,
+        false,
+        bump.alloc(0),
+        &mut [],
+        &system_program::ID,
+        false,
+        Epoch::default(),
+    );
+
+    let set_destinations = |weights: Vec<u32>, wallets: &[AccountInfo<'_>]| {
+        let mut set_accounts = bump_vec![in &bump;
+            accounts.market.clone(),
+            sweeper.clone(),
+            destinations_account.clone(),
+            accounts.rent_sysvar.clone(),
+        ];
+        set_accounts.extend(wallets.iter().cloned());
+        let instruction_data =
+            MarketInstruction::SetFeeDestinations(SetFeeDestinationsInstruction { weights }).pack();
+        State::process(
+            dex_program_id,
+            set_accounts.into_bump_slice(),
+            &instruction_data,
+        )
+    };
+    let sweep = |fees: u64, wallets: Option<&[AccountInfo<'_>]>| {
+        {
+            let mut market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+            market.pc_fees_accrued = fees;
+        }
+        let mut sweep_accounts = bump_vec![in &bump;
+            accounts.market.clone(),
+            accounts.pc_vault.clone(),
+            sweeper.clone(),
+            receiver.clone(),
+            vault_signer.clone(),
+            spl_token_program.clone(),
+        ];
+        if let Some(wallets) = wallets {
+            sweep_accounts.push(destinations_account.clone());
+            sweep_accounts.extend(wallets.iter().cloned());
+        }
+        State::process(
+            dex_program_id,
+            sweep_accounts.into_bump_slice(),
+            &MarketInstruction::SweepFees.pack(),
+        )
+    };
+
+    assert_eq!(
+        set_destinations(vec![1, 0, 1], &wallets),
+        Err(DexErrorCode::InvalidFeeDestinations.into())
+    );
+    assert_eq!(
+        set_destinations(vec![1, 1], &wallets),
+        Err(DexErrorCode::InvalidFeeDestinations.into())
+    );
+    assert!(set_destinations(vec![1, 1], &[wallets[0].clone(), coin_wallet.clone()]).is_err());
+    set_destinations(vec![5, 3, 2], &wallets).unwrap();
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        let destinations = market
+            .load_fee_destinations_mut(&destinations_account, &dex_program_id, None)
+            .unwrap();
+        assert_eq!(destinations.split(1_000), vec![500, 300, 200]);
+        // The remainder of rounding down stays with the fee receivable account.
+        assert_eq!(destinations.split(999), vec![499, 299, 199]);
+        assert_eq!(
+            destinations.wallets(),
+            wallets
+                .iter()
+                .map(|wallet| wallet.key.to_aligned_bytes())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    // The wallets must be passed as registered, in order.
+    let reordered = [wallets[1].clone(), wallets[0].clone(), wallets[2].clone()];
+    assert_eq!(
+        sweep(1_000, Some(&reordered)),
+        Err(DexErrorCode::WrongFeeDestinationsAccount.into())
+    );
+    assert_eq!(
+        sweep(1_000, Some(&wallets[..2])),
+        Err(DexErrorCode::WrongFeeDestinationsAccount.into())
+    );
+    sweep(1_000, Some(&wallets)).unwrap();
+    {
+        let market = MarketState::load(&accounts.market, &dex_program_id).unwrap();
+        assert_eq!(identity(market.pc_fees_accrued), 0);
+    }
+
+    // Registering again replaces the wallets.
+    set_destinations(vec![1], &wallets[2..]).unwrap();
+    assert_eq!(
+        sweep(1_000, Some(&wallets)),
+        Err(DexErrorCode::WrongFeeDestinationsAccount.into())
+    );
+    sweep(1_000, Some(&wallets[2..])).unwrap();
+    sweep(1_000, None).unwrap();
+}
+
+#[test]
+fn test_event_queue_reader() {
+    use client::{EventFlags, EventQueueReader, QueueEvent};
+    use fees::FeeTier;
+    use state::{AccountFlag, Event, EventView, ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING};
+
+    let owner = Pubkey::new(&[7; 32]);
+    let fill = Event::new(EventView::Fill {
+        side: Side::Bid,
+        maker: true,
+        native_qty_paid: 2_000,
+        native_qty_received: 300,
+        native_fee_or_rebate: 4,
+        order_id: (7u128 << 64) | 1,
+        owner: owner.to_aligned_bytes(),
+        owner_slot: 3,
+        fee_tier: FeeTier::SRM2,
+        client_order_id: NonZeroU64::new(42),
+    });
+    let out = Event::new(EventView::Out {
+        side: Side::Ask,
+        native_qty_unlocked: 100,
+        native_qty_still_locked: 0,
+        order_id: 9,
+        owner: owner.to_aligned_bytes(),
+        owner_slot: 4,
+        client_order_id: None,
+    });
+
+    // The two pending events wrap around the end of the buffer.
+    let mut data = ACCOUNT_HEAD_PADDING.to_vec();
+    let flags = (AccountFlag::Initialized | AccountFlag::EventQueue).bits();
+    for word in &[flags, 2, 2, 7] {
+        data.extend_from_slice(&word.to_le_bytes());
+    }
+    for event in &[out, out, fill] {
+        data.extend_from_slice(bytes_of(event));
+    }
+    data.extend_from_slice(ACCOUNT_TAIL_PADDING);
+
+    let reader = EventQueueReader::new(&data).unwrap();
+    assert_eq!(reader.len(), 2);
+    assert_eq!(reader.seq_num(), 7);
+    let events: Vec<(u64, QueueEvent)> = reader.iter().map(Result::unwrap).collect();
+    assert_eq!(events.len(), 2);
+
+    let (seq_num, event) = &events[0];
+    assert_eq!(*seq_num, 5);
+    assert_eq!(
+        event.flags(),
+        EventFlags {
+            fill: true,
+            out: false,
+            bid: true,
+            maker: true,
+        }
+    );
+    match event {
+        QueueEvent::Fill(fill) => {
+            assert_eq!(fill.owner, owner);
+            assert_eq!(fill.price_lots(), 7);
+            assert_eq!(fill.native_coin(), 300);
+            assert_eq!(fill.native_pc(), 2_000);
+            assert_eq!(fill.fee_tier, FeeTier::SRM2);
+            assert_eq!(fill.client_order_id, NonZeroU64::new(42));
+        }
+        QueueEvent::Out(_) => panic!("expected a fill"),
+    }
+
+    let (seq_num, event) = &events[1];
+    assert_eq!(*seq_num, 6);
+    assert!(event.flags().out);
+    assert!(!event.flags().bid);
+    assert_eq!(event.owner(), &owner);
+
+    // Data that isn't an event queue is rejected.
+    data[ACCOUNT_HEAD_PADDING.len()] = AccountFlag::Initialized as u8;
+    assert!(EventQueueReader::new(&data).is_err());
+}
+
+#[test]
+fn test_parse_fill_logs() {
+    use client::{parse_fill_logs, QueueEvent};
+    use fees::FeeTier;
+    use state::{Event, EventView};
+
+    let fill = Event::new(EventView::Fill {
+        side: Side::Ask,
+        maker: false,
+        native_qty_paid: 500,
+        native_qty_received: 9_978,
+        native_fee_or_rebate: 22,
+        order_id: (20u128 << 64) | 3,
+        owner: Pubkey::new(&[5; 32]).to_aligned_bytes(),
+        owner_slot: 1,
+        fee_tier: FeeTier::Base,
+        client_order_id: None,
+    });
+    let mut logs = vec!["Program log: Instruction: ConsumeEvents".to_string()];
+    for line in fill.log_words().iter() {
+        let words: Vec<String> = line.iter().map(|word| format!("{:#x}", word)).collect();
+        logs.push(format!("Program log: {}", words.join(", ")));
+    }
+    // A fill cut short by an unrelated line is dropped.
+    logs.extend_from_slice(&logs[1..3].to_vec());
+    logs.push("Program log: invoked".to_string());
+
+    let fills = parse_fill_logs(logs.iter().map(String::as_str));
+    assert_eq!(fills.len(), 1);
+    match QueueEvent::decode(&fill).unwrap() {
+        QueueEvent::Fill(expected) => assert_eq!(fills[0], expected),
+        QueueEvent::Out(_) => unreachable!(),
+    }
+    assert_eq!(fills[0].price_lots(), 20);
+    assert_eq!(fills[0].native_pc(), 9_978);
+}
+
+#[test]
+fn test_describe_error() {
+    use client::describe_error;
+
+    assert_eq!(
+        describe_error(DexErrorCode::OrderNotFound.into()),
+        "order not found (OrderNotFound)"
+    );
+    assert_eq!(
+        describe_error((2 << 24) | 123),
+        "assertion failed at src/matching.rs:123"
+    );
+    assert_eq!(
+        describe_error((99 << 24) | 7),
+        "assertion failed at line 7 of file 99"
+    );
+}
+
+#[test]
+fn test_order_book_reader() {
+    use client::{LotSizes, NativePriceLevel, OrderBookReader, PriceLevel};
+    use critbit::{LeafNode, Slab};
+    use fees::FeeTier;
+    use state::{AccountFlag, ACCOUNT_HEAD_PADDING, ACCOUNT_TAIL_PADDING};
+
+    let mut slab_bytes = vec![0u8; 32 + 72 * 16];
+    let slab = Slab::new(&mut slab_bytes);
+    for (seq, &(price, quantity)) in [(100u64, 3), (101, 5), (99, 1), (100, 4)]
+        .iter()
+        .enumerate()
+    {
+        let key = ((price as u128) << 64) | seq as u128;
+        let leaf = LeafNode::new(0, key, [0; 4], quantity, FeeTier::Base, 0, 0);
+        slab.insert_leaf(&leaf).unwrap();
+    }
+
+    let account_data = |flag: AccountFlag| {
+        let mut data = ACCOUNT_HEAD_PADDING.to_vec();
+        data.extend_from_slice(&(AccountFlag::Initialized | flag).bits().to_le_bytes());
+        data.extend_from_slice(&slab_bytes);
+        data.extend_from_slice(ACCOUNT_TAIL_PADDING);
+        data
+    };
+    let level = |price, quantity, order_count| PriceLevel {
+        price,
+        quantity,
+        order_count,
+    };
+
+    let bids = account_data(AccountFlag::Bids);
+    let reader = OrderBookReader::new(&bids).unwrap();
+    assert_eq!(reader.side(), Side::Bid);
+    assert_eq!(reader.orders().count(), 4);
+    assert_eq!(reader.levels(2), vec![level(101, 5, 1), level(100, 7, 2)]);
+
+    let asks = account_data(AccountFlag::Asks);
+    let reader = OrderBookReader::new(&asks).unwrap();
+    assert_eq!(reader.side(), Side::Ask);
+    assert_eq!(
+        reader.levels(10),
+        vec![level(99, 1, 1), level(100, 7, 2), level(101, 5, 1)]
+    );
+
+    let lot_sizes = LotSizes {
+        coin_lot_size: 10,
+        pc_lot_size: 5,
+    };
+    assert_eq!(
+        level(100, 7, 2).to_native(&lot_sizes),
+        Some(NativePriceLevel {
+            price: 50.0,
+            quantity: 70,
+            order_count: 2,
+        })
+    );
+
+    let market = account_data(AccountFlag::Market);
+    assert!(OrderBookReader::new(&market).is_err());
+}
+
+#[test]
+fn test_market_filter() {
+    use client::{read_market, MarketFilter};
+
+    let mut rng = StdRng::seed_from_u64(9);
+    let bump = Bump::new();
+
+    let accounts = setup_market(&mut rng, &bump);
+    let data = accounts.market.try_borrow_data().unwrap().to_vec();
+
+    let market = read_market(&data).unwrap();
+    assert_eq!(
+        identity(market.own_address),
+        accounts.market.key.to_aligned_bytes()
+    );
+    assert_eq!(
+        identity(market.coin_mint),
+        accounts.coin_mint.key.to_aligned_bytes()
+    );
+    assert_eq!(
+        identity(market.pc_mint),
+        accounts.pc_mint.key.to_aligned_bytes()
+    );
+
+    assert!(MarketFilter::default().matches(&data));
+    let both = MarketFilter {
+        coin_mint: Some(*accounts.coin_mint.key),
+        pc_mint: Some(*accounts.pc_mint.key),
+    };
+    assert!(both.matches(&data));
+    assert_eq!(
+        both.memcmp_filters(),
+        vec![
+            (MarketFilter::COIN_MINT_OFFSET, *accounts.coin_mint.key),
+            (MarketFilter::PC_MINT_OFFSET, *accounts.pc_mint.key),
+        ]
+    );
+    // The mints the wrong way round.
+    let flipped = MarketFilter {
+        coin_mint: Some(*accounts.pc_mint.key),
+        pc_mint: None,
+    };
+    assert!(!flipped.matches(&data));
+    assert!(!MarketFilter::default().matches(&data[..data.len() - 1]));
+
+    // Other dex accounts aren't markets.
+    let bids = accounts.bids.try_borrow_data().unwrap();
+    assert!(read_market(&bids).is_err());
 }
@@ -1,10 +1,13 @@
 use anyhow::{anyhow, Result};
 use clap::Clap;
+use serum_common::client::args::KeypairPath;
 use serum_common::client::rpc;
-use serum_lockup::accounts::WhitelistEntry;
+use serum_lockup::accounts::{VestingSchedule, WhitelistEntry};
+use serum_lockup::calculator::vesting_amounts;
 use serum_lockup_client::*;
 use serum_node_context::Context;
 use solana_client_gen::prelude::*;
+use std::str::FromStr;
 
 #[derive(Debug, Clap)]
 #[clap(name = "Serum Lockup CLI")]
@@ -33,7 +36,7 @@ pub enum SubCommand {
     Gov {
         /// Filepath to the authority key.
         #[clap(short = 'f', long)]
-        authority_file: String,
+        authority_file: KeypairPath,
         /// Safe account to govern.
         #[clap(short, long)]
         safe: Pubkey,
@@ -45,6 +48,9 @@ pub enum SubCommand {
         /// Authority to set on the new safe.
         #[clap(short, long)]
         authority: Pubkey,
+        /// Mint of the token locked by the safe. Defaults to SRM.
+        #[clap(long)]
+        mint: Option<Pubkey>,
     },
     /// Creates a vesting account.
     CreateVesting {
@@ -67,6 +73,85 @@ pub enum SubCommand {
         #[clap(short = 'a', long)]
         deposit_amount: u64,
     },
+    /// Creates a vesting account unlocking in equal parts between two unix
+    /// timestamps, with nothing unlocked before the cliff.
+    CreateScheduledVesting {
+        /// Token account sending funds.
+        #[clap(short, long)]
+        depositor: Pubkey,
+        /// Safe to associate this Vesting account with.
+        #[clap(short, long)]
+        safe: Pubkey,
+        /// Beneficiary address to give this Vesting account to.
+        #[clap(short, long)]
+        beneficiary: Pubkey,
+        /// Unix timestamp at which vesting starts.
+        #[clap(long)]
+        start_ts: i64,
+        /// Unix timestamp before which nothing is vested.
+        #[clap(long)]
+        cliff_ts: i64,
+        /// Unix timestamp at which the entire account is vested.
+        #[clap(long)]
+        end_ts: i64,
+        /// Number of unlocks between the start and end.
+        #[clap(short, long)]
+        period_count: u64,
+        /// Amount of tokens to give this Vesting account.
+        #[clap(short = 'a', long)]
+        deposit_amount: u64,
+        /// Authority allowed to revoke the unvested tokens. If not given,
+        /// the grant can't be revoked.
+        #[clap(long)]
+        revoker: Option<Pubkey>,
+    },
+    /// Creates a vesting account streaming its deposit per second between
+    /// two unix timestamps.
+    CreateStreamingVesting {
+        /// Token account sending funds.
+        #[clap(short, long)]
+        depositor: Pubkey,
+        /// Safe to associate this Vesting account with.
+        #[clap(short, long)]
+        safe: Pubkey,
+        /// Beneficiary address to give this Vesting account to.
+        #[clap(short, long)]
+        beneficiary: Pubkey,
+        /// Unix timestamp at which the stream starts.
+        #[clap(long)]
+        start_ts: i64,
+        /// Unix timestamp at which the entire account is vested.
+        #[clap(long)]
+        end_ts: i64,
+        /// Amount of tokens to give this Vesting account.
+        #[clap(short = 'a', long)]
+        deposit_amount: u64,
+        /// Authority allowed to revoke the unvested tokens. If not given,
+        /// the stream can't be revoked.
+        #[clap(long)]
+        revoker: Option<Pubkey>,
+    },
+    /// Moves part of a vesting account's balance into a new vesting account.
+    Split {
+        /// Vesting account to split.
+        #[clap(short, long)]
+        vesting: Pubkey,
+        /// Amount of the balance to move.
+        #[clap(short, long)]
+        amount: u64,
+        /// Beneficiary of the new vesting account.
+        #[clap(short, long)]
+        new_beneficiary: Pubkey,
+    },
+    /// Revokes a vesting account, using the wallet as the revoker.
+    Revoke {
+        /// Vesting account to revoke.
+        #[clap(short, long)]
+        vesting: Pubkey,
+        /// Token account receiving the unvested tokens.
+        #[clap(short, long)]
+        receiver: Pubkey,
+    },
     /// Claim a vesting account, receiving a non-fungible token receipt.
     Claim {
         /// The vesting account to claim.
@@ -74,6 +159,7 @@ pub enum SubCommand {
         vesting: Pubkey,
     },
     /// Redeem a claimed token receipt for an amount of vested tokens.
+    #[clap(visible_alias = "withdraw")]
     Redeem {
         /// The amount of vested tokens to redeem.
         #[clap(short, long)]
@@ -85,6 +171,86 @@ pub enum SubCommand {
         #[clap(short, long)]
         token_account: Pubkey,
     },
+    /// Sends locked tokens to a whitelisted program, relaying an
+    /// instruction to it, e.g., to stake them.
+    WhitelistWithdraw {
+        /// Vesting account to send from. The wallet must be its beneficiary.
+        #[clap(short, long)]
+        vesting: Pubkey,
+        /// Amount the whitelisted program is approved to take.
+        #[clap(short, long)]
+        amount: u64,
+        #[clap(flatten)]
+        relay: RelayArgs,
+    },
+    /// Relays an instruction to a whitelisted program that returns locked
+    /// tokens to the safe's vault.
+    WhitelistDeposit {
+        /// Vesting account the tokens are returned to.
+        #[clap(short, long)]
+        vesting: Pubkey,
+        #[clap(flatten)]
+        relay: RelayArgs,
+    },
+}
+
+/// The whitelisted program and the instruction relayed to it.
+#[derive(Debug, Clap)]
+pub struct RelayArgs {
+    /// Whitelisted program to relay the instruction to.
+    #[clap(short, long)]
+    pub whitelist_program: Pubkey,
+    /// Token account of the whitelisted program.
+    #[clap(long)]
+    pub whitelist_vault: Pubkey,
+    /// Owner of the whitelisted program's token account.
+    #[clap(long)]
+    pub whitelist_vault_authority: Pubkey,
+    /// Hex encoded instruction data for the whitelisted program.
+    #[clap(long, default_value = "")]
+    pub relay_data: HexData,
+    /// Additional account for the whitelisted program, in order. Suffix
+    /// with `:w` if it's writable. May be repeated.
+    #[clap(long)]
+    pub relay_account: Vec<RelayAccount>,
+}
+
+#[derive(Debug)]
+pub struct HexData(pub Vec<u8>);
+
+impl FromStr for HexData {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.len() % 2 != 0 {
+            return Err(anyhow!("hex data must have an even length"));
+        }
+        (0..s.len())
+            .step_by(2)
+            .map(|i| {
+                u8::from_str_radix(&s[i..i + 2], 16).map_err(|_| anyhow!("invalid hex: {}", s))
+            })
+            .collect::<Result<Vec<u8>>>()
+            .map(HexData)
+    }
+}
+
+#[derive(Debug)]
+pub struct RelayAccount(pub AccountMeta);
+
+impl FromStr for RelayAccount {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let is_writable = s.ends_with(":w");
+        let key = s.trim_end_matches(":w");
+        let pubkey = Pubkey::from_str(key).map_err(|_| anyhow!("invalid pubkey: {}", key))?;
+        Ok(RelayAccount(AccountMeta {
+            pubkey,
+            is_signer: false,
+            is_writable,
+        }))
+    }
 }
 
 #[derive(Debug, Clap)]
@@ -129,8 +295,28 @@ pub enum GovCommand {
         /// WhitelistEntry signer-seeds nonce.
         #[clap(short, long)]
         nonce: u8,
+        /// Max amount that may be sent to the program at any given time.
+        /// Unlimited if not given.
+        #[clap(short, long)]
+        max_outstanding: Option<u64>,
+    },
+    /// Sets the max amount that may be sent to a whitelisted program.
+    WhitelistSetMaxOutstanding {
+        /// WhitelistEntry program id.
+        #[clap(short, long)]
+        program_id: Pubkey,
+        /// WhitelistEntry signer-seeds instance.
+        #[clap(short, long)]
+        instance: Pubkey,
+        /// WhitelistEntry signer-seeds nonce.
+        #[clap(short, long)]
+        nonce: u8,
+        /// Max amount that may be sent to the program at any given time.
+        #[clap(short, long)]
+        max_outstanding: u64,
     },
     /// Removes a program from the whitelist.
+    #[clap(visible_alias = "whitelist-remove")]
     WhitelistDelete {
         /// WhitelistEntry program id.
         #[clap(short, long)]
@@ -166,10 +352,10 @@ pub fn run(opts: Opts) -> Result<()> {
             safe,
             cmd,
         } => gov_cmd(ctx, opts.cmd.pid, authority_file, safe, cmd),
-        SubCommand::Initialize { authority } => {
+        SubCommand::Initialize { authority, mint } => {
             let client = ctx.connect::<Client>(opts.cmd.pid)?;
             let resp = client.initialize(InitializeRequest {
-                mint: ctx.srm_mint,
+                mint: mint.unwrap_or(ctx.srm_mint),
                 authority: authority,
             })?;
             println!("{:#?}", resp);
@@ -196,6 +382,84 @@ pub fn run(opts: Opts) -> Result<()> {
             println!("{:#?}", resp);
             Ok(())
         }
+        SubCommand::CreateScheduledVesting {
+            depositor,
+            safe,
+            beneficiary,
+            start_ts,
+            cliff_ts,
+            end_ts,
+            period_count,
+            deposit_amount,
+            revoker,
+        } => {
+            let (cliff_ts, unlocks) =
+                VestingSchedule::linear(start_ts, cliff_ts, end_ts, period_count)
+                    .ok_or_else(|| anyhow!("invalid vesting schedule"))?;
+            let client = ctx.connect::<Client>(opts.cmd.pid)?;
+            let resp = client.create_scheduled_vesting(CreateScheduledVestingRequest {
+                depositor,
+                depositor_owner: &ctx.wallet()?,
+                safe,
+                beneficiary,
+                cliff_ts,
+                unlocks,
+                revoker,
+                deposit_amount,
+            })?;
+            println!("{:#?}", resp);
+            Ok(())
+        }
+        SubCommand::CreateStreamingVesting {
+            depositor,
+            safe,
+            beneficiary,
+            start_ts,
+            end_ts,
+            deposit_amount,
+            revoker,
+        } => {
+            let client = ctx.connect::<Client>(opts.cmd.pid)?;
+            let resp = client.create_streaming_vesting(CreateStreamingVestingRequest {
+                depositor,
+                depositor_owner: &ctx.wallet()?,
+                safe,
+                beneficiary,
+                start_ts,
+                end_ts,
+                revoker,
+                deposit_amount,
+            })?;
+            println!("{:#?}", resp);
+            Ok(())
+        }
+        SubCommand::Split {
+            vesting,
+            amount,
+            new_beneficiary,
+        } => {
+            let client = ctx.connect::<Client>(opts.cmd.pid)?;
+            let resp = client.split_vesting(SplitVestingRequest {
+                beneficiary: &ctx.wallet()?,
+                vesting,
+                amount,
+                new_beneficiary,
+            })?;
+            println!("{:#?}", resp);
+            Ok(())
+        }
+        SubCommand::Revoke { vesting, receiver } => {
+            let client = ctx.connect::<Client>(opts.cmd.pid)?;
+            let safe = client.vesting(&vesting)?.safe;
+            let resp = client.revoke(RevokeRequest {
+                revoker: &ctx.wallet()?,
+                vesting,
+                safe,
+                receiver,
+            })?;
+            println!("{:#?}", resp);
+            Ok(())
+        }
         SubCommand::Claim { vesting } => {
             let client = ctx.connect::<Client>(opts.cmd.pid)?;
             let beneficiary = ctx.wallet()?;
@@ -243,6 +507,49 @@ pub fn run(opts: Opts) -> Result<()> {
             println!("{:#?}", resp);
             Ok(())
         }
+        SubCommand::WhitelistWithdraw {
+            vesting,
+            amount,
+            relay,
+        } => {
+            let beneficiary = ctx.wallet()?;
+            let client = ctx.connect::<Client>(opts.cmd.pid)?;
+            let safe = client.vesting(&vesting)?.safe;
+            let vault = client.safe(&safe)?.vault;
+            let resp = client.whitelist_withdraw(WhitelistWithdrawRequest {
+                beneficiary: &beneficiary,
+                vesting,
+                safe,
+                whitelist_program: relay.whitelist_program,
+                relay_accounts: relay.relay_account.into_iter().map(|a| a.0).collect(),
+                vault,
+                whitelist_vault: relay.whitelist_vault,
+                whitelist_vault_authority: relay.whitelist_vault_authority,
+                delegate_amount: amount,
+                relay_data: relay.relay_data.0,
+            })?;
+            println!("{:#?}", resp);
+            Ok(())
+        }
+        SubCommand::WhitelistDeposit { vesting, relay } => {
+            let beneficiary = ctx.wallet()?;
+            let client = ctx.connect::<Client>(opts.cmd.pid)?;
+            let safe = client.vesting(&vesting)?.safe;
+            let vault = client.safe(&safe)?.vault;
+            let resp = client.whitelist_deposit(WhitelistDepositRequest {
+                beneficiary: &beneficiary,
+                vesting,
+                safe,
+                whitelist_program: relay.whitelist_program,
+                relay_accounts: relay.relay_account.into_iter().map(|a| a.0).collect(),
+                vault,
+                whitelist_vault: relay.whitelist_vault,
+                whitelist_vault_authority: relay.whitelist_vault_authority,
+                relay_data: relay.relay_data.0,
+            })?;
+            println!("{:#?}", resp);
+            Ok(())
+        }
     }
 }
 
@@ -259,16 +566,28 @@ fn account_cmd(ctx: &Context, pid: Pubkey, cmd: AccountsCommand) -> Result<()> {
             println!("{:#?}", vault);
 
             let current_slot = client.rpc().get_slot()?;
-            let amount = vault.available_for_withdrawal(current_slot);
-            println!("Redeemable balance: {:?}", amount);
-            println!("Whitelistable balance: {:?}", amount);
+            let current_ts = client.rpc().get_block_time(current_slot)?;
+            let schedule = if vault.has_schedule() {
+                let schedule = client.vesting_schedule(&address)?;
+                println!("{:#?}", schedule);
+                Some(schedule)
+            } else {
+                None
+            };
+            let amounts = vesting_amounts(&vault, schedule.as_ref(), current_slot, current_ts);
+            println!("Vested: {:?}", amounts.vested);
+            println!("Locked: {:?}", amounts.locked);
+            println!("Redeemable balance: {:?}", amounts.withdrawable);
+            println!(
+                "Whitelistable balance: {:?}",
+                vault.available_for_whitelist()
+            );
 
             Ok(())
         }
         AccountsCommand::Whitelist { safe } => {
-            client.with_whitelist(&safe, |whitelist| {
-                println!("{:#?}", whitelist);
-            })?;
+            let items = client.whitelist_items(&safe)?;
+            println!("{:#?}", items);
             Ok(())
         }
         AccountsCommand::Vault { safe } => {
@@ -282,23 +601,48 @@ fn account_cmd(ctx: &Context, pid: Pubkey, cmd: AccountsCommand) -> Result<()> {
 fn gov_cmd(
     ctx: &Context,
     pid: Pubkey,
-    authority_file: String,
+    authority_file: KeypairPath,
     safe: Pubkey,
     cmd: GovCommand,
 ) -> Result<()> {
     let client = ctx.connect::<Client>(pid)?;
-    let authority = solana_sdk::signature::read_keypair_file(&authority_file)
-        .map_err(|_| anyhow!("Unable to read leader keypair file"))?;
+    let authority = authority_file.read()?;
     match cmd {
         GovCommand::WhitelistAdd {
             program_id,
             instance,
             nonce,
+            max_outstanding,
+        } => {
+            let entry = WhitelistEntry::new(program_id, instance, nonce);
+            match max_outstanding {
+                None => {
+                    client.whitelist_add(WhitelistAddRequest {
+                        authority: &authority,
+                        safe,
+                        entry,
+                    })?;
+                }
+                Some(max_outstanding) => {
+                    client.whitelist_add_many(WhitelistAddManyRequest {
+                        authority: &authority,
+                        safe,
+                        entries: vec![(entry, max_outstanding)],
+                    })?;
+                }
+            }
+        }
+        GovCommand::WhitelistSetMaxOutstanding {
+            program_id,
+            instance,
+            nonce,
+            max_outstanding,
         } => {
-            client.whitelist_add(WhitelistAddRequest {
+            client.whitelist_set_max_outstanding(WhitelistSetMaxOutstandingRequest {
                 authority: &authority,
                 safe,
                 entry: WhitelistEntry::new(program_id, instance, nonce),
+                max_outstanding,
             })?;
         }
         GovCommand::WhitelistDelete {
@@ -1,11 +1,11 @@
 use crate::InitializeResponse;
 use serum_common::client::rpc;
-use serum_common::pack::Pack;
-use serum_lockup::accounts::{Safe, Whitelist};
+use serum_common::pack::{Pack, PackedSize};
+use serum_lockup::accounts::{Safe, Unlock, Vesting, VestingSchedule, Whitelist};
 use serum_lockup::client::{Client as InnerClient, ClientError as InnerClientError};
 use solana_client_gen::prelude::*;
 use solana_client_gen::solana_sdk;
-use solana_client_gen::solana_sdk::instruction::AccountMeta;
+use solana_client_gen::solana_sdk::instruction::{AccountMeta, Instruction};
 use solana_client_gen::solana_sdk::pubkey::Pubkey;
 use solana_client_gen::solana_sdk::system_instruction;
 
@@ -83,25 +83,14 @@ pub fn create_all_accounts_and_initialize(
         ]
     };
 
-    let tx = {
-        let (recent_hash, _fee_calc) = client
-            .rpc()
-            .get_recent_blockhash()
-            .map_err(|e| InnerClientError::RawError(e.to_string()))?;
-        let signers = vec![client.payer(), &safe_acc, &wl_kp];
-        Transaction::new_signed_with_payer(
-            &instructions,
-            Some(&client.payer().pubkey()),
-            &signers,
-            recent_hash,
-        )
-    };
-
     // Execute the transaction.
+    let signers = vec![client.payer(), &safe_acc, &wl_kp];
     client
-        .rpc()
-        .send_and_confirm_transaction_with_spinner_and_config(
-            &tx,
+        .session()
+        .send_and_confirm_with_config(
+            &instructions,
+            &client.payer().pubkey(),
+            &signers,
             client.options().commitment,
             client.options().tx,
         )
@@ -115,6 +104,25 @@ pub fn create_all_accounts_and_initialize(
             nonce,
         })
 }
+
+/// How a new vesting account releases its deposit.
+pub enum Release {
+    Linear {
+        end_slot: u64,
+        period_count: u64,
+    },
+    Scheduled {
+        cliff_ts: i64,
+        unlocks: Vec<Unlock>,
+        revoker: Option<Pubkey>,
+    },
+    Streaming {
+        start_ts: i64,
+        end_ts: i64,
+        revoker: Option<Pubkey>,
+    },
+}
+
 pub fn create_vesting_account(
     client: &InnerClient,
     depositor: &Pubkey,
@@ -123,25 +131,26 @@ pub fn create_vesting_account(
     safe_vault: &Pubkey,
     safe_vault_authority: &Pubkey,
     vesting_acc_beneficiary: &Pubkey,
-    end_slot: u64,
-    period_count: u64,
+    release: Release,
     deposit_amount: u64,
     mint_decimals: u8,
 ) -> Result<(Signature, Keypair, Pubkey), InnerClientError> {
     let mint_kp = Keypair::generate(&mut OsRng);
 
-    let _tx_sig = rpc::create_and_init_mint(
+    // The vault authority freezes the receipts if the vesting is revoked.
+    let _tx_sig = rpc::create_and_init_mint_with_freeze_authority(
         client.rpc(),
         client.payer(),
         &mint_kp,
         &safe_vault_authority,
+        Some(&safe_vault_authority),
         mint_decimals,
     )
     .map_err(|e| InnerClientError::RawError(e.to_string()))?;
 
     // The vesting account being created.
     let new_account = Keypair::generate(&mut OsRng);
-    let deposit_accs = [
+    let mut deposit_accs = vec![
         AccountMeta::new(new_account.pubkey(), true),
         AccountMeta::new(*depositor, false),
         AccountMeta::new(depositor_owner.pubkey(), true),
@@ -156,46 +165,103 @@ pub fn create_vesting_account(
     let create_account_instr = {
         let lamports = client
             .rpc()
-            .get_minimum_balance_for_rent_exemption(*serum_lockup::accounts::vesting::SIZE as usize)
+            .get_minimum_balance_for_rent_exemption(Vesting::SIZE as usize)
             .map_err(InnerClientError::RpcError)?;
         system_instruction::create_account(
             &client.payer().pubkey(),
             &new_account.pubkey(),
             lamports,
-            *serum_lockup::accounts::vesting::SIZE,
+            Vesting::SIZE,
             client.program(),
         )
     };
-    let create_vesting_instr = serum_lockup::instruction::create_vesting(
-        *client.program(),
-        &deposit_accs,
-        *vesting_acc_beneficiary,
-        end_slot,
-        period_count,
-        deposit_amount,
-    );
+    let mut instructions = vec![create_account_instr];
+    match release {
+        Release::Linear {
+            end_slot,
+            period_count,
+        } => {
+            instructions.push(serum_lockup::instruction::create_vesting(
+                *client.program(),
+                &deposit_accs,
+                *vesting_acc_beneficiary,
+                end_slot,
+                period_count,
+                deposit_amount,
+            ));
+        }
+        Release::Scheduled {
+            cliff_ts,
+            unlocks,
+            revoker,
+        } => {
+            let (create_schedule_instr, schedule) =
+                create_vesting_schedule_account(client, &new_account.pubkey())?;
+            instructions.push(create_schedule_instr);
+            deposit_accs.push(AccountMeta::new(schedule, false));
+            instructions.push(serum_lockup::instruction::create_scheduled_vesting(
+                *client.program(),
+                &deposit_accs,
+                *vesting_acc_beneficiary,
+                cliff_ts,
+                unlocks,
+                revoker,
+                deposit_amount,
+            ));
+        }
+        Release::Streaming {
+            start_ts,
+            end_ts,
+            revoker,
+        } => {
+            let (create_schedule_instr, schedule) =
+                create_vesting_schedule_account(client, &new_account.pubkey())?;
+            instructions.push(create_schedule_instr);
+            deposit_accs.push(AccountMeta::new(schedule, false));
+            instructions.push(serum_lockup::instruction::create_streaming_vesting(
+                *client.program(),
+                &deposit_accs,
+                *vesting_acc_beneficiary,
+                start_ts,
+                end_ts,
+                revoker,
+                deposit_amount,
+            ));
+        }
+    }
 
-    let instructions = [create_account_instr, create_vesting_instr];
-    let tx = {
-        let (recent_hash, _fee_calc) = client
-            .rpc()
-            .get_recent_blockhash()
-            .map_err(|e| InnerClientError::RawError(e.to_string()))?;
-        let signers = vec![client.payer(), depositor_owner, &new_account];
-        Transaction::new_signed_with_payer(
+    let signers = vec![client.payer(), depositor_owner, &new_account];
+    client
+        .session()
+        .send_and_confirm_with_config(
             &instructions,
-            Some(&client.payer().pubkey()),
+            &client.payer().pubkey(),
             &signers,
-            recent_hash,
-        )
-    };
-    client
-        .rpc()
-        .send_and_confirm_transaction_with_spinner_and_config(
-            &tx,
             client.options().commitment,
             client.options().tx,
         )
         .map_err(InnerClientError::RpcError)
         .map(|sig| (sig, new_account, mint_kp.pubkey()))
 }
+
+// The schedule is derived from the vesting account, which signs as the base.
+fn create_vesting_schedule_account(
+    client: &InnerClient,
+    vesting: &Pubkey,
+) -> Result<(Instruction, Pubkey), InnerClientError> {
+    let schedule = VestingSchedule::address(vesting, client.program());
+    let lamports = client
+        .rpc()
+        .get_minimum_balance_for_rent_exemption(VestingSchedule::SIZE)
+        .map_err(InnerClientError::RpcError)?;
+    let instr = system_instruction::create_account_with_seed(
+        &client.payer().pubkey(),
+        &schedule,
+        vesting,
+        VestingSchedule::SEED,
+        lamports,
+        VestingSchedule::SIZE as u64,
+        client.program(),
+    );
+    Ok((instr, schedule))
+}
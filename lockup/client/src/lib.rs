@@ -3,7 +3,12 @@
 
 use anyhow::anyhow;
 use serum_common::client::rpc;
-use serum_lockup::accounts::{Safe, TokenVault, Vesting, Whitelist, WhitelistEntry};
+use serum_common::pack::PackedSize;
+use serum_dex::instruction::{NewOrderInstructionV2, SelfTradeBehavior};
+use serum_dex::matching::{OrderType, Side};
+use serum_lockup::accounts::{
+    Safe, TokenVault, Unlock, Vesting, VestingSchedule, Whitelist, WhitelistEntry, WhitelistItem,
+};
 use serum_lockup::client::{Client as InnerClient, ClientError as InnerClientError};
 use serum_lockup::error::LockupError;
 use solana_client_gen::prelude::Signer;
@@ -12,8 +17,9 @@ use solana_client_gen::solana_sdk;
 use solana_sdk::instruction::AccountMeta;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
-use spl_token::state::Account as TokenAccount;
+use spl_token::state::{Account as TokenAccount, Mint};
 use std::convert::Into;
+use std::num::NonZeroU64;
 use thiserror::Error;
 
 mod inner;
@@ -36,8 +42,10 @@ impl Client {
         &self,
         req: CreateVestingRequest,
     ) -> Result<CreateVestingResponse, ClientError> {
-        let vault = self.safe(&req.safe)?.vault;
-        let mint_decimals = 3; // TODO: decide this.
+        let safe = self.safe(&req.safe)?;
+        let vault = safe.vault;
+        // The receipt is denominated like the locked token.
+        let mint_decimals = self.mint_decimals(&safe.mint)?;
         inner::create_vesting_account(
             &self.inner,
             &req.depositor,
@@ -46,8 +54,77 @@ impl Client {
             &vault,
             &self.vault_authority(req.safe)?,
             &req.beneficiary,
-            req.end_slot,
-            req.period_count,
+            inner::Release::Linear {
+                end_slot: req.end_slot,
+                period_count: req.period_count,
+            },
+            req.deposit_amount,
+            mint_decimals,
+        )
+        .map_err(Into::into)
+        .map(|r| CreateVestingResponse {
+            tx: r.0,
+            vesting: r.1.pubkey(),
+            mint: r.2,
+        })
+    }
+
+    /// Same as `create_vesting`, releasing the deposit according to the
+    /// given unlock points instead of linearly.
+    pub fn create_scheduled_vesting(
+        &self,
+        req: CreateScheduledVestingRequest,
+    ) -> Result<CreateVestingResponse, ClientError> {
+        let safe = self.safe(&req.safe)?;
+        let vault = safe.vault;
+        // The receipt is denominated like the locked token.
+        let mint_decimals = self.mint_decimals(&safe.mint)?;
+        inner::create_vesting_account(
+            &self.inner,
+            &req.depositor,
+            req.depositor_owner,
+            &req.safe,
+            &vault,
+            &self.vault_authority(req.safe)?,
+            &req.beneficiary,
+            inner::Release::Scheduled {
+                cliff_ts: req.cliff_ts,
+                unlocks: req.unlocks,
+                revoker: req.revoker,
+            },
+            req.deposit_amount,
+            mint_decimals,
+        )
+        .map_err(Into::into)
+        .map(|r| CreateVestingResponse {
+            tx: r.0,
+            vesting: r.1.pubkey(),
+            mint: r.2,
+        })
+    }
+
+    /// Same as `create_vesting`, streaming the deposit per second between
+    /// two unix timestamps.
+    pub fn create_streaming_vesting(
+        &self,
+        req: CreateStreamingVestingRequest,
+    ) -> Result<CreateVestingResponse, ClientError> {
+        let safe = self.safe(&req.safe)?;
+        let vault = safe.vault;
+        let mint_decimals = self.mint_decimals(&safe.mint)?;
+        inner::create_vesting_account(
+            &self.inner,
+            &req.depositor,
+            req.depositor_owner,
+            &req.safe,
+            &vault,
+            &self.vault_authority(req.safe)?,
+            &req.beneficiary,
+            inner::Release::Streaming {
+                start_ts: req.start_ts,
+                end_ts: req.end_ts,
+                revoker: req.revoker,
+            },
             req.deposit_amount,
             mint_decimals,
         )
@@ -103,6 +180,76 @@ impl Client {
         Ok(WhitelistDeleteResponse { tx })
     }
 
+    pub fn whitelist_add_many(
+        &self,
+        req: WhitelistAddManyRequest,
+    ) -> Result<WhitelistAddManyResponse, ClientError> {
+        let WhitelistAddManyRequest {
+            authority,
+            safe,
+            entries,
+        } = req;
+        let whitelist = self.safe(&safe)?.whitelist;
+        let accounts = [
+            AccountMeta::new_readonly(authority.pubkey(), true),
+            AccountMeta::new_readonly(safe, false),
+            AccountMeta::new(whitelist, false),
+        ];
+        let signers = [self.payer(), authority];
+        let tx = self
+            .inner
+            .whitelist_add_many_with_signers(&signers, &accounts, entries)?;
+        Ok(WhitelistAddManyResponse { tx })
+    }
+
+    pub fn whitelist_remove_many(
+        &self,
+        req: WhitelistRemoveManyRequest,
+    ) -> Result<WhitelistRemoveManyResponse, ClientError> {
+        let WhitelistRemoveManyRequest {
+            authority,
+            safe,
+            entries,
+        } = req;
+        let whitelist = self.safe(&safe)?.whitelist;
+        let accounts = [
+            AccountMeta::new_readonly(authority.pubkey(), true),
+            AccountMeta::new_readonly(safe, false),
+            AccountMeta::new(whitelist, false),
+        ];
+        let signers = [self.payer(), authority];
+        let tx = self
+            .inner
+            .whitelist_remove_many_with_signers(&signers, &accounts, entries)?;
+        Ok(WhitelistRemoveManyResponse { tx })
+    }
+
+    pub fn whitelist_set_max_outstanding(
+        &self,
+        req: WhitelistSetMaxOutstandingRequest,
+    ) -> Result<WhitelistSetMaxOutstandingResponse, ClientError> {
+        let WhitelistSetMaxOutstandingRequest {
+            authority,
+            safe,
+            entry,
+            max_outstanding,
+        } = req;
+        let whitelist = self.safe(&safe)?.whitelist;
+        let accounts = [
+            AccountMeta::new_readonly(authority.pubkey(), true),
+            AccountMeta::new_readonly(safe, false),
+            AccountMeta::new(whitelist, false),
+        ];
+        let signers = [self.payer(), authority];
+        let tx = self.inner.whitelist_set_max_outstanding_with_signers(
+            &signers,
+            &accounts,
+            entry,
+            max_outstanding,
+        )?;
+        Ok(WhitelistSetMaxOutstandingResponse { tx })
+    }
+
     pub fn whitelist_withdraw(
         &self,
         req: WhitelistWithdrawRequest,
@@ -126,7 +273,7 @@ impl Client {
             AccountMeta::new_readonly(safe, false),
             AccountMeta::new_readonly(self.vault_authority(safe)?, false),
             AccountMeta::new_readonly(whitelist_program, false),
-            AccountMeta::new_readonly(whitelist, false),
+            AccountMeta::new(whitelist, false),
             // Below are relay accounts.
             AccountMeta::new(vault, false),
             AccountMeta::new(whitelist_vault, false),
@@ -167,7 +314,7 @@ impl Client {
             AccountMeta::new_readonly(safe, false),
             AccountMeta::new_readonly(self.vault_authority(safe)?, false),
             AccountMeta::new_readonly(whitelist_program, false),
-            AccountMeta::new_readonly(whitelist, false),
+            AccountMeta::new(whitelist, false),
             // Below are relay accounts.
             AccountMeta::new(vault, false),
             AccountMeta::new(whitelist_vault, false),
@@ -220,7 +367,7 @@ impl Client {
             locked_mint,
             amount,
         } = req;
-        let accounts = [
+        let mut accounts = vec![
             AccountMeta::new_readonly(beneficiary.pubkey(), true),
             AccountMeta::new(vesting, false),
             AccountMeta::new(token_account, false),
@@ -232,6 +379,7 @@ impl Client {
             AccountMeta::new_readonly(spl_token::ID, false),
             AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
         ];
+        accounts.extend(self.vesting_schedule_account(&vesting)?);
         let signers = [self.payer(), &beneficiary];
         let tx = self
             .inner
@@ -239,6 +387,413 @@ impl Client {
         Ok(RedeemResponse { tx })
     }
 
+    /// Ends vesting on a revocable account, sending the unvested tokens to
+    /// `receiver`.
+    pub fn revoke(&self, req: RevokeRequest) -> Result<RevokeResponse, ClientError> {
+        let RevokeRequest {
+            revoker,
+            vesting,
+            safe,
+            receiver,
+        } = req;
+        let vault = self.safe(&safe)?.vault;
+        let vesting_acc = self.vesting(&vesting)?;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(revoker.pubkey(), true),
+            AccountMeta::new(vesting, false),
+            AccountMeta::new(VestingSchedule::address(&vesting, self.program()), false),
+            AccountMeta::new_readonly(safe, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(self.vault_authority(safe)?, false),
+            AccountMeta::new(receiver, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+        ];
+        if vesting_acc.claimed {
+            accounts.extend_from_slice(&[
+                AccountMeta::new(vesting_acc.locked_nft_token, false),
+                AccountMeta::new_readonly(vesting_acc.locked_nft_mint, false),
+            ]);
+        }
+        let signers = [self.payer(), revoker];
+        let tx = self.inner.revoke_with_signers(&signers, &accounts)?;
+        Ok(RevokeResponse { tx })
+    }
+
+    /// Replaces the release of a vesting account, adding `deposit_amount`
+    /// from the depositor. Signed by both the beneficiary and the grantor.
+    pub fn relock_vesting(
+        &self,
+        req: RelockVestingRequest,
+    ) -> Result<RelockVestingResponse, ClientError> {
+        let RelockVestingRequest {
+            beneficiary,
+            grantor,
+            vesting,
+            depositor,
+            depositor_owner,
+            relock,
+            deposit_amount,
+        } = req;
+        let vesting_acc = self.vesting(&vesting)?;
+        let safe = vesting_acc.safe;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(beneficiary.pubkey(), true),
+            AccountMeta::new_readonly(grantor.pubkey(), true),
+            AccountMeta::new(vesting, false),
+            AccountMeta::new_readonly(safe, false),
+            AccountMeta::new(depositor, false),
+            AccountMeta::new_readonly(depositor_owner.pubkey(), true),
+            AccountMeta::new(self.safe(&safe)?.vault, false),
+            AccountMeta::new_readonly(self.vault_authority(safe)?, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+        ];
+        if vesting_acc.has_schedule() {
+            let schedule = VestingSchedule::address(&vesting, self.program());
+            accounts.push(AccountMeta::new(schedule, false));
+        }
+        if vesting_acc.claimed {
+            accounts.push(AccountMeta::new(vesting_acc.locked_nft_token, false));
+            accounts.push(AccountMeta::new(vesting_acc.locked_nft_mint, false));
+        }
+        let signers = [self.payer(), beneficiary, grantor, depositor_owner];
+        let tx = match relock {
+            Relock::Linear {
+                end_slot,
+                period_count,
+            } => self.inner.relock_vesting_with_signers(
+                &signers,
+                &accounts,
+                end_slot,
+                period_count,
+                deposit_amount,
+            )?,
+            Relock::Scheduled { cliff_ts, unlocks } => {
+                self.inner.relock_scheduled_vesting_with_signers(
+                    &signers,
+                    &accounts,
+                    cliff_ts,
+                    unlocks,
+                    deposit_amount,
+                )?
+            }
+        };
+        Ok(RelockVestingResponse { tx })
+    }
+
+    /// Tops up a vesting account with `deposit_amount` from the depositor,
+    /// vesting over what remains of its release. Signed by the grantor.
+    pub fn deposit_to_vesting(
+        &self,
+        req: DepositToVestingRequest,
+    ) -> Result<DepositToVestingResponse, ClientError> {
+        let DepositToVestingRequest {
+            grantor,
+            vesting,
+            depositor,
+            depositor_owner,
+            deposit_amount,
+        } = req;
+        let vesting_acc = self.vesting(&vesting)?;
+        let safe = vesting_acc.safe;
+        let mut accounts = vec![
+            AccountMeta::new_readonly(grantor.pubkey(), true),
+            AccountMeta::new(vesting, false),
+            AccountMeta::new_readonly(safe, false),
+            AccountMeta::new(depositor, false),
+            AccountMeta::new_readonly(depositor_owner.pubkey(), true),
+            AccountMeta::new(self.safe(&safe)?.vault, false),
+            AccountMeta::new_readonly(self.vault_authority(safe)?, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+        ];
+        if vesting_acc.has_schedule() {
+            let schedule = VestingSchedule::address(&vesting, self.program());
+            accounts.push(AccountMeta::new_readonly(schedule, false));
+        }
+        if vesting_acc.claimed {
+            accounts.push(AccountMeta::new(vesting_acc.locked_nft_token, false));
+            accounts.push(AccountMeta::new(vesting_acc.locked_nft_mint, false));
+        }
+        let signers = [self.payer(), grantor, depositor_owner];
+        let tx = self
+            .inner
+            .deposit_to_vesting_with_signers(&signers, &accounts, deposit_amount)?;
+        Ok(DepositToVestingResponse { tx })
+    }
+
+    /// Moves `amount` of a vesting account's balance into a new vesting
+    /// account for `new_beneficiary`.
+    pub fn split_vesting(
+        &self,
+        req: SplitVestingRequest,
+    ) -> Result<SplitVestingResponse, ClientError> {
+        let SplitVestingRequest {
+            beneficiary,
+            vesting,
+            amount,
+            new_beneficiary,
+        } = req;
+        let vesting_acc = self.vesting(&vesting)?;
+        let safe = self.safe(&vesting_acc.safe)?;
+        let vault_authority = self.vault_authority(vesting_acc.safe)?;
+
+        let mint_kp = Keypair::generate(&mut OsRng);
+        rpc::create_and_init_mint_with_freeze_authority(
+            self.rpc(),
+            self.payer(),
+            &mint_kp,
+            &vault_authority,
+            Some(&vault_authority),
+            self.mint_decimals(&safe.mint)?,
+        )?;
+
+        let new_vesting = Keypair::generate(&mut OsRng);
+        let mut instructions = vec![solana_sdk::system_instruction::create_account(
+            &self.payer().pubkey(),
+            &new_vesting.pubkey(),
+            self.rpc()
+                .get_minimum_balance_for_rent_exemption(Vesting::SIZE as usize)?,
+            Vesting::SIZE,
+            self.program(),
+        )];
+        let mut accounts = vec![
+            AccountMeta::new_readonly(beneficiary.pubkey(), true),
+            AccountMeta::new(vesting, false),
+            AccountMeta::new(new_vesting.pubkey(), false),
+            AccountMeta::new_readonly(vesting_acc.safe, false),
+            AccountMeta::new(mint_kp.pubkey(), false),
+            AccountMeta::new_readonly(vault_authority, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+        ];
+        if vesting_acc.claimed {
+            accounts.push(AccountMeta::new(vesting_acc.locked_nft_token, false));
+            accounts.push(AccountMeta::new(vesting_acc.locked_nft_mint, false));
+        }
+        if vesting_acc.has_schedule() {
+            // The new schedule is derived from the new vesting account,
+            // which signs as the base.
+            let new_schedule = VestingSchedule::address(&new_vesting.pubkey(), self.program());
+            instructions.push(solana_sdk::system_instruction::create_account_with_seed(
+                &self.payer().pubkey(),
+                &new_schedule,
+                &new_vesting.pubkey(),
+                VestingSchedule::SEED,
+                self.rpc()
+                    .get_minimum_balance_for_rent_exemption(VestingSchedule::SIZE)?,
+                VestingSchedule::SIZE as u64,
+                self.program(),
+            ));
+            accounts.push(AccountMeta::new_readonly(
+                VestingSchedule::address(&vesting, self.program()),
+                false,
+            ));
+            accounts.push(AccountMeta::new(new_schedule, false));
+        }
+        instructions.push(serum_lockup::instruction::split_vesting(
+            *self.program(),
+            &accounts,
+            amount,
+            new_beneficiary,
+        ));
+
+        let signers = [self.payer(), beneficiary, &new_vesting];
+        let tx = self.inner.session().send_and_confirm_with_config(
+            &instructions,
+            &self.payer().pubkey(),
+            &signers,
+            self.inner.options().commitment,
+            self.inner.options().tx,
+        )?;
+        Ok(SplitVestingResponse {
+            tx,
+            vesting: new_vesting.pubkey(),
+            mint: mint_kp.pubkey(),
+        })
+    }
+
+    /// Proposes `new_beneficiary` as the beneficiary of a vesting account.
+    /// Any receipts are burned until the transfer is accepted or cancelled.
+    pub fn initiate_beneficiary_transfer(
+        &self,
+        req: InitiateBeneficiaryTransferRequest,
+    ) -> Result<InitiateBeneficiaryTransferResponse, ClientError> {
+        let InitiateBeneficiaryTransferRequest {
+            beneficiary,
+            vesting,
+            new_beneficiary,
+        } = req;
+        let vesting_acc = self.vesting(&vesting)?;
+        let transfer = Keypair::generate(&mut OsRng);
+        let mut accounts = vec![
+            AccountMeta::new(transfer.pubkey(), false),
+            AccountMeta::new(vesting, false),
+            AccountMeta::new_readonly(beneficiary.pubkey(), true),
+            AccountMeta::new_readonly(vesting_acc.safe, false),
+            AccountMeta::new_readonly(self.vault_authority(vesting_acc.safe)?, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+        ];
+        if vesting_acc.claimed {
+            accounts.push(AccountMeta::new(vesting_acc.locked_nft_token, false));
+            accounts.push(AccountMeta::new(vesting_acc.locked_nft_mint, false));
+        }
+        let signers = [self.payer(), &transfer, beneficiary];
+        let (tx, transfer) = self
+            .inner
+            .create_account_and_initiate_beneficiary_transfer_with_signers(
+                Keypair::from_bytes(&transfer.to_bytes()).expect("valid keypair"),
+                &signers,
+                &accounts,
+                new_beneficiary,
+            )?;
+        Ok(InitiateBeneficiaryTransferResponse {
+            tx,
+            transfer: transfer.pubkey(),
+        })
+    }
+
+    pub fn accept_beneficiary_transfer(
+        &self,
+        req: AcceptBeneficiaryTransferRequest,
+    ) -> Result<AcceptBeneficiaryTransferResponse, ClientError> {
+        let AcceptBeneficiaryTransferRequest {
+            new_beneficiary,
+            transfer,
+            vesting,
+        } = req;
+        let accounts = [
+            AccountMeta::new(transfer, false),
+            AccountMeta::new(vesting, false),
+            AccountMeta::new(new_beneficiary.pubkey(), true),
+        ];
+        let signers = [self.payer(), new_beneficiary];
+        let tx = self
+            .inner
+            .accept_beneficiary_transfer_with_signers(&signers, &accounts)?;
+        Ok(AcceptBeneficiaryTransferResponse { tx })
+    }
+
+    pub fn cancel_beneficiary_transfer(
+        &self,
+        req: CancelBeneficiaryTransferRequest,
+    ) -> Result<CancelBeneficiaryTransferResponse, ClientError> {
+        let CancelBeneficiaryTransferRequest {
+            beneficiary,
+            transfer,
+        } = req;
+        let accounts = [
+            AccountMeta::new(transfer, false),
+            AccountMeta::new(beneficiary.pubkey(), true),
+        ];
+        let signers = [self.payer(), beneficiary];
+        let tx = self
+            .inner
+            .cancel_beneficiary_transfer_with_signers(&signers, &accounts)?;
+        Ok(CancelBeneficiaryTransferResponse { tx })
+    }
+
+    /// Redeems vested tokens and sells them on a DEX market in the same
+    /// transaction. The sell is immediate-or-cancel at `limit_price` or
+    /// better, so nothing is sold below that price, and whatever doesn't
+    /// fill is settled back into `token_account` with the proceeds going to
+    /// `pc_wallet`.
+    pub fn redeem_and_sell(
+        &self,
+        req: RedeemAndSellRequest,
+    ) -> Result<RedeemAndSellResponse, ClientError> {
+        let RedeemAndSellRequest {
+            beneficiary,
+            vesting,
+            token_account,
+            safe,
+            locked_token_account,
+            locked_mint,
+            amount,
+            dex_program_id,
+            market,
+            open_orders,
+            pc_wallet,
+            limit_price,
+            max_coin_qty,
+        } = req;
+        let vault = self.safe(&safe)?.vault;
+        let market_keys = crank::get_keys_for_market(self.rpc(), &dex_program_id, &market)?;
+
+        let open_orders_kp;
+        let mut instructions = vec![];
+        let mut signers = vec![self.payer(), beneficiary];
+        let open_orders = match open_orders {
+            Some(open_orders) => open_orders,
+            None => {
+                let (kp, instr) = crank::create_dex_account(
+                    self.rpc(),
+                    &dex_program_id,
+                    &self.payer().pubkey(),
+                    std::mem::size_of::<serum_dex::state::OpenOrders>(),
+                )?;
+                instructions.push(instr);
+                open_orders_kp = kp;
+                signers.push(&open_orders_kp);
+                open_orders_kp.pubkey()
+            }
+        };
+
+        let mut redeem_accounts = vec![
+            AccountMeta::new_readonly(beneficiary.pubkey(), true),
+            AccountMeta::new(vesting, false),
+            AccountMeta::new(token_account, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(self.vault_authority(safe)?, false),
+            AccountMeta::new_readonly(safe, false),
+            AccountMeta::new(locked_token_account, false),
+            AccountMeta::new(locked_mint, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+        ];
+        redeem_accounts.extend(self.vesting_schedule_account(&vesting)?);
+        instructions.push(serum_lockup::instruction::redeem(
+            *self.program(),
+            &redeem_accounts,
+            amount,
+        ));
+        instructions.push(crank::new_order_v3_instruction(
+            &dex_program_id,
+            &market_keys,
+            &open_orders,
+            &token_account,
+            &beneficiary.pubkey(),
+            NewOrderInstructionV2 {
+                side: Side::Ask,
+                limit_price,
+                max_qty: max_coin_qty,
+                order_type: OrderType::ImmediateOrCancel,
+                client_id: 0,
+                self_trade_behavior: SelfTradeBehavior::DecrementTake,
+            },
+        ));
+        instructions.push(crank::settle_funds_instruction(
+            &dex_program_id,
+            &market_keys,
+            &open_orders,
+            &beneficiary.pubkey(),
+            &token_account,
+            &pc_wallet,
+            None,
+        ));
+
+        let tx = self.inner.session().send_and_confirm_with_config(
+            &instructions,
+            &self.payer().pubkey(),
+            &signers,
+            self.inner.options().commitment,
+            self.inner.options().tx,
+        )?;
+        Ok(RedeemAndSellResponse { tx, open_orders })
+    }
+
     pub fn set_authority(
         &self,
         req: SetAuthorityRequest,
@@ -315,6 +870,15 @@ impl Client {
         Ok(())
     }
 
+    /// Returns the occupied whitelist entries along with their metadata.
+    pub fn whitelist_items(&self, safe: &Pubkey) -> Result<Vec<WhitelistItem>, ClientError> {
+        let mut items = Ok(vec![]);
+        self.with_whitelist(safe, |wl: Whitelist| {
+            items = wl.items();
+        })?;
+        items.map_err(Into::into)
+    }
+
     pub fn vault(&self, safe: &Pubkey) -> Result<TokenAccount, ClientError> {
         let safe = rpc::get_account::<Safe>(self.inner.rpc(), &safe)?;
         rpc::get_token_account::<TokenAccount>(self.inner.rpc(), &safe.vault).map_err(Into::into)
@@ -323,10 +887,32 @@ impl Client {
     pub fn vesting(&self, addr: &Pubkey) -> Result<Vesting, ClientError> {
         rpc::get_account::<Vesting>(self.inner.rpc(), addr).map_err(Into::into)
     }
+
+    pub fn vesting_schedule(&self, vesting: &Pubkey) -> Result<VestingSchedule, ClientError> {
+        let address = VestingSchedule::address(vesting, self.program());
+        rpc::get_account::<VestingSchedule>(self.inner.rpc(), &address).map_err(Into::into)
+    }
 }
 
 // Private.
 impl Client {
+    fn mint_decimals(&self, mint: &Pubkey) -> Result<u8, ClientError> {
+        let mint = rpc::get_token_account::<Mint>(self.inner.rpc(), mint)?;
+        Ok(mint.decimals)
+    }
+
+    // Redeem takes the vesting schedule as a trailing account, if there is one.
+    fn vesting_schedule_account(
+        &self,
+        vesting: &Pubkey,
+    ) -> Result<Option<AccountMeta>, ClientError> {
+        if !self.vesting(vesting)?.has_schedule() {
+            return Ok(None);
+        }
+        let address = VestingSchedule::address(vesting, self.program());
+        Ok(Some(AccountMeta::new_readonly(address, false)))
+    }
+
     fn vault_authority(&self, safe_addr: Pubkey) -> Result<Pubkey, ClientError> {
         let safe = self.safe(&safe_addr)?;
         let seeds = TokenVault::signer_seeds(&safe_addr, &safe.nonce);
@@ -383,6 +969,28 @@ pub struct CreateVestingRequest<'a> {
     pub deposit_amount: u64,
 }
 
+pub struct CreateScheduledVestingRequest<'a> {
+    pub depositor: Pubkey,
+    pub depositor_owner: &'a Keypair,
+    pub safe: Pubkey,
+    pub beneficiary: Pubkey,
+    pub cliff_ts: i64,
+    pub unlocks: Vec<Unlock>,
+    pub revoker: Option<Pubkey>,
+    pub deposit_amount: u64,
+}
+
+pub struct CreateStreamingVestingRequest<'a> {
+    pub depositor: Pubkey,
+    pub depositor_owner: &'a Keypair,
+    pub safe: Pubkey,
+    pub beneficiary: Pubkey,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub revoker: Option<Pubkey>,
+    pub deposit_amount: u64,
+}
+
 #[derive(Debug)]
 pub struct CreateVestingResponse {
     pub tx: Signature,
@@ -401,6 +1009,41 @@ pub struct WhitelistAddResponse {
     pub tx: Signature,
 }
 
+pub struct WhitelistAddManyRequest<'a> {
+    pub authority: &'a Keypair,
+    pub safe: Pubkey,
+    /// (entry, max outstanding) pairs.
+    pub entries: Vec<(WhitelistEntry, u64)>,
+}
+
+#[derive(Debug)]
+pub struct WhitelistAddManyResponse {
+    pub tx: Signature,
+}
+
+pub struct WhitelistRemoveManyRequest<'a> {
+    pub authority: &'a Keypair,
+    pub safe: Pubkey,
+    pub entries: Vec<WhitelistEntry>,
+}
+
+#[derive(Debug)]
+pub struct WhitelistRemoveManyResponse {
+    pub tx: Signature,
+}
+
+pub struct WhitelistSetMaxOutstandingRequest<'a> {
+    pub authority: &'a Keypair,
+    pub safe: Pubkey,
+    pub entry: WhitelistEntry,
+    pub max_outstanding: u64,
+}
+
+#[derive(Debug)]
+pub struct WhitelistSetMaxOutstandingResponse {
+    pub tx: Signature,
+}
+
 pub struct WhitelistDeleteRequest<'a> {
     pub authority: &'a Keypair,
     pub safe: Pubkey,
@@ -476,6 +1119,131 @@ pub struct RedeemResponse {
     pub tx: Signature,
 }
 
+pub struct RevokeRequest<'a> {
+    pub revoker: &'a Keypair,
+    pub vesting: Pubkey,
+    pub safe: Pubkey,
+    pub receiver: Pubkey,
+}
+
+#[derive(Debug)]
+pub struct RevokeResponse {
+    pub tx: Signature,
+}
+
+/// The new release of a relocked vesting account, of the same kind as the
+/// one it replaces.
+pub enum Relock {
+    Linear { end_slot: u64, period_count: u64 },
+    Scheduled { cliff_ts: i64, unlocks: Vec<Unlock> },
+}
+
+pub struct RelockVestingRequest<'a> {
+    pub beneficiary: &'a Keypair,
+    pub grantor: &'a Keypair,
+    pub vesting: Pubkey,
+    pub depositor: Pubkey,
+    pub depositor_owner: &'a Keypair,
+    pub relock: Relock,
+    pub deposit_amount: u64,
+}
+
+#[derive(Debug)]
+pub struct RelockVestingResponse {
+    pub tx: Signature,
+}
+
+pub struct DepositToVestingRequest<'a> {
+    pub grantor: &'a Keypair,
+    pub vesting: Pubkey,
+    pub depositor: Pubkey,
+    pub depositor_owner: &'a Keypair,
+    pub deposit_amount: u64,
+}
+
+#[derive(Debug)]
+pub struct DepositToVestingResponse {
+    pub tx: Signature,
+}
+
+pub struct SplitVestingRequest<'a> {
+    pub beneficiary: &'a Keypair,
+    pub vesting: Pubkey,
+    pub amount: u64,
+    pub new_beneficiary: Pubkey,
+}
+
+#[derive(Debug)]
+pub struct SplitVestingResponse {
+    pub tx: Signature,
+    /// The new vesting account.
+    pub vesting: Pubkey,
+    /// Receipt mint of the new vesting account.
+    pub mint: Pubkey,
+}
+
+pub struct InitiateBeneficiaryTransferRequest<'a> {
+    pub beneficiary: &'a Keypair,
+    pub vesting: Pubkey,
+    pub new_beneficiary: Pubkey,
+}
+
+#[derive(Debug)]
+pub struct InitiateBeneficiaryTransferResponse {
+    pub tx: Signature,
+    pub transfer: Pubkey,
+}
+
+pub struct AcceptBeneficiaryTransferRequest<'a> {
+    pub new_beneficiary: &'a Keypair,
+    pub transfer: Pubkey,
+    pub vesting: Pubkey,
+}
+
+#[derive(Debug)]
+pub struct AcceptBeneficiaryTransferResponse {
+    pub tx: Signature,
+}
+
+pub struct CancelBeneficiaryTransferRequest<'a> {
+    pub beneficiary: &'a Keypair,
+    pub transfer: Pubkey,
+}
+
+#[derive(Debug)]
+pub struct CancelBeneficiaryTransferResponse {
+    pub tx: Signature,
+}
+
+pub struct RedeemAndSellRequest<'a> {
+    pub beneficiary: &'a Keypair,
+    pub vesting: Pubkey,
+    /// Receives the redeemed tokens, pays for the sell, and gets back any
+    /// amount that wasn't sold.
+    pub token_account: Pubkey,
+    pub safe: Pubkey,
+    pub locked_token_account: Pubkey,
+    pub locked_mint: Pubkey,
+    pub amount: u64,
+    pub dex_program_id: Pubkey,
+    pub market: Pubkey,
+    /// The beneficiary's OpenOrders account on `market`. A new one is
+    /// created when None.
+    pub open_orders: Option<Pubkey>,
+    pub pc_wallet: Pubkey,
+    /// Minimum price accepted, in pc lots per coin lot.
+    pub limit_price: NonZeroU64,
+    /// Coin lots to sell, at most `amount` divided by the market's coin lot
+    /// size.
+    pub max_coin_qty: NonZeroU64,
+}
+
+#[derive(Debug)]
+pub struct RedeemAndSellResponse {
+    pub tx: Signature,
+    pub open_orders: Pubkey,
+}
+
 pub struct SetAuthorityRequest<'a> {
     pub authority: &'a Keypair,
     pub safe: Pubkey,
@@ -0,0 +1,122 @@
+use serum_common::pack::Pack;
+use serum_lockup::accounts::{BeneficiaryTransfer, Vesting};
+use serum_lockup::error::{LockupError, LockupErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use std::convert::Into;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), LockupError> {
+    info!("handler: accept_beneficiary_transfer");
+
+    let acc_infos = &mut accounts.iter();
+
+    let transfer_acc_info = next_account_info(acc_infos)?;
+    let vesting_acc_info = next_account_info(acc_infos)?;
+    let new_beneficiary_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        vesting_acc_info,
+        new_beneficiary_acc_info,
+    })?;
+
+    Vesting::unpack_mut(
+        &mut vesting_acc_info.try_borrow_mut_data()?,
+        &mut |vesting_acc: &mut Vesting| {
+            state_transition(StateTransitionRequest {
+                vesting_acc,
+                transfer_acc_info,
+                new_beneficiary_acc_info,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), LockupError> {
+    info!("access-control: accept_beneficiary_transfer");
+
+    let AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        vesting_acc_info,
+        new_beneficiary_acc_info,
+    } = req;
+
+    if !new_beneficiary_acc_info.is_signer {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+    if transfer_acc_info.owner != program_id || vesting_acc_info.owner != program_id {
+        return Err(LockupErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    let transfer = BeneficiaryTransfer::unpack(&transfer_acc_info.try_borrow_data()?)?;
+    if !transfer.initialized || transfer.vesting != *vesting_acc_info.key {
+        return Err(LockupErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    if transfer.new_beneficiary != *new_beneficiary_acc_info.key {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+    // A transfer initiated by a previous beneficiary is stale, as is one
+    // whose receipts have been claimed again since.
+    let vesting = Vesting::unpack(&vesting_acc_info.try_borrow_data()?)?;
+    if vesting.beneficiary != transfer.beneficiary || vesting.claimed {
+        return Err(LockupErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+
+    info!("access-control: success");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
+    info!("state-transition: accept_beneficiary_transfer");
+
+    let StateTransitionRequest {
+        vesting_acc,
+        transfer_acc_info,
+        new_beneficiary_acc_info,
+    } = req;
+
+    vesting_acc.beneficiary = *new_beneficiary_acc_info.key;
+
+    close(transfer_acc_info, new_beneficiary_acc_info)?;
+
+    info!("state-transition: success");
+
+    Ok(())
+}
+
+/// Clears the transfer account and returns its lamports to `dst_acc_info`.
+pub fn close(
+    transfer_acc_info: &AccountInfo,
+    dst_acc_info: &AccountInfo,
+) -> Result<(), LockupError> {
+    BeneficiaryTransfer::unpack_mut(
+        &mut transfer_acc_info.try_borrow_mut_data()?,
+        &mut |transfer: &mut BeneficiaryTransfer| {
+            *transfer = BeneficiaryTransfer::default();
+            Ok(())
+        },
+    )?;
+    crate::lamports::drain(transfer_acc_info, dst_acc_info)
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    vesting_acc_info: &'a AccountInfo<'a>,
+    new_beneficiary_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    vesting_acc: &'b mut Vesting,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    new_beneficiary_acc_info: &'a AccountInfo<'a>,
+}
@@ -4,7 +4,7 @@
 //       type of thing.
 
 use serum_common::pack::Pack;
-use serum_lockup::accounts::{Safe, TokenVault, Vesting, Whitelist};
+use serum_lockup::accounts::{Safe, TokenVault, Vesting, VestingSchedule, Whitelist};
 use serum_lockup::error::{LockupError, LockupErrorCode};
 use solana_sdk::account_info::AccountInfo;
 use solana_sdk::program_option::COption;
@@ -71,6 +71,30 @@ pub fn vesting(
     Ok(vesting)
 }
 
+/// Access control on the VestingSchedule of a Vesting account, given as an
+/// optional trailing account.
+pub fn vesting_schedule(
+    program_id: &Pubkey,
+    vesting_acc_info: &AccountInfo,
+    schedule_acc_info: Option<&AccountInfo>,
+) -> Result<VestingSchedule, LockupError> {
+    let schedule_acc_info = schedule_acc_info.ok_or(LockupErrorCode::WrongNumberOfAccounts)?;
+    if *schedule_acc_info.key != VestingSchedule::address(vesting_acc_info.key, program_id) {
+        return Err(LockupErrorCode::WrongVestingSchedule)?;
+    }
+    if schedule_acc_info.owner != program_id {
+        return Err(LockupErrorCode::InvalidAccountOwner)?;
+    }
+    let schedule = VestingSchedule::unpack(&schedule_acc_info.try_borrow_data()?)?;
+    if !schedule.initialized {
+        return Err(LockupErrorCode::NotInitialized)?;
+    }
+    if schedule.vesting != *vesting_acc_info.key {
+        return Err(LockupErrorCode::WrongVestingSchedule)?;
+    }
+    Ok(schedule)
+}
+
 pub fn rent(acc_info: &AccountInfo) -> Result<Rent, LockupError> {
     if *acc_info.key != solana_sdk::sysvar::rent::id() {
         return Err(LockupErrorCode::InvalidRentSysvar)?;
@@ -183,8 +207,14 @@ pub fn locked_token(
         return Err(LockupErrorCode::InvalidMintAuthority)?;
     }
 
-    // Token.
-    let token_acc = token(acc_info)?;
+    // Token, which is frozen once the vesting is revoked.
+    if *acc_info.owner != spl_token::ID {
+        return Err(LockupErrorCode::InvalidAccountOwner)?;
+    }
+    let token_acc = TokenAccount::unpack(&acc_info.try_borrow_data()?)?;
+    if token_acc.state == spl_token::state::AccountState::Uninitialized {
+        return Err(LockupErrorCode::NotInitialized)?;
+    }
     if token_acc.owner != vesting.beneficiary {
         return Err(LockupErrorCode::InvalidTokenAccountOwner)?;
     }
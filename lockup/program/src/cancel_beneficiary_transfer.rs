@@ -0,0 +1,86 @@
+use crate::accept_beneficiary_transfer::close;
+use serum_common::pack::Pack;
+use serum_lockup::accounts::BeneficiaryTransfer;
+use serum_lockup::error::{LockupError, LockupErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), LockupError> {
+    info!("handler: cancel_beneficiary_transfer");
+
+    let acc_infos = &mut accounts.iter();
+
+    let transfer_acc_info = next_account_info(acc_infos)?;
+    let beneficiary_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        beneficiary_acc_info,
+    })?;
+
+    state_transition(StateTransitionRequest {
+        transfer_acc_info,
+        beneficiary_acc_info,
+    })?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), LockupError> {
+    info!("access-control: cancel_beneficiary_transfer");
+
+    let AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        beneficiary_acc_info,
+    } = req;
+
+    if !beneficiary_acc_info.is_signer {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+    if transfer_acc_info.owner != program_id {
+        return Err(LockupErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    let transfer = BeneficiaryTransfer::unpack(&transfer_acc_info.try_borrow_data()?)?;
+    if !transfer.initialized {
+        return Err(LockupErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    if transfer.beneficiary != *beneficiary_acc_info.key {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+
+    info!("access-control: success");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
+    info!("state-transition: cancel_beneficiary_transfer");
+
+    let StateTransitionRequest {
+        transfer_acc_info,
+        beneficiary_acc_info,
+    } = req;
+
+    close(transfer_acc_info, beneficiary_acc_info)?;
+
+    info!("state-transition: success");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    beneficiary_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a> {
+    transfer_acc_info: &'a AccountInfo<'a>,
+    beneficiary_acc_info: &'a AccountInfo<'a>,
+}
@@ -1,7 +1,9 @@
 use crate::access_control;
+use serum_common::emit_event;
 use serum_common::pack::Pack;
 use serum_lockup::accounts::{Safe, TokenVault, Vesting};
 use serum_lockup::error::{LockupError, LockupErrorCode};
+use serum_lockup::events::Claimed;
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
 use solana_sdk::pubkey::Pubkey;
@@ -40,6 +42,7 @@ pub fn handler<'a>(
             state_transition(StateTransitionRequest {
                 accounts,
                 vesting_acc,
+                vesting_acc_info,
                 safe_acc_info,
                 safe_vault_authority_acc_info,
                 mint_acc_info,
@@ -107,6 +110,7 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
 
     let StateTransitionRequest {
         accounts,
+        vesting_acc_info,
         safe_acc_info,
         safe_vault_authority_acc_info,
         mint_acc_info,
@@ -127,7 +131,7 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
             token_acc_info.key,
             safe_vault_authority_acc_info.key,
             &[],
-            vesting_acc.start_balance,
+            vesting_acc.balance,
         )?;
 
         let signer_seeds = TokenVault::signer_seeds(safe_acc_info.key, &nonce);
@@ -138,6 +142,13 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
     vesting_acc.claimed = true;
     vesting_acc.locked_nft_token = *token_acc_info.key;
 
+    emit_event!(Claimed {
+        vesting: *vesting_acc_info.key,
+        beneficiary: vesting_acc.beneficiary,
+        token: *token_acc_info.key,
+        amount: vesting_acc.balance,
+    })?;
+
     info!("state-transition: success");
 
     Ok(())
@@ -155,6 +166,7 @@ struct AccessControlRequest<'a> {
 
 struct StateTransitionRequest<'a, 'b> {
     accounts: &'a [AccountInfo<'a>],
+    vesting_acc_info: &'a AccountInfo<'a>,
     safe_acc_info: &'a AccountInfo<'a>,
     safe_vault_authority_acc_info: &'a AccountInfo<'a>,
     mint_acc_info: &'a AccountInfo<'a>,
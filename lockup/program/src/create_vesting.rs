@@ -1,7 +1,9 @@
 use crate::access_control;
+use serum_common::emit_event;
 use serum_common::pack::Pack;
-use serum_lockup::accounts::{TokenVault, Vesting};
+use serum_lockup::accounts::{TokenVault, Unlock, Vesting, VestingSchedule};
 use serum_lockup::error::{LockupError, LockupErrorCode};
+use serum_lockup::events::Deposited;
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
 use solana_sdk::program_option::COption;
@@ -12,8 +14,7 @@ pub fn handler<'a>(
     program_id: &'a Pubkey,
     accounts: &'a [AccountInfo<'a>],
     vesting_acc_beneficiary: Pubkey,
-    end_slot: u64,
-    period_count: u64,
+    release: Release,
     deposit_amount: u64,
 ) -> Result<(), LockupError> {
     info!("handler: create_vesting");
@@ -30,12 +31,15 @@ pub fn handler<'a>(
     let token_program_acc_info = next_account_info(acc_infos)?;
     let rent_acc_info = next_account_info(acc_infos)?;
     let clock_acc_info = next_account_info(acc_infos)?;
+    let schedule_acc_info = match release {
+        Release::Linear { .. } => None,
+        Release::Scheduled { .. } => Some(next_account_info(acc_infos)?),
+    };
     let clock_slot = access_control::clock(&clock_acc_info)?.slot;
 
     access_control(AccessControlRequest {
         program_id,
-        end_slot,
-        period_count,
+        release: &release,
         deposit_amount,
         vesting_acc_info,
         safe_acc_info,
@@ -44,6 +48,7 @@ pub fn handler<'a>(
         vault_authority_acc_info,
         nft_mint_acc_info,
         rent_acc_info,
+        schedule_acc_info,
         clock_slot,
     })?;
 
@@ -52,11 +57,11 @@ pub fn handler<'a>(
         &mut |vesting_acc: &mut Vesting| {
             state_transition(StateTransitionRequest {
                 clock_slot,
-                end_slot,
-                period_count,
+                release: &release,
                 deposit_amount,
                 vesting_acc,
                 vesting_acc_beneficiary,
+                vesting_acc_info,
                 safe_acc_info,
                 nft_mint_acc_info,
                 depositor_acc_info,
@@ -68,16 +73,39 @@ pub fn handler<'a>(
         },
     )?;
 
+    if let (
+        Some(schedule_acc_info),
+        Release::Scheduled {
+            cliff_ts,
+            unlocks,
+            revoker,
+            streaming,
+        },
+    ) = (schedule_acc_info, release)
+    {
+        VestingSchedule::unpack_mut(
+            &mut schedule_acc_info.try_borrow_mut_data()?,
+            &mut |schedule_acc: &mut VestingSchedule| {
+                schedule_acc.initialized = true;
+                schedule_acc.vesting = *vesting_acc_info.key;
+                schedule_acc.cliff_ts = cliff_ts;
+                schedule_acc.unlocks = unlocks.clone();
+                schedule_acc.revoker = revoker;
+                schedule_acc.streaming = streaming;
+                Ok(())
+            },
+        )?;
+    }
+
     Ok(())
 }
 
-fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), LockupError> {
+fn access_control<'a, 'b>(req: AccessControlRequest<'a, 'b>) -> Result<(), LockupError> {
     info!("access-control: create_vesting");
 
     let AccessControlRequest {
         program_id,
-        end_slot,
-        period_count,
+        release,
         deposit_amount,
         vesting_acc_info,
         vault_authority_acc_info,
@@ -86,6 +114,7 @@ fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), LockupError>
         vault_acc_info,
         depositor_authority_acc_info,
         rent_acc_info,
+        schedule_acc_info,
         clock_slot,
     } = req;
 
@@ -124,13 +153,48 @@ fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), LockupError>
             }
         }
         // Vesting schedule.
-        {
-            if end_slot <= clock_slot {
-                return Err(LockupErrorCode::InvalidSlot)?;
+        match release {
+            Release::Linear {
+                end_slot,
+                period_count,
+            } => {
+                if *end_slot <= clock_slot {
+                    return Err(LockupErrorCode::InvalidSlot)?;
+                }
+                if *period_count == 0 {
+                    return Err(LockupErrorCode::InvalidPeriod)?;
+                }
             }
-            if period_count == 0 {
-                return Err(LockupErrorCode::InvalidPeriod)?;
+            Release::Scheduled {
+                cliff_ts, unlocks, ..
+            } => {
+                if !VestingSchedule::is_valid(*cliff_ts, unlocks) {
+                    return Err(LockupErrorCode::InvalidSchedule)?;
+                }
+                let schedule_acc_info =
+                    schedule_acc_info.ok_or(LockupErrorCode::WrongNumberOfAccounts)?;
+                let schedule = VestingSchedule::unpack(&schedule_acc_info.try_borrow_data()?)?;
+                if *schedule_acc_info.key
+                    != VestingSchedule::address(vesting_acc_info.key, program_id)
+                {
+                    return Err(LockupErrorCode::WrongVestingSchedule)?;
+                }
+                if schedule_acc_info.owner != program_id {
+                    return Err(LockupErrorCode::NotOwnedByProgram)?;
+                }
+                if !rent.is_exempt(
+                    schedule_acc_info.lamports(),
+                    schedule_acc_info.try_data_len()?,
+                ) {
+                    return Err(LockupErrorCode::NotRentExempt)?;
+                }
+                if schedule.initialized {
+                    return Err(LockupErrorCode::AlreadyInitialized)?;
+                }
             }
+        }
+        // Deposit.
+        {
             if deposit_amount == 0 {
                 return Err(LockupErrorCode::InvalidDepositAmount)?;
             }
@@ -149,6 +213,15 @@ fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), LockupError>
             if mint.supply != 0 {
                 return Err(LockupErrorCode::InvalidMintSupply)?;
             }
+            // Revoking freezes the receipts.
+            if let Release::Scheduled {
+                revoker: Some(_), ..
+            } = release
+            {
+                if mint.freeze_authority != COption::Some(vault_authority) {
+                    return Err(LockupErrorCode::InvalidFreezeAuthority)?;
+                }
+            }
         }
     }
 
@@ -162,11 +235,11 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
 
     let StateTransitionRequest {
         clock_slot,
-        end_slot,
-        period_count,
+        release,
         deposit_amount,
         vesting_acc,
         vesting_acc_beneficiary,
+        vesting_acc_info,
         safe_acc_info,
         depositor_acc_info,
         nft_mint_acc_info,
@@ -181,9 +254,20 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
         vesting_acc.beneficiary = vesting_acc_beneficiary;
         vesting_acc.initialized = true;
         vesting_acc.claimed = false;
-        vesting_acc.period_count = period_count;
         vesting_acc.start_balance = deposit_amount;
-        vesting_acc.end_slot = end_slot;
+        match release {
+            Release::Linear {
+                end_slot,
+                period_count,
+            } => {
+                vesting_acc.period_count = *period_count;
+                vesting_acc.end_slot = *end_slot;
+            }
+            Release::Scheduled { .. } => {
+                vesting_acc.period_count = 0;
+                vesting_acc.end_slot = 0;
+            }
+        }
         vesting_acc.start_slot = clock_slot;
         vesting_acc.balance = deposit_amount;
         vesting_acc.locked_nft_mint = *nft_mint_acc_info.key;
@@ -214,15 +298,34 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
         )?;
     }
 
+    emit_event!(Deposited {
+        vesting: *vesting_acc_info.key,
+        beneficiary: vesting_acc.beneficiary,
+        amount: deposit_amount,
+        balance: vesting_acc.balance,
+    })?;
+
     info!("state-transition: complete");
 
     Ok(())
 }
 
-struct AccessControlRequest<'a> {
+/// How the deposit is released to the beneficiary.
+pub enum Release {
+    /// The Vesting account's built-in linear release.
+    Linear { end_slot: u64, period_count: u64 },
+    /// An explicit VestingSchedule.
+    Scheduled {
+        cliff_ts: i64,
+        unlocks: Vec<Unlock>,
+        revoker: Option<Pubkey>,
+        streaming: bool,
+    },
+}
+
+struct AccessControlRequest<'a, 'b> {
     program_id: &'a Pubkey,
-    end_slot: u64,
-    period_count: u64,
+    release: &'b Release,
     deposit_amount: u64,
     vesting_acc_info: &'a AccountInfo<'a>,
     safe_acc_info: &'a AccountInfo<'a>,
@@ -231,16 +334,17 @@ struct AccessControlRequest<'a> {
     nft_mint_acc_info: &'a AccountInfo<'a>,
     vault_authority_acc_info: &'a AccountInfo<'a>,
     rent_acc_info: &'a AccountInfo<'a>,
+    schedule_acc_info: Option<&'a AccountInfo<'a>>,
     clock_slot: u64,
 }
 
 struct StateTransitionRequest<'a, 'b> {
     clock_slot: u64,
-    end_slot: u64,
-    period_count: u64,
+    release: &'b Release,
     deposit_amount: u64,
     vesting_acc: &'b mut Vesting,
     vesting_acc_beneficiary: Pubkey,
+    vesting_acc_info: &'a AccountInfo<'a>,
     safe_acc_info: &'a AccountInfo<'a>,
     depositor_acc_info: &'a AccountInfo<'a>,
     vault_acc_info: &'a AccountInfo<'a>,
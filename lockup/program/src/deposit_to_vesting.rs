@@ -0,0 +1,275 @@
+use crate::access_control;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_lockup::accounts::{Safe, TokenVault, Vesting, VestingSchedule};
+use serum_lockup::error::{LockupError, LockupErrorCode};
+use serum_lockup::events::Deposited;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use std::convert::Into;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    deposit_amount: u64,
+) -> Result<(), LockupError> {
+    info!("handler: deposit_to_vesting");
+
+    let acc_infos = &mut accounts.iter();
+
+    let grantor_acc_info = next_account_info(acc_infos)?;
+    let vesting_acc_info = next_account_info(acc_infos)?;
+    let safe_acc_info = next_account_info(acc_infos)?;
+    let depositor_acc_info = next_account_info(acc_infos)?;
+    let depositor_authority_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let vault_authority_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    // Trailing accounts depend on the state of the account being topped up.
+    let vesting = Vesting::unpack(&vesting_acc_info.try_borrow_data()?)?;
+    let schedule_acc_info = if vesting.has_schedule() {
+        Some(next_account_info(acc_infos)?)
+    } else {
+        None
+    };
+    let nft_acc_infos = if vesting.claimed {
+        Some((next_account_info(acc_infos)?, next_account_info(acc_infos)?))
+    } else {
+        None
+    };
+
+    let clock = access_control::clock(clock_acc_info)?;
+
+    let AccessControlResponse { schedule } = access_control(AccessControlRequest {
+        program_id,
+        deposit_amount,
+        grantor_acc_info,
+        vesting_acc_info,
+        safe_acc_info,
+        depositor_authority_acc_info,
+        vault_acc_info,
+        vault_authority_acc_info,
+        schedule_acc_info,
+        nft_acc_infos,
+    })?;
+
+    Vesting::unpack_mut(
+        &mut vesting_acc_info.try_borrow_mut_data()?,
+        &mut |vesting_acc: &mut Vesting| {
+            state_transition(StateTransitionRequest {
+                deposit_amount,
+                vesting_acc,
+                vesting_acc_info,
+                schedule: schedule.as_ref(),
+                clock: &clock,
+                safe_acc_info,
+                depositor_acc_info,
+                depositor_authority_acc_info,
+                vault_acc_info,
+                vault_authority_acc_info,
+                token_program_acc_info,
+                nft_acc_infos,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, LockupError> {
+    info!("access-control: deposit_to_vesting");
+
+    let AccessControlRequest {
+        program_id,
+        deposit_amount,
+        grantor_acc_info,
+        vesting_acc_info,
+        safe_acc_info,
+        depositor_authority_acc_info,
+        vault_acc_info,
+        vault_authority_acc_info,
+        schedule_acc_info,
+        nft_acc_infos,
+    } = req;
+
+    // Grantor and depositor authorization.
+    if !grantor_acc_info.is_signer || !depositor_authority_acc_info.is_signer {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+    if deposit_amount == 0 {
+        return Err(LockupErrorCode::InvalidDepositAmount)?;
+    }
+
+    // Account validation.
+    let safe = access_control::safe(safe_acc_info, program_id)?;
+    let _ = access_control::vault(
+        vault_acc_info,
+        vault_authority_acc_info,
+        safe_acc_info,
+        program_id,
+    )?;
+    let vesting = Vesting::unpack(&vesting_acc_info.try_borrow_data()?)?;
+    if vesting_acc_info.owner != program_id {
+        return Err(LockupErrorCode::InvalidAccount)?;
+    }
+    if !vesting.initialized {
+        return Err(LockupErrorCode::NotInitialized)?;
+    }
+    if vesting.safe != *safe_acc_info.key {
+        return Err(LockupErrorCode::WrongSafe)?;
+    }
+    let schedule = match schedule_acc_info {
+        None => None,
+        Some(schedule_acc_info) => Some(access_control::vesting_schedule(
+            program_id,
+            vesting_acc_info,
+            Some(schedule_acc_info),
+        )?),
+    };
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        let _ = access_control::locked_token(
+            nft_token_acc_info,
+            nft_mint_acc_info,
+            vault_authority_acc_info.key,
+            &vesting,
+        )?;
+    }
+
+    // The grantor is the revoker of a revocable grant, and the safe
+    // authority otherwise.
+    let grantor = match schedule.as_ref().and_then(|s| s.revoker) {
+        Some(revoker) => revoker,
+        None => safe.authority,
+    };
+    if *grantor_acc_info.key != grantor {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+
+    info!("access-control: success");
+
+    Ok(AccessControlResponse { schedule })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
+    info!("state-transition: deposit_to_vesting");
+
+    let StateTransitionRequest {
+        deposit_amount,
+        vesting_acc,
+        vesting_acc_info,
+        schedule,
+        clock,
+        safe_acc_info,
+        depositor_acc_info,
+        depositor_authority_acc_info,
+        vault_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        nft_acc_infos,
+    } = req;
+
+    // Spread the deposit over the remaining release.
+    let topped_up = match schedule {
+        None => vesting_acc.top_up(deposit_amount, clock.slot),
+        Some(schedule) => {
+            vesting_acc.top_up_with_schedule(deposit_amount, schedule, clock.unix_timestamp)
+        }
+    };
+    topped_up.ok_or(LockupErrorCode::FullyVested)?;
+
+    // Transfer the deposit into the vault.
+    {
+        info!("invoke SPL token transfer");
+        let deposit_instruction = spl_token::instruction::transfer(
+            &spl_token::ID,
+            depositor_acc_info.key,
+            vault_acc_info.key,
+            depositor_authority_acc_info.key,
+            &[],
+            deposit_amount,
+        )?;
+        solana_sdk::program::invoke_signed(
+            &deposit_instruction,
+            &[
+                depositor_acc_info.clone(),
+                depositor_authority_acc_info.clone(),
+                vault_acc_info.clone(),
+                token_program_acc_info.clone(),
+            ],
+            &[],
+        )?;
+    }
+
+    // Keep the receipt supply equal to the balance.
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        info!("minting token receipts");
+        let mint_to_instr = spl_token::instruction::mint_to(
+            &spl_token::ID,
+            nft_mint_acc_info.key,
+            nft_token_acc_info.key,
+            vault_authority_acc_info.key,
+            &[],
+            deposit_amount,
+        )?;
+        let safe = Safe::unpack(&safe_acc_info.try_borrow_data()?)?;
+        let signer_seeds = TokenVault::signer_seeds(safe_acc_info.key, &safe.nonce);
+        solana_sdk::program::invoke_signed(
+            &mint_to_instr,
+            &[
+                nft_mint_acc_info.clone(),
+                nft_token_acc_info.clone(),
+                vault_authority_acc_info.clone(),
+                token_program_acc_info.clone(),
+            ],
+            &[&signer_seeds],
+        )?;
+    }
+
+    emit_event!(Deposited {
+        vesting: *vesting_acc_info.key,
+        beneficiary: vesting_acc.beneficiary,
+        amount: deposit_amount,
+        balance: vesting_acc.balance,
+    })?;
+
+    info!("state-transition: success");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    deposit_amount: u64,
+    grantor_acc_info: &'a AccountInfo<'a>,
+    vesting_acc_info: &'a AccountInfo<'a>,
+    safe_acc_info: &'a AccountInfo<'a>,
+    depositor_authority_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    schedule_acc_info: Option<&'a AccountInfo<'a>>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+}
+
+struct AccessControlResponse {
+    schedule: Option<VestingSchedule>,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    deposit_amount: u64,
+    vesting_acc: &'b mut Vesting,
+    vesting_acc_info: &'a AccountInfo<'a>,
+    schedule: Option<&'b VestingSchedule>,
+    clock: &'b Clock,
+    safe_acc_info: &'a AccountInfo<'a>,
+    depositor_acc_info: &'a AccountInfo<'a>,
+    depositor_authority_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+}
@@ -0,0 +1,199 @@
+use crate::access_control;
+use serum_common::pack::Pack;
+use serum_lockup::accounts::{BeneficiaryTransfer, Vesting};
+use serum_lockup::error::{LockupError, LockupErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use std::convert::Into;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    new_beneficiary: Pubkey,
+) -> Result<(), LockupError> {
+    info!("handler: initiate_beneficiary_transfer");
+
+    let acc_infos = &mut accounts.iter();
+
+    let transfer_acc_info = next_account_info(acc_infos)?;
+    let vesting_acc_info = next_account_info(acc_infos)?;
+    let vesting_acc_beneficiary_info = next_account_info(acc_infos)?;
+    let safe_acc_info = next_account_info(acc_infos)?;
+    let safe_vault_authority_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+
+    // The receipts are only passed in once they exist.
+    let nft_acc_infos = if Vesting::unpack(&vesting_acc_info.try_borrow_data()?)?.claimed {
+        Some((next_account_info(acc_infos)?, next_account_info(acc_infos)?))
+    } else {
+        None
+    };
+
+    access_control(AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        vesting_acc_info,
+        vesting_acc_beneficiary_info,
+        safe_acc_info,
+        safe_vault_authority_acc_info,
+        rent_acc_info,
+        nft_acc_infos,
+    })?;
+
+    BeneficiaryTransfer::unpack_mut(
+        &mut transfer_acc_info.try_borrow_mut_data()?,
+        &mut |transfer: &mut BeneficiaryTransfer| {
+            Vesting::unpack_mut(
+                &mut vesting_acc_info.try_borrow_mut_data()?,
+                &mut |vesting_acc: &mut Vesting| {
+                    state_transition(StateTransitionRequest {
+                        transfer,
+                        vesting_acc,
+                        vesting_acc_info,
+                        vesting_acc_beneficiary_info,
+                        token_program_acc_info,
+                        nft_acc_infos,
+                        new_beneficiary,
+                    })
+                    .map_err(Into::into)
+                },
+            )
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), LockupError> {
+    info!("access-control: initiate_beneficiary_transfer");
+
+    let AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        vesting_acc_info,
+        vesting_acc_beneficiary_info,
+        safe_acc_info,
+        safe_vault_authority_acc_info,
+        rent_acc_info,
+        nft_acc_infos,
+    } = req;
+
+    // Beneficiary authorization.
+    if !vesting_acc_beneficiary_info.is_signer {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+
+    // Account validation.
+    let rent = access_control::rent(rent_acc_info)?;
+    let safe = access_control::safe(safe_acc_info, program_id)?;
+    let vault_authority = access_control::vault_authority(
+        safe_vault_authority_acc_info,
+        safe_acc_info.key,
+        &safe,
+        program_id,
+    )?;
+    let vesting = access_control::vesting(
+        program_id,
+        safe_acc_info.key,
+        vesting_acc_info,
+        vesting_acc_beneficiary_info,
+    )?;
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        let _ = access_control::locked_token(
+            nft_token_acc_info,
+            nft_mint_acc_info,
+            &vault_authority,
+            &vesting,
+        )?;
+    }
+
+    // Transfer account.
+    if transfer_acc_info.owner != program_id {
+        return Err(LockupErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    let transfer = BeneficiaryTransfer::unpack(&transfer_acc_info.try_borrow_data()?)?;
+    if transfer.initialized {
+        return Err(LockupErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    if !rent.is_exempt(
+        transfer_acc_info.lamports(),
+        transfer_acc_info.try_data_len()?,
+    ) {
+        return Err(LockupErrorCode::NotRentExempt)?;
+    }
+
+    info!("access-control: success");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
+    info!("state-transition: initiate_beneficiary_transfer");
+
+    let StateTransitionRequest {
+        transfer,
+        vesting_acc,
+        vesting_acc_info,
+        vesting_acc_beneficiary_info,
+        token_program_acc_info,
+        nft_acc_infos,
+        new_beneficiary,
+    } = req;
+
+    transfer.initialized = true;
+    transfer.vesting = *vesting_acc_info.key;
+    transfer.beneficiary = *vesting_acc_beneficiary_info.key;
+    transfer.new_beneficiary = new_beneficiary;
+
+    // Burn the receipts, so that the new beneficiary can claim them once
+    // the transfer is accepted.
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        info!("burning token receipts");
+        let burn_instruction = spl_token::instruction::burn(
+            &spl_token::ID,
+            nft_token_acc_info.key,
+            nft_mint_acc_info.key,
+            vesting_acc_beneficiary_info.key,
+            &[],
+            vesting_acc.balance,
+        )?;
+        solana_sdk::program::invoke_signed(
+            &burn_instruction,
+            &[
+                nft_token_acc_info.clone(),
+                nft_mint_acc_info.clone(),
+                vesting_acc_beneficiary_info.clone(),
+                token_program_acc_info.clone(),
+            ],
+            &[],
+        )?;
+        vesting_acc.claimed = false;
+    }
+
+    info!("state-transition: success");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    vesting_acc_info: &'a AccountInfo<'a>,
+    vesting_acc_beneficiary_info: &'a AccountInfo<'a>,
+    safe_acc_info: &'a AccountInfo<'a>,
+    safe_vault_authority_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    transfer: &'b mut BeneficiaryTransfer,
+    vesting_acc: &'b mut Vesting,
+    vesting_acc_info: &'a AccountInfo<'a>,
+    vesting_acc_beneficiary_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+    new_beneficiary: Pubkey,
+}
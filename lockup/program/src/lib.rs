@@ -3,6 +3,7 @@
 #![cfg_attr(feature = "strict", deny(warnings))]
 
 use serum_common::pack::Pack;
+use serum_lockup::accounts::VestingSchedule;
 use serum_lockup::error::{LockupError, LockupErrorCode};
 use serum_lockup::instruction::LockupInstruction;
 use solana_sdk::account_info::AccountInfo;
@@ -10,16 +11,27 @@ use solana_sdk::entrypoint::ProgramResult;
 use solana_sdk::info;
 use solana_sdk::pubkey::Pubkey;
 
+mod accept_beneficiary_transfer;
 pub(crate) mod access_control;
+mod cancel_beneficiary_transfer;
 mod claim;
 mod create_vesting;
+mod deposit_to_vesting;
 mod initialize;
+mod initiate_beneficiary_transfer;
+mod lamports;
 mod migrate;
 mod redeem;
+mod relock_vesting;
+mod revoke;
 mod set_authority;
+mod split_vesting;
 mod whitelist_add;
+mod whitelist_add_many;
 mod whitelist_delete;
 mod whitelist_deposit;
+mod whitelist_remove_many;
+mod whitelist_set_max_outstanding;
 mod whitelist_withdraw;
 
 solana_sdk::entrypoint!(entry);
@@ -46,8 +58,10 @@ fn entry<'a>(
             program_id,
             accounts,
             beneficiary,
-            end_slot,
-            period_count,
+            create_vesting::Release::Linear {
+                end_slot,
+                period_count,
+            },
             deposit_amount,
         ),
         LockupInstruction::Claim => claim::handler(program_id, accounts),
@@ -69,6 +83,92 @@ fn entry<'a>(
             set_authority::handler(program_id, accounts, new_authority)
         }
         LockupInstruction::Migrate => migrate::handler(program_id, accounts),
+        LockupInstruction::CreateScheduledVesting {
+            beneficiary,
+            cliff_ts,
+            unlocks,
+            revoker,
+            deposit_amount,
+        } => create_vesting::handler(
+            program_id,
+            accounts,
+            beneficiary,
+            create_vesting::Release::Scheduled {
+                cliff_ts,
+                unlocks,
+                revoker,
+                streaming: false,
+            },
+            deposit_amount,
+        ),
+        LockupInstruction::Revoke => revoke::handler(program_id, accounts),
+        LockupInstruction::SplitVesting {
+            amount,
+            new_beneficiary,
+        } => split_vesting::handler(program_id, accounts, amount, new_beneficiary),
+        LockupInstruction::InitiateBeneficiaryTransfer { new_beneficiary } => {
+            initiate_beneficiary_transfer::handler(program_id, accounts, new_beneficiary)
+        }
+        LockupInstruction::AcceptBeneficiaryTransfer => {
+            accept_beneficiary_transfer::handler(program_id, accounts)
+        }
+        LockupInstruction::CancelBeneficiaryTransfer => {
+            cancel_beneficiary_transfer::handler(program_id, accounts)
+        }
+        LockupInstruction::WhitelistAddMany { entries } => {
+            whitelist_add_many::handler(program_id, accounts, entries)
+        }
+        LockupInstruction::WhitelistRemoveMany { entries } => {
+            whitelist_remove_many::handler(program_id, accounts, entries)
+        }
+        LockupInstruction::CreateStreamingVesting {
+            beneficiary,
+            start_ts,
+            end_ts,
+            revoker,
+            deposit_amount,
+        } => create_vesting::handler(
+            program_id,
+            accounts,
+            beneficiary,
+            create_vesting::Release::Scheduled {
+                cliff_ts: start_ts,
+                unlocks: VestingSchedule::stream(start_ts, end_ts),
+                revoker,
+                streaming: true,
+            },
+            deposit_amount,
+        ),
+        LockupInstruction::WhitelistSetMaxOutstanding {
+            entry,
+            max_outstanding,
+        } => whitelist_set_max_outstanding::handler(program_id, accounts, entry, max_outstanding),
+        LockupInstruction::RelockVesting {
+            end_slot,
+            period_count,
+            deposit_amount,
+        } => relock_vesting::handler(
+            program_id,
+            accounts,
+            relock_vesting::Relock::Linear {
+                end_slot,
+                period_count,
+            },
+            deposit_amount,
+        ),
+        LockupInstruction::RelockScheduledVesting {
+            cliff_ts,
+            unlocks,
+            deposit_amount,
+        } => relock_vesting::handler(
+            program_id,
+            accounts,
+            relock_vesting::Relock::Scheduled { cliff_ts, unlocks },
+            deposit_amount,
+        ),
+        LockupInstruction::DepositToVesting { deposit_amount } => {
+            deposit_to_vesting::handler(program_id, accounts, deposit_amount)
+        }
     };
 
     result?;
@@ -1,10 +1,14 @@
 use crate::access_control;
+use serum_common::emit_event;
 use serum_common::pack::Pack;
 use serum_lockup::accounts::{Safe, TokenVault, Vesting};
 use serum_lockup::error::{LockupError, LockupErrorCode};
+use serum_lockup::events::Redeemed;
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
 use solana_sdk::pubkey::Pubkey;
+use spl_token::state::{Account as TokenAccount, AccountState};
 use std::convert::Into;
 
 pub fn handler<'a>(
@@ -26,6 +30,7 @@ pub fn handler<'a>(
     let nft_mint_acc_info = next_account_info(acc_infos)?;
     let token_program_acc_info = next_account_info(acc_infos)?;
     let clock_acc_info = next_account_info(acc_infos)?;
+    let schedule_acc_info = acc_infos.next();
 
     access_control(AccessControlRequest {
         program_id,
@@ -38,6 +43,7 @@ pub fn handler<'a>(
         nft_token_acc_info,
         nft_mint_acc_info,
         clock_acc_info,
+        schedule_acc_info,
     })?;
 
     Vesting::unpack_mut(
@@ -46,6 +52,7 @@ pub fn handler<'a>(
             state_transition(StateTransitionRequest {
                 amount,
                 vesting_acc,
+                vesting_acc_info,
                 accounts,
                 safe_vault_acc_info,
                 safe_vault_authority_acc_info,
@@ -75,6 +82,7 @@ fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), LockupError>
         nft_token_acc_info,
         nft_mint_acc_info,
         clock_acc_info,
+        schedule_acc_info,
     } = req;
 
     // Beneficiary authorization.
@@ -110,7 +118,14 @@ fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), LockupError>
         if !vesting.claimed {
             return Err(LockupErrorCode::NotYetClaimed)?;
         }
-        if amount > vesting.available_for_withdrawal(clock.slot) {
+        let available = if vesting.has_schedule() {
+            let schedule =
+                access_control::vesting_schedule(program_id, vesting_acc_info, schedule_acc_info)?;
+            vesting.available_for_withdrawal_with_schedule(&schedule, clock.unix_timestamp)
+        } else {
+            vesting.available_for_withdrawal(clock.slot)
+        };
+        if amount > available {
             return Err(LockupErrorCode::InsufficientWithdrawalBalance)?;
         }
     }
@@ -125,6 +140,7 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
 
     let StateTransitionRequest {
         vesting_acc,
+        vesting_acc_info,
         amount,
         accounts,
         safe_vault_acc_info,
@@ -141,6 +157,30 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
         vesting_acc.deduct(amount);
     }
 
+    let safe = Safe::unpack(&safe_acc_info.try_borrow_data()?)?;
+    let signer_seeds = TokenVault::signer_seeds(safe_acc_info.key, &safe.nonce);
+
+    // Receipts of a revoked vesting are frozen, and only thawed to burn.
+    let frozen =
+        TokenAccount::unpack(&nft_token_acc_info.try_borrow_data()?)?.state == AccountState::Frozen;
+    let freeze_accounts = [
+        nft_token_acc_info.clone(),
+        nft_mint_acc_info.clone(),
+        safe_vault_authority_acc_info.clone(),
+        token_program_acc_info.clone(),
+    ];
+    if frozen {
+        info!("thawing token receipts");
+        let thaw_instruction = spl_token::instruction::thaw_account(
+            &spl_token::ID,
+            nft_token_acc_info.key,
+            nft_mint_acc_info.key,
+            safe_vault_authority_acc_info.key,
+            &[],
+        )?;
+        solana_sdk::program::invoke_signed(&thaw_instruction, &freeze_accounts, &[&signer_seeds])?;
+    }
+
     // Burn the NFT.
     {
         info!("burning token receipts");
@@ -155,6 +195,22 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
         solana_sdk::program::invoke_signed(&burn_instruction, &accounts[..], &[])?;
     }
 
+    if frozen {
+        info!("freezing token receipts");
+        let freeze_instruction = spl_token::instruction::freeze_account(
+            &spl_token::ID,
+            nft_token_acc_info.key,
+            nft_mint_acc_info.key,
+            safe_vault_authority_acc_info.key,
+            &[],
+        )?;
+        solana_sdk::program::invoke_signed(
+            &freeze_instruction,
+            &freeze_accounts,
+            &[&signer_seeds],
+        )?;
+    }
+
     // Transfer token from the vault to the user address.
     {
         info!("invoking token transfer");
@@ -167,9 +223,6 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
             amount,
         )?;
 
-        let safe = Safe::unpack(&safe_acc_info.try_borrow_data()?)?;
-        let signer_seeds = TokenVault::signer_seeds(safe_acc_info.key, &safe.nonce);
-
         solana_sdk::program::invoke_signed(
             &withdraw_instruction,
             &[
@@ -182,6 +235,13 @@ fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), L
         )?;
     }
 
+    emit_event!(Redeemed {
+        vesting: *vesting_acc_info.key,
+        beneficiary: vesting_acc.beneficiary,
+        amount,
+        balance: vesting_acc.balance,
+    })?;
+
     info!("state-transition: success");
 
     Ok(())
@@ -198,11 +258,13 @@ struct AccessControlRequest<'a> {
     nft_token_acc_info: &'a AccountInfo<'a>,
     nft_mint_acc_info: &'a AccountInfo<'a>,
     clock_acc_info: &'a AccountInfo<'a>,
+    schedule_acc_info: Option<&'a AccountInfo<'a>>,
 }
 
 struct StateTransitionRequest<'a, 'b> {
     amount: u64,
     vesting_acc: &'b mut Vesting,
+    vesting_acc_info: &'a AccountInfo<'a>,
     accounts: &'a [AccountInfo<'a>],
     safe_acc_info: &'a AccountInfo<'a>,
     beneficiary_token_acc_info: &'a AccountInfo<'a>,
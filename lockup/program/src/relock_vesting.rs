@@ -0,0 +1,329 @@
+use crate::access_control;
+use serum_common::pack::Pack;
+use serum_lockup::accounts::{Safe, TokenVault, Unlock, Vesting, VestingSchedule};
+use serum_lockup::error::{LockupError, LockupErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use std::convert::Into;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    relock: Relock,
+    deposit_amount: u64,
+) -> Result<(), LockupError> {
+    info!("handler: relock_vesting");
+
+    let acc_infos = &mut accounts.iter();
+
+    let vesting_acc_beneficiary_info = next_account_info(acc_infos)?;
+    let grantor_acc_info = next_account_info(acc_infos)?;
+    let vesting_acc_info = next_account_info(acc_infos)?;
+    let safe_acc_info = next_account_info(acc_infos)?;
+    let depositor_acc_info = next_account_info(acc_infos)?;
+    let depositor_authority_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let vault_authority_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    // Trailing accounts depend on the state of the account being relocked.
+    let vesting = Vesting::unpack(&vesting_acc_info.try_borrow_data()?)?;
+    let schedule_acc_info = if vesting.has_schedule() {
+        Some(next_account_info(acc_infos)?)
+    } else {
+        None
+    };
+    let nft_acc_infos = if vesting.claimed {
+        Some((next_account_info(acc_infos)?, next_account_info(acc_infos)?))
+    } else {
+        None
+    };
+
+    let clock_slot = access_control::clock(clock_acc_info)?.slot;
+
+    access_control(AccessControlRequest {
+        program_id,
+        relock: &relock,
+        deposit_amount,
+        vesting_acc_beneficiary_info,
+        grantor_acc_info,
+        vesting_acc_info,
+        safe_acc_info,
+        depositor_authority_acc_info,
+        vault_acc_info,
+        vault_authority_acc_info,
+        schedule_acc_info,
+        nft_acc_infos,
+        clock_slot,
+    })?;
+
+    Vesting::unpack_mut(
+        &mut vesting_acc_info.try_borrow_mut_data()?,
+        &mut |vesting_acc: &mut Vesting| {
+            state_transition(StateTransitionRequest {
+                relock: &relock,
+                deposit_amount,
+                vesting_acc,
+                safe_acc_info,
+                depositor_acc_info,
+                depositor_authority_acc_info,
+                vault_acc_info,
+                vault_authority_acc_info,
+                token_program_acc_info,
+                schedule_acc_info,
+                nft_acc_infos,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control<'a, 'b>(req: AccessControlRequest<'a, 'b>) -> Result<(), LockupError> {
+    info!("access-control: relock_vesting");
+
+    let AccessControlRequest {
+        program_id,
+        relock,
+        deposit_amount,
+        vesting_acc_beneficiary_info,
+        grantor_acc_info,
+        vesting_acc_info,
+        safe_acc_info,
+        depositor_authority_acc_info,
+        vault_acc_info,
+        vault_authority_acc_info,
+        schedule_acc_info,
+        nft_acc_infos,
+        clock_slot,
+    } = req;
+
+    // Beneficiary and grantor authorization.
+    if !vesting_acc_beneficiary_info.is_signer || !grantor_acc_info.is_signer {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+    if deposit_amount > 0 && !depositor_authority_acc_info.is_signer {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+
+    // Account validation.
+    let safe = access_control::safe(safe_acc_info, program_id)?;
+    let _ = access_control::vault(
+        vault_acc_info,
+        vault_authority_acc_info,
+        safe_acc_info,
+        program_id,
+    )?;
+    let vesting = access_control::vesting(
+        program_id,
+        safe_acc_info.key,
+        vesting_acc_info,
+        vesting_acc_beneficiary_info,
+    )?;
+    let schedule = match schedule_acc_info {
+        None => None,
+        Some(schedule_acc_info) => Some(access_control::vesting_schedule(
+            program_id,
+            vesting_acc_info,
+            Some(schedule_acc_info),
+        )?),
+    };
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        let _ = access_control::locked_token(
+            nft_token_acc_info,
+            nft_mint_acc_info,
+            vault_authority_acc_info.key,
+            &vesting,
+        )?;
+    }
+
+    // The grantor is the revoker of a revocable grant, and the safe
+    // authority otherwise.
+    let grantor = match schedule.as_ref().and_then(|s| s.revoker) {
+        Some(revoker) => revoker,
+        None => safe.authority,
+    };
+    if *grantor_acc_info.key != grantor {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+
+    // Relock checks. The new release may only end later than the old one.
+    match (relock, schedule) {
+        (
+            Relock::Linear {
+                end_slot,
+                period_count,
+            },
+            None,
+        ) => {
+            if *end_slot < vesting.end_slot || *end_slot <= clock_slot {
+                return Err(LockupErrorCode::InvalidSlot)?;
+            }
+            // Each period must span at least one slot, or the unlock
+            // calculation divides by zero.
+            if *period_count == 0 || *period_count > *end_slot - vesting.start_slot {
+                return Err(LockupErrorCode::InvalidPeriod)?;
+            }
+        }
+        (Relock::Scheduled { cliff_ts, unlocks }, Some(schedule)) => {
+            if !VestingSchedule::is_valid(*cliff_ts, unlocks) {
+                return Err(LockupErrorCode::InvalidSchedule)?;
+            }
+            let old_end = schedule.unlocks.last().map(|u| u.ts).unwrap_or(0);
+            let new_end = unlocks[unlocks.len() - 1].ts;
+            if new_end < old_end {
+                return Err(LockupErrorCode::InvalidSchedule)?;
+            }
+        }
+        _ => return Err(LockupErrorCode::WrongVestingSchedule)?,
+    }
+
+    info!("access-control: success");
+
+    Ok(())
+}
+
+fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), LockupError> {
+    info!("state-transition: relock_vesting");
+
+    let StateTransitionRequest {
+        relock,
+        deposit_amount,
+        vesting_acc,
+        safe_acc_info,
+        depositor_acc_info,
+        depositor_authority_acc_info,
+        vault_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        schedule_acc_info,
+        nft_acc_infos,
+    } = req;
+
+    // Apply the new release to the whole account.
+    vesting_acc.start_balance = vesting_acc
+        .start_balance
+        .checked_add(deposit_amount)
+        .ok_or(LockupErrorCode::Overflow)?;
+    vesting_acc.balance = vesting_acc
+        .balance
+        .checked_add(deposit_amount)
+        .ok_or(LockupErrorCode::Overflow)?;
+    match relock {
+        Relock::Linear {
+            end_slot,
+            period_count,
+        } => {
+            vesting_acc.end_slot = *end_slot;
+            vesting_acc.period_count = *period_count;
+        }
+        Relock::Scheduled { cliff_ts, unlocks } => {
+            let schedule_acc_info =
+                schedule_acc_info.ok_or(LockupErrorCode::WrongNumberOfAccounts)?;
+            VestingSchedule::unpack_mut(
+                &mut schedule_acc_info.try_borrow_mut_data()?,
+                &mut |schedule_acc: &mut VestingSchedule| {
+                    schedule_acc.cliff_ts = *cliff_ts;
+                    schedule_acc.unlocks = unlocks.clone();
+                    Ok(())
+                },
+            )?;
+        }
+    }
+
+    if deposit_amount > 0 {
+        // Transfer the new deposit into the vault.
+        {
+            info!("invoke SPL token transfer");
+            let deposit_instruction = spl_token::instruction::transfer(
+                &spl_token::ID,
+                depositor_acc_info.key,
+                vault_acc_info.key,
+                depositor_authority_acc_info.key,
+                &[],
+                deposit_amount,
+            )?;
+            solana_sdk::program::invoke_signed(
+                &deposit_instruction,
+                &[
+                    depositor_acc_info.clone(),
+                    depositor_authority_acc_info.clone(),
+                    vault_acc_info.clone(),
+                    token_program_acc_info.clone(),
+                ],
+                &[],
+            )?;
+        }
+
+        // Keep the receipt supply equal to the balance.
+        if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+            info!("minting token receipts");
+            let mint_to_instr = spl_token::instruction::mint_to(
+                &spl_token::ID,
+                nft_mint_acc_info.key,
+                nft_token_acc_info.key,
+                vault_authority_acc_info.key,
+                &[],
+                deposit_amount,
+            )?;
+            let safe = Safe::unpack(&safe_acc_info.try_borrow_data()?)?;
+            let signer_seeds = TokenVault::signer_seeds(safe_acc_info.key, &safe.nonce);
+            solana_sdk::program::invoke_signed(
+                &mint_to_instr,
+                &[
+                    nft_mint_acc_info.clone(),
+                    nft_token_acc_info.clone(),
+                    vault_authority_acc_info.clone(),
+                    token_program_acc_info.clone(),
+                ],
+                &[&signer_seeds],
+            )?;
+        }
+    }
+
+    info!("state-transition: success");
+
+    Ok(())
+}
+
+/// The new release of a relocked Vesting account, which must be of the same
+/// kind as the one it replaces.
+pub enum Relock {
+    /// The Vesting account's built-in linear release.
+    Linear { end_slot: u64, period_count: u64 },
+    /// The account's VestingSchedule, keeping its revoker and streaming mode.
+    Scheduled { cliff_ts: i64, unlocks: Vec<Unlock> },
+}
+
+struct AccessControlRequest<'a, 'b> {
+    program_id: &'a Pubkey,
+    relock: &'b Relock,
+    deposit_amount: u64,
+    vesting_acc_beneficiary_info: &'a AccountInfo<'a>,
+    grantor_acc_info: &'a AccountInfo<'a>,
+    vesting_acc_info: &'a AccountInfo<'a>,
+    safe_acc_info: &'a AccountInfo<'a>,
+    depositor_authority_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    schedule_acc_info: Option<&'a AccountInfo<'a>>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+    clock_slot: u64,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    relock: &'b Relock,
+    deposit_amount: u64,
+    vesting_acc: &'b mut Vesting,
+    safe_acc_info: &'a AccountInfo<'a>,
+    depositor_acc_info: &'a AccountInfo<'a>,
+    depositor_authority_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    schedule_acc_info: Option<&'a AccountInfo<'a>>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+}
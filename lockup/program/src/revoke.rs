@@ -0,0 +1,265 @@
+use crate::access_control;
+use serum_common::pack::Pack;
+use serum_lockup::accounts::{Safe, TokenVault, Vesting, VestingSchedule};
+use serum_lockup::error::{LockupError, LockupErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::program_option::COption;
+use solana_sdk::pubkey::Pubkey;
+use std::convert::Into;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), LockupError> {
+    info!("handler: revoke");
+
+    let acc_infos = &mut accounts.iter();
+
+    let revoker_acc_info = next_account_info(acc_infos)?;
+    let vesting_acc_info = next_account_info(acc_infos)?;
+    let schedule_acc_info = next_account_info(acc_infos)?;
+    let safe_acc_info = next_account_info(acc_infos)?;
+    let safe_vault_acc_info = next_account_info(acc_infos)?;
+    let safe_vault_authority_acc_info = next_account_info(acc_infos)?;
+    let receiver_token_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    // Trailing accounts depend on the state of the account being revoked.
+    let vesting = Vesting::unpack(&vesting_acc_info.try_borrow_data()?)?;
+    let nft_acc_infos = if vesting.claimed {
+        Some((next_account_info(acc_infos)?, next_account_info(acc_infos)?))
+    } else {
+        None
+    };
+
+    let clock_ts = access_control::clock(clock_acc_info)?.unix_timestamp;
+
+    access_control(AccessControlRequest {
+        program_id,
+        revoker_acc_info,
+        vesting_acc_info,
+        schedule_acc_info,
+        safe_acc_info,
+        safe_vault_acc_info,
+        safe_vault_authority_acc_info,
+        nft_acc_infos,
+        clock_ts,
+    })?;
+
+    Vesting::unpack_mut(
+        &mut vesting_acc_info.try_borrow_mut_data()?,
+        &mut |vesting_acc: &mut Vesting| {
+            VestingSchedule::unpack_mut(
+                &mut schedule_acc_info.try_borrow_mut_data()?,
+                &mut |schedule_acc: &mut VestingSchedule| {
+                    state_transition(StateTransitionRequest {
+                        vesting_acc,
+                        schedule_acc,
+                        clock_ts,
+                        safe_acc_info,
+                        safe_vault_acc_info,
+                        safe_vault_authority_acc_info,
+                        receiver_token_acc_info,
+                        token_program_acc_info,
+                        nft_acc_infos,
+                    })
+                    .map_err(Into::into)
+                },
+            )
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), LockupError> {
+    info!("access-control: revoke");
+
+    let AccessControlRequest {
+        program_id,
+        revoker_acc_info,
+        vesting_acc_info,
+        schedule_acc_info,
+        safe_acc_info,
+        safe_vault_acc_info,
+        safe_vault_authority_acc_info,
+        nft_acc_infos,
+        clock_ts,
+    } = req;
+
+    // Revoker authorization.
+    if !revoker_acc_info.is_signer {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+
+    // Account validation.
+    let safe = access_control::safe(safe_acc_info, program_id)?;
+    let _ = access_control::vault(
+        safe_vault_acc_info,
+        safe_vault_authority_acc_info,
+        safe_acc_info,
+        program_id,
+    )?;
+    let vesting = Vesting::unpack(&vesting_acc_info.try_borrow_data()?)?;
+    if vesting_acc_info.owner != program_id {
+        return Err(LockupErrorCode::InvalidAccount)?;
+    }
+    if !vesting.initialized {
+        return Err(LockupErrorCode::NotInitialized)?;
+    }
+    if vesting.safe != *safe_acc_info.key {
+        return Err(LockupErrorCode::WrongSafe)?;
+    }
+    if !vesting.has_schedule() {
+        return Err(LockupErrorCode::NotRevocable)?;
+    }
+    let schedule =
+        access_control::vesting_schedule(program_id, vesting_acc_info, Some(schedule_acc_info))?;
+    // The receipts minted on claim, which are frozen so the unvested ones
+    // can't be passed off as backed.
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        let vault_authority = access_control::vault_authority(
+            safe_vault_authority_acc_info,
+            safe_acc_info.key,
+            &safe,
+            program_id,
+        )?;
+        let _ = access_control::locked_token(
+            nft_token_acc_info,
+            nft_mint_acc_info,
+            &vault_authority,
+            &vesting,
+        )?;
+        if *nft_token_acc_info.key != vesting.locked_nft_token {
+            return Err(LockupErrorCode::InvalidAccount)?;
+        }
+        let mint = access_control::mint(nft_mint_acc_info)?;
+        if mint.freeze_authority != COption::Some(vault_authority) {
+            return Err(LockupErrorCode::InvalidFreezeAuthority)?;
+        }
+    }
+
+    // Revoke checks.
+    {
+        match schedule.revoker {
+            None => return Err(LockupErrorCode::NotRevocable)?,
+            Some(revoker) if revoker != *revoker_acc_info.key => {
+                return Err(LockupErrorCode::Unauthorized)?
+            }
+            Some(_) => {}
+        }
+        // The unvested tokens must all be in the vault.
+        let unvested = vesting
+            .start_balance
+            .checked_sub(schedule.total_vested(vesting.start_balance, clock_ts))
+            .ok_or(LockupErrorCode::Underflow)?;
+        if unvested > vesting.available_for_whitelist() {
+            return Err(LockupErrorCode::InsufficientWhitelistBalance)?;
+        }
+    }
+
+    info!("access-control: success");
+
+    Ok(())
+}
+
+fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), LockupError> {
+    info!("state-transition: revoke");
+
+    let StateTransitionRequest {
+        vesting_acc,
+        schedule_acc,
+        clock_ts,
+        safe_acc_info,
+        safe_vault_acc_info,
+        safe_vault_authority_acc_info,
+        receiver_token_acc_info,
+        token_program_acc_info,
+        nft_acc_infos,
+    } = req;
+
+    // Stop vesting.
+    let unvested = vesting_acc
+        .revoke(schedule_acc, clock_ts)
+        .ok_or(LockupErrorCode::Underflow)?;
+
+    let safe = Safe::unpack(&safe_acc_info.try_borrow_data()?)?;
+    let signer_seeds = TokenVault::signer_seeds(safe_acc_info.key, &safe.nonce);
+
+    // Transfer the unvested tokens out of the vault.
+    {
+        info!("invoking token transfer");
+        let withdraw_instruction = spl_token::instruction::transfer(
+            &spl_token::ID,
+            safe_vault_acc_info.key,
+            receiver_token_acc_info.key,
+            &safe_vault_authority_acc_info.key,
+            &[],
+            unvested,
+        )?;
+
+        solana_sdk::program::invoke_signed(
+            &withdraw_instruction,
+            &[
+                safe_vault_acc_info.clone(),
+                receiver_token_acc_info.clone(),
+                safe_vault_authority_acc_info.clone(),
+                token_program_acc_info.clone(),
+            ],
+            &[&signer_seeds],
+        )?;
+    }
+
+    // Freeze the receipts. The beneficiary holds more than the balance now
+    // backs, and only Redeem thaws them, to burn what it pays out.
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        info!("freezing token receipts");
+        let freeze_instruction = spl_token::instruction::freeze_account(
+            &spl_token::ID,
+            nft_token_acc_info.key,
+            nft_mint_acc_info.key,
+            safe_vault_authority_acc_info.key,
+            &[],
+        )?;
+        solana_sdk::program::invoke_signed(
+            &freeze_instruction,
+            &[
+                nft_token_acc_info.clone(),
+                nft_mint_acc_info.clone(),
+                safe_vault_authority_acc_info.clone(),
+                token_program_acc_info.clone(),
+            ],
+            &[&signer_seeds],
+        )?;
+    }
+
+    info!("state-transition: success");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    revoker_acc_info: &'a AccountInfo<'a>,
+    vesting_acc_info: &'a AccountInfo<'a>,
+    schedule_acc_info: &'a AccountInfo<'a>,
+    safe_acc_info: &'a AccountInfo<'a>,
+    safe_vault_acc_info: &'a AccountInfo<'a>,
+    safe_vault_authority_acc_info: &'a AccountInfo<'a>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+    clock_ts: i64,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    vesting_acc: &'b mut Vesting,
+    schedule_acc: &'b mut VestingSchedule,
+    clock_ts: i64,
+    safe_acc_info: &'a AccountInfo<'a>,
+    safe_vault_acc_info: &'a AccountInfo<'a>,
+    safe_vault_authority_acc_info: &'a AccountInfo<'a>,
+    receiver_token_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+}
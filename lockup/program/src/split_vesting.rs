@@ -0,0 +1,312 @@
+use crate::access_control;
+use serum_common::pack::Pack;
+use serum_lockup::accounts::{Vesting, VestingSchedule};
+use serum_lockup::error::{LockupError, LockupErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::program_option::COption;
+use solana_sdk::pubkey::Pubkey;
+use std::convert::Into;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    amount: u64,
+    new_beneficiary: Pubkey,
+) -> Result<(), LockupError> {
+    info!("handler: split_vesting");
+
+    let acc_infos = &mut accounts.iter();
+
+    let vesting_acc_beneficiary_info = next_account_info(acc_infos)?;
+    let vesting_acc_info = next_account_info(acc_infos)?;
+    let new_vesting_acc_info = next_account_info(acc_infos)?;
+    let safe_acc_info = next_account_info(acc_infos)?;
+    let new_nft_mint_acc_info = next_account_info(acc_infos)?;
+    let safe_vault_authority_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+
+    // Trailing accounts depend on the state of the account being split.
+    let vesting = Vesting::unpack(&vesting_acc_info.try_borrow_data()?)?;
+    let nft_acc_infos = if vesting.claimed {
+        Some((next_account_info(acc_infos)?, next_account_info(acc_infos)?))
+    } else {
+        None
+    };
+    let schedule_acc_infos = if vesting.has_schedule() {
+        Some((next_account_info(acc_infos)?, next_account_info(acc_infos)?))
+    } else {
+        None
+    };
+
+    access_control(AccessControlRequest {
+        program_id,
+        amount,
+        vesting_acc_beneficiary_info,
+        vesting_acc_info,
+        new_vesting_acc_info,
+        safe_acc_info,
+        new_nft_mint_acc_info,
+        safe_vault_authority_acc_info,
+        rent_acc_info,
+        nft_acc_infos,
+        schedule_acc_infos,
+    })?;
+
+    Vesting::unpack_mut(
+        &mut vesting_acc_info.try_borrow_mut_data()?,
+        &mut |vesting_acc: &mut Vesting| {
+            Vesting::unpack_mut(
+                &mut new_vesting_acc_info.try_borrow_mut_data()?,
+                &mut |new_vesting_acc: &mut Vesting| {
+                    state_transition(StateTransitionRequest {
+                        amount,
+                        new_beneficiary,
+                        vesting_acc,
+                        new_vesting_acc,
+                        vesting_acc_beneficiary_info,
+                        new_vesting_acc_info,
+                        safe_acc_info,
+                        new_nft_mint_acc_info,
+                        token_program_acc_info,
+                        nft_acc_infos,
+                        schedule_acc_infos,
+                    })
+                    .map_err(Into::into)
+                },
+            )
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), LockupError> {
+    info!("access-control: split_vesting");
+
+    let AccessControlRequest {
+        program_id,
+        amount,
+        vesting_acc_beneficiary_info,
+        vesting_acc_info,
+        new_vesting_acc_info,
+        safe_acc_info,
+        new_nft_mint_acc_info,
+        safe_vault_authority_acc_info,
+        rent_acc_info,
+        nft_acc_infos,
+        schedule_acc_infos,
+    } = req;
+
+    // Beneficiary authorization.
+    if !vesting_acc_beneficiary_info.is_signer {
+        return Err(LockupErrorCode::Unauthorized)?;
+    }
+
+    // Account validation.
+    let rent = access_control::rent(rent_acc_info)?;
+    let safe = access_control::safe(safe_acc_info, program_id)?;
+    let vault_authority = access_control::vault_authority(
+        safe_vault_authority_acc_info,
+        safe_acc_info.key,
+        &safe,
+        program_id,
+    )?;
+    let vesting = access_control::vesting(
+        program_id,
+        safe_acc_info.key,
+        vesting_acc_info,
+        vesting_acc_beneficiary_info,
+    )?;
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        let _ = access_control::locked_token(
+            nft_token_acc_info,
+            nft_mint_acc_info,
+            &vault_authority,
+            &vesting,
+        )?;
+    }
+    let revocable = match schedule_acc_infos {
+        Some((schedule_acc_info, _)) => {
+            access_control::vesting_schedule(program_id, vesting_acc_info, Some(schedule_acc_info))?
+                .revoker
+                .is_some()
+        }
+        None => false,
+    };
+
+    // Initialize checks.
+    {
+        // New vesting account.
+        {
+            let new_vesting = Vesting::unpack(&new_vesting_acc_info.try_borrow_data()?)?;
+            if new_vesting_acc_info.owner != program_id {
+                return Err(LockupErrorCode::NotOwnedByProgram)?;
+            }
+            if !rent.is_exempt(
+                new_vesting_acc_info.lamports(),
+                new_vesting_acc_info.try_data_len()?,
+            ) {
+                return Err(LockupErrorCode::NotRentExempt)?;
+            }
+            if new_vesting.initialized {
+                return Err(LockupErrorCode::AlreadyInitialized)?;
+            }
+        }
+        // New vesting schedule.
+        if let Some((_, new_schedule_acc_info)) = schedule_acc_infos {
+            let new_schedule = VestingSchedule::unpack(&new_schedule_acc_info.try_borrow_data()?)?;
+            if *new_schedule_acc_info.key
+                != VestingSchedule::address(new_vesting_acc_info.key, program_id)
+            {
+                return Err(LockupErrorCode::WrongVestingSchedule)?;
+            }
+            if new_schedule_acc_info.owner != program_id {
+                return Err(LockupErrorCode::NotOwnedByProgram)?;
+            }
+            if !rent.is_exempt(
+                new_schedule_acc_info.lamports(),
+                new_schedule_acc_info.try_data_len()?,
+            ) {
+                return Err(LockupErrorCode::NotRentExempt)?;
+            }
+            if new_schedule.initialized {
+                return Err(LockupErrorCode::AlreadyInitialized)?;
+            }
+        }
+        // New vesting mint.
+        {
+            let mint = access_control::mint(new_nft_mint_acc_info)?;
+            if mint.mint_authority != COption::Some(vault_authority) {
+                return Err(LockupErrorCode::InvalidMintAuthority)?;
+            }
+            if mint.supply != 0 {
+                return Err(LockupErrorCode::InvalidMintSupply)?;
+            }
+            // The new account keeps the revoker, and revoking freezes the
+            // receipts.
+            if revocable && mint.freeze_authority != COption::Some(vault_authority) {
+                return Err(LockupErrorCode::InvalidFreezeAuthority)?;
+            }
+        }
+    }
+
+    // Split checks.
+    //
+    // Funds sent to whitelisted programs stay with the original account.
+    if amount == 0 || amount > vesting.available_for_whitelist() {
+        return Err(LockupErrorCode::InvalidSplitAmount)?;
+    }
+
+    info!("access-control: success");
+
+    Ok(())
+}
+
+fn state_transition<'a, 'b>(req: StateTransitionRequest<'a, 'b>) -> Result<(), LockupError> {
+    info!("state-transition: split_vesting");
+
+    let StateTransitionRequest {
+        amount,
+        new_beneficiary,
+        vesting_acc,
+        new_vesting_acc,
+        vesting_acc_beneficiary_info,
+        new_vesting_acc_info,
+        safe_acc_info,
+        new_nft_mint_acc_info,
+        token_program_acc_info,
+        nft_acc_infos,
+        schedule_acc_infos,
+    } = req;
+
+    // Move the amount into the new account, which vests at the same rate.
+    {
+        let start_balance = vesting_acc.split(amount);
+
+        new_vesting_acc.safe = *safe_acc_info.key;
+        new_vesting_acc.beneficiary = new_beneficiary;
+        new_vesting_acc.initialized = true;
+        new_vesting_acc.claimed = false;
+        new_vesting_acc.period_count = vesting_acc.period_count;
+        new_vesting_acc.start_balance = start_balance;
+        new_vesting_acc.end_slot = vesting_acc.end_slot;
+        new_vesting_acc.start_slot = vesting_acc.start_slot;
+        new_vesting_acc.balance = amount;
+        new_vesting_acc.locked_nft_mint = *new_nft_mint_acc_info.key;
+        new_vesting_acc.whitelist_owned = 0;
+    }
+
+    // Copy the schedule, if any.
+    if let Some((schedule_acc_info, new_schedule_acc_info)) = schedule_acc_infos {
+        let schedule = VestingSchedule::unpack(&schedule_acc_info.try_borrow_data()?)?;
+        VestingSchedule::unpack_mut(
+            &mut new_schedule_acc_info.try_borrow_mut_data()?,
+            &mut |new_schedule_acc: &mut VestingSchedule| {
+                new_schedule_acc.initialized = true;
+                new_schedule_acc.vesting = *new_vesting_acc_info.key;
+                new_schedule_acc.cliff_ts = schedule.cliff_ts;
+                new_schedule_acc.unlocks = schedule.unlocks.clone();
+                new_schedule_acc.revoker = schedule.revoker;
+                new_schedule_acc.streaming = schedule.streaming;
+                Ok(())
+            },
+        )?;
+    }
+
+    // Burn the receipts for the amount moved out.
+    if let Some((nft_token_acc_info, nft_mint_acc_info)) = nft_acc_infos {
+        info!("burning token receipts");
+        let burn_instruction = spl_token::instruction::burn(
+            &spl_token::ID,
+            nft_token_acc_info.key,
+            nft_mint_acc_info.key,
+            vesting_acc_beneficiary_info.key,
+            &[],
+            amount,
+        )?;
+        solana_sdk::program::invoke_signed(
+            &burn_instruction,
+            &[
+                nft_token_acc_info.clone(),
+                nft_mint_acc_info.clone(),
+                vesting_acc_beneficiary_info.clone(),
+                token_program_acc_info.clone(),
+            ],
+            &[],
+        )?;
+    }
+
+    info!("state-transition: success");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    amount: u64,
+    vesting_acc_beneficiary_info: &'a AccountInfo<'a>,
+    vesting_acc_info: &'a AccountInfo<'a>,
+    new_vesting_acc_info: &'a AccountInfo<'a>,
+    safe_acc_info: &'a AccountInfo<'a>,
+    new_nft_mint_acc_info: &'a AccountInfo<'a>,
+    safe_vault_authority_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+    schedule_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    amount: u64,
+    new_beneficiary: Pubkey,
+    vesting_acc: &'b mut Vesting,
+    new_vesting_acc: &'b mut Vesting,
+    vesting_acc_beneficiary_info: &'a AccountInfo<'a>,
+    new_vesting_acc_info: &'a AccountInfo<'a>,
+    safe_acc_info: &'a AccountInfo<'a>,
+    new_nft_mint_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    nft_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+    schedule_acc_infos: Option<(&'a AccountInfo<'a>, &'a AccountInfo<'a>)>,
+}
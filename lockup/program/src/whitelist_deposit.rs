@@ -1,7 +1,9 @@
 use crate::access_control;
+use serum_common::emit_event;
 use serum_common::pack::Pack;
-use serum_lockup::accounts::{Safe, TokenVault, Vesting};
+use serum_lockup::accounts::{Safe, TokenVault, Vesting, Whitelist};
 use serum_lockup::error::{LockupError, LockupErrorCode};
+use serum_lockup::events::WhitelistDeposited;
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
 use solana_sdk::instruction::{AccountMeta, Instruction};
@@ -60,7 +62,9 @@ pub fn handler<'a>(
                 wl_prog_vault_authority_acc_info,
                 safe_vault_acc_info,
                 tok_prog_acc_info,
+                wl_acc_info,
                 vesting,
+                vesting_acc_info,
                 remaining_relay_accs: remaining_relay_accs.clone(),
             })
             .map_err(Into::into)
@@ -127,6 +131,7 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
 
     let StateTransitionRequest {
         vesting,
+        vesting_acc_info,
         instruction_data,
         accounts,
         nonce,
@@ -137,6 +142,7 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
         wl_prog_vault_authority_acc_info,
         remaining_relay_accs,
         tok_prog_acc_info,
+        wl_acc_info,
     } = req;
 
     // Check before balance.
@@ -170,13 +176,25 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
         solana_sdk::program::invoke_signed(&relay_instruction, &accounts[..], &[&signer_seeds])?;
     }
 
-    // Update vesting account.
+    // Update vesting account and the amount outstanding with the entry.
     {
         let vault = spl_token::state::Account::unpack(&safe_vault_acc_info.try_borrow_data()?)?;
         assert!(vault.amount > before_amount);
         let deposit_amount = vault.amount - before_amount;
         assert!(deposit_amount <= vesting.whitelist_owned);
         vesting.whitelist_owned -= deposit_amount;
+
+        let whitelist = Whitelist::new(wl_acc_info.clone())?;
+        let idx = whitelist
+            .index_of_derived(wl_prog_vault_authority_acc_info.key)?
+            .ok_or(LockupErrorCode::WhitelistNotFound)?;
+        whitelist.sub_outstanding(idx, deposit_amount)?;
+
+        emit_event!(WhitelistDeposited {
+            vesting: *vesting_acc_info.key,
+            program: *wl_prog_acc_info.key,
+            amount: deposit_amount,
+        })?;
     }
 
     info!("state-transition: success");
@@ -199,6 +217,7 @@ struct AccessControlRequest<'a> {
 struct StateTransitionRequest<'a, 'b> {
     instruction_data: Vec<u8>,
     vesting: &'b mut Vesting,
+    vesting_acc_info: &'a AccountInfo<'a>,
     accounts: &'a [AccountInfo<'a>],
     nonce: u8,
     safe_acc: &'a Pubkey,
@@ -208,4 +227,5 @@ struct StateTransitionRequest<'a, 'b> {
     wl_prog_vault_authority_acc_info: &'a AccountInfo<'a>,
     remaining_relay_accs: Vec<&'a AccountInfo<'a>>,
     tok_prog_acc_info: &'a AccountInfo<'a>,
+    wl_acc_info: &'a AccountInfo<'a>,
 }
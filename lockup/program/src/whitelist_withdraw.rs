@@ -1,7 +1,9 @@
 use crate::access_control;
+use serum_common::emit_event;
 use serum_common::pack::Pack;
-use serum_lockup::accounts::{Safe, TokenVault, Vesting};
+use serum_lockup::accounts::{Safe, TokenVault, Vesting, Whitelist};
 use serum_lockup::error::{LockupError, LockupErrorCode};
+use serum_lockup::events::WhitelistWithdrawn;
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
 use solana_sdk::instruction::{AccountMeta, Instruction};
@@ -64,7 +66,9 @@ pub fn handler<'a>(
                 safe_vault_acc_info,
                 safe_vault_auth_acc_info,
                 tok_prog_acc_info,
+                wl_acc_info,
                 vesting,
+                vesting_acc_info,
                 remaining_relay_accs: remaining_relay_accs.clone(),
             })
             .map_err(Into::into)
@@ -118,12 +122,17 @@ fn access_control(req: AccessControlRequest) -> Result<(), LockupError> {
     if amount > vesting.available_for_whitelist() {
         return Err(LockupErrorCode::InsufficientWhitelistBalance)?;
     }
-    let entry = whitelist
-        .get_derived(wl_prog_vault_authority_acc_info.key)?
+    let idx = whitelist
+        .index_of_derived(wl_prog_vault_authority_acc_info.key)?
         .ok_or(LockupErrorCode::WhitelistNotFound)?;
+    let entry = whitelist.get_at(idx)?;
     if entry.program_id() != *wl_prog_acc_info.key {
         return Err(LockupErrorCode::WhitelistInvalidProgramId)?;
     }
+    let (max_outstanding, outstanding) = whitelist.get_meta_at(idx)?;
+    if outstanding.saturating_add(amount) > max_outstanding {
+        return Err(LockupErrorCode::WhitelistLimitExceeded)?;
+    }
 
     info!("access-control: success");
 
@@ -135,6 +144,7 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
 
     let StateTransitionRequest {
         vesting,
+        vesting_acc_info,
         instruction_data,
         accounts,
         amount,
@@ -147,6 +157,7 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
         remaining_relay_accs,
         tok_prog_acc_info,
         safe_vault_auth_acc_info,
+        wl_acc_info,
     } = req;
 
     let signer_seeds = TokenVault::signer_seeds(safe_acc, &nonce);
@@ -201,11 +212,23 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), LockupError> {
         solana_sdk::program::invoke_signed(&revoke_instr, &accounts[..], &[&signer_seeds])?;
     }
 
-    // Update vesting account.
+    // Update vesting account and the amount outstanding with the entry.
     {
         let vault = spl_token::state::Account::unpack(&safe_vault_acc_info.try_borrow_data()?)?;
         let amount_transferred = amount - vault.delegated_amount;
         vesting.whitelist_owned += amount_transferred;
+
+        let whitelist = Whitelist::new(wl_acc_info.clone())?;
+        let idx = whitelist
+            .index_of_derived(wl_prog_vault_authority_acc_info.key)?
+            .ok_or(LockupErrorCode::WhitelistNotFound)?;
+        whitelist.add_outstanding(idx, amount_transferred)?;
+
+        emit_event!(WhitelistWithdrawn {
+            vesting: *vesting_acc_info.key,
+            program: *wl_prog_acc_info.key,
+            amount: amount_transferred,
+        })?;
     }
 
     info!("state-transition: success");
@@ -229,6 +252,7 @@ struct AccessControlRequest<'a> {
 struct StateTransitionRequest<'a, 'b> {
     instruction_data: Vec<u8>,
     vesting: &'b mut Vesting,
+    vesting_acc_info: &'a AccountInfo<'a>,
     accounts: &'a [AccountInfo<'a>],
     amount: u64,
     nonce: u8,
@@ -240,4 +264,5 @@ struct StateTransitionRequest<'a, 'b> {
     wl_prog_vault_authority_acc_info: &'a AccountInfo<'a>,
     remaining_relay_accs: Vec<&'a AccountInfo<'a>>,
     tok_prog_acc_info: &'a AccountInfo<'a>,
+    wl_acc_info: &'a AccountInfo<'a>,
 }
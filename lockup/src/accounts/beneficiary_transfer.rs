@@ -0,0 +1,18 @@
+use serum_common::pack::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// BeneficiaryTransfer is a pending hand off of a Vesting account to a new
+/// beneficiary. It's created by the current beneficiary and closed once the
+/// new beneficiary accepts, or the current one cancels.
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize, Pack)]
+pub struct BeneficiaryTransfer {
+    /// Set by the program on creation.
+    pub initialized: bool,
+    /// Vesting account being transferred.
+    pub vesting: Pubkey,
+    /// Beneficiary that initiated the transfer. The transfer can't be
+    /// accepted once the vesting account's beneficiary has changed.
+    pub beneficiary: Pubkey,
+    /// Key that must sign to accept the transfer.
+    pub new_beneficiary: Pubkey,
+}
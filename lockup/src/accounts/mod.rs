@@ -1,11 +1,15 @@
 //! mod accounts defines the storage layout for the accounts used by this program.
 
+pub mod beneficiary_transfer;
 pub mod safe;
 pub mod token_vault;
 pub mod vesting;
+pub mod vesting_schedule;
 pub mod whitelist;
 
+pub use beneficiary_transfer::BeneficiaryTransfer;
 pub use safe::Safe;
 pub use token_vault::TokenVault;
 pub use vesting::Vesting;
-pub use whitelist::{Whitelist, WhitelistEntry};
+pub use vesting_schedule::{Unlock, VestingSchedule};
+pub use whitelist::{Whitelist, WhitelistEntry, WhitelistItem};
@@ -3,7 +3,7 @@ use serum_common::pack::*;
 use solana_client_gen::solana_sdk::pubkey::Pubkey;
 
 /// Safe is the account representing an instance of this program.
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Default, Debug, Serialize, Deserialize, Pack)]
 pub struct Safe {
     /// Is `true` if this structure has been initialized
     pub initialized: bool,
@@ -20,8 +20,6 @@ pub struct Safe {
     pub vault: Pubkey,
 }
 
-serum_common::packable!(Safe);
-
 #[cfg(test)]
 mod tests {
     use super::*;
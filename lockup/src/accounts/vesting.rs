@@ -1,17 +1,11 @@
+use crate::accounts::{Unlock, VestingSchedule};
 use serum_common::pack::*;
 use solana_client_gen::solana_sdk::pubkey::Pubkey;
 
-#[cfg(feature = "client")]
-lazy_static::lazy_static! {
-    pub static ref SIZE: u64 = Vesting::default()
-                .size()
-                .expect("Vesting has a fixed size");
-}
-
 /// The Vesting account represents a single deposit of a token
 /// available for withdrawal over a period of time determined by
 /// a vesting schedule.
-#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize, Pack)]
 pub struct Vesting {
     /// True iff the vesting account has been initialized via deposit.
     pub initialized: bool,
@@ -30,10 +24,11 @@ pub struct Vesting {
     /// The slot at which this vesting account was created.
     pub start_slot: u64,
     /// The slot at which all the tokens associated with this account
-    /// should be vested.
+    /// should be vested. Zero if the account has a VestingSchedule.
     pub end_slot: u64,
     /// The number of times vesting will occur. For example, if vesting
-    /// is once a year over seven years, this will be 7.
+    /// is once a year over seven years, this will be 7. Zero if the
+    /// account vests according to a VestingSchedule instead.
     pub period_count: u64,
     /// The spl token mint associated with this vesting account. The supply
     /// should always equal the `balance` field.
@@ -45,6 +40,17 @@ pub struct Vesting {
 }
 
 impl Vesting {
+    /// Byte offset of `safe` in the packed account. The DEX reads vesting
+    /// accounts of the SRM safe directly for fee discounts, so these
+    /// offsets must not change.
+    pub const SAFE_OFFSET: usize = 2;
+    /// Byte offset of `beneficiary` in the packed account.
+    pub const BENEFICIARY_OFFSET: usize = 34;
+    /// Byte offset of `balance` in the packed account.
+    pub const BALANCE_OFFSET: usize = 66;
+    /// Byte offset of `whitelist_owned` in the packed account.
+    pub const WHITELIST_OWNED_OFFSET: usize = 170;
+
     /// Deducts the given amount from the vesting account upon
     /// withdrawal/redemption.
     pub fn deduct(&mut self, amount: u64) {
@@ -63,6 +69,93 @@ impl Vesting {
         )
     }
 
+    /// Same as `available_for_withdrawal`, for an account vesting according
+    /// to the given schedule rather than the built-in linear release.
+    pub fn available_for_withdrawal_with_schedule(
+        &self,
+        schedule: &VestingSchedule,
+        unix_timestamp: i64,
+    ) -> u64 {
+        let balance_vested = schedule
+            .total_vested(self.start_balance, unix_timestamp)
+            .saturating_sub(self.withdrawn_amount());
+        std::cmp::min(balance_vested, self.available_for_whitelist())
+    }
+
+    /// Ends vesting as of the given unix timestamp, so that only the amount
+    /// vested so far remains, fully vested. Returns the unvested amount
+    /// removed from the account, or None if the balance doesn't cover it.
+    pub fn revoke(&mut self, schedule: &mut VestingSchedule, unix_timestamp: i64) -> Option<u64> {
+        let vested = schedule.total_vested(self.start_balance, unix_timestamp);
+        let unvested = self.start_balance.checked_sub(vested)?;
+        self.balance = self.balance.checked_sub(unvested)?;
+        self.start_balance = vested;
+        schedule.cliff_ts = unix_timestamp;
+        schedule.unlocks = vec![Unlock {
+            ts: unix_timestamp,
+            bps: VestingSchedule::BPS_TOTAL,
+        }];
+        schedule.revoker = None;
+        Some(unvested)
+    }
+
+    /// Moves `amount` of the balance out of this account, along with the
+    /// same fraction of the starting balance, so that both parts keep
+    /// vesting at the same rate. Returns the starting balance moved out.
+    pub fn split(&mut self, amount: u64) -> u64 {
+        let start_balance =
+            (self.start_balance as u128 * amount as u128 / self.balance as u128) as u64;
+        self.start_balance -= start_balance;
+        self.balance -= amount;
+        start_balance
+    }
+
+    /// Adds a deposit of `amount` to the account as of the given slot, to
+    /// vest over the periods that remain. Returns None if nothing remains
+    /// to vest or on overflow.
+    pub fn top_up(&mut self, amount: u64, current_slot: u64) -> Option<()> {
+        let vested = self.total_vested(current_slot);
+        self.top_up_vested(amount, vested)
+    }
+
+    /// Same as `top_up`, for an account vesting according to the given
+    /// schedule, with the deposit vesting over the unlocks that remain.
+    pub fn top_up_with_schedule(
+        &mut self,
+        amount: u64,
+        schedule: &VestingSchedule,
+        unix_timestamp: i64,
+    ) -> Option<()> {
+        let vested = schedule.total_vested(self.start_balance, unix_timestamp);
+        self.top_up_vested(amount, vested)
+    }
+
+    // Scales the starting balance so that the unvested part of it grows by
+    // `amount`, i.e., so that each remaining unlock releases its share of
+    // the deposit. The amount available for withdrawal now is unchanged, up
+    // to rounding, since the amount considered withdrawn grows along with
+    // the amount considered vested.
+    fn top_up_vested(&mut self, amount: u64, vested: u64) -> Option<()> {
+        let unvested = self.start_balance - vested;
+        if unvested == 0 {
+            return None;
+        }
+        let unvested_after = unvested as u128 + amount as u128;
+        let start_balance =
+            (unvested_after * self.start_balance as u128 + unvested as u128 - 1) / unvested as u128;
+        if start_balance > u64::MAX as u128 {
+            return None;
+        }
+        self.start_balance = start_balance as u64;
+        self.balance = self.balance.checked_add(amount)?;
+        Some(())
+    }
+
+    /// True if the account vests according to a VestingSchedule.
+    pub fn has_schedule(&self) -> bool {
+        self.period_count == 0
+    }
+
     /// Amount available for whitelisted programs to transfer.
     pub fn available_for_whitelist(&self) -> u64 {
         self.balance - self.whitelist_owned
@@ -71,12 +164,15 @@ impl Vesting {
     // The amount vested that's available for withdrawal, if no funds were ever
     // sent to another program.
     fn balance_vested(&self, current_slot: u64) -> u64 {
-        self.total_vested(current_slot) - self.withdrawn_amount()
+        // Saturates since a split can round the vested amount of either
+        // part below what it has withdrawn.
+        self.total_vested(current_slot)
+            .saturating_sub(self.withdrawn_amount())
     }
 
     // Returns the total vested amount up to the given slot, assuming zero
     // withdrawals and zero funds sent to other programs.
-    fn total_vested(&self, current_slot: u64) -> u64 {
+    pub(crate) fn total_vested(&self, current_slot: u64) -> u64 {
         assert!(current_slot >= self.start_slot);
 
         if current_slot >= self.end_slot {
@@ -117,8 +213,6 @@ impl Vesting {
     }
 }
 
-serum_common::packable!(Vesting);
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -213,6 +307,143 @@ mod tests {
         assert_eq!(10, vesting_acc.available_for_withdrawal(100));
     }
 
+    #[test]
+    fn revoke() {
+        let mut vesting_acc = Vesting {
+            initialized: true,
+            balance: 70,
+            start_balance: 100,
+            ..Default::default()
+        };
+        let mut schedule = VestingSchedule {
+            initialized: true,
+            unlocks: vec![
+                Unlock { ts: 10, bps: 5_000 },
+                Unlock {
+                    ts: 20,
+                    bps: 10_000,
+                },
+            ],
+            revoker: Some(Pubkey::new_rand()),
+            ..Default::default()
+        };
+        // Half vested, 30 of which was withdrawn.
+        assert_eq!(Some(50), vesting_acc.revoke(&mut schedule, 15));
+        assert_eq!(vesting_acc.start_balance, 50);
+        assert_eq!(vesting_acc.balance, 20);
+        assert_eq!(schedule.revoker, None);
+        assert_eq!(
+            20,
+            vesting_acc.available_for_withdrawal_with_schedule(&schedule, 15)
+        );
+        assert_eq!(
+            20,
+            vesting_acc.available_for_withdrawal_with_schedule(&schedule, 100)
+        );
+    }
+
+    #[test]
+    fn revoke_more_than_balance() {
+        let mut vesting_acc = Vesting {
+            initialized: true,
+            balance: 10,
+            start_balance: 100,
+            ..Default::default()
+        };
+        let mut schedule = VestingSchedule {
+            initialized: true,
+            unlocks: vec![Unlock {
+                ts: 10,
+                bps: 10_000,
+            }],
+            revoker: Some(Pubkey::new_rand()),
+            ..Default::default()
+        };
+        assert_eq!(None, vesting_acc.revoke(&mut schedule, 5));
+        assert_eq!(vesting_acc.balance, 10);
+        assert!(schedule.revoker.is_some());
+    }
+
+    #[test]
+    fn split() {
+        let mut vesting_acc = Vesting {
+            initialized: true,
+            balance: 70,
+            start_balance: 100,
+            start_slot: 10,
+            end_slot: 20,
+            period_count: 10,
+            ..Default::default()
+        };
+        // 30 vested and withdrawn, so each part has withdrawn its share.
+        assert_eq!(50, vesting_acc.split(35));
+        assert_eq!(vesting_acc.start_balance, 50);
+        assert_eq!(vesting_acc.balance, 35);
+        assert_eq!(0, vesting_acc.available_for_withdrawal(13));
+        assert_eq!(5, vesting_acc.available_for_withdrawal(14));
+        assert_eq!(35, vesting_acc.available_for_withdrawal(20));
+    }
+
+    #[test]
+    fn top_up() {
+        let mut vesting_acc = Vesting {
+            initialized: true,
+            balance: 70,
+            start_balance: 100,
+            start_slot: 10,
+            end_slot: 20,
+            period_count: 10,
+            ..Default::default()
+        };
+        // Half vested, 30 of which was withdrawn. The deposit vests over the
+        // remaining five periods.
+        assert_eq!(20, vesting_acc.available_for_withdrawal(15));
+        vesting_acc.top_up(50, 15).unwrap();
+        assert_eq!(vesting_acc.start_balance, 200);
+        assert_eq!(vesting_acc.balance, 120);
+        assert_eq!(20, vesting_acc.available_for_withdrawal(15));
+        assert_eq!(40, vesting_acc.available_for_withdrawal(16));
+        assert_eq!(100, vesting_acc.available_for_withdrawal(19));
+        assert_eq!(120, vesting_acc.available_for_withdrawal(20));
+
+        // Nothing remains to vest under.
+        assert!(vesting_acc.top_up(50, 20).is_none());
+    }
+
+    #[test]
+    fn top_up_with_schedule() {
+        let mut vesting_acc = Vesting {
+            initialized: true,
+            balance: 100,
+            start_balance: 100,
+            ..Default::default()
+        };
+        let schedule = VestingSchedule {
+            initialized: true,
+            unlocks: vec![
+                Unlock { ts: 10, bps: 5_000 },
+                Unlock {
+                    ts: 20,
+                    bps: 10_000,
+                },
+            ],
+            ..Default::default()
+        };
+        vesting_acc
+            .top_up_with_schedule(100, &schedule, 15)
+            .unwrap();
+        assert_eq!(vesting_acc.start_balance, 300);
+        assert_eq!(vesting_acc.balance, 200);
+        assert_eq!(
+            50,
+            vesting_acc.available_for_withdrawal_with_schedule(&schedule, 15)
+        );
+        assert_eq!(
+            200,
+            vesting_acc.available_for_withdrawal_with_schedule(&schedule, 20)
+        );
+    }
+
     #[test]
     fn unpack_zeroes() {
         let og_size = Vesting::default().size().unwrap();
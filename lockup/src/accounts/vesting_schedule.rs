@@ -0,0 +1,278 @@
+use serum_common::pack::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// VestingSchedule replaces the built-in linear release of a Vesting account
+/// with an explicit list of unlock points, e.g., a one year cliff followed by
+/// monthly unlocks over four years. A streaming schedule instead accrues
+/// linearly, per second, between consecutive unlock points, e.g., for
+/// payroll.
+///
+/// The account lives at the address derived from the Vesting account with
+/// `VestingSchedule::SEED`, so that clients can always find it. A Vesting
+/// account with a schedule has its `period_count` set to zero.
+///
+/// Its serialized size varies with the number of unlocks, so the account
+/// is allocated at `SIZE`, the largest possible serialization, and the
+/// unused tail is kept zeroed.
+#[derive(Default, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VestingSchedule {
+    /// Set by the program on creation.
+    pub initialized: bool,
+    /// The Vesting account this schedule applies to.
+    pub vesting: Pubkey,
+    /// Unix timestamp before which nothing is vested, regardless of the
+    /// unlock points.
+    pub cliff_ts: i64,
+    /// Unlock points, sorted by timestamp. At most `MAX_UNLOCKS`.
+    pub unlocks: Vec<Unlock>,
+    /// Authority allowed to revoke the unvested part of the deposit, if any.
+    pub revoker: Option<Pubkey>,
+    /// If true, the vested fraction is interpolated between unlock points
+    /// instead of stepping at each one.
+    pub streaming: bool,
+}
+
+/// A point in time at which a cumulative fraction of the deposit is vested.
+#[derive(Default, Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Unlock {
+    /// Unix timestamp at which the unlock occurs.
+    pub ts: i64,
+    /// Total fraction of the deposit vested as of `ts`, in basis points.
+    pub bps: u16,
+}
+
+impl VestingSchedule {
+    /// Seed used with `Pubkey::create_with_seed` on the Vesting address.
+    pub const SEED: &'static str = "vesting-schedule";
+
+    /// Enough for monthly unlocks over four years.
+    pub const MAX_UNLOCKS: usize = 48;
+
+    /// Basis points of a fully vested deposit.
+    pub const BPS_TOTAL: u16 = 10_000;
+
+    /// Account size. The unlocks are serialized with a u64 length prefix
+    /// and the revoker with a one byte tag.
+    pub const SIZE: usize =
+        1 + 32 + 8 + (8 + VestingSchedule::MAX_UNLOCKS * (8 + 2)) + (1 + 32) + 1;
+
+    /// Address of the schedule for the given Vesting account.
+    pub fn address(vesting: &Pubkey, program_id: &Pubkey) -> Pubkey {
+        Pubkey::create_with_seed(vesting, VestingSchedule::SEED, program_id)
+            .expect("seed is within bounds")
+    }
+
+    /// Unlocks `period_count` equal parts between `start_ts` and `end_ts`,
+    /// with nothing vested before `cliff_ts`. Returns None if the periods
+    /// don't fit in `MAX_UNLOCKS` or the timestamps aren't ordered.
+    pub fn linear(
+        start_ts: i64,
+        cliff_ts: i64,
+        end_ts: i64,
+        period_count: u64,
+    ) -> Option<(i64, Vec<Unlock>)> {
+        if period_count == 0
+            || period_count as usize > VestingSchedule::MAX_UNLOCKS
+            || start_ts >= end_ts
+            || cliff_ts < start_ts
+            || cliff_ts > end_ts
+        {
+            return None;
+        }
+        let duration = (end_ts - start_ts) as u64;
+        let unlocks = (1..=period_count)
+            .map(|p| Unlock {
+                ts: start_ts + (duration * p / period_count) as i64,
+                bps: (VestingSchedule::BPS_TOTAL as u64 * p / period_count) as u16,
+            })
+            .collect();
+        Some((cliff_ts, unlocks))
+    }
+
+    /// Unlock points for a stream vesting nothing at `start_ts` and
+    /// everything at `end_ts`. Only meaningful with `streaming` set.
+    pub fn stream(start_ts: i64, end_ts: i64) -> Vec<Unlock> {
+        vec![
+            Unlock {
+                ts: start_ts,
+                bps: 0,
+            },
+            Unlock {
+                ts: end_ts,
+                bps: VestingSchedule::BPS_TOTAL,
+            },
+        ]
+    }
+
+    /// True if the unlocks are non-empty, bounded, strictly increasing in
+    /// time, non-decreasing in fraction, and end fully vested.
+    pub fn is_valid(cliff_ts: i64, unlocks: &[Unlock]) -> bool {
+        if unlocks.is_empty() || unlocks.len() > VestingSchedule::MAX_UNLOCKS {
+            return false;
+        }
+        let ordered = unlocks
+            .windows(2)
+            .all(|w| w[0].ts < w[1].ts && w[0].bps <= w[1].bps);
+        let last = unlocks[unlocks.len() - 1];
+        ordered && last.bps == VestingSchedule::BPS_TOTAL && cliff_ts <= last.ts
+    }
+
+    /// Returns the amount of `start_balance` vested as of the given unix
+    /// timestamp, assuming zero withdrawals.
+    pub fn total_vested(&self, start_balance: u64, unix_ts: i64) -> u64 {
+        if unix_ts < self.cliff_ts {
+            return 0;
+        }
+        let idx = self.unlocks.iter().take_while(|u| u.ts <= unix_ts).count();
+        let bps_total = VestingSchedule::BPS_TOTAL as u128;
+        // Can't overflow: bps <= BPS_TOTAL, so the products fit in a u128
+        // and the quotients are at most start_balance.
+        match (idx, self.streaming) {
+            (0, _) => 0,
+            (idx, true) if idx < self.unlocks.len() => {
+                let prev = self.unlocks[idx - 1];
+                let next = self.unlocks[idx];
+                let span = (next.ts - prev.ts) as u128;
+                let elapsed = (unix_ts - prev.ts) as u128;
+                let bps = prev.bps as u128 * span + (next.bps - prev.bps) as u128 * elapsed;
+                (start_balance as u128 * bps / (bps_total * span)) as u64
+            }
+            (idx, _) => {
+                let bps = self.unlocks[idx - 1].bps as u128;
+                (start_balance as u128 * bps / bps_total) as u64
+            }
+        }
+    }
+}
+
+impl Pack for VestingSchedule {
+    fn pack(src: VestingSchedule, dst: &mut [u8]) -> Result<(), ProgramError> {
+        let size = src.size()? as usize;
+        if size > dst.len() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        into_bytes(&src, &mut dst[..size])?;
+        for b in dst[size..].iter_mut() {
+            *b = 0;
+        }
+        Ok(())
+    }
+
+    fn unpack_unchecked(src: &mut &[u8]) -> Result<VestingSchedule, ProgramError> {
+        from_reader(src)
+    }
+
+    fn size(&self) -> Result<u64, ProgramError> {
+        bytes_size(&self)
+    }
+
+    // The zeroed tail after the serialized unlocks is expected.
+    fn unpack(src: &[u8]) -> Result<VestingSchedule, ProgramError> {
+        let mut src_mut = src;
+        let schedule = VestingSchedule::unpack_unchecked(&mut src_mut)?;
+        if src_mut.iter().any(|b| *b != 0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(schedule)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const YEAR: i64 = 365 * 24 * 60 * 60;
+
+    #[test]
+    fn cliff_then_linear() {
+        let start = 1_000;
+        let (cliff_ts, unlocks) =
+            VestingSchedule::linear(start, start + YEAR, start + 4 * YEAR, 48).unwrap();
+        assert!(VestingSchedule::is_valid(cliff_ts, &unlocks));
+        let schedule = VestingSchedule {
+            initialized: true,
+            vesting: Pubkey::new_rand(),
+            cliff_ts,
+            unlocks,
+            revoker: None,
+            streaming: false,
+        };
+        let balance = 4_800;
+        assert_eq!(0, schedule.total_vested(balance, start));
+        assert_eq!(0, schedule.total_vested(balance, start + YEAR - 1));
+        assert_eq!(1_200, schedule.total_vested(balance, start + YEAR));
+        assert_eq!(2_400, schedule.total_vested(balance, start + 2 * YEAR));
+        assert_eq!(4_800, schedule.total_vested(balance, start + 4 * YEAR));
+        assert_eq!(4_800, schedule.total_vested(balance, start + 10 * YEAR));
+    }
+
+    #[test]
+    fn streaming() {
+        let start = 1_000;
+        let schedule = VestingSchedule {
+            initialized: true,
+            vesting: Pubkey::new_rand(),
+            cliff_ts: start,
+            unlocks: VestingSchedule::stream(start, start + YEAR),
+            revoker: None,
+            streaming: true,
+        };
+        assert!(VestingSchedule::is_valid(
+            schedule.cliff_ts,
+            &schedule.unlocks
+        ));
+        let balance = YEAR as u64 * 3;
+        assert_eq!(0, schedule.total_vested(balance, start - 1));
+        assert_eq!(0, schedule.total_vested(balance, start));
+        assert_eq!(3, schedule.total_vested(balance, start + 1));
+        assert_eq!(
+            balance / 2,
+            schedule.total_vested(balance, start + YEAR / 2)
+        );
+        assert_eq!(
+            balance - 3,
+            schedule.total_vested(balance, start + YEAR - 1)
+        );
+        assert_eq!(balance, schedule.total_vested(balance, start + YEAR));
+        assert_eq!(balance, schedule.total_vested(balance, start + 2 * YEAR));
+    }
+
+    #[test]
+    fn invalid_schedules() {
+        let u = |ts, bps| Unlock { ts, bps };
+        assert!(!VestingSchedule::is_valid(0, &[]));
+        assert!(!VestingSchedule::is_valid(0, &[u(10, 5_000)]));
+        assert!(!VestingSchedule::is_valid(
+            0,
+            &[u(10, 10_000), u(10, 10_000)]
+        ));
+        assert!(!VestingSchedule::is_valid(0, &[u(10, 6_000), u(20, 5_000)]));
+        assert!(!VestingSchedule::is_valid(
+            30,
+            &[u(10, 5_000), u(20, 10_000)]
+        ));
+        assert!(VestingSchedule::is_valid(
+            20,
+            &[u(10, 5_000), u(20, 10_000)]
+        ));
+        let too_many = vec![u(1, 10_000); VestingSchedule::MAX_UNLOCKS + 1];
+        assert!(!VestingSchedule::is_valid(0, &too_many));
+    }
+
+    #[test]
+    fn pack_unpack_padded() {
+        let schedule = VestingSchedule {
+            initialized: true,
+            vesting: Pubkey::new_rand(),
+            cliff_ts: 5,
+            unlocks: vec![Unlock { ts: 5, bps: 10_000 }],
+            revoker: Some(Pubkey::new_rand()),
+            streaming: false,
+        };
+        let mut dst = vec![1u8; VestingSchedule::SIZE];
+        VestingSchedule::pack(schedule, &mut dst).unwrap();
+        let unpacked = VestingSchedule::unpack(&dst).unwrap();
+        assert_eq!(unpacked.cliff_ts, 5);
+        assert_eq!(unpacked.unlocks, vec![Unlock { ts: 5, bps: 10_000 }]);
+    }
+}
@@ -16,6 +16,11 @@ use solana_client_gen::solana_sdk::pubkey::Pubkey;
 /// This makes it a bit unsafe to use--since Solana's data storage
 /// is wrapped in a RefCell, so be careful when you're mutating the
 /// whitelist to avoid a RefCell induced panic.
+///
+/// The entries are followed by a metadata region holding, for each index,
+/// the max amount that may be outstanding with the entry and the amount
+/// currently outstanding. Whitelists created before the metadata region
+/// existed are `ENTRIES_SIZE` bytes long and treat every entry as unlimited.
 #[derive(Debug)]
 pub struct Whitelist<'a> {
     pub acc_info: AccountInfo<'a>,
@@ -26,16 +31,26 @@ impl<'a> Whitelist<'a> {
     pub const ITEM_SIZE: usize = 65;
     /// Number of items in the whitelist.
     pub const LEN: usize = 50; // TODO: how big do we want this?
+    /// Byte size of the entries region.
+    pub const ENTRIES_SIZE: usize = 65 * Whitelist::LEN;
+    /// Byte size of the metadata for a single item in the whitelist.
+    pub const META_ITEM_SIZE: usize = 16;
     /// Byte size of the entire whitelist.
-    pub const SIZE: usize = 65 * Whitelist::LEN;
+    pub const SIZE: usize = Whitelist::ENTRIES_SIZE + Whitelist::META_ITEM_SIZE * Whitelist::LEN;
 
     pub fn new(acc_info: AccountInfo<'a>) -> Result<Self, LockupError> {
-        if acc_info.try_data_len()? != Whitelist::SIZE {
+        let len = acc_info.try_data_len()?;
+        if len != Whitelist::SIZE && len != Whitelist::ENTRIES_SIZE {
             return Err(LockupErrorCode::WhitelistInvalidData)?;
         }
         Ok(Self { acc_info })
     }
 
+    /// Returns true if the whitelist has a metadata region.
+    pub fn has_metadata(&self) -> Result<bool, LockupError> {
+        Ok(self.acc_info.try_data_len()? == Whitelist::SIZE)
+    }
+
     /// Returns the WhitelistEntry at the given index.
     pub fn get_at(&self, index: usize) -> Result<WhitelistEntry, LockupError> {
         let data = self.acc_info.try_borrow_data()?;
@@ -59,10 +74,66 @@ impl<'a> Whitelist<'a> {
         Ok(())
     }
 
+    /// Returns the (max outstanding, outstanding) amounts for the entry at
+    /// the given index. Without a metadata region, the entry is unlimited.
+    pub fn get_meta_at(&self, index: usize) -> Result<(u64, u64), LockupError> {
+        if !self.has_metadata()? {
+            return Ok((u64::MAX, 0));
+        }
+        let data = self.acc_info.try_borrow_data()?;
+        let src = array_ref![
+            data,
+            Whitelist::ENTRIES_SIZE + index * Whitelist::META_ITEM_SIZE,
+            Whitelist::META_ITEM_SIZE
+        ];
+        let (max_outstanding, outstanding) = array_refs![src, 8, 8];
+        Ok((
+            u64::from_le_bytes(*max_outstanding),
+            u64::from_le_bytes(*outstanding),
+        ))
+    }
+
+    /// Sets the (max outstanding, outstanding) amounts for the entry at the
+    /// given index. Errors if a limit is given for a whitelist without a
+    /// metadata region.
+    pub fn set_meta_at(
+        &self,
+        index: usize,
+        max_outstanding: u64,
+        outstanding: u64,
+    ) -> Result<(), LockupError> {
+        if !self.has_metadata()? {
+            if max_outstanding != u64::MAX {
+                return Err(LockupErrorCode::WhitelistInvalidData)?;
+            }
+            return Ok(());
+        }
+        let mut data = self.acc_info.try_borrow_mut_data()?;
+        let dst = array_mut_ref![
+            data,
+            Whitelist::ENTRIES_SIZE + index * Whitelist::META_ITEM_SIZE,
+            Whitelist::META_ITEM_SIZE
+        ];
+        let (max_outstanding_dst, outstanding_dst) = mut_array_refs![dst, 8, 8];
+        max_outstanding_dst.copy_from_slice(&max_outstanding.to_le_bytes());
+        outstanding_dst.copy_from_slice(&outstanding.to_le_bytes());
+        Ok(())
+    }
+
     /// Inserts the given WhitelistEntry at the first available index.
     /// Returns Some(index) where the entry was inserted. If the Whitelist
     /// is full, returns None.
     pub fn push(&self, entry: WhitelistEntry) -> Result<Option<usize>, LockupError> {
+        self.push_with_limit(entry, u64::MAX)
+    }
+
+    /// Same as `push`, allowing at most `max_outstanding` to be sent to the
+    /// entry at any given time.
+    pub fn push_with_limit(
+        &self,
+        entry: WhitelistEntry,
+        max_outstanding: u64,
+    ) -> Result<Option<usize>, LockupError> {
         let existing_idx = self.index_of(&entry)?;
         if let Some(_) = existing_idx {
             return Err(LockupErrorCode::WhitelistEntryAlreadyExists)?;
@@ -70,6 +141,7 @@ impl<'a> Whitelist<'a> {
         let idx = self.index_of(&WhitelistEntry::zero())?;
         if let Some(idx) = idx {
             self.add_at(idx, entry)?;
+            self.set_meta_at(idx, max_outstanding, 0)?;
             return Ok(Some(idx));
         }
         Ok(idx)
@@ -80,17 +152,48 @@ impl<'a> Whitelist<'a> {
         let idx = self.index_of(&entry)?;
         if let Some(idx) = idx {
             self.add_at(idx, WhitelistEntry::zero())?;
+            if self.has_metadata()? {
+                self.set_meta_at(idx, 0, 0)?;
+            }
             return Ok(Some(idx));
         }
         Ok(idx)
     }
 
+    /// Sets the max outstanding amount of the given entry, keeping the amount
+    /// currently outstanding. Lowering it below the amount outstanding only
+    /// blocks further withdrawals to the entry.
+    pub fn set_max_outstanding(
+        &self,
+        entry: &WhitelistEntry,
+        max_outstanding: u64,
+    ) -> Result<Option<usize>, LockupError> {
+        let idx = self.index_of(entry)?;
+        if let Some(idx) = idx {
+            let (_, outstanding) = self.get_meta_at(idx)?;
+            self.set_meta_at(idx, max_outstanding, outstanding)?;
+        }
+        Ok(idx)
+    }
+
     fn index_of(&self, e: &WhitelistEntry) -> Result<Option<usize>, LockupError> {
-        for k in (0..Whitelist::SIZE).step_by(Whitelist::ITEM_SIZE) {
-            let curr_idx = k / Whitelist::ITEM_SIZE;
-            let entry = &self.get_at(curr_idx)?;
+        for idx in 0..Whitelist::LEN {
+            let entry = &self.get_at(idx)?;
             if entry == e {
-                return Ok(Some(k));
+                return Ok(Some(idx));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Returns the index of the entry representing the given derived
+    /// address. If no such entry exists, returns Ok(None).
+    pub fn index_of_derived(&self, derived: &Pubkey) -> Result<Option<usize>, LockupError> {
+        let zero = WhitelistEntry::zero();
+        for idx in 0..Whitelist::LEN {
+            let entry = self.get_at(idx)?;
+            if entry != zero && &entry.derived_address()? == derived {
+                return Ok(Some(idx));
             }
         }
         Ok(None)
@@ -99,14 +202,55 @@ impl<'a> Whitelist<'a> {
     /// Returns the entry representing the given derived address. If no such
     /// entry exists, returns Ok(None).
     pub fn get_derived(&self, derived: &Pubkey) -> Result<Option<WhitelistEntry>, LockupError> {
-        for k in (0..Whitelist::SIZE).step_by(Whitelist::ITEM_SIZE) {
-            let curr_idx = k / Whitelist::ITEM_SIZE;
-            let entry = self.get_at(curr_idx)?;
-            if &entry.derived_address()? == derived {
-                return Ok(Some(entry));
+        match self.index_of_derived(derived)? {
+            None => Ok(None),
+            Some(idx) => self.get_at(idx).map(Some),
+        }
+    }
+
+    /// Records `amount` as sent to the entry at the given index, erroring if
+    /// it would exceed the entry's max outstanding amount.
+    pub fn add_outstanding(&self, index: usize, amount: u64) -> Result<(), LockupError> {
+        if !self.has_metadata()? {
+            return Ok(());
+        }
+        let (max_outstanding, outstanding) = self.get_meta_at(index)?;
+        let outstanding = outstanding
+            .checked_add(amount)
+            .ok_or(LockupErrorCode::Overflow)?;
+        if outstanding > max_outstanding {
+            return Err(LockupErrorCode::WhitelistLimitExceeded)?;
+        }
+        self.set_meta_at(index, max_outstanding, outstanding)
+    }
+
+    /// Records `amount` as returned from the entry at the given index.
+    pub fn sub_outstanding(&self, index: usize, amount: u64) -> Result<(), LockupError> {
+        if !self.has_metadata()? {
+            return Ok(());
+        }
+        let (max_outstanding, outstanding) = self.get_meta_at(index)?;
+        self.set_meta_at(index, max_outstanding, outstanding.saturating_sub(amount))
+    }
+
+    /// Returns all non-empty entries along with their metadata.
+    pub fn items(&self) -> Result<Vec<WhitelistItem>, LockupError> {
+        let zero = WhitelistEntry::zero();
+        let mut items = vec![];
+        for index in 0..Whitelist::LEN {
+            let entry = self.get_at(index)?;
+            if entry == zero {
+                continue;
             }
+            let (max_outstanding, outstanding) = self.get_meta_at(index)?;
+            items.push(WhitelistItem {
+                index,
+                entry,
+                max_outstanding,
+                outstanding,
+            });
         }
-        Ok(None)
+        Ok(items)
     }
 
     /// Returns true if a WhitelistEntry representing the given derived address
@@ -116,12 +260,24 @@ impl<'a> Whitelist<'a> {
     }
 }
 
+/// WhitelistItem is a view of a single occupied slot in the Whitelist, for
+/// auditing where locked funds may flow.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhitelistItem {
+    pub index: usize,
+    pub entry: WhitelistEntry,
+    /// Max amount that may be sent to the entry at any given time.
+    pub max_outstanding: u64,
+    /// Amount sent to the entry and not yet returned.
+    pub outstanding: u64,
+}
+
 /// WhitelistEntry consists of the components required to generate a program-
 /// derived address: program-id and the signer seeds. The signer seeds are
 /// assumed to be an additional pubkey and a nonce.
 ///
 /// We store this rather than the derived address for inspectibility.
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, Pack)]
 pub struct WhitelistEntry {
     program_id: Pubkey,
     instance: Pubkey,
@@ -161,5 +317,3 @@ impl WhitelistEntry {
         }
     }
 }
-
-serum_common::packable!(WhitelistEntry);
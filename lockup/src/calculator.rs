@@ -0,0 +1,122 @@
+//! Off-chain calculator for the amounts vested in a Vesting account,
+//! mirroring the math the program uses on Redeem.
+
+use crate::accounts::{Vesting, VestingSchedule};
+
+/// Amounts of a Vesting account's deposit as of a given point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VestingAmounts {
+    /// Total amount of the deposit vested, including anything already
+    /// redeemed.
+    pub vested: u64,
+    /// Amount of the deposit not yet vested.
+    pub locked: u64,
+    /// Amount the beneficiary can redeem right now.
+    pub withdrawable: u64,
+}
+
+/// Returns the amounts for `vesting` as of the given slot and unix timestamp.
+///
+/// Accounts created with CreateVesting release by slot, so only `slot` is
+/// used for them. Accounts with a VestingSchedule release by unix timestamp,
+/// so only `unix_ts` is used, and `schedule` must be given or this panics.
+pub fn vesting_amounts(
+    vesting: &Vesting,
+    schedule: Option<&VestingSchedule>,
+    slot: u64,
+    unix_ts: i64,
+) -> VestingAmounts {
+    let (vested, withdrawable) = match (vesting.has_schedule(), schedule) {
+        (true, Some(schedule)) => (
+            schedule.total_vested(vesting.start_balance, unix_ts),
+            vesting.available_for_withdrawal_with_schedule(schedule, unix_ts),
+        ),
+        (true, None) => panic!("a scheduled Vesting account requires its VestingSchedule"),
+        // Nothing is vested before the account exists.
+        (false, _) if slot < vesting.start_slot => (0, 0),
+        (false, _) => (
+            vesting.total_vested(slot),
+            vesting.available_for_withdrawal(slot),
+        ),
+    };
+    VestingAmounts {
+        vested,
+        locked: vesting.start_balance - vested,
+        withdrawable,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accounts::Unlock;
+
+    #[test]
+    fn linear() {
+        let vesting = Vesting {
+            initialized: true,
+            balance: 90,
+            start_balance: 100,
+            start_slot: 10,
+            end_slot: 20,
+            period_count: 2,
+            ..Default::default()
+        };
+        let at = |slot| vesting_amounts(&vesting, None, slot, 0);
+        assert_eq!(
+            at(0),
+            VestingAmounts {
+                vested: 0,
+                locked: 100,
+                withdrawable: 0
+            }
+        );
+        assert_eq!(
+            at(15),
+            VestingAmounts {
+                vested: 50,
+                locked: 50,
+                withdrawable: 40
+            }
+        );
+        assert_eq!(
+            at(20),
+            VestingAmounts {
+                vested: 100,
+                locked: 0,
+                withdrawable: 90
+            }
+        );
+    }
+
+    #[test]
+    fn scheduled() {
+        let vesting = Vesting {
+            initialized: true,
+            balance: 100,
+            start_balance: 100,
+            whitelist_owned: 80,
+            ..Default::default()
+        };
+        let schedule = VestingSchedule {
+            initialized: true,
+            unlocks: vec![
+                Unlock { ts: 10, bps: 5_000 },
+                Unlock {
+                    ts: 20,
+                    bps: 10_000,
+                },
+            ],
+            ..Default::default()
+        };
+        // The whitelisted programs hold all but 20 of the balance.
+        assert_eq!(
+            vesting_amounts(&vesting, Some(&schedule), 0, 10),
+            VestingAmounts {
+                vested: 50,
+                locked: 50,
+                withdrawable: 20
+            }
+        );
+    }
+}
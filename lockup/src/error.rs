@@ -1,4 +1,4 @@
-use num_enum::IntoPrimitive;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 use solana_client_gen::solana_sdk::program_error::ProgramError;
 use thiserror::Error;
 
@@ -10,7 +10,7 @@ pub enum LockupError {
     ErrorCode(#[from] LockupErrorCode),
 }
 
-#[derive(Debug, IntoPrimitive, Clone, Copy)]
+#[derive(Debug, IntoPrimitive, TryFromPrimitive, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum LockupErrorCode {
     WrongSerialization = 0,
@@ -68,9 +68,104 @@ pub enum LockupErrorCode {
     InvalidWhitelistEntry = 55,
     WhitelistInvalidProgramId = 56,
     WhitelistEntryAlreadyExists = 57,
+    InvalidSchedule = 58,
+    WrongVestingSchedule = 59,
+    NotRevocable = 60,
+    InvalidSplitAmount = 61,
+    InvalidBeneficiaryTransfer = 62,
+    Overflow = 63,
+    WhitelistLimitExceeded = 64,
+    FullyVested = 65,
+    InvalidFreezeAuthority = 66,
+    Underflow = 67,
     Unknown = 1000,
 }
 
+#[cfg(any(feature = "client", feature = "wasm"))]
+impl LockupErrorCode {
+    /// Describes the error for users of a client.
+    pub fn message(&self) -> &'static str {
+        match self {
+            LockupErrorCode::WrongSerialization => "account data failed to serialize",
+            LockupErrorCode::NotRentExempt => "account is not rent exempt",
+            LockupErrorCode::AlreadyInitialized => "account is already initialized",
+            LockupErrorCode::NotInitialized => "account is not initialized",
+            LockupErrorCode::NotOwnedByProgram => "account is not owned by the lockup program",
+            LockupErrorCode::VestingAccountDataInvalid => "invalid vesting account data",
+            LockupErrorCode::WrongCoinMint => "wrong token mint",
+            LockupErrorCode::SafeDataInvalid => "invalid safe account data",
+            LockupErrorCode::NotSignedByAuthority => "safe authority didn't sign",
+            LockupErrorCode::WrongNumberOfAccounts => "wrong number of accounts",
+            LockupErrorCode::InsufficientMintBalance => "insufficient mint balance",
+            LockupErrorCode::Unauthorized => "missing or wrong signer",
+            LockupErrorCode::MintAlreadyInitialized => "mint is already initialized",
+            LockupErrorCode::ReceiptAlreadyInitialized => "receipt is already initialized",
+            LockupErrorCode::InvalidAccount => "invalid account",
+            LockupErrorCode::InvalidVault => "wrong safe vault",
+            LockupErrorCode::InvalidVaultNonce => "invalid vault nonce",
+            LockupErrorCode::InvalidReceipt => "invalid receipt",
+            LockupErrorCode::AlreadyBurned => "receipt is already burned",
+            LockupErrorCode::InvalidAccountOwner => "account has the wrong owner",
+            LockupErrorCode::UnauthorizedReceipt => "receipt doesn't belong to the vesting account",
+            LockupErrorCode::TokenAccountAlreadyInitialized => {
+                "token account is already initialized"
+            }
+            LockupErrorCode::TokenAccountOwnerMismatch => "token account has the wrong owner",
+            LockupErrorCode::InvalidTokenProgram => "wrong token program",
+            LockupErrorCode::InvalidSerialization => "account data failed to deserialize",
+            LockupErrorCode::SizeNotAvailable => "account size is unknown",
+            LockupErrorCode::UnitializedTokenMint => "token mint is not initialized",
+            LockupErrorCode::InvalidSlot => "invalid slot",
+            LockupErrorCode::InvalidClock => "invalid clock",
+            LockupErrorCode::InvalidRentSysvar => "wrong rent sysvar account",
+            LockupErrorCode::InvalidMint => "wrong mint",
+            LockupErrorCode::WrongSafe => "vesting account belongs to another safe",
+            LockupErrorCode::WrongVestingAccount => "wrong vesting account",
+            LockupErrorCode::InvalidDepositAmount => "deposit amount must be positive",
+            LockupErrorCode::InvalidPeriod => "invalid vesting period",
+            LockupErrorCode::InsufficientWithdrawalBalance => "not enough vested to withdraw",
+            LockupErrorCode::WhitelistAlreadyInitialized => "whitelist is already initialized",
+            LockupErrorCode::AlreadyClaimed => "vesting account is already claimed",
+            LockupErrorCode::InvalidTokenAccountOwner => "token account has the wrong owner",
+            LockupErrorCode::InvalidTokenAccountMint => "token account has the wrong mint",
+            LockupErrorCode::WhitelistFull => "whitelist is full",
+            LockupErrorCode::WhitelistNotFound => "program is not whitelisted",
+            LockupErrorCode::WhitelistDepositInvariantViolation => {
+                "whitelisted program returned more than was withdrawn"
+            }
+            LockupErrorCode::InvalidRedemptionMint => "wrong redemption mint",
+            LockupErrorCode::InvalidWhitelist => "wrong whitelist account",
+            LockupErrorCode::InvalidMintAuthority => "mint has the wrong authority",
+            LockupErrorCode::InvalidMintSupply => "mint supply must be zero",
+            LockupErrorCode::NotYetClaimed => "vesting account is not yet claimed",
+            LockupErrorCode::InvalidClockSysvar => "wrong clock sysvar account",
+            LockupErrorCode::InsufficientWhitelistBalance => {
+                "not enough balance in whitelisted programs"
+            }
+            LockupErrorCode::WhitelistProgramWrongOwner => "whitelist has the wrong owner",
+            LockupErrorCode::WhitelistInvalidData => "invalid whitelist data",
+            LockupErrorCode::InvalidWhitelistEntry => "invalid whitelist entry",
+            LockupErrorCode::WhitelistInvalidProgramId => "wrong whitelisted program",
+            LockupErrorCode::WhitelistEntryAlreadyExists => "whitelist entry already exists",
+            LockupErrorCode::InvalidSchedule => "invalid vesting schedule",
+            LockupErrorCode::WrongVestingSchedule => "wrong vesting schedule account",
+            LockupErrorCode::NotRevocable => "vesting account is not revocable",
+            LockupErrorCode::InvalidSplitAmount => "invalid split amount",
+            LockupErrorCode::InvalidBeneficiaryTransfer => {
+                "beneficiary transfer account doesn't match the vesting account"
+            }
+            LockupErrorCode::Overflow => "amount overflowed",
+            LockupErrorCode::WhitelistLimitExceeded => "amount exceeds the whitelist entry's limit",
+            LockupErrorCode::FullyVested => "vesting account is fully vested",
+            LockupErrorCode::InvalidFreezeAuthority => {
+                "revocable vesting's mint must be freezable by the vault authority"
+            }
+            LockupErrorCode::Underflow => "amount exceeds the balance",
+            LockupErrorCode::Unknown => "unknown error",
+        }
+    }
+}
+
 impl std::fmt::Display for LockupErrorCode {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         <Self as std::fmt::Debug>::fmt(self, fmt)
@@ -0,0 +1,70 @@
+//! Events logged by the lockup program with `serum_common::emit_event!`.
+
+use serum_common::event::Event;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// Tokens deposited into a vesting account, on creation or top up.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Deposited {
+    pub vesting: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    /// The vesting account's balance after the deposit.
+    pub balance: u64,
+}
+
+impl Event for Deposited {
+    const NAME: &'static str = "Deposited";
+}
+
+/// The locked token receipts of a vesting account were minted to the
+/// beneficiary.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Claimed {
+    pub vesting: Pubkey,
+    pub beneficiary: Pubkey,
+    pub token: Pubkey,
+    pub amount: u64,
+}
+
+impl Event for Claimed {
+    const NAME: &'static str = "Claimed";
+}
+
+/// Vested tokens withdrawn by the beneficiary.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Redeemed {
+    pub vesting: Pubkey,
+    pub beneficiary: Pubkey,
+    pub amount: u64,
+    /// The vesting account's balance after the withdrawal.
+    pub balance: u64,
+}
+
+impl Event for Redeemed {
+    const NAME: &'static str = "Redeemed";
+}
+
+/// Locked tokens sent to a whitelisted program, e.g., to stake them.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WhitelistWithdrawn {
+    pub vesting: Pubkey,
+    pub program: Pubkey,
+    pub amount: u64,
+}
+
+impl Event for WhitelistWithdrawn {
+    const NAME: &'static str = "WhitelistWithdrawn";
+}
+
+/// Locked tokens returned by a whitelisted program.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct WhitelistDeposited {
+    pub vesting: Pubkey,
+    pub program: Pubkey,
+    pub amount: u64,
+}
+
+impl Event for WhitelistDeposited {
+    const NAME: &'static str = "WhitelistDeposited";
+}
@@ -7,12 +7,21 @@ use serum_common::pack::*;
 use solana_client_gen::prelude::*;
 
 pub mod accounts;
+#[cfg(any(feature = "client", feature = "wasm"))]
+pub mod calculator;
 pub mod error;
+pub mod events;
 
-#[cfg_attr(feature = "client", solana_client_gen)]
+#[cfg(feature = "client")]
+use error::LockupErrorCode;
+
+#[cfg_attr(
+    any(feature = "client", feature = "wasm"),
+    solana_client_gen(typescript, error(LockupErrorCode))
+)]
 pub mod instruction {
     use super::*;
-    #[derive(Serialize, Deserialize)]
+    #[derive(Serialize, Deserialize, Pack)]
     pub enum LockupInstruction {
         /// Initializes a safe instance for use.
         ///
@@ -37,6 +46,10 @@ pub mod instruction {
         /// from the controlling token account to one owned by the SrmSafe
         /// program. Anyone with funds to deposit can invoke this instruction.
         ///
+        /// The depositor authority only needs to be a signer, so it can be a
+        /// program derived address (e.g., a multisig or DAO treasury) when
+        /// this instruction is invoked via CPI with `invoke_signed`.
+        ///
         /// Accounts:
         ///
         /// 0. `[writable]  Vesting account representing this deposit.
@@ -102,6 +115,8 @@ pub mod instruction {
         /// 7. `[writable]` NFT mint to burn the token being redeemed.
         /// 8. `[]`         SPL token program.
         /// 9. `[]`         Clock sysvar.
+        /// 10. `[]`        VestingSchedule, iff the Vesting account was
+        ///                 created with CreateScheduledVesting.
         Redeem { amount: u64 },
         /// Invokes an opaque instruction on a whitelisted program,
         /// giving it delegate access to send `amount` funds to itself.
@@ -117,7 +132,8 @@ pub mod instruction {
         /// 2. `[]`         Safe (containing the nonce).
         /// 3. `[]`         Safe vault authority.
         /// 4. `[]`         Whitelisted program to invoke.
-        /// 5. `[]`         Whitelist.
+        /// 5. `[writable]` Whitelist, tracking the amount outstanding with
+        ///                 the whitelisted program.
         ///
         /// All accounts below will be relayed to the whitelisted program.
         ///
@@ -143,7 +159,8 @@ pub mod instruction {
         ///
         /// Same as WhitelistWithdraw.
         WhitelistDeposit { instruction_data: Vec<u8> },
-        /// Adds the given entry to the whitelist.
+        /// Adds the given entry to the whitelist, with no limit on the amount
+        /// outstanding.
         ///
         /// Accounts:
         ///
@@ -186,7 +203,295 @@ pub mod instruction {
         /// 4. `[writable]` Token account to receive the new tokens.
         /// 5. `[]`         SPL token program.
         Migrate,
+        /// CreateScheduledVesting is CreateVesting with an explicit release
+        /// schedule in place of the built-in linear one, e.g., to represent
+        /// a one year cliff followed by four years of monthly unlocks.
+        ///
+        /// Accounts:
+        ///
+        /// Same as CreateVesting, with the addition of
+        ///
+        /// 10. `[writable]` VestingSchedule to initialize, at the address
+        ///                  given by `VestingSchedule::address` for
+        ///                  Accounts[0].
+        ///
+        /// If there's a revoker, the freeze authority of the receipt mint
+        /// must be the Safe's vault authority, so that Revoke can freeze the
+        /// receipts.
+        CreateScheduledVesting {
+            /// The beneficiary of the vesting account.
+            beneficiary: Pubkey,
+            /// Unix timestamp before which nothing is vested.
+            cliff_ts: i64,
+            /// Unlock points, sorted by timestamp, with cumulative fractions
+            /// in basis points ending at `VestingSchedule::BPS_TOTAL`.
+            unlocks: Vec<crate::accounts::Unlock>,
+            /// Authority allowed to revoke the unvested part of the deposit.
+            /// None for a grant that can't be revoked.
+            revoker: Option<Pubkey>,
+            /// The amount to deposit into the vesting account.
+            deposit_amount: u64,
+        },
+        /// Revoke ends vesting on a revocable scheduled Vesting account,
+        /// sending the unvested tokens to the given token account. Tokens
+        /// vested so far stay redeemable by the beneficiary, and the
+        /// schedule's revoker is cleared.
+        ///
+        /// Fails if the unvested tokens aren't in the Safe's vault, i.e.,
+        /// they've been sent to a whitelisted program.
+        ///
+        /// If the Vesting account has been claimed, the beneficiary's
+        /// receipt token account is frozen, so the receipts of the revoked
+        /// tokens can't be moved. Redeem thaws it around the burn. Receipts
+        /// already moved elsewhere stay transferable, but can't redeem more
+        /// than the remaining balance.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   VestingSchedule revoker.
+        /// 1. `[writable]` Vesting account.
+        /// 2. `[writable]` VestingSchedule of the Vesting account.
+        /// 3. `[]`         Safe instance.
+        /// 4. `[writable]` Safe's token vault.
+        /// 5. `[]`         Safe's vault authority, a program derived address.
+        /// 6. `[writable]` Token account receiving the unvested tokens.
+        /// 7. `[]`         SPL token program.
+        /// 8. `[]`         Clock sysvar.
+        ///
+        /// If the Vesting account has been claimed:
+        ///
+        /// 9.  `[writable]` Beneficiary's receipt token account, i.e.,
+        ///                  `Vesting::locked_nft_token`.
+        /// 10. `[]`         Receipt token mint of the Vesting account.
+        Revoke,
+        /// SplitVesting moves `amount` of a Vesting account's balance into
+        /// a new Vesting account for `new_beneficiary`, along with the same
+        /// fraction of its starting balance, so that both accounts keep
+        /// vesting on the original schedule. Tokens sent to whitelisted
+        /// programs can't be split off.
+        ///
+        /// The new account is unclaimed. If the original account has been
+        /// claimed, `amount` of its receipts are burned. If the schedule is
+        /// revocable, the new mint's freeze authority must be the Safe's
+        /// vault authority.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Vesting account beneficiary.
+        /// 1. `[writable]` Vesting account to split.
+        /// 2. `[writable]` New Vesting account.
+        /// 3. `[]`         Safe instance.
+        /// 4. `[writable]` Token mint for the new account's receipt.
+        /// 5. `[]`         Safe's vault authority, a program derived address.
+        ///                 The mint authority.
+        /// 6. `[]`         SPL token program.
+        /// 7. `[]`         Rent sysvar.
+        ///
+        /// If the Vesting account has been claimed:
+        ///
+        /// .. `[writable]` Beneficiary's receipt token account.
+        /// .. `[writable]` Receipt token mint of the Vesting account.
+        ///
+        /// If the Vesting account has a VestingSchedule:
+        ///
+        /// .. `[]`         VestingSchedule of the Vesting account.
+        /// .. `[writable]` VestingSchedule to initialize for the new
+        ///                 account, at the address given by
+        ///                 `VestingSchedule::address` for Accounts[2].
+        SplitVesting {
+            /// Amount of the balance to move to the new account.
+            amount: u64,
+            /// Beneficiary of the new account.
+            new_beneficiary: Pubkey,
+        },
+        /// Proposes handing a Vesting account to a new beneficiary, who must
+        /// accept before the change takes effect. Whitelisted balances are
+        /// unaffected.
+        ///
+        /// If the account has been claimed, its receipts are burned and the
+        /// account becomes unclaimed, so that the new beneficiary can claim
+        /// them after accepting. Claiming again before then makes the
+        /// transfer stale. Should be run in the same transaction as the
+        /// create_account instruction.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` BeneficiaryTransfer to initialize.
+        /// 1. `[writable]` Vesting account.
+        /// 2. `[signer]`   Vesting account beneficiary.
+        /// 3. `[]`         Safe instance.
+        /// 4. `[]`         Safe's vault authority, a program derived address.
+        /// 5. `[]`         SPL token program.
+        /// 6. `[]`         Rent sysvar.
+        ///
+        /// If the Vesting account has been claimed:
+        ///
+        /// 7. `[writable]` Beneficiary's receipt token account.
+        /// 8. `[writable]` Receipt token mint.
+        #[cfg_attr(
+            feature = "client",
+            create_account(crate::accounts::BeneficiaryTransfer::SIZE)
+        )]
+        InitiateBeneficiaryTransfer { new_beneficiary: Pubkey },
+        /// Completes a BeneficiaryTransfer, closing it.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]`         BeneficiaryTransfer.
+        /// 1. `[writable]`         Vesting account.
+        /// 2. `[writable, signer]` New beneficiary, receiving the transfer
+        ///                         account's lamports.
+        AcceptBeneficiaryTransfer,
+        /// Closes a BeneficiaryTransfer without applying it. The Vesting
+        /// account stays unclaimed until the beneficiary claims it again.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]`         BeneficiaryTransfer.
+        /// 1. `[writable, signer]` Beneficiary that initiated the transfer,
+        ///                         receiving the transfer account's lamports.
+        CancelBeneficiaryTransfer,
+        /// Adds the given entries to the whitelist, each with the max amount
+        /// that may be outstanding with it at any given time. A limit of
+        /// `u64::MAX` is unlimited, and the only one allowed for whitelists
+        /// created without a metadata region.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signed]`   Safe authority.
+        /// 1. `[]`         Safe account.
+        /// 2. `[writable]` Whitelist.
+        WhitelistAddMany {
+            /// (entry, max outstanding) pairs.
+            entries: Vec<(crate::accounts::WhitelistEntry, u64)>,
+        },
+        /// Removes the given entries from the whitelist.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signed]`   Safe authority.
+        /// 1. `[]`         Safe account.
+        /// 2. `[writable]` Whitelist.
+        WhitelistRemoveMany {
+            entries: Vec<crate::accounts::WhitelistEntry>,
+        },
+        /// Same as CreateScheduledVesting, with the deposit streamed to the
+        /// beneficiary, accruing linearly per second from `start_ts` to
+        /// `end_ts`.
+        ///
+        /// Accounts:
+        ///
+        /// Same as CreateScheduledVesting.
+        CreateStreamingVesting {
+            /// The beneficiary of the vesting account.
+            beneficiary: Pubkey,
+            /// Unix timestamp at which the stream starts.
+            start_ts: i64,
+            /// Unix timestamp at which the entire deposit is vested.
+            end_ts: i64,
+            /// Authority allowed to revoke the unvested part of the deposit.
+            /// None for a stream that can't be revoked.
+            revoker: Option<Pubkey>,
+            /// The amount to deposit into the vesting account.
+            deposit_amount: u64,
+        },
+        /// Sets the max amount that may be outstanding with an existing
+        /// whitelist entry, keeping the amount currently outstanding.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signed]`   Safe authority.
+        /// 1. `[]`         Safe account.
+        /// 2. `[writable]` Whitelist.
+        WhitelistSetMaxOutstanding {
+            entry: crate::accounts::WhitelistEntry,
+            max_outstanding: u64,
+        },
+        /// Replaces the linear release of a Vesting account created with
+        /// CreateVesting, optionally topping it up with a new deposit. The
+        /// new release applies to the whole account and can't end before
+        /// the old one.
+        ///
+        /// Both the beneficiary and the grantor must sign. The grantor is
+        /// the safe authority.
+        ///
+        /// Accounts:
+        ///
+        /// 0.  `[signer]`   Vesting account beneficiary.
+        /// 1.  `[signer]`   Grantor.
+        /// 2.  `[writable]` Vesting account.
+        /// 3.  `[]`         Safe instance.
+        /// 4.  `[writable]` Depositor token account.
+        /// 5.  `[signer]`   The authority||owner||delegate of Accounts[4].
+        ///                  Only needs to sign if `deposit_amount` is nonzero.
+        /// 6.  `[writable]` Safe's token vault.
+        /// 7.  `[]`         Safe's vault authority, a program derived address.
+        /// 8.  `[]`         SPL token program.
+        /// 9.  `[]`         Clock sysvar.
+        ///
+        /// If the Vesting account has been claimed:
+        ///
+        /// 10. `[writable]` Beneficiary's receipt token account.
+        /// 11. `[writable]` Receipt token mint.
+        RelockVesting {
+            /// The new slot at which the entire account is vested.
+            end_slot: u64,
+            /// The new number of vesting periods.
+            period_count: u64,
+            /// The amount to add to the vesting account.
+            deposit_amount: u64,
+        },
+        /// Same as RelockVesting, replacing the unlock points of a Vesting
+        /// account with a VestingSchedule. The schedule keeps its revoker
+        /// and streaming mode. If the schedule has a revoker, the revoker is
+        /// the grantor.
+        ///
+        /// Accounts:
+        ///
+        /// Same as RelockVesting, with the VestingSchedule given as
+        /// `[writable]` Accounts[10], before the receipt accounts.
+        RelockScheduledVesting {
+            /// Unix timestamp before which nothing is vested.
+            cliff_ts: i64,
+            /// Unlock points, sorted by timestamp, with cumulative fractions
+            /// in basis points ending at `VestingSchedule::BPS_TOTAL`.
+            unlocks: Vec<crate::accounts::Unlock>,
+            /// The amount to add to the vesting account.
+            deposit_amount: u64,
+        },
+        /// Tops up a Vesting account with a new deposit that vests over what
+        /// remains of the account's release, linear or scheduled. The
+        /// starting balance is rescaled so that each remaining period, or
+        /// unlock, releases its share of the deposit, and the amount
+        /// available for redemption now is unchanged. Fails once the account
+        /// is fully vested.
+        ///
+        /// Only the grantor can deposit: the revoker of a revocable grant,
+        /// and the safe authority otherwise.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Grantor.
+        /// 1. `[writable]` Vesting account.
+        /// 2. `[]`         Safe instance.
+        /// 3. `[writable]` Depositor token account.
+        /// 4. `[signer]`   The authority||owner||delegate of Accounts[3].
+        /// 5. `[writable]` Safe's token vault.
+        /// 6. `[]`         Safe's vault authority, a program derived address.
+        /// 7. `[]`         SPL token program.
+        /// 8. `[]`         Clock sysvar.
+        ///
+        /// If the Vesting account has a VestingSchedule:
+        ///
+        /// .. `[]`         VestingSchedule of the Vesting account.
+        ///
+        /// If the Vesting account has been claimed:
+        ///
+        /// .. `[writable]` Beneficiary's receipt token account.
+        /// .. `[writable]` Receipt token mint.
+        DepositToVesting {
+            /// The amount to add to the vesting account.
+            deposit_amount: u64,
+        },
     }
 }
-
-serum_common::packable!(instruction::LockupInstruction);
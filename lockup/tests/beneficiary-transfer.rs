@@ -0,0 +1,149 @@
+use common::lifecycle;
+use rand::rngs::OsRng;
+use serum_common::client::rpc;
+use serum_lockup_client::*;
+use solana_client_gen::prelude::*;
+use spl_token::state::Account as TokenAccount;
+
+mod common;
+
+#[test]
+fn beneficiary_transfer() {
+    // Given.
+    //
+    // A claimed vesting account.
+    let lifecycle::Deposited {
+        client,
+        vesting_acc_beneficiary,
+        vesting_acc,
+        safe_acc,
+        deposit_amount,
+        ..
+    } = lifecycle::deposit_with_schedule(100, 100_000, 10);
+    let vesting = client.vesting(&vesting_acc).unwrap();
+    let nft_tok_acc = rpc::create_token_account(
+        client.rpc(),
+        &vesting.locked_nft_mint,
+        &vesting_acc_beneficiary.pubkey(),
+        client.payer(),
+    )
+    .unwrap();
+    client
+        .claim(ClaimRequest {
+            beneficiary: &vesting_acc_beneficiary,
+            safe: safe_acc,
+            vesting: vesting_acc,
+            locked_mint: vesting.locked_nft_mint,
+            locked_token_account: nft_tok_acc.pubkey(),
+        })
+        .unwrap();
+
+    // When.
+    //
+    // The beneficiary initiates a transfer.
+    let new_beneficiary = Keypair::generate(&mut OsRng);
+    let InitiateBeneficiaryTransferResponse { transfer, .. } = client
+        .initiate_beneficiary_transfer(InitiateBeneficiaryTransferRequest {
+            beneficiary: &vesting_acc_beneficiary,
+            vesting: vesting_acc,
+            new_beneficiary: new_beneficiary.pubkey(),
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // The receipts are burned and the account is unclaimed.
+    {
+        let nft = rpc::account_token_unpacked::<TokenAccount>(client.rpc(), &nft_tok_acc.pubkey());
+        assert_eq!(nft.amount, 0);
+        let vesting = client.vesting(&vesting_acc).unwrap();
+        assert_eq!(vesting.beneficiary, vesting_acc_beneficiary.pubkey());
+        assert_eq!(vesting.claimed, false);
+    }
+
+    // When.
+    //
+    // The new beneficiary accepts.
+    client
+        .accept_beneficiary_transfer(AcceptBeneficiaryTransferRequest {
+            new_beneficiary: &new_beneficiary,
+            transfer,
+            vesting: vesting_acc,
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // The vesting account is theirs, the transfer is closed, and they can
+    // claim the receipts.
+    {
+        let vesting = client.vesting(&vesting_acc).unwrap();
+        assert_eq!(vesting.beneficiary, new_beneficiary.pubkey());
+        assert!(client.rpc().get_account(&transfer).is_err());
+        let new_nft_tok_acc = rpc::create_token_account(
+            client.rpc(),
+            &vesting.locked_nft_mint,
+            &new_beneficiary.pubkey(),
+            client.payer(),
+        )
+        .unwrap();
+        client
+            .claim(ClaimRequest {
+                beneficiary: &new_beneficiary,
+                safe: safe_acc,
+                vesting: vesting_acc,
+                locked_mint: vesting.locked_nft_mint,
+                locked_token_account: new_nft_tok_acc.pubkey(),
+            })
+            .unwrap();
+        let nft =
+            rpc::account_token_unpacked::<TokenAccount>(client.rpc(), &new_nft_tok_acc.pubkey());
+        assert_eq!(nft.amount, deposit_amount);
+    }
+}
+
+#[test]
+fn cancel_beneficiary_transfer() {
+    // Given.
+    //
+    // A pending transfer.
+    let lifecycle::Deposited {
+        client,
+        vesting_acc_beneficiary,
+        vesting_acc,
+        ..
+    } = lifecycle::deposit_with_schedule(100, 100_000, 10);
+    let new_beneficiary = Keypair::generate(&mut OsRng);
+    let InitiateBeneficiaryTransferResponse { transfer, .. } = client
+        .initiate_beneficiary_transfer(InitiateBeneficiaryTransferRequest {
+            beneficiary: &vesting_acc_beneficiary,
+            vesting: vesting_acc,
+            new_beneficiary: new_beneficiary.pubkey(),
+        })
+        .unwrap();
+
+    // When.
+    //
+    // The beneficiary cancels it.
+    client
+        .cancel_beneficiary_transfer(CancelBeneficiaryTransferRequest {
+            beneficiary: &vesting_acc_beneficiary,
+            transfer,
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // It can no longer be accepted.
+    {
+        assert!(client
+            .accept_beneficiary_transfer(AcceptBeneficiaryTransferRequest {
+                new_beneficiary: &new_beneficiary,
+                transfer,
+                vesting: vesting_acc,
+            })
+            .is_err());
+        let vesting = client.vesting(&vesting_acc).unwrap();
+        assert_eq!(vesting.beneficiary, vesting_acc_beneficiary.pubkey());
+    }
+}
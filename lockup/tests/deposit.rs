@@ -0,0 +1,70 @@
+use common::lifecycle::{self, Initialized};
+use rand::rngs::OsRng;
+use serum_lockup::accounts::{Unlock, VestingSchedule};
+use serum_lockup_client::*;
+use solana_client_gen::prelude::*;
+
+mod common;
+
+#[test]
+fn deposit_to_scheduled_vesting() {
+    // Given.
+    //
+    // An irrevocable scheduled vesting account, with nothing vested yet.
+    let Initialized {
+        client,
+        safe_acc,
+        safe_authority,
+        depositor,
+        ..
+    } = lifecycle::initialize();
+    let beneficiary = Keypair::generate(&mut OsRng);
+    let CreateVestingResponse { vesting, .. } = client
+        .create_scheduled_vesting(CreateScheduledVestingRequest {
+            depositor: depositor.pubkey(),
+            depositor_owner: client.payer(),
+            safe: safe_acc,
+            beneficiary: beneficiary.pubkey(),
+            cliff_ts: 0,
+            unlocks: vec![Unlock {
+                ts: i64::MAX,
+                bps: VestingSchedule::BPS_TOTAL,
+            }],
+            revoker: None,
+            deposit_amount: 100,
+        })
+        .unwrap();
+    let deposit = |grantor| {
+        client.deposit_to_vesting(DepositToVestingRequest {
+            grantor,
+            vesting,
+            depositor: depositor.pubkey(),
+            depositor_owner: client.payer(),
+            deposit_amount: 50,
+        })
+    };
+
+    // Then.
+    //
+    // Only the safe authority can top up an irrevocable grant.
+    {
+        let stranger = Keypair::generate(&mut OsRng);
+        assert!(deposit(&stranger).is_err());
+        assert!(deposit(&beneficiary).is_err());
+    }
+
+    // When.
+    //
+    // The grantor tops it up.
+    deposit(&safe_authority).unwrap();
+
+    // Then.
+    //
+    // The deposit joins the remaining schedule, which is all of it.
+    {
+        let vesting_acc = client.vesting(&vesting).unwrap();
+        assert_eq!(vesting_acc.start_balance, 150);
+        assert_eq!(vesting_acc.balance, 150);
+        assert_eq!(client.vault(&safe_acc).unwrap().amount, 150);
+    }
+}
@@ -0,0 +1,105 @@
+use serum_common::pack::Pack;
+use serum_common_tests::golden::assert_golden;
+use serum_lockup::accounts::{
+    BeneficiaryTransfer, Safe, Unlock, Vesting, VestingSchedule, WhitelistEntry,
+};
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+// Golden tests pin the serialized layout of every lockup account. If one of
+// these fails, either revert the layout change or write a migration and
+// regenerate the fixtures with `SERUM_UPDATE_GOLDEN=1`.
+
+#[test]
+fn safe() {
+    let safe = Safe {
+        initialized: true,
+        mint: key(1),
+        authority: key(2),
+        nonce: 3,
+        whitelist: key(4),
+        vault: key(5),
+    };
+    assert_golden(safe, fixture("safe"));
+}
+
+#[test]
+fn vesting() {
+    let vesting = Vesting {
+        initialized: true,
+        claimed: false,
+        safe: key(1),
+        beneficiary: key(2),
+        balance: 3,
+        start_balance: 4,
+        start_slot: 5,
+        end_slot: 6,
+        period_count: 7,
+        locked_nft_mint: key(8),
+        locked_nft_token: key(9),
+        whitelist_owned: 10,
+    };
+    assert_golden(vesting, fixture("vesting"));
+}
+
+#[test]
+fn vesting_schedule() {
+    let schedule = VestingSchedule {
+        initialized: true,
+        vesting: key(1),
+        cliff_ts: 2,
+        unlocks: vec![Unlock { ts: 3, bps: 5_000 }, Unlock { ts: 4, bps: 10_000 }],
+        revoker: Some(key(5)),
+        streaming: false,
+    };
+    assert_golden(schedule, fixture("vesting_schedule"));
+}
+
+#[test]
+fn beneficiary_transfer() {
+    let transfer = BeneficiaryTransfer {
+        initialized: true,
+        vesting: key(1),
+        beneficiary: key(2),
+        new_beneficiary: key(3),
+    };
+    assert_golden(transfer, fixture("beneficiary_transfer"));
+}
+
+#[test]
+fn whitelist_entry() {
+    let entry = WhitelistEntry::new(key(1), key(2), 3);
+    assert_golden(entry, fixture("whitelist_entry"));
+}
+
+// The DEX reads these offsets for fee discounts on locked SRM.
+#[test]
+fn vesting_offsets() {
+    let vesting = Vesting {
+        safe: key(1),
+        beneficiary: key(2),
+        balance: 3,
+        whitelist_owned: 4,
+        ..Default::default()
+    };
+    let mut bytes = vec![0; vesting.size().unwrap() as usize];
+    Vesting::pack(vesting, &mut bytes).unwrap();
+    let at = |offset: usize| &bytes[offset..offset + 32];
+    let u64_at = |offset: usize| {
+        let mut le = [0; 8];
+        le.copy_from_slice(&bytes[offset..offset + 8]);
+        u64::from_le_bytes(le)
+    };
+    assert_eq!(at(Vesting::SAFE_OFFSET), key(1).as_ref());
+    assert_eq!(at(Vesting::BENEFICIARY_OFFSET), key(2).as_ref());
+    assert_eq!(u64_at(Vesting::BALANCE_OFFSET), 3);
+    assert_eq!(u64_at(Vesting::WHITELIST_OWNED_OFFSET), 4);
+    assert_eq!(bytes.len(), Vesting::WHITELIST_OWNED_OFFSET + 8);
+}
+
+fn key(b: u8) -> Pubkey {
+    Pubkey::new(&[b; 32])
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}.hex", env!("CARGO_MANIFEST_DIR"), name)
+}
@@ -2,12 +2,10 @@ use common::blockchain;
 use common::lifecycle::Initialized;
 use rand::rngs::OsRng;
 use serum_common::client::rpc;
-use serum_common::pack::Pack;
 use serum_lockup::accounts::{Vesting, Whitelist, WhitelistEntry};
 use serum_lockup_client::*;
 use serum_lockup_test_stake::client::Client as StakeClient;
 use solana_client_gen::prelude::*;
-use solana_client_gen::solana_sdk::instruction::AccountMeta;
 use solana_client_gen::solana_sdk::program_option::COption;
 use solana_client_gen::solana_sdk::pubkey::Pubkey;
 use solana_client_gen::solana_sdk::signature::{Keypair, Signer};
@@ -148,19 +146,7 @@ fn lifecycle() {
     // Transfer funds from the safe to the whitelisted program.
     {
         // Instruction data to proxy to the whitelisted program.
-        let relay_data = {
-            let stake_instr = serum_lockup_test_stake::instruction::StakeInstruction::Stake {
-                amount: stake_amount,
-            };
-            let mut relay_data = vec![0; stake_instr.size().unwrap() as usize];
-            serum_lockup_test_stake::instruction::StakeInstruction::pack(
-                stake_instr,
-                &mut relay_data,
-            )
-            .unwrap();
-
-            relay_data
-        };
+        let relay_data = serum_lockup_test_stake::relay::stake_data(stake_amount);
         // Send tx.
         let _ = client.whitelist_withdraw(WhitelistWithdrawRequest {
             beneficiary: &expected_beneficiary,
@@ -172,7 +158,7 @@ fn lifecycle() {
             whitelist_vault_authority: stake_init.vault_authority,
             delegate_amount: stake_amount,
             relay_data,
-            relay_accounts: vec![AccountMeta::new(stake_init.instance, false)],
+            relay_accounts: stake_init.relay_accounts(),
         });
 
         // Checks.
@@ -199,18 +185,7 @@ fn lifecycle() {
     {
         let stake_withdraw = 95;
         // Relay tx data.
-        let relay_data = {
-            let stake_instr = serum_lockup_test_stake::instruction::StakeInstruction::Unstake {
-                amount: stake_withdraw,
-            };
-            let mut relay_data = vec![0; stake_instr.size().unwrap() as usize];
-            serum_lockup_test_stake::instruction::StakeInstruction::pack(
-                stake_instr,
-                &mut relay_data,
-            )
-            .unwrap();
-            relay_data
-        };
+        let relay_data = serum_lockup_test_stake::relay::unstake_data(stake_withdraw);
         // Send tx.
         let _ = client.whitelist_deposit(WhitelistDepositRequest {
             beneficiary: &expected_beneficiary,
@@ -221,7 +196,7 @@ fn lifecycle() {
             whitelist_vault: stake_init.vault,
             whitelist_vault_authority: stake_init.vault_authority,
             relay_data,
-            relay_accounts: vec![AccountMeta::new(stake_init.instance, false)],
+            relay_accounts: stake_init.relay_accounts(),
         });
 
         // Checks.
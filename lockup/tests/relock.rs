@@ -0,0 +1,132 @@
+use common::lifecycle::{self, Initialized};
+use rand::rngs::OsRng;
+use serum_lockup::accounts::{Unlock, VestingSchedule};
+use serum_lockup_client::*;
+use solana_client_gen::prelude::*;
+
+mod common;
+
+#[test]
+fn relock_scheduled_vesting() {
+    // Given.
+    //
+    // A revocable scheduled vesting account.
+    let Initialized {
+        client,
+        safe_acc,
+        depositor,
+        ..
+    } = lifecycle::initialize();
+    let beneficiary = Keypair::generate(&mut OsRng);
+    let revoker = Keypair::generate(&mut OsRng);
+    let CreateVestingResponse { vesting, .. } = client
+        .create_scheduled_vesting(CreateScheduledVestingRequest {
+            depositor: depositor.pubkey(),
+            depositor_owner: client.payer(),
+            safe: safe_acc,
+            beneficiary: beneficiary.pubkey(),
+            cliff_ts: 0,
+            unlocks: vec![Unlock {
+                ts: i64::MAX - 1,
+                bps: VestingSchedule::BPS_TOTAL,
+            }],
+            revoker: Some(revoker.pubkey()),
+            deposit_amount: 100,
+        })
+        .unwrap();
+    let relock = |grantor, unlocks: Vec<Unlock>| {
+        client.relock_vesting(RelockVestingRequest {
+            beneficiary: &beneficiary,
+            grantor,
+            vesting,
+            depositor: depositor.pubkey(),
+            depositor_owner: client.payer(),
+            relock: Relock::Scheduled {
+                cliff_ts: 0,
+                unlocks,
+            },
+            deposit_amount: 50,
+        })
+    };
+    let later = vec![Unlock {
+        ts: i64::MAX,
+        bps: VestingSchedule::BPS_TOTAL,
+    }];
+
+    // Then.
+    //
+    // Only the revoker can consent as the grantor, and the schedule can't
+    // end any earlier.
+    {
+        let stranger = Keypair::generate(&mut OsRng);
+        assert!(relock(&stranger, later.clone()).is_err());
+        let earlier = vec![Unlock {
+            ts: 1,
+            bps: VestingSchedule::BPS_TOTAL,
+        }];
+        assert!(relock(&revoker, earlier).is_err());
+    }
+
+    // When.
+    //
+    // Both sign to top it up under a later schedule.
+    relock(&revoker, later.clone()).unwrap();
+
+    // Then.
+    //
+    // The whole balance follows the new schedule.
+    {
+        let vesting_acc = client.vesting(&vesting).unwrap();
+        assert_eq!(vesting_acc.start_balance, 150);
+        assert_eq!(vesting_acc.balance, 150);
+        let schedule = client.vesting_schedule(&vesting).unwrap();
+        assert_eq!(schedule.unlocks, later);
+        assert_eq!(schedule.revoker, Some(revoker.pubkey()));
+    }
+}
+
+#[test]
+fn relock_linear_vesting_periods() {
+    // Given.
+    //
+    // A linear vesting account.
+    let lifecycle::Deposited {
+        client,
+        vesting_acc,
+        vesting_acc_beneficiary,
+        safe_authority,
+        end_slot,
+        ..
+    } = lifecycle::deposit_with_schedule(100, 100_000, 10);
+    let start_slot = client.vesting(&vesting_acc).unwrap().start_slot;
+    let relock = |period_count| {
+        client.relock_vesting(RelockVestingRequest {
+            beneficiary: &vesting_acc_beneficiary,
+            grantor: &safe_authority,
+            vesting: vesting_acc,
+            depositor: Keypair::generate(&mut OsRng).pubkey(),
+            depositor_owner: client.payer(),
+            relock: Relock::Linear {
+                end_slot,
+                period_count,
+            },
+            deposit_amount: 0,
+        })
+    };
+
+    // Then.
+    //
+    // A period can't be shorter than a slot.
+    assert!(relock(end_slot - start_slot + 1).is_err());
+
+    // When.
+    //
+    // Every slot is its own period.
+    relock(end_slot - start_slot).unwrap();
+
+    // Then.
+    //
+    // The account takes the new period count.
+    let vesting = client.vesting(&vesting_acc).unwrap();
+    assert_eq!(vesting.period_count, end_slot - start_slot);
+}
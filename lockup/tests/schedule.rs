@@ -0,0 +1,350 @@
+use common::lifecycle::{self, Initialized};
+use rand::rngs::OsRng;
+use serum_common::client::rpc;
+use serum_lockup::accounts::{Unlock, VestingSchedule};
+use serum_lockup_client::*;
+use solana_client_gen::prelude::*;
+use spl_token::state::{Account as TokenAccount, AccountState};
+
+mod common;
+
+#[test]
+fn scheduled_vesting() {
+    // Given.
+    //
+    // An initialized safe.
+    let Initialized {
+        client,
+        safe_acc,
+        safe_srm_vault,
+        srm_mint,
+        depositor,
+        ..
+    } = lifecycle::initialize();
+
+    // When.
+    //
+    // I create a vesting account with half unlocked in the past and the
+    // other half far in the future.
+    let beneficiary = Keypair::generate(&mut OsRng);
+    let deposit_amount = 100;
+    let unlocks = vec![
+        Unlock { ts: 1, bps: 5_000 },
+        Unlock {
+            ts: i64::MAX,
+            bps: VestingSchedule::BPS_TOTAL,
+        },
+    ];
+    let CreateVestingResponse { vesting, mint, .. } = client
+        .create_scheduled_vesting(CreateScheduledVestingRequest {
+            depositor: depositor.pubkey(),
+            depositor_owner: client.payer(),
+            safe: safe_acc,
+            beneficiary: beneficiary.pubkey(),
+            cliff_ts: 0,
+            unlocks: unlocks.clone(),
+            revoker: None,
+            deposit_amount,
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // The vesting account defers to the schedule.
+    {
+        let vesting_acc = client.vesting(&vesting).unwrap();
+        assert!(vesting_acc.has_schedule());
+        assert_eq!(vesting_acc.start_balance, deposit_amount);
+        let schedule = client.vesting_schedule(&vesting).unwrap();
+        assert!(schedule.initialized);
+        assert_eq!(schedule.vesting, vesting);
+        assert_eq!(schedule.unlocks, unlocks);
+    }
+
+    // Claim the receipt.
+    let nft_tok_acc =
+        rpc::create_token_account(client.rpc(), &mint, &beneficiary.pubkey(), client.payer())
+            .unwrap();
+    client
+        .claim(ClaimRequest {
+            beneficiary: &beneficiary,
+            safe: safe_acc,
+            vesting,
+            locked_mint: mint,
+            locked_token_account: nft_tok_acc.pubkey(),
+        })
+        .unwrap();
+
+    let bene_tok_acc = rpc::create_token_account(
+        client.rpc(),
+        &srm_mint.pubkey(),
+        &beneficiary.pubkey(),
+        client.payer(),
+    )
+    .unwrap();
+    let redeem = |amount| {
+        client.redeem(RedeemRequest {
+            beneficiary: &beneficiary,
+            vesting,
+            token_account: bene_tok_acc.pubkey(),
+            vault: safe_srm_vault,
+            safe: safe_acc,
+            locked_token_account: nft_tok_acc.pubkey(),
+            locked_mint: mint,
+            amount,
+        })
+    };
+
+    // When.
+    //
+    // I redeem the unlocked half.
+    redeem(50).unwrap();
+
+    // Then.
+    //
+    // The tokens are received, and nothing more can be redeemed.
+    {
+        let bene_tok =
+            rpc::account_token_unpacked::<TokenAccount>(client.rpc(), &bene_tok_acc.pubkey());
+        assert_eq!(bene_tok.amount, 50);
+        assert!(redeem(1).is_err());
+    }
+}
+
+#[test]
+fn revoke() {
+    // Given.
+    //
+    // A revocable vesting account with half unlocked.
+    let Initialized {
+        client,
+        safe_acc,
+        srm_mint,
+        depositor,
+        ..
+    } = lifecycle::initialize();
+    let beneficiary = Keypair::generate(&mut OsRng);
+    let revoker = Keypair::generate(&mut OsRng);
+    let deposit_amount = 100;
+    let CreateVestingResponse { vesting, .. } = client
+        .create_scheduled_vesting(CreateScheduledVestingRequest {
+            depositor: depositor.pubkey(),
+            depositor_owner: client.payer(),
+            safe: safe_acc,
+            beneficiary: beneficiary.pubkey(),
+            cliff_ts: 0,
+            unlocks: vec![
+                Unlock { ts: 1, bps: 5_000 },
+                Unlock {
+                    ts: i64::MAX,
+                    bps: VestingSchedule::BPS_TOTAL,
+                },
+            ],
+            revoker: Some(revoker.pubkey()),
+            deposit_amount,
+        })
+        .unwrap();
+    let receiver = rpc::create_token_account(
+        client.rpc(),
+        &srm_mint.pubkey(),
+        &revoker.pubkey(),
+        client.payer(),
+    )
+    .unwrap();
+
+    // When.
+    //
+    // The revoker revokes it.
+    client
+        .revoke(RevokeRequest {
+            revoker: &revoker,
+            vesting,
+            safe: safe_acc,
+            receiver: receiver.pubkey(),
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // The unvested half is sent to the receiver, the vested half stays
+    // with the beneficiary, and it can't be revoked again.
+    {
+        let receiver_tok =
+            rpc::account_token_unpacked::<TokenAccount>(client.rpc(), &receiver.pubkey());
+        assert_eq!(receiver_tok.amount, 50);
+        let vesting_acc = client.vesting(&vesting).unwrap();
+        assert_eq!(vesting_acc.start_balance, 50);
+        assert_eq!(vesting_acc.balance, 50);
+        let schedule = client.vesting_schedule(&vesting).unwrap();
+        assert_eq!(schedule.revoker, None);
+        assert!(client
+            .revoke(RevokeRequest {
+                revoker: &revoker,
+                vesting,
+                safe: safe_acc,
+                receiver: receiver.pubkey(),
+            })
+            .is_err());
+    }
+}
+
+#[test]
+fn revoke_claimed() {
+    // Given.
+    //
+    // A claimed, revocable vesting account with half unlocked.
+    let Initialized {
+        client,
+        safe_acc,
+        safe_srm_vault,
+        srm_mint,
+        depositor,
+        ..
+    } = lifecycle::initialize();
+    let beneficiary = Keypair::generate(&mut OsRng);
+    let revoker = Keypair::generate(&mut OsRng);
+    let CreateVestingResponse { vesting, mint, .. } = client
+        .create_scheduled_vesting(CreateScheduledVestingRequest {
+            depositor: depositor.pubkey(),
+            depositor_owner: client.payer(),
+            safe: safe_acc,
+            beneficiary: beneficiary.pubkey(),
+            cliff_ts: 0,
+            unlocks: vec![
+                Unlock { ts: 1, bps: 5_000 },
+                Unlock {
+                    ts: i64::MAX,
+                    bps: VestingSchedule::BPS_TOTAL,
+                },
+            ],
+            revoker: Some(revoker.pubkey()),
+            deposit_amount: 100,
+        })
+        .unwrap();
+    let nft_tok_acc =
+        rpc::create_token_account(client.rpc(), &mint, &beneficiary.pubkey(), client.payer())
+            .unwrap();
+    client
+        .claim(ClaimRequest {
+            beneficiary: &beneficiary,
+            safe: safe_acc,
+            vesting,
+            locked_mint: mint,
+            locked_token_account: nft_tok_acc.pubkey(),
+        })
+        .unwrap();
+    let receiver = rpc::create_token_account(
+        client.rpc(),
+        &srm_mint.pubkey(),
+        &revoker.pubkey(),
+        client.payer(),
+    )
+    .unwrap();
+
+    // When.
+    //
+    // The revoker revokes it.
+    client
+        .revoke(RevokeRequest {
+            revoker: &revoker,
+            vesting,
+            safe: safe_acc,
+            receiver: receiver.pubkey(),
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // The receipts are frozen.
+    {
+        let nft_tok =
+            rpc::account_token_unpacked::<TokenAccount>(client.rpc(), &nft_tok_acc.pubkey());
+        assert_eq!(nft_tok.state, AccountState::Frozen);
+    }
+
+    // Then.
+    //
+    // The beneficiary still redeems the vested half, and the receipts stay
+    // frozen.
+    let bene_tok_acc = rpc::create_token_account(
+        client.rpc(),
+        &srm_mint.pubkey(),
+        &beneficiary.pubkey(),
+        client.payer(),
+    )
+    .unwrap();
+    client
+        .redeem(RedeemRequest {
+            beneficiary: &beneficiary,
+            vesting,
+            token_account: bene_tok_acc.pubkey(),
+            vault: safe_srm_vault,
+            safe: safe_acc,
+            locked_token_account: nft_tok_acc.pubkey(),
+            locked_mint: mint,
+            amount: 50,
+        })
+        .unwrap();
+    {
+        let bene_tok =
+            rpc::account_token_unpacked::<TokenAccount>(client.rpc(), &bene_tok_acc.pubkey());
+        assert_eq!(bene_tok.amount, 50);
+        let nft_tok =
+            rpc::account_token_unpacked::<TokenAccount>(client.rpc(), &nft_tok_acc.pubkey());
+        assert_eq!(nft_tok.amount, 50);
+        assert_eq!(nft_tok.state, AccountState::Frozen);
+    }
+}
+
+#[test]
+fn streaming_vesting() {
+    // Given.
+    //
+    // An initialized safe.
+    let Initialized {
+        client,
+        safe_acc,
+        depositor,
+        ..
+    } = lifecycle::initialize();
+
+    // When.
+    //
+    // I create a stream over a far future period.
+    let beneficiary = Keypair::generate(&mut OsRng);
+    let CreateVestingResponse { vesting, .. } = client
+        .create_streaming_vesting(CreateStreamingVestingRequest {
+            depositor: depositor.pubkey(),
+            depositor_owner: client.payer(),
+            safe: safe_acc,
+            beneficiary: beneficiary.pubkey(),
+            start_ts: i64::MAX - 2,
+            end_ts: i64::MAX,
+            revoker: None,
+            deposit_amount: 100,
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // The schedule interpolates between the start and end, and nothing is
+    // vested yet.
+    {
+        let schedule = client.vesting_schedule(&vesting).unwrap();
+        assert!(schedule.streaming);
+        assert_eq!(schedule.cliff_ts, i64::MAX - 2);
+        assert_eq!(
+            schedule.unlocks,
+            VestingSchedule::stream(i64::MAX - 2, i64::MAX)
+        );
+        let vesting_acc = client.vesting(&vesting).unwrap();
+        assert_eq!(
+            vesting_acc.available_for_withdrawal_with_schedule(&schedule, 0),
+            0
+        );
+        assert_eq!(
+            vesting_acc.available_for_withdrawal_with_schedule(&schedule, i64::MAX - 1),
+            50
+        );
+    }
+}
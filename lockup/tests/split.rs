@@ -0,0 +1,81 @@
+use common::lifecycle;
+use serum_common::client::rpc;
+use serum_lockup_client::*;
+use solana_client_gen::prelude::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+use spl_token::state::Account as TokenAccount;
+
+mod common;
+
+#[test]
+fn split_vesting() {
+    // Given.
+    //
+    // A claimed vesting account.
+    let lifecycle::Deposited {
+        client,
+        vesting_acc_beneficiary,
+        vesting_acc,
+        safe_acc,
+        ..
+    } = lifecycle::deposit_with_schedule(100, 100_000, 10);
+    let vesting = client.vesting(&vesting_acc).unwrap();
+    let nft_tok_acc = rpc::create_token_account(
+        client.rpc(),
+        &vesting.locked_nft_mint,
+        &vesting_acc_beneficiary.pubkey(),
+        client.payer(),
+    )
+    .unwrap();
+    client
+        .claim(ClaimRequest {
+            beneficiary: &vesting_acc_beneficiary,
+            safe: safe_acc,
+            vesting: vesting_acc,
+            locked_mint: vesting.locked_nft_mint,
+            locked_token_account: nft_tok_acc.pubkey(),
+        })
+        .unwrap();
+
+    // When.
+    //
+    // The beneficiary splits off a part for someone else.
+    let new_beneficiary = Pubkey::new_rand();
+    let SplitVestingResponse {
+        vesting: new_vesting,
+        mint,
+        ..
+    } = client
+        .split_vesting(SplitVestingRequest {
+            beneficiary: &vesting_acc_beneficiary,
+            vesting: vesting_acc,
+            amount: 40,
+            new_beneficiary,
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // Both accounts keep the original schedule.
+    {
+        let old = client.vesting(&vesting_acc).unwrap();
+        assert_eq!(old.balance, 60);
+        assert_eq!(old.start_balance, 60);
+        let new = client.vesting(&new_vesting).unwrap();
+        assert_eq!(new.beneficiary, new_beneficiary);
+        assert_eq!(new.balance, 40);
+        assert_eq!(new.start_balance, 40);
+        assert_eq!(new.claimed, false);
+        assert_eq!(new.locked_nft_mint, mint);
+        assert_eq!(new.start_slot, old.start_slot);
+        assert_eq!(new.end_slot, old.end_slot);
+        assert_eq!(new.period_count, old.period_count);
+    }
+    // Then.
+    //
+    // The receipts for the amount split off are burned.
+    {
+        let nft = rpc::account_token_unpacked::<TokenAccount>(client.rpc(), &nft_tok_acc.pubkey());
+        assert_eq!(nft.amount, 60);
+    }
+}
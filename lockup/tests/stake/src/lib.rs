@@ -1,4 +1,9 @@
 //! Client crate for the test stake program.
+//!
+//! The test stake program is a minimal mock of a whitelisted program. It
+//! implements the lockup program's relay interface and nothing else, so
+//! integrators can test `WhitelistWithdraw` and `WhitelistDeposit` flows
+//! against the lockup program without deploying the full registry.
 
 use serde::{Deserialize, Serialize};
 use serum_common::pack::*;
@@ -9,7 +14,7 @@ use solana_client_gen::prelude::*;
 #[cfg_attr(feature = "client", solana_client_gen(ext))]
 pub mod instruction {
     use super::*;
-    #[derive(serde::Serialize, serde::Deserialize)]
+    #[derive(serde::Serialize, serde::Deserialize, Pack)]
     pub enum StakeInstruction {
         /// Accounts:
         ///
@@ -55,7 +60,7 @@ solana_client_gen_extension! {
             let lamports = self
                 .rpc()
                 .get_minimum_balance_for_rent_exemption(
-                    *crate::accounts::WL_SIZE as usize,
+                    crate::accounts::Instance::SIZE as usize,
                 )
                 .map_err(ClientError::RpcError)?;
 
@@ -63,7 +68,7 @@ solana_client_gen_extension! {
                 &self.payer().pubkey(),
                 &wl_acc.pubkey(),
                 lamports,
-                *crate::accounts::WL_SIZE,
+                crate::accounts::Instance::SIZE,
                 self.program(),
             );
 
@@ -97,6 +102,7 @@ solana_client_gen_extension! {
                 )
                 .map_err(ClientError::RpcError)
                 .map(|sig| InitializeResponse {
+                    program_id: *self.program(),
                     signature: sig,
                     vault_authority,
                     vault: vault.pubkey(),
@@ -107,30 +113,53 @@ solana_client_gen_extension! {
     }
     pub struct InitializeResponse {
         pub signature: solana_sdk::signature::Signature,
+        pub program_id: Pubkey,
         pub nonce: u8,
         pub instance: Pubkey,
         pub vault: Pubkey,
         pub vault_authority: Pubkey,
     }
-}
 
-serum_common::packable!(crate::instruction::StakeInstruction);
+    impl InitializeResponse {
+        /// Accounts to append to a lockup whitelist relay, after the
+        /// whitelisted program's vault, vault authority, and token program.
+        pub fn relay_accounts(&self) -> Vec<AccountMeta> {
+            vec![AccountMeta::new(self.instance, false)]
+        }
+    }
+}
 
-pub mod accounts {
+/// Instruction data for the lockup program to relay to the mock program.
+pub mod relay {
+    use super::instruction::StakeInstruction;
     use super::*;
 
-    #[cfg(feature = "client")]
-    lazy_static::lazy_static! {
-        pub static ref WL_SIZE: u64 = Instance::default()
-                    .size()
-                    .expect("Vesting has a fixed size");
+    /// Relay data for a `WhitelistWithdraw`, moving `amount` from the
+    /// Safe's vault into the mock program's vault.
+    pub fn stake_data(amount: u64) -> Vec<u8> {
+        pack_relay_data(StakeInstruction::Stake { amount })
+    }
+
+    /// Relay data for a `WhitelistDeposit`, moving `amount` from the mock
+    /// program's vault back into the Safe's vault.
+    pub fn unstake_data(amount: u64) -> Vec<u8> {
+        pack_relay_data(StakeInstruction::Unstake { amount })
     }
 
-    #[derive(Default, Serialize, Deserialize)]
+    fn pack_relay_data(instr: StakeInstruction) -> Vec<u8> {
+        let mut data = vec![0; instr.size().expect("instruction has a fixed size") as usize];
+        StakeInstruction::pack(instr, &mut data).expect("instruction must serialize");
+        data
+    }
+}
+
+pub mod accounts {
+    use super::*;
+
+    #[derive(Default, Serialize, Deserialize, Pack)]
     pub struct Instance {
         pub nonce: u8,
     }
-    serum_common::packable!(Instance);
 
     pub fn signer_seeds<'a>(wl: &'a Pubkey, nonce: &'a u8) -> [&'a [u8]; 2] {
         [wl.as_ref(), bytemuck::bytes_of(nonce)]
@@ -0,0 +1,107 @@
+use common::lifecycle::{self, Initialized};
+use serum_lockup::accounts::WhitelistEntry;
+use serum_lockup_client::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+mod common;
+
+#[test]
+fn whitelist_add_and_remove_many() {
+    // Given.
+    //
+    // An initialized safe.
+    let Initialized {
+        client,
+        safe_acc,
+        safe_authority,
+        ..
+    } = lifecycle::initialize();
+
+    // When.
+    //
+    // I add several entries at once.
+    let entries: Vec<WhitelistEntry> = (0..3)
+        .map(|k| WhitelistEntry::new(Pubkey::new_rand(), Pubkey::new_rand(), k))
+        .collect();
+    client
+        .whitelist_add_many(WhitelistAddManyRequest {
+            authority: &safe_authority,
+            safe: safe_acc,
+            entries: vec![
+                (entries[0].clone(), 100),
+                (entries[1].clone(), 200),
+                (entries[2].clone(), u64::MAX),
+            ],
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // They're listed in order with their limits.
+    {
+        let items = client.whitelist_items(&safe_acc).unwrap();
+        assert_eq!(items.len(), 3);
+        for (k, item) in items.iter().enumerate() {
+            assert_eq!(item.index, k);
+            assert_eq!(item.entry, entries[k]);
+            assert_eq!(item.outstanding, 0);
+        }
+        assert_eq!(items[0].max_outstanding, 100);
+        assert_eq!(items[1].max_outstanding, 200);
+        assert_eq!(items[2].max_outstanding, u64::MAX);
+    }
+
+    // When.
+    //
+    // I raise the limit of the first.
+    client
+        .whitelist_set_max_outstanding(WhitelistSetMaxOutstandingRequest {
+            authority: &safe_authority,
+            safe: safe_acc,
+            entry: entries[0].clone(),
+            max_outstanding: 150,
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // Only its limit changes.
+    {
+        let items = client.whitelist_items(&safe_acc).unwrap();
+        assert_eq!(items[0].max_outstanding, 150);
+        assert_eq!(items[0].outstanding, 0);
+        assert_eq!(items[1].max_outstanding, 200);
+    }
+
+    // When.
+    //
+    // I remove two of them.
+    client
+        .whitelist_remove_many(WhitelistRemoveManyRequest {
+            authority: &safe_authority,
+            safe: safe_acc,
+            entries: vec![entries[0].clone(), entries[2].clone()],
+        })
+        .unwrap();
+
+    // Then.
+    //
+    // Only the remaining entry is listed.
+    {
+        let items = client.whitelist_items(&safe_acc).unwrap();
+        assert_eq!(items.len(), 1);
+        assert_eq!(items[0].index, 1);
+        assert_eq!(items[0].entry, entries[1]);
+    }
+
+    // Then.
+    //
+    // Removing an entry that isn't there fails.
+    assert!(client
+        .whitelist_remove_many(WhitelistRemoveManyRequest {
+            authority: &safe_authority,
+            safe: safe_acc,
+            entries: vec![entries[0].clone()],
+        })
+        .is_err());
+}
@@ -0,0 +1,98 @@
+//! Versioned snapshot of every account owned by a registry program, used to
+//! migrate a deployment to a new program.
+
+use anyhow::{anyhow, Result};
+use serde::{Deserialize, Serialize};
+use serum_common::pack::{Pack, PackedSize};
+use serum_registry::accounts::{Entity, EntityMetadata, Member, Registrar};
+use solana_client_gen::solana_client::rpc_client::RpcClient;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// Bumped whenever an account layout changes, so that an archive is never
+/// read back with the wrong layout.
+pub const VERSION: u32 = 2;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Archive {
+    pub version: u32,
+    /// Program the accounts were exported from.
+    pub program_id: Pubkey,
+    /// Slot at which the export started.
+    pub slot: u64,
+    pub registrars: Vec<Entry<Registrar>>,
+    pub entities: Vec<Entry<Entity>>,
+    pub members: Vec<Entry<Member>>,
+    #[serde(default)]
+    pub entity_metadata: Vec<Entry<EntityMetadata>>,
+    /// Program owned accounts that didn't match any known layout.
+    pub unknown: Vec<Pubkey>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Entry<T> {
+    pub address: Pubkey,
+    pub account: T,
+}
+
+impl Archive {
+    pub fn export(client: &RpcClient, program_id: &Pubkey) -> Result<Archive> {
+        let slot = client.get_slot()?;
+        let mut archive = Archive {
+            version: VERSION,
+            program_id: *program_id,
+            slot,
+            registrars: vec![],
+            entities: vec![],
+            members: vec![],
+            entity_metadata: vec![],
+            unknown: vec![],
+        };
+        for (address, account) in client.get_program_accounts(program_id)? {
+            let len = account.data.len() as u64;
+            // Accounts are told apart by size, which differs for each layout.
+            if len == Registrar::SIZE {
+                archive.registrars.push(Entry {
+                    address,
+                    account: Registrar::unpack(&account.data)?,
+                });
+            } else if len == Entity::SIZE {
+                archive.entities.push(Entry {
+                    address,
+                    account: Entity::unpack(&account.data)?,
+                });
+            } else if len == Member::SIZE {
+                archive.members.push(Entry {
+                    address,
+                    account: Member::unpack(&account.data)?,
+                });
+            } else if len == EntityMetadata::SIZE as u64 {
+                archive.entity_metadata.push(Entry {
+                    address,
+                    account: EntityMetadata::unpack(&account.data)?,
+                });
+            } else {
+                archive.unknown.push(address);
+            }
+        }
+        Ok(archive)
+    }
+
+    pub fn write(&self, path: &str) -> Result<()> {
+        let file = std::fs::File::create(path)?;
+        serde_json::to_writer_pretty(file, self)?;
+        Ok(())
+    }
+
+    pub fn read(path: &str) -> Result<Archive> {
+        let file = std::fs::File::open(path)?;
+        let archive: Archive = serde_json::from_reader(file)?;
+        if archive.version != VERSION {
+            return Err(anyhow!(
+                "archive version {} is not supported, expected {}",
+                archive.version,
+                VERSION
+            ));
+        }
+        Ok(archive)
+    }
+}
@@ -3,10 +3,12 @@ use clap::Clap;
 use serum_common::client::rpc;
 use serum_node_context::Context;
 use serum_node_logging::info;
-use serum_registry::accounts::{Entity, Member, Registrar};
+use serum_registry::accounts::{Entity, Member, PendingWithdrawal, Registrar, StakeSnapshot};
 use serum_registry::client_ext::client::Client;
 use solana_client_gen::prelude::*;
 
+mod archive;
+
 #[derive(Debug, Clap)]
 #[clap(name = "Serum Registry CLI")]
 pub struct Opts {
@@ -38,10 +40,35 @@ pub enum SubCommand {
         /// The keypair filepath for the node leader.
         #[clap(short, long)]
         leader: String,
+        /// Registrar the entity is registered with.
+        #[clap(short, long)]
+        registrar: Pubkey,
         /// Flag for specifiying the crank capability. Required.
         #[clap(short, long)]
         crank: bool,
     },
+    /// Sets the commission the leader takes from rewards distributed to
+    /// their entity. A raise applies once the registrar's withdrawal
+    /// timelock has passed.
+    SetCommission {
+        /// The keypair filepath for the node leader.
+        #[clap(short, long)]
+        leader: String,
+        /// Commission in bps, e.g., 500 for 5%.
+        commission_bps: u32,
+    },
+    /// Sets who can join the leader's entity.
+    SetJoinRules {
+        /// The keypair filepath for the node leader.
+        #[clap(short, long)]
+        leader: String,
+        /// Most members the entity takes, or 0 for no limit.
+        #[clap(long, default_value = "0")]
+        max_members: u64,
+        /// Require the leader's signature for members to join.
+        #[clap(long)]
+        approve_joins: bool,
+    },
     /// Joins an entity, creating an associated member account.
     JoinEntity {
         /// Node entity to join with.
@@ -54,6 +81,81 @@ pub enum SubCommand {
         #[clap(short, long)]
         delegate: Option<Pubkey>,
     },
+    /// Stakes SRM, or MSRM with --mega, from a token account owned by the
+    /// wallet to the wallet's member account.
+    #[clap(visible_alias = "deposit")]
+    Stake {
+        /// Token account to stake from.
+        #[clap(short, long)]
+        depositor: Pubkey,
+        /// Amount to stake, in the token's smallest unit.
+        amount: u64,
+        /// Flag for staking MSRM instead of SRM.
+        #[clap(long)]
+        mega: bool,
+        /// Address of the member account [optional]. Defaults to the
+        /// wallet's derived member account.
+        #[clap(short, long)]
+        member: Option<Pubkey>,
+    },
+    /// Starts withdrawing stake from the wallet's member account, creating
+    /// a pending withdrawal that completes once the registrar's withdrawal
+    /// timelock passes.
+    StartWithdrawal {
+        /// Amount of SRM to withdraw.
+        #[clap(long, default_value = "0")]
+        amount: u64,
+        /// Amount of MSRM to withdraw.
+        #[clap(long, default_value = "0")]
+        mega_amount: u64,
+        /// Registrar whose withdrawal timelock applies.
+        #[clap(short, long)]
+        registrar: Pubkey,
+        /// Address of the member account [optional].
+        #[clap(short, long)]
+        member: Option<Pubkey>,
+    },
+    /// Completes a pending withdrawal, paying the stake out to the given
+    /// token accounts.
+    EndWithdrawal {
+        /// Address of the pending withdrawal.
+        #[clap(short, long)]
+        pending_withdrawal: Pubkey,
+        /// SRM token account to pay out to.
+        #[clap(long)]
+        srm_wallet: Pubkey,
+        /// MSRM token account to pay out to.
+        #[clap(long)]
+        msrm_wallet: Pubkey,
+        /// Address of the member account [optional].
+        #[clap(short, long)]
+        member: Option<Pubkey>,
+    },
+    /// Shows a member account along with the entity it's staked to.
+    ShowMember {
+        /// Address of the member account [optional]. Defaults to the
+        /// wallet's derived member account.
+        #[clap(short, long)]
+        address: Option<Pubkey>,
+    },
+    /// Sets the memo on a member account, e.g., "treasury".
+    SetMemberMemo {
+        /// Address of the member account.
+        #[clap(short, long)]
+        member: Pubkey,
+        /// The keypair filepath for the member's beneficiary.
+        #[clap(short, long)]
+        beneficiary: String,
+        /// Memo to set, at most 32 bytes.
+        memo: String,
+    },
+    /// Writes every account owned by the registry program to a versioned
+    /// JSON archive, e.g., before migrating to a new deployment.
+    Export {
+        /// File to write the archive to.
+        #[clap(short, long)]
+        output: String,
+    },
 }
 
 // AccountsComand defines the subcommand to view formatted account data
@@ -95,9 +197,12 @@ pub enum GovCommand {
         /// Not required if authority is present.
         #[clap(short = 'f', long, required_unless_present("authority"))]
         authority_file: Option<String>,
-        /// The amount of slots one must wait for a staking withdrawal.
-        #[clap(short, long, default_value = "10000")]
+        /// The amount of seconds one must wait for a staking withdrawal.
+        #[clap(short, long, default_value = "604800")]
         withdrawal_timelock: u64,
+        /// The amount of seconds each reward drop is paid out over.
+        #[clap(short, long, default_value = "604800")]
+        reward_period: u64,
     },
     /// Registers a new node capability in the registrar.
     RegisterCapability {
@@ -114,6 +219,74 @@ pub enum GovCommand {
         #[clap(long = "authority-file")]
         registrar_authority_file: String,
     },
+    /// Updates the parameters of a registrar.
+    UpdateRegistrar {
+        /// Address of an initialized on-chain registrar.
+        #[clap(long)]
+        registrar: Pubkey,
+        /// Registrar authority key for signing.
+        #[clap(long = "authority-file")]
+        registrar_authority_file: String,
+        /// Hands the registrar to a new authority.
+        #[clap(long)]
+        new_authority: Option<Pubkey>,
+        /// The amount of seconds one must wait for a staking withdrawal.
+        #[clap(long)]
+        withdrawal_timelock: Option<u64>,
+        /// The amount of seconds each reward drop is paid out over.
+        #[clap(long)]
+        reward_period: Option<u64>,
+        /// The most SRM-equivalent stake a member may hold, 0 for no cap.
+        #[clap(long)]
+        max_stake_per_member: Option<u64>,
+    },
+    /// Initializes a registrar on this deployment from an exported archive,
+    /// carrying over its withdrawal timelock and capability fees.
+    Import {
+        /// Archive written by the export command.
+        #[clap(short, long)]
+        archive: String,
+        /// Address of the registrar in the archive to import.
+        #[clap(short, long)]
+        registrar: Pubkey,
+        /// Authority of the new registrar. Must sign for the capabilities.
+        #[clap(short = 'f', long)]
+        authority_file: String,
+    },
+    /// Records the stake backing an entity in the current epoch in a new
+    /// stake snapshot account.
+    SnapshotEntity {
+        /// Address of an initialized on-chain registrar.
+        #[clap(long)]
+        registrar: Pubkey,
+        /// Registrar authority key for signing.
+        #[clap(long = "authority-file")]
+        registrar_authority_file: String,
+        /// Address of the entity to snapshot.
+        #[clap(long)]
+        entity: Pubkey,
+        /// Hex encoded Merkle root of the entity's members' stake.
+        #[clap(long)]
+        members_root: Option<String>,
+    },
+    /// Pauses a registrar, blocking reward drops.
+    Pause {
+        /// Address of an initialized on-chain registrar.
+        #[clap(long)]
+        registrar: Pubkey,
+        /// Registrar authority key for signing.
+        #[clap(long = "authority-file")]
+        registrar_authority_file: String,
+    },
+    /// Unpauses a registrar.
+    Unpause {
+        /// Address of an initialized on-chain registrar.
+        #[clap(long)]
+        registrar: Pubkey,
+        /// Registrar authority key for signing.
+        #[clap(long = "authority-file")]
+        registrar_authority_file: String,
+    },
 }
 
 pub fn run(opts: Opts) -> Result<()> {
@@ -123,15 +296,278 @@ pub fn run(opts: Opts) -> Result<()> {
     match opts.cmd.sub_cmd {
         SubCommand::Accounts(cmd) => account_cmd(ctx, registry_pid, cmd),
         SubCommand::Gov(cmd) => gov_cmd(ctx, registry_pid, cmd),
-        SubCommand::CreateEntity { crank, leader } => {
-            create_entity_cmd(ctx, registry_pid, leader, crank)
-        }
+        SubCommand::CreateEntity {
+            crank,
+            leader,
+            registrar,
+        } => create_entity_cmd(ctx, registry_pid, leader, registrar, crank),
+        SubCommand::SetCommission {
+            leader,
+            commission_bps,
+        } => set_commission_cmd(ctx, registry_pid, leader, commission_bps),
+        SubCommand::SetJoinRules {
+            leader,
+            max_members,
+            approve_joins,
+        } => set_join_rules_cmd(ctx, registry_pid, leader, max_members, approve_joins),
         SubCommand::JoinEntity {
             entity,
             beneficiary,
             delegate,
         } => join_entity_cmd(ctx, registry_pid, entity, beneficiary, delegate),
+        SubCommand::SetMemberMemo {
+            member,
+            beneficiary,
+            memo,
+        } => set_member_memo_cmd(ctx, registry_pid, member, beneficiary, memo),
+        SubCommand::Stake {
+            depositor,
+            amount,
+            mega,
+            member,
+        } => stake_cmd(ctx, registry_pid, member, depositor, amount, mega),
+        SubCommand::StartWithdrawal {
+            amount,
+            mega_amount,
+            registrar,
+            member,
+        } => start_withdrawal_cmd(ctx, registry_pid, member, registrar, amount, mega_amount),
+        SubCommand::EndWithdrawal {
+            pending_withdrawal,
+            srm_wallet,
+            msrm_wallet,
+            member,
+        } => end_withdrawal_cmd(
+            ctx,
+            registry_pid,
+            member,
+            pending_withdrawal,
+            srm_wallet,
+            msrm_wallet,
+        ),
+        SubCommand::ShowMember { address } => show_member_cmd(ctx, registry_pid, address),
+        SubCommand::Export { output } => export_cmd(ctx, registry_pid, output),
+    }
+}
+
+// The given member account, or the one derived from the wallet.
+fn member_address(
+    ctx: &Context,
+    registry_pid: Option<Pubkey>,
+    address: Option<Pubkey>,
+) -> Result<Pubkey> {
+    match address {
+        Some(a) => Ok(a),
+        None => {
+            let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
+            Member::address(&registry_pid, &ctx.wallet()?.pubkey())
+                .map_err(|e| anyhow!("unable to derive stake address: {}", e.to_string()))
+        }
+    }
+}
+
+fn stake_cmd(
+    ctx: &Context,
+    registry_pid: Option<Pubkey>,
+    member: Option<Pubkey>,
+    depositor: Pubkey,
+    amount: u64,
+    is_mega: bool,
+) -> Result<()> {
+    let member = member_address(ctx, registry_pid, member)?;
+    let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
+
+    let client = ctx.connect::<Client>(registry_pid)?;
+    let member_acc: Member = rpc::get_account(client.rpc(), &member)?;
+    let registrar_acc: Registrar = rpc::get_account(client.rpc(), &member_acc.registrar)?;
+    let wallet = client.payer().pubkey();
+    let accounts = [
+        AccountMeta::new_readonly(wallet, true),
+        AccountMeta::new(depositor, false),
+        AccountMeta::new(member, false),
+        AccountMeta::new_readonly(wallet, true),
+        AccountMeta::new(member_acc.entity, false),
+        AccountMeta::new(*registrar_acc.stake_vault(is_mega), false),
+        AccountMeta::new_readonly(member_acc.registrar, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+        AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+    ];
+    let signers = [client.payer()];
+    let tx_sig = client.stake_with_signers(&signers, &accounts, amount, is_mega)?;
+
+    let logger = serum_node_logging::get_logger("node/registry");
+    info!(logger, "Confirmed transaction: {:?}", tx_sig);
+
+    Ok(())
+}
+
+fn start_withdrawal_cmd(
+    ctx: &Context,
+    registry_pid: Option<Pubkey>,
+    member: Option<Pubkey>,
+    registrar: Pubkey,
+    amount: u64,
+    mega_amount: u64,
+) -> Result<()> {
+    let member = member_address(ctx, registry_pid, member)?;
+    let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
+    if amount == 0 && mega_amount == 0 {
+        return Err(anyhow!("Nothing to withdraw"));
+    }
+
+    let client = ctx.connect::<Client>(registry_pid)?;
+    let member_acc: Member = rpc::get_account(client.rpc(), &member)?;
+    let wallet = client.payer().pubkey();
+    let pending_withdrawal = Keypair::generate(&mut OsRng);
+    let accounts = [
+        AccountMeta::new(pending_withdrawal.pubkey(), false),
+        AccountMeta::new_readonly(wallet, true),
+        AccountMeta::new(member, false),
+        AccountMeta::new(member_acc.entity, false),
+        AccountMeta::new_readonly(registrar, false),
+        AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+        AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+    ];
+    let signers = [client.payer(), &pending_withdrawal];
+    let (tx_sig, _) = client.create_account_and_start_stake_withdrawal_with_signers(
+        Keypair::from_bytes(&pending_withdrawal.to_bytes()).map_err(|e| anyhow!("{}", e))?,
+        &signers,
+        &accounts,
+        amount,
+        mega_amount,
+    )?;
+
+    let logger = serum_node_logging::get_logger("node/registry");
+    info!(logger, "Confirmed transaction: {:?}", tx_sig);
+    info!(
+        logger,
+        "Created pending withdrawal with address: {:?}",
+        pending_withdrawal.pubkey()
+    );
+    let pending_withdrawal_acc: PendingWithdrawal =
+        rpc::get_account(client.rpc(), &pending_withdrawal.pubkey())?;
+    info!(
+        logger,
+        "Withdrawal can be completed at unix timestamp: {}", pending_withdrawal_acc.end_ts
+    );
+
+    Ok(())
+}
+
+fn end_withdrawal_cmd(
+    ctx: &Context,
+    registry_pid: Option<Pubkey>,
+    member: Option<Pubkey>,
+    pending_withdrawal: Pubkey,
+    srm_wallet: Pubkey,
+    msrm_wallet: Pubkey,
+) -> Result<()> {
+    let member = member_address(ctx, registry_pid, member)?;
+    let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
+
+    let client = ctx.connect::<Client>(registry_pid)?;
+    let member_acc: Member = rpc::get_account(client.rpc(), &member)?;
+    let registrar_acc: Registrar = rpc::get_account(client.rpc(), &member_acc.registrar)?;
+    let vault_authority = Pubkey::create_program_address(
+        &Registrar::signer_seeds(&member_acc.registrar, &registrar_acc.nonce),
+        &registry_pid,
+    )
+    .map_err(|e| anyhow!("unable to derive vault authority: {}", e.to_string()))?;
+    let accounts = [
+        AccountMeta::new(pending_withdrawal, false),
+        AccountMeta::new_readonly(client.payer().pubkey(), true),
+        AccountMeta::new(member, false),
+        AccountMeta::new(member_acc.entity, false),
+        AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+        AccountMeta::new_readonly(spl_token::ID, false),
+        AccountMeta::new_readonly(member_acc.registrar, false),
+        AccountMeta::new(registrar_acc.vault, false),
+        AccountMeta::new(registrar_acc.mega_vault, false),
+        AccountMeta::new_readonly(vault_authority, false),
+        AccountMeta::new(srm_wallet, false),
+        AccountMeta::new(msrm_wallet, false),
+    ];
+    let signers = [client.payer()];
+    let tx_sig = client.end_stake_withdrawal_with_signers(&signers, &accounts)?;
+
+    let logger = serum_node_logging::get_logger("node/registry");
+    info!(logger, "Confirmed transaction: {:?}", tx_sig);
+
+    Ok(())
+}
+
+fn show_member_cmd(
+    ctx: &Context,
+    registry_pid: Option<Pubkey>,
+    address: Option<Pubkey>,
+) -> Result<()> {
+    let address = member_address(ctx, registry_pid, address)?;
+    let rpc_client = ctx.rpc_client();
+
+    let member: Member = rpc::get_account(&rpc_client, &address)?;
+    let entity: Entity = rpc::get_account(&rpc_client, &member.entity)?;
+    println!("Member: {}", address);
+    println!("Memo: {}", member.memo_str());
+    println!("{:#?}", member);
+    println!("Entity: {}", member.entity);
+    println!("{:#?}", entity);
+
+    Ok(())
+}
+
+fn export_cmd(ctx: &Context, registry_pid: Option<Pubkey>, output: String) -> Result<()> {
+    let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
+
+    let archive = archive::Archive::export(&ctx.rpc_client(), &registry_pid)?;
+    archive.write(&output)?;
+
+    let logger = serum_node_logging::get_logger("node/registry");
+    info!(
+        logger,
+        "Exported {} registrars, {} entities and {} members at slot {} to {}",
+        archive.registrars.len(),
+        archive.entities.len(),
+        archive.members.len(),
+        archive.slot,
+        output
+    );
+    if !archive.unknown.is_empty() {
+        info!(
+            logger,
+            "Skipped {} accounts with an unknown layout: {:?}",
+            archive.unknown.len(),
+            archive.unknown
+        );
     }
+
+    Ok(())
+}
+
+fn set_member_memo_cmd(
+    ctx: &Context,
+    registry_pid: Option<Pubkey>,
+    member: Pubkey,
+    beneficiary_filepath: String,
+    memo: String,
+) -> Result<()> {
+    let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
+    let memo = Member::encode_memo(&memo).ok_or(anyhow!("memo must be at most 32 bytes"))?;
+
+    let beneficiary_kp = solana_sdk::signature::read_keypair_file(&beneficiary_filepath)
+        .map_err(|_| anyhow!("Unable to read beneficiary keypair file"))?;
+
+    let client = ctx.connect::<Client>(registry_pid)?;
+    let accounts = [
+        AccountMeta::new(member, false),
+        AccountMeta::new_readonly(beneficiary_kp.pubkey(), true),
+    ];
+    let signers = [&beneficiary_kp, client.payer()];
+    let tx_sig = client.update_member_memo_with_signers(&signers, &accounts, memo)?;
+
+    let logger = serum_node_logging::get_logger("node/registry");
+    info!(logger, "Confirmed transaction: {:?}", tx_sig);
+
+    Ok(())
 }
 
 fn join_entity_cmd(
@@ -159,6 +595,7 @@ fn create_entity_cmd(
     ctx: &Context,
     registry_pid: Option<Pubkey>,
     leader_filepath: String,
+    registrar: Pubkey,
     crank: bool,
 ) -> Result<()> {
     let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
@@ -175,6 +612,7 @@ fn create_entity_cmd(
     let client = ctx.connect::<Client>(registry_pid)?;
     let (tx_sig, entity_address) = client.create_entity_derived(
         &leader_kp,
+        registrar,
         capabilities,
         serum_registry::accounts::StakeKind::Delegated,
     )?;
@@ -186,6 +624,75 @@ fn create_entity_cmd(
     Ok(())
 }
 
+fn set_commission_cmd(
+    ctx: &Context,
+    registry_pid: Option<Pubkey>,
+    leader_filepath: String,
+    commission_bps: u32,
+) -> Result<()> {
+    let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
+    if commission_bps > Entity::MAX_COMMISSION_BPS {
+        return Err(anyhow!("Commission can't exceed 10000 bps"));
+    }
+    let leader_kp = solana_sdk::signature::read_keypair_file(&leader_filepath)
+        .map_err(|_| anyhow!("Unable to read leader keypair file"))?;
+    let entity = Entity::address(&registry_pid, &leader_kp.pubkey())?;
+
+    let client = ctx.connect::<Client>(registry_pid)?;
+    let entity_acc: Entity = rpc::get_account(client.rpc(), &entity)?;
+    let accounts = [
+        AccountMeta::new(entity, false),
+        AccountMeta::new_readonly(leader_kp.pubkey(), true),
+        AccountMeta::new_readonly(entity_acc.registrar, false),
+        AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+    ];
+    let signers = [&leader_kp, client.payer()];
+    let tx_sig = client.set_entity_commission_with_signers(&signers, &accounts, commission_bps)?;
+
+    let logger = serum_node_logging::get_logger("node/registry");
+    info!(logger, "Confirmed transaction: {:?}", tx_sig);
+    let entity_acc: Entity = rpc::get_account(client.rpc(), &entity)?;
+    if entity_acc.pending_commission_ts != 0 {
+        info!(
+            logger,
+            "Commission applies at unix timestamp: {}", entity_acc.pending_commission_ts
+        );
+    }
+
+    Ok(())
+}
+
+fn set_join_rules_cmd(
+    ctx: &Context,
+    registry_pid: Option<Pubkey>,
+    leader_filepath: String,
+    max_members: u64,
+    approve_joins: bool,
+) -> Result<()> {
+    let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
+    let leader_kp = solana_sdk::signature::read_keypair_file(&leader_filepath)
+        .map_err(|_| anyhow!("Unable to read leader keypair file"))?;
+    let entity = Entity::address(&registry_pid, &leader_kp.pubkey())?;
+
+    let client = ctx.connect::<Client>(registry_pid)?;
+    let accounts = [
+        AccountMeta::new(entity, false),
+        AccountMeta::new_readonly(leader_kp.pubkey(), true),
+    ];
+    let signers = [&leader_kp, client.payer()];
+    let tx_sig = client.set_entity_join_rules_with_signers(
+        &signers,
+        &accounts,
+        max_members,
+        approve_joins,
+    )?;
+
+    let logger = serum_node_logging::get_logger("node/registry");
+    info!(logger, "Confirmed transaction: {:?}", tx_sig);
+
+    Ok(())
+}
+
 pub fn gov_cmd(ctx: &Context, registry_pid: Option<Pubkey>, gov_cmd: GovCommand) -> Result<()> {
     let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
     match gov_cmd {
@@ -193,12 +700,14 @@ pub fn gov_cmd(ctx: &Context, registry_pid: Option<Pubkey>, gov_cmd: GovCommand)
             authority,
             authority_file,
             withdrawal_timelock,
+            reward_period,
         } => gov::init(
             ctx,
             registry_pid,
             authority,
             authority_file,
             withdrawal_timelock,
+            reward_period,
         ),
         GovCommand::RegisterCapability {
             force_id,
@@ -213,6 +722,55 @@ pub fn gov_cmd(ctx: &Context, registry_pid: Option<Pubkey>, gov_cmd: GovCommand)
             force_id,
             fee_bps,
         ),
+        GovCommand::UpdateRegistrar {
+            registrar,
+            registrar_authority_file,
+            new_authority,
+            withdrawal_timelock,
+            reward_period,
+            max_stake_per_member,
+        } => gov::update_registrar(
+            ctx,
+            registry_pid,
+            registrar,
+            registrar_authority_file,
+            new_authority,
+            withdrawal_timelock,
+            reward_period,
+            max_stake_per_member,
+        ),
+        GovCommand::Import {
+            archive,
+            registrar,
+            authority_file,
+        } => gov::import(ctx, registry_pid, archive, registrar, authority_file),
+        GovCommand::SnapshotEntity {
+            registrar,
+            registrar_authority_file,
+            entity,
+            members_root,
+        } => gov::snapshot_entity(
+            ctx,
+            registry_pid,
+            registrar,
+            registrar_authority_file,
+            entity,
+            members_root,
+        ),
+        GovCommand::Pause {
+            registrar,
+            registrar_authority_file,
+        } => gov::set_paused(ctx, registry_pid, registrar, registrar_authority_file, true),
+        GovCommand::Unpause {
+            registrar,
+            registrar_authority_file,
+        } => gov::set_paused(
+            ctx,
+            registry_pid,
+            registrar,
+            registrar_authority_file,
+            false,
+        ),
     }
 }
 
@@ -243,19 +801,10 @@ fn account_cmd(ctx: &Context, registry_pid: Option<Pubkey>, cmd: AccountsCommand
             println!("{:#?}", acc);
         }
         AccountsCommand::Member { address } => {
-            let address = match address {
-                Some(a) => a,
-                None => {
-                    let registry_pid = registry_pid.ok_or(anyhow!("--pid not provided"))?;
-                    Pubkey::create_with_seed(
-                        &ctx.wallet()?.pubkey(),
-                        Client::member_seed(),
-                        &registry_pid,
-                    )
-                    .map_err(|e| anyhow!("unable to derive stake address: {}", e.to_string()))?
-                }
-            };
+            let address = member_address(ctx, registry_pid, address)?;
             let acc: Member = rpc::get_account(&rpc_client, &address)?;
+            println!("Address: {}", address);
+            println!("Memo: {}", acc.memo_str());
             println!("{:#?}", acc);
         }
     };
@@ -271,6 +820,7 @@ mod gov {
         authority: Option<Pubkey>,
         authority_file: Option<String>,
         withdrawal_timelock: u64,
+        reward_period: u64,
     ) -> Result<()> {
         let logger = serum_node_logging::get_logger("node/registry");
 
@@ -285,17 +835,17 @@ mod gov {
                 kp.pubkey()
             }
         };
-        let accounts = [AccountMeta::new_readonly(
-            solana_sdk::sysvar::rent::ID,
-            false,
-        )];
-        let (_tx_sig, registrar_kp) =
-            client.create_account_and_initialize(&accounts, authority, withdrawal_timelock)?;
+        let (_tx_sig, registrar) = client.create_registrar(
+            &ctx.srm_mint,
+            &ctx.msrm_mint,
+            authority,
+            withdrawal_timelock,
+            reward_period,
+        )?;
 
         info!(
             logger,
-            "Registrar initialized with address: {:?}",
-            registrar_kp.pubkey()
+            "Registrar initialized with address: {:?}", registrar
         );
 
         Ok(())
@@ -341,4 +891,206 @@ mod gov {
 
         Ok(())
     }
+
+    pub fn update_registrar(
+        ctx: &Context,
+        registry_pid: Pubkey,
+        registrar: Pubkey,
+        registrar_authority_file: String,
+        new_authority: Option<Pubkey>,
+        withdrawal_timelock: Option<u64>,
+        reward_period: Option<u64>,
+        max_stake_per_member: Option<u64>,
+    ) -> Result<()> {
+        let logger = serum_node_logging::get_logger("node/registry");
+        let client = ctx.connect::<Client>(registry_pid)?;
+
+        let registrar_authority =
+            solana_sdk::signature::read_keypair_file(&registrar_authority_file)
+                .map_err(|_| anyhow!("Unable to read provided authority file"))?;
+        let accounts = [
+            AccountMeta::new_readonly(registrar_authority.pubkey(), true),
+            AccountMeta::new(registrar, false),
+        ];
+        let signers = [&registrar_authority, client.payer()];
+
+        let tx_sig = client.update_registrar_with_signers(
+            &signers,
+            &accounts,
+            new_authority,
+            withdrawal_timelock,
+            reward_period,
+            max_stake_per_member,
+        )?;
+
+        info!(
+            logger,
+            "Updated registrar with transaction signature: {:?}", tx_sig
+        );
+
+        Ok(())
+    }
+
+    pub fn set_paused(
+        ctx: &Context,
+        registry_pid: Pubkey,
+        registrar: Pubkey,
+        registrar_authority_file: String,
+        paused: bool,
+    ) -> Result<()> {
+        let logger = serum_node_logging::get_logger("node/registry");
+        let client = ctx.connect::<Client>(registry_pid)?;
+
+        let registrar_authority =
+            solana_sdk::signature::read_keypair_file(&registrar_authority_file)
+                .map_err(|_| anyhow!("Unable to read provided authority file"))?;
+        let accounts = [
+            AccountMeta::new_readonly(registrar_authority.pubkey(), true),
+            AccountMeta::new(registrar, false),
+        ];
+        let signers = [&registrar_authority, client.payer()];
+
+        let tx_sig = if paused {
+            client.pause_with_signers(&signers, &accounts)?
+        } else {
+            client.unpause_with_signers(&signers, &accounts)?
+        };
+
+        info!(
+            logger,
+            "Set registrar paused to {} with transaction signature: {:?}", paused, tx_sig
+        );
+
+        Ok(())
+    }
+
+    pub fn import(
+        ctx: &Context,
+        registry_pid: Pubkey,
+        archive_path: String,
+        registrar: Pubkey,
+        authority_file: String,
+    ) -> Result<()> {
+        let logger = serum_node_logging::get_logger("node/registry");
+        let archive = archive::Archive::read(&archive_path)?;
+        let exported = archive
+            .registrars
+            .iter()
+            .find(|entry| entry.address == registrar)
+            .ok_or(anyhow!("registrar {} is not in the archive", registrar))?;
+
+        let client = ctx.connect::<Client>(registry_pid)?;
+        let authority = solana_sdk::signature::read_keypair_file(&authority_file)
+            .map_err(|_| anyhow!("Unable to read provided authority file"))?;
+
+        let (_tx_sig, new_registrar) = client.create_registrar(
+            &ctx.srm_mint,
+            &ctx.msrm_mint,
+            authority.pubkey(),
+            exported.account.withdrawal_timelock_secs,
+            exported.account.reward_period_secs,
+        )?;
+        info!(
+            logger,
+            "Registrar {} imported with address: {:?}", registrar, new_registrar
+        );
+
+        let accounts = [
+            AccountMeta::new_readonly(authority.pubkey(), true),
+            AccountMeta::new(new_registrar, false),
+        ];
+        let signers = [&authority, client.payer()];
+        for (capability_id, fee_bps) in exported.account.capabilities_fees_bps.iter().enumerate() {
+            if *fee_bps == 0 {
+                continue;
+            }
+            client.register_capability_with_signers(
+                &signers,
+                &accounts,
+                capability_id as u8,
+                *fee_bps,
+            )?;
+        }
+        if exported.account.max_stake_per_member != 0 {
+            client.update_registrar_with_signers(
+                &signers,
+                &accounts,
+                None,
+                None,
+                None,
+                Some(exported.account.max_stake_per_member),
+            )?;
+        }
+
+        // Entities and members can only be created with their leader's
+        // signature and stake only moves with real deposits, so they're
+        // recreated by their owners against the new registrar.
+        info!(
+            logger,
+            "{} entities and {} members in the archive must be recreated by their owners",
+            archive.entities.len(),
+            archive.members.len()
+        );
+
+        Ok(())
+    }
+
+    pub fn snapshot_entity(
+        ctx: &Context,
+        registry_pid: Pubkey,
+        registrar: Pubkey,
+        registrar_authority_file: String,
+        entity: Pubkey,
+        members_root: Option<String>,
+    ) -> Result<()> {
+        let logger = serum_node_logging::get_logger("node/registry");
+        let client = ctx.connect::<Client>(registry_pid)?;
+
+        let members_root = match members_root {
+            Some(hex) => parse_root(&hex)?,
+            None => [0; 32],
+        };
+        let registrar_authority =
+            solana_sdk::signature::read_keypair_file(&registrar_authority_file)
+                .map_err(|_| anyhow!("Unable to read provided authority file"))?;
+        // The program checks the address against the epoch it runs in, so
+        // this fails if the epoch ends before the transaction lands.
+        let epoch = client.rpc().get_epoch_info()?.epoch;
+        let (snapshot, nonce) =
+            StakeSnapshot::find_address(client.program(), &registrar, &entity, epoch);
+        let accounts = [
+            AccountMeta::new(snapshot, false),
+            AccountMeta::new_readonly(entity, false),
+            AccountMeta::new_readonly(registrar, false),
+            AccountMeta::new(registrar_authority.pubkey(), true),
+            AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+            AccountMeta::new_readonly(solana_sdk::sysvar::clock::ID, false),
+        ];
+        let signers = [client.payer(), &registrar_authority];
+        let tx_sig =
+            client.snapshot_entity_stake_with_signers(&signers, &accounts, members_root, nonce)?;
+
+        info!(logger, "Confirmed transaction: {:?}", tx_sig);
+        let snapshot_acc: StakeSnapshot = rpc::get_account(client.rpc(), &snapshot)?;
+        info!(
+            logger,
+            "Snapshot {:?} of epoch {}: {:#?}", snapshot, snapshot_acc.epoch, snapshot_acc
+        );
+
+        Ok(())
+    }
+
+    fn parse_root(hex: &str) -> Result<[u8; 32]> {
+        let hex = hex.trim_start_matches("0x");
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(anyhow!("Members root must be 32 hex encoded bytes"));
+        }
+        let mut root = [0; 32];
+        for (i, b) in root.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&hex[2 * i..2 * i + 2], 16)
+                .map_err(|_| anyhow!("Members root must be 32 hex encoded bytes"))?;
+        }
+        Ok(root)
+    }
 }
@@ -0,0 +1,123 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{BeneficiaryTransfer, Member};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), RegistryError> {
+    info!("handler: accept_beneficiary_transfer");
+
+    let acc_infos = &mut accounts.iter();
+
+    let transfer_acc_info = next_account_info(acc_infos)?;
+    let member_acc_info = next_account_info(acc_infos)?;
+    let new_beneficiary_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        member_acc_info,
+        new_beneficiary_acc_info,
+    })?;
+
+    Member::unpack_mut(
+        &mut member_acc_info.try_borrow_mut_data()?,
+        &mut |member: &mut Member| {
+            state_transition(StateTransitionRequest {
+                member,
+                transfer_acc_info,
+                new_beneficiary_acc_info,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: accept_beneficiary_transfer");
+
+    let AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        member_acc_info,
+        new_beneficiary_acc_info,
+    } = req;
+
+    if !new_beneficiary_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if transfer_acc_info.owner != program_id || member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    let transfer = BeneficiaryTransfer::unpack(&transfer_acc_info.try_borrow_data()?)?;
+    if !transfer.initialized || transfer.member != *member_acc_info.key {
+        return Err(RegistryErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    if transfer.new_beneficiary != *new_beneficiary_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    // A transfer initiated by a previous beneficiary is stale.
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if member.beneficiary != transfer.beneficiary {
+        return Err(RegistryErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: accept_beneficiary_transfer");
+
+    let StateTransitionRequest {
+        member,
+        transfer_acc_info,
+        new_beneficiary_acc_info,
+    } = req;
+
+    member.beneficiary = *new_beneficiary_acc_info.key;
+
+    close(transfer_acc_info, new_beneficiary_acc_info)?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+/// Clears the transfer account and returns its lamports to `dst_acc_info`.
+pub fn close(
+    transfer_acc_info: &AccountInfo,
+    dst_acc_info: &AccountInfo,
+) -> Result<(), RegistryError> {
+    BeneficiaryTransfer::unpack_mut(
+        &mut transfer_acc_info.try_borrow_mut_data()?,
+        &mut |transfer: &mut BeneficiaryTransfer| {
+            *transfer = BeneficiaryTransfer::default();
+            Ok(())
+        },
+    )?;
+    crate::lamports::drain(transfer_acc_info, dst_acc_info)
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    new_beneficiary_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    member: &'b mut Member,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    new_beneficiary_acc_info: &'a AccountInfo<'a>,
+}
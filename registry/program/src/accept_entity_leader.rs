@@ -0,0 +1,123 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, EntityLeaderTransfer};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), RegistryError> {
+    info!("handler: accept_entity_leader");
+
+    let acc_infos = &mut accounts.iter();
+
+    let transfer_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let new_leader_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        entity_acc_info,
+        new_leader_acc_info,
+    })?;
+
+    Entity::unpack_mut(
+        &mut entity_acc_info.try_borrow_mut_data()?,
+        &mut |entity: &mut Entity| {
+            state_transition(StateTransitionRequest {
+                entity,
+                transfer_acc_info,
+                new_leader_acc_info,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: accept_entity_leader");
+
+    let AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        entity_acc_info,
+        new_leader_acc_info,
+    } = req;
+
+    if !new_leader_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if transfer_acc_info.owner != program_id || entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidEntityLeaderTransfer)?;
+    }
+    let transfer = EntityLeaderTransfer::unpack(&transfer_acc_info.try_borrow_data()?)?;
+    if !transfer.initialized || transfer.entity != *entity_acc_info.key {
+        return Err(RegistryErrorCode::InvalidEntityLeaderTransfer)?;
+    }
+    if transfer.new_leader != *new_leader_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    // A transfer proposed by a previous leader is stale.
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if entity.leader != transfer.leader {
+        return Err(RegistryErrorCode::InvalidEntityLeaderTransfer)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: accept_entity_leader");
+
+    let StateTransitionRequest {
+        entity,
+        transfer_acc_info,
+        new_leader_acc_info,
+    } = req;
+
+    entity.leader = *new_leader_acc_info.key;
+
+    close(transfer_acc_info, new_leader_acc_info)?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+/// Clears the transfer account and returns its lamports to `dst_acc_info`.
+pub fn close(
+    transfer_acc_info: &AccountInfo,
+    dst_acc_info: &AccountInfo,
+) -> Result<(), RegistryError> {
+    EntityLeaderTransfer::unpack_mut(
+        &mut transfer_acc_info.try_borrow_mut_data()?,
+        &mut |transfer: &mut EntityLeaderTransfer| {
+            *transfer = EntityLeaderTransfer::default();
+            Ok(())
+        },
+    )?;
+    crate::lamports::drain(transfer_acc_info, dst_acc_info)
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    new_leader_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    entity: &'b mut Entity,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    new_leader_acc_info: &'a AccountInfo<'a>,
+}
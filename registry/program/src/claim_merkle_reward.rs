@@ -0,0 +1,278 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::RewardVendor;
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::MerkleRewardClaimed;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    index: u64,
+    amount: u64,
+    proof: Vec<[u8; 32]>,
+    nonce: u8,
+) -> Result<(), RegistryError> {
+    info!("handler: claim_merkle_reward");
+
+    let acc_infos = &mut accounts.iter();
+
+    let claimant_acc_info = next_account_info(acc_infos)?;
+    let vendor_acc_info = next_account_info(acc_infos)?;
+    let vendor_vault_acc_info = next_account_info(acc_infos)?;
+    let vault_authority_acc_info = next_account_info(acc_infos)?;
+    let token_acc_info = next_account_info(acc_infos)?;
+    let receipt_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let system_program_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { vendor, rent } = access_control(AccessControlRequest {
+        program_id,
+        claimant_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        receipt_acc_info,
+        token_program_acc_info,
+        system_program_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+        index,
+        amount,
+        proof: &proof,
+        nonce,
+    })?;
+
+    state_transition(StateTransitionRequest {
+        program_id,
+        vendor: &vendor,
+        rent: &rent,
+        index,
+        amount,
+        nonce,
+        claimant_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        receipt_acc_info,
+        token_program_acc_info,
+        system_program_acc_info,
+    })?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: claim_merkle_reward");
+
+    let AccessControlRequest {
+        program_id,
+        claimant_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        receipt_acc_info,
+        token_program_acc_info,
+        system_program_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+        index,
+        amount,
+        proof,
+        nonce,
+    } = req;
+
+    // Claimant authorization.
+    if !claimant_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Vendor, which must be a Merkle vendor whose tree owes the claimant.
+    if vendor_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRewardVendor)?;
+    }
+    let vendor = RewardVendor::unpack(&vendor_acc_info.try_borrow_data()?)?;
+    if !vendor.initialized || vendor.merkle_root().is_none() {
+        return Err(RegistryErrorCode::InvalidRewardVendor)?;
+    }
+    let leaf = RewardVendor::merkle_leaf(index, claimant_acc_info.key, amount);
+    if !vendor.verify_merkle_proof(leaf, proof) {
+        return Err(RegistryErrorCode::InvalidMerkleProof)?;
+    }
+
+    // Claim receipt. Derived from the index, so the runtime refuses to
+    // create it, and so pay out the leaf, twice.
+    let index = index.to_le_bytes();
+    let nonce = [nonce];
+    let seeds = RewardVendor::claim_signer_seeds(vendor_acc_info.key, &index, &nonce);
+    let receipt_address = Pubkey::create_program_address(&seeds, program_id)
+        .map_err(|_| RegistryErrorCode::InvalidMerkleProof)?;
+    if receipt_address != *receipt_acc_info.key || receipt_acc_info.owner == program_id {
+        return Err(RegistryErrorCode::InvalidMerkleProof)?;
+    }
+
+    // Vendor vault to pay from, and the account to pay into, which must
+    // belong to the claimant.
+    if *vendor_vault_acc_info.key != vendor.vault {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    crate::vault::check_authority(
+        program_id,
+        vendor_acc_info.key,
+        vendor.nonce,
+        vault_authority_acc_info,
+    )?;
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let to = spl_token::state::Account::unpack(&token_acc_info.try_borrow_data()?)?;
+    if to.owner != *claimant_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // System program, creating the receipt.
+    if *system_program_acc_info.key != solana_sdk::system_program::id() {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+
+    // Clock, for the vendor's expiry.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+    if vendor.expired(clock.unix_timestamp) {
+        return Err(RegistryErrorCode::VendorExpired)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse { vendor, rent })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: claim_merkle_reward");
+
+    let StateTransitionRequest {
+        program_id,
+        vendor,
+        rent,
+        index,
+        amount,
+        nonce,
+        claimant_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        receipt_acc_info,
+        token_program_acc_info,
+        system_program_acc_info,
+    } = req;
+
+    // Create the empty receipt, paid for by the claimant.
+    {
+        info!("invoke system create account");
+
+        let index = index.to_le_bytes();
+        let nonce = [nonce];
+        let seeds = RewardVendor::claim_signer_seeds(vendor_acc_info.key, &index, &nonce);
+        let create_instruction = system_instruction::create_account(
+            claimant_acc_info.key,
+            receipt_acc_info.key,
+            rent.minimum_balance(0),
+            0,
+            program_id,
+        );
+        solana_sdk::program::invoke_signed(
+            &create_instruction,
+            &[
+                claimant_acc_info.clone(),
+                receipt_acc_info.clone(),
+                system_program_acc_info.clone(),
+            ],
+            &[&seeds],
+        )?;
+    }
+
+    crate::vault::transfer_out(
+        vendor_acc_info.key,
+        vendor.nonce,
+        vendor_vault_acc_info,
+        token_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        amount,
+    )?;
+
+    emit_event!(MerkleRewardClaimed {
+        vendor: *vendor_acc_info.key,
+        claimant: *claimant_acc_info.key,
+        index,
+        amount,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a, 'b> {
+    program_id: &'a Pubkey,
+    claimant_acc_info: &'a AccountInfo<'a>,
+    vendor_acc_info: &'a AccountInfo<'a>,
+    vendor_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    token_acc_info: &'a AccountInfo<'a>,
+    receipt_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    system_program_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+    index: u64,
+    amount: u64,
+    proof: &'b [[u8; 32]],
+    nonce: u8,
+}
+
+struct AccessControlResponse {
+    vendor: RewardVendor,
+    rent: Rent,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    program_id: &'a Pubkey,
+    vendor: &'b RewardVendor,
+    rent: &'b Rent,
+    index: u64,
+    amount: u64,
+    nonce: u8,
+    claimant_acc_info: &'a AccountInfo<'a>,
+    vendor_acc_info: &'a AccountInfo<'a>,
+    vendor_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    token_acc_info: &'a AccountInfo<'a>,
+    receipt_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    system_program_acc_info: &'a AccountInfo<'a>,
+}
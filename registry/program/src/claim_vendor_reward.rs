@@ -0,0 +1,218 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Member, RewardVendor};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::VendorRewardClaimed;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), RegistryError> {
+    info!("handler: claim_vendor_reward");
+
+    let acc_infos = &mut accounts.iter();
+
+    let member_authority_acc_info = next_account_info(acc_infos)?;
+    let member_acc_info = next_account_info(acc_infos)?;
+    let vendor_acc_info = next_account_info(acc_infos)?;
+    let vendor_vault_acc_info = next_account_info(acc_infos)?;
+    let vault_authority_acc_info = next_account_info(acc_infos)?;
+    let token_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { vendor, clock } = access_control(AccessControlRequest {
+        program_id,
+        member_authority_acc_info,
+        member_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        token_program_acc_info,
+        clock_acc_info,
+    })?;
+
+    Member::unpack_mut(
+        &mut member_acc_info.try_borrow_mut_data()?,
+        &mut |member: &mut Member| {
+            state_transition(StateTransitionRequest {
+                member,
+                vendor: &vendor,
+                member_acc_info,
+                vendor_acc_info,
+                vendor_vault_acc_info,
+                vault_authority_acc_info,
+                token_acc_info,
+                token_program_acc_info,
+                clock: &clock,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: claim_vendor_reward");
+
+    let AccessControlRequest {
+        program_id,
+        member_authority_acc_info,
+        member_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        token_program_acc_info,
+        clock_acc_info,
+    } = req;
+
+    // Beneficiary/delegate authorization.
+    if !member_authority_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member.beneficiary != *member_authority_acc_info.key
+        && member.delegate != *member_authority_acc_info.key
+    {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Vendor, which must be the next of the member's entity to claim. Merkle
+    // vendors are claimed with ClaimMerkleReward.
+    if vendor_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRewardVendor)?;
+    }
+    let vendor = RewardVendor::unpack(&vendor_acc_info.try_borrow_data()?)?;
+    if !vendor.initialized
+        || vendor.merkle_root().is_some()
+        || vendor.entity != member.entity
+        || vendor.cursor != member.rewards_cursor
+    {
+        return Err(RegistryErrorCode::InvalidRewardVendor)?;
+    }
+
+    // Vendor vault to pay from, and the account to pay into, which must
+    // belong to the signing beneficiary or delegate.
+    if *vendor_vault_acc_info.key != vendor.vault {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    crate::vault::check_authority(
+        program_id,
+        vendor_acc_info.key,
+        vendor.nonce,
+        vault_authority_acc_info,
+    )?;
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let to = spl_token::state::Account::unpack(&token_acc_info.try_borrow_data()?)?;
+    if to.owner != *member_authority_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Clock, for the vendor's expiry.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse { vendor, clock })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: claim_vendor_reward");
+
+    let StateTransitionRequest {
+        member,
+        vendor,
+        member_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        token_program_acc_info,
+        clock,
+    } = req;
+
+    // The member's stake hasn't changed since the drop, so its share is
+    // by its stake now. An expired vendor may have been reclaimed, so it
+    // pays nothing, but the member still moves past it.
+    let amount = if vendor.expired(clock.unix_timestamp) {
+        0
+    } else {
+        vendor.reward_share(member)
+    };
+    crate::vault::transfer_out(
+        vendor_acc_info.key,
+        vendor.nonce,
+        vendor_vault_acc_info,
+        token_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        amount,
+    )?;
+
+    member.rewards_cursor = member
+        .rewards_cursor
+        .checked_add(1)
+        .ok_or(RegistryErrorCode::Overflow)?;
+
+    emit_event!(VendorRewardClaimed {
+        vendor: *vendor_acc_info.key,
+        member: *member_acc_info.key,
+        amount,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    member_authority_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    vendor_acc_info: &'a AccountInfo<'a>,
+    vendor_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    token_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+}
+
+struct AccessControlResponse {
+    vendor: RewardVendor,
+    clock: Clock,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    member: &'b mut Member,
+    vendor: &'b RewardVendor,
+    member_acc_info: &'a AccountInfo<'a>,
+    vendor_acc_info: &'a AccountInfo<'a>,
+    vendor_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    token_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    clock: &'b Clock,
+}
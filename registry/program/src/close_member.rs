@@ -0,0 +1,107 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Member};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), RegistryError> {
+    info!("handler: close_member");
+
+    let acc_infos = &mut accounts.iter();
+
+    let member_acc_info = next_account_info(acc_infos)?;
+    let beneficiary_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        member_acc_info,
+        beneficiary_acc_info,
+        entity_acc_info,
+    })?;
+
+    Entity::unpack_mut(
+        &mut entity_acc_info.try_borrow_mut_data()?,
+        &mut |entity: &mut Entity| {
+            Member::unpack_mut(
+                &mut member_acc_info.try_borrow_mut_data()?,
+                &mut |member: &mut Member| {
+                    state_transition(StateTransitionRequest { member, entity }).map_err(Into::into)
+                },
+            )
+        },
+    )?;
+
+    crate::lamports::drain(member_acc_info, beneficiary_acc_info)?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: close_member");
+
+    let AccessControlRequest {
+        program_id,
+        member_acc_info,
+        beneficiary_acc_info,
+        entity_acc_info,
+    } = req;
+
+    if !beneficiary_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized || member.beneficiary != *beneficiary_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if !member.can_close() {
+        return Err(RegistryErrorCode::MemberNotEmpty)?;
+    }
+
+    // Entity, counting its members.
+    if entity_acc_info.owner != program_id || member.entity != *entity_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: close_member");
+
+    let StateTransitionRequest { member, entity } = req;
+
+    entity.did_leave();
+
+    // Zero the account so a closed member can't be read as initialized
+    // within the same transaction.
+    *member = Member::default();
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    member_acc_info: &'a AccountInfo<'a>,
+    beneficiary_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a> {
+    member: &'a mut Member,
+    entity: &'a mut Entity,
+}
@@ -0,0 +1,167 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Member, Registrar};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::RewardsCompounded;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), RegistryError> {
+    info!("handler: compound_rewards");
+
+    let acc_infos = &mut accounts.iter();
+
+    let member_authority_acc_info = next_account_info(acc_infos)?;
+    let member_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { registrar, clock } = access_control(AccessControlRequest {
+        program_id,
+        member_authority_acc_info,
+        member_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        clock_acc_info,
+    })?;
+
+    Entity::unpack_mut(
+        &mut entity_acc_info.try_borrow_mut_data()?,
+        &mut |entity: &mut Entity| {
+            Member::unpack_mut(
+                &mut member_acc_info.try_borrow_mut_data()?,
+                &mut |member: &mut Member| {
+                    state_transition(StateTransitionRequest {
+                        entity,
+                        member,
+                        member_acc_info,
+                        entity_acc_info,
+                        registrar: &registrar,
+                        clock: &clock,
+                    })
+                    .map_err(Into::into)
+                },
+            )
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: compound_rewards");
+
+    let AccessControlRequest {
+        program_id,
+        member_authority_acc_info,
+        member_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        clock_acc_info,
+    } = req;
+
+    // Beneficiary/delegate authorization.
+    if !member_authority_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member.beneficiary != *member_authority_acc_info.key
+        && member.delegate != *member_authority_acc_info.key
+    {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Entity.
+    if entity_acc_info.owner != program_id || member.entity != *entity_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+
+    // Registrar, whose SRM vault holds the rewards.
+    if registrar_acc_info.owner != program_id
+        || entity.registrar != *registrar_acc_info.key
+        || member.registrar != *registrar_acc_info.key
+    {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Clock, to bring the entity's reward stream up to date.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse { registrar, clock })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: compound_rewards");
+
+    let StateTransitionRequest {
+        entity,
+        member,
+        member_acc_info,
+        entity_acc_info,
+        registrar,
+        clock,
+    } = req;
+
+    entity.update_reward_index(clock.unix_timestamp)?;
+    let amount = member.compound_rewards(entity)?;
+    registrar.check_stake_cap(member.amount, member.mega_amount)?;
+
+    emit_event!(RewardsCompounded {
+        member: *member_acc_info.key,
+        entity: *entity_acc_info.key,
+        amount,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    member_authority_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+}
+
+struct AccessControlResponse {
+    registrar: Registrar,
+    clock: Clock,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    entity: &'b mut Entity,
+    member: &'b mut Member,
+    member_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar: &'b Registrar,
+    clock: &'b Clock,
+}
@@ -1,9 +1,12 @@
+use serum_common::compute_checkpoint;
 use serum_common::pack::Pack;
-use serum_registry::accounts::{Entity, StakeKind};
+use serum_registry::accounts::{Entity, Registrar, StakeKind};
 use serum_registry::error::{RegistryError, RegistryErrorCode};
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
 
 pub fn handler<'a>(
     program_id: &'a Pubkey,
@@ -17,11 +20,14 @@ pub fn handler<'a>(
 
     let entity_acc_info = next_account_info(acc_infos)?;
     let entity_leader_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
     let rent_acc_info = next_account_info(acc_infos)?;
 
     access_control(AccessControlRequest {
+        program_id,
         entity_acc_info,
         entity_leader_acc_info,
+        registrar_acc_info,
         rent_acc_info,
         stake_kind,
     })?;
@@ -31,6 +37,7 @@ pub fn handler<'a>(
         &mut |entity: &mut Entity| {
             state_transition(StateTransitionRequest {
                 leader: entity_leader_acc_info.key,
+                registrar: registrar_acc_info.key,
                 entity,
                 capabilities,
                 stake_kind,
@@ -46,8 +53,10 @@ fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
     info!("access-control: create_entity");
 
     let AccessControlRequest {
+        program_id,
         entity_acc_info,
         entity_leader_acc_info,
+        registrar_acc_info,
         rent_acc_info,
         stake_kind,
     } = req;
@@ -57,9 +66,40 @@ fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
         return Err(RegistryErrorCode::MustBeDelegated)?;
     }
 
-    // todo
+    // Leader authorization.
+    if !entity_leader_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Registrar.
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Entity.
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if entity.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(entity_acc_info.lamports(), entity_acc_info.try_data_len()?) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
     Ok(())
 }
@@ -70,6 +110,7 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
     let StateTransitionRequest {
         entity,
         leader,
+        registrar,
         capabilities,
         stake_kind,
     } = req;
@@ -80,15 +121,22 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
     entity.mega_amount = 0;
     entity.capabilities = capabilities;
     entity.stake_kind = stake_kind;
+    entity.registrar = *registrar;
+    entity.member_count = 0;
+    entity.max_members = 0;
+    entity.approve_joins = false;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
 
 struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
     entity_acc_info: &'a AccountInfo<'a>,
     entity_leader_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
     rent_acc_info: &'a AccountInfo<'a>,
     stake_kind: StakeKind,
 }
@@ -96,6 +144,7 @@ struct AccessControlRequest<'a> {
 struct StateTransitionRequest<'a, 'b> {
     entity: &'b mut Entity,
     leader: &'a Pubkey,
+    registrar: &'a Pubkey,
     capabilities: u32,
     stake_kind: StakeKind,
 }
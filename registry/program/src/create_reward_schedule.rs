@@ -0,0 +1,210 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Registrar, RewardSchedule};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::RewardScheduleCreated;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+use std::convert::TryFrom;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    amount: u64,
+    period_secs: u64,
+    start_ts: i64,
+    nonce: u8,
+) -> Result<(), RegistryError> {
+    info!("handler: create_reward_schedule");
+
+    let acc_infos = &mut accounts.iter();
+
+    let schedule_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        schedule_acc_info,
+        vault_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        rent_acc_info,
+        amount,
+        period_secs,
+        nonce,
+    })?;
+
+    RewardSchedule::unpack_mut(
+        &mut schedule_acc_info.try_borrow_mut_data()?,
+        &mut |schedule: &mut RewardSchedule| {
+            state_transition(StateTransitionRequest {
+                schedule,
+                amount,
+                period_secs,
+                start_ts,
+                nonce,
+                schedule_acc_info,
+                vault_acc_info,
+                entity_acc_info,
+                registrar_acc_info,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: create_reward_schedule");
+
+    let AccessControlRequest {
+        program_id,
+        schedule_acc_info,
+        vault_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        rent_acc_info,
+        amount,
+        period_secs,
+        nonce,
+    } = req;
+
+    // Registrar.
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Entity, which must be under the registrar.
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity.registrar != *registrar_acc_info.key {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Schedule account. Can only be initialized once, and must release
+    // something every period.
+    if schedule_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRewardSchedule)?;
+    }
+    let schedule = RewardSchedule::unpack(&schedule_acc_info.try_borrow_data()?)?;
+    if schedule.initialized {
+        return Err(RegistryErrorCode::InvalidRewardSchedule)?;
+    }
+    if amount == 0 || period_secs == 0 || i64::try_from(period_secs).is_err() {
+        return Err(RegistryErrorCode::InvalidRewardSchedule)?;
+    }
+
+    // Funding vault, which only the program can transfer out of. Its mint
+    // is checked by the token program when a tranche moves into the
+    // registrar's SRM vault.
+    if *vault_acc_info.owner != spl_token::ID {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    let vault = spl_token::state::Account::unpack(&vault_acc_info.try_borrow_data()?)?;
+    let vault_authority = Pubkey::create_program_address(
+        &RewardSchedule::signer_seeds(schedule_acc_info.key, &nonce),
+        program_id,
+    )
+    .map_err(|_| RegistryErrorCode::InvalidVault)?;
+    if vault.owner != vault_authority {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(
+        schedule_acc_info.lamports(),
+        schedule_acc_info.try_data_len()?,
+    ) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: create_reward_schedule");
+
+    let StateTransitionRequest {
+        schedule,
+        amount,
+        period_secs,
+        start_ts,
+        nonce,
+        schedule_acc_info,
+        vault_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+    } = req;
+
+    schedule.initialized = true;
+    schedule.entity = *entity_acc_info.key;
+    schedule.registrar = *registrar_acc_info.key;
+    schedule.vault = *vault_acc_info.key;
+    schedule.nonce = nonce;
+    schedule.amount = amount;
+    schedule.period_secs = period_secs;
+    schedule.next_ts = start_ts;
+    schedule.epoch = 0;
+
+    emit_event!(RewardScheduleCreated {
+        schedule: *schedule_acc_info.key,
+        entity: *entity_acc_info.key,
+        amount,
+        period_secs,
+        start_ts,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    schedule_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    amount: u64,
+    period_secs: u64,
+    nonce: u8,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    schedule: &'b mut RewardSchedule,
+    amount: u64,
+    period_secs: u64,
+    start_ts: i64,
+    nonce: u8,
+    schedule_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+}
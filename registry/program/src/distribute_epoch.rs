@@ -0,0 +1,270 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Member, Registrar, RewardSchedule};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::EpochDistributed;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::Sysvar;
+use std::convert::TryFrom;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), RegistryError> {
+    info!("handler: distribute_epoch");
+
+    let acc_infos = &mut accounts.iter();
+
+    let schedule_acc_info = next_account_info(acc_infos)?;
+    let schedule_vault_acc_info = next_account_info(acc_infos)?;
+    let schedule_vault_authority_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let leader_member_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse {
+        registrar,
+        clock,
+        amount,
+    } = access_control(AccessControlRequest {
+        program_id,
+        schedule_acc_info,
+        schedule_vault_acc_info,
+        schedule_vault_authority_acc_info,
+        vault_acc_info,
+        registrar_acc_info,
+        entity_acc_info,
+        leader_member_acc_info,
+        token_program_acc_info,
+        clock_acc_info,
+    })?;
+
+    RewardSchedule::unpack_mut(
+        &mut schedule_acc_info.try_borrow_mut_data()?,
+        &mut |schedule: &mut RewardSchedule| {
+            Entity::unpack_mut(
+                &mut entity_acc_info.try_borrow_mut_data()?,
+                &mut |entity: &mut Entity| {
+                    Member::unpack_mut(
+                        &mut leader_member_acc_info.try_borrow_mut_data()?,
+                        &mut |leader_member: &mut Member| {
+                            state_transition(StateTransitionRequest {
+                                schedule,
+                                entity,
+                                leader_member,
+                                amount,
+                                registrar: &registrar,
+                                clock: &clock,
+                                schedule_acc_info,
+                                schedule_vault_acc_info,
+                                schedule_vault_authority_acc_info,
+                                vault_acc_info,
+                                token_program_acc_info,
+                                entity_acc_info,
+                                leader_member_acc_info,
+                            })
+                            .map_err(Into::into)
+                        },
+                    )
+                },
+            )
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: distribute_epoch");
+
+    let AccessControlRequest {
+        program_id,
+        schedule_acc_info,
+        schedule_vault_acc_info,
+        schedule_vault_authority_acc_info,
+        vault_acc_info,
+        registrar_acc_info,
+        entity_acc_info,
+        leader_member_acc_info,
+        token_program_acc_info,
+        clock_acc_info,
+    } = req;
+
+    // Schedule.
+    if schedule_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRewardSchedule)?;
+    }
+    let schedule = RewardSchedule::unpack(&schedule_acc_info.try_borrow_data()?)?;
+    if !schedule.initialized
+        || schedule.entity != *entity_acc_info.key
+        || schedule.registrar != *registrar_acc_info.key
+    {
+        return Err(RegistryErrorCode::InvalidRewardSchedule)?;
+    }
+
+    // Registrar.
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    if registrar.paused {
+        return Err(RegistryErrorCode::Paused)?;
+    }
+
+    // Vaults, so the tranche can't be sent anywhere but the registrar's.
+    if *schedule_vault_acc_info.key != schedule.vault {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    let schedule_vault =
+        spl_token::state::Account::unpack(&schedule_vault_acc_info.try_borrow_data()?)?;
+    crate::vault::check_authority(
+        program_id,
+        schedule_acc_info.key,
+        schedule.nonce,
+        schedule_vault_authority_acc_info,
+    )?;
+    if *vault_acc_info.key != registrar.vault {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Entity and its leader's member account.
+    if entity_acc_info.owner != program_id || leader_member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let leader_member = Member::unpack(&leader_member_acc_info.try_borrow_data()?)?;
+    if !leader_member.initialized
+        || leader_member.entity != *entity_acc_info.key
+        || leader_member.beneficiary != entity.leader
+    {
+        return Err(RegistryErrorCode::InvalidLeaderMember)?;
+    }
+
+    // Clock, as the tranche must be due.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+    if !schedule.is_due(clock.unix_timestamp) {
+        return Err(RegistryErrorCode::EpochNotDue)?;
+    }
+    let amount = schedule.tranche(schedule_vault.amount);
+    if amount == 0 {
+        return Err(RegistryErrorCode::RewardScheduleEmpty)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse {
+        registrar,
+        clock,
+        amount,
+    })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: distribute_epoch");
+
+    let StateTransitionRequest {
+        schedule,
+        entity,
+        leader_member,
+        amount,
+        registrar,
+        clock,
+        schedule_acc_info,
+        schedule_vault_acc_info,
+        schedule_vault_authority_acc_info,
+        vault_acc_info,
+        token_program_acc_info,
+        entity_acc_info,
+        leader_member_acc_info,
+    } = req;
+
+    crate::vault::transfer_out(
+        schedule_acc_info.key,
+        schedule.nonce,
+        schedule_vault_acc_info,
+        vault_acc_info,
+        schedule_vault_authority_acc_info,
+        token_program_acc_info,
+        amount,
+    )?;
+
+    // Drop the tranche as DropEntityReward would.
+    entity.apply_pending_commission(clock.unix_timestamp);
+    let reward_period_secs =
+        i64::try_from(registrar.reward_period_secs).map_err(|_| RegistryErrorCode::Overflow)?;
+    let commission = entity.stream_reward(amount, clock.unix_timestamp, reward_period_secs)?;
+    leader_member.did_earn(commission)?;
+
+    let epoch = schedule.epoch;
+    schedule.did_distribute()?;
+
+    emit_event!(EpochDistributed {
+        schedule: *schedule_acc_info.key,
+        entity: *entity_acc_info.key,
+        leader_member: *leader_member_acc_info.key,
+        epoch,
+        amount,
+        commission,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    schedule_acc_info: &'a AccountInfo<'a>,
+    schedule_vault_acc_info: &'a AccountInfo<'a>,
+    schedule_vault_authority_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    leader_member_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+}
+
+struct AccessControlResponse {
+    registrar: Registrar,
+    clock: Clock,
+    amount: u64,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    schedule: &'b mut RewardSchedule,
+    entity: &'b mut Entity,
+    leader_member: &'b mut Member,
+    amount: u64,
+    registrar: &'b Registrar,
+    clock: &'b Clock,
+    schedule_acc_info: &'a AccountInfo<'a>,
+    schedule_vault_acc_info: &'a AccountInfo<'a>,
+    schedule_vault_authority_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    leader_member_acc_info: &'a AccountInfo<'a>,
+}
@@ -1,3 +1,4 @@
+use serum_common::compute_checkpoint;
 use serum_registry::error::{RegistryError, RegistryErrorCode};
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
@@ -52,6 +53,7 @@ fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), RegistryError
     // todo
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
     Ok(())
 }
@@ -90,6 +92,7 @@ fn state_transition<'a>(req: StateTransitionRequest<'a>) -> Result<(), RegistryE
     )?;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
@@ -0,0 +1,218 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Member, Registrar};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::EntityRewardDropped;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::Sysvar;
+use std::convert::TryFrom;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    amount: u64,
+) -> Result<(), RegistryError> {
+    info!("handler: drop_entity_reward");
+
+    let acc_infos = &mut accounts.iter();
+
+    let donator_authority_acc_info = next_account_info(acc_infos)?;
+    let donator_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let registry_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let leader_member_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { registrar, clock } = access_control(AccessControlRequest {
+        program_id,
+        vault_acc_info,
+        registry_acc_info,
+        token_program_acc_info,
+        entity_acc_info,
+        leader_member_acc_info,
+        clock_acc_info,
+    })?;
+
+    Entity::unpack_mut(
+        &mut entity_acc_info.try_borrow_mut_data()?,
+        &mut |entity: &mut Entity| {
+            Member::unpack_mut(
+                &mut leader_member_acc_info.try_borrow_mut_data()?,
+                &mut |leader_member: &mut Member| {
+                    state_transition(StateTransitionRequest {
+                        entity,
+                        leader_member,
+                        amount,
+                        registrar: &registrar,
+                        clock: &clock,
+                        donator_authority_acc_info,
+                        donator_acc_info,
+                        vault_acc_info,
+                        token_program_acc_info,
+                        entity_acc_info,
+                        leader_member_acc_info,
+                    })
+                    .map_err(Into::into)
+                },
+            )
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: drop_entity_reward");
+
+    let AccessControlRequest {
+        program_id,
+        vault_acc_info,
+        registry_acc_info,
+        token_program_acc_info,
+        entity_acc_info,
+        leader_member_acc_info,
+        clock_acc_info,
+    } = req;
+
+    // Registrar.
+    if registry_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registry_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    if registrar.paused {
+        return Err(RegistryErrorCode::Paused)?;
+    }
+
+    // Vault, so the reward can't be sent anywhere but the registrar's.
+    if *vault_acc_info.key != registrar.vault {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Entity and its leader's member account.
+    if entity_acc_info.owner != program_id || leader_member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity.registrar != *registry_acc_info.key {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let leader_member = Member::unpack(&leader_member_acc_info.try_borrow_data()?)?;
+    if !leader_member.initialized
+        || leader_member.entity != *entity_acc_info.key
+        || leader_member.beneficiary != entity.leader
+    {
+        return Err(RegistryErrorCode::InvalidLeaderMember)?;
+    }
+
+    // Clock, for the reward stream and a commission raise that has come due.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse { registrar, clock })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: drop_entity_reward");
+
+    let StateTransitionRequest {
+        entity,
+        leader_member,
+        amount,
+        registrar,
+        clock,
+        donator_authority_acc_info,
+        donator_acc_info,
+        vault_acc_info,
+        token_program_acc_info,
+        entity_acc_info,
+        leader_member_acc_info,
+    } = req;
+
+    info!("invoke SPL token transfer");
+
+    let transfer_instruction = spl_token::instruction::transfer(
+        &spl_token::ID,
+        donator_acc_info.key,
+        vault_acc_info.key,
+        donator_authority_acc_info.key,
+        &[],
+        amount,
+    )?;
+    solana_sdk::program::invoke_signed(
+        &transfer_instruction,
+        &[
+            donator_acc_info.clone(),
+            donator_authority_acc_info.clone(),
+            vault_acc_info.clone(),
+            token_program_acc_info.clone(),
+        ],
+        &[],
+    )?;
+
+    entity.apply_pending_commission(clock.unix_timestamp);
+    let reward_period_secs =
+        i64::try_from(registrar.reward_period_secs).map_err(|_| RegistryErrorCode::Overflow)?;
+    let commission = entity.stream_reward(amount, clock.unix_timestamp, reward_period_secs)?;
+    leader_member.did_earn(commission)?;
+
+    emit_event!(EntityRewardDropped {
+        entity: *entity_acc_info.key,
+        leader_member: *leader_member_acc_info.key,
+        amount,
+        commission,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    vault_acc_info: &'a AccountInfo<'a>,
+    registry_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    leader_member_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+}
+
+struct AccessControlResponse {
+    registrar: Registrar,
+    clock: Clock,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    entity: &'b mut Entity,
+    leader_member: &'b mut Member,
+    amount: u64,
+    registrar: &'b Registrar,
+    clock: &'b Clock,
+    donator_authority_acc_info: &'a AccountInfo<'a>,
+    donator_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    leader_member_acc_info: &'a AccountInfo<'a>,
+}
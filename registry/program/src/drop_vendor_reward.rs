@@ -0,0 +1,307 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Registrar, RewardVendor};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::VendorRewardDropped;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    amount: u64,
+    nonce: u8,
+    merkle_root: [u8; 32],
+    expiry_ts: i64,
+    expiry_receiver: Pubkey,
+) -> Result<(), RegistryError> {
+    info!("handler: drop_vendor_reward");
+
+    let acc_infos = &mut accounts.iter();
+
+    let vendor_acc_info = next_account_info(acc_infos)?;
+    let vendor_vault_acc_info = next_account_info(acc_infos)?;
+    let depositor_authority_acc_info = next_account_info(acc_infos)?;
+    let depositor_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+    let authority_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { mint } = access_control(AccessControlRequest {
+        program_id,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        token_program_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+        authority_acc_info,
+        amount,
+        nonce,
+        merkle_root,
+        expiry_ts,
+    })?;
+
+    RewardVendor::unpack_mut(
+        &mut vendor_acc_info.try_borrow_mut_data()?,
+        &mut |vendor: &mut RewardVendor| {
+            Entity::unpack_mut(
+                &mut entity_acc_info.try_borrow_mut_data()?,
+                &mut |entity: &mut Entity| {
+                    state_transition(StateTransitionRequest {
+                        vendor,
+                        entity,
+                        amount,
+                        nonce,
+                        merkle_root,
+                        expiry_ts,
+                        expiry_receiver,
+                        mint,
+                        vendor_acc_info,
+                        vendor_vault_acc_info,
+                        depositor_authority_acc_info,
+                        depositor_acc_info,
+                        entity_acc_info,
+                        token_program_acc_info,
+                    })
+                    .map_err(Into::into)
+                },
+            )
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: drop_vendor_reward");
+
+    let AccessControlRequest {
+        program_id,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        token_program_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+        authority_acc_info,
+        amount,
+        nonce,
+        merkle_root,
+        expiry_ts,
+    } = req;
+
+    // Registrar, which takes no drops while paused.
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    if registrar.paused {
+        return Err(RegistryErrorCode::Paused)?;
+    }
+
+    // Entity, with stake to share the reward by, unless the Merkle root
+    // says who's owed what.
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity.registrar != *registrar_acc_info.key {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    if merkle_root == [0; 32] && entity.stake_weight() == 0 {
+        return Err(RegistryErrorCode::NothingStaked)?;
+    }
+
+    // Authorization. Every member has to claim each vendor shared by stake,
+    // so only the registrar's authority or the entity's leader can drop one,
+    // and it must reward something.
+    if !authority_acc_info.is_signer
+        || (*authority_acc_info.key != registrar.authority
+            && *authority_acc_info.key != entity.leader)
+    {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if amount == 0 {
+        return Err(RegistryErrorCode::EmptyReward)?;
+    }
+
+    // Vendor account. Can only be initialized once.
+    if vendor_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRewardVendor)?;
+    }
+    let vendor = RewardVendor::unpack(&vendor_acc_info.try_borrow_data()?)?;
+    if vendor.initialized {
+        return Err(RegistryErrorCode::InvalidRewardVendor)?;
+    }
+
+    // Vendor vault, of any mint, which only the program can transfer out of.
+    if *vendor_vault_acc_info.owner != spl_token::ID {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    let vendor_vault =
+        spl_token::state::Account::unpack(&vendor_vault_acc_info.try_borrow_data()?)?;
+    let vault_authority = Pubkey::create_program_address(
+        &RewardVendor::signer_seeds(vendor_acc_info.key, &nonce),
+        program_id,
+    )
+    .map_err(|_| RegistryErrorCode::InvalidVault)?;
+    if vendor_vault.owner != vault_authority {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(vendor_acc_info.lamports(), vendor_acc_info.try_data_len()?) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
+
+    // Clock, so the vendor isn't dropped already expired.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+    if clock.unix_timestamp >= expiry_ts {
+        return Err(RegistryErrorCode::VendorExpired)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse {
+        mint: vendor_vault.mint,
+    })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: drop_vendor_reward");
+
+    let StateTransitionRequest {
+        vendor,
+        entity,
+        amount,
+        nonce,
+        merkle_root,
+        expiry_ts,
+        expiry_receiver,
+        mint,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        depositor_authority_acc_info,
+        depositor_acc_info,
+        entity_acc_info,
+        token_program_acc_info,
+    } = req;
+
+    // Transfer the reward into the vendor's vault.
+    {
+        info!("invoke SPL token transfer");
+
+        let transfer_instruction = spl_token::instruction::transfer(
+            &spl_token::ID,
+            depositor_acc_info.key,
+            vendor_vault_acc_info.key,
+            depositor_authority_acc_info.key,
+            &[],
+            amount,
+        )?;
+        solana_sdk::program::invoke_signed(
+            &transfer_instruction,
+            &[
+                depositor_acc_info.clone(),
+                depositor_authority_acc_info.clone(),
+                vendor_vault_acc_info.clone(),
+                token_program_acc_info.clone(),
+            ],
+            &[],
+        )?;
+    }
+
+    vendor.initialized = true;
+    vendor.entity = *entity_acc_info.key;
+    vendor.vault = *vendor_vault_acc_info.key;
+    vendor.mint = mint;
+    vendor.nonce = nonce;
+    vendor.total = amount;
+    vendor.merkle_root = merkle_root;
+    vendor.expiry_ts = expiry_ts;
+    vendor.expiry_receiver = expiry_receiver;
+    // Only vendors shared by stake are claimed in order, so a Merkle vendor
+    // doesn't hold up the members' stake.
+    if vendor.merkle_root().is_none() {
+        vendor.cursor = entity.did_drop_vendor()?;
+        vendor.stake_weight = entity.stake_weight();
+    }
+
+    emit_event!(VendorRewardDropped {
+        vendor: *vendor_acc_info.key,
+        entity: *entity_acc_info.key,
+        mint,
+        amount,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    vendor_acc_info: &'a AccountInfo<'a>,
+    vendor_vault_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+    authority_acc_info: &'a AccountInfo<'a>,
+    amount: u64,
+    nonce: u8,
+    merkle_root: [u8; 32],
+    expiry_ts: i64,
+}
+
+struct AccessControlResponse {
+    mint: Pubkey,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    vendor: &'b mut RewardVendor,
+    entity: &'b mut Entity,
+    amount: u64,
+    nonce: u8,
+    merkle_root: [u8; 32],
+    expiry_ts: i64,
+    expiry_receiver: Pubkey,
+    mint: Pubkey,
+    vendor_acc_info: &'a AccountInfo<'a>,
+    vendor_vault_acc_info: &'a AccountInfo<'a>,
+    depositor_authority_acc_info: &'a AccountInfo<'a>,
+    depositor_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+}
@@ -1,43 +1,271 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Member, PendingWithdrawal, Registrar};
 use serum_registry::error::{RegistryError, RegistryErrorCode};
-use solana_sdk::account_info::AccountInfo;
+use serum_registry::events::StakeWithdrawalEnded;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
 use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::Sysvar;
 
 pub fn handler<'a>(
     program_id: &'a Pubkey,
     accounts: &'a [AccountInfo<'a>],
 ) -> Result<(), RegistryError> {
-    // todo
     info!("handler: complete_stake_withdrawl");
 
-    access_control(AccessControlRequest {})?;
+    let acc_infos = &mut accounts.iter();
 
-    state_transition(StateTransitionRequest {})
-}
+    let pending_withdrawal_acc_info = next_account_info(acc_infos)?;
+    let member_authority_acc_info = next_account_info(acc_infos)?;
+    let member_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let mega_vault_acc_info = next_account_info(acc_infos)?;
+    let vault_authority_acc_info = next_account_info(acc_infos)?;
+    let srm_acc_info = next_account_info(acc_infos)?;
+    let msrm_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { registrar } = access_control(AccessControlRequest {
+        program_id,
+        pending_withdrawal_acc_info,
+        member_authority_acc_info,
+        member_acc_info,
+        entity_acc_info,
+        clock_acc_info,
+        token_program_acc_info,
+        registrar_acc_info,
+        vault_acc_info,
+        mega_vault_acc_info,
+        vault_authority_acc_info,
+        srm_acc_info,
+        msrm_acc_info,
+    })?;
+
+    PendingWithdrawal::unpack_mut(
+        &mut pending_withdrawal_acc_info.try_borrow_mut_data()?,
+        &mut |pending_withdrawal: &mut PendingWithdrawal| {
+            Member::unpack_mut(
+                &mut member_acc_info.try_borrow_mut_data()?,
+                &mut |member: &mut Member| {
+                    state_transition(StateTransitionRequest {
+                        pending_withdrawal,
+                        member,
+                        registrar: &registrar,
+                        pending_withdrawal_acc_info,
+                        token_program_acc_info,
+                        registrar_acc_info,
+                        vault_acc_info,
+                        mega_vault_acc_info,
+                        vault_authority_acc_info,
+                        srm_acc_info,
+                        msrm_acc_info,
+                    })
+                    .map_err(Into::into)
+                },
+            )
+        },
+    )?;
 
-fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
-    // todo
+    Ok(())
+}
 
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
     info!("access-control: complete_stake_withdrawal");
 
-    let AccessControlRequest {} = req;
+    let AccessControlRequest {
+        program_id,
+        pending_withdrawal_acc_info,
+        member_authority_acc_info,
+        member_acc_info,
+        entity_acc_info,
+        clock_acc_info,
+        token_program_acc_info,
+        registrar_acc_info,
+        vault_acc_info,
+        mega_vault_acc_info,
+        vault_authority_acc_info,
+        srm_acc_info,
+        msrm_acc_info,
+    } = req;
+
+    // Beneficiary/delegate authorization.
+    if !member_authority_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member.beneficiary != *member_authority_acc_info.key
+        && member.delegate != *member_authority_acc_info.key
+    {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Entity. The member's, even if it has since migrated away.
+    if entity_acc_info.owner != program_id || member.entity != *entity_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Pending withdrawal account.
+    if pending_withdrawal_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidPendingWithdrawal)?;
+    }
+    let pending_withdrawal =
+        PendingWithdrawal::unpack(&pending_withdrawal_acc_info.try_borrow_data()?)?;
+    if !pending_withdrawal.initialized
+        || pending_withdrawal.burned
+        || pending_withdrawal.member != *member_acc_info.key
+    {
+        return Err(RegistryErrorCode::InvalidPendingWithdrawal)?;
+    }
+
+    // Timelock. Enforced whatever the state of the member's entity.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+    if !pending_withdrawal.can_complete(clock.unix_timestamp) {
+        return Err(RegistryErrorCode::WithdrawalTimelockNotPassed)?;
+    }
+
+    // Registrar the stake was deposited under. A member keeps it on
+    // migrating, so withdrawals pending since are paid by the old one.
+    if registrar_acc_info.owner != program_id || member.registrar != *registrar_acc_info.key {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Vaults to pay from, and the accounts to pay into, which must belong
+    // to the signing beneficiary or delegate.
+    if *vault_acc_info.key != registrar.vault || *mega_vault_acc_info.key != registrar.mega_vault {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    crate::vault::check_authority(
+        program_id,
+        registrar_acc_info.key,
+        registrar.nonce,
+        vault_authority_acc_info,
+    )?;
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    for (amount, to_acc_info) in &[
+        (pending_withdrawal.amount, srm_acc_info),
+        (pending_withdrawal.mega_amount, msrm_acc_info),
+    ] {
+        if *amount == 0 {
+            continue;
+        }
+        let to = spl_token::state::Account::unpack(&to_acc_info.try_borrow_data()?)?;
+        if to.owner != *member_authority_acc_info.key {
+            return Err(RegistryErrorCode::Unauthorized)?;
+        }
+    }
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
-    Ok(())
+    Ok(AccessControlResponse { registrar })
 }
 
 fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
-    // todo
     info!("state-transition: complete_stake_withdrawal");
 
-    let StateTransitionRequest {} = req;
+    let StateTransitionRequest {
+        pending_withdrawal,
+        member,
+        registrar,
+        pending_withdrawal_acc_info,
+        token_program_acc_info,
+        registrar_acc_info,
+        vault_acc_info,
+        mega_vault_acc_info,
+        vault_authority_acc_info,
+        srm_acc_info,
+        msrm_acc_info,
+    } = req;
+
+    // Transfer funds out of the registrar's vaults. Exactly what was set
+    // aside when the withdrawal started, whatever is staked since.
+    crate::vault::transfer_out(
+        registrar_acc_info.key,
+        registrar.nonce,
+        vault_acc_info,
+        srm_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        pending_withdrawal.amount,
+    )?;
+    crate::vault::transfer_out(
+        registrar_acc_info.key,
+        registrar.nonce,
+        mega_vault_acc_info,
+        msrm_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        pending_withdrawal.mega_amount,
+    )?;
+
+    pending_withdrawal.burned = true;
+    member.did_end_withdrawal()?;
+
+    emit_event!(StakeWithdrawalEnded {
+        member: pending_withdrawal.member,
+        pending_withdrawal: *pending_withdrawal_acc_info.key,
+        amount: pending_withdrawal.amount,
+        mega_amount: pending_withdrawal.mega_amount,
+    })?;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
 
-struct AccessControlRequest {}
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    pending_withdrawal_acc_info: &'a AccountInfo<'a>,
+    member_authority_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    mega_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    srm_acc_info: &'a AccountInfo<'a>,
+    msrm_acc_info: &'a AccountInfo<'a>,
+}
 
-struct StateTransitionRequest {}
+struct AccessControlResponse {
+    registrar: Registrar,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    pending_withdrawal: &'b mut PendingWithdrawal,
+    member: &'b mut Member,
+    registrar: &'b Registrar,
+    pending_withdrawal_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    mega_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    srm_acc_info: &'a AccountInfo<'a>,
+    msrm_acc_info: &'a AccountInfo<'a>,
+}
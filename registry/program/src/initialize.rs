@@ -1,26 +1,40 @@
+use serum_common::compute_checkpoint;
 use serum_common::pack::Pack;
-use serum_registry::accounts::{registrar, Registrar};
+use serum_registry::accounts::Registrar;
 use serum_registry::error::{RegistryError, RegistryErrorCode};
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+use std::convert::TryFrom;
 
 pub fn handler<'a>(
     program_id: &'a Pubkey,
     accounts: &'a [AccountInfo<'a>],
     authority: Pubkey,
-    withdrawal_timelock: u64,
+    withdrawal_timelock_secs: u64,
+    nonce: u8,
+    reward_period_secs: u64,
 ) -> Result<(), RegistryError> {
     info!("handler: initialize");
 
     let acc_infos = &mut accounts.iter();
 
     let registrar_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let mega_vault_acc_info = next_account_info(acc_infos)?;
     let rent_acc_info = next_account_info(acc_infos)?;
 
     access_control(AccessControlRequest {
+        program_id,
         registrar_acc_info,
+        vault_acc_info,
+        mega_vault_acc_info,
         rent_acc_info,
+        nonce,
+        reward_period_secs,
     })?;
 
     Registrar::unpack_mut(
@@ -29,7 +43,11 @@ pub fn handler<'a>(
             state_transition(StateTransitionRequest {
                 registrar,
                 authority,
-                withdrawal_timelock,
+                withdrawal_timelock_secs,
+                vault: *vault_acc_info.key,
+                mega_vault: *mega_vault_acc_info.key,
+                nonce,
+                reward_period_secs,
             })
             .map_err(Into::into)
         },
@@ -42,13 +60,66 @@ fn access_control<'a>(req: AccessControlRequest<'a>) -> Result<(), RegistryError
     info!("access-control: initialize");
 
     let AccessControlRequest {
+        program_id,
         registrar_acc_info,
+        vault_acc_info,
+        mega_vault_acc_info,
         rent_acc_info,
+        nonce,
+        reward_period_secs,
     } = req;
 
-    // todo
+    // Registrar. Can only be initialized once, or anyone could hand it to
+    // themselves.
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Vaults, which only the program can transfer out of. Distinct mints,
+    // so MSRM can't be paid out for SRM.
+    let vault_authority = Pubkey::create_program_address(
+        &Registrar::signer_seeds(registrar_acc_info.key, &nonce),
+        program_id,
+    )
+    .map_err(|_| RegistryErrorCode::InvalidVault)?;
+    let mut mints = vec![];
+    for vault_acc_info in &[vault_acc_info, mega_vault_acc_info] {
+        if *vault_acc_info.owner != spl_token::ID {
+            return Err(RegistryErrorCode::InvalidVault)?;
+        }
+        let vault = spl_token::state::Account::unpack(&vault_acc_info.try_borrow_data()?)?;
+        if vault.owner != vault_authority {
+            return Err(RegistryErrorCode::InvalidVault)?;
+        }
+        mints.push(vault.mint);
+    }
+    if mints[0] == mints[1] {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+
+    // Reward period, which must fit a unix timestamp.
+    if i64::try_from(reward_period_secs).is_err() {
+        return Err(RegistryErrorCode::Overflow)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(
+        registrar_acc_info.lamports(),
+        registrar_acc_info.try_data_len()?,
+    ) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
     Ok(())
 }
@@ -59,26 +130,46 @@ fn state_transition<'a>(req: StateTransitionRequest<'a>) -> Result<(), RegistryE
     let StateTransitionRequest {
         registrar,
         authority,
-        withdrawal_timelock,
+        withdrawal_timelock_secs,
+        vault,
+        mega_vault,
+        nonce,
+        reward_period_secs,
     } = req;
 
     registrar.initialized = true;
     registrar.capabilities_fees_bps = [0; 32];
     registrar.authority = authority;
-    registrar.withdrawal_timelock = withdrawal_timelock;
+    registrar.withdrawal_timelock = 0;
+    registrar.withdrawal_timelock_secs = withdrawal_timelock_secs;
+    registrar.vault = vault;
+    registrar.nonce = nonce;
+    registrar.paused = false;
+    registrar.mega_vault = mega_vault;
+    registrar.reward_period_secs = reward_period_secs;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
 
 struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
     registrar_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    mega_vault_acc_info: &'a AccountInfo<'a>,
     rent_acc_info: &'a AccountInfo<'a>,
+    nonce: u8,
+    reward_period_secs: u64,
 }
 
 struct StateTransitionRequest<'a> {
     registrar: &'a mut Registrar,
     authority: Pubkey,
-    withdrawal_timelock: u64,
+    withdrawal_timelock_secs: u64,
+    vault: Pubkey,
+    mega_vault: Pubkey,
+    nonce: u8,
+    reward_period_secs: u64,
 }
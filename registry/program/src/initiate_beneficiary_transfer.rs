@@ -0,0 +1,133 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{BeneficiaryTransfer, Member};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    new_beneficiary: Pubkey,
+) -> Result<(), RegistryError> {
+    info!("handler: initiate_beneficiary_transfer");
+
+    let acc_infos = &mut accounts.iter();
+
+    let transfer_acc_info = next_account_info(acc_infos)?;
+    let member_acc_info = next_account_info(acc_infos)?;
+    let beneficiary_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        member_acc_info,
+        beneficiary_acc_info,
+        rent_acc_info,
+    })?;
+
+    BeneficiaryTransfer::unpack_mut(
+        &mut transfer_acc_info.try_borrow_mut_data()?,
+        &mut |transfer: &mut BeneficiaryTransfer| {
+            state_transition(StateTransitionRequest {
+                transfer,
+                member: *member_acc_info.key,
+                beneficiary: *beneficiary_acc_info.key,
+                new_beneficiary,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: initiate_beneficiary_transfer");
+
+    let AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        member_acc_info,
+        beneficiary_acc_info,
+        rent_acc_info,
+    } = req;
+
+    // Beneficiary authorization.
+    if !beneficiary_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized || member.beneficiary != *beneficiary_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Transfer account.
+    if transfer_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+    let transfer = BeneficiaryTransfer::unpack(&transfer_acc_info.try_borrow_data()?)?;
+    if transfer.initialized {
+        return Err(RegistryErrorCode::InvalidBeneficiaryTransfer)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(
+        transfer_acc_info.lamports(),
+        transfer_acc_info.try_data_len()?,
+    ) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: initiate_beneficiary_transfer");
+
+    let StateTransitionRequest {
+        transfer,
+        member,
+        beneficiary,
+        new_beneficiary,
+    } = req;
+
+    transfer.initialized = true;
+    transfer.member = member;
+    transfer.beneficiary = beneficiary;
+    transfer.new_beneficiary = new_beneficiary;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    beneficiary_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a> {
+    transfer: &'a mut BeneficiaryTransfer,
+    member: Pubkey,
+    beneficiary: Pubkey,
+    new_beneficiary: Pubkey,
+}
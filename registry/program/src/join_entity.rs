@@ -1,9 +1,12 @@
+use serum_common::compute_checkpoint;
 use serum_common::pack::Pack;
-use serum_registry::accounts::Member;
+use serum_registry::accounts::{Entity, Member};
 use serum_registry::error::{RegistryError, RegistryErrorCode};
 use solana_sdk::account_info::{next_account_info, AccountInfo};
 use solana_sdk::info;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
 
 pub fn handler<'a>(
     program_id: &'a Pubkey,
@@ -19,22 +22,38 @@ pub fn handler<'a>(
     let entity_acc_info = next_account_info(acc_infos)?;
     let rent_acc_info = next_account_info(acc_infos)?;
 
+    // The leader signs only for entities approving their members.
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    let entity_leader_acc_info = if entity.approve_joins {
+        Some(next_account_info(acc_infos)?)
+    } else {
+        None
+    };
+
     access_control(AccessControlRequest {
+        program_id,
         member_acc_info,
         entity_acc_info,
         rent_acc_info,
+        entity_leader_acc_info,
     })?;
 
-    Member::unpack_mut(
-        &mut member_acc_info.try_borrow_mut_data()?,
-        &mut |member: &mut Member| {
-            state_transition(StateTransitionRequest {
-                member,
-                beneficiary,
-                delegate,
-                entity_acc_info,
-            })
-            .map_err(Into::into)
+    Entity::unpack_mut(
+        &mut entity_acc_info.try_borrow_mut_data()?,
+        &mut |entity: &mut Entity| {
+            Member::unpack_mut(
+                &mut member_acc_info.try_borrow_mut_data()?,
+                &mut |member: &mut Member| {
+                    state_transition(StateTransitionRequest {
+                        member,
+                        entity,
+                        beneficiary,
+                        delegate,
+                        entity_acc_info,
+                    })
+                    .map_err(Into::into)
+                },
+            )
         },
     )?;
 
@@ -45,14 +64,50 @@ fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
     info!("access-control: join_entity");
 
     let AccessControlRequest {
+        program_id,
         member_acc_info,
         entity_acc_info,
         rent_acc_info,
+        entity_leader_acc_info,
     } = req;
 
-    // todo
+    // Entity.
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity.is_full() {
+        return Err(RegistryErrorCode::EntityFull)?;
+    }
+    if let Some(entity_leader_acc_info) = entity_leader_acc_info {
+        if !entity_leader_acc_info.is_signer || entity.leader != *entity_leader_acc_info.key {
+            return Err(RegistryErrorCode::Unauthorized)?;
+        }
+    }
+
+    // Member.
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if member.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(member_acc_info.lamports(), member_acc_info.try_data_len()?) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
     Ok(())
 }
@@ -62,31 +117,45 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
 
     let StateTransitionRequest {
         member,
+        entity,
         beneficiary,
         delegate,
         entity_acc_info,
     } = req;
 
+    entity.did_join()?;
+
     member.initialized = true;
     member.entity = *entity_acc_info.key;
     member.beneficiary = beneficiary;
     member.delegate = delegate;
     member.amount = 0;
     member.mega_amount = 0;
+    member.memo = [0; 32];
+    // Rewards dropped before joining aren't the member's.
+    member.rewards = 0;
+    member.reward_index = entity.reward_index;
+    member.registrar = entity.registrar;
+    member.pending_withdrawals = 0;
+    member.rewards_cursor = entity.vendor_count;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
 
 struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
     member_acc_info: &'a AccountInfo<'a>,
     entity_acc_info: &'a AccountInfo<'a>,
     rent_acc_info: &'a AccountInfo<'a>,
+    entity_leader_acc_info: Option<&'a AccountInfo<'a>>,
 }
 
 struct StateTransitionRequest<'a, 'b> {
     member: &'b mut Member,
+    entity: &'b mut Entity,
     beneficiary: Pubkey,
     delegate: Pubkey,
     entity_acc_info: &'a AccountInfo<'a>,
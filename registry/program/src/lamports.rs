@@ -0,0 +1,15 @@
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::AccountInfo;
+
+/// Moves every lamport in `src_acc_info` to `dst_acc_info`. With no
+/// lamports left, the runtime reclaims `src_acc_info` at the end of the
+/// transaction.
+pub fn drain(src_acc_info: &AccountInfo, dst_acc_info: &AccountInfo) -> Result<(), RegistryError> {
+    let dst_lamports = dst_acc_info
+        .lamports()
+        .checked_add(src_acc_info.lamports())
+        .ok_or(RegistryErrorCode::Overflow)?;
+    **src_acc_info.try_borrow_mut_lamports()? = 0;
+    **dst_acc_info.try_borrow_mut_lamports()? = dst_lamports;
+    Ok(())
+}
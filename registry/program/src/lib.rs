@@ -2,6 +2,7 @@
 
 #![cfg_attr(feature = "strict", deny(warnings))]
 
+use serum_common::compute_checkpoint;
 use serum_common::pack::Pack;
 use serum_registry::error::{RegistryError, RegistryErrorCode};
 use serum_registry::instruction::RegistryInstruction;
@@ -10,15 +11,41 @@ use solana_sdk::entrypoint::ProgramResult;
 use solana_sdk::info;
 use solana_sdk::pubkey::Pubkey;
 
+mod accept_beneficiary_transfer;
+mod accept_entity_leader;
+mod cancel_beneficiary_transfer;
+mod cancel_entity_leader;
+mod claim_merkle_reward;
+mod claim_vendor_reward;
+mod close_member;
+mod compound_rewards;
 mod create_entity;
+mod create_reward_schedule;
+mod distribute_epoch;
 mod donate;
+mod drop_entity_reward;
+mod drop_vendor_reward;
 mod end_stake_withdrawal;
 mod initialize;
+mod initiate_beneficiary_transfer;
 mod join_entity;
+mod lamports;
+mod migrate_member;
+mod pause;
+mod propose_entity_leader;
+mod reclaim_vendor;
 mod register_capability;
+mod set_entity_commission;
+mod set_entity_join_rules;
+mod snapshot_entity_stake;
 mod stake;
 mod start_stake_withdrawal;
 mod update_entity;
+mod update_entity_metadata;
+mod update_member_memo;
+mod update_registrar;
+mod update_voter_weight_record;
+mod vault;
 
 solana_sdk::entrypoint!(process_instruction);
 fn process_instruction<'a>(
@@ -30,12 +57,22 @@ fn process_instruction<'a>(
 
     let instruction: RegistryInstruction = RegistryInstruction::unpack(instruction_data)
         .map_err(|_| RegistryError::ErrorCode(RegistryErrorCode::WrongSerialization))?;
+    compute_checkpoint!("unpack");
 
     let result = match instruction {
         RegistryInstruction::Initialize {
             authority,
-            withdrawal_timelock,
-        } => initialize::handler(program_id, accounts, authority, withdrawal_timelock),
+            withdrawal_timelock_secs,
+            nonce,
+            reward_period_secs,
+        } => initialize::handler(
+            program_id,
+            accounts,
+            authority,
+            withdrawal_timelock_secs,
+            nonce,
+            reward_period_secs,
+        ),
         RegistryInstruction::RegisterCapability {
             capability_id,
             capability_fee_bps,
@@ -52,21 +89,127 @@ fn process_instruction<'a>(
             beneficiary,
             delegate,
         } => join_entity::handler(program_id, accounts, beneficiary, delegate),
-        RegistryInstruction::Stake { amount, is_mega } => Err(RegistryError::ErrorCode(
-            RegistryErrorCode::NotReadySeeNextMajorVersion,
-        )),
+        RegistryInstruction::Stake { amount, is_mega } => {
+            stake::handler(program_id, accounts, amount, is_mega)
+        }
         RegistryInstruction::StartStakeWithdrawal {
             amount,
             mega_amount,
-        } => Err(RegistryError::ErrorCode(
-            RegistryErrorCode::NotReadySeeNextMajorVersion,
-        )),
-        RegistryInstruction::EndStakeWithdrawal => Err(RegistryError::ErrorCode(
-            RegistryErrorCode::NotReadySeeNextMajorVersion,
-        )),
+        } => start_stake_withdrawal::handler(program_id, accounts, amount, mega_amount),
+        RegistryInstruction::EndStakeWithdrawal => {
+            end_stake_withdrawal::handler(program_id, accounts)
+        }
         RegistryInstruction::Donate { amount } => Err(RegistryError::ErrorCode(
             RegistryErrorCode::NotReadySeeNextMajorVersion,
         )),
+        RegistryInstruction::UpdateMemberMemo { memo } => {
+            update_member_memo::handler(program_id, accounts, memo)
+        }
+        RegistryInstruction::InitiateBeneficiaryTransfer { new_beneficiary } => {
+            initiate_beneficiary_transfer::handler(program_id, accounts, new_beneficiary)
+        }
+        RegistryInstruction::AcceptBeneficiaryTransfer => {
+            accept_beneficiary_transfer::handler(program_id, accounts)
+        }
+        RegistryInstruction::CancelBeneficiaryTransfer => {
+            cancel_beneficiary_transfer::handler(program_id, accounts)
+        }
+        RegistryInstruction::CloseMember => close_member::handler(program_id, accounts),
+        RegistryInstruction::UpdateRegistrar {
+            new_authority,
+            withdrawal_timelock_secs,
+            reward_period_secs,
+            max_stake_per_member,
+        } => update_registrar::handler(
+            program_id,
+            accounts,
+            new_authority,
+            withdrawal_timelock_secs,
+            reward_period_secs,
+            max_stake_per_member,
+        ),
+        RegistryInstruction::UpdateEntityMetadata {
+            name,
+            website,
+            description,
+            image_uri,
+        } => update_entity_metadata::handler(
+            program_id,
+            accounts,
+            name,
+            website,
+            description,
+            image_uri,
+        ),
+        RegistryInstruction::ProposeEntityLeader { new_leader } => {
+            propose_entity_leader::handler(program_id, accounts, new_leader)
+        }
+        RegistryInstruction::AcceptEntityLeader => {
+            accept_entity_leader::handler(program_id, accounts)
+        }
+        RegistryInstruction::CancelEntityLeader => {
+            cancel_entity_leader::handler(program_id, accounts)
+        }
+        RegistryInstruction::SetEntityCommission { commission_bps } => {
+            set_entity_commission::handler(program_id, accounts, commission_bps)
+        }
+        RegistryInstruction::DropEntityReward { amount } => {
+            drop_entity_reward::handler(program_id, accounts, amount)
+        }
+        RegistryInstruction::MigrateMember => migrate_member::handler(program_id, accounts),
+        RegistryInstruction::SnapshotEntityStake {
+            members_root,
+            nonce,
+        } => snapshot_entity_stake::handler(program_id, accounts, members_root, nonce),
+        RegistryInstruction::Pause => pause::handler(program_id, accounts, true),
+        RegistryInstruction::Unpause => pause::handler(program_id, accounts, false),
+        RegistryInstruction::SetEntityJoinRules {
+            max_members,
+            approve_joins,
+        } => set_entity_join_rules::handler(program_id, accounts, max_members, approve_joins),
+        RegistryInstruction::DropVendorReward {
+            amount,
+            nonce,
+            merkle_root,
+            expiry_ts,
+            expiry_receiver,
+        } => drop_vendor_reward::handler(
+            program_id,
+            accounts,
+            amount,
+            nonce,
+            merkle_root,
+            expiry_ts,
+            expiry_receiver,
+        ),
+        RegistryInstruction::ClaimVendorReward => {
+            claim_vendor_reward::handler(program_id, accounts)
+        }
+        RegistryInstruction::ClaimMerkleReward {
+            index,
+            amount,
+            proof,
+            nonce,
+        } => claim_merkle_reward::handler(program_id, accounts, index, amount, proof, nonce),
+        RegistryInstruction::ReclaimVendor => reclaim_vendor::handler(program_id, accounts),
+        RegistryInstruction::CompoundRewards => compound_rewards::handler(program_id, accounts),
+        RegistryInstruction::CreateRewardSchedule {
+            amount,
+            period_secs,
+            start_ts,
+            nonce,
+        } => create_reward_schedule::handler(
+            program_id,
+            accounts,
+            amount,
+            period_secs,
+            start_ts,
+            nonce,
+        ),
+        RegistryInstruction::DistributeEpoch => distribute_epoch::handler(program_id, accounts),
+        RegistryInstruction::UpdateVoterWeightRecord { realm, is_mega } => {
+            update_voter_weight_record::handler(program_id, accounts, realm, is_mega)
+        }
     };
 
     result?;
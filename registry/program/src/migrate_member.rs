@@ -0,0 +1,365 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Member, Registrar};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::MemberMigrated;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), RegistryError> {
+    info!("handler: migrate_member");
+
+    let acc_infos = &mut accounts.iter();
+
+    let new_member_acc_info = next_account_info(acc_infos)?;
+    let member_acc_info = next_account_info(acc_infos)?;
+    let beneficiary_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let registrar_authority_acc_info = next_account_info(acc_infos)?;
+    let new_entity_acc_info = next_account_info(acc_infos)?;
+    let new_registrar_acc_info = next_account_info(acc_infos)?;
+    let new_registrar_authority_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let mega_vault_acc_info = next_account_info(acc_infos)?;
+    let vault_authority_acc_info = next_account_info(acc_infos)?;
+    let new_vault_acc_info = next_account_info(acc_infos)?;
+    let new_mega_vault_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    // The new entity's leader signs only if it approves its members.
+    let new_entity = Entity::unpack(&new_entity_acc_info.try_borrow_data()?)?;
+    let new_entity_leader_acc_info = if new_entity.approve_joins {
+        Some(next_account_info(acc_infos)?)
+    } else {
+        None
+    };
+
+    let AccessControlResponse { registrar, clock } = access_control(AccessControlRequest {
+        program_id,
+        new_member_acc_info,
+        member_acc_info,
+        beneficiary_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        registrar_authority_acc_info,
+        new_entity_acc_info,
+        new_registrar_acc_info,
+        new_registrar_authority_acc_info,
+        vault_acc_info,
+        mega_vault_acc_info,
+        vault_authority_acc_info,
+        new_vault_acc_info,
+        new_mega_vault_acc_info,
+        token_program_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+        new_entity_leader_acc_info,
+    })?;
+
+    Member::unpack_mut(
+        &mut new_member_acc_info.try_borrow_mut_data()?,
+        &mut |new_member: &mut Member| {
+            Member::unpack_mut(
+                &mut member_acc_info.try_borrow_mut_data()?,
+                &mut |member: &mut Member| {
+                    Entity::unpack_mut(
+                        &mut entity_acc_info.try_borrow_mut_data()?,
+                        &mut |entity: &mut Entity| {
+                            Entity::unpack_mut(
+                                &mut new_entity_acc_info.try_borrow_mut_data()?,
+                                &mut |new_entity: &mut Entity| {
+                                    state_transition(StateTransitionRequest {
+                                        new_member,
+                                        member,
+                                        entity,
+                                        new_entity,
+                                        registrar: &registrar,
+                                        clock: &clock,
+                                        new_member_acc_info,
+                                        member_acc_info,
+                                        new_entity_acc_info,
+                                        registrar_acc_info,
+                                        vault_acc_info,
+                                        mega_vault_acc_info,
+                                        vault_authority_acc_info,
+                                        new_vault_acc_info,
+                                        new_mega_vault_acc_info,
+                                        token_program_acc_info,
+                                    })
+                                    .map_err(Into::into)
+                                },
+                            )
+                        },
+                    )
+                },
+            )
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: migrate_member");
+
+    let AccessControlRequest {
+        program_id,
+        new_member_acc_info,
+        member_acc_info,
+        beneficiary_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        registrar_authority_acc_info,
+        new_entity_acc_info,
+        new_registrar_acc_info,
+        new_registrar_authority_acc_info,
+        vault_acc_info,
+        mega_vault_acc_info,
+        vault_authority_acc_info,
+        new_vault_acc_info,
+        new_mega_vault_acc_info,
+        token_program_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+        new_entity_leader_acc_info,
+    } = req;
+
+    // Beneficiary authorization.
+    if !beneficiary_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized || member.beneficiary != *beneficiary_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Entities. Moving within one entity would only reset the member.
+    if entity_acc_info.owner != program_id || member.entity != *entity_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if new_entity_acc_info.owner != program_id || new_entity_acc_info.key == entity_acc_info.key {
+        return Err(RegistryErrorCode::InvalidMemberMigration)?;
+    }
+    let new_entity = Entity::unpack(&new_entity_acc_info.try_borrow_data()?)?;
+    if !new_entity.initialized {
+        return Err(RegistryErrorCode::InvalidMemberMigration)?;
+    }
+    if new_entity.is_full() {
+        return Err(RegistryErrorCode::EntityFull)?;
+    }
+    if let Some(new_entity_leader_acc_info) = new_entity_leader_acc_info {
+        if !new_entity_leader_acc_info.is_signer
+            || new_entity.leader != *new_entity_leader_acc_info.key
+        {
+            return Err(RegistryErrorCode::Unauthorized)?;
+        }
+    }
+
+    // Registrars, each approving through its authority. Each must be the
+    // one its entity was created under, or the approvals would come from
+    // registrars unrelated to the move.
+    if new_registrar_acc_info.key == registrar_acc_info.key {
+        return Err(RegistryErrorCode::InvalidMemberMigration)?;
+    }
+    if entity.registrar != *registrar_acc_info.key
+        || member.registrar != *registrar_acc_info.key
+        || new_entity.registrar != *new_registrar_acc_info.key
+    {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    for (registrar_acc_info, authority_acc_info) in &[
+        (registrar_acc_info, registrar_authority_acc_info),
+        (new_registrar_acc_info, new_registrar_authority_acc_info),
+    ] {
+        if registrar_acc_info.owner != program_id {
+            return Err(RegistryErrorCode::InvalidRegistrar)?;
+        }
+        let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+        if !registrar.initialized {
+            return Err(RegistryErrorCode::InvalidRegistrar)?;
+        }
+        if !authority_acc_info.is_signer || registrar.authority != *authority_acc_info.key {
+            return Err(RegistryErrorCode::Unauthorized)?;
+        }
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    let new_registrar = Registrar::unpack(&new_registrar_acc_info.try_borrow_data()?)?;
+    new_registrar.check_stake_cap(member.amount, member.mega_amount)?;
+
+    // Vaults. The member's stake and unpaid rewards move with it, since
+    // they're paid out of its registrar's vaults.
+    if *vault_acc_info.key != registrar.vault
+        || *mega_vault_acc_info.key != registrar.mega_vault
+        || *new_vault_acc_info.key != new_registrar.vault
+        || *new_mega_vault_acc_info.key != new_registrar.mega_vault
+    {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    crate::vault::check_authority(
+        program_id,
+        registrar_acc_info.key,
+        registrar.nonce,
+        vault_authority_acc_info,
+    )?;
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // New member account.
+    if new_member_acc_info.owner != program_id || new_member_acc_info.key == member_acc_info.key {
+        return Err(RegistryErrorCode::InvalidMemberMigration)?;
+    }
+    let new_member = Member::unpack(&new_member_acc_info.try_borrow_data()?)?;
+    if new_member.initialized {
+        return Err(RegistryErrorCode::InvalidMemberMigration)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(
+        new_member_acc_info.lamports(),
+        new_member_acc_info.try_data_len()?,
+    ) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
+
+    // Clock, to bring both entities' reward streams up to date.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse { registrar, clock })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: migrate_member");
+
+    let StateTransitionRequest {
+        new_member,
+        member,
+        entity,
+        new_entity,
+        registrar,
+        clock,
+        new_member_acc_info,
+        member_acc_info,
+        new_entity_acc_info,
+        registrar_acc_info,
+        vault_acc_info,
+        mega_vault_acc_info,
+        vault_authority_acc_info,
+        new_vault_acc_info,
+        new_mega_vault_acc_info,
+        token_program_acc_info,
+    } = req;
+
+    entity.update_reward_index(clock.unix_timestamp)?;
+    new_entity.update_reward_index(clock.unix_timestamp)?;
+    *new_member = member.migrate(entity, new_entity, *new_entity_acc_info.key)?;
+
+    // Transfer the funds backing the new member into its registrar's vaults.
+    let amount = new_member
+        .amount
+        .checked_add(new_member.rewards)
+        .ok_or(RegistryErrorCode::Overflow)?;
+    crate::vault::transfer_out(
+        registrar_acc_info.key,
+        registrar.nonce,
+        vault_acc_info,
+        new_vault_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        amount,
+    )?;
+    crate::vault::transfer_out(
+        registrar_acc_info.key,
+        registrar.nonce,
+        mega_vault_acc_info,
+        new_mega_vault_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        new_member.mega_amount,
+    )?;
+
+    emit_event!(MemberMigrated {
+        member: *member_acc_info.key,
+        new_member: *new_member_acc_info.key,
+        new_entity: *new_entity_acc_info.key,
+        amount: new_member.amount,
+        mega_amount: new_member.mega_amount,
+        rewards: new_member.rewards,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    new_member_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    beneficiary_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    registrar_authority_acc_info: &'a AccountInfo<'a>,
+    new_entity_acc_info: &'a AccountInfo<'a>,
+    new_registrar_acc_info: &'a AccountInfo<'a>,
+    new_registrar_authority_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    mega_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    new_vault_acc_info: &'a AccountInfo<'a>,
+    new_mega_vault_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+    new_entity_leader_acc_info: Option<&'a AccountInfo<'a>>,
+}
+
+struct AccessControlResponse {
+    registrar: Registrar,
+    clock: Clock,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    new_member: &'b mut Member,
+    member: &'b mut Member,
+    entity: &'b mut Entity,
+    new_entity: &'b mut Entity,
+    registrar: &'b Registrar,
+    clock: &'b Clock,
+    new_member_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    new_entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    mega_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    new_vault_acc_info: &'a AccountInfo<'a>,
+    new_mega_vault_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+}
@@ -0,0 +1,86 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::Registrar;
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+
+// Handles both Pause and Unpause.
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    paused: bool,
+) -> Result<(), RegistryError> {
+    info!("handler: pause");
+
+    let acc_infos = &mut accounts.iter();
+
+    let registrar_authority_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        registrar_authority_acc_info,
+        registrar_acc_info,
+    })?;
+
+    Registrar::unpack_mut(
+        &mut registrar_acc_info.try_borrow_mut_data()?,
+        &mut |registrar: &mut Registrar| {
+            state_transition(StateTransitionRequest { registrar, paused }).map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: pause");
+
+    let AccessControlRequest {
+        program_id,
+        registrar_authority_acc_info,
+        registrar_acc_info,
+    } = req;
+
+    if !registrar_authority_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized || registrar.authority != *registrar_authority_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: pause");
+
+    let StateTransitionRequest { registrar, paused } = req;
+
+    registrar.paused = paused;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    registrar_authority_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a> {
+    registrar: &'a mut Registrar,
+    paused: bool,
+}
@@ -0,0 +1,133 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, EntityLeaderTransfer};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    new_leader: Pubkey,
+) -> Result<(), RegistryError> {
+    info!("handler: propose_entity_leader");
+
+    let acc_infos = &mut accounts.iter();
+
+    let transfer_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let leader_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        entity_acc_info,
+        leader_acc_info,
+        rent_acc_info,
+    })?;
+
+    EntityLeaderTransfer::unpack_mut(
+        &mut transfer_acc_info.try_borrow_mut_data()?,
+        &mut |transfer: &mut EntityLeaderTransfer| {
+            state_transition(StateTransitionRequest {
+                transfer,
+                entity: *entity_acc_info.key,
+                leader: *leader_acc_info.key,
+                new_leader,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: propose_entity_leader");
+
+    let AccessControlRequest {
+        program_id,
+        transfer_acc_info,
+        entity_acc_info,
+        leader_acc_info,
+        rent_acc_info,
+    } = req;
+
+    // Leader authorization.
+    if !leader_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized || entity.leader != *leader_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Transfer account.
+    if transfer_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidEntityLeaderTransfer)?;
+    }
+    let transfer = EntityLeaderTransfer::unpack(&transfer_acc_info.try_borrow_data()?)?;
+    if transfer.initialized {
+        return Err(RegistryErrorCode::InvalidEntityLeaderTransfer)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(
+        transfer_acc_info.lamports(),
+        transfer_acc_info.try_data_len()?,
+    ) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: propose_entity_leader");
+
+    let StateTransitionRequest {
+        transfer,
+        entity,
+        leader,
+        new_leader,
+    } = req;
+
+    transfer.initialized = true;
+    transfer.entity = entity;
+    transfer.leader = leader;
+    transfer.new_leader = new_leader;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    transfer_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    leader_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a> {
+    transfer: &'a mut EntityLeaderTransfer,
+    entity: Pubkey,
+    leader: Pubkey,
+    new_leader: Pubkey,
+}
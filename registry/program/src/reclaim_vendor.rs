@@ -0,0 +1,183 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::RewardVendor;
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::VendorReclaimed;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+) -> Result<(), RegistryError> {
+    info!("handler: reclaim_vendor");
+
+    let acc_infos = &mut accounts.iter();
+
+    let expiry_receiver_acc_info = next_account_info(acc_infos)?;
+    let vendor_acc_info = next_account_info(acc_infos)?;
+    let vendor_vault_acc_info = next_account_info(acc_infos)?;
+    let vault_authority_acc_info = next_account_info(acc_infos)?;
+    let token_acc_info = next_account_info(acc_infos)?;
+    let token_program_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { vendor, amount } = access_control(AccessControlRequest {
+        program_id,
+        expiry_receiver_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        token_program_acc_info,
+        clock_acc_info,
+    })?;
+
+    state_transition(StateTransitionRequest {
+        vendor: &vendor,
+        amount,
+        expiry_receiver_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        token_program_acc_info,
+    })?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: reclaim_vendor");
+
+    let AccessControlRequest {
+        program_id,
+        expiry_receiver_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        token_program_acc_info,
+        clock_acc_info,
+    } = req;
+
+    // Vendor, and its expiry receiver's authorization.
+    if vendor_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRewardVendor)?;
+    }
+    let vendor = RewardVendor::unpack(&vendor_acc_info.try_borrow_data()?)?;
+    if !vendor.initialized {
+        return Err(RegistryErrorCode::InvalidRewardVendor)?;
+    }
+    if !expiry_receiver_acc_info.is_signer
+        || vendor.expiry_receiver != *expiry_receiver_acc_info.key
+    {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Clock, as claims must have closed.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+    if !vendor.expired(clock.unix_timestamp) {
+        return Err(RegistryErrorCode::VendorNotExpired)?;
+    }
+
+    // Vendor vault to empty, and the account to pay into, which must
+    // belong to the expiry receiver.
+    if *vendor_vault_acc_info.key != vendor.vault {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    let vendor_vault =
+        spl_token::state::Account::unpack(&vendor_vault_acc_info.try_borrow_data()?)?;
+    crate::vault::check_authority(
+        program_id,
+        vendor_acc_info.key,
+        vendor.nonce,
+        vault_authority_acc_info,
+    )?;
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let to = spl_token::state::Account::unpack(&token_acc_info.try_borrow_data()?)?;
+    if to.owner != *expiry_receiver_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse {
+        vendor,
+        amount: vendor_vault.amount,
+    })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: reclaim_vendor");
+
+    let StateTransitionRequest {
+        vendor,
+        amount,
+        expiry_receiver_acc_info,
+        vendor_acc_info,
+        vendor_vault_acc_info,
+        vault_authority_acc_info,
+        token_acc_info,
+        token_program_acc_info,
+    } = req;
+
+    crate::vault::transfer_out(
+        vendor_acc_info.key,
+        vendor.nonce,
+        vendor_vault_acc_info,
+        token_acc_info,
+        vault_authority_acc_info,
+        token_program_acc_info,
+        amount,
+    )?;
+
+    emit_event!(VendorReclaimed {
+        vendor: *vendor_acc_info.key,
+        expiry_receiver: *expiry_receiver_acc_info.key,
+        amount,
+    })?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    expiry_receiver_acc_info: &'a AccountInfo<'a>,
+    vendor_acc_info: &'a AccountInfo<'a>,
+    vendor_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    token_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+}
+
+struct AccessControlResponse {
+    vendor: RewardVendor,
+    amount: u64,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    vendor: &'b RewardVendor,
+    amount: u64,
+    expiry_receiver_acc_info: &'a AccountInfo<'a>,
+    vendor_acc_info: &'a AccountInfo<'a>,
+    vendor_vault_acc_info: &'a AccountInfo<'a>,
+    vault_authority_acc_info: &'a AccountInfo<'a>,
+    token_acc_info: &'a AccountInfo<'a>,
+    token_program_acc_info: &'a AccountInfo<'a>,
+}
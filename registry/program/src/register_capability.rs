@@ -1,3 +1,4 @@
+use serum_common::compute_checkpoint;
 use serum_common::pack::Pack;
 use serum_registry::accounts::Registrar;
 use serum_registry::error::{RegistryError, RegistryErrorCode};
@@ -51,6 +52,7 @@ fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
     // todo
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
     Ok(())
 }
@@ -67,6 +69,7 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
     registrar.capabilities_fees_bps[capability_id as usize] = capability_fee_bps;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
@@ -0,0 +1,138 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Registrar};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::Sysvar;
+use std::convert::TryFrom;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    commission_bps: u32,
+) -> Result<(), RegistryError> {
+    info!("handler: set_entity_commission");
+
+    let acc_infos = &mut accounts.iter();
+
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let entity_leader_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { registrar, clock } = access_control(AccessControlRequest {
+        program_id,
+        entity_acc_info,
+        entity_leader_acc_info,
+        registrar_acc_info,
+        clock_acc_info,
+        commission_bps,
+    })?;
+
+    Entity::unpack_mut(
+        &mut entity_acc_info.try_borrow_mut_data()?,
+        &mut |entity: &mut Entity| {
+            state_transition(StateTransitionRequest {
+                entity,
+                registrar: &registrar,
+                clock: &clock,
+                commission_bps,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: set_entity_commission");
+
+    let AccessControlRequest {
+        program_id,
+        entity_acc_info,
+        entity_leader_acc_info,
+        registrar_acc_info,
+        clock_acc_info,
+        commission_bps,
+    } = req;
+
+    if !entity_leader_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized || entity.leader != *entity_leader_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if commission_bps > Entity::MAX_COMMISSION_BPS {
+        return Err(RegistryErrorCode::InvalidCommission)?;
+    }
+
+    // Registrar, whose withdrawal timelock delays raises.
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized || entity.registrar != *registrar_acc_info.key {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Clock.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse { registrar, clock })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: set_entity_commission");
+
+    let StateTransitionRequest {
+        entity,
+        registrar,
+        clock,
+        commission_bps,
+    } = req;
+
+    let timelock = i64::try_from(registrar.withdrawal_timelock_secs)
+        .map_err(|_| RegistryErrorCode::Overflow)?;
+    entity.apply_pending_commission(clock.unix_timestamp);
+    entity.set_commission(commission_bps, clock.unix_timestamp, timelock)?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    entity_acc_info: &'a AccountInfo<'a>,
+    entity_leader_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+    commission_bps: u32,
+}
+
+struct AccessControlResponse {
+    registrar: Registrar,
+    clock: Clock,
+}
+
+struct StateTransitionRequest<'a> {
+    entity: &'a mut Entity,
+    registrar: &'a Registrar,
+    clock: &'a Clock,
+    commission_bps: u32,
+}
@@ -0,0 +1,97 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::Entity;
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    max_members: u64,
+    approve_joins: bool,
+) -> Result<(), RegistryError> {
+    info!("handler: set_entity_join_rules");
+
+    let acc_infos = &mut accounts.iter();
+
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let entity_leader_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        entity_acc_info,
+        entity_leader_acc_info,
+    })?;
+
+    Entity::unpack_mut(
+        &mut entity_acc_info.try_borrow_mut_data()?,
+        &mut |entity: &mut Entity| {
+            state_transition(StateTransitionRequest {
+                entity,
+                max_members,
+                approve_joins,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: set_entity_join_rules");
+
+    let AccessControlRequest {
+        program_id,
+        entity_acc_info,
+        entity_leader_acc_info,
+    } = req;
+
+    if !entity_leader_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized || entity.leader != *entity_leader_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: set_entity_join_rules");
+
+    let StateTransitionRequest {
+        entity,
+        max_members,
+        approve_joins,
+    } = req;
+
+    entity.max_members = max_members;
+    entity.approve_joins = approve_joins;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    entity_acc_info: &'a AccountInfo<'a>,
+    entity_leader_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a> {
+    entity: &'a mut Entity,
+    max_members: u64,
+    approve_joins: bool,
+}
@@ -0,0 +1,251 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::{Pack, PackedSize};
+use serum_registry::accounts::{Entity, Registrar, StakeSnapshot};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::EntityStakeSnapshotted;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::system_instruction;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    members_root: [u8; 32],
+    nonce: u8,
+) -> Result<(), RegistryError> {
+    info!("handler: snapshot_entity_stake");
+
+    let acc_infos = &mut accounts.iter();
+
+    let snapshot_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let registrar_authority_acc_info = next_account_info(acc_infos)?;
+    let system_program_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse {
+        entity,
+        rent,
+        clock,
+    } = access_control(AccessControlRequest {
+        program_id,
+        snapshot_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        registrar_authority_acc_info,
+        system_program_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+        nonce,
+    })?;
+
+    state_transition(StateTransitionRequest {
+        program_id,
+        snapshot_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        registrar_authority_acc_info,
+        system_program_acc_info,
+        entity: &entity,
+        rent: &rent,
+        clock: &clock,
+        members_root,
+        nonce,
+    })?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: snapshot_entity_stake");
+
+    let AccessControlRequest {
+        program_id,
+        snapshot_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        registrar_authority_acc_info,
+        system_program_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+        nonce,
+    } = req;
+
+    // Registrar authorization.
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    if !registrar_authority_acc_info.is_signer
+        || registrar.authority != *registrar_authority_acc_info.key
+    {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Entity, which must be under the registrar.
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity.registrar != *registrar_acc_info.key {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Clock.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+
+    // Snapshot account. Derived from the epoch, so there's one per entity
+    // and epoch, and the runtime refuses to create it twice.
+    let epoch = clock.epoch.to_le_bytes();
+    let nonce = [nonce];
+    let seeds =
+        StakeSnapshot::signer_seeds(registrar_acc_info.key, entity_acc_info.key, &epoch, &nonce);
+    let snapshot_address = Pubkey::create_program_address(&seeds, program_id)
+        .map_err(|_| RegistryErrorCode::InvalidStakeSnapshot)?;
+    if snapshot_address != *snapshot_acc_info.key || snapshot_acc_info.owner == program_id {
+        return Err(RegistryErrorCode::InvalidStakeSnapshot)?;
+    }
+
+    // System program, creating the snapshot account.
+    if *system_program_acc_info.key != solana_sdk::system_program::id() {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse {
+        entity,
+        rent,
+        clock,
+    })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: snapshot_entity_stake");
+
+    let StateTransitionRequest {
+        program_id,
+        snapshot_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        registrar_authority_acc_info,
+        system_program_acc_info,
+        entity,
+        rent,
+        clock,
+        members_root,
+        nonce,
+    } = req;
+
+    // Create the snapshot account, paid for by the registrar authority.
+    {
+        info!("invoke system create account");
+
+        let epoch = clock.epoch.to_le_bytes();
+        let nonce = [nonce];
+        let seeds = StakeSnapshot::signer_seeds(
+            registrar_acc_info.key,
+            entity_acc_info.key,
+            &epoch,
+            &nonce,
+        );
+        let create_instruction = system_instruction::create_account(
+            registrar_authority_acc_info.key,
+            snapshot_acc_info.key,
+            rent.minimum_balance(StakeSnapshot::SIZE as usize),
+            StakeSnapshot::SIZE,
+            program_id,
+        );
+        solana_sdk::program::invoke_signed(
+            &create_instruction,
+            &[
+                registrar_authority_acc_info.clone(),
+                snapshot_acc_info.clone(),
+                system_program_acc_info.clone(),
+            ],
+            &[&seeds],
+        )?;
+    }
+
+    let snapshot = StakeSnapshot {
+        initialized: true,
+        registrar: *registrar_acc_info.key,
+        entity: *entity_acc_info.key,
+        epoch: clock.epoch,
+        slot: clock.slot,
+        unix_timestamp: clock.unix_timestamp,
+        amount: entity.amount,
+        mega_amount: entity.mega_amount,
+        members_root,
+    };
+
+    emit_event!(EntityStakeSnapshotted {
+        snapshot: *snapshot_acc_info.key,
+        entity: *entity_acc_info.key,
+        epoch: snapshot.epoch,
+        amount: snapshot.amount,
+        mega_amount: snapshot.mega_amount,
+    })?;
+
+    StakeSnapshot::pack(snapshot, &mut snapshot_acc_info.try_borrow_mut_data()?)?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    snapshot_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    registrar_authority_acc_info: &'a AccountInfo<'a>,
+    system_program_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+    nonce: u8,
+}
+
+struct AccessControlResponse {
+    entity: Entity,
+    rent: Rent,
+    clock: Clock,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    program_id: &'a Pubkey,
+    snapshot_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    registrar_authority_acc_info: &'a AccountInfo<'a>,
+    system_program_acc_info: &'a AccountInfo<'a>,
+    entity: &'b Entity,
+    rent: &'b Rent,
+    clock: &'b Clock,
+    members_root: [u8; 32],
+    nonce: u8,
+}
@@ -1,9 +1,14 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
 use serum_common::pack::Pack;
-use serum_registry::accounts::{Entity, Member};
+use serum_registry::accounts::{Entity, Member, Registrar};
 use serum_registry::error::{RegistryError, RegistryErrorCode};
+use serum_registry::events::Staked;
 use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
 use solana_sdk::info;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::Sysvar;
 
 pub fn handler<'a>(
     program_id: &'a Pubkey,
@@ -20,15 +25,21 @@ pub fn handler<'a>(
     let member_acc_info = next_account_info(acc_infos)?;
     let member_authority_acc_info = next_account_info(acc_infos)?;
     let entity_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
     let token_program_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
 
-    access_control(AccessControlRequest {
-        depositor_tok_owner_acc_info,
-        depositor_tok_acc_info,
+    let AccessControlResponse { registrar, clock } = access_control(AccessControlRequest {
+        program_id,
         member_acc_info,
         member_authority_acc_info,
         entity_acc_info,
+        vault_acc_info,
+        registrar_acc_info,
         token_program_acc_info,
+        clock_acc_info,
+        is_mega,
     })?;
 
     Entity::unpack_mut(
@@ -45,9 +56,11 @@ pub fn handler<'a>(
                         depositor_tok_owner_acc_info,
                         depositor_tok_acc_info,
                         member_acc_info,
-                        member_authority_acc_info,
                         entity_acc_info,
+                        vault_acc_info,
                         token_program_acc_info,
+                        registrar: &registrar,
+                        clock: &clock,
                     })
                     .map_err(Into::into)
                 },
@@ -58,23 +71,74 @@ pub fn handler<'a>(
     Ok(())
 }
 
-fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
     info!("access-control: stake");
 
     let AccessControlRequest {
-        depositor_tok_owner_acc_info,
-        depositor_tok_acc_info,
+        program_id,
         member_acc_info,
         member_authority_acc_info,
         entity_acc_info,
+        vault_acc_info,
+        registrar_acc_info,
         token_program_acc_info,
+        clock_acc_info,
+        is_mega,
     } = req;
 
-    // todo
+    // Beneficiary/delegate authorization.
+    if !member_authority_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member.beneficiary != *member_authority_acc_info.key
+        && member.delegate != *member_authority_acc_info.key
+    {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Entity.
+    if entity_acc_info.owner != program_id || member.entity != *entity_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+
+    // Registrar, whose vault the stake is paid back out of.
+    if registrar_acc_info.owner != program_id
+        || entity.registrar != *registrar_acc_info.key
+        || member.registrar != *registrar_acc_info.key
+    {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Vault, so the stake can't be sent anywhere but the registrar's.
+    if *vault_acc_info.key != *registrar.stake_vault(is_mega) {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    if *token_program_acc_info.key != spl_token::ID {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Clock, to bring the entity's reward stream up to date.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
-    Ok(())
+    Ok(AccessControlResponse { registrar, clock })
 }
 
 fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
@@ -88,46 +152,75 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
         depositor_tok_owner_acc_info,
         depositor_tok_acc_info,
         member_acc_info,
-        member_authority_acc_info,
         entity_acc_info,
+        vault_acc_info,
         token_program_acc_info,
+        registrar,
+        clock,
     } = req;
 
-    // Transfer funds into the staking pool.
+    // Transfer funds into the registrar's vault.
     {
-        // todo
+        info!("invoke SPL token transfer");
+
+        let deposit_instruction = spl_token::instruction::transfer(
+            &spl_token::ID,
+            depositor_tok_acc_info.key,
+            vault_acc_info.key,
+            depositor_tok_owner_acc_info.key,
+            &[],
+            amount,
+        )?;
+        solana_sdk::program::invoke_signed(
+            &deposit_instruction,
+            &[
+                depositor_tok_acc_info.clone(),
+                depositor_tok_owner_acc_info.clone(),
+                vault_acc_info.clone(),
+                token_program_acc_info.clone(),
+            ],
+            &[],
+        )?;
     }
 
     // Member account.
-    {
-        if is_mega {
-            member.mega_amount += amount;
-        } else {
-            member.amount = amount;
-        }
-    }
+    member.sync_rewards_cursor(entity)?;
+    entity.update_reward_index(clock.unix_timestamp)?;
+    member.accrue_rewards(entity)?;
+    member.did_deposit(amount, is_mega)?;
+    registrar.check_stake_cap(member.amount, member.mega_amount)?;
 
     // Entity.
-    {
-        if is_mega {
-            entity.mega_amount += amount;
-        } else {
-            entity.amount += amount;
-        }
-    }
+    entity.did_deposit(amount, is_mega)?;
+
+    emit_event!(Staked {
+        member: *member_acc_info.key,
+        entity: *entity_acc_info.key,
+        amount,
+        mega: is_mega,
+    })?;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
 
 struct AccessControlRequest<'a> {
-    depositor_tok_owner_acc_info: &'a AccountInfo<'a>,
-    depositor_tok_acc_info: &'a AccountInfo<'a>,
+    program_id: &'a Pubkey,
     member_acc_info: &'a AccountInfo<'a>,
     member_authority_acc_info: &'a AccountInfo<'a>,
     entity_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
     token_program_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+    is_mega: bool,
+}
+
+struct AccessControlResponse {
+    registrar: Registrar,
+    clock: Clock,
 }
 
 struct StateTransitionRequest<'a, 'b> {
@@ -138,7 +231,9 @@ struct StateTransitionRequest<'a, 'b> {
     depositor_tok_owner_acc_info: &'a AccountInfo<'a>,
     depositor_tok_acc_info: &'a AccountInfo<'a>,
     member_acc_info: &'a AccountInfo<'a>,
-    member_authority_acc_info: &'a AccountInfo<'a>,
     entity_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
     token_program_acc_info: &'a AccountInfo<'a>,
+    registrar: &'b Registrar,
+    clock: &'b Clock,
 }
@@ -1,42 +1,240 @@
+use serum_common::compute_checkpoint;
+use serum_common::emit_event;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, Member, PendingWithdrawal, Registrar};
 use serum_registry::error::{RegistryError, RegistryErrorCode};
-use solana_sdk::account_info::AccountInfo;
+use serum_registry::events::StakeWithdrawalStarted;
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
 use solana_sdk::info;
 use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+use std::convert::TryFrom;
 
 pub fn handler<'a>(
     program_id: &'a Pubkey,
     accounts: &'a [AccountInfo<'a>],
+    amount: u64,
+    mega_amount: u64,
 ) -> Result<(), RegistryError> {
     info!("handler: initiate_stake_withdrawal");
 
-    access_control(AccessControlRequest {})?;
+    let acc_infos = &mut accounts.iter();
 
-    state_transition(StateTransitionRequest {})
+    let pending_withdrawal_acc_info = next_account_info(acc_infos)?;
+    let member_authority_acc_info = next_account_info(acc_infos)?;
+    let member_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse { registrar, clock } = access_control(AccessControlRequest {
+        program_id,
+        pending_withdrawal_acc_info,
+        member_authority_acc_info,
+        member_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+    })?;
+
+    PendingWithdrawal::unpack_mut(
+        &mut pending_withdrawal_acc_info.try_borrow_mut_data()?,
+        &mut |pending_withdrawal: &mut PendingWithdrawal| {
+            Member::unpack_mut(
+                &mut member_acc_info.try_borrow_mut_data()?,
+                &mut |member: &mut Member| {
+                    Entity::unpack_mut(
+                        &mut entity_acc_info.try_borrow_mut_data()?,
+                        &mut |entity: &mut Entity| {
+                            state_transition(StateTransitionRequest {
+                                pending_withdrawal,
+                                member,
+                                entity,
+                                pending_withdrawal_acc_info,
+                                member_acc_info,
+                                registrar: &registrar,
+                                clock: &clock,
+                                amount,
+                                mega_amount,
+                            })
+                            .map_err(Into::into)
+                        },
+                    )
+                },
+            )
+        },
+    )?;
+
+    Ok(())
 }
 
-fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
-    // todo
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
     info!("access-control: initiate_stake_withdrawal");
 
-    let AccessControlRequest {} = req;
+    let AccessControlRequest {
+        program_id,
+        pending_withdrawal_acc_info,
+        member_authority_acc_info,
+        member_acc_info,
+        entity_acc_info,
+        registrar_acc_info,
+        rent_acc_info,
+        clock_acc_info,
+    } = req;
+
+    // Beneficiary/delegate authorization.
+    if !member_authority_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member.beneficiary != *member_authority_acc_info.key
+        && member.delegate != *member_authority_acc_info.key
+    {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Entity.
+    if entity_acc_info.owner != program_id || member.entity != *entity_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+
+    // Registrar. Must be the one the entity was created under, or anyone
+    // could pick a registrar of their own without a timelock.
+    if registrar_acc_info.owner != program_id
+        || entity.registrar != *registrar_acc_info.key
+        || member.registrar != *registrar_acc_info.key
+    {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Pending withdrawal account.
+    if pending_withdrawal_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::InvalidPendingWithdrawal)?;
+    }
+    let pending_withdrawal =
+        PendingWithdrawal::unpack(&pending_withdrawal_acc_info.try_borrow_data()?)?;
+    if pending_withdrawal.initialized {
+        return Err(RegistryErrorCode::InvalidPendingWithdrawal)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(
+        pending_withdrawal_acc_info.lamports(),
+        pending_withdrawal_acc_info.try_data_len()?,
+    ) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
+
+    // Clock.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
-    Ok(())
+    Ok(AccessControlResponse { registrar, clock })
 }
 
 fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
-    // todo
-
     info!("state-transition: initiate_stake_withdrawal");
 
-    let StateTransitionRequest {} = req;
+    let StateTransitionRequest {
+        pending_withdrawal,
+        member,
+        entity,
+        pending_withdrawal_acc_info,
+        member_acc_info,
+        registrar,
+        clock,
+        amount,
+        mega_amount,
+    } = req;
+
+    // The stake stops counting towards the member and entity immediately,
+    // so it can't be used while it waits out the timelock. Vendors not yet
+    // claimed are forfeited, as the stake they'd be shared by is changing.
+    member.forfeit_unclaimed_vendors(entity);
+    entity.update_reward_index(clock.unix_timestamp)?;
+    member.accrue_rewards(entity)?;
+    member.did_withdraw(amount, false)?;
+    member.did_withdraw(mega_amount, true)?;
+    entity.did_withdraw(amount, false)?;
+    entity.did_withdraw(mega_amount, true)?;
+
+    member.did_start_withdrawal()?;
+
+    let timelock = i64::try_from(registrar.withdrawal_timelock_secs)
+        .map_err(|_| RegistryErrorCode::Overflow)?;
+    pending_withdrawal.initialized = true;
+    pending_withdrawal.burned = false;
+    pending_withdrawal.member = *member_acc_info.key;
+    pending_withdrawal.start_ts = clock.unix_timestamp;
+    pending_withdrawal.end_ts = clock
+        .unix_timestamp
+        .checked_add(timelock)
+        .ok_or(RegistryErrorCode::Overflow)?;
+    pending_withdrawal.amount = amount;
+    pending_withdrawal.mega_amount = mega_amount;
+
+    emit_event!(StakeWithdrawalStarted {
+        member: *member_acc_info.key,
+        pending_withdrawal: *pending_withdrawal_acc_info.key,
+        amount,
+        mega_amount,
+        end_ts: pending_withdrawal.end_ts,
+    })?;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
 
-struct AccessControlRequest {}
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    pending_withdrawal_acc_info: &'a AccountInfo<'a>,
+    member_authority_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+}
 
-struct StateTransitionRequest {}
+struct AccessControlResponse {
+    registrar: Registrar,
+    clock: Clock,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    pending_withdrawal: &'b mut PendingWithdrawal,
+    member: &'b mut Member,
+    entity: &'b mut Entity,
+    pending_withdrawal_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    registrar: &'b Registrar,
+    clock: &'b Clock,
+    amount: u64,
+    mega_amount: u64,
+}
@@ -1,3 +1,4 @@
+use serum_common::compute_checkpoint;
 use serum_common::pack::Pack;
 use serum_registry::accounts::Entity;
 use serum_registry::error::{RegistryError, RegistryErrorCode};
@@ -49,6 +50,7 @@ fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
     // todo
 
     info!("access-control: success");
+    compute_checkpoint!("access-control");
 
     Ok(())
 }
@@ -66,6 +68,7 @@ fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
     entity.capabilities = capabilities;
 
     info!("state-transition: success");
+    compute_checkpoint!("state-transition");
 
     Ok(())
 }
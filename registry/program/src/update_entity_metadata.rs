@@ -0,0 +1,135 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Entity, EntityMetadata};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    name: String,
+    website: String,
+    description: String,
+    image_uri: String,
+) -> Result<(), RegistryError> {
+    info!("handler: update_entity_metadata");
+
+    let acc_infos = &mut accounts.iter();
+
+    let metadata_acc_info = next_account_info(acc_infos)?;
+    let entity_acc_info = next_account_info(acc_infos)?;
+    let entity_leader_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+
+    let metadata = EntityMetadata {
+        initialized: true,
+        entity: *entity_acc_info.key,
+        name,
+        website,
+        description,
+        image_uri,
+    };
+
+    access_control(AccessControlRequest {
+        program_id,
+        metadata: &metadata,
+        metadata_acc_info,
+        entity_acc_info,
+        entity_leader_acc_info,
+        rent_acc_info,
+    })?;
+
+    state_transition(StateTransitionRequest {
+        metadata,
+        metadata_acc_info,
+    })?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: update_entity_metadata");
+
+    let AccessControlRequest {
+        program_id,
+        metadata,
+        metadata_acc_info,
+        entity_acc_info,
+        entity_leader_acc_info,
+        rent_acc_info,
+    } = req;
+
+    // Entity leader authorization.
+    if !entity_leader_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if entity_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let entity = Entity::unpack(&entity_acc_info.try_borrow_data()?)?;
+    if !entity.initialized || entity.leader != *entity_leader_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Metadata account.
+    if metadata_acc_info.owner != program_id
+        || metadata_acc_info.try_data_len()? != EntityMetadata::SIZE
+    {
+        return Err(RegistryErrorCode::InvalidEntityMetadata)?;
+    }
+    let existing = EntityMetadata::unpack(&metadata_acc_info.try_borrow_data()?)?;
+    if existing.initialized && existing.entity != *entity_acc_info.key {
+        return Err(RegistryErrorCode::InvalidEntityMetadata)?;
+    }
+    if !metadata.is_valid() {
+        return Err(RegistryErrorCode::InvalidEntityMetadata)?;
+    }
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(metadata_acc_info.lamports(), EntityMetadata::SIZE) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: update_entity_metadata");
+
+    let StateTransitionRequest {
+        metadata,
+        metadata_acc_info,
+    } = req;
+
+    EntityMetadata::pack(metadata, &mut metadata_acc_info.try_borrow_mut_data()?)?;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    metadata: &'a EntityMetadata,
+    metadata_acc_info: &'a AccountInfo<'a>,
+    entity_acc_info: &'a AccountInfo<'a>,
+    entity_leader_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+}
+
+struct StateTransitionRequest<'a> {
+    metadata: EntityMetadata,
+    metadata_acc_info: &'a AccountInfo<'a>,
+}
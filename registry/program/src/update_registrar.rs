@@ -0,0 +1,126 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::Registrar;
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+use std::convert::TryFrom;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    new_authority: Option<Pubkey>,
+    withdrawal_timelock_secs: Option<u64>,
+    reward_period_secs: Option<u64>,
+    max_stake_per_member: Option<u64>,
+) -> Result<(), RegistryError> {
+    info!("handler: update_registrar");
+
+    let acc_infos = &mut accounts.iter();
+
+    let registrar_authority_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+
+    access_control(AccessControlRequest {
+        program_id,
+        registrar_authority_acc_info,
+        registrar_acc_info,
+        reward_period_secs,
+    })?;
+
+    Registrar::unpack_mut(
+        &mut registrar_acc_info.try_borrow_mut_data()?,
+        &mut |registrar: &mut Registrar| {
+            state_transition(StateTransitionRequest {
+                registrar,
+                new_authority,
+                withdrawal_timelock_secs,
+                reward_period_secs,
+                max_stake_per_member,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<(), RegistryError> {
+    info!("access-control: update_registrar");
+
+    let AccessControlRequest {
+        program_id,
+        registrar_authority_acc_info,
+        registrar_acc_info,
+        reward_period_secs,
+    } = req;
+
+    if !registrar_authority_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if registrar_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized || registrar.authority != *registrar_authority_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Reward period, which must fit a unix timestamp.
+    if let Some(reward_period_secs) = reward_period_secs {
+        if i64::try_from(reward_period_secs).is_err() {
+            return Err(RegistryErrorCode::Overflow)?;
+        }
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(())
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: update_registrar");
+
+    let StateTransitionRequest {
+        registrar,
+        new_authority,
+        withdrawal_timelock_secs,
+        reward_period_secs,
+        max_stake_per_member,
+    } = req;
+
+    if let Some(new_authority) = new_authority {
+        registrar.authority = new_authority;
+    }
+    if let Some(withdrawal_timelock_secs) = withdrawal_timelock_secs {
+        registrar.withdrawal_timelock_secs = withdrawal_timelock_secs;
+    }
+    if let Some(reward_period_secs) = reward_period_secs {
+        registrar.reward_period_secs = reward_period_secs;
+    }
+    if let Some(max_stake_per_member) = max_stake_per_member {
+        registrar.max_stake_per_member = max_stake_per_member;
+    }
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    registrar_authority_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    reward_period_secs: Option<u64>,
+}
+
+struct StateTransitionRequest<'a> {
+    registrar: &'a mut Registrar,
+    new_authority: Option<Pubkey>,
+    withdrawal_timelock_secs: Option<u64>,
+    reward_period_secs: Option<u64>,
+    max_stake_per_member: Option<u64>,
+}
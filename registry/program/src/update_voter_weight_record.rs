@@ -0,0 +1,207 @@
+use serum_common::compute_checkpoint;
+use serum_common::pack::Pack;
+use serum_registry::accounts::{Member, Registrar, VoterWeightRecord};
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::{next_account_info, AccountInfo};
+use solana_sdk::clock::Clock;
+use solana_sdk::info;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::sysvar::rent::Rent;
+use solana_sdk::sysvar::Sysvar;
+
+pub fn handler<'a>(
+    program_id: &'a Pubkey,
+    accounts: &'a [AccountInfo<'a>],
+    realm: Pubkey,
+    is_mega: bool,
+) -> Result<(), RegistryError> {
+    info!("handler: update_voter_weight_record");
+
+    let acc_infos = &mut accounts.iter();
+
+    let record_acc_info = next_account_info(acc_infos)?;
+    let beneficiary_acc_info = next_account_info(acc_infos)?;
+    let member_acc_info = next_account_info(acc_infos)?;
+    let registrar_acc_info = next_account_info(acc_infos)?;
+    let vault_acc_info = next_account_info(acc_infos)?;
+    let clock_acc_info = next_account_info(acc_infos)?;
+    let rent_acc_info = next_account_info(acc_infos)?;
+
+    let AccessControlResponse {
+        member,
+        governing_token_mint,
+        clock,
+    } = access_control(AccessControlRequest {
+        program_id,
+        record_acc_info,
+        beneficiary_acc_info,
+        member_acc_info,
+        registrar_acc_info,
+        vault_acc_info,
+        clock_acc_info,
+        rent_acc_info,
+        realm,
+        is_mega,
+    })?;
+
+    VoterWeightRecord::unpack_mut(
+        &mut record_acc_info.try_borrow_mut_data()?,
+        &mut |record: &mut VoterWeightRecord| {
+            state_transition(StateTransitionRequest {
+                record,
+                member: &member,
+                realm,
+                governing_token_mint,
+                is_mega,
+                clock: &clock,
+                beneficiary_acc_info,
+            })
+            .map_err(Into::into)
+        },
+    )?;
+
+    Ok(())
+}
+
+fn access_control(req: AccessControlRequest) -> Result<AccessControlResponse, RegistryError> {
+    info!("access-control: update_voter_weight_record");
+
+    let AccessControlRequest {
+        program_id,
+        record_acc_info,
+        beneficiary_acc_info,
+        member_acc_info,
+        registrar_acc_info,
+        vault_acc_info,
+        clock_acc_info,
+        rent_acc_info,
+        realm,
+        is_mega,
+    } = req;
+
+    // Beneficiary authorization.
+    if !beneficiary_acc_info.is_signer {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    if member_acc_info.owner != program_id {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+    let member = Member::unpack(&member_acc_info.try_borrow_data()?)?;
+    if !member.initialized || member.beneficiary != *beneficiary_acc_info.key {
+        return Err(RegistryErrorCode::Unauthorized)?;
+    }
+
+    // Registrar.
+    if registrar_acc_info.owner != program_id || member.registrar != *registrar_acc_info.key {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+    let registrar = Registrar::unpack(&registrar_acc_info.try_borrow_data()?)?;
+    if !registrar.initialized {
+        return Err(RegistryErrorCode::InvalidRegistrar)?;
+    }
+
+    // Vault, whose mint is the governing token. Anyone can create a
+    // registrar, but not the tokens staked into it, so the weight is only
+    // ever backed by the realm's own token.
+    if *vault_acc_info.key != *registrar.stake_vault(is_mega) {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    let vault = spl_token::state::Account::unpack(&vault_acc_info.try_borrow_data()?)?;
+
+    // Record. Once initialized, it reports for one realm, mint and owner.
+    if record_acc_info.owner != program_id
+        || record_acc_info.try_data_len()? != VoterWeightRecord::SIZE
+    {
+        return Err(RegistryErrorCode::InvalidVoterWeightRecord)?;
+    }
+    let record = VoterWeightRecord::unpack(&record_acc_info.try_borrow_data()?)?;
+    if record.is_initialized()
+        && (record.realm != realm
+            || record.governing_token_mint != vault.mint
+            || record.governing_token_owner != *beneficiary_acc_info.key)
+    {
+        return Err(RegistryErrorCode::InvalidVoterWeightRecord)?;
+    }
+
+    // Clock, for the slot the weight is valid in.
+    if *clock_acc_info.key != solana_sdk::sysvar::clock::id() {
+        return Err(RegistryErrorCode::InvalidClockSysvar)?;
+    }
+    let clock = Clock::from_account_info(clock_acc_info)?;
+
+    // Rent.
+    if *rent_acc_info.key != solana_sdk::sysvar::rent::id() {
+        return Err(RegistryErrorCode::InvalidRentSysvar)?;
+    }
+    let rent = Rent::from_account_info(rent_acc_info)?;
+    if !rent.is_exempt(record_acc_info.lamports(), VoterWeightRecord::SIZE) {
+        return Err(RegistryErrorCode::NotRentExempt)?;
+    }
+
+    info!("access-control: success");
+    compute_checkpoint!("access-control");
+
+    Ok(AccessControlResponse {
+        member,
+        governing_token_mint: vault.mint,
+        clock,
+    })
+}
+
+fn state_transition(req: StateTransitionRequest) -> Result<(), RegistryError> {
+    info!("state-transition: update_voter_weight_record");
+
+    let StateTransitionRequest {
+        record,
+        member,
+        realm,
+        governing_token_mint,
+        is_mega,
+        clock,
+        beneficiary_acc_info,
+    } = req;
+
+    record.account_discriminator = VoterWeightRecord::ACCOUNT_DISCRIMINATOR;
+    record.realm = realm;
+    record.governing_token_mint = governing_token_mint;
+    record.governing_token_owner = *beneficiary_acc_info.key;
+    record.voter_weight = member.stake(is_mega);
+    record.voter_weight_expiry = Some(clock.slot);
+    record.weight_action = None;
+    record.weight_action_target = None;
+
+    info!("state-transition: success");
+    compute_checkpoint!("state-transition");
+
+    Ok(())
+}
+
+struct AccessControlRequest<'a> {
+    program_id: &'a Pubkey,
+    record_acc_info: &'a AccountInfo<'a>,
+    beneficiary_acc_info: &'a AccountInfo<'a>,
+    member_acc_info: &'a AccountInfo<'a>,
+    registrar_acc_info: &'a AccountInfo<'a>,
+    vault_acc_info: &'a AccountInfo<'a>,
+    clock_acc_info: &'a AccountInfo<'a>,
+    rent_acc_info: &'a AccountInfo<'a>,
+    realm: Pubkey,
+    is_mega: bool,
+}
+
+struct AccessControlResponse {
+    member: Member,
+    governing_token_mint: Pubkey,
+    clock: Clock,
+}
+
+struct StateTransitionRequest<'a, 'b> {
+    record: &'b mut VoterWeightRecord,
+    member: &'b Member,
+    realm: Pubkey,
+    governing_token_mint: Pubkey,
+    is_mega: bool,
+    clock: &'b Clock,
+    beneficiary_acc_info: &'a AccountInfo<'a>,
+}
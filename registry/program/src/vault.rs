@@ -0,0 +1,70 @@
+use serum_registry::error::{RegistryError, RegistryErrorCode};
+use solana_sdk::account_info::AccountInfo;
+use solana_sdk::info;
+use solana_sdk::pubkey::Pubkey;
+
+/// Checks `vault_authority_acc_info` is the program-derived-address owning
+/// the vaults of `owner`, a registrar, reward vendor or reward schedule. All
+/// derive it from their address and `nonce`, see `Registrar::signer_seeds`,
+/// `RewardVendor::signer_seeds` and `RewardSchedule::signer_seeds`.
+pub fn check_authority(
+    program_id: &Pubkey,
+    owner: &Pubkey,
+    nonce: u8,
+    vault_authority_acc_info: &AccountInfo,
+) -> Result<(), RegistryError> {
+    let vault_authority =
+        Pubkey::create_program_address(&signer_seeds(owner, &[nonce]), program_id)
+            .map_err(|_| RegistryErrorCode::InvalidVault)?;
+    if *vault_authority_acc_info.key != vault_authority {
+        return Err(RegistryErrorCode::InvalidVault)?;
+    }
+    Ok(())
+}
+
+/// Transfers `amount` out of a vault of `owner`, a registrar, reward vendor
+/// or reward schedule, signing as its program-derived-address. Does nothing
+/// for 0, so a withdrawal of only one of SRM and MSRM needs no account for
+/// the other.
+pub fn transfer_out<'a>(
+    owner: &Pubkey,
+    nonce: u8,
+    vault_acc_info: &AccountInfo<'a>,
+    to_acc_info: &AccountInfo<'a>,
+    vault_authority_acc_info: &AccountInfo<'a>,
+    token_program_acc_info: &AccountInfo<'a>,
+    amount: u64,
+) -> Result<(), RegistryError> {
+    if amount == 0 {
+        return Ok(());
+    }
+
+    info!("invoke SPL token transfer");
+
+    let transfer_instruction = spl_token::instruction::transfer(
+        &spl_token::ID,
+        vault_acc_info.key,
+        to_acc_info.key,
+        vault_authority_acc_info.key,
+        &[],
+        amount,
+    )?;
+    let nonce = [nonce];
+    let signer_seeds = signer_seeds(owner, &nonce);
+    solana_sdk::program::invoke_signed(
+        &transfer_instruction,
+        &[
+            vault_acc_info.clone(),
+            to_acc_info.clone(),
+            vault_authority_acc_info.clone(),
+            token_program_acc_info.clone(),
+        ],
+        &[&signer_seeds],
+    )?;
+
+    Ok(())
+}
+
+fn signer_seeds<'a>(owner: &'a Pubkey, nonce: &'a [u8; 1]) -> [&'a [u8]; 2] {
+    [owner.as_ref(), nonce]
+}
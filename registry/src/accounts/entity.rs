@@ -1,18 +1,12 @@
+use crate::error::{RegistryError, RegistryErrorCode};
 use num_enum::IntoPrimitive;
 use serde::{Deserialize, Serialize};
 use serum_common::pack::*;
-use solana_client_gen::solana_sdk::pubkey::Pubkey;
-
-#[cfg(feature = "client")]
-lazy_static::lazy_static! {
-    pub static ref SIZE: u64 = Entity::default()
-                .size()
-                .expect("Vesting has a fixed size");
-}
+use solana_client_gen::solana_sdk::pubkey::{Pubkey, PubkeyError};
 
 /// Entity is the account representing a single "node" that addresses can
 /// stake with.
-#[derive(Default, Debug, Serialize, Deserialize)]
+#[derive(Clone, Default, Debug, Serialize, Deserialize, Pack)]
 pub struct Entity {
     /// Set when this entity is registered with the program.
     pub initialized: bool,
@@ -28,9 +22,43 @@ pub struct Entity {
     /// Type of stake backing this entity (determines voting rights)
     /// of the stakers.
     pub stake_kind: StakeKind,
+    /// Share of the rewards distributed to this entity, in bps, skimmed to
+    /// the leader before the rest is split among the members.
+    pub commission_bps: u32,
+    /// Rewards dropped to the members per unit of stake weight since the
+    /// entity was created, scaled by `REWARD_INDEX_PRECISION`. Members
+    /// accrue their share from the growth since their last checkpoint.
+    pub reward_index: u128,
+    /// Registrar the entity was created under, whose rules, e.g., the
+    /// withdrawal timelock, apply to the entity's members.
+    pub registrar: Pubkey,
+    /// Number of open Member accounts of the entity, counted by JoinEntity,
+    /// MigrateMember and CloseMember.
+    pub member_count: u64,
+    /// Most members the entity takes, or 0 for no limit. Lowering it below
+    /// `member_count` only stops new members from joining.
+    pub max_members: u64,
+    /// True iff the leader must sign for new members to join.
+    pub approve_joins: bool,
+    /// Commission raise waiting out the registrar's withdrawal timelock,
+    /// in bps. See `Entity::set_commission`.
+    pub pending_commission_bps: u32,
+    /// Unix timestamp from which `pending_commission_bps` applies, or 0 if
+    /// no raise is pending.
+    pub pending_commission_ts: i64,
+    /// Members' part of the rewards being streamed into `reward_index`, per
+    /// second, scaled by `REWARD_INDEX_PRECISION`. See
+    /// `Entity::stream_reward`.
+    pub reward_rate: u128,
+    /// Unix timestamp `reward_index` has been streamed up to.
+    pub reward_ts: i64,
+    /// Unix timestamp the stream ends at.
+    pub reward_end_ts: i64,
+    /// Number of RewardVendors dropped on the entity.
+    pub vendor_count: u64,
 }
 
-#[derive(Debug, PartialEq, IntoPrimitive, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, IntoPrimitive, Clone, Copy, Serialize, Deserialize, Pack)]
 #[repr(u32)]
 pub enum StakeKind {
     Voting,
@@ -43,4 +71,215 @@ impl Default for StakeKind {
     }
 }
 
-serum_common::packable!(Entity);
+impl Entity {
+    /// Byte offset of `leader` in the packed account, for memcmp filters.
+    pub const LEADER_OFFSET: usize = 1;
+    /// Seed the account of a leader's entity is created with.
+    pub const SEED: &'static str = "srm:registry:entity";
+    /// Upper bound on `commission_bps`, i.e., 100%.
+    pub const MAX_COMMISSION_BPS: u32 = 10_000;
+    /// Scale of `reward_index`.
+    pub const REWARD_INDEX_PRECISION: u128 = 1_000_000_000_000;
+
+    /// Address of the entity account led by `leader`.
+    pub fn address(program_id: &Pubkey, leader: &Pubkey) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_with_seed(leader, Self::SEED, program_id)
+    }
+
+    /// True iff the entity takes no more members.
+    pub fn is_full(&self) -> bool {
+        self.max_members != 0 && self.member_count >= self.max_members
+    }
+
+    /// Counts a new member, failing if the entity is full.
+    pub fn did_join(&mut self) -> Result<(), RegistryError> {
+        if self.is_full() {
+            return Err(RegistryErrorCode::EntityFull)?;
+        }
+        self.member_count = self
+            .member_count
+            .checked_add(1)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    /// Counts a member closing its account.
+    pub fn did_leave(&mut self) {
+        self.member_count = self.member_count.saturating_sub(1);
+    }
+
+    /// Adds a member's stake deposit to the entity's total.
+    pub fn did_deposit(&mut self, amount: u64, is_mega: bool) -> Result<(), RegistryError> {
+        let total = if is_mega {
+            &mut self.mega_amount
+        } else {
+            &mut self.amount
+        };
+        *total = total
+            .checked_add(amount)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    /// Total stake backing the entity, in SRM base units.
+    pub fn stake_weight(&self) -> u128 {
+        stake_weight(self.amount, self.mega_amount)
+    }
+
+    /// Sets the leader's commission. A cut applies at once. A raise only
+    /// applies `timelock` seconds after `now`, so members have time to
+    /// withdraw before it's charged on their rewards. Either replaces any
+    /// raise still pending.
+    pub fn set_commission(
+        &mut self,
+        commission_bps: u32,
+        now: i64,
+        timelock: i64,
+    ) -> Result<(), RegistryError> {
+        self.pending_commission_bps = 0;
+        self.pending_commission_ts = 0;
+        if commission_bps <= self.commission_bps {
+            self.commission_bps = commission_bps;
+        } else {
+            self.pending_commission_bps = commission_bps;
+            self.pending_commission_ts = now
+                .checked_add(timelock)
+                .ok_or(RegistryErrorCode::Overflow)?;
+        }
+        Ok(())
+    }
+
+    /// Applies the pending commission raise if it's due at `now`.
+    pub fn apply_pending_commission(&mut self, now: i64) {
+        if self.pending_commission_ts != 0 && now >= self.pending_commission_ts {
+            self.commission_bps = self.pending_commission_bps;
+            self.pending_commission_bps = 0;
+            self.pending_commission_ts = 0;
+        }
+    }
+
+    /// Splits a reward of `amount` distributed to the entity into the
+    /// leader's commission and the remainder owed to the members, in that
+    /// order. See `Member::reward_share` for each member's cut of the
+    /// remainder.
+    pub fn split_reward(&self, amount: u64) -> (u64, u64) {
+        let commission_bps = self.commission_bps.min(Self::MAX_COMMISSION_BPS);
+        let commission =
+            (amount as u128 * commission_bps as u128 / Self::MAX_COMMISSION_BPS as u128) as u64;
+        (commission, amount - commission)
+    }
+
+    /// Drops a reward of `amount` on the entity. The members' part raises
+    /// `reward_index`, see `Member::accrue_rewards`. Returns the leader's
+    /// commission, which the caller credits to the leader's Member account.
+    /// With nothing staked, the whole reward is the leader's.
+    pub fn drop_reward(&mut self, amount: u64) -> Result<u64, RegistryError> {
+        let weight = self.stake_weight();
+        if weight == 0 {
+            return Ok(amount);
+        }
+        let (commission, members_amount) = self.split_reward(amount);
+        // Rounding leaves at most one unit of weight's worth unaccounted.
+        let growth = members_amount as u128 * Self::REWARD_INDEX_PRECISION / weight;
+        self.reward_index = self
+            .reward_index
+            .checked_add(growth)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        Ok(commission)
+    }
+
+    /// Streams a reward of `amount` to the members over the `period_secs`
+    /// following `now`, on top of what's left of the current stream, so
+    /// each member's share is proportional to its stake over time rather
+    /// than at the moment of the drop. Returns the leader's commission,
+    /// taken at once. A period of 0 drops the reward at once, see
+    /// `Entity::drop_reward`.
+    pub fn stream_reward(
+        &mut self,
+        amount: u64,
+        now: i64,
+        period_secs: i64,
+    ) -> Result<u64, RegistryError> {
+        self.update_reward_index(now)?;
+        if period_secs == 0 {
+            return self.drop_reward(amount);
+        }
+        if self.stake_weight() == 0 {
+            return Ok(amount);
+        }
+        let (commission, members_amount) = self.split_reward(amount);
+        let left = if self.reward_end_ts > now {
+            self.reward_rate
+                .checked_mul((self.reward_end_ts - now) as u128)
+                .ok_or(RegistryErrorCode::Overflow)?
+        } else {
+            0
+        };
+        let total = (members_amount as u128 * Self::REWARD_INDEX_PRECISION)
+            .checked_add(left)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        self.reward_rate = total / period_secs as u128;
+        self.reward_end_ts = now
+            .checked_add(period_secs)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        Ok(commission)
+    }
+
+    /// Streams rewards into `reward_index` up to `now`. Must run before
+    /// `Member::accrue_rewards` or any change to the entity's stake. While
+    /// nothing is staked the stream is paused, so it isn't paid to no one,
+    /// nor all at once to the next member to stake.
+    pub fn update_reward_index(&mut self, now: i64) -> Result<(), RegistryError> {
+        if now > self.reward_ts && self.reward_end_ts > self.reward_ts {
+            let weight = self.stake_weight();
+            if weight == 0 {
+                self.reward_end_ts = self
+                    .reward_end_ts
+                    .checked_add(now - self.reward_ts)
+                    .ok_or(RegistryErrorCode::Overflow)?;
+            } else {
+                let elapsed = now.min(self.reward_end_ts) - self.reward_ts;
+                let growth = self
+                    .reward_rate
+                    .checked_mul(elapsed as u128)
+                    .ok_or(RegistryErrorCode::Overflow)?
+                    / weight;
+                self.reward_index = self
+                    .reward_index
+                    .checked_add(growth)
+                    .ok_or(RegistryErrorCode::Overflow)?;
+            }
+        }
+        self.reward_ts = self.reward_ts.max(now);
+        Ok(())
+    }
+
+    /// Numbers a new RewardVendor of the entity, returning its cursor.
+    pub fn did_drop_vendor(&mut self) -> Result<u64, RegistryError> {
+        let cursor = self.vendor_count;
+        self.vendor_count = cursor.checked_add(1).ok_or(RegistryErrorCode::Overflow)?;
+        Ok(cursor)
+    }
+
+    /// Removes a member's stake withdrawal from the entity's total.
+    pub fn did_withdraw(&mut self, amount: u64, is_mega: bool) -> Result<(), RegistryError> {
+        let total = if is_mega {
+            &mut self.mega_amount
+        } else {
+            &mut self.amount
+        };
+        *total = total
+            .checked_sub(amount)
+            .ok_or(RegistryErrorCode::Underflow)?;
+        Ok(())
+    }
+}
+
+/// SRM base units one MSRM is worth when weighing stake: 1 MSRM (0 decimals)
+/// for 1,000,000 SRM (6 decimals).
+pub const MSRM_STAKE_WEIGHT: u128 = 1_000_000_000_000;
+
+/// Weighs SRM and MSRM stake on the same scale, in SRM base units.
+pub fn stake_weight(amount: u64, mega_amount: u64) -> u128 {
+    amount as u128 + mega_amount as u128 * MSRM_STAKE_WEIGHT
+}
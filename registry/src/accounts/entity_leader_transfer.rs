@@ -0,0 +1,18 @@
+use serum_common::pack::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// EntityLeaderTransfer is a proposed hand off of an Entity to a new
+/// leader. It's created by the current leader and closed once the new
+/// leader accepts, or the current one cancels.
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize, Pack)]
+pub struct EntityLeaderTransfer {
+    /// Set by the program on creation.
+    pub initialized: bool,
+    /// Entity being transferred.
+    pub entity: Pubkey,
+    /// Leader that proposed the transfer. The transfer can't be accepted
+    /// once the entity's leader has changed.
+    pub leader: Pubkey,
+    /// Key that must sign to accept the transfer.
+    pub new_leader: Pubkey,
+}
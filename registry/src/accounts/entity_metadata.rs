@@ -0,0 +1,83 @@
+use serum_common::pack::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// EntityMetadata holds the human readable description of a node Entity,
+/// written by the entity's leader. Clients can find it by filtering program
+/// accounts on the `entity` field.
+///
+/// Unlike other registry accounts, its serialized size varies with the
+/// strings it holds. The account is allocated at `SIZE`, the largest
+/// possible serialization, and the unused tail is kept zeroed.
+#[derive(Default, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EntityMetadata {
+    /// Set by the program on creation.
+    pub initialized: bool,
+    /// Entity this metadata describes.
+    pub entity: Pubkey,
+    /// Display name, at most `MAX_NAME_LEN` bytes.
+    pub name: String,
+    /// Website URL, at most `MAX_WEBSITE_LEN` bytes.
+    pub website: String,
+    /// Free form description, at most `MAX_DESCRIPTION_LEN` bytes.
+    pub description: String,
+    /// Image URI, at most `MAX_IMAGE_URI_LEN` bytes.
+    pub image_uri: String,
+}
+
+impl EntityMetadata {
+    /// Byte offset of `entity` in the packed account, for memcmp filters.
+    pub const ENTITY_OFFSET: usize = 1;
+
+    pub const MAX_NAME_LEN: usize = 32;
+    pub const MAX_WEBSITE_LEN: usize = 64;
+    pub const MAX_DESCRIPTION_LEN: usize = 256;
+    pub const MAX_IMAGE_URI_LEN: usize = 128;
+
+    /// Account size. Strings are serialized with a u64 length prefix.
+    pub const SIZE: usize = 1
+        + 32
+        + (8 + EntityMetadata::MAX_NAME_LEN)
+        + (8 + EntityMetadata::MAX_WEBSITE_LEN)
+        + (8 + EntityMetadata::MAX_DESCRIPTION_LEN)
+        + (8 + EntityMetadata::MAX_IMAGE_URI_LEN);
+
+    /// True if every string is within its bound.
+    pub fn is_valid(&self) -> bool {
+        self.name.len() <= EntityMetadata::MAX_NAME_LEN
+            && self.website.len() <= EntityMetadata::MAX_WEBSITE_LEN
+            && self.description.len() <= EntityMetadata::MAX_DESCRIPTION_LEN
+            && self.image_uri.len() <= EntityMetadata::MAX_IMAGE_URI_LEN
+    }
+}
+
+impl Pack for EntityMetadata {
+    fn pack(src: EntityMetadata, dst: &mut [u8]) -> Result<(), ProgramError> {
+        let size = src.size()? as usize;
+        if size > dst.len() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        into_bytes(&src, &mut dst[..size])?;
+        for b in dst[size..].iter_mut() {
+            *b = 0;
+        }
+        Ok(())
+    }
+
+    fn unpack_unchecked(src: &mut &[u8]) -> Result<EntityMetadata, ProgramError> {
+        from_reader(src)
+    }
+
+    fn size(&self) -> Result<u64, ProgramError> {
+        bytes_size(&self)
+    }
+
+    // The zeroed tail after the serialized strings is expected.
+    fn unpack(src: &[u8]) -> Result<EntityMetadata, ProgramError> {
+        let mut src_mut = src;
+        let metadata = EntityMetadata::unpack_unchecked(&mut src_mut)?;
+        if src_mut.iter().any(|b| *b != 0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(metadata)
+    }
+}
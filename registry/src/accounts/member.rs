@@ -1,15 +1,11 @@
+use crate::accounts::entity::{self, Entity};
+use crate::error::{RegistryError, RegistryErrorCode};
 use serum_common::pack::*;
-use solana_client_gen::solana_sdk::pubkey::Pubkey;
-
-#[cfg(feature = "client")]
-lazy_static::lazy_static! {
-    pub static ref SIZE: u64 = Member::default()
-                .size()
-                .expect("Vesting has a fixed size");
-}
+use solana_client_gen::solana_sdk::pubkey::{Pubkey, PubkeyError};
+use std::convert::TryFrom;
 
 /// Member account tracks membership with a node `Entity`.
-#[derive(Default, Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Default, Debug, serde::Serialize, serde::Deserialize, Pack)]
 pub struct Member {
     /// Set by the program on creation.
     pub initialized: bool,
@@ -24,6 +20,232 @@ pub struct Member {
     pub amount: u64,
     /// Amount of MSRM staked.
     pub mega_amount: u64,
+    /// Free form label set by the beneficiary, e.g., "treasury". Zero padded.
+    pub memo: [u8; 32],
+    /// Rewards accrued and not yet paid out, including the commission on
+    /// the entity led by the beneficiary.
+    pub rewards: u64,
+    /// The entity's `reward_index` as of the last accrual.
+    pub reward_index: u128,
+    /// Registrar of the member's entity, copied on creation.
+    pub registrar: Pubkey,
+    /// Number of PendingWithdrawals started and not yet completed. The
+    /// account can't be closed while any are outstanding, since completing
+    /// them needs it.
+    pub pending_withdrawals: u32,
+    /// Cursor of the next RewardVendor of the entity the member can claim.
+    /// See `Member::sync_rewards_cursor`.
+    pub rewards_cursor: u64,
 }
 
-serum_common::packable!(Member);
+impl Member {
+    /// Byte offset of `entity` in the packed account, for memcmp filters.
+    pub const ENTITY_OFFSET: usize = 1;
+    /// Byte offset of `beneficiary` in the packed account.
+    pub const BENEFICIARY_OFFSET: usize = 33;
+    /// Seed the member account of a beneficiary is created with.
+    pub const SEED: &'static str = "srm:registry:member";
+
+    /// Address of the member account created by `beneficiary`.
+    pub fn address(program_id: &Pubkey, beneficiary: &Pubkey) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_with_seed(beneficiary, Self::SEED, program_id)
+    }
+
+    /// Credits a stake deposit to the member's balance.
+    pub fn did_deposit(&mut self, amount: u64, is_mega: bool) -> Result<(), RegistryError> {
+        let balance = self.balance_mut(is_mega);
+        *balance = balance
+            .checked_add(amount)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    /// Debits a stake withdrawal from the member's balance.
+    pub fn did_withdraw(&mut self, amount: u64, is_mega: bool) -> Result<(), RegistryError> {
+        let balance = self.balance_mut(is_mega);
+        *balance = balance
+            .checked_sub(amount)
+            .ok_or(RegistryErrorCode::Underflow)?;
+        Ok(())
+    }
+
+    /// The member's stake of the given kind, in that token's units.
+    pub fn stake(&self, is_mega: bool) -> u64 {
+        if is_mega {
+            self.mega_amount
+        } else {
+            self.amount
+        }
+    }
+
+    /// True if the member has no SRM or MSRM staked.
+    pub fn stake_is_empty(&self) -> bool {
+        self.amount == 0 && self.mega_amount == 0
+    }
+
+    /// True if the account can be closed, i.e., nothing is staked, no
+    /// rewards are left unpaid and no withdrawal is waiting to complete.
+    pub fn can_close(&self) -> bool {
+        self.stake_is_empty() && self.rewards == 0 && self.pending_withdrawals == 0
+    }
+
+    /// Records a PendingWithdrawal started from this member.
+    pub fn did_start_withdrawal(&mut self) -> Result<(), RegistryError> {
+        self.pending_withdrawals = self
+            .pending_withdrawals
+            .checked_add(1)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    /// Records the completion of a PendingWithdrawal started from this
+    /// member.
+    pub fn did_end_withdrawal(&mut self) -> Result<(), RegistryError> {
+        self.pending_withdrawals = self
+            .pending_withdrawals
+            .checked_sub(1)
+            .ok_or(RegistryErrorCode::Underflow)?;
+        Ok(())
+    }
+
+    /// This member's cut of `members_amount`, the part of a reward left to
+    /// the members of `entity` after the leader's commission. Shares are
+    /// proportional to stake and round down.
+    pub fn reward_share(&self, entity: &Entity, members_amount: u64) -> u64 {
+        let total = entity.stake_weight();
+        if total == 0 {
+            return 0;
+        }
+        // Token supplies keep the product well within a u128.
+        let weight = entity::stake_weight(self.amount, self.mega_amount).min(total);
+        (members_amount as u128 * weight / total) as u64
+    }
+
+    /// Accrues the member's pro-rata share of the rewards dropped on
+    /// `entity` since the last accrual. Must run before the member's stake
+    /// changes, so each drop is shared by the stake at the time, and after
+    /// `Entity::update_reward_index`, so streamed rewards are included.
+    pub fn accrue_rewards(&mut self, entity: &Entity) -> Result<(), RegistryError> {
+        let growth = entity
+            .reward_index
+            .checked_sub(self.reward_index)
+            .ok_or(RegistryErrorCode::Underflow)?;
+        let accrued = entity::stake_weight(self.amount, self.mega_amount)
+            .checked_mul(growth)
+            .ok_or(RegistryErrorCode::Overflow)?
+            / Entity::REWARD_INDEX_PRECISION;
+        self.did_earn(u64::try_from(accrued).map_err(|_| RegistryErrorCode::Overflow)?)?;
+        self.reward_index = entity.reward_index;
+        Ok(())
+    }
+
+    /// Checks the member has claimed every RewardVendor of `entity`, so each
+    /// was shared by the stake the member had at the drop. Must run before
+    /// the member's stake changes. A member with nothing staked has nothing
+    /// to claim, and skips the vendors dropped so far.
+    pub fn sync_rewards_cursor(&mut self, entity: &Entity) -> Result<(), RegistryError> {
+        if self.stake_is_empty() {
+            self.rewards_cursor = entity.vendor_count;
+        }
+        if self.rewards_cursor != entity.vendor_count {
+            return Err(RegistryErrorCode::VendorRewardsUnclaimed)?;
+        }
+        Ok(())
+    }
+
+    /// Moves the member past every RewardVendor of `entity` it hasn't
+    /// claimed, giving up its share of them, so a withdrawal is never held
+    /// up by unclaimed vendors.
+    pub fn forfeit_unclaimed_vendors(&mut self, entity: &Entity) {
+        self.rewards_cursor = entity.vendor_count;
+    }
+
+    /// Credits `amount` to the member's unpaid rewards, e.g., the leader's
+    /// commission returned by `Entity::drop_reward`.
+    pub fn did_earn(&mut self, amount: u64) -> Result<(), RegistryError> {
+        self.rewards = self
+            .rewards
+            .checked_add(amount)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        Ok(())
+    }
+
+    /// Stakes the member's unpaid rewards with `entity` as SRM, returning the
+    /// amount. The rewards already sit in the registrar's SRM vault, so no
+    /// tokens move. Must run after `Entity::update_reward_index`, like
+    /// `accrue_rewards`.
+    pub fn compound_rewards(&mut self, entity: &mut Entity) -> Result<u64, RegistryError> {
+        self.sync_rewards_cursor(entity)?;
+        self.accrue_rewards(entity)?;
+        let amount = self.rewards;
+        self.did_deposit(amount, false)?;
+        entity.did_deposit(amount, false)?;
+        self.rewards = 0;
+        Ok(amount)
+    }
+
+    /// Moves the member's stake and unpaid rewards out of `entity` into a
+    /// new member of `new_entity`, at `new_entity_key`, keeping the
+    /// beneficiary, delegate and memo. Rewards dropped on `entity` until now
+    /// stay the member's, and those dropped on `new_entity` before the move
+    /// aren't. Returns the new member, leaving this one empty. Withdrawals
+    /// pending on this member stay with it, and it counts as a member of
+    /// `entity` until closed. Fails if `new_entity` is full.
+    pub fn migrate(
+        &mut self,
+        entity: &mut Entity,
+        new_entity: &mut Entity,
+        new_entity_key: Pubkey,
+    ) -> Result<Member, RegistryError> {
+        new_entity.did_join()?;
+        self.sync_rewards_cursor(entity)?;
+        self.accrue_rewards(entity)?;
+        entity.did_withdraw(self.amount, false)?;
+        entity.did_withdraw(self.mega_amount, true)?;
+        new_entity.did_deposit(self.amount, false)?;
+        new_entity.did_deposit(self.mega_amount, true)?;
+        let migrated = Member {
+            initialized: true,
+            entity: new_entity_key,
+            beneficiary: self.beneficiary,
+            delegate: self.delegate,
+            amount: self.amount,
+            mega_amount: self.mega_amount,
+            memo: self.memo,
+            rewards: self.rewards,
+            reward_index: new_entity.reward_index,
+            registrar: new_entity.registrar,
+            pending_withdrawals: 0,
+            rewards_cursor: new_entity.vendor_count,
+        };
+        self.amount = 0;
+        self.mega_amount = 0;
+        self.rewards = 0;
+        Ok(migrated)
+    }
+
+    fn balance_mut(&mut self, is_mega: bool) -> &mut u64 {
+        if is_mega {
+            &mut self.mega_amount
+        } else {
+            &mut self.amount
+        }
+    }
+
+    /// Zero pads `memo` into the on-chain representation. Returns None if
+    /// it's longer than 32 bytes.
+    pub fn encode_memo(memo: &str) -> Option<[u8; 32]> {
+        if memo.len() > 32 {
+            return None;
+        }
+        let mut bytes = [0u8; 32];
+        bytes[..memo.len()].copy_from_slice(memo.as_bytes());
+        Some(bytes)
+    }
+
+    /// The memo with its zero padding stripped.
+    pub fn memo_str(&self) -> String {
+        let len = self.memo.iter().position(|b| *b == 0).unwrap_or(32);
+        String::from_utf8_lossy(&self.memo[..len]).into_owned()
+    }
+}
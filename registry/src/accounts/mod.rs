@@ -1,7 +1,23 @@
+pub mod beneficiary_transfer;
 pub mod entity;
+pub mod entity_leader_transfer;
+pub mod entity_metadata;
 pub mod member;
+pub mod pending_withdrawal;
 pub mod registrar;
+pub mod reward_schedule;
+pub mod reward_vendor;
+pub mod stake_snapshot;
+pub mod voter_weight_record;
 
+pub use beneficiary_transfer::BeneficiaryTransfer;
 pub use entity::{Entity, StakeKind};
+pub use entity_leader_transfer::EntityLeaderTransfer;
+pub use entity_metadata::EntityMetadata;
 pub use member::Member;
+pub use pending_withdrawal::PendingWithdrawal;
 pub use registrar::Registrar;
+pub use reward_schedule::RewardSchedule;
+pub use reward_vendor::RewardVendor;
+pub use stake_snapshot::StakeSnapshot;
+pub use voter_weight_record::VoterWeightRecord;
@@ -4,13 +4,29 @@ use solana_client_gen::solana_sdk::pubkey::Pubkey;
 /// PendingWithdrawal accounts are created to initiate a withdrawal.
 /// Once the timelock on the pendign withdrawal passes, the PendingWithdrawal
 /// can be burned in exchange for the specified withdrawal amount.
+#[derive(Default, Debug, serde::Serialize, serde::Deserialize, Pack)]
 pub struct PendingWithdrawal {
+    /// Set by the program on creation.
     pub initialized: bool,
+    /// Set once the withdrawal completes, so it can't be completed twice.
     pub burned: bool,
-    pub start_slot: Pubkey,
+    /// Member account the stake is withdrawn from.
+    pub member: Pubkey,
+    /// Unix timestamp when the withdrawal was started.
+    pub start_ts: i64,
+    /// Unix timestamp when the withdrawal can be completed. Fixed from the
+    /// registrar's timelock at the start, so later registrar updates don't
+    /// affect withdrawals already pending.
+    pub end_ts: i64,
+    /// Amount of SRM being withdrawn.
     pub amount: u64,
+    /// Amount of MSRM being withdrawn.
     pub mega_amount: u64,
-    pub member: Pubkey,
 }
 
-serum_common::packable!(PendingWithdrawal);
+impl PendingWithdrawal {
+    /// True if the timelock has passed at `now`.
+    pub fn can_complete(&self, now: i64) -> bool {
+        now >= self.end_ts
+    }
+}
@@ -1,15 +1,10 @@
+use crate::accounts::entity;
+use crate::error::{RegistryError, RegistryErrorCode};
 use serum_common::pack::*;
 use solana_client_gen::solana_sdk::pubkey::Pubkey;
 
-#[cfg(feature = "client")]
-lazy_static::lazy_static! {
-    pub static ref SIZE: u64 = Registrar::default()
-                .size()
-                .expect("Vesting has a fixed size");
-}
-
 /// Registry defines the account representing an instance of the program.
-#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, serde::Serialize, serde::Deserialize, Pack)]
 pub struct Registrar {
     /// Set by the program on initialization.
     pub initialized: bool,
@@ -17,11 +12,61 @@ pub struct Registrar {
     pub authority: Pubkey,
     /// Maps capability identifier to the bps fee rate earned for the capability.
     pub capabilities_fees_bps: [u32; 32],
-    /// Number of slots that must pass for a withdrawal to complete.
+    /// Number of slots that must pass for a withdrawal to complete, as set
+    /// by registrars initialized before withdrawals were timelocked in
+    /// seconds. No longer read; see `withdrawal_timelock_secs`.
     pub withdrawal_timelock: u64,
+    /// Number of seconds that must pass for a withdrawal to complete.
+    pub withdrawal_timelock_secs: u64,
+    /// SRM vault holding the stake of the registrar's members and the
+    /// rewards dropped on its entities, owned by the
+    /// program-derived-address of `signer_seeds`.
+    pub vault: Pubkey,
+    /// Nonce of the vault's program-derived-address.
+    pub nonce: u8,
+    /// Set by Pause, blocking reward drops until Unpause. Withdrawals are
+    /// never paused.
+    pub paused: bool,
+    /// MSRM vault holding the members' MSRM stake, owned by the same
+    /// program-derived-address as `vault`.
+    pub mega_vault: Pubkey,
+    /// Number of seconds a reward dropped on an entity is streamed to its
+    /// members over, so stake added just before a drop only earns its
+    /// share of the time it's staked. 0 drops rewards at once.
+    pub reward_period_secs: u64,
+    /// Most stake a single member may hold, in SRM, counting MSRM as
+    /// `entity::stake_weight` does, so no one member dominates an entity's
+    /// reward share. 0 for no cap.
+    pub max_stake_per_member: u64,
 }
 
 impl Registrar {
+    /// Seeds of the program-derived-address owning the vault of the
+    /// registrar at `registrar`.
+    pub fn signer_seeds<'a>(registrar: &'a Pubkey, nonce: &'a u8) -> [&'a [u8]; 2] {
+        [registrar.as_ref(), bytemuck::bytes_of(nonce)]
+    }
+
+    /// The vault holding stake of the given kind.
+    pub fn stake_vault(&self, is_mega: bool) -> &Pubkey {
+        if is_mega {
+            &self.mega_vault
+        } else {
+            &self.vault
+        }
+    }
+
+    /// Checks a member holding `amount` SRM and `mega_amount` MSRM is within
+    /// `max_stake_per_member`.
+    pub fn check_stake_cap(&self, amount: u64, mega_amount: u64) -> Result<(), RegistryError> {
+        if self.max_stake_per_member != 0
+            && entity::stake_weight(amount, mega_amount) > self.max_stake_per_member as u128
+        {
+            return Err(RegistryErrorCode::StakeCapExceeded)?;
+        }
+        Ok(())
+    }
+
     /// Returns the capability id of the next available slot. Otherwise None,
     /// if full.
     pub fn next_free_capability_id(&self) -> Option<u8> {
@@ -33,5 +78,3 @@ impl Registrar {
         None
     }
 }
-
-serum_common::packable!(Registrar);
@@ -0,0 +1,70 @@
+use crate::error::{RegistryError, RegistryErrorCode};
+use serum_common::pack::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+use std::convert::TryFrom;
+
+/// RewardSchedule drops a fixed SRM reward on an Entity every period, out of
+/// a funding vault topped up ahead of time, e.g., a project's weekly
+/// emissions. Once a period has come due, anyone can crank DistributeEpoch
+/// to drop the next tranche, as DropEntityReward would, so the schedule
+/// doesn't depend on someone remembering to send each drop.
+#[derive(Default, Debug, PartialEq, serde::Serialize, serde::Deserialize, Pack)]
+pub struct RewardSchedule {
+    /// Set by the program on creation.
+    pub initialized: bool,
+    /// Entity the rewards are dropped on.
+    pub entity: Pubkey,
+    /// Registrar of the entity, whose SRM vault the tranches move into.
+    pub registrar: Pubkey,
+    /// SRM token account funding the tranches, owned by the
+    /// program-derived-address of `signer_seeds`.
+    pub vault: Pubkey,
+    /// Nonce of the vault's program-derived-address.
+    pub nonce: u8,
+    /// Amount dropped each period. The last tranche is whatever's left.
+    pub amount: u64,
+    /// Number of seconds between tranches.
+    pub period_secs: u64,
+    /// Unix timestamp at which the next tranche comes due.
+    pub next_ts: i64,
+    /// Number of tranches dropped so far.
+    pub epoch: u64,
+}
+
+impl RewardSchedule {
+    /// Byte offset of `entity` in the packed account, for memcmp filters.
+    pub const ENTITY_OFFSET: usize = 1;
+
+    /// Seeds of the program-derived-address owning the vault of the
+    /// schedule at `schedule`.
+    pub fn signer_seeds<'a>(schedule: &'a Pubkey, nonce: &'a u8) -> [&'a [u8]; 2] {
+        [schedule.as_ref(), bytemuck::bytes_of(nonce)]
+    }
+
+    /// True iff the next tranche can be dropped at `now`.
+    pub fn is_due(&self, now: i64) -> bool {
+        now >= self.next_ts
+    }
+
+    /// The next tranche, given the funding vault holds `balance`.
+    pub fn tranche(&self, balance: u64) -> u64 {
+        self.amount.min(balance)
+    }
+
+    /// Records a tranche dropped, scheduling the next one a period after
+    /// this one came due, not after it was cranked, so late cranks don't
+    /// push the schedule back. Missed periods can be cranked one by one.
+    pub fn did_distribute(&mut self) -> Result<(), RegistryError> {
+        let period_secs =
+            i64::try_from(self.period_secs).map_err(|_| RegistryErrorCode::Overflow)?;
+        self.next_ts = self
+            .next_ts
+            .checked_add(period_secs)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        self.epoch = self
+            .epoch
+            .checked_add(1)
+            .ok_or(RegistryErrorCode::Overflow)?;
+        Ok(())
+    }
+}
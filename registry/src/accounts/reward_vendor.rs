@@ -0,0 +1,144 @@
+use crate::accounts::entity;
+use crate::accounts::Member;
+use serum_common::pack::*;
+use solana_client_gen::solana_sdk::hash::hashv;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// RewardVendor holds a reward dropped on an Entity in any SPL token, e.g.,
+/// by a partner project sponsoring the entity's members in its own token.
+/// The reward sits in the vendor's own vault until each member claims its
+/// share with ClaimVendorReward.
+///
+/// Shares are proportional to stake at the time of the drop. Vendors of an
+/// entity are numbered in the order they're dropped, see `cursor`, and a
+/// member claims them in that order, see `Member::rewards_cursor`. Since its
+/// stake can't change before it has claimed them all, its stake at claim
+/// time is its stake at the drop.
+///
+/// Alternatively, the dropper publishes a `merkle_root` of what each
+/// claimant is owed, computed off chain, e.g., from a StakeSnapshot, and
+/// claimants prove their leaf with ClaimMerkleReward. No per-member state is
+/// kept but a receipt per claim, so it scales to any number of members.
+///
+/// Claims close at `expiry_ts`, after which `expiry_receiver` can take back
+/// whatever's left with ReclaimVendor.
+#[derive(Default, Debug, PartialEq, serde::Serialize, serde::Deserialize, Pack)]
+pub struct RewardVendor {
+    /// Set by the program on creation.
+    pub initialized: bool,
+    /// Entity the reward was dropped on.
+    pub entity: Pubkey,
+    /// Position of the vendor among the entity's, i.e., the entity's
+    /// `vendor_count` before the drop.
+    pub cursor: u64,
+    /// Token account holding the reward, owned by the
+    /// program-derived-address of `signer_seeds`.
+    pub vault: Pubkey,
+    /// Mint of the reward.
+    pub mint: Pubkey,
+    /// Nonce of the vault's program-derived-address.
+    pub nonce: u8,
+    /// Amount dropped.
+    pub total: u64,
+    /// `Entity::stake_weight` at the drop, which the total is shared by.
+    pub stake_weight: u128,
+    /// Merkle root of the claims on the vendor, see
+    /// `RewardVendor::merkle_leaf`, or zeroes if shared by stake. A Merkle
+    /// vendor isn't numbered, so `cursor` and `stake_weight` are 0.
+    pub merkle_root: [u8; 32],
+    /// Unix timestamp at which claims close.
+    pub expiry_ts: i64,
+    /// Who can reclaim the unclaimed reward once expired.
+    pub expiry_receiver: Pubkey,
+}
+
+impl RewardVendor {
+    /// Byte offset of `entity` in the packed account, for memcmp filters.
+    pub const ENTITY_OFFSET: usize = 1;
+    /// First seed of the address of a claim receipt of a Merkle vendor.
+    pub const CLAIM_SEED: &'static [u8] = b"srm:registry:vendor-claim";
+
+    /// Seeds of the program-derived-address owning the vault of the vendor
+    /// at `vendor`.
+    pub fn signer_seeds<'a>(vendor: &'a Pubkey, nonce: &'a u8) -> [&'a [u8]; 2] {
+        [vendor.as_ref(), bytemuck::bytes_of(nonce)]
+    }
+
+    /// `member`'s share of the reward, proportional to its stake. Rounds
+    /// down, so the vault always covers every share.
+    pub fn reward_share(&self, member: &Member) -> u64 {
+        if self.stake_weight == 0 {
+            return 0;
+        }
+        // Token supplies keep the product well within a u128.
+        let weight = entity::stake_weight(member.amount, member.mega_amount).min(self.stake_weight);
+        (self.total as u128 * weight / self.stake_weight) as u64
+    }
+
+    /// True iff claims have closed at `now`.
+    pub fn expired(&self, now: i64) -> bool {
+        now >= self.expiry_ts
+    }
+
+    /// The Merkle root of the claims, if this is a Merkle vendor.
+    pub fn merkle_root(&self) -> Option<[u8; 32]> {
+        if self.merkle_root == [0; 32] {
+            None
+        } else {
+            Some(self.merkle_root)
+        }
+    }
+
+    /// Leaf of a Merkle vendor's tree owing `amount` to `claimant`. `index`
+    /// is the leaf's position, unique within the tree.
+    pub fn merkle_leaf(index: u64, claimant: &Pubkey, amount: u64) -> [u8; 32] {
+        hashv(&[
+            &[0],
+            &index.to_le_bytes(),
+            claimant.as_ref(),
+            &amount.to_le_bytes(),
+        ])
+        .to_bytes()
+    }
+
+    /// Parent of two nodes of a Merkle vendor's tree. Pairs are hashed in
+    /// sorted order, so proofs needn't say which side each node is on, and
+    /// behind a different prefix than leaves, so a node can't pass for a
+    /// leaf.
+    pub fn merkle_parent(a: &[u8; 32], b: &[u8; 32]) -> [u8; 32] {
+        let (left, right) = if a <= b { (a, b) } else { (b, a) };
+        hashv(&[&[1], left, right]).to_bytes()
+    }
+
+    /// True if `proof` leads from `leaf` to the vendor's Merkle root.
+    pub fn verify_merkle_proof(&self, leaf: [u8; 32], proof: &[[u8; 32]]) -> bool {
+        match self.merkle_root() {
+            None => false,
+            Some(root) => {
+                proof
+                    .iter()
+                    .fold(leaf, |node, sibling| Self::merkle_parent(&node, sibling))
+                    == root
+            }
+        }
+    }
+
+    /// Seeds of the address of the receipt of the claim at `index` on the
+    /// Merkle vendor at `vendor`, whose little endian bytes are `index`.
+    pub fn claim_signer_seeds<'a>(
+        vendor: &'a Pubkey,
+        index: &'a [u8; 8],
+        nonce: &'a [u8; 1],
+    ) -> [&'a [u8]; 4] {
+        [Self::CLAIM_SEED, vendor.as_ref(), index, nonce]
+    }
+
+    /// Address of the receipt of the claim at `index` on the Merkle vendor
+    /// at `vendor`, and the nonce to pass to ClaimMerkleReward.
+    pub fn find_claim_address(program_id: &Pubkey, vendor: &Pubkey, index: u64) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[Self::CLAIM_SEED, vendor.as_ref(), &index.to_le_bytes()],
+            program_id,
+        )
+    }
+}
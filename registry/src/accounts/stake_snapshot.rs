@@ -0,0 +1,95 @@
+use serum_common::pack::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// StakeSnapshot records the stake backing an Entity at a point in time,
+/// taken by the registrar's authority, e.g., once per epoch. Nothing ever
+/// modifies it after creation, so third parties can base airdrops or
+/// retroactive rewards on it without trusting an off-chain indexer.
+///
+/// Each lives at a program derived address of its registrar, entity and
+/// epoch, see `StakeSnapshot::find_address`, so there's at most one per
+/// entity and epoch, and clients can compute where to find it. They can also
+/// find the snapshots of an entity by filtering program accounts on the
+/// `entity` field, and of an epoch on `epoch`.
+#[derive(Default, Debug, PartialEq, serde::Serialize, serde::Deserialize, Pack)]
+pub struct StakeSnapshot {
+    /// Set by the program on creation.
+    pub initialized: bool,
+    /// Registrar whose authority took the snapshot.
+    pub registrar: Pubkey,
+    /// Entity the stake is backing.
+    pub entity: Pubkey,
+    /// Epoch the snapshot was taken in.
+    pub epoch: u64,
+    /// Slot the snapshot was taken in.
+    pub slot: u64,
+    /// Unix timestamp the snapshot was taken at.
+    pub unix_timestamp: i64,
+    /// `Entity::amount` at the snapshot.
+    pub amount: u64,
+    /// `Entity::mega_amount` at the snapshot.
+    pub mega_amount: u64,
+    /// Optional Merkle root of the entity's members' stake, computed off
+    /// chain by the authority. The program doesn't check it, so it's only as
+    /// trustworthy as the authority. Zeroed when not provided.
+    pub members_root: [u8; 32],
+}
+
+impl StakeSnapshot {
+    /// Byte offset of `entity` in the packed account, for memcmp filters.
+    pub const ENTITY_OFFSET: usize = 33;
+    /// Byte offset of `epoch` in the packed account, for memcmp filters.
+    pub const EPOCH_OFFSET: usize = 65;
+    /// First seed of a snapshot's program derived address.
+    pub const SEED: &'static [u8] = b"srm:registry:stake-snapshot";
+
+    /// Seeds of the address of the snapshot of `entity` under `registrar`
+    /// in the epoch whose little endian bytes are `epoch`.
+    pub fn signer_seeds<'a>(
+        registrar: &'a Pubkey,
+        entity: &'a Pubkey,
+        epoch: &'a [u8; 8],
+        nonce: &'a [u8; 1],
+    ) -> [&'a [u8]; 5] {
+        [
+            Self::SEED,
+            registrar.as_ref(),
+            entity.as_ref(),
+            epoch,
+            nonce,
+        ]
+    }
+
+    /// Address of the snapshot of `entity` under `registrar` in `epoch`,
+    /// and the nonce to pass to `SnapshotEntityStake`.
+    pub fn find_address(
+        program_id: &Pubkey,
+        registrar: &Pubkey,
+        entity: &Pubkey,
+        epoch: u64,
+    ) -> (Pubkey, u8) {
+        Pubkey::find_program_address(
+            &[
+                Self::SEED,
+                registrar.as_ref(),
+                entity.as_ref(),
+                &epoch.to_le_bytes(),
+            ],
+            program_id,
+        )
+    }
+
+    /// Total stake backing the entity at the snapshot, in SRM base units.
+    pub fn stake_weight(&self) -> u128 {
+        super::entity::stake_weight(self.amount, self.mega_amount)
+    }
+
+    /// The members' Merkle root, if one was provided.
+    pub fn members_root(&self) -> Option<[u8; 32]> {
+        if self.members_root == [0; 32] {
+            None
+        } else {
+            Some(self.members_root)
+        }
+    }
+}
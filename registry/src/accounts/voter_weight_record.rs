@@ -0,0 +1,85 @@
+use serum_common::pack::*;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// VoterWeightRecord reports a member's stake to spl-governance, laid out as
+/// the voter weight addin's account of the same name, so a realm configured
+/// with this program as its voter weight addin votes with staked SRM or
+/// MSRM instead of a parallel staking system.
+///
+/// The weight is the member's stake of the realm's governing token, in that
+/// token's units, as of the slot it was last updated in. It expires after
+/// that slot, so governance only accepts it in the transaction refreshing
+/// it, e.g., the one casting the vote, freezing the weight at that slot.
+///
+/// Like EntityMetadata, its serialized size varies, here with the options
+/// set. The account is allocated at `SIZE`, the largest possible
+/// serialization, and the unused tail is kept zeroed.
+#[derive(Default, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VoterWeightRecord {
+    /// `ACCOUNT_DISCRIMINATOR` once initialized, zeroed before.
+    pub account_discriminator: [u8; 8],
+    /// Realm the record votes in.
+    pub realm: Pubkey,
+    /// Governing token of the realm, the registrar's SRM or MSRM mint.
+    pub governing_token_mint: Pubkey,
+    /// Beneficiary of the member whose stake is reported.
+    pub governing_token_owner: Pubkey,
+    /// The member's stake of the governing token.
+    pub voter_weight: u64,
+    /// Last slot `voter_weight` is valid in.
+    pub voter_weight_expiry: Option<u64>,
+    /// Governance action the weight is restricted to. Never set.
+    pub weight_action: Option<u8>,
+    /// Target of `weight_action`. Never set.
+    pub weight_action_target: Option<Pubkey>,
+    /// Reserved by the addin interface.
+    pub reserved: [u8; 8],
+}
+
+impl VoterWeightRecord {
+    /// Discriminator of the addin interface's VoterWeightRecord.
+    pub const ACCOUNT_DISCRIMINATOR: [u8; 8] = *b"2ef99b4b";
+    /// Byte offset of `governing_token_owner` in the packed account, for
+    /// memcmp filters.
+    pub const GOVERNING_TOKEN_OWNER_OFFSET: usize = 72;
+
+    /// Account size. Options are serialized with a one byte tag.
+    pub const SIZE: usize = 8 + 32 + 32 + 32 + 8 + (1 + 8) + (1 + 1) + (1 + 32) + 8;
+
+    /// True once the program has written the record.
+    pub fn is_initialized(&self) -> bool {
+        self.account_discriminator == VoterWeightRecord::ACCOUNT_DISCRIMINATOR
+    }
+}
+
+impl Pack for VoterWeightRecord {
+    fn pack(src: VoterWeightRecord, dst: &mut [u8]) -> Result<(), ProgramError> {
+        let size = src.size()? as usize;
+        if size > dst.len() {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        into_bytes(&src, &mut dst[..size])?;
+        for b in dst[size..].iter_mut() {
+            *b = 0;
+        }
+        Ok(())
+    }
+
+    fn unpack_unchecked(src: &mut &[u8]) -> Result<VoterWeightRecord, ProgramError> {
+        from_reader(src)
+    }
+
+    fn size(&self) -> Result<u64, ProgramError> {
+        bytes_size(&self)
+    }
+
+    // The zeroed tail after unset options is expected.
+    fn unpack(src: &[u8]) -> Result<VoterWeightRecord, ProgramError> {
+        let mut src_mut = src;
+        let record = VoterWeightRecord::unpack_unchecked(&mut src_mut)?;
+        if src_mut.iter().any(|b| *b != 0) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        Ok(record)
+    }
+}
@@ -1,22 +1,111 @@
-use crate::accounts::member;
-use crate::accounts::registrar;
+//! The generated client and instruction builders. Only the instruction
+//! builders are compiled with the `wasm` feature.
+
 use serum_common::pack::Pack;
 use solana_client_gen::prelude::*;
-use solana_client_gen::solana_sdk;
 use solana_client_gen::solana_sdk::instruction::AccountMeta;
 use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+#[cfg(feature = "client")]
+use crate::accounts::{
+    Entity, EntityMetadata, Member, Registrar, RewardSchedule, RewardVendor, StakeSnapshot,
+    VoterWeightRecord,
+};
+#[cfg(feature = "client")]
+use crate::error::RegistryErrorCode;
+#[cfg(feature = "client")]
+use crate::estimate::{self, RewardsEstimate};
+#[cfg(feature = "client")]
+use serum_common::client::rpc;
+#[cfg(feature = "client")]
+use serum_common::pack::PackedSize;
+#[cfg(feature = "client")]
+use solana_client_gen::solana_sdk;
+#[cfg(feature = "client")]
 use solana_client_gen::solana_sdk::signers::Signers;
+#[cfg(feature = "client")]
 use solana_client_gen::solana_sdk::system_instruction;
 
 solana_client_gen_extension! {
     impl Client {
+        /// Creates a registrar and its SRM and MSRM vaults, owned by the
+        /// registrar's program-derived-address, and initializes it.
+        pub fn create_registrar(
+            &self,
+            srm_mint: &Pubkey,
+            msrm_mint: &Pubkey,
+            authority: Pubkey,
+            withdrawal_timelock_secs: u64,
+            reward_period_secs: u64,
+        ) -> Result<(Signature, Pubkey), ClientError> {
+            let registrar_kp = Keypair::generate(&mut OsRng);
+            let (vault_authority, nonce) = Pubkey::find_program_address(
+                &[registrar_kp.pubkey().as_ref()],
+                self.program(),
+            );
+            let vault = serum_common::client::rpc::create_token_account(
+                self.rpc(),
+                srm_mint,
+                &vault_authority,
+                self.payer(),
+            )
+            .map_err(|e| ClientError::RawError(e.to_string()))?;
+            let mega_vault = serum_common::client::rpc::create_token_account(
+                self.rpc(),
+                msrm_mint,
+                &vault_authority,
+                self.payer(),
+            )
+            .map_err(|e| ClientError::RawError(e.to_string()))?;
+
+            let lamports = self.rpc().get_minimum_balance_for_rent_exemption(
+                Registrar::SIZE as usize,
+            )?;
+            let create_acc_instr = system_instruction::create_account(
+                &self.payer().pubkey(),
+                &registrar_kp.pubkey(),
+                lamports,
+                Registrar::SIZE,
+                self.program(),
+            );
+            let accounts = [
+                AccountMeta::new(registrar_kp.pubkey(), false),
+                AccountMeta::new_readonly(vault.pubkey(), false),
+                AccountMeta::new_readonly(mega_vault.pubkey(), false),
+                AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
+            ];
+            let initialize_instr = super::instruction::initialize(
+                *self.program(),
+                &accounts,
+                authority,
+                withdrawal_timelock_secs,
+                nonce,
+                reward_period_secs,
+            );
+
+            let instructions = [create_acc_instr, initialize_instr];
+            let signers = [self.payer(), &registrar_kp];
+            self
+                .session()
+                .send_and_confirm_with_config(
+                    &instructions,
+                    &self.payer().pubkey(),
+                    &signers,
+                    self.options().commitment,
+                    self.options().tx,
+                )
+                .map_err(ClientError::RpcError)
+                .map(|sig| (sig, registrar_kp.pubkey()))
+        }
+
         pub fn create_entity_derived(
             &self,
             leader_kp: &Keypair,
+            registrar: Pubkey,
             capabilities: u32,
             stake_kind: crate::accounts::StakeKind,
         ) -> Result<(Signature, Pubkey), ClientError> {
-            let entity_account_size = *crate::accounts::entity::SIZE;
+            let entity_account_size = Entity::SIZE;
             let lamports = self.rpc().get_minimum_balance_for_rent_exemption(
                 entity_account_size as usize,
             )?;
@@ -36,6 +125,7 @@ solana_client_gen_extension! {
             let accounts = [
                 AccountMeta::new(entity_address, false),
                 AccountMeta::new_readonly(leader_kp.pubkey(), true),
+                AccountMeta::new_readonly(registrar, false),
                 AccountMeta::new_readonly(solana_sdk::sysvar::rent::ID, false),
             ];
             let create_entity_instr = super::instruction::create_entity(
@@ -48,21 +138,12 @@ solana_client_gen_extension! {
                 create_acc_instr, create_entity_instr,
             ];
             let signers = [leader_kp, self.payer()];
-            let (recent_hash, _fee_calc) = self
-                .rpc()
-                .get_recent_blockhash()?;
-
-            let tx = Transaction::new_signed_with_payer(
-                &instructions,
-                Some(&self.payer().pubkey()),
-                &signers,
-                recent_hash,
-            );
-
             self
-                .rpc()
-                .send_and_confirm_transaction_with_spinner_and_config(
-                    &tx,
+                .session()
+                .send_and_confirm_with_config(
+                    &instructions,
+                    &self.payer().pubkey(),
+                    &signers,
                     self.options().commitment,
                     self.options().tx,
                 )
@@ -80,7 +161,7 @@ solana_client_gen_extension! {
             let member_address = self.member_address_derived()?;
 
             let lamports = self.rpc().get_minimum_balance_for_rent_exemption(
-                *crate::accounts::member::SIZE as usize,
+                Member::SIZE as usize,
             )?;
 
             let create_acc_instr =
@@ -90,7 +171,7 @@ solana_client_gen_extension! {
                     &self.payer().pubkey(),
                     Self::member_seed(),
                     lamports,
-                    *crate::accounts::member::SIZE,
+                    Member::SIZE,
                     &self.program(),
                 );
 
@@ -111,21 +192,12 @@ solana_client_gen_extension! {
                 create_acc_instr, member_instr,
             ];
             let signers = [self.payer()];
-            let (recent_hash, _fee_calc) = self
-                .rpc()
-                .get_recent_blockhash()?;
-
-            let tx = Transaction::new_signed_with_payer(
-                &instructions,
-                Some(&self.payer().pubkey()),
-                &signers,
-                recent_hash,
-            );
-
             self
-                .rpc()
-                .send_and_confirm_transaction_with_spinner_and_config(
-                    &tx,
+                .session()
+                .send_and_confirm_with_config(
+                    &instructions,
+                    &self.payer().pubkey(),
+                    &signers,
                     self.options().commitment,
                     self.options().tx,
                 )
@@ -134,27 +206,183 @@ solana_client_gen_extension! {
         }
 
         pub fn entity_address_derived(&self, leader: &Pubkey) -> Result<Pubkey, ClientError> {
-            Pubkey::create_with_seed(
-                leader,
-                Self::entity_seed(),
-                &self.program(),
-            ).map_err(|e| ClientError::RawError(e.to_string()))
+            Entity::address(&self.program(), leader)
+                .map_err(|e| ClientError::RawError(e.to_string()))
         }
 
         pub fn entity_seed() -> &'static str {
-            "srm:registry:entity"
+            Entity::SEED
         }
 
         pub fn member_address_derived(&self) -> Result<Pubkey, ClientError> {
-            Pubkey::create_with_seed(
-                &self.payer().pubkey(),
-                Self::member_seed(),
-                &self.program(),
-            ).map_err(|e| ClientError::RawError(e.to_string()))
+            Member::address(&self.program(), &self.payer().pubkey())
+                .map_err(|e| ClientError::RawError(e.to_string()))
         }
 
         pub fn member_seed() -> &'static str {
-            "srm:registry:member"
+            Member::SEED
+        }
+
+        /// Every Member account that joined `entity`.
+        pub fn members_of_entity(&self, entity: &Pubkey) -> Result<Vec<(Pubkey, Member)>, ClientError> {
+            program_accounts(
+                self.rpc(),
+                self.program(),
+                Member::SIZE as usize,
+                &[(Member::ENTITY_OFFSET, entity)],
+            )
+        }
+
+        /// Every Member account with the given beneficiary.
+        pub fn members_by_beneficiary(&self, beneficiary: &Pubkey) -> Result<Vec<(Pubkey, Member)>, ClientError> {
+            program_accounts(
+                self.rpc(),
+                self.program(),
+                Member::SIZE as usize,
+                &[(Member::BENEFICIARY_OFFSET, beneficiary)],
+            )
+        }
+
+        /// Every Entity account registered with the program.
+        pub fn entities(&self) -> Result<Vec<(Pubkey, Entity)>, ClientError> {
+            program_accounts(self.rpc(), self.program(), Entity::SIZE as usize, &[])
+        }
+
+        /// Every Entity account led by `leader`.
+        pub fn entities_by_leader(&self, leader: &Pubkey) -> Result<Vec<(Pubkey, Entity)>, ClientError> {
+            program_accounts(
+                self.rpc(),
+                self.program(),
+                Entity::SIZE as usize,
+                &[(Entity::LEADER_OFFSET, leader)],
+            )
+        }
+
+        /// The EntityMetadata account describing `entity`, if any.
+        pub fn entity_metadata(&self, entity: &Pubkey) -> Result<Option<(Pubkey, EntityMetadata)>, ClientError> {
+            program_accounts(
+                self.rpc(),
+                self.program(),
+                EntityMetadata::SIZE,
+                &[(EntityMetadata::ENTITY_OFFSET, entity)],
+            )
+            .map(|accounts| accounts.into_iter().next())
+        }
+
+        /// Every StakeSnapshot taken of `entity`, oldest first.
+        pub fn stake_snapshots(&self, entity: &Pubkey) -> Result<Vec<(Pubkey, StakeSnapshot)>, ClientError> {
+            let mut snapshots: Vec<(Pubkey, StakeSnapshot)> = program_accounts(
+                self.rpc(),
+                self.program(),
+                StakeSnapshot::SIZE as usize,
+                &[(StakeSnapshot::ENTITY_OFFSET, entity)],
+            )?;
+            snapshots.sort_by_key(|(_, snapshot)| snapshot.slot);
+            Ok(snapshots)
+        }
+
+        /// Every RewardVendor dropped on `entity`, in the order members
+        /// claim them.
+        pub fn reward_vendors(&self, entity: &Pubkey) -> Result<Vec<(Pubkey, RewardVendor)>, ClientError> {
+            let mut vendors: Vec<(Pubkey, RewardVendor)> = program_accounts(
+                self.rpc(),
+                self.program(),
+                RewardVendor::SIZE as usize,
+                &[(RewardVendor::ENTITY_OFFSET, entity)],
+            )?;
+            vendors.sort_by_key(|(_, vendor)| vendor.cursor);
+            Ok(vendors)
         }
+
+        /// Every RewardSchedule of `entity`, soonest due first, e.g., for a
+        /// crank running DistributeEpoch.
+        pub fn reward_schedules(&self, entity: &Pubkey) -> Result<Vec<(Pubkey, RewardSchedule)>, ClientError> {
+            let mut schedules: Vec<(Pubkey, RewardSchedule)> = program_accounts(
+                self.rpc(),
+                self.program(),
+                RewardSchedule::SIZE as usize,
+                &[(RewardSchedule::ENTITY_OFFSET, entity)],
+            )?;
+            schedules.sort_by_key(|(_, schedule)| schedule.next_ts);
+            Ok(schedules)
+        }
+
+        /// Every VoterWeightRecord reporting the stake of `owner`'s members.
+        pub fn voter_weight_records(&self, owner: &Pubkey) -> Result<Vec<(Pubkey, VoterWeightRecord)>, ClientError> {
+            program_accounts(
+                self.rpc(),
+                self.program(),
+                VoterWeightRecord::SIZE,
+                &[(VoterWeightRecord::GOVERNING_TOKEN_OWNER_OFFSET, owner)],
+            )
+        }
+
+        /// Estimates the rewards of the member at `member` as of `now`, e.g.,
+        /// the cluster's latest block time. See `estimate::estimate`.
+        pub fn rewards_estimate(&self, member: &Pubkey, now: i64) -> Result<RewardsEstimate, ClientError> {
+            let member: Member = rpc::get_account(self.rpc(), member)
+                .map_err(|e| ClientError::RawError(e.to_string()))?;
+            let registrar: Registrar = rpc::get_account(self.rpc(), &member.registrar)
+                .map_err(|e| ClientError::RawError(e.to_string()))?;
+            let entity: Entity = rpc::get_account(self.rpc(), &member.entity)
+                .map_err(|e| ClientError::RawError(e.to_string()))?;
+            let vendors = self.reward_vendors(&member.entity)?;
+            let schedules = self
+                .reward_schedules(&member.entity)?
+                .into_iter()
+                .map(|(_, schedule)| {
+                    let vault: spl_token::state::Account = rpc::get_token_account(self.rpc(), &schedule.vault)
+                        .map_err(|e| ClientError::RawError(e.to_string()))?;
+                    Ok((schedule, vault.amount))
+                })
+                .collect::<Result<Vec<_>, ClientError>>()?;
+            estimate::estimate(&registrar, &entity, &member, &vendors, &schedules, now)
+                .map_err(|e| ClientError::RawError(e.to_string()))
+        }
+    }
+
+    // Fetches the program's accounts of `data_size` bytes whose data matches
+    // every `(offset, key)` filter, and unpacks them.
+    fn program_accounts<T: Pack>(
+        rpc: &RpcClient,
+        program_id: &Pubkey,
+        data_size: usize,
+        filters: &[(usize, &Pubkey)],
+    ) -> Result<Vec<(Pubkey, T)>, ClientError> {
+        let mut rpc_filters = vec![serde_json::json!({ "dataSize": data_size })];
+        for (offset, key) in filters {
+            rpc_filters.push(serde_json::json!({
+                "memcmp": {
+                    "offset": offset,
+                    "bytes": bs58::encode(key.as_ref()).into_string(),
+                }
+            }));
+        }
+        let response: Vec<serde_json::Value> = rpc
+            .send(
+                solana_client::rpc_request::RpcRequest::GetProgramAccounts,
+                serde_json::json!([
+                    program_id.to_string(),
+                    { "encoding": "base64", "filters": rpc_filters },
+                ]),
+            )
+            .map_err(ClientError::RpcError)?;
+
+        let malformed = || ClientError::RawError("malformed getProgramAccounts response".to_string());
+        response
+            .iter()
+            .map(|keyed| {
+                let address = keyed["pubkey"]
+                    .as_str()
+                    .and_then(|s| s.parse::<Pubkey>().ok())
+                    .ok_or_else(malformed)?;
+                let data = keyed["account"]["data"][0]
+                    .as_str()
+                    .and_then(|s| base64::decode(s).ok())
+                    .ok_or_else(malformed)?;
+                let account = T::unpack(&data).map_err(|e| ClientError::RawError(e.to_string()))?;
+                Ok((address, account))
+            })
+            .collect()
     }
 }
@@ -1,4 +1,4 @@
-use num_enum::IntoPrimitive;
+use num_enum::{IntoPrimitive, TryFromPrimitive};
 use solana_client_gen::solana_sdk::program_error::ProgramError;
 use thiserror::Error;
 
@@ -10,15 +10,120 @@ pub enum RegistryError {
     ErrorCode(#[from] RegistryErrorCode),
 }
 
-#[derive(Debug, IntoPrimitive, Clone, Copy)]
+#[derive(Debug, IntoPrimitive, TryFromPrimitive, Clone, Copy, PartialEq, Eq)]
 #[repr(u32)]
 pub enum RegistryErrorCode {
     WrongSerialization = 1,
     NotReadySeeNextMajorVersion = 2,
     MustBeDelegated = 3,
+    Unauthorized = 4,
+    Overflow = 5,
+    Underflow = 6,
+    InvalidRentSysvar = 7,
+    NotRentExempt = 8,
+    InvalidBeneficiaryTransfer = 9,
+    MemberNotEmpty = 10,
+    InvalidEntityMetadata = 11,
+    InvalidEntityLeaderTransfer = 12,
+    InvalidClockSysvar = 13,
+    InvalidRegistrar = 14,
+    InvalidPendingWithdrawal = 15,
+    WithdrawalTimelockNotPassed = 16,
+    InvalidCommission = 17,
+    InvalidLeaderMember = 18,
+    InvalidMemberMigration = 19,
+    InvalidStakeSnapshot = 20,
+    InvalidVault = 21,
+    Paused = 22,
+    EntityFull = 23,
+    VendorRewardsUnclaimed = 24,
+    InvalidRewardVendor = 25,
+    NothingStaked = 26,
+    InvalidMerkleProof = 27,
+    VendorExpired = 28,
+    VendorNotExpired = 29,
+    InvalidRewardSchedule = 30,
+    EpochNotDue = 31,
+    RewardScheduleEmpty = 32,
+    EmptyReward = 33,
+    StakeCapExceeded = 34,
+    InvalidVoterWeightRecord = 35,
     Unknown = 1000,
 }
 
+#[cfg(any(feature = "client", feature = "wasm"))]
+impl RegistryErrorCode {
+    /// Describes the error for users of a client.
+    pub fn message(&self) -> &'static str {
+        match self {
+            RegistryErrorCode::WrongSerialization => "account data failed to serialize",
+            RegistryErrorCode::NotReadySeeNextMajorVersion => {
+                "not supported until the next major version"
+            }
+            RegistryErrorCode::MustBeDelegated => "entity must be delegated",
+            RegistryErrorCode::Unauthorized => "missing or wrong signer",
+            RegistryErrorCode::Overflow => "amount overflowed",
+            RegistryErrorCode::Underflow => "amount exceeds the balance",
+            RegistryErrorCode::InvalidRentSysvar => "wrong rent sysvar account",
+            RegistryErrorCode::NotRentExempt => "account is not rent exempt",
+            RegistryErrorCode::InvalidBeneficiaryTransfer => {
+                "beneficiary transfer account doesn't match the member"
+            }
+            RegistryErrorCode::MemberNotEmpty => {
+                "member still has stake, unpaid rewards or pending withdrawals"
+            }
+            RegistryErrorCode::InvalidEntityMetadata => "invalid entity metadata account",
+            RegistryErrorCode::InvalidEntityLeaderTransfer => {
+                "leader transfer account doesn't match the entity"
+            }
+            RegistryErrorCode::InvalidClockSysvar => "wrong clock sysvar account",
+            RegistryErrorCode::InvalidRegistrar => "invalid registrar account",
+            RegistryErrorCode::InvalidPendingWithdrawal => {
+                "pending withdrawal doesn't match the member or was already completed"
+            }
+            RegistryErrorCode::WithdrawalTimelockNotPassed => "withdrawal timelock hasn't passed",
+            RegistryErrorCode::InvalidCommission => "commission exceeds 10,000 bps",
+            RegistryErrorCode::InvalidLeaderMember => "member account isn't the entity leader's",
+            RegistryErrorCode::InvalidMemberMigration => {
+                "migration needs a fresh member of another entity under another registrar"
+            }
+            RegistryErrorCode::InvalidStakeSnapshot => {
+                "invalid or already written stake snapshot account"
+            }
+            RegistryErrorCode::InvalidVault => {
+                "vault isn't the registrar's or reward vendor's token account"
+            }
+            RegistryErrorCode::Paused => "registrar is paused",
+            RegistryErrorCode::EntityFull => "entity has reached its member limit",
+            RegistryErrorCode::VendorRewardsUnclaimed => {
+                "claim the entity's reward vendors before changing stake"
+            }
+            RegistryErrorCode::InvalidRewardVendor => {
+                "reward vendor doesn't match the member or is claimed out of order"
+            }
+            RegistryErrorCode::NothingStaked => "nothing is staked with the entity",
+            RegistryErrorCode::InvalidMerkleProof => {
+                "Merkle proof or claim receipt doesn't match the reward vendor"
+            }
+            RegistryErrorCode::VendorExpired => "reward vendor has expired",
+            RegistryErrorCode::VendorNotExpired => "reward vendor hasn't expired yet",
+            RegistryErrorCode::InvalidRewardSchedule => {
+                "invalid reward schedule or schedule parameters"
+            }
+            RegistryErrorCode::EpochNotDue => "the reward schedule's next tranche isn't due yet",
+            RegistryErrorCode::RewardScheduleEmpty => "the reward schedule's vault is empty",
+            RegistryErrorCode::EmptyReward => "the reward dropped is empty",
+            RegistryErrorCode::StakeCapExceeded => {
+                "stake would exceed the registrar's per-member cap"
+            }
+            RegistryErrorCode::InvalidVoterWeightRecord => {
+                "voter weight record belongs to another realm, mint or owner"
+            }
+            RegistryErrorCode::Unknown => "unknown error",
+        }
+    }
+}
+
 impl std::fmt::Display for RegistryErrorCode {
     fn fmt(&self, fmt: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
         <Self as std::fmt::Debug>::fmt(self, fmt)
@@ -0,0 +1,127 @@
+//! Client-side estimates of a member's rewards, using the same account
+//! methods as the program, so staking UIs agree with each other and with
+//! what claims actually pay.
+
+use crate::accounts::{Entity, Member, Registrar, RewardSchedule, RewardVendor};
+use crate::error::RegistryError;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// Seconds in the 365 day year APRs are annualized over.
+pub const SECS_PER_YEAR: u128 = 365 * 24 * 60 * 60;
+
+/// A member's rewards as of a point in time.
+#[derive(Debug, Default, PartialEq)]
+pub struct RewardsEstimate {
+    /// SRM rewards the member could withdraw or compound, including those
+    /// streamed since it last accrued.
+    pub unpaid: u64,
+    /// The member's share of each RewardVendor it can still claim, in the
+    /// order they must be claimed.
+    pub vendors: Vec<VendorClaim>,
+    /// SRM rewards the entity pays a year per unit of stake weight, after
+    /// the leader's commission, in bps.
+    pub apr_bps: u64,
+}
+
+/// A member's claimable share of a RewardVendor.
+#[derive(Debug, PartialEq)]
+pub struct VendorClaim {
+    pub vendor: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+/// Estimates `member`'s rewards at `now`. `vendors` are the RewardVendors of
+/// the member's entity, and `schedules` its RewardSchedules along with the
+/// balance of each one's funding vault. See `apr_bps`.
+pub fn estimate(
+    registrar: &Registrar,
+    entity: &Entity,
+    member: &Member,
+    vendors: &[(Pubkey, RewardVendor)],
+    schedules: &[(RewardSchedule, u64)],
+    now: i64,
+) -> Result<RewardsEstimate, RegistryError> {
+    Ok(RewardsEstimate {
+        unpaid: unpaid_rewards(entity, member, now)?,
+        vendors: vendor_claims(member, vendors, now),
+        apr_bps: apr_bps(registrar, entity, schedules, now),
+    })
+}
+
+/// The rewards `member` would have unpaid if it accrued at `now`.
+pub fn unpaid_rewards(entity: &Entity, member: &Member, now: i64) -> Result<u64, RegistryError> {
+    let mut entity = entity.clone();
+    let mut member = member.clone();
+    entity.update_reward_index(now)?;
+    member.accrue_rewards(&entity)?;
+    Ok(member.rewards)
+}
+
+/// What ClaimVendorReward would pay `member` from each of `vendors` at
+/// `now`, skipping those already claimed or expired. Merkle vendors are
+/// skipped too, since their amounts are only known to whoever holds the
+/// tree.
+pub fn vendor_claims(
+    member: &Member,
+    vendors: &[(Pubkey, RewardVendor)],
+    now: i64,
+) -> Vec<VendorClaim> {
+    let mut claimable: Vec<&(Pubkey, RewardVendor)> = vendors
+        .iter()
+        .filter(|(_, vendor)| {
+            vendor.entity == member.entity
+                && vendor.cursor >= member.rewards_cursor
+                && vendor.merkle_root().is_none()
+                && !vendor.expired(now)
+        })
+        .collect();
+    claimable.sort_by_key(|(_, vendor)| vendor.cursor);
+    claimable
+        .into_iter()
+        .map(|(address, vendor)| VendorClaim {
+            vendor: *address,
+            mint: vendor.mint,
+            amount: vendor.reward_share(member),
+        })
+        .collect()
+}
+
+/// Annualized SRM rewards of `entity` per unit of its current stake weight,
+/// after the leader's commission, in bps.
+///
+/// If the registrar streams rewards, this is the rate of the current
+/// stream, which every drop, scheduled or not, tops up, and 0 once it has
+/// run dry. Otherwise drops are paid at once and leave no rate behind, so
+/// it's the rate of the RewardSchedules whose vaults can still pay a
+/// tranche. Either way it assumes the stake weight stays as it is.
+pub fn apr_bps(
+    registrar: &Registrar,
+    entity: &Entity,
+    schedules: &[(RewardSchedule, u64)],
+    now: i64,
+) -> u64 {
+    let weight = entity.stake_weight();
+    if weight == 0 {
+        return 0;
+    }
+    let rate = if registrar.reward_period_secs != 0 {
+        if now >= entity.reward_end_ts {
+            return 0;
+        }
+        entity.reward_rate
+    } else {
+        schedules
+            .iter()
+            .filter(|(schedule, _)| schedule.period_secs != 0)
+            .map(|(schedule, balance)| {
+                let (_, members_amount) = entity.split_reward(schedule.tranche(*balance));
+                members_amount as u128 * Entity::REWARD_INDEX_PRECISION
+                    / schedule.period_secs as u128
+            })
+            .fold(0u128, |total, rate| total.saturating_add(rate))
+    };
+    let apr_bps =
+        rate.saturating_mul(SECS_PER_YEAR * 10_000) / Entity::REWARD_INDEX_PRECISION / weight;
+    apr_bps.min(u64::MAX as u128) as u64
+}
@@ -0,0 +1,181 @@
+//! Events logged by the registry program with `serum_common::emit_event!`.
+
+use serum_common::event::Event;
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+/// Stake deposited into a member account.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Staked {
+    pub member: Pubkey,
+    pub entity: Pubkey,
+    pub amount: u64,
+    /// True if the amount is MSRM.
+    pub mega: bool,
+}
+
+impl Event for Staked {
+    const NAME: &'static str = "Staked";
+}
+
+/// Stake moved out of a member account into a pending withdrawal.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StakeWithdrawalStarted {
+    pub member: Pubkey,
+    pub pending_withdrawal: Pubkey,
+    pub amount: u64,
+    pub mega_amount: u64,
+    /// Unix timestamp when the withdrawal can be completed.
+    pub end_ts: i64,
+}
+
+impl Event for StakeWithdrawalStarted {
+    const NAME: &'static str = "StakeWithdrawalStarted";
+}
+
+/// A pending withdrawal completed.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct StakeWithdrawalEnded {
+    pub member: Pubkey,
+    pub pending_withdrawal: Pubkey,
+    pub amount: u64,
+    pub mega_amount: u64,
+}
+
+impl Event for StakeWithdrawalEnded {
+    const NAME: &'static str = "StakeWithdrawalEnded";
+}
+
+/// A reward dropped on an entity.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EntityRewardDropped {
+    pub entity: Pubkey,
+    /// The leader's Member account, credited with the commission.
+    pub leader_member: Pubkey,
+    pub amount: u64,
+    pub commission: u64,
+}
+
+impl Event for EntityRewardDropped {
+    const NAME: &'static str = "EntityRewardDropped";
+}
+
+/// A reward in any SPL token dropped on an entity into a new RewardVendor.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VendorRewardDropped {
+    pub vendor: Pubkey,
+    pub entity: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+}
+
+impl Event for VendorRewardDropped {
+    const NAME: &'static str = "VendorRewardDropped";
+}
+
+/// A member's share of a RewardVendor paid out.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VendorRewardClaimed {
+    pub vendor: Pubkey,
+    pub member: Pubkey,
+    pub amount: u64,
+}
+
+impl Event for VendorRewardClaimed {
+    const NAME: &'static str = "VendorRewardClaimed";
+}
+
+/// A leaf of a Merkle RewardVendor paid out.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MerkleRewardClaimed {
+    pub vendor: Pubkey,
+    pub claimant: Pubkey,
+    pub index: u64,
+    pub amount: u64,
+}
+
+impl Event for MerkleRewardClaimed {
+    const NAME: &'static str = "MerkleRewardClaimed";
+}
+
+/// The unclaimed reward of an expired RewardVendor taken back.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct VendorReclaimed {
+    pub vendor: Pubkey,
+    pub expiry_receiver: Pubkey,
+    pub amount: u64,
+}
+
+impl Event for VendorReclaimed {
+    const NAME: &'static str = "VendorReclaimed";
+}
+
+/// A RewardSchedule created for an entity.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RewardScheduleCreated {
+    pub schedule: Pubkey,
+    pub entity: Pubkey,
+    pub amount: u64,
+    pub period_secs: u64,
+    pub start_ts: i64,
+}
+
+impl Event for RewardScheduleCreated {
+    const NAME: &'static str = "RewardScheduleCreated";
+}
+
+/// A tranche of a RewardSchedule dropped on its entity.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EpochDistributed {
+    pub schedule: Pubkey,
+    pub entity: Pubkey,
+    /// The leader's Member account, credited with the commission.
+    pub leader_member: Pubkey,
+    pub epoch: u64,
+    pub amount: u64,
+    pub commission: u64,
+}
+
+impl Event for EpochDistributed {
+    const NAME: &'static str = "EpochDistributed";
+}
+
+/// A member's unpaid rewards staked with its entity.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RewardsCompounded {
+    pub member: Pubkey,
+    pub entity: Pubkey,
+    pub amount: u64,
+}
+
+impl Event for RewardsCompounded {
+    const NAME: &'static str = "RewardsCompounded";
+}
+
+/// A member's books moved to a new member account under another registrar.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct MemberMigrated {
+    pub member: Pubkey,
+    pub new_member: Pubkey,
+    pub new_entity: Pubkey,
+    pub amount: u64,
+    pub mega_amount: u64,
+    pub rewards: u64,
+}
+
+impl Event for MemberMigrated {
+    const NAME: &'static str = "MemberMigrated";
+}
+
+/// The stake backing an entity was snapshotted.
+#[derive(Clone, Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct EntityStakeSnapshotted {
+    pub snapshot: Pubkey,
+    pub entity: Pubkey,
+    pub epoch: u64,
+    pub amount: u64,
+    pub mega_amount: u64,
+}
+
+impl Event for EntityStakeSnapshotted {
+    const NAME: &'static str = "EntityStakeSnapshotted";
+}
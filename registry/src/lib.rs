@@ -7,25 +7,40 @@ use solana_client_gen::prelude::*;
 
 // TODO: add pool accounts once it's ready.
 
-#[cfg_attr(feature = "client", solana_client_gen(ext))]
+#[cfg_attr(
+    any(feature = "client", feature = "wasm"),
+    solana_client_gen(
+        ext,
+        typescript,
+        error(RegistryErrorCode),
+        accounts(Registrar, Entity, Member)
+    )
+)]
 pub mod instruction {
     use super::*;
-    #[derive(serde::Serialize, serde::Deserialize)]
+    #[derive(serde::Serialize, serde::Deserialize, Pack)]
     pub enum RegistryInstruction {
         /// Initializes the registry instance for use. Anyone can invoke this
         /// instruction so it should be run in the same transaction as the
-        /// create_account instruction.
+        /// create_account instruction. Fails if the registrar is already
+        /// initialized.
         ///
         /// Accounts:
         ///
         /// 0. `[writable]` Registrar to initialize.
-        /// 1. `[]`         Rent sysvar.
-        #[cfg_attr(feature = "client", create_account(*registrar::SIZE))]
+        /// 1. `[]`         SRM vault, owned by the program-derived-address of
+        ///                 the registrar and nonce. See `Registrar::signer_seeds`.
+        /// 2. `[]`         MSRM vault, owned by the same address.
+        /// 3. `[]`         Rent sysvar.
         Initialize {
             /// The priviledged account.
             authority: Pubkey,
-            /// Number of slots that must pass for a withdrawal to complete.
-            withdrawal_timelock: u64,
+            /// Number of seconds that must pass for a withdrawal to complete.
+            withdrawal_timelock_secs: u64,
+            /// Nonce of the vault's program-derived-address.
+            nonce: u8,
+            /// Number of seconds rewards are streamed to members over.
+            reward_period_secs: u64,
         },
         /// RegisterCapability registers a node capability for reward collection,
         /// or overwrites an existing capability (e.g., on fee change).
@@ -42,12 +57,14 @@ pub mod instruction {
             capability_fee_bps: u32,
         },
         /// CreateEntity initializes the new "node" with the Registry, designated "inactive".
+        /// The entity stays under the given registrar for its lifetime.
         ///
         /// Accounts:
         ///
         /// 0. `[writable]` Entity account.
         /// 1. `[signer]`   Leader of the node.
-        /// 2. `[]`         Rent sysvar.
+        /// 2. `[]`         Registrar instance.
+        /// 3. `[]`         Rent sysvar.
         CreateEntity {
             /// The Serum ecosystem duties a Node performs to earn extra performance
             /// based rewards, for example, cranking.
@@ -64,13 +81,19 @@ pub mod instruction {
         /// 0. `[writable]` Entity account.
         /// 1. `[signer]`   Leader of the entity.
         UpdateEntity { leader: Pubkey, capabilities: u32 },
-        /// Joins the entity by creating a membership account.
+        /// Joins the entity by creating a membership account, under the
+        /// entity's registrar. Fails with `EntityFull` if the entity has
+        /// `max_members` already.
         ///
         /// Accounts:
         ///
         /// 0. `[writable]` Member account being created.
-        /// 1. `[]`         Entity account to stake to.
-        /// 3. `[]`         Rent sysvar.
+        /// 1. `[writable]` Entity account to stake to.
+        /// 2. `[]`         Rent sysvar.
+        ///
+        /// If the entity approves joins:
+        ///
+        /// 3. `[signer]`   Leader of the entity.
         JoinEntity {
             /// The owner of this entity account. Must sign off when staking and
             /// withdrawing.
@@ -80,53 +103,74 @@ pub mod instruction {
             delegate: Pubkey,
         },
         // TODO: update member to change delegate access.
-        /// Deposits funds into the staking pool on behalf Member account of
-        /// the Member account, issuing staking pool tokens as proof of deposit.
-        ///
-        /// Fails if there is less than 1 MSRM in the associated `Entity`
-        /// account *or* the deposit is less than 1 MSRM.
+        /// Deposits funds into the vault of the entity's registrar on behalf
+        /// of the Member account, crediting the stake to the Member and
+        /// Entity. Fails until the member has claimed the entity's
+        /// RewardVendors, unless nothing is staked yet, and with
+        /// `StakeCapExceeded` if the member would hold more than the
+        /// registrar's `max_stake_per_member`.
         ///
         /// Accounts:
         ///
         /// 0. `[signer]`   Owner of the depositing token account.
-        /// 1. `[]`         The depositing token account.
+        /// 1. `[writable]` The depositing token account.
         /// 2. `[writable]` Member account responsibile for the stake.
         /// 3. `[signer]`   Beneficiary *or* delegate of the Member account
         ///                 being staked.
         /// 4. `[writable]` Entity account to stake to.
-        /// 5. `[]`         SPL token program.
-        #[cfg_attr(feature = "client", create_account(*member::SIZE))]
+        /// 5. `[writable]` The registrar's vault for the staked token,
+        ///                 `Registrar::stake_vault`.
+        /// 6. `[]`         Registrar of the entity.
+        /// 7. `[]`         SPL token program.
+        /// 8. `[]`         Clock sysvar.
+        #[cfg_attr(feature = "client", create_account(crate::accounts::Member::SIZE))]
         Stake {
             // Amount of of the token to stake with the entity.
             amount: u64,
             // True iff staking MSRM.
             is_mega: bool,
         },
-        /// Initiates a stake withdrawal. Funds are locked up until the
-        /// withdrawl timelock passes.
+        /// Initiates a stake withdrawal, debiting the stake from the Member
+        /// and Entity. Funds are locked up until the registrar's withdrawal
+        /// timelock passes, regardless of the entity's state. The member
+        /// forfeits the entity's RewardVendors it hasn't claimed. Should be
+        /// run in the same transaction as the create_account instruction.
         ///
         /// Accounts:
         ///
-        /// 0. `[writable]  PendingWithdrawal account to initialize.
-        /// 0  `[signed]`   Benficiary/delegate of the Stake account.
-        /// 1. `[writable]` The Member account to withdraw from.
-        /// 2. `[writable]` Entity the Stake is associated with.
-        /// 3. `[signed]`   Owner of the staking pool token account to redeem.
+        /// 0. `[writable]` PendingWithdrawal account to initialize.
+        /// 1. `[signed]`   Benficiary/delegate of the Member account.
+        /// 2. `[writable]` The Member account to withdraw from.
+        /// 3. `[writable]` Entity the Member is associated with.
+        /// 4. `[]`         Registrar of the entity, defining the timelock.
+        /// 5. `[]`         Rent sysvar.
+        /// 6. `[]`         Clock sysvar.
+        #[cfg_attr(
+            feature = "client",
+            create_account(crate::accounts::PendingWithdrawal::SIZE)
+        )]
         StartStakeWithdrawal { amount: u64, mega_amount: u64 },
-        /// Completes the pending withdrawal once the timelock period passes.
-        ///
-        /// Accounts:
+        /// Completes the pending withdrawal once the timelock period passes,
+        /// paying out exactly the amounts it set aside. The rest of the
+        /// member's stake stays staked. Fails if the PendingWithdrawal's end
+        /// timestamp hasn't been reached.
         ///
         /// Accounts:
         ///
-        /// 0. `[writable]  PendingWithdrawal account to complete.
+        /// 0. `[writable]` PendingWithdrawal account to complete.
         /// 1. `[signed]`   Beneficiary/delegate of the member account.
         /// 2. `[writable]` Member account to withdraw from.
         /// 3. `[writable]` Entity account the member is associated with.
-        /// 4. `[]`         SPL token program (SRM).
-        /// 5. `[]`         SPL mega token program (MSRM).
-        /// 6. `[writable]` SRM token account to send to upon redemption
-        /// 7. `[writable]` MSRM token account to send to upon redemption
+        /// 4. `[]`         Clock sysvar.
+        /// 5. `[]`         SPL token program.
+        /// 6. `[]`         Registrar of the member.
+        /// 7. `[writable]` The registrar's SRM vault.
+        /// 8. `[writable]` The registrar's MSRM vault.
+        /// 9. `[]`         Program-derived-address owning the vaults.
+        /// 10. `[writable]` SRM token account to send to upon redemption,
+        ///                  owned by the signer. Unchecked if no SRM is due.
+        /// 11. `[writable]` MSRM token account to send to upon redemption,
+        ///                  owned by the signer. Unchecked if no MSRM is due.
         EndStakeWithdrawal,
         /// Donates funds into the staking pool for reward distribution. Anyone
         /// can invoke this instruction. Only the non-mega token can be donated.
@@ -135,26 +179,481 @@ pub mod instruction {
         ///
         /// 0. `[signer]`   Owner of the account sending the funds.
         /// 1. `[writable]` Account from which to send the funds.
-        /// 2. `[writable]` Program controlled token vault to transfer funds
-        ///                 into.
-        /// 3. `[]`         Registry instance, holding the nonce to calculate
-        ///                 the program-derived-address.
+        /// 2. `[writable]` The registrar's vault, to transfer funds into.
+        /// 3. `[]`         Registrar of the entity.
         /// 4. `[]`         SPL token program.
         Donate {
             /// The amount to deposit.
             amount: u64,
         },
+        /// Sets the memo on a Member account, a free form label for the
+        /// beneficiary's own bookkeeping.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` Member account to update.
+        /// 1. `[signer]`   Beneficiary of the Member account.
+        UpdateMemberMemo {
+            /// Zero padded memo. See `Member::encode_memo`.
+            memo: [u8; 32],
+        },
+        /// Starts handing a Member account to a new beneficiary. Nothing
+        /// changes until the new beneficiary accepts. Should be run in the
+        /// same transaction as the create_account instruction.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` BeneficiaryTransfer account to initialize.
+        /// 1. `[]`         Member account to transfer.
+        /// 2. `[signer]`   Current beneficiary of the Member account.
+        /// 3. `[]`         Rent sysvar.
+        #[cfg_attr(
+            feature = "client",
+            create_account(crate::accounts::BeneficiaryTransfer::SIZE)
+        )]
+        InitiateBeneficiaryTransfer {
+            /// Key that must sign to accept the transfer.
+            new_beneficiary: Pubkey,
+        },
+        /// Completes a transfer, setting the Member's beneficiary to the
+        /// new key. Fails if the beneficiary changed since it was initiated.
+        /// The BeneficiaryTransfer account is closed and its lamports sent
+        /// to the new beneficiary.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` BeneficiaryTransfer account.
+        /// 1. `[writable]` Member account to transfer.
+        /// 2. `[writable, signer]` New beneficiary.
+        AcceptBeneficiaryTransfer,
+        /// Abandons a pending transfer. The BeneficiaryTransfer account is
+        /// closed and its lamports sent to the beneficiary.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` BeneficiaryTransfer account.
+        /// 1. `[writable, signer]` Beneficiary that initiated the transfer.
+        CancelBeneficiaryTransfer,
+        /// Closes a Member account with nothing staked, no unpaid rewards
+        /// and no pending withdrawals, returning its rent to the
+        /// beneficiary.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` Member account to close.
+        /// 1. `[writable, signer]` Beneficiary of the Member account.
+        /// 2. `[writable]` Entity the Member is associated with.
+        CloseMember,
+        /// Updates the registrar's parameters. Fields left as None are
+        /// unchanged. Takes effect immediately.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Registrar authority.
+        /// 1. `[writable]` Registrar instance.
+        UpdateRegistrar {
+            /// Hands the registrar to a new authority.
+            new_authority: Option<Pubkey>,
+            /// Number of seconds that must pass for a withdrawal to complete.
+            withdrawal_timelock_secs: Option<u64>,
+            /// Number of seconds rewards are streamed to members over. Drops
+            /// already streaming keep their end.
+            reward_period_secs: Option<u64>,
+            /// Most SRM-equivalent stake a member may hold, 0 for no cap.
+            /// Stake above a new cap stays until withdrawn.
+            max_stake_per_member: Option<u64>,
+        },
+        /// Sets the metadata describing an Entity, initializing the
+        /// EntityMetadata account on first use. Should be run in the same
+        /// transaction as the create_account instruction when initializing.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` EntityMetadata account.
+        /// 1. `[]`         Entity account described.
+        /// 2. `[signer]`   Leader of the entity.
+        /// 3. `[]`         Rent sysvar.
+        #[cfg_attr(
+            feature = "client",
+            create_account(crate::accounts::EntityMetadata::SIZE as u64)
+        )]
+        UpdateEntityMetadata {
+            /// See `EntityMetadata` for the bound on each string.
+            name: String,
+            website: String,
+            description: String,
+            image_uri: String,
+        },
+        /// Proposes handing an Entity to a new leader. Nothing changes
+        /// until the new leader accepts. Should be run in the same
+        /// transaction as the create_account instruction.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` EntityLeaderTransfer account to initialize.
+        /// 1. `[]`         Entity account to transfer.
+        /// 2. `[signer]`   Current leader of the entity.
+        /// 3. `[]`         Rent sysvar.
+        #[cfg_attr(
+            feature = "client",
+            create_account(crate::accounts::EntityLeaderTransfer::SIZE)
+        )]
+        ProposeEntityLeader {
+            /// Key that must sign to accept the transfer.
+            new_leader: Pubkey,
+        },
+        /// Completes a proposed transfer, setting the Entity's leader to
+        /// the new key. Fails if the leader changed since it was proposed.
+        /// The EntityLeaderTransfer account is closed and its lamports sent
+        /// to the new leader.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` EntityLeaderTransfer account.
+        /// 1. `[writable]` Entity account to transfer.
+        /// 2. `[writable, signer]` New leader.
+        AcceptEntityLeader,
+        /// Abandons a proposed transfer. The EntityLeaderTransfer account is
+        /// closed and its lamports sent to the leader.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` EntityLeaderTransfer account.
+        /// 1. `[writable, signer]` Leader that proposed the transfer.
+        CancelEntityLeader,
+        /// Sets the commission the entity's leader takes from rewards
+        /// distributed to the entity. See `Entity::split_reward`. A cut
+        /// applies at once, while a raise waits out the registrar's
+        /// withdrawal timelock. See `Entity::set_commission`.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` Entity account.
+        /// 1. `[signer]`   Leader of the entity.
+        /// 2. `[]`         Registrar of the entity.
+        /// 3. `[]`         Clock sysvar.
+        SetEntityCommission {
+            /// Commission in bps, at most `Entity::MAX_COMMISSION_BPS`.
+            commission_bps: u32,
+        },
+        /// Drops a reward on an Entity. The leader's commission accrues to
+        /// the leader's Member account and the rest to the entity's members,
+        /// pro-rata to stake over the registrar's `reward_period_secs`. See
+        /// `Entity::stream_reward`. Anyone can invoke this instruction. Only
+        /// the non-mega token can be dropped.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Owner of the account sending the funds.
+        /// 1. `[writable]` Account from which to send the funds.
+        /// 2. `[writable]` The registrar's vault, to transfer funds into.
+        /// 3. `[]`         Registrar of the entity.
+        /// 4. `[]`         SPL token program.
+        /// 5. `[writable]` Entity account to reward.
+        /// 6. `[writable]` Member account whose beneficiary is the entity's
+        ///                 leader.
+        /// 7. `[]`         Clock sysvar.
+        DropEntityReward {
+            /// The amount to drop, commission included.
+            amount: u64,
+        },
+        /// Moves a member's stake, unpaid rewards, delegate and memo into a
+        /// fresh Member account of an entity under another registrar, e.g.,
+        /// when upgrading registrars, without going through the withdrawal
+        /// timelock. The authorities of both entities' registrars must
+        /// approve, the entity to join must have room, and the stake must be
+        /// within the new registrar's `max_stake_per_member`. The funds backing
+        /// the member move to the new registrar's vaults. The old Member
+        /// account is left empty, and pending withdrawals from it still
+        /// complete. See `Member::migrate`. Should
+        /// be run in the same transaction as the create_account instruction.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` Member account to initialize.
+        /// 1. `[writable]` Member account to migrate from.
+        /// 2. `[signer]`   Beneficiary of the Member account.
+        /// 3. `[writable]` Entity the Member is associated with.
+        /// 4. `[]`         Registrar of the entity migrated from.
+        /// 5. `[signer]`   Authority of the registrar migrated from.
+        /// 6. `[writable]` Entity to join.
+        /// 7. `[]`         Registrar of the entity to join.
+        /// 8. `[signer]`   Authority of the registrar migrated to.
+        /// 9. `[writable]` SRM vault of the registrar migrated from.
+        /// 10. `[writable]` MSRM vault of the registrar migrated from.
+        /// 11. `[]`        Program-derived-address owning those vaults.
+        /// 12. `[writable]` SRM vault of the registrar migrated to.
+        /// 13. `[writable]` MSRM vault of the registrar migrated to.
+        /// 14. `[]`        SPL token program.
+        /// 15. `[]`        Rent sysvar.
+        /// 16. `[]`        Clock sysvar.
+        ///
+        /// If the entity to join approves joins:
+        ///
+        /// 17. `[signer]`  Leader of the entity to join.
+        #[cfg_attr(feature = "client", create_account(crate::accounts::Member::SIZE))]
+        MigrateMember,
+        /// Records the stake backing an Entity in the current epoch in a new
+        /// StakeSnapshot account, which is never modified afterwards. The
+        /// program creates the account at its derived address, so an entity
+        /// is snapshotted at most once an epoch. See `StakeSnapshot`.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` StakeSnapshot account to create, at
+        ///                 `StakeSnapshot::find_address` for the current
+        ///                 epoch.
+        /// 1. `[]`         Entity account to snapshot.
+        /// 2. `[]`         Registrar of the entity.
+        /// 3. `[writable, signer]` Registrar authority, paying for the
+        ///                 account.
+        /// 4. `[]`         System program.
+        /// 5. `[]`         Rent sysvar.
+        /// 6. `[]`         Clock sysvar.
+        SnapshotEntityStake {
+            /// Merkle root of the entity's members' stake, or zeroes for
+            /// none. See `StakeSnapshot::members_root`.
+            members_root: [u8; 32],
+            /// Nonce of the snapshot's address.
+            nonce: u8,
+        },
+        /// Pauses the registrar, e.g., while a suspected vulnerability is
+        /// investigated. Reward drops fail with `Paused` until Unpause.
+        /// Withdrawals and everything else work as usual.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Registrar authority.
+        /// 1. `[writable]` Registrar instance.
+        Pause,
+        /// Lifts a Pause.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Registrar authority.
+        /// 1. `[writable]` Registrar instance.
+        Unpause,
+        /// Sets who can join the entity. Existing members are unaffected.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` Entity account.
+        /// 1. `[signer]`   Leader of the entity.
+        SetEntityJoinRules {
+            /// Most members the entity takes, or 0 for no limit.
+            max_members: u64,
+            /// True iff the leader must sign JoinEntity and MigrateMember.
+            approve_joins: bool,
+        },
+        /// Drops a reward in any SPL token on an Entity into a new
+        /// RewardVendor, shared among the entity's members by their stake
+        /// at the drop, or by the leaves of `merkle_root`. No commission is
+        /// taken. Must be approved by the registrar's authority or the
+        /// entity's leader, as members claim the entity's vendors one by one.
+        /// Fails if nothing is dropped, or if shared by stake and nothing is
+        /// staked with the entity. Should be run in the same transaction as
+        /// the create_account instruction.
+        ///
+        /// Claims close at `expiry_ts`, see ReclaimVendor.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` RewardVendor to initialize.
+        /// 1. `[writable]` Vendor vault, a token account of the reward's
+        ///                 mint owned by the program-derived-address of the
+        ///                 vendor and `nonce`.
+        /// 2. `[signer]`   Owner of the account sending the funds.
+        /// 3. `[writable]` Account from which to send the funds.
+        /// 4. `[writable]` Entity account to reward.
+        /// 5. `[]`         Registrar of the entity.
+        /// 6. `[]`         SPL token program.
+        /// 7. `[]`         Rent sysvar.
+        /// 8. `[]`         Clock sysvar.
+        /// 9. `[signer]`   Registrar authority or leader of the entity.
+        #[cfg_attr(
+            feature = "client",
+            create_account(crate::accounts::RewardVendor::SIZE)
+        )]
+        DropVendorReward {
+            /// The amount to drop.
+            amount: u64,
+            /// Nonce of the vendor vault's program-derived-address.
+            nonce: u8,
+            /// Merkle root of what's owed to whom, or zeroes to share by
+            /// stake. See `RewardVendor::merkle_leaf`.
+            merkle_root: [u8; 32],
+            /// Unix timestamp at which claims close. Must be in the future.
+            expiry_ts: i64,
+            /// Who can reclaim the unclaimed reward once expired.
+            expiry_receiver: Pubkey,
+        },
+        /// Pays a member its share of a RewardVendor of its entity. A member
+        /// claims the entity's vendors in the order they're dropped, and
+        /// must claim them all before its stake can change. See
+        /// `Member::sync_rewards_cursor`. Once the vendor has expired, the
+        /// claim pays nothing, and only moves the member past it.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Beneficiary/delegate of the Member account.
+        /// 1. `[writable]` Member account claiming.
+        /// 2. `[]`         RewardVendor to claim, at the member's
+        ///                 `rewards_cursor`.
+        /// 3. `[writable]` Vendor vault.
+        /// 4. `[]`         Program-derived-address owning the vendor vault.
+        /// 5. `[writable]` Token account of the reward's mint to pay into,
+        ///                 owned by the signer.
+        /// 6. `[]`         SPL token program.
+        /// 7. `[]`         Clock sysvar.
+        ClaimVendorReward,
+        /// Pays a leaf of a Merkle RewardVendor to the claimant it names,
+        /// given a proof of the leaf. Records the claim in a new, empty
+        /// receipt account at its derived address, so each leaf is paid
+        /// once. Fails once the vendor has expired.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable, signer]` Claimant named by the leaf, paying for
+        ///                 the receipt.
+        /// 1. `[]`         RewardVendor to claim.
+        /// 2. `[writable]` Vendor vault.
+        /// 3. `[]`         Program-derived-address owning the vendor vault.
+        /// 4. `[writable]` Token account of the reward's mint to pay into,
+        ///                 owned by the claimant.
+        /// 5. `[writable]` Receipt account to create, at
+        ///                 `RewardVendor::find_claim_address` for `index`.
+        /// 6. `[]`         SPL token program.
+        /// 7. `[]`         System program.
+        /// 8. `[]`         Rent sysvar.
+        /// 9. `[]`         Clock sysvar.
+        ClaimMerkleReward {
+            /// Index of the leaf.
+            index: u64,
+            /// Amount the leaf owes.
+            amount: u64,
+            /// Siblings on the path from the leaf to the root, leaf first.
+            proof: Vec<[u8; 32]>,
+            /// Nonce of the receipt's address.
+            nonce: u8,
+        },
+        /// Sends what's left in the vault of an expired RewardVendor to its
+        /// `expiry_receiver`.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Expiry receiver of the vendor.
+        /// 1. `[]`         RewardVendor to reclaim.
+        /// 2. `[writable]` Vendor vault.
+        /// 3. `[]`         Program-derived-address owning the vendor vault.
+        /// 4. `[writable]` Token account of the reward's mint to pay into,
+        ///                 owned by the signer.
+        /// 5. `[]`         SPL token program.
+        /// 6. `[]`         Clock sysvar.
+        ReclaimVendor,
+        /// Stakes the member's unpaid rewards, which are SRM, with its
+        /// entity, in place of paying them out and staking them again. Like
+        /// Stake, fails until the member has claimed the entity's reward
+        /// vendors, and if the member would exceed the registrar's
+        /// `max_stake_per_member`.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[signer]`   Beneficiary/delegate of the Member account.
+        /// 1. `[writable]` Member account.
+        /// 2. `[writable]` Entity the Member is associated with.
+        /// 3. `[]`         Registrar of the entity.
+        /// 4. `[]`         Clock sysvar.
+        CompoundRewards,
+        /// Creates a RewardSchedule dropping `amount` SRM on an Entity every
+        /// `period_secs`, starting at `start_ts`, out of the schedule's
+        /// funding vault. Anyone can invoke this instruction, and fund the
+        /// vault with a plain token transfer. Should be run in the same
+        /// transaction as the create_account instruction.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` RewardSchedule to initialize.
+        /// 1. `[]`         Funding vault, an SRM token account owned by the
+        ///                 program-derived-address of the schedule and
+        ///                 `nonce`.
+        /// 2. `[]`         Entity account to reward.
+        /// 3. `[]`         Registrar of the entity.
+        /// 4. `[]`         Rent sysvar.
+        #[cfg_attr(
+            feature = "client",
+            create_account(crate::accounts::RewardSchedule::SIZE)
+        )]
+        CreateRewardSchedule {
+            /// Amount dropped each period.
+            amount: u64,
+            /// Number of seconds between tranches.
+            period_secs: u64,
+            /// Unix timestamp at which the first tranche comes due.
+            start_ts: i64,
+            /// Nonce of the funding vault's program-derived-address.
+            nonce: u8,
+        },
+        /// Drops the next due tranche of a RewardSchedule on its entity, as
+        /// DropEntityReward does, moving it from the funding vault into the
+        /// registrar's vault. Anyone can invoke this instruction. Fails if
+        /// the tranche isn't due, the funding vault is empty or the
+        /// registrar is paused.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` RewardSchedule.
+        /// 1. `[writable]` Funding vault of the schedule.
+        /// 2. `[]`         Program-derived-address owning the funding vault.
+        /// 3. `[writable]` The registrar's vault, to transfer funds into.
+        /// 4. `[]`         Registrar of the entity.
+        /// 5. `[writable]` Entity account to reward.
+        /// 6. `[writable]` Member account whose beneficiary is the entity's
+        ///                 leader.
+        /// 7. `[]`         SPL token program.
+        /// 8. `[]`         Clock sysvar.
+        DistributeEpoch,
+        /// Writes a member's stake of a realm's governing token to a
+        /// VoterWeightRecord, for spl-governance's voter weight addin,
+        /// initializing the record on first use. The weight expires after
+        /// the current slot, so this should be run in the same transaction
+        /// as the governance instruction using it, and with the
+        /// create_account instruction when initializing. See
+        /// `VoterWeightRecord`.
+        ///
+        /// Accounts:
+        ///
+        /// 0. `[writable]` VoterWeightRecord account.
+        /// 1. `[signer]`   Beneficiary of the Member account.
+        /// 2. `[]`         Member account whose stake is reported.
+        /// 3. `[]`         Registrar of the member.
+        /// 4. `[]`         The registrar's vault for the governing token,
+        ///                 `Registrar::stake_vault`.
+        /// 5. `[]`         Clock sysvar.
+        /// 6. `[]`         Rent sysvar.
+        #[cfg_attr(
+            feature = "client",
+            create_account(crate::accounts::VoterWeightRecord::SIZE as u64)
+        )]
+        UpdateVoterWeightRecord {
+            /// Realm the record votes in.
+            realm: Pubkey,
+            /// True iff the realm's governing token is MSRM.
+            is_mega: bool,
+        },
     }
 }
 
-#[cfg(feature = "client")]
+#[cfg(any(feature = "client", feature = "wasm"))]
 pub mod client_ext;
 #[cfg(feature = "client")]
 pub use client_ext::client;
-#[cfg(feature = "client")]
+#[cfg(any(feature = "client", feature = "wasm"))]
 pub use client_ext::instruction;
 
 pub mod accounts;
 pub mod error;
-
-serum_common::packable!(crate::instruction::RegistryInstruction);
+#[cfg(any(feature = "client", feature = "wasm"))]
+pub mod estimate;
+pub mod events;
+#[cfg(any(feature = "client", feature = "wasm"))]
+pub mod simulator;
@@ -0,0 +1,243 @@
+//! An off-chain, deterministic model of a registrar, for tuning timelocks,
+//! reward periods, commissions, caps and reward sizes before deploying.
+//!
+//! Each operation applies the account methods the program's instruction
+//! of the same name applies, in the same order, to in-memory accounts, so
+//! balances, reward shares and rounding come out exactly as on chain. Time
+//! only moves with `Simulator::advance`. Token transfers and signatures
+//! aren't modeled, nor are reward vendors.
+//!
+//! As with a failed transaction, an operation that fails changes nothing.
+//! Entities, members and pending withdrawals are referred to by the index
+//! returned when they were created. Passing any other index panics.
+
+use crate::accounts::{Entity, Member, PendingWithdrawal, Registrar};
+use crate::error::{RegistryError, RegistryErrorCode};
+use crate::estimate;
+use std::convert::TryFrom;
+
+/// A registrar's accounts, along with its entities', members' and pending
+/// withdrawals'.
+#[derive(Debug)]
+pub struct Simulator {
+    pub registrar: Registrar,
+    pub entities: Vec<Entity>,
+    pub members: Vec<Member>,
+    pub pending_withdrawals: Vec<PendingWithdrawal>,
+    /// Unix timestamp the next operation runs at.
+    pub now: i64,
+    // Entity of each member, by index.
+    member_entities: Vec<usize>,
+    // Member account of each entity's leader, by index.
+    leader_members: Vec<usize>,
+    // Member of each pending withdrawal, by index.
+    withdrawal_members: Vec<usize>,
+}
+
+impl Simulator {
+    /// Starts a simulation of `registrar` at `now`.
+    pub fn new(registrar: Registrar, now: i64) -> Self {
+        Self {
+            registrar: Registrar {
+                initialized: true,
+                ..registrar
+            },
+            entities: vec![],
+            members: vec![],
+            pending_withdrawals: vec![],
+            now,
+            member_entities: vec![],
+            leader_members: vec![],
+            withdrawal_members: vec![],
+        }
+    }
+
+    /// Moves time forward by `secs`.
+    pub fn advance(&mut self, secs: i64) {
+        self.now += secs;
+    }
+
+    /// CreateEntity, followed by JoinEntity for the leader's own member
+    /// account, which reward drops credit the commission to. Returns the
+    /// entity.
+    pub fn create_entity(&mut self) -> Result<usize, RegistryError> {
+        self.entities.push(Entity {
+            initialized: true,
+            ..Default::default()
+        });
+        let entity = self.entities.len() - 1;
+        let leader_member = self.join_entity(entity)?;
+        self.leader_members.push(leader_member);
+        Ok(entity)
+    }
+
+    /// The leader's member account of `entity`.
+    pub fn leader_member(&self, entity: usize) -> usize {
+        self.leader_members[entity]
+    }
+
+    /// JoinEntity. Returns the new member.
+    pub fn join_entity(&mut self, entity: usize) -> Result<usize, RegistryError> {
+        let entity_account = &mut self.entities[entity];
+        entity_account.did_join()?;
+        self.members.push(Member {
+            initialized: true,
+            reward_index: entity_account.reward_index,
+            rewards_cursor: entity_account.vendor_count,
+            ..Default::default()
+        });
+        self.member_entities.push(entity);
+        Ok(self.members.len() - 1)
+    }
+
+    /// SetEntityJoinRules.
+    pub fn set_entity_join_rules(&mut self, entity: usize, max_members: u64, approve_joins: bool) {
+        let entity = &mut self.entities[entity];
+        entity.max_members = max_members;
+        entity.approve_joins = approve_joins;
+    }
+
+    /// SetEntityCommission.
+    pub fn set_entity_commission(
+        &mut self,
+        entity: usize,
+        commission_bps: u32,
+    ) -> Result<(), RegistryError> {
+        if commission_bps > Entity::MAX_COMMISSION_BPS {
+            return Err(RegistryErrorCode::InvalidCommission)?;
+        }
+        let mut entity_account = self.entities[entity].clone();
+        let timelock = i64::try_from(self.registrar.withdrawal_timelock_secs)
+            .map_err(|_| RegistryErrorCode::Overflow)?;
+        entity_account.set_commission(commission_bps, self.now, timelock)?;
+        self.entities[entity] = entity_account;
+        Ok(())
+    }
+
+    /// Stake.
+    pub fn stake(
+        &mut self,
+        member: usize,
+        amount: u64,
+        is_mega: bool,
+    ) -> Result<(), RegistryError> {
+        let entity = self.member_entities[member];
+        let mut entity_account = self.entities[entity].clone();
+        let mut member_account = self.members[member].clone();
+        member_account.sync_rewards_cursor(&entity_account)?;
+        entity_account.update_reward_index(self.now)?;
+        member_account.accrue_rewards(&entity_account)?;
+        member_account.did_deposit(amount, is_mega)?;
+        entity_account.did_deposit(amount, is_mega)?;
+        self.registrar
+            .check_stake_cap(member_account.amount, member_account.mega_amount)?;
+        self.entities[entity] = entity_account;
+        self.members[member] = member_account;
+        Ok(())
+    }
+
+    /// DropEntityReward. Returns the leader's commission.
+    pub fn drop_entity_reward(&mut self, entity: usize, amount: u64) -> Result<u64, RegistryError> {
+        if self.registrar.paused {
+            return Err(RegistryErrorCode::Paused)?;
+        }
+        let leader_member = self.leader_members[entity];
+        let mut entity_account = self.entities[entity].clone();
+        let mut leader_member_account = self.members[leader_member].clone();
+        entity_account.apply_pending_commission(self.now);
+        let reward_period_secs = i64::try_from(self.registrar.reward_period_secs)
+            .map_err(|_| RegistryErrorCode::Overflow)?;
+        let commission = entity_account.stream_reward(amount, self.now, reward_period_secs)?;
+        leader_member_account.did_earn(commission)?;
+        self.entities[entity] = entity_account;
+        self.members[leader_member] = leader_member_account;
+        Ok(commission)
+    }
+
+    /// CompoundRewards. Returns the amount staked.
+    pub fn compound_rewards(&mut self, member: usize) -> Result<u64, RegistryError> {
+        let entity = self.member_entities[member];
+        let mut entity_account = self.entities[entity].clone();
+        let mut member_account = self.members[member].clone();
+        entity_account.update_reward_index(self.now)?;
+        let amount = member_account.compound_rewards(&mut entity_account)?;
+        self.registrar
+            .check_stake_cap(member_account.amount, member_account.mega_amount)?;
+        self.entities[entity] = entity_account;
+        self.members[member] = member_account;
+        Ok(amount)
+    }
+
+    /// StartStakeWithdrawal. Returns the pending withdrawal.
+    pub fn start_stake_withdrawal(
+        &mut self,
+        member: usize,
+        amount: u64,
+        mega_amount: u64,
+    ) -> Result<usize, RegistryError> {
+        let entity = self.member_entities[member];
+        let mut entity_account = self.entities[entity].clone();
+        let mut member_account = self.members[member].clone();
+        member_account.forfeit_unclaimed_vendors(&entity_account);
+        entity_account.update_reward_index(self.now)?;
+        member_account.accrue_rewards(&entity_account)?;
+        member_account.did_withdraw(amount, false)?;
+        member_account.did_withdraw(mega_amount, true)?;
+        entity_account.did_withdraw(amount, false)?;
+        entity_account.did_withdraw(mega_amount, true)?;
+        member_account.did_start_withdrawal()?;
+
+        let timelock = i64::try_from(self.registrar.withdrawal_timelock_secs)
+            .map_err(|_| RegistryErrorCode::Overflow)?;
+        let pending_withdrawal = PendingWithdrawal {
+            initialized: true,
+            burned: false,
+            start_ts: self.now,
+            end_ts: self
+                .now
+                .checked_add(timelock)
+                .ok_or(RegistryErrorCode::Overflow)?,
+            amount,
+            mega_amount,
+            ..Default::default()
+        };
+
+        self.entities[entity] = entity_account;
+        self.members[member] = member_account;
+        self.pending_withdrawals.push(pending_withdrawal);
+        self.withdrawal_members.push(member);
+        Ok(self.pending_withdrawals.len() - 1)
+    }
+
+    /// EndStakeWithdrawal. Returns the SRM and MSRM paid out.
+    pub fn end_stake_withdrawal(
+        &mut self,
+        pending_withdrawal: usize,
+    ) -> Result<(u64, u64), RegistryError> {
+        let member = self.withdrawal_members[pending_withdrawal];
+        let mut member_account = self.members[member].clone();
+        let withdrawal = &self.pending_withdrawals[pending_withdrawal];
+        if withdrawal.burned {
+            return Err(RegistryErrorCode::InvalidPendingWithdrawal)?;
+        }
+        if !withdrawal.can_complete(self.now) {
+            return Err(RegistryErrorCode::WithdrawalTimelockNotPassed)?;
+        }
+        member_account.did_end_withdrawal()?;
+        let paid = (withdrawal.amount, withdrawal.mega_amount);
+
+        self.pending_withdrawals[pending_withdrawal].burned = true;
+        self.members[member] = member_account;
+        Ok(paid)
+    }
+
+    /// The rewards `member` would have unpaid if it accrued now, without
+    /// changing any account.
+    pub fn unpaid_rewards(&self, member: usize) -> Result<u64, RegistryError> {
+        estimate::unpaid_rewards(
+            &self.entities[self.member_entities[member]],
+            &self.members[member],
+            self.now,
+        )
+    }
+}
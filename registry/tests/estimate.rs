@@ -0,0 +1,127 @@
+use serum_registry::accounts::{Entity, Member, Registrar, RewardSchedule, RewardVendor};
+use serum_registry::estimate::{self, VendorClaim, SECS_PER_YEAR};
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+#[test]
+fn vendor_claims() {
+    let entity = Pubkey::new(&[1; 32]);
+    let member = Member {
+        entity,
+        amount: 100,
+        rewards_cursor: 1,
+        ..Default::default()
+    };
+    let vendor = |cursor: u64| RewardVendor {
+        entity,
+        cursor,
+        mint: Pubkey::new(&[cursor as u8; 32]),
+        total: 1_000,
+        stake_weight: 400,
+        expiry_ts: 100,
+        ..Default::default()
+    };
+    let vendors = vec![
+        (Pubkey::new(&[10; 32]), vendor(2)),
+        (Pubkey::new(&[11; 32]), vendor(0)),
+        (Pubkey::new(&[12; 32]), vendor(1)),
+        (
+            Pubkey::new(&[13; 32]),
+            RewardVendor {
+                merkle_root: [1; 32],
+                ..vendor(3)
+            },
+        ),
+        (
+            Pubkey::new(&[14; 32]),
+            RewardVendor {
+                expiry_ts: 50,
+                ..vendor(4)
+            },
+        ),
+        (
+            Pubkey::new(&[15; 32]),
+            RewardVendor {
+                entity: Pubkey::new(&[2; 32]),
+                ..vendor(5)
+            },
+        ),
+    ];
+
+    // Claimed, Merkle, expired and foreign vendors are skipped, and the
+    // rest come in claim order.
+    assert_eq!(
+        estimate::vendor_claims(&member, &vendors, 50),
+        vec![
+            VendorClaim {
+                vendor: Pubkey::new(&[12; 32]),
+                mint: Pubkey::new(&[1; 32]),
+                amount: 250,
+            },
+            VendorClaim {
+                vendor: Pubkey::new(&[10; 32]),
+                mint: Pubkey::new(&[2; 32]),
+                amount: 250,
+            },
+        ]
+    );
+    assert!(estimate::vendor_claims(&member, &vendors, 100).is_empty());
+}
+
+#[test]
+fn apr_of_stream() {
+    let registrar = Registrar {
+        reward_period_secs: SECS_PER_YEAR as u64,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: 315_360,
+        commission_bps: 1_000,
+        ..Default::default()
+    };
+    assert_eq!(estimate::apr_bps(&registrar, &entity, &[], 0), 0);
+
+    // 31,536 a year to the members on 315,360 staked.
+    entity
+        .stream_reward(35_040, 0, SECS_PER_YEAR as i64)
+        .unwrap();
+    assert_eq!(estimate::apr_bps(&registrar, &entity, &[], 0), 1_000);
+    assert_eq!(
+        estimate::apr_bps(&registrar, &entity, &[], SECS_PER_YEAR as i64 - 1),
+        1_000
+    );
+    assert_eq!(
+        estimate::apr_bps(&registrar, &entity, &[], SECS_PER_YEAR as i64),
+        0
+    );
+
+    // Twice the stake, half the APR.
+    entity.did_deposit(315_360, false).unwrap();
+    assert_eq!(estimate::apr_bps(&registrar, &entity, &[], 0), 500);
+}
+
+#[test]
+fn apr_of_schedules() {
+    let registrar = Registrar::default();
+    let entity = Entity {
+        amount: 31_536_000,
+        ..Default::default()
+    };
+    let schedule = || RewardSchedule {
+        amount: 8_640,
+        period_secs: 86_400,
+        ..Default::default()
+    };
+
+    // A funded schedule pays 3,153,600 a year, one with less than a
+    // tranche left is counted at what's left, and an empty one not at all.
+    let schedules = vec![
+        (schedule(), 1_000_000),
+        (schedule(), 4_320),
+        (schedule(), 0),
+    ];
+    assert_eq!(estimate::apr_bps(&registrar, &entity, &schedules, 0), 1_500);
+    assert_eq!(
+        estimate::apr_bps(&registrar, &Entity::default(), &schedules, 0),
+        0
+    );
+}
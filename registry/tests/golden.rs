@@ -0,0 +1,277 @@
+use serum_common::pack::Pack;
+use serum_common_tests::golden::assert_golden;
+use serum_registry::accounts::{
+    BeneficiaryTransfer, Entity, EntityLeaderTransfer, EntityMetadata, Member, PendingWithdrawal,
+    Registrar, RewardSchedule, RewardVendor, StakeKind, StakeSnapshot, VoterWeightRecord,
+};
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+// Golden tests pin the serialized layout of every registry account. If one
+// of these fails, either revert the layout change or write a migration and
+// regenerate the fixtures with `SERUM_UPDATE_GOLDEN=1`.
+
+#[test]
+fn registrar() {
+    let mut capabilities_fees_bps = [0; 32];
+    for (i, fee) in capabilities_fees_bps.iter_mut().enumerate() {
+        *fee = i as u32;
+    }
+    let registrar = Registrar {
+        initialized: true,
+        authority: key(1),
+        capabilities_fees_bps,
+        withdrawal_timelock: 2,
+        withdrawal_timelock_secs: 3,
+        vault: key(4),
+        nonce: 5,
+        paused: true,
+        mega_vault: key(6),
+        reward_period_secs: 7,
+        max_stake_per_member: 8,
+    };
+    assert_golden(registrar, fixture("registrar"));
+}
+
+#[test]
+fn entity() {
+    let entity = Entity {
+        initialized: true,
+        leader: key(1),
+        amount: 2,
+        mega_amount: 3,
+        capabilities: 4,
+        stake_kind: StakeKind::Voting,
+        commission_bps: 5,
+        reward_index: 6,
+        registrar: key(7),
+        member_count: 8,
+        max_members: 9,
+        approve_joins: true,
+        pending_commission_bps: 10,
+        pending_commission_ts: 11,
+        reward_rate: 12,
+        reward_ts: 13,
+        reward_end_ts: 14,
+        vendor_count: 15,
+    };
+    assert_golden(entity, fixture("entity"));
+}
+
+#[test]
+fn member() {
+    let member = Member {
+        initialized: true,
+        entity: key(1),
+        beneficiary: key(2),
+        delegate: key(3),
+        amount: 4,
+        mega_amount: 5,
+        memo: Member::encode_memo("treasury").unwrap(),
+        rewards: 6,
+        reward_index: 7,
+        registrar: key(8),
+        pending_withdrawals: 9,
+        rewards_cursor: 10,
+    };
+    assert_golden(member, fixture("member"));
+}
+
+#[test]
+fn entity_leader_transfer() {
+    let transfer = EntityLeaderTransfer {
+        initialized: true,
+        entity: key(1),
+        leader: key(2),
+        new_leader: key(3),
+    };
+    assert_golden(transfer, fixture("entity_leader_transfer"));
+}
+
+#[test]
+fn entity_metadata() {
+    let metadata = EntityMetadata {
+        initialized: true,
+        entity: key(1),
+        name: "node".to_string(),
+        website: "https://example.com".to_string(),
+        description: "A crank node.".to_string(),
+        image_uri: "ipfs://image".to_string(),
+    };
+    assert_golden(metadata, fixture("entity_metadata"));
+}
+
+#[test]
+fn beneficiary_transfer() {
+    let transfer = BeneficiaryTransfer {
+        initialized: true,
+        member: key(1),
+        beneficiary: key(2),
+        new_beneficiary: key(3),
+    };
+    assert_golden(transfer, fixture("beneficiary_transfer"));
+}
+
+#[test]
+fn pending_withdrawal() {
+    let pending_withdrawal = PendingWithdrawal {
+        initialized: true,
+        burned: true,
+        member: key(1),
+        start_ts: 2,
+        end_ts: 3,
+        amount: 4,
+        mega_amount: 5,
+    };
+    assert_golden(pending_withdrawal, fixture("pending_withdrawal"));
+}
+
+#[test]
+fn stake_snapshot() {
+    let snapshot = StakeSnapshot {
+        initialized: true,
+        registrar: key(1),
+        entity: key(2),
+        epoch: 3,
+        slot: 4,
+        unix_timestamp: 5,
+        amount: 6,
+        mega_amount: 7,
+        members_root: [8; 32],
+    };
+    assert_golden(snapshot, fixture("stake_snapshot"));
+}
+
+#[test]
+fn reward_vendor() {
+    let vendor = RewardVendor {
+        initialized: true,
+        entity: key(1),
+        cursor: 2,
+        vault: key(3),
+        mint: key(4),
+        nonce: 5,
+        total: 6,
+        stake_weight: 7,
+        merkle_root: [8; 32],
+        expiry_ts: 9,
+        expiry_receiver: key(10),
+    };
+    assert_golden(vendor, fixture("reward_vendor"));
+}
+
+#[test]
+fn reward_schedule() {
+    let schedule = RewardSchedule {
+        initialized: true,
+        entity: key(1),
+        registrar: key(2),
+        vault: key(3),
+        nonce: 4,
+        amount: 5,
+        period_secs: 6,
+        next_ts: 7,
+        epoch: 8,
+    };
+    assert_golden(schedule, fixture("reward_schedule"));
+}
+
+// Also pinned by spl-governance's voter weight addin interface.
+#[test]
+fn voter_weight_record() {
+    let record = VoterWeightRecord {
+        account_discriminator: VoterWeightRecord::ACCOUNT_DISCRIMINATOR,
+        realm: key(1),
+        governing_token_mint: key(2),
+        governing_token_owner: key(3),
+        voter_weight: 4,
+        voter_weight_expiry: Some(5),
+        weight_action: Some(6),
+        weight_action_target: Some(key(7)),
+        reserved: [8; 8],
+    };
+    assert_eq!(record.size().unwrap() as usize, VoterWeightRecord::SIZE);
+    assert_golden(record, fixture("voter_weight_record"));
+
+    // A zeroed account is an uninitialized record.
+    let record = VoterWeightRecord::unpack(&[0; VoterWeightRecord::SIZE]).unwrap();
+    assert!(!record.is_initialized());
+}
+
+// Offsets used for getProgramAccounts filters must follow the layout.
+#[test]
+fn filter_offsets() {
+    let member = Member {
+        entity: key(1),
+        beneficiary: key(2),
+        ..Default::default()
+    };
+    let bytes = pack(member);
+    assert_eq!(at(&bytes, Member::ENTITY_OFFSET), key(1));
+    assert_eq!(at(&bytes, Member::BENEFICIARY_OFFSET), key(2));
+
+    let entity = Entity {
+        leader: key(1),
+        ..Default::default()
+    };
+    assert_eq!(at(&pack(entity), Entity::LEADER_OFFSET), key(1));
+
+    let metadata = EntityMetadata {
+        entity: key(1),
+        ..Default::default()
+    };
+    assert_eq!(at(&pack(metadata), EntityMetadata::ENTITY_OFFSET), key(1));
+
+    let vendor = RewardVendor {
+        entity: key(1),
+        ..Default::default()
+    };
+    assert_eq!(at(&pack(vendor), RewardVendor::ENTITY_OFFSET), key(1));
+
+    let schedule = RewardSchedule {
+        entity: key(1),
+        ..Default::default()
+    };
+    assert_eq!(at(&pack(schedule), RewardSchedule::ENTITY_OFFSET), key(1));
+
+    let record = VoterWeightRecord {
+        governing_token_owner: key(1),
+        ..Default::default()
+    };
+    assert_eq!(
+        at(
+            &pack(record),
+            VoterWeightRecord::GOVERNING_TOKEN_OWNER_OFFSET
+        ),
+        key(1)
+    );
+
+    let snapshot = StakeSnapshot {
+        entity: key(1),
+        epoch: u64::from_le_bytes([2; 8]),
+        ..Default::default()
+    };
+    let bytes = pack(snapshot);
+    assert_eq!(at(&bytes, StakeSnapshot::ENTITY_OFFSET), key(1));
+    assert_eq!(
+        bytes[StakeSnapshot::EPOCH_OFFSET..StakeSnapshot::EPOCH_OFFSET + 8],
+        [2; 8]
+    );
+}
+
+fn pack<T: Pack>(src: T) -> Vec<u8> {
+    let mut bytes = vec![0; src.size().unwrap() as usize];
+    T::pack(src, &mut bytes).unwrap();
+    bytes
+}
+
+fn at(bytes: &[u8], offset: usize) -> Pubkey {
+    Pubkey::new(&bytes[offset..offset + 32])
+}
+
+fn key(b: u8) -> Pubkey {
+    Pubkey::new(&[b; 32])
+}
+
+fn fixture(name: &str) -> String {
+    format!("{}/tests/fixtures/{}.hex", env!("CARGO_MANIFEST_DIR"), name)
+}
@@ -1,12 +1,17 @@
 use rand::rngs::OsRng;
+use serum_common::pack::PackedSize;
 use serum_common_tests::Genesis;
 use serum_registry::accounts::Registrar;
-use serum_registry::accounts::{Entity, Member, StakeKind};
+use serum_registry::accounts::RewardSchedule;
+use serum_registry::accounts::RewardVendor;
+use serum_registry::accounts::VoterWeightRecord;
+use serum_registry::accounts::{Entity, EntityMetadata, Member, PendingWithdrawal, StakeKind};
 use serum_registry::client::Client;
 use solana_client_gen::solana_sdk::instruction::AccountMeta;
 use solana_client_gen::solana_sdk::pubkey::Pubkey;
 use solana_client_gen::solana_sdk::signature::{Keypair, Signer};
 use solana_client_gen::solana_sdk::sysvar;
+use spl_token::state::Account as TokenAccount;
 
 mod common;
 
@@ -22,32 +27,42 @@ fn lifecycle() {
 
     let Genesis {
         client,
-        srm_mint: _,
-        msrm_mint: _,
+        srm_mint,
+        msrm_mint,
         mint_authority: _,
-        god: _,
-        god_msrm: _,
-        god_balance_before: _,
+        god,
+        god_msrm,
+        god_balance_before,
         god_msrm_balance_before: _,
-        god_owner: _,
+        god_owner,
     } = genesis;
 
     // Initialize the registrar.
     let withdrawal_timelock = 1234;
     let registrar_authority = Keypair::generate(&mut OsRng);
-    let accounts = [AccountMeta::new_readonly(sysvar::rent::ID, false)];
     let (_tx_sig, registrar) = client
-        .create_account_and_initialize(&accounts, registrar_authority.pubkey(), withdrawal_timelock)
+        .create_registrar(
+            &srm_mint.pubkey(),
+            &msrm_mint.pubkey(),
+            registrar_authority.pubkey(),
+            withdrawal_timelock,
+            0,
+        )
         .unwrap();
 
     // Verify initialization.
-    {
+    let vault = {
         let registrar: Registrar =
-            serum_common::client::rpc::account_unpacked(client.rpc(), &registrar.pubkey());
+            serum_common::client::rpc::account_unpacked(client.rpc(), &registrar);
         assert_eq!(registrar.initialized, true);
         assert_eq!(registrar.authority, registrar_authority.pubkey());
         assert_eq!(registrar.capabilities_fees_bps, [0; 32]);
-    }
+        let vault: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &registrar.vault);
+        assert_eq!(vault.mint, srm_mint.pubkey());
+        assert_eq!(vault.amount, 0);
+        registrar.vault
+    };
 
     // Register capabilities.
     {
@@ -55,7 +70,7 @@ fn lifecycle() {
         let capability_fee = 1234;
         let accounts = [
             AccountMeta::new_readonly(registrar_authority.pubkey(), true),
-            AccountMeta::new(registrar.pubkey(), false),
+            AccountMeta::new(registrar, false),
         ];
         let signers = [&registrar_authority, client.payer()];
         client
@@ -63,12 +78,37 @@ fn lifecycle() {
             .unwrap();
 
         let registrar: Registrar =
-            serum_common::client::rpc::account_unpacked(client.rpc(), &registrar.pubkey());
+            serum_common::client::rpc::account_unpacked(client.rpc(), &registrar);
         let mut expected = [0; 32];
         expected[capability_id as usize] = capability_fee;
         assert_eq!(registrar.capabilities_fees_bps, expected);
     }
 
+    // Update the registrar's withdrawal timelock.
+    {
+        let new_withdrawal_timelock = 4321;
+        let accounts = [
+            AccountMeta::new_readonly(registrar_authority.pubkey(), true),
+            AccountMeta::new(registrar, false),
+        ];
+        let signers = [&registrar_authority, client.payer()];
+        client
+            .update_registrar_with_signers(
+                &signers,
+                &accounts,
+                None,
+                Some(new_withdrawal_timelock),
+                None,
+                None,
+            )
+            .unwrap();
+
+        let registrar: Registrar =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &registrar);
+        assert_eq!(registrar.authority, registrar_authority.pubkey());
+        assert_eq!(registrar.withdrawal_timelock_secs, new_withdrawal_timelock);
+    }
+
     // Create entity.
     let node_leader = Keypair::generate(&mut OsRng);
     let node_leader_pubkey = node_leader.pubkey();
@@ -77,7 +117,7 @@ fn lifecycle() {
         let stake_kind = StakeKind::Delegated;
 
         let (_tx_sig, entity_addr) = client
-            .create_entity_derived(&node_leader, capabilities, stake_kind)
+            .create_entity_derived(&node_leader, registrar, capabilities, stake_kind)
             .unwrap();
 
         let entity: Entity =
@@ -88,15 +128,342 @@ fn lifecycle() {
         assert_eq!(entity.mega_amount, 0);
         assert_eq!(entity.capabilities, capabilities);
         assert_eq!(entity.stake_kind, stake_kind);
+        assert_eq!(entity.registrar, registrar);
 
         entity_addr
     };
 
+    // Set the entity's metadata.
+    {
+        let metadata_kp = Keypair::generate(&mut OsRng);
+        let accounts = [
+            AccountMeta::new(metadata_kp.pubkey(), false),
+            AccountMeta::new_readonly(entity, false),
+            AccountMeta::new_readonly(node_leader.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ];
+        let signers = [client.payer(), &metadata_kp, &node_leader];
+        let (_tx_sig, metadata_kp) = client
+            .create_account_and_update_entity_metadata_with_signers(
+                Keypair::from_bytes(&metadata_kp.to_bytes()).unwrap(),
+                &signers,
+                &accounts,
+                "node".to_string(),
+                "https://example.com".to_string(),
+                "A crank node.".to_string(),
+                "".to_string(),
+            )
+            .unwrap();
+
+        let metadata: EntityMetadata =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &metadata_kp.pubkey());
+        assert_eq!(metadata.initialized, true);
+        assert_eq!(metadata.entity, entity);
+        assert_eq!(metadata.name, "node");
+        assert_eq!(metadata.website, "https://example.com");
+        assert_eq!(metadata.description, "A crank node.");
+        assert_eq!(metadata.image_uri, "");
+    }
+
+    // Hand the entity to a new leader.
+    let new_node_leader = Keypair::generate(&mut OsRng);
+    {
+        let transfer_kp = Keypair::generate(&mut OsRng);
+        let accounts = [
+            AccountMeta::new(transfer_kp.pubkey(), false),
+            AccountMeta::new_readonly(entity, false),
+            AccountMeta::new_readonly(node_leader.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ];
+        let signers = [client.payer(), &transfer_kp, &node_leader];
+        let (_tx_sig, transfer_kp) = client
+            .create_account_and_propose_entity_leader_with_signers(
+                Keypair::from_bytes(&transfer_kp.to_bytes()).unwrap(),
+                &signers,
+                &accounts,
+                new_node_leader.pubkey(),
+            )
+            .unwrap();
+
+        let accounts = [
+            AccountMeta::new(transfer_kp.pubkey(), false),
+            AccountMeta::new(entity, false),
+            AccountMeta::new(new_node_leader.pubkey(), true),
+        ];
+        let signers = [&new_node_leader, client.payer()];
+        client
+            .accept_entity_leader_with_signers(&signers, &accounts)
+            .unwrap();
+
+        let entity_account: Entity =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &entity);
+        assert_eq!(entity_account.leader, new_node_leader.pubkey());
+    }
+
+    // Set the leader's commission. The raise waits out the withdrawal
+    // timelock.
+    {
+        let accounts = [
+            AccountMeta::new(entity, false),
+            AccountMeta::new_readonly(new_node_leader.pubkey(), true),
+            AccountMeta::new_readonly(registrar, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+        ];
+        let signers = [&new_node_leader, client.payer()];
+        client
+            .set_entity_commission_with_signers(&signers, &accounts, 500)
+            .unwrap();
+        assert!(client
+            .set_entity_commission_with_signers(&signers, &accounts, Entity::MAX_COMMISSION_BPS + 1)
+            .is_err());
+
+        let entity_account: Entity =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &entity);
+        assert_eq!(entity_account.commission_bps, 0);
+        assert_eq!(entity_account.pending_commission_bps, 500);
+        assert!(entity_account.pending_commission_ts >= withdrawal_timelock as i64);
+    }
+
+    // Drop a reward on the entity. It lands in the registrar's vault, and
+    // the commission accrues to the leader's member account.
+    {
+        let leader_member = serum_common::client::rpc::create_account_rent_exempt(
+            client.rpc(),
+            client.payer(),
+            Member::SIZE as usize,
+            client.program(),
+        )
+        .unwrap()
+        .pubkey();
+        let accounts = [
+            AccountMeta::new(leader_member, false),
+            AccountMeta::new(entity, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ];
+        client
+            .join_entity(
+                &accounts,
+                new_node_leader.pubkey(),
+                Pubkey::new_from_array([0; 32]),
+            )
+            .unwrap();
+
+        let drop_accounts = |vault| {
+            [
+                AccountMeta::new_readonly(god_owner.pubkey(), true),
+                AccountMeta::new(god.pubkey(), false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(registrar, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new(entity, false),
+                AccountMeta::new(leader_member, false),
+                AccountMeta::new_readonly(sysvar::clock::ID, false),
+            ]
+        };
+        let signers = [&god_owner, client.payer()];
+        // Only into the registrar's vault.
+        assert!(client
+            .drop_entity_reward_with_signers(&signers, &drop_accounts(god.pubkey()), 1_000)
+            .is_err());
+
+        // Nor while the registrar is paused.
+        let pause_accounts = [
+            AccountMeta::new_readonly(registrar_authority.pubkey(), true),
+            AccountMeta::new(registrar, false),
+        ];
+        let pause_signers = [&registrar_authority, client.payer()];
+        client
+            .pause_with_signers(&pause_signers, &pause_accounts)
+            .unwrap();
+        assert!(client
+            .drop_entity_reward_with_signers(&signers, &drop_accounts(vault), 1_000)
+            .is_err());
+        client
+            .unpause_with_signers(&pause_signers, &pause_accounts)
+            .unwrap();
+
+        client
+            .drop_entity_reward_with_signers(&signers, &drop_accounts(vault), 1_000)
+            .unwrap();
+
+        let vault_account: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &vault);
+        assert_eq!(vault_account.amount, 1_000);
+        let god_account: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &god.pubkey());
+        assert_eq!(god_account.amount, god_balance_before - 1_000);
+        let leader_member_account: Member =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &leader_member);
+        // Nothing is staked, so the leader keeps it all.
+        assert_eq!(leader_member_account.rewards, 1_000);
+
+        // Schedule a tranche of 600 a second, funded with 1,000, out of
+        // which anyone can crank the due tranches.
+        let schedule = Keypair::generate(&mut OsRng);
+        let (schedule_authority, nonce) =
+            Pubkey::find_program_address(&[schedule.pubkey().as_ref()], client.program());
+        let schedule_vault = serum_common::client::rpc::create_token_account(
+            client.rpc(),
+            &srm_mint.pubkey(),
+            &schedule_authority,
+            client.payer(),
+        )
+        .unwrap()
+        .pubkey();
+        let fund_instruction = spl_token::instruction::transfer(
+            &spl_token::ID,
+            &god.pubkey(),
+            &schedule_vault,
+            &god_owner.pubkey(),
+            &[],
+            1_000,
+        )
+        .unwrap();
+        serum_common::client::rpc::send_instructions(
+            client.rpc(),
+            &[fund_instruction],
+            &client.payer().pubkey(),
+            &[client.payer(), &god_owner],
+        )
+        .unwrap();
+        let accounts = [
+            AccountMeta::new(schedule.pubkey(), false),
+            AccountMeta::new_readonly(schedule_vault, false),
+            AccountMeta::new_readonly(entity, false),
+            AccountMeta::new_readonly(registrar, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ];
+        let signers = [client.payer(), &schedule];
+        let (_tx_sig, schedule) = client
+            .create_account_and_create_reward_schedule_with_signers(
+                Keypair::from_bytes(&schedule.to_bytes()).unwrap(),
+                &signers,
+                &accounts,
+                600,
+                1,
+                0,
+                nonce,
+            )
+            .unwrap();
+
+        let distribute_accounts = [
+            AccountMeta::new(schedule.pubkey(), false),
+            AccountMeta::new(schedule_vault, false),
+            AccountMeta::new_readonly(schedule_authority, false),
+            AccountMeta::new(vault, false),
+            AccountMeta::new_readonly(registrar, false),
+            AccountMeta::new(entity, false),
+            AccountMeta::new(leader_member, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+        ];
+        // The last tranche is what's left, after which there's nothing to
+        // crank.
+        client.distribute_epoch(&distribute_accounts).unwrap();
+        client.distribute_epoch(&distribute_accounts).unwrap();
+        assert!(client.distribute_epoch(&distribute_accounts).is_err());
+
+        let schedule_account: RewardSchedule =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &schedule.pubkey());
+        assert_eq!(schedule_account.epoch, 2);
+        assert_eq!(schedule_account.next_ts, 2);
+        let vault_account: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &vault);
+        assert_eq!(vault_account.amount, 2_000);
+        let leader_member_account: Member =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &leader_member);
+        assert_eq!(leader_member_account.rewards, 2_000);
+
+        // Nor can the leader close the account before the rewards are paid.
+        let accounts = [
+            AccountMeta::new(leader_member, false),
+            AccountMeta::new(new_node_leader.pubkey(), true),
+            AccountMeta::new(entity, false),
+        ];
+        let signers = [&new_node_leader, client.payer()];
+        assert!(client
+            .close_member_with_signers(&signers, &accounts)
+            .is_err());
+    }
+
+    // Limit who joins the entity. A full entity takes no new members, and
+    // one approving joins only those its leader signs for.
+    {
+        let rules_accounts = [
+            AccountMeta::new(entity, false),
+            AccountMeta::new_readonly(new_node_leader.pubkey(), true),
+        ];
+        let signers = [&new_node_leader, client.payer()];
+        client
+            .set_entity_join_rules_with_signers(&signers, &rules_accounts, 1, false)
+            .unwrap();
+
+        let member = serum_common::client::rpc::create_account_rent_exempt(
+            client.rpc(),
+            client.payer(),
+            Member::SIZE as usize,
+            client.program(),
+        )
+        .unwrap()
+        .pubkey();
+        let accounts = [
+            AccountMeta::new(member, false),
+            AccountMeta::new(entity, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ];
+        let delegate = Pubkey::new_from_array([0; 32]);
+        assert!(client
+            .join_entity(&accounts, new_node_leader.pubkey(), delegate)
+            .is_err());
+
+        client
+            .set_entity_join_rules_with_signers(&signers, &rules_accounts, 0, true)
+            .unwrap();
+        assert!(client
+            .join_entity(&accounts, new_node_leader.pubkey(), delegate)
+            .is_err());
+        let mut approved_accounts = accounts.to_vec();
+        approved_accounts.push(AccountMeta::new_readonly(new_node_leader.pubkey(), true));
+        client
+            .join_entity_with_signers(
+                &signers,
+                &approved_accounts,
+                new_node_leader.pubkey(),
+                delegate,
+            )
+            .unwrap();
+
+        let entity_account: Entity =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &entity);
+        assert_eq!(entity_account.member_count, 2);
+
+        // Closing a member makes room for another.
+        client
+            .set_entity_join_rules_with_signers(&signers, &rules_accounts, 2, false)
+            .unwrap();
+        let accounts = [
+            AccountMeta::new(member, false),
+            AccountMeta::new(new_node_leader.pubkey(), true),
+            AccountMeta::new(entity, false),
+        ];
+        client
+            .close_member_with_signers(&signers, &accounts)
+            .unwrap();
+        let entity_account: Entity =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &entity);
+        assert_eq!(entity_account.member_count, 1);
+        assert!(!entity_account.is_full());
+
+        client
+            .set_entity_join_rules_with_signers(&signers, &rules_accounts, 0, false)
+            .unwrap();
+    }
+
     // Update entity.
     {
         let accounts = [
             AccountMeta::new(entity, false),
-            AccountMeta::new_readonly(node_leader.pubkey(), true),
+            AccountMeta::new_readonly(new_node_leader.pubkey(), true),
         ];
 
         let new_capabilities = 1 | 2;
@@ -104,7 +471,7 @@ fn lifecycle() {
 
         client
             .update_entity_with_signers(
-                &[&node_leader, client.payer()],
+                &[&new_node_leader, client.payer()],
                 &accounts,
                 new_leader.clone(),
                 new_capabilities,
@@ -119,7 +486,7 @@ fn lifecycle() {
 
     // Join enitty.
     let beneficiary = Keypair::generate(&mut OsRng);
-    {
+    let member_addr = {
         let delegate = Pubkey::new_from_array([0; 32]);
         let (_tx_sig, member_addr) = client
             .join_entity_derived(entity, beneficiary.pubkey(), delegate)
@@ -130,8 +497,597 @@ fn lifecycle() {
         assert_eq!(member.initialized, true);
         assert_eq!(member.entity, entity);
         assert_eq!(member.beneficiary, beneficiary.pubkey());
+        let entity_account: Entity =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &entity);
+        assert_eq!(entity_account.member_count, 2);
         assert_eq!(member.delegate, Pubkey::new_from_array([0; 32]));
         assert_eq!(member.amount, 0);
         assert_eq!(member.mega_amount, 0);
+        assert_eq!(member.memo, [0; 32]);
+        assert_eq!(member.registrar, registrar);
+        assert_eq!(member.pending_withdrawals, 0);
+        member_addr
+    };
+
+    // Transfer the member to a new beneficiary.
+    let new_beneficiary = Keypair::generate(&mut OsRng);
+    {
+        let accounts = [
+            AccountMeta::new_readonly(member_addr, false),
+            AccountMeta::new_readonly(beneficiary.pubkey(), true),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+        ];
+        let transfer = Keypair::generate(&mut OsRng);
+        let mut accounts_with_transfer = accounts.to_vec();
+        accounts_with_transfer.insert(0, AccountMeta::new(transfer.pubkey(), false));
+        let signers = [client.payer(), &transfer, &beneficiary];
+        let (_tx_sig, transfer) = client
+            .create_account_and_initiate_beneficiary_transfer_with_signers(
+                Keypair::from_bytes(&transfer.to_bytes()).unwrap(),
+                &signers,
+                &accounts_with_transfer,
+                new_beneficiary.pubkey(),
+            )
+            .unwrap();
+
+        // Not changed until accepted.
+        let member: Member =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &member_addr);
+        assert_eq!(member.beneficiary, beneficiary.pubkey());
+
+        let accounts = [
+            AccountMeta::new(transfer.pubkey(), false),
+            AccountMeta::new(member_addr, false),
+            AccountMeta::new(new_beneficiary.pubkey(), true),
+        ];
+        let signers = [&new_beneficiary, client.payer()];
+        client
+            .accept_beneficiary_transfer_with_signers(&signers, &accounts)
+            .unwrap();
+
+        let member: Member =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &member_addr);
+        assert_eq!(member.beneficiary, new_beneficiary.pubkey());
+        assert!(client.rpc().get_account(&transfer.pubkey()).is_err());
+    }
+
+    // Accounts completing a withdrawal, paying out to `srm` and `msrm`.
+    let registrar_account: Registrar =
+        serum_common::client::rpc::account_unpacked(client.rpc(), &registrar);
+    let vault_authority = Pubkey::create_program_address(
+        &Registrar::signer_seeds(&registrar, &registrar_account.nonce),
+        client.program(),
+    )
+    .unwrap();
+    let end_withdrawal_accounts = |pending_withdrawal, srm, msrm| {
+        [
+            AccountMeta::new(pending_withdrawal, false),
+            AccountMeta::new_readonly(new_beneficiary.pubkey(), true),
+            AccountMeta::new(member_addr, false),
+            AccountMeta::new(entity, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(registrar, false),
+            AccountMeta::new(registrar_account.vault, false),
+            AccountMeta::new(registrar_account.mega_vault, false),
+            AccountMeta::new_readonly(vault_authority, false),
+            AccountMeta::new(srm, false),
+            AccountMeta::new(msrm, false),
+        ]
+    };
+
+    // Stake SRM and MSRM into the registrar's vaults.
+    {
+        let stake_accounts = |depositor, vault| {
+            [
+                AccountMeta::new_readonly(god_owner.pubkey(), true),
+                AccountMeta::new(depositor, false),
+                AccountMeta::new(member_addr, false),
+                AccountMeta::new_readonly(new_beneficiary.pubkey(), true),
+                AccountMeta::new(entity, false),
+                AccountMeta::new(vault, false),
+                AccountMeta::new_readonly(registrar, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(sysvar::clock::ID, false),
+            ]
+        };
+        let signers = [&god_owner, &new_beneficiary, client.payer()];
+        client
+            .stake_with_signers(
+                &signers,
+                &stake_accounts(god.pubkey(), registrar_account.vault),
+                100,
+                false,
+            )
+            .unwrap();
+        client
+            .stake_with_signers(
+                &signers,
+                &stake_accounts(god_msrm.pubkey(), registrar_account.mega_vault),
+                2,
+                true,
+            )
+            .unwrap();
+        // Only into the registrar's vault for the token.
+        assert!(client
+            .stake_with_signers(
+                &signers,
+                &stake_accounts(god.pubkey(), registrar_account.mega_vault),
+                100,
+                false,
+            )
+            .is_err());
+
+        let member: Member =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &member_addr);
+        assert_eq!(member.amount, 100);
+        assert_eq!(member.mega_amount, 2);
+        let entity_account: Entity =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &entity);
+        assert_eq!(entity_account.amount, 100);
+        assert_eq!(entity_account.mega_amount, 2);
+        let vault: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &vault);
+        assert_eq!(vault.amount, 2_100);
+        let mega_vault: TokenAccount = serum_common::client::rpc::account_token_unpacked(
+            client.rpc(),
+            &registrar_account.mega_vault,
+        );
+        assert_eq!(mega_vault.amount, 2);
+    }
+
+    // Report the member's stake to spl-governance, for a realm governed by
+    // SRM. The record only ever reports for that realm and token.
+    {
+        let realm = Pubkey::new_rand();
+        let record_kp = Keypair::generate(&mut OsRng);
+        let accounts = |vault| {
+            [
+                AccountMeta::new(record_kp.pubkey(), false),
+                AccountMeta::new_readonly(new_beneficiary.pubkey(), true),
+                AccountMeta::new_readonly(member_addr, false),
+                AccountMeta::new_readonly(registrar, false),
+                AccountMeta::new_readonly(vault, false),
+                AccountMeta::new_readonly(sysvar::clock::ID, false),
+                AccountMeta::new_readonly(sysvar::rent::ID, false),
+            ]
+        };
+        let signers = [client.payer(), &record_kp, &new_beneficiary];
+        client
+            .create_account_and_update_voter_weight_record_with_signers(
+                Keypair::from_bytes(&record_kp.to_bytes()).unwrap(),
+                &signers,
+                &accounts(registrar_account.vault),
+                realm,
+                false,
+            )
+            .unwrap();
+
+        let record: VoterWeightRecord =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &record_kp.pubkey());
+        assert!(record.is_initialized());
+        assert_eq!(record.realm, realm);
+        assert_eq!(record.governing_token_mint, srm_mint.pubkey());
+        assert_eq!(record.governing_token_owner, new_beneficiary.pubkey());
+        assert_eq!(record.voter_weight, 100);
+        assert!(record.voter_weight_expiry.is_some());
+
+        let signers = [&new_beneficiary, client.payer()];
+        assert!(client
+            .update_voter_weight_record_with_signers(
+                &signers,
+                &accounts(registrar_account.mega_vault),
+                realm,
+                true,
+            )
+            .is_err());
+        assert!(client
+            .update_voter_weight_record_with_signers(
+                &signers,
+                &accounts(registrar_account.vault),
+                Pubkey::new_rand(),
+                false,
+            )
+            .is_err());
+    }
+
+    // Drop a reward in another token. The member, the entity's only staker,
+    // must claim all of it before its stake can change.
+    {
+        let reward_mint = Keypair::generate(&mut OsRng);
+        serum_common::client::rpc::create_and_init_mint(
+            client.rpc(),
+            client.payer(),
+            &reward_mint,
+            &client.payer().pubkey(),
+            0,
+        )
+        .unwrap();
+        let depositor = serum_common::client::rpc::mint_to_new_account(
+            client.rpc(),
+            client.payer(),
+            client.payer(),
+            &reward_mint.pubkey(),
+            1_000,
+        )
+        .unwrap();
+
+        let vendor = Keypair::generate(&mut OsRng);
+        let (vendor_authority, nonce) =
+            Pubkey::find_program_address(&[vendor.pubkey().as_ref()], client.program());
+        let vendor_vault = serum_common::client::rpc::create_token_account(
+            client.rpc(),
+            &reward_mint.pubkey(),
+            &vendor_authority,
+            client.payer(),
+        )
+        .unwrap()
+        .pubkey();
+        let accounts = [
+            AccountMeta::new(vendor.pubkey(), false),
+            AccountMeta::new(vendor_vault, false),
+            AccountMeta::new_readonly(client.payer().pubkey(), true),
+            AccountMeta::new(depositor.pubkey(), false),
+            AccountMeta::new(entity, false),
+            AccountMeta::new_readonly(registrar, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+        ];
+        let drop_accounts = |authority| {
+            let mut accounts = accounts.to_vec();
+            accounts.push(AccountMeta::new_readonly(authority, true));
+            accounts
+        };
+        // Only the registrar's authority or the entity's leader can drop.
+        let signers = [client.payer(), &vendor];
+        assert!(client
+            .create_account_and_drop_vendor_reward_with_signers(
+                Keypair::from_bytes(&vendor.to_bytes()).unwrap(),
+                &signers,
+                &drop_accounts(client.payer().pubkey()),
+                1_000,
+                nonce,
+                [0; 32],
+                i64::MAX,
+                client.payer().pubkey(),
+            )
+            .is_err());
+        let accounts = drop_accounts(registrar_authority.pubkey());
+        let signers = [client.payer(), &vendor, &registrar_authority];
+        let (_tx_sig, vendor) = client
+            .create_account_and_drop_vendor_reward_with_signers(
+                Keypair::from_bytes(&vendor.to_bytes()).unwrap(),
+                &signers,
+                &accounts,
+                1_000,
+                nonce,
+                [0; 32],
+                i64::MAX,
+                client.payer().pubkey(),
+            )
+            .unwrap();
+
+        let vendor_account: RewardVendor =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &vendor.pubkey());
+        assert_eq!(vendor_account.mint, reward_mint.pubkey());
+        assert_eq!(vendor_account.cursor, 0);
+        assert_eq!(vendor_account.total, 1_000);
+
+        let reward = serum_common::client::rpc::create_token_account(
+            client.rpc(),
+            &reward_mint.pubkey(),
+            &new_beneficiary.pubkey(),
+            client.payer(),
+        )
+        .unwrap()
+        .pubkey();
+        let claim_accounts = |to| {
+            [
+                AccountMeta::new_readonly(new_beneficiary.pubkey(), true),
+                AccountMeta::new(member_addr, false),
+                AccountMeta::new_readonly(vendor.pubkey(), false),
+                AccountMeta::new(vendor_vault, false),
+                AccountMeta::new_readonly(vendor_authority, false),
+                AccountMeta::new(to, false),
+                AccountMeta::new_readonly(spl_token::ID, false),
+                AccountMeta::new_readonly(sysvar::clock::ID, false),
+            ]
+        };
+        let signers = [&new_beneficiary, client.payer()];
+        // Only into the signer's own token account.
+        assert!(client
+            .claim_vendor_reward_with_signers(&signers, &claim_accounts(depositor.pubkey()))
+            .is_err());
+        client
+            .claim_vendor_reward_with_signers(&signers, &claim_accounts(reward))
+            .unwrap();
+        // Only once.
+        assert!(client
+            .claim_vendor_reward_with_signers(&signers, &claim_accounts(reward))
+            .is_err());
+
+        let reward: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &reward);
+        assert_eq!(reward.amount, 1_000);
+        let member: Member =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &member_addr);
+        assert_eq!(member.rewards_cursor, 1);
+    }
+
+    // Drop a reward shared by a Merkle root instead, here of a single leaf
+    // owing the payer. It doesn't hold up the member's stake.
+    {
+        let reward_mint = Keypair::generate(&mut OsRng);
+        serum_common::client::rpc::create_and_init_mint(
+            client.rpc(),
+            client.payer(),
+            &reward_mint,
+            &client.payer().pubkey(),
+            0,
+        )
+        .unwrap();
+        let depositor = serum_common::client::rpc::mint_to_new_account(
+            client.rpc(),
+            client.payer(),
+            client.payer(),
+            &reward_mint.pubkey(),
+            400,
+        )
+        .unwrap();
+
+        let vendor = Keypair::generate(&mut OsRng);
+        let (vendor_authority, nonce) =
+            Pubkey::find_program_address(&[vendor.pubkey().as_ref()], client.program());
+        let vendor_vault = serum_common::client::rpc::create_token_account(
+            client.rpc(),
+            &reward_mint.pubkey(),
+            &vendor_authority,
+            client.payer(),
+        )
+        .unwrap()
+        .pubkey();
+        let accounts = [
+            AccountMeta::new(vendor.pubkey(), false),
+            AccountMeta::new(vendor_vault, false),
+            AccountMeta::new_readonly(client.payer().pubkey(), true),
+            AccountMeta::new(depositor.pubkey(), false),
+            AccountMeta::new(entity, false),
+            AccountMeta::new_readonly(registrar, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+            AccountMeta::new_readonly(new_node_leader.pubkey(), true),
+        ];
+        let signers = [client.payer(), &vendor, &new_node_leader];
+        let (_tx_sig, vendor) = client
+            .create_account_and_drop_vendor_reward_with_signers(
+                Keypair::from_bytes(&vendor.to_bytes()).unwrap(),
+                &signers,
+                &accounts,
+                400,
+                nonce,
+                RewardVendor::merkle_leaf(0, &client.payer().pubkey(), 400),
+                i64::MAX,
+                client.payer().pubkey(),
+            )
+            .unwrap();
+
+        let entity_account: Entity =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &entity);
+        assert_eq!(entity_account.vendor_count, 1);
+
+        let reward = serum_common::client::rpc::create_token_account(
+            client.rpc(),
+            &reward_mint.pubkey(),
+            &client.payer().pubkey(),
+            client.payer(),
+        )
+        .unwrap()
+        .pubkey();
+        let (receipt, receipt_nonce) =
+            RewardVendor::find_claim_address(client.program(), &vendor.pubkey(), 0);
+        let accounts = [
+            AccountMeta::new(client.payer().pubkey(), true),
+            AccountMeta::new_readonly(vendor.pubkey(), false),
+            AccountMeta::new(vendor_vault, false),
+            AccountMeta::new_readonly(vendor_authority, false),
+            AccountMeta::new(reward, false),
+            AccountMeta::new(receipt, false),
+            AccountMeta::new_readonly(spl_token::ID, false),
+            AccountMeta::new_readonly(solana_client_gen::solana_sdk::system_program::ID, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+        ];
+        let signers = [client.payer()];
+        // Only for the amount in the leaf.
+        assert!(client
+            .claim_merkle_reward_with_signers(&signers, &accounts, 0, 401, vec![], receipt_nonce)
+            .is_err());
+        client
+            .claim_merkle_reward_with_signers(&signers, &accounts, 0, 400, vec![], receipt_nonce)
+            .unwrap();
+        // Only once.
+        assert!(client
+            .claim_merkle_reward_with_signers(&signers, &accounts, 0, 400, vec![], receipt_nonce)
+            .is_err());
+
+        let reward: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &reward);
+        assert_eq!(reward.amount, 400);
+    }
+
+    // Without a timelock, a withdrawal completes right away. Each pays out
+    // exactly its amounts, and the rest stays staked until withdrawn too.
+    {
+        let update_timelock = |withdrawal_timelock| {
+            let accounts = [
+                AccountMeta::new_readonly(registrar_authority.pubkey(), true),
+                AccountMeta::new(registrar, false),
+            ];
+            let signers = [&registrar_authority, client.payer()];
+            client
+                .update_registrar_with_signers(
+                    &signers,
+                    &accounts,
+                    None,
+                    Some(withdrawal_timelock),
+                    None,
+                    None,
+                )
+                .unwrap();
+        };
+        update_timelock(0);
+
+        let srm = serum_common::client::rpc::create_token_account(
+            client.rpc(),
+            &srm_mint.pubkey(),
+            &new_beneficiary.pubkey(),
+            client.payer(),
+        )
+        .unwrap()
+        .pubkey();
+        let msrm = serum_common::client::rpc::create_token_account(
+            client.rpc(),
+            &msrm_mint.pubkey(),
+            &new_beneficiary.pubkey(),
+            client.payer(),
+        )
+        .unwrap()
+        .pubkey();
+
+        for (amount, mega_amount, staked, mega_staked) in &[(40, 1, 60, 1), (60, 1, 0, 0)] {
+            let pending_withdrawal = Keypair::generate(&mut OsRng);
+            let accounts = [
+                AccountMeta::new(pending_withdrawal.pubkey(), false),
+                AccountMeta::new_readonly(new_beneficiary.pubkey(), true),
+                AccountMeta::new(member_addr, false),
+                AccountMeta::new(entity, false),
+                AccountMeta::new_readonly(registrar, false),
+                AccountMeta::new_readonly(sysvar::rent::ID, false),
+                AccountMeta::new_readonly(sysvar::clock::ID, false),
+            ];
+            let signers = [client.payer(), &pending_withdrawal, &new_beneficiary];
+            let (_tx_sig, pending_withdrawal) = client
+                .create_account_and_start_stake_withdrawal_with_signers(
+                    Keypair::from_bytes(&pending_withdrawal.to_bytes()).unwrap(),
+                    &signers,
+                    &accounts,
+                    *amount,
+                    *mega_amount,
+                )
+                .unwrap();
+
+            // The stake stops counting as soon as the withdrawal starts.
+            let member: Member =
+                serum_common::client::rpc::account_unpacked(client.rpc(), &member_addr);
+            assert_eq!(member.amount, *staked);
+            assert_eq!(member.mega_amount, *mega_staked);
+
+            // Only paid into the signer's own token accounts.
+            let signers = [&new_beneficiary, client.payer()];
+            assert!(client
+                .end_stake_withdrawal_with_signers(
+                    &signers,
+                    &end_withdrawal_accounts(pending_withdrawal.pubkey(), god.pubkey(), msrm),
+                )
+                .is_err());
+
+            client
+                .end_stake_withdrawal_with_signers(
+                    &signers,
+                    &end_withdrawal_accounts(pending_withdrawal.pubkey(), srm, msrm),
+                )
+                .unwrap();
+
+            let pending: PendingWithdrawal = serum_common::client::rpc::account_unpacked(
+                client.rpc(),
+                &pending_withdrawal.pubkey(),
+            );
+            assert_eq!(pending.burned, true);
+            let member: Member =
+                serum_common::client::rpc::account_unpacked(client.rpc(), &member_addr);
+            assert_eq!(member.pending_withdrawals, 0);
+            let entity_account: Entity =
+                serum_common::client::rpc::account_unpacked(client.rpc(), &entity);
+            assert_eq!(entity_account.amount, *staked);
+            assert_eq!(entity_account.mega_amount, *mega_staked);
+        }
+
+        let srm: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &srm);
+        assert_eq!(srm.amount, 100);
+        let msrm: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &msrm);
+        assert_eq!(msrm.amount, 2);
+        // The leader's unpaid rewards are all that's left.
+        let vault: TokenAccount =
+            serum_common::client::rpc::account_token_unpacked(client.rpc(), &vault);
+        assert_eq!(vault.amount, 2_000);
+
+        update_timelock(4321);
+    }
+
+    // Start a withdrawal. It can't complete until the timelock passes, even
+    // though the entity is inactive.
+    {
+        let pending_withdrawal = Keypair::generate(&mut OsRng);
+        let accounts = [
+            AccountMeta::new(pending_withdrawal.pubkey(), false),
+            AccountMeta::new_readonly(new_beneficiary.pubkey(), true),
+            AccountMeta::new(member_addr, false),
+            AccountMeta::new(entity, false),
+            AccountMeta::new_readonly(registrar, false),
+            AccountMeta::new_readonly(sysvar::rent::ID, false),
+            AccountMeta::new_readonly(sysvar::clock::ID, false),
+        ];
+        let signers = [client.payer(), &pending_withdrawal, &new_beneficiary];
+        let (_tx_sig, pending_withdrawal) = client
+            .create_account_and_start_stake_withdrawal_with_signers(
+                Keypair::from_bytes(&pending_withdrawal.to_bytes()).unwrap(),
+                &signers,
+                &accounts,
+                0,
+                0,
+            )
+            .unwrap();
+
+        let pending: PendingWithdrawal =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &pending_withdrawal.pubkey());
+        assert_eq!(pending.initialized, true);
+        assert_eq!(pending.burned, false);
+        assert_eq!(pending.member, member_addr);
+        assert_eq!(pending.end_ts - pending.start_ts, 4321);
+        let member: Member =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &member_addr);
+        assert_eq!(member.pending_withdrawals, 1);
+
+        let accounts = end_withdrawal_accounts(
+            pending_withdrawal.pubkey(),
+            Pubkey::new_rand(),
+            Pubkey::new_rand(),
+        );
+        let signers = [&new_beneficiary, client.payer()];
+        assert!(client
+            .end_stake_withdrawal_with_signers(&signers, &accounts)
+            .is_err());
+    }
+
+    // The member can't be closed while the withdrawal is pending, though
+    // nothing is staked, or it could never complete.
+    {
+        let accounts = [
+            AccountMeta::new(member_addr, false),
+            AccountMeta::new(new_beneficiary.pubkey(), true),
+            AccountMeta::new(entity, false),
+        ];
+        let signers = [&new_beneficiary, client.payer()];
+        assert!(client
+            .close_member_with_signers(&signers, &accounts)
+            .is_err());
+
+        let member: Member =
+            serum_common::client::rpc::account_unpacked(client.rpc(), &member_addr);
+        assert_eq!(member.initialized, true);
     }
 }
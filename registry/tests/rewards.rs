@@ -0,0 +1,481 @@
+use serum_registry::accounts::{Entity, Member, Registrar, RewardSchedule, RewardVendor};
+use solana_client_gen::solana_sdk::pubkey::Pubkey;
+
+#[test]
+fn split_reward() {
+    let entity = Entity {
+        commission_bps: 1_000,
+        ..Default::default()
+    };
+    assert_eq!(entity.split_reward(1_000), (100, 900));
+    assert_eq!(entity.split_reward(9), (0, 9));
+
+    let entity = Entity::default();
+    assert_eq!(entity.split_reward(1_000), (0, 1_000));
+
+    let entity = Entity {
+        commission_bps: Entity::MAX_COMMISSION_BPS,
+        ..Default::default()
+    };
+    assert_eq!(entity.split_reward(u64::MAX), (u64::MAX, 0));
+}
+
+#[test]
+fn set_commission() {
+    let mut entity = Entity {
+        commission_bps: 1_000,
+        ..Default::default()
+    };
+
+    // A raise waits out the timelock.
+    entity.set_commission(3_000, 100, 50).unwrap();
+    assert_eq!(entity.commission_bps, 1_000);
+    entity.apply_pending_commission(149);
+    assert_eq!(entity.commission_bps, 1_000);
+    entity.apply_pending_commission(150);
+    assert_eq!(entity.commission_bps, 3_000);
+    assert_eq!(entity.pending_commission_ts, 0);
+
+    // A cut applies at once and drops the pending raise.
+    entity.set_commission(4_000, 200, 50).unwrap();
+    entity.set_commission(2_000, 210, 50).unwrap();
+    assert_eq!(entity.commission_bps, 2_000);
+    entity.apply_pending_commission(300);
+    assert_eq!(entity.commission_bps, 2_000);
+}
+
+#[test]
+fn reward_share() {
+    let alice = Member {
+        amount: 3_000_000,
+        ..Default::default()
+    };
+    let bob = Member {
+        amount: 1_000_000,
+        mega_amount: 1,
+        ..Default::default()
+    };
+    let entity = Entity {
+        amount: alice.amount + bob.amount,
+        mega_amount: alice.mega_amount + bob.mega_amount,
+        commission_bps: 2_000,
+        ..Default::default()
+    };
+
+    let (commission, members_amount) = entity.split_reward(1_000_000);
+    assert_eq!(commission, 200_000);
+    let alice_share = alice.reward_share(&entity, members_amount);
+    let bob_share = bob.reward_share(&entity, members_amount);
+    assert_eq!(alice_share, 2);
+    assert_eq!(bob_share, 799_997);
+    assert!(commission + alice_share + bob_share <= 1_000_000);
+}
+
+#[test]
+fn reward_share_empty_entity() {
+    let member = Member::default();
+    assert_eq!(member.reward_share(&Entity::default(), 1_000), 0);
+}
+
+#[test]
+fn drop_reward() {
+    let mut leader = Member::default();
+    let mut alice = Member {
+        amount: 3_000_000,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: alice.amount,
+        commission_bps: 1_000,
+        ..Default::default()
+    };
+
+    let commission = entity.drop_reward(1_000_000).unwrap();
+    leader.did_earn(commission).unwrap();
+    assert_eq!(leader.rewards, 100_000);
+
+    // Bob joins after the first drop and only shares in the second.
+    let mut bob = Member {
+        reward_index: entity.reward_index,
+        ..Default::default()
+    };
+    bob.accrue_rewards(&entity).unwrap();
+    bob.did_deposit(1_000_000, false).unwrap();
+    entity.did_deposit(1_000_000, false).unwrap();
+
+    let commission = entity.drop_reward(2_000_000).unwrap();
+    leader.did_earn(commission).unwrap();
+    alice.accrue_rewards(&entity).unwrap();
+    bob.accrue_rewards(&entity).unwrap();
+
+    assert_eq!(leader.rewards, 300_000);
+    assert_eq!(alice.rewards, 900_000 + 1_350_000);
+    assert_eq!(bob.rewards, 450_000);
+
+    // Accruing again is a no-op.
+    alice.accrue_rewards(&entity).unwrap();
+    assert_eq!(alice.rewards, 2_250_000);
+}
+
+#[test]
+fn drop_reward_rounds_down() {
+    let mut alice = Member {
+        amount: 3,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: alice.amount,
+        ..Default::default()
+    };
+    assert_eq!(entity.drop_reward(10).unwrap(), 0);
+    alice.accrue_rewards(&entity).unwrap();
+    assert_eq!(alice.rewards, 9);
+}
+
+#[test]
+fn drop_reward_empty_entity() {
+    let mut entity = Entity {
+        commission_bps: 1_000,
+        ..Default::default()
+    };
+    assert_eq!(entity.drop_reward(1_000).unwrap(), 1_000);
+    assert_eq!(entity.reward_index, 0);
+}
+
+#[test]
+fn stream_reward() {
+    let mut alice = Member {
+        amount: 1_000_000,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: alice.amount,
+        ..Default::default()
+    };
+    assert_eq!(entity.stream_reward(1_000_000, 0, 100).unwrap(), 0);
+
+    // Bob stakes halfway through and only shares in the second half.
+    entity.update_reward_index(50).unwrap();
+    let mut bob = Member {
+        reward_index: entity.reward_index,
+        ..Default::default()
+    };
+    bob.accrue_rewards(&entity).unwrap();
+    bob.did_deposit(1_000_000, false).unwrap();
+    entity.did_deposit(1_000_000, false).unwrap();
+
+    // Nothing more is streamed past the end.
+    entity.update_reward_index(1_000).unwrap();
+    alice.accrue_rewards(&entity).unwrap();
+    bob.accrue_rewards(&entity).unwrap();
+    assert_eq!(alice.rewards, 750_000);
+    assert_eq!(bob.rewards, 250_000);
+}
+
+#[test]
+fn stream_reward_rolls_over() {
+    let mut alice = Member {
+        amount: 1_000_000,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: alice.amount,
+        commission_bps: 1_000,
+        ..Default::default()
+    };
+    assert_eq!(entity.stream_reward(1_000_000, 0, 100).unwrap(), 100_000);
+
+    // What's left of the first drop is streamed along with the second.
+    assert_eq!(entity.stream_reward(1_000_000, 50, 100).unwrap(), 100_000);
+    assert_eq!(entity.reward_end_ts, 150);
+    entity.update_reward_index(100).unwrap();
+    alice.accrue_rewards(&entity).unwrap();
+    assert_eq!(alice.rewards, 450_000 + 675_000);
+
+    entity.update_reward_index(150).unwrap();
+    alice.accrue_rewards(&entity).unwrap();
+    assert_eq!(alice.rewards, 1_800_000);
+}
+
+#[test]
+fn stream_reward_pauses_while_unstaked() {
+    let mut alice = Member {
+        amount: 1_000_000,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: alice.amount,
+        ..Default::default()
+    };
+    entity.stream_reward(1_000_000, 0, 100).unwrap();
+
+    entity.update_reward_index(50).unwrap();
+    alice.accrue_rewards(&entity).unwrap();
+    alice.did_withdraw(1_000_000, false).unwrap();
+    entity.did_withdraw(1_000_000, false).unwrap();
+    assert_eq!(alice.rewards, 500_000);
+
+    // The rest of the stream waits for the stake to come back.
+    entity.update_reward_index(80).unwrap();
+    assert_eq!(entity.reward_end_ts, 130);
+    alice.accrue_rewards(&entity).unwrap();
+    alice.did_deposit(1_000_000, false).unwrap();
+    entity.did_deposit(1_000_000, false).unwrap();
+
+    entity.update_reward_index(1_000).unwrap();
+    alice.accrue_rewards(&entity).unwrap();
+    assert_eq!(alice.rewards, 1_000_000);
+}
+
+#[test]
+fn stream_reward_without_period() {
+    let mut entity = Entity {
+        amount: 1_000,
+        ..Default::default()
+    };
+    assert_eq!(entity.stream_reward(1_000, 10, 0).unwrap(), 0);
+    assert_eq!(entity.reward_index, Entity::REWARD_INDEX_PRECISION);
+    assert_eq!(entity.reward_rate, 0);
+}
+
+#[test]
+fn vendor_reward_share() {
+    let alice = Member {
+        amount: 3_000_000_000_000,
+        ..Default::default()
+    };
+    let bob = Member {
+        mega_amount: 1,
+        ..Default::default()
+    };
+    let entity = Entity {
+        amount: alice.amount,
+        mega_amount: bob.mega_amount,
+        ..Default::default()
+    };
+    let vendor = RewardVendor {
+        total: 1_001,
+        stake_weight: entity.stake_weight(),
+        ..Default::default()
+    };
+    // MSRM is weighed as 1,000,000 SRM, and shares round down.
+    assert_eq!(vendor.reward_share(&alice), 750);
+    assert_eq!(vendor.reward_share(&bob), 250);
+    assert_eq!(RewardVendor::default().reward_share(&alice), 0);
+}
+
+#[test]
+fn sync_rewards_cursor() {
+    let mut entity = Entity::default();
+    let mut alice = Member {
+        amount: 1,
+        ..Default::default()
+    };
+    assert_eq!(entity.did_drop_vendor().unwrap(), 0);
+    assert_eq!(entity.did_drop_vendor().unwrap(), 1);
+
+    // Stake can't change until every vendor is claimed.
+    assert!(alice.sync_rewards_cursor(&entity).is_err());
+    alice.rewards_cursor = 2;
+    alice.sync_rewards_cursor(&entity).unwrap();
+
+    // Without stake there's nothing to claim.
+    let mut bob = Member::default();
+    bob.sync_rewards_cursor(&entity).unwrap();
+    assert_eq!(bob.rewards_cursor, 2);
+
+    // A withdrawal gives up the vendors not yet claimed.
+    let mut carol = Member {
+        amount: 1,
+        ..Default::default()
+    };
+    carol.forfeit_unclaimed_vendors(&entity);
+    assert_eq!(carol.rewards_cursor, 2);
+    carol.sync_rewards_cursor(&entity).unwrap();
+}
+
+#[test]
+fn vendor_expiry() {
+    let vendor = RewardVendor {
+        expiry_ts: 100,
+        ..Default::default()
+    };
+    assert!(!vendor.expired(99));
+    assert!(vendor.expired(100));
+    assert!(vendor.expired(101));
+}
+
+#[test]
+fn merkle_proof() {
+    let alice = Pubkey::new(&[1; 32]);
+    let bob = Pubkey::new(&[2; 32]);
+    let carol = Pubkey::new(&[3; 32]);
+    let leaves = [
+        RewardVendor::merkle_leaf(0, &alice, 100),
+        RewardVendor::merkle_leaf(1, &bob, 200),
+        RewardVendor::merkle_leaf(2, &carol, 300),
+    ];
+    let ab = RewardVendor::merkle_parent(&leaves[0], &leaves[1]);
+    let vendor = RewardVendor {
+        merkle_root: RewardVendor::merkle_parent(&ab, &leaves[2]),
+        ..Default::default()
+    };
+
+    assert!(vendor.verify_merkle_proof(leaves[0], &[leaves[1], leaves[2]]));
+    assert!(vendor.verify_merkle_proof(leaves[1], &[leaves[0], leaves[2]]));
+    assert!(vendor.verify_merkle_proof(leaves[2], &[ab]));
+
+    // Not for another amount, claimant or index.
+    let proof = [leaves[1], leaves[2]];
+    assert!(!vendor.verify_merkle_proof(RewardVendor::merkle_leaf(0, &alice, 101), &proof));
+    assert!(!vendor.verify_merkle_proof(RewardVendor::merkle_leaf(0, &bob, 100), &proof));
+    assert!(!vendor.verify_merkle_proof(RewardVendor::merkle_leaf(1, &alice, 100), &proof));
+
+    // Nor without a root.
+    let vendor = RewardVendor::default();
+    assert!(vendor.merkle_root().is_none());
+    assert!(!vendor.verify_merkle_proof(leaves[2], &[ab]));
+}
+
+#[test]
+fn compound_rewards() {
+    let mut alice = Member {
+        amount: 3_000_000,
+        rewards: 5,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: alice.amount,
+        ..Default::default()
+    };
+    entity.drop_reward(3_000_000).unwrap();
+
+    assert_eq!(alice.compound_rewards(&mut entity).unwrap(), 3_000_005);
+    assert_eq!(alice.amount, 6_000_005);
+    assert_eq!(alice.rewards, 0);
+    assert_eq!(entity.amount, 6_000_005);
+
+    // Compounding again is a no-op.
+    assert_eq!(alice.compound_rewards(&mut entity).unwrap(), 0);
+    assert_eq!(alice.amount, 6_000_005);
+
+    // Not before claiming the entity's reward vendors.
+    entity.did_drop_vendor().unwrap();
+    assert!(alice.compound_rewards(&mut entity).is_err());
+}
+
+#[test]
+fn reward_schedule() {
+    let mut schedule = RewardSchedule {
+        amount: 600,
+        period_secs: 100,
+        next_ts: 1_000,
+        ..Default::default()
+    };
+    assert!(!schedule.is_due(999));
+    assert!(schedule.is_due(1_000));
+    assert_eq!(schedule.tranche(1_000), 600);
+    // The last tranche is what's left.
+    assert_eq!(schedule.tranche(400), 400);
+
+    // Cranked late, the next tranche still comes due a period after this
+    // one did.
+    schedule.did_distribute().unwrap();
+    assert_eq!(schedule.next_ts, 1_100);
+    assert_eq!(schedule.epoch, 1);
+}
+
+#[test]
+fn migrate_member() {
+    let beneficiary = Pubkey::new_rand();
+    let new_entity_key = Pubkey::new_rand();
+    let mut member = Member {
+        initialized: true,
+        beneficiary,
+        amount: 1_000_000,
+        mega_amount: 1,
+        memo: Member::encode_memo("treasury").unwrap(),
+        rewards: 5,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: member.amount,
+        mega_amount: member.mega_amount,
+        ..Default::default()
+    };
+    entity.drop_reward(1_000_001).unwrap();
+    // Drops before the move belong to the new entity's existing members.
+    let new_registrar = Pubkey::new_rand();
+    let mut new_entity = Entity {
+        amount: 1_000_000,
+        registrar: new_registrar,
+        ..Default::default()
+    };
+    new_entity.drop_reward(1_000_000).unwrap();
+
+    let migrated = member
+        .migrate(&mut entity, &mut new_entity, new_entity_key)
+        .unwrap();
+
+    assert!(migrated.initialized);
+    assert_eq!(migrated.entity, new_entity_key);
+    assert_eq!(migrated.beneficiary, beneficiary);
+    assert_eq!(migrated.memo_str(), "treasury");
+    assert_eq!((migrated.amount, migrated.mega_amount), (1_000_000, 1));
+    assert_eq!(migrated.rewards, 5 + 1_000_001);
+    assert_eq!(migrated.reward_index, new_entity.reward_index);
+    assert_eq!(migrated.registrar, new_registrar);
+    assert!(member.stake_is_empty());
+    assert_eq!(member.rewards, 0);
+    assert_eq!((entity.amount, entity.mega_amount), (0, 0));
+    assert_eq!((new_entity.amount, new_entity.mega_amount), (2_000_000, 1));
+    assert_eq!(new_entity.member_count, 1);
+}
+
+#[test]
+fn migrate_member_into_full_entity() {
+    let mut member = Member {
+        initialized: true,
+        amount: 1_000,
+        ..Default::default()
+    };
+    let mut entity = Entity {
+        amount: member.amount,
+        ..Default::default()
+    };
+    let mut new_entity = Entity {
+        member_count: 2,
+        max_members: 2,
+        ..Default::default()
+    };
+
+    assert!(member
+        .migrate(&mut entity, &mut new_entity, Pubkey::new_rand())
+        .is_err());
+    assert_eq!(member.amount, 1_000);
+    assert_eq!(new_entity.member_count, 2);
+}
+
+#[test]
+fn unpaid_rewards_keep_member_open() {
+    let mut member = Member {
+        initialized: true,
+        ..Default::default()
+    };
+    assert!(member.can_close());
+    member.did_earn(1).unwrap();
+    assert!(member.stake_is_empty());
+    assert!(!member.can_close());
+}
+
+#[test]
+fn stake_cap() {
+    let mut registrar = Registrar::default();
+    assert!(registrar.check_stake_cap(u64::MAX, u64::MAX).is_ok());
+
+    registrar.max_stake_per_member = 2_000_000;
+    assert!(registrar.check_stake_cap(2_000_000, 0).is_ok());
+    assert!(registrar.check_stake_cap(1_000_000, 1).is_ok());
+    assert!(registrar.check_stake_cap(2_000_001, 0).is_err());
+    assert!(registrar.check_stake_cap(1, 2).is_err());
+}
@@ -0,0 +1,71 @@
+use serum_registry::accounts::Registrar;
+use serum_registry::simulator::Simulator;
+
+#[test]
+fn stake_reward_withdraw() {
+    let mut sim = Simulator::new(
+        Registrar {
+            withdrawal_timelock_secs: 50,
+            reward_period_secs: 100,
+            ..Default::default()
+        },
+        0,
+    );
+    let entity = sim.create_entity().unwrap();
+    let leader_member = sim.leader_member(entity);
+
+    // A commission raise waits out the timelock.
+    sim.set_entity_commission(entity, 1_000).unwrap();
+    sim.advance(50);
+
+    let alice = sim.join_entity(entity).unwrap();
+    let bob = sim.join_entity(entity).unwrap();
+    sim.stake(alice, 100, false).unwrap();
+    sim.stake(bob, 300, false).unwrap();
+
+    // The commission is paid at once, and the rest streamed over the
+    // reward period.
+    assert_eq!(sim.drop_entity_reward(entity, 1_000).unwrap(), 100);
+    assert_eq!(sim.members[leader_member].rewards, 100);
+    sim.advance(50);
+    assert_eq!(sim.unpaid_rewards(alice).unwrap(), 112);
+    assert_eq!(sim.unpaid_rewards(bob).unwrap(), 337);
+    sim.advance(100);
+    assert_eq!(sim.unpaid_rewards(alice).unwrap(), 225);
+    assert_eq!(sim.unpaid_rewards(bob).unwrap(), 675);
+
+    let withdrawal = sim.start_stake_withdrawal(alice, 100, 0).unwrap();
+    assert_eq!(sim.members[alice].rewards, 225);
+    assert_eq!(sim.entities[entity].amount, 300);
+    assert!(sim.end_stake_withdrawal(withdrawal).is_err());
+    sim.advance(50);
+    assert_eq!(sim.end_stake_withdrawal(withdrawal).unwrap(), (100, 0));
+    assert!(sim.end_stake_withdrawal(withdrawal).is_err());
+
+    assert_eq!(sim.compound_rewards(bob).unwrap(), 675);
+    assert_eq!(sim.members[bob].amount, 975);
+    assert_eq!(sim.entities[entity].amount, 975);
+}
+
+#[test]
+fn failed_operation_changes_nothing() {
+    let mut sim = Simulator::new(
+        Registrar {
+            max_stake_per_member: 1_000,
+            ..Default::default()
+        },
+        0,
+    );
+    let entity = sim.create_entity().unwrap();
+    let member = sim.join_entity(entity).unwrap();
+    sim.stake(member, 1_000, false).unwrap();
+
+    assert!(sim.stake(member, 1, false).is_err());
+    assert_eq!(sim.members[member].amount, 1_000);
+    assert_eq!(sim.entities[entity].amount, 1_000);
+
+    assert!(sim.start_stake_withdrawal(member, 1_001, 0).is_err());
+    assert_eq!(sim.members[member].amount, 1_000);
+    assert_eq!(sim.members[member].pending_withdrawals, 0);
+    assert!(sim.pending_withdrawals.is_empty());
+}
@@ -6,6 +6,8 @@ use proc_quote::quote;
 use syn::parse::Parser;
 use syn::parse_macro_input;
 
+mod typescript;
+
 // At a high level, the macro works in three passes over the
 // instruction enum (inside the mod).
 //
@@ -19,9 +21,18 @@ pub fn solana_client_gen(
     args: proc_macro::TokenStream,
     input: proc_macro::TokenStream,
 ) -> proc_macro::TokenStream {
-    // The one and only argument of the macro should be the boolean marker for
-    // using the client extension
-    let needs_client_ext = parse_client_ext_arg(args);
+    // The arguments are the `ext` marker for using the client extension,
+    // the `typescript` marker for generating TypeScript instruction builders,
+    // the program's error code enum and the account types to generate
+    // subscriptions for, e.g.,
+    // `#[solana_client_gen(ext, typescript, error(ErrorCode), accounts(Member))]`.
+    let args = parse_macro_input!(args as syn::AttributeArgs);
+    let MacroArgs {
+        needs_client_ext,
+        needs_typescript,
+        error_code,
+        subscribe_accounts,
+    } = parse_args(args);
 
     // Interpet token stream as the instruction `mod`.
     let instruction_mod = parse_macro_input!(input as syn::ItemMod);
@@ -52,10 +63,24 @@ pub fn solana_client_gen(
     // And can be removed once solana udpates its rust version.
     instruction_enum_item = strip_cfg_attrs(instruction_enum_item);
 
+    // The TypeScript builders are exposed as a string constant on the client
+    // module, for build scripts or tools to write out.
+    let typescript_const = match needs_typescript {
+        false => quote! {},
+        true => {
+            let typescript = typescript::instruction_builders(&instruction_enum_item);
+            quote! {
+                // TypeScript instruction builders for the program, encoding
+                // instructions the same way as the `instruction` module.
+                pub const TYPESCRIPT: &str = #typescript;
+            }
+        }
+    };
+
     // Second pass:
     //
     // Parse the instruction enum and generate code from each enum variant.
-    let (client_methods, instruction_methods, decode_and_dispatch_tree) =
+    let (client_methods, async_client_methods, instruction_methods, decode_and_dispatch_tree) =
         enum_to_methods(&instruction_enum_item);
 
     // Now recreate the highest level instruction `mod`, but with our new
@@ -81,6 +106,67 @@ pub fn solana_client_gen(
         }
     };
 
+    // Generate a `subscribe_<account>` method for each account type.
+    let subscribe_methods: Vec<proc_macro2::TokenStream> = subscribe_accounts
+        .iter()
+        .map(|account| {
+            let method_name = proc_macro2::Ident::new(
+                format!("subscribe_{}", account.to_string().to_snake_case()).as_str(),
+                proc_macro2::Span::call_site(),
+            );
+            quote! {
+                // Iterates over the updates to the account at `address`,
+                // using the client's commitment.
+                pub fn #method_name(
+                    &self,
+                    address: &Pubkey,
+                ) -> Result<solana_client_gen::subscription::AccountSubscription<#account>, ClientError> {
+                    solana_client_gen::subscription::account_subscribe(
+                        &self.url,
+                        address,
+                        self.opts.commitment,
+                    )
+                    .map_err(|e| ClientError::RawError(e.to_string()))
+                }
+            }
+        })
+        .collect();
+
+    // With the program's error code enum, custom errors returned by the
+    // program are decoded into it and described by its `message`.
+    let program_error = match &error_code {
+        None => quote! {
+            fn describe_rpc_error(err: &solana_client::client_error::ClientError) -> String {
+                err.to_string()
+            }
+        },
+        Some(error_code) => quote! {
+            impl ClientError {
+                // program_error returns the error returned by the on chain
+                // program, if it's one of its error codes.
+                pub fn program_error(&self) -> Option<#error_code> {
+                    use std::convert::TryFrom;
+                    self.error_code().and_then(|code| #error_code::try_from(code).ok())
+                }
+            }
+
+            fn describe_rpc_error(err: &solana_client::client_error::ClientError) -> String {
+                use std::convert::TryFrom;
+                match custom_error_code(err)
+                    .and_then(|code| #error_code::try_from(code).ok().map(|e| (code, e)))
+                {
+                    Some((code, e)) => format!(
+                        "{} ({:?}, custom program error: {:#x})",
+                        e.message(),
+                        e,
+                        code
+                    ),
+                    None => err.to_string(),
+                }
+            }
+        },
+    };
+
     let client = quote! {
         use super::*;
         use solana_client_gen::solana_sdk::instruction::InstructionError;
@@ -91,7 +177,7 @@ pub fn solana_client_gen(
         pub enum ClientError {
             #[error("Invalid keypair filename: {0}")]
             InvalidKeyPairFile(String),
-            #[error("Error invoking rpc: {0}")]
+            #[error("Error invoking rpc: {}", describe_rpc_error(.0))]
             RpcError(#[from] solana_client::client_error::ClientError),
             #[error("{0}")]
             RawError(String),
@@ -121,13 +207,16 @@ pub fn solana_client_gen(
             }
         }
 
+        #program_error
+
         // Client is the RPC client generated to talk to a program running
         // on a configured Solana cluster.
         pub struct Client {
             program_id: Pubkey,
             payer: Keypair,
-            rpc: RpcClient,
+            session: RpcSession,
             opts: RequestOptions,
+            url: String,
         }
 
         impl Client {
@@ -137,7 +226,7 @@ pub fn solana_client_gen(
                 url: &str,
                 given_opts: Option<RequestOptions>,
             ) -> Self {
-                let rpc = RpcClient::new(url.to_string());
+                let session = RpcSession::new(url);
                 let opts = match given_opts {
                     Some(opts) => opts,
                     // Use these default options if None are given.
@@ -152,11 +241,25 @@ pub fn solana_client_gen(
                 Self {
                     program_id,
                     payer,
-                    rpc,
+                    session,
                     opts,
+                    url: url.to_string(),
                 }
             }
 
+            // Like `new`, sending through `urls` in order and failing over
+            // between them. Panics if `urls` is empty.
+            pub fn with_endpoints(
+                program_id: Pubkey,
+                payer: Keypair,
+                urls: &[String],
+                config: SessionConfig,
+            ) -> Self {
+                let mut client = Self::new(program_id, payer, &urls[0], None);
+                client.session = RpcSession::with_endpoints(urls, config);
+                client
+            }
+
             pub fn from_keypair_file(program_id: Pubkey, filename: &str, url: &str) -> Result<Self, ClientError> {
                 let kp = solana_sdk::signature::read_keypair_file(filename)
                     .map_err(|_| ClientError::InvalidKeyPairFile(filename.to_string()))?;
@@ -170,7 +273,13 @@ pub fn solana_client_gen(
             }
 
             pub fn rpc(&self) -> &RpcClient {
-                &self.rpc
+                self.session.client()
+            }
+
+            // The session the client sends through, sharing its cached
+            // blockhash and retry settings.
+            pub fn session(&self) -> &RpcSession {
+                &self.session
             }
 
             pub fn payer(&self) -> &Keypair {
@@ -186,8 +295,12 @@ pub fn solana_client_gen(
                         }
 
             #client_methods
+
+            #(#subscribe_methods)*
         }
 
+        #typescript_const
+
         // Used for tests.
         impl solana_client_gen::prelude::ClientGen for Client {
             fn from_keypair_file(
@@ -213,6 +326,115 @@ pub fn solana_client_gen(
         }
     };
 
+    // The async client mirrors the blocking one. The RpcClient it wraps is
+    // still blocking, so every RPC runs on tokio's blocking thread pool.
+    let async_client = quote! {
+        use super::*;
+        use std::sync::Arc;
+        pub use super::client::ClientError;
+
+        // Runs a blocking RPC without blocking the async executor.
+        async fn blocking<F, R>(f: F) -> Result<R, ClientError>
+        where
+            F: FnOnce() -> Result<R, solana_client::client_error::ClientError> + Send + 'static,
+            R: Send + 'static,
+        {
+            solana_client_gen::prelude::tokio::task::spawn_blocking(f)
+                .await
+                .map_err(|e| ClientError::RawError(e.to_string()))?
+                .map_err(ClientError::RpcError)
+        }
+
+        // Client is the async version of `client::Client`, with the same
+        // methods returning futures.
+        pub struct Client {
+            program_id: Pubkey,
+            payer: Keypair,
+            rpc: Arc<RpcClient>,
+            opts: RequestOptions,
+        }
+
+        impl Client {
+            pub fn new(
+                program_id: Pubkey,
+                payer: Keypair,
+                url: &str,
+                given_opts: Option<RequestOptions>,
+            ) -> Self {
+                let rpc = Arc::new(RpcClient::new(url.to_string()));
+                let opts = match given_opts {
+                    Some(opts) => opts,
+                    // Same defaults as the blocking client.
+                    None => RequestOptions {
+                        commitment: CommitmentConfig::single(),
+                        tx: RpcSendTransactionConfig {
+                            skip_preflight: true,
+                            ..RpcSendTransactionConfig::default()
+                        },
+                    },
+                };
+                Self {
+                    program_id,
+                    payer,
+                    rpc,
+                    opts,
+                }
+            }
+
+            pub fn from_keypair_file(program_id: Pubkey, filename: &str, url: &str) -> Result<Self, ClientError> {
+                let kp = solana_sdk::signature::read_keypair_file(filename)
+                    .map_err(|_| ClientError::InvalidKeyPairFile(filename.to_string()))?;
+                Ok(Self::new(program_id, kp, url, None))
+            }
+
+            // Builder method to set the default options for each RPC request.
+            pub fn with_options(mut self, opts: RequestOptions) -> Self {
+                self.opts = opts;
+                self
+            }
+
+            // The underlying RpcClient is blocking. Move a clone of it into
+            // `spawn_blocking` to use it from async code.
+            pub fn rpc(&self) -> &Arc<RpcClient> {
+                &self.rpc
+            }
+
+            pub fn payer(&self) -> &Keypair {
+                &self.payer
+            }
+
+            pub fn program(&self) -> &Pubkey {
+                &self.program_id
+            }
+
+            pub fn options(&self) -> &RequestOptions {
+                &self.opts
+            }
+
+            async fn recent_blockhash(&self) -> Result<solana_sdk::hash::Hash, ClientError> {
+                let rpc = self.rpc.clone();
+                blocking(move || rpc.get_recent_blockhash().map(|(hash, _fee_calc)| hash)).await
+            }
+
+            async fn minimum_balance_for_rent_exemption(&self, data_len: usize) -> Result<u64, ClientError> {
+                let rpc = self.rpc.clone();
+                blocking(move || rpc.get_minimum_balance_for_rent_exemption(data_len)).await
+            }
+
+            async fn send_and_confirm(&self, tx: Transaction) -> Result<Signature, ClientError> {
+                let rpc = self.rpc.clone();
+                let commitment = self.opts.commitment;
+                let config = self.opts.tx;
+                blocking(move || {
+                    rpc.send_and_confirm_transaction_with_spinner_and_config(&tx, commitment, config)
+                })
+                .await
+            }
+
+            #async_client_methods
+        }
+    };
+
     // Generate the entire client module.
     let client_mod = quote! {
         #[cfg(feature = "client")]
@@ -221,6 +443,15 @@ pub fn solana_client_gen(
         }
     };
 
+    // Generated only for crates with an `async-client` feature, which must
+    // also enable `client` and `solana-client-gen/async-client`.
+    let async_client_mod = quote! {
+        #[cfg(feature = "async-client")]
+        pub mod async_client {
+            #async_client
+        }
+    };
+
     // Lastly, generate the api macro.
     //
     // The api_macro is a meta-macro emmited from this attribute macro.
@@ -260,6 +491,7 @@ pub fn solana_client_gen(
     // By default, just output the new modules directly.
     let default_output = quote! {
         #client_mod
+        #async_client_mod
         #new_instruction_mod
     };
 
@@ -280,10 +512,12 @@ pub fn solana_client_gen(
         #[macro_export]
         macro_rules! solana_client_gen_extension {
             ($($client_ext:tt)*) => {
+                #[cfg(feature = "client")]
                 pub mod client {
                     #client
                     $($client_ext)*
                 }
+                #async_client_mod
                 #new_instruction_mod
             }
         }
@@ -299,6 +533,7 @@ pub fn solana_client_gen(
 // each variant into several token streams:
 //
 // * Client RPC methods for each instruction variant.
+// * The same methods for the async client.
 // * Instruction methods for generating instances of solana_sdk::instruction::Instruction.
 // * Decode and dispatch tree, i.e., the code to execute on entry to the program.
 //
@@ -308,6 +543,7 @@ fn enum_to_methods(
     proc_macro2::TokenStream,
     proc_macro2::TokenStream,
     proc_macro2::TokenStream,
+    proc_macro2::TokenStream,
 ) {
     // When combined together, all the dispatch arms are used on
     // program entry, to define a `match` statement to interpret an
@@ -319,6 +555,7 @@ fn enum_to_methods(
     let instruction_enum_ident = instruction_enum.ident.clone();
 
     // Parse the enum and create methods.
+    let mut variant_async_client_methods = vec![];
     let (variant_client_methods, variant_instruction_methods): (
         Vec<proc_macro2::TokenStream>,
         Vec<proc_macro2::TokenStream>,
@@ -436,6 +673,10 @@ fn enum_to_methods(
                 format!("{}_with_signers", variant_name.to_string().to_snake_case()).as_str(),
                 proc_macro2::Span::call_site(),
             );
+            let simulate_method_name = proc_macro2::Ident::new(
+                format!("simulate_{}", variant_name.to_string().to_snake_case()).as_str(),
+                proc_macro2::Span::call_site(),
+            );
 
 
             // Create the optional method *if* the variant contains the
@@ -451,39 +692,39 @@ fn enum_to_methods(
             // The first will be created when #[create_account(SIZE)] is used with a
             // fixed size. The second will be used when #[create_account(..)] is used
             // with a `..`.
-            let create_account_client_method = {
-                let (create_account_client_method_name, create_account_client_method_name_with_signers) = {
-                    match account_data_size {
-                        CreateAccountDataSize::Fixed(_) => {
-                            (
-                                proc_macro2::Ident::new(
-                                    format!("create_account_and_{}", variant_name.to_string().to_snake_case()).as_str(),
-                                    proc_macro2::Span::call_site(),
-                                ),
-                                proc_macro2::Ident::new(
-                                    format!("create_account_and_{}_with_signers", variant_name.to_string().to_snake_case()).as_str(),
-                                    proc_macro2::Span::call_site(),
-                                )
+            let (create_account_client_method_name, create_account_client_method_name_with_signers) = {
+                match account_data_size {
+                    CreateAccountDataSize::Fixed(_) => {
+                        (
+                            proc_macro2::Ident::new(
+                                format!("create_account_and_{}", variant_name.to_string().to_snake_case()).as_str(),
+                                proc_macro2::Span::call_site(),
+                            ),
+                            proc_macro2::Ident::new(
+                                format!("create_account_and_{}_with_signers", variant_name.to_string().to_snake_case()).as_str(),
+                                proc_macro2::Span::call_site(),
                             )
-                        },
-                        CreateAccountDataSize::Dynamic => {
-                            (
-                                proc_macro2::Ident::new(
-                                    format!("create_account_with_size_and_{}", variant_name.to_string().to_snake_case()).as_str(),
-                                    proc_macro2::Span::call_site(),
-                                ),
-                                // Note: the dynamic with_signers variant is not used at the moment.
-                                proc_macro2::Ident::new(
-                                    format!("create_account_with_size_and_{}_with_signers", variant_name.to_string().to_snake_case()).as_str(),
-                                    proc_macro2::Span::call_site(),
-                                )
+                        )
+                    },
+                    CreateAccountDataSize::Dynamic => {
+                        (
+                            proc_macro2::Ident::new(
+                                format!("create_account_with_size_and_{}", variant_name.to_string().to_snake_case()).as_str(),
+                                proc_macro2::Span::call_site(),
+                            ),
+                            // Note: the dynamic with_signers variant is not used at the moment.
+                            proc_macro2::Ident::new(
+                                format!("create_account_with_size_and_{}_with_signers", variant_name.to_string().to_snake_case()).as_str(),
+                                proc_macro2::Span::call_site(),
                             )
-                        },
-                    }
-                };
+                        )
+                    },
+                }
+            };
+            let create_account_client_method = {
                 match needs_account_creation {
                     false => quote!{},
-                    true => match account_data_size {
+                    true => match &account_data_size {
                         CreateAccountDataSize::Fixed(account_data_size) => quote!{
                             // Inserts a create account instruction immediately before this
                             // instruction variant, so that a transaction executes twice.
@@ -530,8 +771,8 @@ fn enum_to_methods(
                                 // Instruction: create the new account system instruction.
                                 let create_account_instr = {
                                     let lamports = self
-                                        .rpc()
-                                        .get_minimum_balance_for_rent_exemption(#account_data_size as usize)
+                                        .session
+                                        .retry(|rpc| rpc.get_minimum_balance_for_rent_exemption(#account_data_size as usize))
                                         .map_err(ClientError::RpcError)?;
                                     system_instruction::create_account(
                                         &self.payer().pubkey(),    // The from account on the tx.
@@ -549,26 +790,14 @@ fn enum_to_methods(
                                     #method_arg_idents
                                 );
 
-                                // Transaction: create the transaction with the combined instructions.
-                                let tx = {
-                                    let instructions = vec![create_account_instr, variant_instr];
-
-                                    let (recent_hash, _fee_calc) = self
-                                        .rpc()
-                                        .get_recent_blockhash()
-                                        .map_err(|e| ClientError::RawError(e.to_string()))?;
-                                    Transaction::new_signed_with_payer(
+                                // Execute the transaction with the combined instructions.
+                                let instructions = vec![create_account_instr, variant_instr];
+                                self
+                                    .session
+                                    .send_and_confirm_with_config(
                                         &instructions,
-                                        Some(&self.payer().pubkey()),
+                                        &self.payer().pubkey(),
                                         signers,
-                                        recent_hash,
-                                    )
-                                };
-                                // Execute the transaction.
-                                self
-                                    .rpc
-                                    .send_and_confirm_transaction_with_spinner_and_config(
-                                        &tx,
                                         self.opts.commitment,
                                         self.opts.tx,
                                     )
@@ -586,8 +815,8 @@ fn enum_to_methods(
                                 // Instruction: create the new account system instruction.
                                 let create_account_instr = {
                                     let lamports = self
-                                        .rpc()
-                                        .get_minimum_balance_for_rent_exemption(account_data_size)
+                                        .session
+                                        .retry(|rpc| rpc.get_minimum_balance_for_rent_exemption(account_data_size))
                                         .map_err(ClientError::RpcError)?;
                                     system_instruction::create_account(
                                         &self.payer().pubkey(),    // The from account on the tx.
@@ -608,30 +837,15 @@ fn enum_to_methods(
                                     #method_arg_idents,
                                 );
 
-                                // Transaction: create the transaction with the combined instructions.
-                                let tx = {
-                                    let instructions = vec![create_account_instr, variant_instr];
-
-                                    let (recent_hash, _fee_calc) = self
-                                        .rpc()
-                                        .get_recent_blockhash()
-                                        .map_err(|e| ClientError::RawError(e.to_string()))?;
-
-                                    let signers = vec![self.payer(), &new_account];
-
-                                    Transaction::new_signed_with_payer(
+                                // Execute the transaction with the combined instructions.
+                                let instructions = vec![create_account_instr, variant_instr];
+                                let signers = vec![self.payer(), &new_account];
+                                self
+                                    .session
+                                    .send_and_confirm_with_config(
                                         &instructions,
-                                        Some(&self.payer().pubkey()),
+                                        &self.payer().pubkey(),
                                         &signers,
-                                        recent_hash,
-                                    )
-                                };
-
-                                // Execute the transaction.
-                                self
-                                    .rpc
-                                    .send_and_confirm_transaction_with_spinner_and_config(
-                                        &tx,
                                         self.opts.commitment,
                                         self.opts.tx,
                                     )
@@ -662,29 +876,172 @@ fn enum_to_methods(
                             #method_arg_idents
                         ),
                     ];
-                    let (recent_hash, _fee_calc) = self
-                        .rpc
-                        .get_recent_blockhash()
-                        .map_err(ClientError::RpcError)?;
-                    let txn = Transaction::new_signed_with_payer(
-                        &instructions,
-                        Some(&self.payer.pubkey()),
-                        signers,
-                        recent_hash,
-                    );
                     self
-                        .rpc
-                        .send_and_confirm_transaction_with_spinner_and_config(
-                            &txn,
+                        .session
+                        .send_and_confirm_with_config(
+                            &instructions,
+                            &self.payer.pubkey(),
+                            signers,
                             self.opts.commitment,
                             self.opts.tx,
                         )
                         .map_err(ClientError::RpcError)
                 }
+                // Simulates the instruction, paid for by the client's payer,
+                // without signing it.
+                pub fn #simulate_method_name(&self, accounts: &[AccountMeta], #method_args) -> Result<Simulation, ClientError> {
+                    let instructions = vec![
+                        super::instruction::#method_name(
+                            self.program_id,
+                            accounts,
+                            #method_arg_idents
+                        ),
+                    ];
+                    self
+                        .session
+                        .simulate(&instructions, &self.payer.pubkey())
+                        .map_err(ClientError::RpcError)
+                }
 
                 #create_account_client_method
             };
 
+            // The async version of the methods above. Arguments are the same,
+            // but only the RPCs themselves leave the calling task.
+            let async_create_account_client_method = match needs_account_creation {
+                false => quote!{},
+                true => match &account_data_size {
+                    CreateAccountDataSize::Fixed(account_data_size) => quote!{
+                        pub async fn #create_account_client_method_name(
+                            &self,
+                            accounts: &[AccountMeta],
+                            #method_args
+                        ) -> Result<(Signature, Keypair), ClientError> {
+                            let new_account = Keypair::generate(&mut OsRng);
+
+                            let mut new_accounts = accounts.to_vec();
+                            new_accounts.insert(0, AccountMeta::new(new_account.pubkey(), false));
+
+                            let new_account_cpy = Keypair::from_bytes(&new_account.to_bytes()).unwrap();
+                            let signers = vec![self.payer(), &new_account];
+                            self.#create_account_client_method_name_with_signers(
+                                new_account_cpy,
+                                &signers,
+                                &new_accounts,
+                                #method_arg_idents
+                            ).await
+                        }
+                        pub async fn #create_account_client_method_name_with_signers<T: Signers>(
+                            &self,
+                            new_account: Keypair,
+                            signers: &T,
+                            accounts: &[AccountMeta],
+                            #method_args
+                        ) -> Result<(Signature, Keypair), ClientError> {
+                            let lamports = self
+                                .minimum_balance_for_rent_exemption(#account_data_size as usize)
+                                .await?;
+                            let create_account_instr = system_instruction::create_account(
+                                &self.payer().pubkey(),
+                                &new_account.pubkey(),
+                                lamports,
+                                #account_data_size,
+                                self.program(),
+                            );
+                            let variant_instr = super::instruction::#method_name(
+                                self.program_id,
+                                &accounts,
+                                #method_arg_idents
+                            );
+                            let tx = Transaction::new_signed_with_payer(
+                                &[create_account_instr, variant_instr],
+                                Some(&self.payer().pubkey()),
+                                signers,
+                                self.recent_blockhash().await?,
+                            );
+                            self.send_and_confirm(tx).await.map(|sig| (sig, new_account))
+                        }
+                    },
+                    CreateAccountDataSize::Dynamic => quote! {
+                        pub async fn #create_account_client_method_name(&self, account_data_size: u64, accounts: &[AccountMeta], #method_args) -> Result<(Signature, Keypair), ClientError> {
+                            let new_account = Keypair::generate(&mut OsRng);
+
+                            let lamports = self
+                                .minimum_balance_for_rent_exemption(account_data_size as usize)
+                                .await?;
+                            let create_account_instr = system_instruction::create_account(
+                                &self.payer().pubkey(),
+                                &new_account.pubkey(),
+                                lamports,
+                                account_data_size,
+                                self.program(),
+                            );
+
+                            let mut new_accounts = accounts.to_vec();
+                            new_accounts.insert(0, AccountMeta::new(new_account.pubkey(), false));
+
+                            let variant_instr = super::instruction::#method_name(
+                                self.program_id,
+                                &new_accounts,
+                                #method_arg_idents,
+                            );
+                            let signers = vec![self.payer(), &new_account];
+                            let tx = Transaction::new_signed_with_payer(
+                                &[create_account_instr, variant_instr],
+                                Some(&self.payer().pubkey()),
+                                &signers,
+                                self.recent_blockhash().await?,
+                            );
+                            self.send_and_confirm(tx).await.map(|sig| (sig, new_account))
+                        }
+                    },
+                },
+            };
+            variant_async_client_methods.push(quote! {
+                // Invokes the rpc with the client's payer as the only signer.
+                pub async fn #method_name(&self, accounts: &[AccountMeta], #method_args) -> Result<Signature, ClientError> {
+                    self.#method_name_with_signers(&[&self.payer], accounts, #method_arg_idents).await
+                }
+                // Invokes the rpc with the given signers, which should include
+                // the client's payer.
+                pub async fn #method_name_with_signers<T: Signers>(&self, signers: &T, accounts: &[AccountMeta], #method_args) -> Result<Signature, ClientError> {
+                    let instructions = vec![
+                        super::instruction::#method_name(
+                            self.program_id,
+                            accounts,
+                            #method_arg_idents
+                        ),
+                    ];
+                    let txn = Transaction::new_signed_with_payer(
+                        &instructions,
+                        Some(&self.payer.pubkey()),
+                        signers,
+                        self.recent_blockhash().await?,
+                    );
+                    self.send_and_confirm(txn).await
+                }
+                // Simulates the instruction, paid for by the client's payer,
+                // without signing it.
+                pub async fn #simulate_method_name(&self, accounts: &[AccountMeta], #method_args) -> Result<Simulation, ClientError> {
+                    let instructions = vec![
+                        super::instruction::#method_name(
+                            self.program_id,
+                            accounts,
+                            #method_arg_idents
+                        ),
+                    ];
+                    let mut txn = Transaction::new_with_payer(&instructions, Some(&self.payer.pubkey()));
+                    txn.message.recent_blockhash = self.recent_blockhash().await?;
+                    let rpc = self.rpc.clone();
+                    let commitment = self.opts.commitment;
+                    blocking(move || simulate_transaction(&rpc, &txn, false, commitment))
+                        .await
+                        .map(|result| result.value.into())
+                }
+
+                #async_create_account_client_method
+            });
+
             // Save the single dispatch arm representing this enum variant.
             dispatch_arms.push(quote! {
                 #instruction_enum => #method_name(accounts, #method_args)
@@ -698,6 +1055,9 @@ fn enum_to_methods(
     let client_methods = quote! {
         #(#variant_client_methods)*
     };
+    let async_client_methods = quote! {
+        #(#variant_async_client_methods)*
+    };
     // The token stream of all generated `solana_sdk::instruction::Instruction`
     // generation method.
     let instruction_methods = quote! {
@@ -716,15 +1076,59 @@ fn enum_to_methods(
 
     (
         client_methods,
+        async_client_methods,
         instruction_methods,
         decode_and_dispatch_tree,
     )
 }
 
-fn parse_client_ext_arg(args: proc_macro::TokenStream) -> bool {
-    match args.to_string().as_ref() {
-        "ext" => true,
-        _ => false,
+struct MacroArgs {
+    needs_client_ext: bool,
+    needs_typescript: bool,
+    error_code: Option<syn::Ident>,
+    subscribe_accounts: Vec<syn::Ident>,
+}
+
+// Returns whether the `ext` and `typescript` markers are given, the type in
+// `error(...)` and the account types listed in `accounts(...)`.
+fn parse_args(args: syn::AttributeArgs) -> MacroArgs {
+    let mut needs_client_ext = false;
+    let mut needs_typescript = false;
+    let mut error_code = None;
+    let mut accounts = vec![];
+    for arg in args {
+        match arg {
+            syn::NestedMeta::Meta(syn::Meta::Word(ident)) if ident == "ext" => {
+                needs_client_ext = true;
+            }
+            syn::NestedMeta::Meta(syn::Meta::Word(ident)) if ident == "typescript" => {
+                needs_typescript = true;
+            }
+            syn::NestedMeta::Meta(syn::Meta::List(list)) if list.ident == "error" => {
+                let mut types = list.nested.into_iter();
+                match (types.next(), types.next()) {
+                    (Some(syn::NestedMeta::Meta(syn::Meta::Word(ident))), None) => {
+                        error_code = Some(ident)
+                    }
+                    _ => panic!("error must be a single type name"),
+                }
+            }
+            syn::NestedMeta::Meta(syn::Meta::List(list)) if list.ident == "accounts" => {
+                for account in list.nested {
+                    match account {
+                        syn::NestedMeta::Meta(syn::Meta::Word(ident)) => accounts.push(ident),
+                        _ => panic!("accounts must be a list of type names"),
+                    }
+                }
+            }
+            _ => panic!("expected `ext`, `typescript`, `error(...)` or `accounts(...)`"),
+        }
+    }
+    MacroArgs {
+        needs_client_ext,
+        needs_typescript,
+        error_code,
+        subscribe_accounts: accounts,
     }
 }
 
@@ -0,0 +1,245 @@
+// Generates TypeScript instruction builders mirroring the Rust instruction
+// methods, so the JS client encodes instructions from the same definitions.
+//
+// Instructions are serialized with bincode's default configuration: the
+// variant index as a little endian u32, then each field in order, with u64
+// lengths for sequences and a one byte tag for options. Fields of types
+// this module doesn't know how to encode, e.g., structs defined by the
+// program, are taken as a `Buffer` holding their bincode encoding.
+
+use heck::MixedCase;
+
+const PRELUDE: &str = r#"// Generated by solana-client-gen. Do not edit.
+
+import BN from 'bn.js';
+import { AccountMeta, PublicKey, TransactionInstruction } from '@solana/web3.js';
+
+class Writer {
+  private chunks: Buffer[] = [];
+
+  u8(v: number) {
+    const b = Buffer.alloc(1);
+    b.writeUInt8(v, 0);
+    this.chunks.push(b);
+  }
+  i8(v: number) {
+    const b = Buffer.alloc(1);
+    b.writeInt8(v, 0);
+    this.chunks.push(b);
+  }
+  u16(v: number) {
+    const b = Buffer.alloc(2);
+    b.writeUInt16LE(v, 0);
+    this.chunks.push(b);
+  }
+  i16(v: number) {
+    const b = Buffer.alloc(2);
+    b.writeInt16LE(v, 0);
+    this.chunks.push(b);
+  }
+  u32(v: number) {
+    const b = Buffer.alloc(4);
+    b.writeUInt32LE(v, 0);
+    this.chunks.push(b);
+  }
+  i32(v: number) {
+    const b = Buffer.alloc(4);
+    b.writeInt32LE(v, 0);
+    this.chunks.push(b);
+  }
+  bn(v: BN, bytes: number, signed: boolean) {
+    const n = signed ? v.toTwos(bytes * 8) : v;
+    this.chunks.push(n.toArrayLike(Buffer, 'le', bytes));
+  }
+  bool(v: boolean) {
+    this.u8(v ? 1 : 0);
+  }
+  publicKey(v: PublicKey) {
+    this.chunks.push(v.toBuffer());
+  }
+  bytes(v: Buffer) {
+    this.chunks.push(v);
+  }
+  len(n: number) {
+    this.bn(new BN(n), 8, false);
+  }
+  string(v: string) {
+    const b = Buffer.from(v, 'utf8');
+    this.len(b.length);
+    this.chunks.push(b);
+  }
+  finish(): Buffer {
+    return Buffer.concat(this.chunks);
+  }
+}
+"#;
+
+// Returns the TypeScript module with a builder for each variant of
+// `instruction_enum`.
+pub fn instruction_builders(instruction_enum: &syn::ItemEnum) -> String {
+    let mut ts = PRELUDE.to_string();
+    for (index, variant) in instruction_enum.variants.iter().enumerate() {
+        ts.push('\n');
+        ts.push_str(&variant_builder(index, variant));
+    }
+    ts
+}
+
+fn variant_builder(index: usize, variant: &syn::Variant) -> String {
+    let fields: Vec<(String, &syn::Type)> = match &variant.fields {
+        syn::Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|field| {
+                let name = field.ident.as_ref().expect("field identifier not found");
+                (name.to_string().to_mixed_case(), &field.ty)
+            })
+            .collect(),
+        syn::Fields::Unit => vec![],
+        syn::Fields::Unnamed(_fields) => panic!("Unamed variants not supported, yet"),
+    };
+
+    let mut ts = doc_comment(&variant.attrs);
+    let mut params = vec![
+        "programId: PublicKey".to_string(),
+        "keys: AccountMeta[]".to_string(),
+    ];
+    params.extend(
+        fields
+            .iter()
+            .map(|(name, ty)| format!("{}: {}", name, ts_type(ty))),
+    );
+    ts.push_str(&format!(
+        "export function {}(\n  {},\n): TransactionInstruction {{\n",
+        variant.ident.to_string().to_mixed_case(),
+        params.join(",\n  "),
+    ));
+    ts.push_str("  const w = new Writer();\n");
+    ts.push_str(&format!("  w.u32({});\n", index));
+    for (name, ty) in &fields {
+        ts.push_str(&encode(ty, name, 1));
+    }
+    ts.push_str("  return new TransactionInstruction({ programId, keys, data: w.finish() });\n}\n");
+    ts
+}
+
+// The variant's doc comment as a JSDoc comment.
+fn doc_comment(attrs: &[syn::Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident("doc"))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(syn::Meta::NameValue(syn::MetaNameValue {
+                lit: syn::Lit::Str(doc),
+                ..
+            })) => Some(doc.value()),
+            _ => None,
+        })
+        .collect();
+    if lines.is_empty() {
+        return String::new();
+    }
+    let mut ts = "/**\n".to_string();
+    for line in lines {
+        ts.push_str(format!(" *{}", line).trim_end());
+        ts.push('\n');
+    }
+    ts.push_str(" */\n");
+    ts
+}
+
+// The type of the rust type `ty` and its only generic argument, if any.
+fn type_name(ty: &syn::Type) -> Option<(String, Option<&syn::Type>)> {
+    let path = match ty {
+        syn::Type::Path(ty) => &ty.path,
+        _ => return None,
+    };
+    let segment = path.segments.iter().last()?;
+    let arg = match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.iter().next() {
+            Some(syn::GenericArgument::Type(ty)) => Some(ty),
+            _ => None,
+        },
+        _ => None,
+    };
+    Some((segment.ident.to_string(), arg))
+}
+
+fn ts_type(ty: &syn::Type) -> String {
+    match type_name(ty) {
+        Some((name, arg)) => match (name.as_str(), arg) {
+            ("u8", _) | ("i8", _) | ("u16", _) | ("i16", _) | ("u32", _) | ("i32", _) => {
+                "number".to_string()
+            }
+            ("u64", _) | ("i64", _) | ("u128", _) | ("i128", _) => "BN".to_string(),
+            ("bool", _) => "boolean".to_string(),
+            ("String", _) => "string".to_string(),
+            ("Pubkey", _) => "PublicKey".to_string(),
+            ("Vec", Some(arg)) if is_u8(arg) => "Buffer".to_string(),
+            ("Vec", Some(arg)) => format!("{}[]", ts_type(arg)),
+            ("Option", Some(arg)) => format!("{} | null", ts_type(arg)),
+            _ => "Buffer".to_string(),
+        },
+        None => "Buffer".to_string(),
+    }
+}
+
+fn is_u8(ty: &syn::Type) -> bool {
+    match type_name(ty) {
+        Some((name, _)) => name == "u8",
+        None => false,
+    }
+}
+
+// Statements writing `value`, of rust type `ty`, to the writer `w`. `depth`
+// is the nesting level, for indentation and naming loop variables.
+fn encode(ty: &syn::Type, value: &str, depth: usize) -> String {
+    let indent = "  ".repeat(depth);
+    let (name, arg) = match type_name(ty) {
+        Some(name) => name,
+        None => return format!("{}w.bytes({});\n", indent, value),
+    };
+    match (name.as_str(), arg) {
+        ("u8", _) | ("i8", _) | ("u16", _) | ("i16", _) | ("u32", _) | ("i32", _) => {
+            format!("{}w.{}({});\n", indent, name, value)
+        }
+        ("u64", _) | ("i64", _) | ("u128", _) | ("i128", _) => format!(
+            "{}w.bn({}, {}, {});\n",
+            indent,
+            value,
+            name[1..].parse::<usize>().unwrap() / 8,
+            name.starts_with('i'),
+        ),
+        ("bool", _) => format!("{}w.bool({});\n", indent, value),
+        ("String", _) => format!("{}w.string({});\n", indent, value),
+        ("Pubkey", _) => format!("{}w.publicKey({});\n", indent, value),
+        ("Vec", Some(arg)) if is_u8(arg) => format!(
+            "{}w.len({}.length);\n{}w.bytes({});\n",
+            indent, value, indent, value
+        ),
+        ("Vec", Some(arg)) => {
+            let item = format!("item{}", depth);
+            format!(
+                "{}w.len({}.length);\n{}for (const {} of {}) {{\n{}{}}}\n",
+                indent,
+                value,
+                indent,
+                item,
+                value,
+                encode(arg, &item, depth + 1),
+                indent,
+            )
+        }
+        ("Option", Some(arg)) => format!(
+            "{}if ({} === null) {{\n{}  w.u8(0);\n{}}} else {{\n{}  w.u8(1);\n{}{}}}\n",
+            indent,
+            value,
+            indent,
+            indent,
+            indent,
+            encode(arg, value, depth + 1),
+            indent,
+        ),
+        _ => format!("{}w.bytes({});\n", indent, value),
+    }
+}
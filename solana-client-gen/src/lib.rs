@@ -128,6 +128,126 @@
 //! }
 //! ```
 //!
+//! # Account subscriptions.
+//!
+//! Account types listed in the macro's `accounts` argument get a
+//! `subscribe_<account>` method on the client, which opens a websocket
+//! subscription to an account of that type and iterates over its updates,
+//! already unpacked. The types must implement `serum_common::pack::Pack`
+//! and be in scope where the client is generated.
+//!
+//! ```
+//! #[cfg_attr(feature = "client", solana_client_gen(accounts(Member)))]
+//! pub mod instruction {
+//!   ...
+//! }
+//!
+//! for member in client.subscribe_member(&member_address)? {
+//!   println!("{:?}", member?);
+//! }
+//! ```
+//!
+//! # TypeScript bindings.
+//!
+//! With the `typescript` argument, the client module also gets a
+//! `TYPESCRIPT` constant holding a TypeScript module with an instruction
+//! builder for each variant, using `@solana/web3.js` and `bn.js`. The
+//! builders encode their arguments the same way as the generated
+//! `instruction` module. Arguments of types defined by the program are taken
+//! as a `Buffer` of their bincode encoding. Account decoders aren't
+//! generated, since the macro only sees the names of account types.
+//!
+//! ```
+//! #[cfg_attr(feature = "client", solana_client_gen(typescript))]
+//! pub mod instruction {
+//!   ...
+//! }
+//!
+//! std::fs::write("instruction.ts", my_crate::client::TYPESCRIPT)?;
+//! ```
+//!
+//! # Retries.
+//!
+//! The blocking client sends through a `serum_common` `RpcSession`, which
+//! reuses a recent blockhash across transactions, retries failed RPCs with
+//! backoff, and signs and resends transactions that expire unconfirmed.
+//! Use `client.session()` to send other instructions the same way.
+//! `Client::with_endpoints` takes several RPC URLs of the same cluster and
+//! fails over between them when one errors.
+//!
+//! # Simulation.
+//!
+//! Each instruction also gets a `simulate_*` method, running it through
+//! `simulateTransaction` without signing it. The returned `Simulation` has
+//! the error the transaction would fail with, the program logs parsed into
+//! `LogEvent`s and the compute units consumed:
+//!
+//! ```
+//! let simulation = client.simulate_add(accounts, 1, 2)?;
+//! if let Some(err) = simulation.program_error::<MyErrorCode>() {
+//!   println!("{}", err.message());
+//! }
+//! println!("{:?} compute units", simulation.units_consumed());
+//! ```
+//!
+//! # Program errors.
+//!
+//! Given the program's error code enum with `error(...)`, the generated
+//! `ClientError` decodes custom errors returned by the program. The enum
+//! must implement `TryFrom<u32>`, e.g., by deriving num_enum's
+//! `TryFromPrimitive`, `Debug`, and have a `message(&self) -> &'static str`
+//! describing each error, which is used when displaying the `ClientError`.
+//!
+//! ```
+//! #[cfg_attr(feature = "client", solana_client_gen(error(MyErrorCode)))]
+//! pub mod instruction {
+//!   ...
+//! }
+//!
+//! match client.add(accounts, 1, 2) {
+//!   Err(e) if e.program_error() == Some(MyErrorCode::Overflow) => ...,
+//!   ...
+//! }
+//! ```
+//!
+//! The error code is attributed to the program regardless of which
+//! instruction in the transaction failed.
+//!
+//! # WebAssembly.
+//!
+//! The RPC client doesn't compile to `wasm32-unknown-unknown`. With the
+//! `wasm` feature, only the instruction module is generated, so frontends
+//! can build instructions with the program's own serialization via
+//! wasm-bindgen. The interface crate needs its own `wasm` feature, enabling
+//! `solana-client-gen/wasm`, and must apply the macro under it:
+//!
+//! ```
+//! #[cfg_attr(any(feature = "client", feature = "wasm"), solana_client_gen)]
+//! pub mod instruction {
+//!   ...
+//! }
+//! ```
+//!
+//! # Async client.
+//!
+//! With the `async-client` feature, an `async_client::Client` is generated
+//! alongside the blocking one, with the same constructors and methods, each
+//! returning a future. The crate defining the interface needs its own
+//! `async-client` feature, enabling `client` and
+//! `solana-client-gen/async-client`:
+//!
+//! ```
+//! let client = my_crate::async_client::Client::from_keypair_file(
+//!   program_id,
+//!   payer_filepath,
+//!   cluster,
+//! )?;
+//! client.add(accounts, 1, 2).await?;
+//! ```
+//!
+//! Solana's RpcClient is blocking, so the generated client runs each RPC on
+//! tokio's blocking thread pool and must be used within a tokio runtime.
+//!
 //! # Serialization
 //!
 //! Instructions used with this macro must implement the
@@ -156,11 +276,17 @@ pub mod prelude {
 
     #[cfg(feature = "client")]
     pub use anyhow;
-    #[cfg(feature = "client")]
+    #[cfg(any(feature = "client", feature = "wasm"))]
     pub use codegen::solana_client_gen;
     #[cfg(feature = "client")]
     pub use rand::rngs::OsRng;
     #[cfg(feature = "client")]
+    pub use serum_common::client::rpc::{custom_error_code, simulate_transaction};
+    #[cfg(feature = "client")]
+    pub use serum_common::client::session::{RpcSession, SessionConfig};
+    #[cfg(feature = "client")]
+    pub use serum_common::client::simulation::{LogEvent, Simulation};
+    #[cfg(feature = "client")]
     pub use solana_client;
     #[cfg(feature = "client")]
     pub use solana_client::rpc_client::RpcClient;
@@ -180,6 +306,8 @@ pub mod prelude {
     pub use solana_sdk::transaction::Transaction;
     #[cfg(feature = "client")]
     pub use thiserror::Error;
+    #[cfg(feature = "async-client")]
+    pub use tokio;
 
     #[cfg(feature = "client")]
     #[derive(Debug)]
@@ -199,6 +327,9 @@ pub mod prelude {
     }
 }
 
+#[cfg(feature = "client")]
+pub mod subscription;
+
 // Re-export.
 #[cfg(feature = "client")]
 pub use solana_client;
@@ -0,0 +1,116 @@
+//! Account subscriptions over the cluster's websocket, yielding each update
+//! already unpacked. Generated clients expose these as `subscribe_<account>`
+//! methods for the account types given to the macro.
+
+use std::marker::PhantomData;
+
+use anyhow::{anyhow, Result};
+use serum_common::pack::Pack;
+use solana_sdk::commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use tungstenite::client::AutoStream;
+use tungstenite::{Message, WebSocket};
+use url::Url;
+
+/// Iterator over the updates to one account, ending when the websocket
+/// closes.
+pub struct AccountSubscription<T> {
+    socket: WebSocket<AutoStream>,
+    closed: bool,
+    _account: PhantomData<T>,
+}
+
+/// Subscribes to the account at `address` through the websocket of the RPC
+/// node at `rpc_url`.
+pub fn account_subscribe<T: Pack>(
+    rpc_url: &str,
+    address: &Pubkey,
+    commitment: CommitmentConfig,
+) -> Result<AccountSubscription<T>> {
+    let (mut socket, _) = tungstenite::connect(ws_url(rpc_url)?)?;
+    let mut config = serde_json::to_value(&commitment)?;
+    config["encoding"] = "base64".into();
+    let request = serde_json::json!({
+        "jsonrpc": "2.0",
+        "id": 1,
+        "method": "accountSubscribe",
+        "params": [address.to_string(), config],
+    });
+    socket.write_message(Message::Text(request.to_string()))?;
+    Ok(AccountSubscription {
+        socket,
+        closed: false,
+        _account: PhantomData,
+    })
+}
+
+impl<T: Pack> AccountSubscription<T> {
+    pub fn close(mut self) -> Result<()> {
+        self.socket.close(None)?;
+        Ok(())
+    }
+
+    // Reads up to the next account notification, returning None once the
+    // socket is closed.
+    fn next_update(&mut self) -> Result<Option<T>> {
+        loop {
+            let text = match self.socket.read_message()? {
+                Message::Text(text) => text,
+                Message::Ping(data) => {
+                    self.socket.write_message(Message::Pong(data))?;
+                    continue;
+                }
+                Message::Close(_) => return Ok(None),
+                _ => continue,
+            };
+            let message: serde_json::Value = serde_json::from_str(&text)?;
+            if let Some(err) = message.get("error") {
+                return Err(anyhow!("accountSubscribe failed: {}", err));
+            }
+            if message["method"] != "accountNotification" {
+                continue;
+            }
+            let data = message["params"]["result"]["value"]["data"][0]
+                .as_str()
+                .ok_or_else(|| anyhow!("unexpected notification: {}", text))?;
+            let account = T::unpack(&base64::decode(data)?)
+                .map_err(|e| anyhow!("failed to unpack account: {:?}", e))?;
+            return Ok(Some(account));
+        }
+    }
+}
+
+impl<T: Pack> Iterator for AccountSubscription<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        if self.closed {
+            return None;
+        }
+        match self.next_update() {
+            Ok(Some(account)) => Some(Ok(account)),
+            Ok(None) => {
+                self.closed = true;
+                None
+            }
+            // The socket can't be read after an error.
+            Err(err) => {
+                self.closed = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+// The RPC node serves websockets on the port after its HTTP port.
+fn ws_url(rpc_url: &str) -> Result<Url> {
+    let mut url = Url::parse(rpc_url)?;
+    let scheme = if url.scheme() == "https" { "wss" } else { "ws" };
+    url.set_scheme(scheme)
+        .map_err(|_| anyhow!("can't make a websocket URL of {}", rpc_url))?;
+    if let Some(port) = url.port() {
+        url.set_port(Some(port + 1))
+            .map_err(|_| anyhow!("can't make a websocket URL of {}", rpc_url))?;
+    }
+    Ok(url)
+}
@@ -0,0 +1,219 @@
+//! Fixtures for testing against the dex, registry and lockup programs in
+//! `solana-program-test`, so integration tests don't need a running cluster
+//! or their own account setup.
+//!
+//! The programs are loaded from their BPF builds. Build them with
+//! `./do.sh build dex` and `make -C registry build` / `make -C lockup build`,
+//! then point `BPF_OUT_DIR` at the directory holding `serum_dex.so`,
+//! `serum_registry_program.so` and `serum_lockup_program.so`.
+//!
+//! ```ignore
+//! let mut env = TestEnv::start().await;
+//! let coin_mint = env.create_mint(6).await?;
+//! let pc_mint = env.create_mint(6).await?;
+//! let market = env.market(&coin_mint, &pc_mint).build().await?;
+//! let trader = env
+//!     .wallet()
+//!     .tokens(&pc_mint, 1_000_000_000)
+//!     .build()
+//!     .await?;
+//! ```
+
+#![cfg_attr(feature = "strict", deny(warnings))]
+
+use anyhow::{format_err, Result};
+use rand::rngs::OsRng;
+use solana_program_test::{find_file, read_file, BanksClient, ProgramTest};
+use solana_sdk::account::Account;
+use solana_sdk::bpf_loader_deprecated;
+use solana_sdk::instruction::Instruction;
+use solana_sdk::program_pack::Pack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::rent::Rent;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+use solana_sdk::transaction::Transaction;
+use spl_token::instruction as token_instruction;
+
+pub mod market;
+pub mod registrar;
+pub mod vesting;
+pub mod wallet;
+
+pub use market::{Market, MarketBuilder};
+pub use registrar::{Registrar, RegistrarBuilder};
+pub use vesting::{Safe, SafeBuilder, Vesting, VestingBuilder};
+pub use wallet::{Wallet, WalletBuilder};
+
+/// Addresses the programs are loaded at.
+#[derive(Debug, Clone, Copy)]
+pub struct ProgramIds {
+    pub dex: Pubkey,
+    pub registry: Pubkey,
+    pub lockup: Pubkey,
+}
+
+/// A bank with every program loaded, and the payer funding it all. The
+/// payer is also the mint authority of mints made with `create_mint`.
+pub struct TestEnv {
+    pub banks_client: BanksClient,
+    pub payer: Keypair,
+    pub programs: ProgramIds,
+}
+
+impl TestEnv {
+    pub async fn start() -> Self {
+        let programs = ProgramIds {
+            dex: Keypair::generate(&mut OsRng).pubkey(),
+            registry: Keypair::generate(&mut OsRng).pubkey(),
+            lockup: Keypair::generate(&mut OsRng).pubkey(),
+        };
+        let mut program_test = ProgramTest::default();
+        program_test.add_program("serum_registry_program", programs.registry, None);
+        program_test.add_program("serum_lockup_program", programs.lockup, None);
+        // The dex uses the deprecated entrypoint, so it must be owned by the
+        // deprecated loader, which `add_program` doesn't use.
+        let dex = read_file(find_file("serum_dex.so").expect("serum_dex.so not found"));
+        program_test.add_account(
+            programs.dex,
+            Account {
+                lamports: Rent::default().minimum_balance(dex.len()).max(1),
+                data: dex,
+                owner: bpf_loader_deprecated::id(),
+                executable: true,
+                rent_epoch: 0,
+            },
+        );
+        let (banks_client, payer, _recent_blockhash) = program_test.start().await;
+        TestEnv {
+            banks_client,
+            payer,
+            programs,
+        }
+    }
+
+    /// Sends `instructions` in one transaction paid for by the payer, who
+    /// signs along with `signers`.
+    pub async fn process(
+        &mut self,
+        instructions: &[Instruction],
+        signers: &[&Keypair],
+    ) -> Result<()> {
+        let recent_blockhash = self.banks_client.get_recent_blockhash().await?;
+        let mut all_signers = vec![&self.payer];
+        all_signers.extend_from_slice(signers);
+        let txn = Transaction::new_signed_with_payer(
+            instructions,
+            Some(&self.payer.pubkey()),
+            &all_signers,
+            recent_blockhash,
+        );
+        self.banks_client.process_transaction(txn).await?;
+        Ok(())
+    }
+
+    /// Returns an instruction creating a rent exempt account of `len` bytes
+    /// at `address`, owned by `owner`.
+    pub async fn create_account_instruction(
+        &mut self,
+        address: &Pubkey,
+        len: usize,
+        owner: &Pubkey,
+    ) -> Result<Instruction> {
+        let rent = self.banks_client.get_rent().await?;
+        Ok(system_instruction::create_account(
+            &self.payer.pubkey(),
+            address,
+            rent.minimum_balance(len),
+            len as u64,
+            owner,
+        ))
+    }
+
+    pub async fn get_account(&mut self, address: &Pubkey) -> Result<Account> {
+        self.banks_client
+            .get_account(*address)
+            .await?
+            .ok_or_else(|| format_err!("account {} does not exist", address))
+    }
+
+    /// Creates a mint with the payer as its authority.
+    pub async fn create_mint(&mut self, decimals: u8) -> Result<Pubkey> {
+        let mint = Keypair::generate(&mut OsRng);
+        let instructions = vec![
+            self.create_account_instruction(
+                &mint.pubkey(),
+                spl_token::state::Mint::LEN,
+                &spl_token::ID,
+            )
+            .await?,
+            token_instruction::initialize_mint(
+                &spl_token::ID,
+                &mint.pubkey(),
+                &self.payer.pubkey(),
+                None,
+                decimals,
+            )?,
+        ];
+        self.process(&instructions, &[&mint]).await?;
+        Ok(mint.pubkey())
+    }
+
+    pub async fn mint_decimals(&mut self, mint: &Pubkey) -> Result<u8> {
+        let account = self.get_account(mint).await?;
+        Ok(spl_token::state::Mint::unpack(&account.data)?.decimals)
+    }
+
+    pub async fn create_token_account(&mut self, mint: &Pubkey, owner: &Pubkey) -> Result<Pubkey> {
+        let account = Keypair::generate(&mut OsRng);
+        let instructions = vec![
+            self.create_account_instruction(
+                &account.pubkey(),
+                spl_token::state::Account::LEN,
+                &spl_token::ID,
+            )
+            .await?,
+            token_instruction::initialize_account(&spl_token::ID, &account.pubkey(), mint, owner)?,
+        ];
+        self.process(&instructions, &[&account]).await?;
+        Ok(account.pubkey())
+    }
+
+    /// Mints `amount` of a mint made with `create_mint` to `account`.
+    pub async fn mint_to(&mut self, mint: &Pubkey, account: &Pubkey, amount: u64) -> Result<()> {
+        let instruction = token_instruction::mint_to(
+            &spl_token::ID,
+            mint,
+            account,
+            &self.payer.pubkey(),
+            &[],
+            amount,
+        )?;
+        self.process(&[instruction], &[]).await
+    }
+
+    pub async fn token_balance(&mut self, account: &Pubkey) -> Result<u64> {
+        let account = self.get_account(account).await?;
+        Ok(spl_token::state::Account::unpack(&account.data)?.amount)
+    }
+
+    pub fn wallet(&mut self) -> WalletBuilder {
+        WalletBuilder::new(self)
+    }
+
+    pub fn market(&mut self, coin_mint: &Pubkey, pc_mint: &Pubkey) -> MarketBuilder {
+        MarketBuilder::new(self, coin_mint, pc_mint)
+    }
+
+    pub fn registrar(&mut self, mint: &Pubkey) -> RegistrarBuilder {
+        RegistrarBuilder::new(self, mint)
+    }
+
+    pub fn safe(&mut self, mint: &Pubkey) -> SafeBuilder {
+        SafeBuilder::new(self, mint)
+    }
+
+    pub fn vesting<'a>(&'a mut self, safe: &'a Safe, depositor: &'a Wallet) -> VestingBuilder<'a> {
+        VestingBuilder::new(self, safe, depositor)
+    }
+}
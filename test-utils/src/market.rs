@@ -0,0 +1,137 @@
+use std::mem::size_of;
+
+use anyhow::Result;
+use crank::ListingParams;
+use rand::rngs::OsRng;
+use serum_dex::state::{gen_vault_signer_key, MarketState};
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+
+use crate::TestEnv;
+
+/// The accounts of an initialized market.
+#[derive(Debug, Clone, Copy)]
+pub struct Market {
+    pub address: Pubkey,
+    pub req_q: Pubkey,
+    pub event_q: Pubkey,
+    pub bids: Pubkey,
+    pub asks: Pubkey,
+    pub coin_mint: Pubkey,
+    pub pc_mint: Pubkey,
+    pub coin_vault: Pubkey,
+    pub pc_vault: Pubkey,
+    pub vault_signer: Pubkey,
+    pub vault_signer_nonce: u64,
+    pub coin_lot_size: u64,
+    pub pc_lot_size: u64,
+}
+
+pub struct MarketBuilder<'a> {
+    env: &'a mut TestEnv,
+    coin_mint: Pubkey,
+    pc_mint: Pubkey,
+    params: ListingParams,
+}
+
+impl<'a> MarketBuilder<'a> {
+    pub(crate) fn new(env: &'a mut TestEnv, coin_mint: &Pubkey, pc_mint: &Pubkey) -> Self {
+        MarketBuilder {
+            env,
+            coin_mint: *coin_mint,
+            pc_mint: *pc_mint,
+            params: ListingParams::default(),
+        }
+    }
+
+    /// Replaces the lot sizes, queue capacities and dust threshold, which
+    /// default to the crank's `list-market` defaults.
+    pub fn params(mut self, params: ListingParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn lot_sizes(mut self, coin_lot_size: u64, pc_lot_size: u64) -> Self {
+        self.params.coin_lot_size = coin_lot_size;
+        self.params.pc_lot_size = pc_lot_size;
+        self
+    }
+
+    pub async fn build(self) -> Result<Market> {
+        let MarketBuilder {
+            env,
+            coin_mint,
+            pc_mint,
+            params,
+        } = self;
+        params.check()?;
+        let dex_program_id = env.programs.dex;
+
+        let market = Keypair::generate(&mut OsRng);
+        let req_q = Keypair::generate(&mut OsRng);
+        let event_q = Keypair::generate(&mut OsRng);
+        let bids = Keypair::generate(&mut OsRng);
+        let asks = Keypair::generate(&mut OsRng);
+        let (vault_signer_nonce, vault_signer) = {
+            let mut i = 0;
+            loop {
+                assert!(i < 100);
+                if let Ok(pk) = gen_vault_signer_key(i, &market.pubkey(), &dex_program_id) {
+                    break (i, pk);
+                }
+                i += 1;
+            }
+        };
+        let coin_vault = env.create_token_account(&coin_mint, &vault_signer).await?;
+        let pc_vault = env.create_token_account(&pc_mint, &vault_signer).await?;
+
+        let mut instructions = vec![];
+        for (key, len) in &[
+            (&market, size_of::<MarketState>()),
+            (&req_q, params.request_queue_len()),
+            (&event_q, params.event_queue_len()),
+            (&bids, params.slab_len()),
+            (&asks, params.slab_len()),
+        ] {
+            // Dex accounts are padded with 5 bytes in front and 7 behind.
+            let instruction = env
+                .create_account_instruction(&key.pubkey(), len + 12, &dex_program_id)
+                .await?;
+            instructions.push(instruction);
+        }
+        instructions.push(serum_dex::instruction::initialize_market(
+            &market.pubkey(),
+            &dex_program_id,
+            &coin_mint,
+            &pc_mint,
+            &coin_vault,
+            &pc_vault,
+            &bids.pubkey(),
+            &asks.pubkey(),
+            &req_q.pubkey(),
+            &event_q.pubkey(),
+            params.coin_lot_size,
+            params.pc_lot_size,
+            vault_signer_nonce,
+            params.pc_dust_threshold,
+        )?);
+        env.process(&instructions, &[&market, &req_q, &event_q, &bids, &asks])
+            .await?;
+
+        Ok(Market {
+            address: market.pubkey(),
+            req_q: req_q.pubkey(),
+            event_q: event_q.pubkey(),
+            bids: bids.pubkey(),
+            asks: asks.pubkey(),
+            coin_mint,
+            pc_mint,
+            coin_vault,
+            pc_vault,
+            vault_signer,
+            vault_signer_nonce,
+            coin_lot_size: params.coin_lot_size,
+            pc_lot_size: params.pc_lot_size,
+        })
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::Result;
+use rand::rngs::OsRng;
+use serum_common::pack::PackedSize;
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::sysvar;
+
+use crate::TestEnv;
+
+/// An initialized registrar and the authority able to administer it.
+pub struct Registrar {
+    pub address: Pubkey,
+    pub authority: Keypair,
+    pub withdrawal_timelock: u64,
+    pub reward_period: u64,
+    /// Vault receiving SRM stake and the rewards dropped on the registrar's
+    /// entities.
+    pub vault: Pubkey,
+    /// Vault receiving MSRM stake.
+    pub mega_vault: Pubkey,
+    pub vault_authority: Pubkey,
+    pub nonce: u8,
+}
+
+pub struct RegistrarBuilder<'a> {
+    env: &'a mut TestEnv,
+    mint: Pubkey,
+    mega_mint: Option<Pubkey>,
+    withdrawal_timelock: u64,
+    reward_period: u64,
+    capabilities: Vec<(u8, u32)>,
+}
+
+impl<'a> RegistrarBuilder<'a> {
+    pub(crate) fn new(env: &'a mut TestEnv, mint: &Pubkey) -> Self {
+        RegistrarBuilder {
+            env,
+            mint: *mint,
+            mega_mint: None,
+            withdrawal_timelock: 604_800,
+            reward_period: 0,
+            capabilities: vec![],
+        }
+    }
+
+    /// Seconds a stake withdrawal must wait. Defaults to the registry CLI's
+    /// one week.
+    pub fn withdrawal_timelock(mut self, withdrawal_timelock: u64) -> Self {
+        self.withdrawal_timelock = withdrawal_timelock;
+        self
+    }
+
+    /// Seconds each reward drop is streamed over. Defaults to 0, paying
+    /// drops at once.
+    pub fn reward_period(mut self, reward_period: u64) -> Self {
+        self.reward_period = reward_period;
+        self
+    }
+
+    /// Mint of the MSRM stake vault. Defaults to a new mint with no
+    /// decimals.
+    pub fn mega_mint(mut self, mega_mint: &Pubkey) -> Self {
+        self.mega_mint = Some(*mega_mint);
+        self
+    }
+
+    /// Registers a capability once the registrar is initialized.
+    pub fn capability(mut self, capability_id: u8, capability_fee_bps: u32) -> Self {
+        self.capabilities.push((capability_id, capability_fee_bps));
+        self
+    }
+
+    pub async fn build(self) -> Result<Registrar> {
+        let RegistrarBuilder {
+            env,
+            mint,
+            mega_mint,
+            withdrawal_timelock,
+            reward_period,
+            capabilities,
+        } = self;
+        let program_id = env.programs.registry;
+        let registrar = Keypair::generate(&mut OsRng);
+        let authority = Keypair::generate(&mut OsRng);
+        let (vault_authority, nonce) =
+            Pubkey::find_program_address(&[registrar.pubkey().as_ref()], &program_id);
+        let vault = env.create_token_account(&mint, &vault_authority).await?;
+        let mega_mint = match mega_mint {
+            Some(mega_mint) => mega_mint,
+            None => env.create_mint(0).await?,
+        };
+        let mega_vault = env
+            .create_token_account(&mega_mint, &vault_authority)
+            .await?;
+
+        let create_registrar = env
+            .create_account_instruction(
+                &registrar.pubkey(),
+                serum_registry::accounts::Registrar::SIZE as usize,
+                &program_id,
+            )
+            .await?;
+        let initialize = serum_registry::instruction::initialize(
+            program_id,
+            &[
+                AccountMeta::new(registrar.pubkey(), false),
+                AccountMeta::new_readonly(vault, false),
+                AccountMeta::new_readonly(mega_vault, false),
+                AccountMeta::new_readonly(sysvar::rent::ID, false),
+            ],
+            authority.pubkey(),
+            withdrawal_timelock,
+            nonce,
+            reward_period,
+        );
+        env.process(&[create_registrar, initialize], &[&registrar])
+            .await?;
+
+        if !capabilities.is_empty() {
+            let accounts = [
+                AccountMeta::new_readonly(authority.pubkey(), true),
+                AccountMeta::new(registrar.pubkey(), false),
+            ];
+            let instructions: Vec<_> = capabilities
+                .into_iter()
+                .map(|(capability_id, capability_fee_bps)| {
+                    serum_registry::instruction::register_capability(
+                        program_id,
+                        &accounts,
+                        capability_id,
+                        capability_fee_bps,
+                    )
+                })
+                .collect();
+            env.process(&instructions, &[&authority]).await?;
+        }
+
+        Ok(Registrar {
+            address: registrar.pubkey(),
+            authority,
+            withdrawal_timelock,
+            reward_period,
+            vault,
+            mega_vault,
+            vault_authority,
+            nonce,
+        })
+    }
+}
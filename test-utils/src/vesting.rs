@@ -0,0 +1,196 @@
+use anyhow::Result;
+use rand::rngs::OsRng;
+use serum_common::pack::{Pack, PackedSize};
+use serum_lockup::accounts::{Safe as SafeAccount, Vesting as VestingAccount, Whitelist};
+use solana_sdk::instruction::AccountMeta;
+use solana_sdk::program_pack::Pack as TokenPack;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::sysvar;
+
+use crate::{TestEnv, Wallet};
+
+/// An initialized lockup safe for one mint.
+pub struct Safe {
+    pub address: Pubkey,
+    pub authority: Keypair,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    pub vault_authority: Pubkey,
+    pub nonce: u8,
+    pub whitelist: Pubkey,
+}
+
+pub struct SafeBuilder<'a> {
+    env: &'a mut TestEnv,
+    mint: Pubkey,
+}
+
+impl<'a> SafeBuilder<'a> {
+    pub(crate) fn new(env: &'a mut TestEnv, mint: &Pubkey) -> Self {
+        SafeBuilder { env, mint: *mint }
+    }
+
+    pub async fn build(self) -> Result<Safe> {
+        let SafeBuilder { env, mint } = self;
+        let program_id = env.programs.lockup;
+        let safe = Keypair::generate(&mut OsRng);
+        let whitelist = Keypair::generate(&mut OsRng);
+        let authority = Keypair::generate(&mut OsRng);
+        let (vault_authority, nonce) =
+            Pubkey::find_program_address(&[safe.pubkey().as_ref()], &program_id);
+        let vault = env.create_token_account(&mint, &vault_authority).await?;
+
+        let instructions = vec![
+            env.create_account_instruction(
+                &safe.pubkey(),
+                SafeAccount::default().size()? as usize,
+                &program_id,
+            )
+            .await?,
+            env.create_account_instruction(&whitelist.pubkey(), Whitelist::SIZE, &program_id)
+                .await?,
+            serum_lockup::instruction::initialize(
+                program_id,
+                &[
+                    AccountMeta::new(safe.pubkey(), false),
+                    AccountMeta::new(whitelist.pubkey(), false),
+                    AccountMeta::new_readonly(vault, false),
+                    AccountMeta::new_readonly(mint, false),
+                    AccountMeta::new_readonly(sysvar::rent::ID, false),
+                ],
+                authority.pubkey(),
+                nonce,
+            ),
+        ];
+        env.process(&instructions, &[&safe, &whitelist]).await?;
+
+        Ok(Safe {
+            address: safe.pubkey(),
+            authority,
+            mint,
+            vault,
+            vault_authority,
+            nonce,
+            whitelist: whitelist.pubkey(),
+        })
+    }
+}
+
+/// A linearly vesting deposit in a safe.
+pub struct Vesting {
+    pub address: Pubkey,
+    pub beneficiary: Keypair,
+    /// The mint created alongside the vesting, with the safe's vault
+    /// authority as its mint authority.
+    pub locked_mint: Pubkey,
+    pub deposit_amount: u64,
+}
+
+pub struct VestingBuilder<'a> {
+    env: &'a mut TestEnv,
+    safe: &'a Safe,
+    depositor: &'a Wallet,
+    deposit_amount: u64,
+    end_slot: u64,
+    period_count: u64,
+}
+
+impl<'a> VestingBuilder<'a> {
+    pub(crate) fn new(env: &'a mut TestEnv, safe: &'a Safe, depositor: &'a Wallet) -> Self {
+        VestingBuilder {
+            env,
+            safe,
+            depositor,
+            deposit_amount: 100,
+            end_slot: 100_000,
+            period_count: 10,
+        }
+    }
+
+    /// Amount deposited from the depositor's account for the safe's mint.
+    pub fn deposit_amount(mut self, deposit_amount: u64) -> Self {
+        self.deposit_amount = deposit_amount;
+        self
+    }
+
+    /// Vests the deposit in `period_count` equal parts until `end_slot`.
+    pub fn schedule(mut self, end_slot: u64, period_count: u64) -> Self {
+        self.end_slot = end_slot;
+        self.period_count = period_count;
+        self
+    }
+
+    pub async fn build(self) -> Result<Vesting> {
+        let VestingBuilder {
+            env,
+            safe,
+            depositor,
+            deposit_amount,
+            end_slot,
+            period_count,
+        } = self;
+        let program_id = env.programs.lockup;
+        let depositor_account = depositor.token_account(&safe.mint)?;
+        let beneficiary = Keypair::generate(&mut OsRng);
+        let vesting = Keypair::generate(&mut OsRng);
+
+        // Every vesting account gets a mint of its own, controlled by the
+        // safe.
+        let locked_mint = Keypair::generate(&mut OsRng);
+        let decimals = env.mint_decimals(&safe.mint).await?;
+        let instructions = vec![
+            env.create_account_instruction(
+                &locked_mint.pubkey(),
+                spl_token::state::Mint::LEN,
+                &spl_token::ID,
+            )
+            .await?,
+            spl_token::instruction::initialize_mint(
+                &spl_token::ID,
+                &locked_mint.pubkey(),
+                &safe.vault_authority,
+                Some(&safe.vault_authority),
+                decimals,
+            )?,
+        ];
+        env.process(&instructions, &[&locked_mint]).await?;
+
+        let instructions = vec![
+            env.create_account_instruction(
+                &vesting.pubkey(),
+                VestingAccount::SIZE as usize,
+                &program_id,
+            )
+            .await?,
+            serum_lockup::instruction::create_vesting(
+                program_id,
+                &[
+                    AccountMeta::new(vesting.pubkey(), true),
+                    AccountMeta::new(depositor_account, false),
+                    AccountMeta::new(depositor.pubkey(), true),
+                    AccountMeta::new(safe.vault, false),
+                    AccountMeta::new(safe.address, false),
+                    AccountMeta::new(locked_mint.pubkey(), false),
+                    AccountMeta::new_readonly(safe.vault_authority, false),
+                    AccountMeta::new_readonly(spl_token::ID, false),
+                    AccountMeta::new_readonly(sysvar::rent::ID, false),
+                    AccountMeta::new_readonly(sysvar::clock::ID, false),
+                ],
+                beneficiary.pubkey(),
+                end_slot,
+                period_count,
+                deposit_amount,
+            ),
+        ];
+        env.process(&instructions, &[&vesting, &depositor.owner])
+            .await?;
+
+        Ok(Vesting {
+            address: vesting.pubkey(),
+            beneficiary,
+            locked_mint: locked_mint.pubkey(),
+            deposit_amount,
+        })
+    }
+}
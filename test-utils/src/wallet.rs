@@ -0,0 +1,85 @@
+use std::collections::BTreeMap;
+
+use anyhow::{format_err, Result};
+use rand::rngs::OsRng;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::{Keypair, Signer};
+use solana_sdk::system_instruction;
+
+use crate::TestEnv;
+
+/// A keypair with SOL, and a token account for every mint it was funded
+/// with.
+pub struct Wallet {
+    pub owner: Keypair,
+    token_accounts: BTreeMap<Pubkey, Pubkey>,
+}
+
+impl Wallet {
+    pub fn pubkey(&self) -> Pubkey {
+        self.owner.pubkey()
+    }
+
+    /// The wallet's token account for `mint`.
+    pub fn token_account(&self, mint: &Pubkey) -> Result<Pubkey> {
+        self.token_accounts
+            .get(mint)
+            .copied()
+            .ok_or_else(|| format_err!("the wallet has no account for mint {}", mint))
+    }
+}
+
+pub struct WalletBuilder<'a> {
+    env: &'a mut TestEnv,
+    lamports: u64,
+    tokens: Vec<(Pubkey, u64)>,
+}
+
+impl<'a> WalletBuilder<'a> {
+    pub(crate) fn new(env: &'a mut TestEnv) -> Self {
+        WalletBuilder {
+            env,
+            lamports: 1_000_000_000,
+            tokens: vec![],
+        }
+    }
+
+    /// SOL to fund the owner with, in lamports. Defaults to 1 SOL.
+    pub fn lamports(mut self, lamports: u64) -> Self {
+        self.lamports = lamports;
+        self
+    }
+
+    /// Adds a token account for `mint`, holding `amount`. The mint must have
+    /// been made with `TestEnv::create_mint`.
+    pub fn tokens(mut self, mint: &Pubkey, amount: u64) -> Self {
+        self.tokens.push((*mint, amount));
+        self
+    }
+
+    pub async fn build(self) -> Result<Wallet> {
+        let WalletBuilder {
+            env,
+            lamports,
+            tokens,
+        } = self;
+        let owner = Keypair::generate(&mut OsRng);
+        if lamports > 0 {
+            let transfer =
+                system_instruction::transfer(&env.payer.pubkey(), &owner.pubkey(), lamports);
+            env.process(&[transfer], &[]).await?;
+        }
+        let mut token_accounts = BTreeMap::new();
+        for (mint, amount) in tokens {
+            let account = env.create_token_account(&mint, &owner.pubkey()).await?;
+            if amount > 0 {
+                env.mint_to(&mint, &account, amount).await?;
+            }
+            token_accounts.insert(mint, account);
+        }
+        Ok(Wallet {
+            owner,
+            token_accounts,
+        })
+    }
+}
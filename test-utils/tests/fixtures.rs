@@ -0,0 +1,47 @@
+// Requires the BPF builds of the programs; see the crate docs.
+#![cfg(feature = "test-bpf")]
+
+use serum_test_utils::TestEnv;
+
+#[tokio::test]
+async fn fixtures() {
+    let mut env = TestEnv::start().await;
+    let coin_mint = env.create_mint(6).await.unwrap();
+    let pc_mint = env.create_mint(6).await.unwrap();
+
+    let trader = env
+        .wallet()
+        .tokens(&coin_mint, 1_000_000)
+        .tokens(&pc_mint, 2_000_000)
+        .build()
+        .await
+        .unwrap();
+    let pc_account = trader.token_account(&pc_mint).unwrap();
+    assert_eq!(env.token_balance(&pc_account).await.unwrap(), 2_000_000);
+
+    let market = env.market(&coin_mint, &pc_mint).build().await.unwrap();
+    assert_eq!(env.token_balance(&market.coin_vault).await.unwrap(), 0);
+
+    let registrar = env
+        .registrar(&coin_mint)
+        .withdrawal_timelock(10)
+        .capability(0, 1_000)
+        .build()
+        .await
+        .unwrap();
+    let account = env.get_account(&registrar.address).await.unwrap();
+    assert_eq!(account.owner, env.programs.registry);
+    assert_eq!(env.token_balance(&registrar.vault).await.unwrap(), 0);
+
+    let safe = env.safe(&coin_mint).build().await.unwrap();
+    let vesting = env
+        .vesting(&safe, &trader)
+        .deposit_amount(1_000)
+        .build()
+        .await
+        .unwrap();
+    assert_eq!(vesting.deposit_amount, 1_000);
+    assert_eq!(env.token_balance(&safe.vault).await.unwrap(), 1_000);
+    let coin_account = trader.token_account(&coin_mint).unwrap();
+    assert_eq!(env.token_balance(&coin_account).await.unwrap(), 999_000);
+}